@@ -62,6 +62,7 @@ impl Conditioner {
             incoming_latency: bevy::utils::Duration::from_millis(self.latency_ms as u64),
             incoming_jitter: bevy::utils::Duration::from_millis(self.jitter_ms as u64),
             incoming_loss: self.packet_loss,
+            ..Default::default()
         }
     }
 }
@@ -150,6 +151,7 @@ pub fn build_server_netcode_config(
             incoming_latency: Duration::from_millis(c.latency_ms as u64),
             incoming_jitter: Duration::from_millis(c.jitter_ms as u64),
             incoming_loss: c.packet_loss,
+            ..Default::default()
         })
     });
     let netcode_config = server::NetcodeConfig::default()
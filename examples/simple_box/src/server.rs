@@ -123,10 +123,10 @@ pub(crate) fn send_message(
     if input.is_some_and(|input| input.pressed(KeyCode::KeyM)) {
         let message = Message1(5);
         info!("Send message: {:?}", message);
-        server
-            .send_message_to_target::<Channel1, Message1>(Message1(5), NetworkTarget::All)
-            .unwrap_or_else(|e| {
-                error!("Failed to send message: {:?}", e);
-            });
+        if let Err(e) =
+            server.send_message_to_target::<Channel1, Message1>(Message1(5), NetworkTarget::All)
+        {
+            error!("Failed to send message: {:?}", e);
+        }
     }
 }
@@ -0,0 +1,46 @@
+//! Standalone relay server, for hosts and clients that cannot reach each other directly.
+//!
+//! Usage: `relay_server [ADDRESS] [KEY]` (default: `0.0.0.0:5100`).
+//! `KEY` is the base64-encoded 32-byte key used to check the secrets of the sessions
+//! (see `lightyear::transport::relay::session_secret`). If it is not provided, a random key is generated and logged.
+//! The log level can be set with the `RUST_LOG` environment variable.
+#[cfg(not(target_family = "wasm"))]
+fn main() -> std::io::Result<()> {
+    use base64::prelude::{Engine as _, BASE64_STANDARD};
+    use lightyear::transport::relay::server::{RelayServer, RelayServerConfig};
+    use tracing::info;
+    use tracing_subscriber::EnvFilter;
+
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+    let invalid_input = |e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e);
+    let mut args = std::env::args().skip(1);
+    let addr = args
+        .next()
+        .unwrap_or_else(|| "0.0.0.0:5100".to_string())
+        .parse()
+        .map_err(|e| invalid_input(format!("invalid address: {e}")))?;
+    let mut config = RelayServerConfig::default();
+    match args.next() {
+        Some(key) => {
+            config.key = BASE64_STANDARD
+                .decode(key)
+                .ok()
+                .and_then(|key| key.try_into().ok())
+                .ok_or_else(|| {
+                    invalid_input("the key must be 32 bytes encoded in base64".into())
+                })?;
+        }
+        None => info!(
+            key = BASE64_STANDARD.encode(config.key),
+            "generated a relay key"
+        ),
+    }
+    RelayServer::bind(addr, config)?.run()
+}
+
+#[cfg(target_family = "wasm")]
+fn main() {}
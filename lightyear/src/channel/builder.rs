@@ -5,19 +5,21 @@ use bevy::utils::Duration;
 
 use lightyear_macros::ChannelInternal;
 
+use crate::channel::receivers::bulk::BulkReceiver;
 use crate::channel::receivers::ordered_reliable::OrderedReliableReceiver;
 use crate::channel::receivers::sequenced_reliable::SequencedReliableReceiver;
 use crate::channel::receivers::sequenced_unreliable::SequencedUnreliableReceiver;
 use crate::channel::receivers::tick_unreliable::TickUnreliableReceiver;
 use crate::channel::receivers::unordered_reliable::UnorderedReliableReceiver;
 use crate::channel::receivers::unordered_unreliable::UnorderedUnreliableReceiver;
-use crate::channel::receivers::ChannelReceiver;
+use crate::channel::receivers::{ChannelReceive, ChannelReceiver};
+use crate::channel::senders::bulk::BulkSender;
 use crate::channel::senders::reliable::ReliableSender;
 use crate::channel::senders::sequenced_unreliable::SequencedUnreliableSender;
 use crate::channel::senders::tick_unreliable::TickUnreliableSender;
 use crate::channel::senders::unordered_unreliable::UnorderedUnreliableSender;
 use crate::channel::senders::unordered_unreliable_with_acks::UnorderedUnreliableWithAcksSender;
-use crate::channel::senders::ChannelSender;
+use crate::channel::senders::{ChannelSend, ChannelSender};
 use crate::prelude::ChannelKind;
 
 /// A ChannelContainer is a struct that implements the [`Channel`] trait
@@ -42,6 +44,7 @@ pub struct ChannelContainer {
 ///     mode: ChannelMode::UnorderedUnreliable,
 ///     direction: ChannelDirection::Bidirectional,
 ///     priority: 1.0,
+///     ..default()
 /// });
 /// ```
 pub trait Channel: 'static {
@@ -73,8 +76,8 @@ impl ChannelBuilder {
 
 impl ChannelContainer {
     pub fn new(settings: ChannelSettings) -> Self {
-        let receiver: ChannelReceiver;
-        let sender: ChannelSender;
+        let mut receiver: ChannelReceiver;
+        let mut sender: ChannelSender;
         let settings_clone = settings.clone();
         match settings.mode {
             ChannelMode::UnorderedUnreliableWithAcks => {
@@ -101,11 +104,33 @@ impl ChannelContainer {
                 receiver = OrderedReliableReceiver::new().into();
                 sender = ReliableSender::new(reliable_settings).into();
             }
+            ChannelMode::UnorderedReliableWithDeadline(deadline_settings) => {
+                receiver = UnorderedReliableReceiver::new()
+                    .with_time_to_live(deadline_settings.time_to_live)
+                    .into();
+                sender = ReliableSender::new(deadline_settings.reliable)
+                    .with_time_to_live(deadline_settings.time_to_live)
+                    .into();
+            }
+            ChannelMode::OrderedReliableWithDeadline(deadline_settings) => {
+                receiver = OrderedReliableReceiver::new()
+                    .with_time_to_live(deadline_settings.time_to_live)
+                    .into();
+                sender = ReliableSender::new(deadline_settings.reliable)
+                    .with_time_to_live(deadline_settings.time_to_live)
+                    .into();
+            }
+            ChannelMode::Bulk(bulk_settings) => {
+                receiver = BulkReceiver::new(bulk_settings.clone()).into();
+                sender = BulkSender::new(bulk_settings).into();
+            }
             ChannelMode::TickBuffered => {
                 receiver = TickUnreliableReceiver::new().into();
                 sender = TickUnreliableSender::new().into();
             }
         }
+        receiver.set_fragment_limits(settings.max_message_size, settings.max_reassemblies);
+        sender.set_max_reassemblies(settings.max_reassemblies);
         Self {
             setting: settings_clone,
             receiver,
@@ -114,6 +139,12 @@ impl ChannelContainer {
     }
 }
 
+/// Default maximum size (in bytes) of the messages of a channel
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// Default maximum number of fragmented messages that a channel reassembles at the same time
+pub const DEFAULT_MAX_REASSEMBLIES: usize = 32;
+
 /// [`ChannelSettings`] are used to specify how the [`Channel`] behaves (reliability, ordering, direction)
 #[derive(Clone, Debug, PartialEq)]
 pub struct ChannelSettings {
@@ -121,6 +152,28 @@ pub struct ChannelSettings {
     pub direction: ChannelDirection,
     /// Sets the priority of the channel. The final priority of a message will be `MessagePriority * ChannelPriority`
     pub priority: f32,
+    /// How long a message that could not be sent because of the bandwidth cap is kept to be sent later.
+    /// While it waits, the priority of the message keeps accumulating, so that messages of low priority channels
+    /// are eventually sent. If `None` (the default), the message is discarded right away.
+    ///
+    /// Only applies to [`UnorderedUnreliable`](ChannelMode::UnorderedUnreliable) and
+    /// [`SequencedUnreliable`](ChannelMode::SequencedUnreliable) channels; reliable channels already resend their messages.
+    pub max_buffered_age: Option<Duration>,
+    /// Maximum size (in bytes) of a message sent on this channel. Sending a bigger message returns a
+    /// [`ChannelError::MessageTooLarge`](crate::channel::error::ChannelError::MessageTooLarge) error,
+    /// and the fragmented messages from the remote that are bigger are dropped.
+    /// [`Bulk`](ChannelMode::Bulk) channels use [`BulkSettings::max_transfer_size`] instead.
+    ///
+    /// Defaults to [`DEFAULT_MAX_MESSAGE_SIZE`]. If `None`, there is no limit.
+    pub max_message_size: Option<usize>,
+    /// Maximum number of fragmented messages that can be reassembled at the same time on the receiver side.
+    /// On unreliable channels, the fragments of other messages are dropped until some of the messages are completed.
+    /// On reliable channels, the sender waits for some of the messages to be completed before sending new
+    /// fragmented messages, so that no fragment is dropped.
+    /// [`Bulk`](ChannelMode::Bulk) channels use [`BulkSettings::max_concurrent_transfers`] instead.
+    ///
+    /// Defaults to [`DEFAULT_MAX_REASSEMBLIES`]. If `None`, there is no limit.
+    pub max_reassemblies: Option<usize>,
 }
 
 impl Default for ChannelSettings {
@@ -129,6 +182,9 @@ impl Default for ChannelSettings {
             mode: ChannelMode::UnorderedUnreliable,
             direction: ChannelDirection::Bidirectional,
             priority: 1.0,
+            max_buffered_age: None,
+            max_message_size: Some(DEFAULT_MAX_MESSAGE_SIZE),
+            max_reassemblies: Some(DEFAULT_MAX_REASSEMBLIES),
         }
     }
 }
@@ -152,6 +208,18 @@ pub enum ChannelMode {
     SequencedReliable(ReliableSettings),
     /// Messages will arrive in the correct order at the destination
     OrderedReliable(ReliableSettings),
+    /// Same as unordered reliable, but messages that were not acked before their time-to-live expires
+    /// are dropped instead of being resent
+    UnorderedReliableWithDeadline(DeadlineSettings),
+    /// Same as ordered reliable, but messages that were not acked before their time-to-live expires
+    /// are dropped instead of being resent.
+    /// The receiver skips the dropped messages instead of waiting for them forever.
+    OrderedReliableWithDeadline(DeadlineSettings),
+    /// Used to stream large payloads (levels, save files, etc.) without starving the other channels.
+    ///
+    /// Each message is a transfer that is split into chunks. Only a window of chunks is in flight at a time,
+    /// and the receiver controls how far ahead the sender can go. Transfers are received in order.
+    Bulk(BulkSettings),
     /// Inputs from the client are associated with the current tick on the client.
     /// The server will buffer them and only receive them on the same tick.
     TickBuffered,
@@ -166,6 +234,9 @@ impl ChannelMode {
             ChannelMode::UnorderedReliable(_) => true,
             ChannelMode::SequencedReliable(_) => true,
             ChannelMode::OrderedReliable(_) => true,
+            ChannelMode::UnorderedReliableWithDeadline(_) => true,
+            ChannelMode::OrderedReliableWithDeadline(_) => true,
+            ChannelMode::Bulk(_) => true,
             ChannelMode::TickBuffered => false,
         }
     }
@@ -179,9 +250,21 @@ impl ChannelMode {
             ChannelMode::UnorderedReliable(_) => true,
             ChannelMode::SequencedReliable(_) => true,
             ChannelMode::OrderedReliable(_) => true,
+            ChannelMode::UnorderedReliableWithDeadline(_) => true,
+            ChannelMode::OrderedReliableWithDeadline(_) => true,
+            ChannelMode::Bulk(_) => true,
             ChannelMode::TickBuffered => false,
         }
     }
+
+    /// Returns the duration after which unacked messages are dropped, if the channel has one
+    pub(crate) fn time_to_live(&self) -> Option<Duration> {
+        match self {
+            ChannelMode::UnorderedReliableWithDeadline(settings)
+            | ChannelMode::OrderedReliableWithDeadline(settings) => Some(settings.time_to_live),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -216,6 +299,95 @@ impl ReliableSettings {
     }
 }
 
+/// Settings of the channels that resend messages until they are acked, or until their time-to-live expires
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeadlineSettings {
+    pub reliable: ReliableSettings,
+    /// Messages that were not acked after this duration (since they were buffered) are dropped
+    pub time_to_live: Duration,
+}
+
+impl DeadlineSettings {
+    pub fn new(time_to_live: Duration) -> Self {
+        Self {
+            reliable: ReliableSettings::default(),
+            time_to_live,
+        }
+    }
+}
+
+/// Settings of the [`ChannelMode::Bulk`] channels
+#[derive(Clone, Debug, PartialEq)]
+pub struct BulkSettings {
+    pub reliable: ReliableSettings,
+    /// Maximum number of chunks that were sent but not acked yet
+    pub send_window: u32,
+    /// Number of chunks that the receiver accepts after the last chunk it received in order.
+    ///
+    /// The receiver grants more chunks to the sender as the transfer progresses.
+    pub receive_window: u32,
+    /// Maximum fraction of the bandwidth budget of the connection that the channel can use
+    /// (only applies if the bandwidth cap or the congestion control is enabled)
+    pub bandwidth_share: f32,
+    /// Maximum size (in bytes) of a transfer. Sending a bigger transfer returns a
+    /// [`ChannelError::MessageTooLarge`](crate::channel::error::ChannelError::MessageTooLarge) error,
+    /// and the bigger transfers from the remote are cancelled by the receiver.
+    pub max_transfer_size: usize,
+    /// Maximum number of transfers that the receiver reassembles at the same time.
+    /// When a new transfer starts, the oldest transfers are dropped to stay under the limit.
+    pub max_concurrent_transfers: usize,
+}
+
+impl Default for BulkSettings {
+    fn default() -> Self {
+        Self {
+            reliable: ReliableSettings::default(),
+            send_window: 32,
+            receive_window: 128,
+            bandwidth_share: 0.5,
+            max_transfer_size: 32 * 1024 * 1024,
+            max_concurrent_transfers: 1,
+        }
+    }
+}
+
+impl BulkSettings {
+    pub fn with_send_window(mut self, send_window: u32) -> Self {
+        self.send_window = send_window;
+        self
+    }
+
+    pub fn with_receive_window(mut self, receive_window: u32) -> Self {
+        self.receive_window = receive_window;
+        self
+    }
+
+    pub fn with_bandwidth_share(mut self, bandwidth_share: f32) -> Self {
+        self.bandwidth_share = bandwidth_share;
+        self
+    }
+
+    pub fn with_max_transfer_size(mut self, max_transfer_size: usize) -> Self {
+        self.max_transfer_size = max_transfer_size;
+        self
+    }
+
+    pub fn with_max_concurrent_transfers(mut self, max_concurrent_transfers: usize) -> Self {
+        self.max_concurrent_transfers = max_concurrent_transfers;
+        self
+    }
+}
+
+/// Internal channel used to control the transfers of the [`ChannelMode::Bulk`] channels
+/// (flow control and cancellation). This is an Unordered Reliable channel.
+#[derive(ChannelInternal)]
+pub struct BulkControlChannel;
+
+/// Internal channel used to request, grant and revoke the authority over replicated entities.
+/// This is an Ordered Reliable channel.
+#[derive(ChannelInternal)]
+pub struct AuthorityChannel;
+
 /// Default channel to replicate entity actions.
 /// This is an Unordered Reliable channel.
 /// (SpawnEntity, DespawnEntity, InsertComponent, RemoveComponent)
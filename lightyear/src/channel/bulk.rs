@@ -0,0 +1,128 @@
+//! Transfers of large payloads on the [`Bulk`](crate::channel::builder::ChannelMode::Bulk) channels
+//!
+//! Each message sent on a bulk channel is a transfer, identified by a transfer id that increases with every
+//! transfer of the channel. The transfers are sent one after the other.
+//!
+//! A transfer is split into chunks that are sent as individual messages. Each chunk starts with a [`ChunkHeader`]
+//! (the transfer id, the index of the chunk and the number of chunks of the transfer), so that the receiver can
+//! reassemble the transfer.
+//!
+//! The sender only keeps [`send_window`](crate::channel::builder::BulkSettings::send_window) chunks in flight,
+//! and never sends a chunk beyond the limit granted by the receiver. The receiver grants
+//! [`receive_window`](crate::channel::builder::BulkSettings::receive_window) chunks after the last chunk it received
+//! in order, and sends a [`BulkControl::WindowUpdate`] when this limit moves forward.
+//!
+//! Both sides can cancel a transfer, in which case the other side is notified with a [`BulkControl::CancelSend`]
+//! or a [`BulkControl::CancelReceive`]. The control messages are sent on the
+//! [`BulkControlChannel`](crate::channel::builder::BulkControlChannel).
+use bitcode::{Decode, Encode};
+use bytes::Bytes;
+
+use crate::packet::message::MessageId;
+use crate::protocol::registry::NetId;
+
+/// Number of bytes of the [`ChunkHeader`] written at the start of each chunk
+pub(crate) const CHUNK_HEADER_BYTES: usize = 2 + 4 + 4;
+
+/// Header at the start of each chunk of a transfer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ChunkHeader {
+    pub(crate) transfer_id: MessageId,
+    pub(crate) chunk_index: u32,
+    pub(crate) num_chunks: u32,
+}
+
+impl ChunkHeader {
+    /// Build a chunk with this header followed by the data
+    pub(crate) fn write(&self, data: &[u8]) -> Bytes {
+        let mut chunk = Vec::with_capacity(CHUNK_HEADER_BYTES + data.len());
+        chunk.extend_from_slice(&self.transfer_id.0.to_be_bytes());
+        chunk.extend_from_slice(&self.chunk_index.to_be_bytes());
+        chunk.extend_from_slice(&self.num_chunks.to_be_bytes());
+        chunk.extend_from_slice(data);
+        chunk.into()
+    }
+
+    /// Read the header at the start of the chunk, and return it with the data of the chunk
+    pub(crate) fn read(chunk: &Bytes) -> Option<(Self, Bytes)> {
+        if chunk.len() < CHUNK_HEADER_BYTES {
+            return None;
+        }
+        let header = Self {
+            transfer_id: MessageId(u16::from_be_bytes(chunk[0..2].try_into().ok()?)),
+            chunk_index: u32::from_be_bytes(chunk[2..6].try_into().ok()?),
+            num_chunks: u32::from_be_bytes(chunk[6..10].try_into().ok()?),
+        };
+        if header.chunk_index >= header.num_chunks {
+            return None;
+        }
+        Some((header, chunk.slice(CHUNK_HEADER_BYTES..)))
+    }
+}
+
+/// Progress of a transfer sent on a bulk channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferProgress {
+    /// Number of bytes of the transfer that were acked by the receiver
+    pub acked_bytes: usize,
+    /// Total number of bytes of the transfer
+    pub total_bytes: usize,
+}
+
+impl TransferProgress {
+    /// Fraction of the transfer that was acked, between 0.0 and 1.0
+    pub fn fraction(&self) -> f32 {
+        if self.total_bytes == 0 {
+            return 1.0;
+        }
+        self.acked_bytes as f32 / self.total_bytes as f32
+    }
+}
+
+/// Message sent between the two sides of a bulk channel to control a transfer
+#[derive(Encode, Decode, Clone, Debug, PartialEq)]
+pub struct BulkControlMessage {
+    pub(crate) channel: NetId,
+    pub(crate) transfer_id: MessageId,
+    pub(crate) control: BulkControl,
+}
+
+#[derive(Encode, Decode, Clone, Copy, Debug, PartialEq)]
+pub enum BulkControl {
+    /// Sent by the receiver: the chunks of the transfer with an index lower than this limit can be sent
+    WindowUpdate { max_chunk: u32 },
+    /// Sent by the sender: the transfer was cancelled
+    CancelSend,
+    /// Sent by the receiver: the transfer was cancelled
+    CancelReceive,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_header() {
+        let header = ChunkHeader {
+            transfer_id: MessageId(3),
+            chunk_index: 70000,
+            num_chunks: 70001,
+        };
+        let chunk = header.write(b"data");
+        assert_eq!(chunk.len(), CHUNK_HEADER_BYTES + 4);
+        assert_eq!(
+            ChunkHeader::read(&chunk),
+            Some((header, Bytes::from_static(b"data")))
+        );
+
+        // truncated or invalid headers are rejected
+        assert_eq!(ChunkHeader::read(&chunk.slice(..5)), None);
+        let invalid = ChunkHeader {
+            chunk_index: 2,
+            num_chunks: 2,
+            ..header
+        }
+        .write(b"data");
+        assert_eq!(ChunkHeader::read(&invalid), None);
+    }
+}
@@ -0,0 +1,18 @@
+//! Errors returned when a message exceeds the limits of a channel
+use thiserror::Error;
+
+/// Error returned when a message does not respect the limits set in the
+/// [`ChannelSettings`](crate::channel::builder::ChannelSettings) of its channel.
+///
+/// When sending, the error is returned by `send_message` (and can be retrieved with
+/// [`anyhow::Error::downcast_ref`]). When the remote sends messages that exceed the limits, the messages are dropped
+/// and a `MessageRejectedEvent` is emitted.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ChannelError {
+    #[error("the message has {size} bytes, but the channel only accepts messages of at most {max_size} bytes")]
+    MessageTooLarge { size: usize, max_size: usize },
+    #[error("received a fragmented message of at least {size} bytes, but the channel only accepts messages of at most {max_size} bytes")]
+    FragmentedMessageTooLarge { size: usize, max_size: usize },
+    #[error("received the fragments of more than {max_reassemblies} messages at the same time")]
+    TooManyReassemblies { max_reassemblies: usize },
+}
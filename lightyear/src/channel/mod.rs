@@ -1,5 +1,7 @@
 /*! Channels are used to add reliability/ordering on top of the transport layer
 */
 pub mod builder;
+pub mod bulk;
+pub mod error;
 pub(crate) mod receivers;
 pub(crate) mod senders;
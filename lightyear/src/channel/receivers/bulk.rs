@@ -0,0 +1,400 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use tracing::{debug, trace};
+
+use crate::channel::builder::BulkSettings;
+use crate::channel::bulk::{BulkControl, ChunkHeader};
+use crate::channel::receivers::ChannelReceive;
+use crate::packet::message::{MessageContainer, MessageId, SingleData};
+use crate::shared::tick_manager::TickManager;
+use crate::shared::time_manager::TimeManager;
+
+/// A transfer that is being received
+struct IncomingTransfer {
+    transfer_id: MessageId,
+    num_chunks: u32,
+    /// Chunks received so far, by index
+    chunks: BTreeMap<u32, Bytes>,
+    /// All the chunks with an index lower than this were received
+    received_prefix: u32,
+    /// The sender can send the chunks with an index lower than this limit
+    max_chunk: u32,
+    /// Number of bytes buffered so far
+    received_bytes: usize,
+    /// The transfer was completed or cancelled, its chunks are ignored
+    finished: bool,
+}
+
+impl IncomingTransfer {
+    fn payload(&mut self) -> Bytes {
+        let mut payload = Vec::with_capacity(self.received_bytes);
+        for chunk in std::mem::take(&mut self.chunks).into_values() {
+            payload.extend_from_slice(chunk.as_ref());
+        }
+        payload.into()
+    }
+}
+
+/// Receiver of a [`Bulk`](crate::channel::builder::ChannelMode::Bulk) channel: reassembles the transfers,
+/// and grants more chunks to the sender as the transfer progresses
+pub struct BulkReceiver {
+    settings: BulkSettings,
+    /// Lowest transfer id that was not completed or cancelled yet
+    next_transfer_id: MessageId,
+    /// Transfers that are being received, sorted by id.
+    /// The transfers that are finished are kept until the older transfers are finished too.
+    transfers: VecDeque<IncomingTransfer>,
+    /// Buffer of the transfers that were fully received, but not read yet
+    recv_message_buffer: VecDeque<SingleData>,
+    /// Control messages to send to the sender
+    controls: Vec<(MessageId, BulkControl)>,
+    /// Transfers that were cancelled by the sender
+    cancelled_by_sender: Vec<MessageId>,
+}
+
+impl BulkReceiver {
+    pub fn new(settings: BulkSettings) -> Self {
+        Self {
+            settings,
+            next_transfer_id: MessageId(0),
+            transfers: VecDeque::new(),
+            recv_message_buffer: VecDeque::new(),
+            controls: Vec::new(),
+            cancelled_by_sender: Vec::new(),
+        }
+    }
+
+    /// Stop receiving the oldest transfer that is currently being received.
+    ///
+    /// Returns the id of the cancelled transfer, or None if no transfer was being received
+    pub(crate) fn cancel_current(&mut self) -> Option<MessageId> {
+        let transfer_id = self.transfers.front()?.transfer_id;
+        self.cancel(0);
+        Some(transfer_id)
+    }
+
+    /// Ask the sender to stop sending a transfer, and ignore its chunks that could still arrive
+    fn cancel(&mut self, index: usize) {
+        let transfer_id = self.transfers[index].transfer_id;
+        self.controls
+            .push((transfer_id, BulkControl::CancelReceive));
+        self.finish(index);
+    }
+
+    /// Release the chunks of a transfer and ignore the ones that could still arrive
+    fn finish(&mut self, index: usize) {
+        let transfer = &mut self.transfers[index];
+        transfer.finished = true;
+        transfer.chunks = BTreeMap::new();
+        while let Some(transfer) = self.transfers.front() {
+            if !transfer.finished {
+                break;
+            }
+            self.next_transfer_id = transfer.transfer_id + 1;
+            self.transfers.pop_front();
+        }
+    }
+
+    /// Handle a control message sent by the sender
+    pub(crate) fn receive_control(&mut self, transfer_id: MessageId, control: BulkControl) {
+        match control {
+            BulkControl::CancelSend => {
+                // skip the chunks of the transfer that could still arrive
+                let Some(index) = self.get_or_insert(transfer_id, 0) else {
+                    return;
+                };
+                if self.transfers[index].num_chunks > 0 {
+                    debug!(?transfer_id, "transfer cancelled by the sender");
+                    self.cancelled_by_sender.push(transfer_id);
+                }
+                self.finish(index);
+            }
+            BulkControl::WindowUpdate { .. } | BulkControl::CancelReceive => {
+                trace!(?transfer_id, "ignoring control message sent by a receiver");
+            }
+        }
+    }
+
+    /// Control messages to send to the sender
+    pub(crate) fn take_controls(&mut self) -> Vec<(MessageId, BulkControl)> {
+        std::mem::take(&mut self.controls)
+    }
+
+    /// Transfers that were cancelled by the sender since the last call
+    pub(crate) fn take_cancelled_transfers(&mut self) -> Vec<MessageId> {
+        std::mem::take(&mut self.cancelled_by_sender)
+    }
+
+    /// Index of the transfer with the given id, which is created if it was not being received yet.
+    ///
+    /// The sender only starts a transfer once the previous transfers are complete or cancelled, so
+    /// the oldest transfers are dropped if there are more than `max_concurrent_transfers`.
+    /// Returns None if the transfer is finished.
+    fn get_or_insert(&mut self, transfer_id: MessageId, num_chunks: u32) -> Option<usize> {
+        if transfer_id < self.next_transfer_id {
+            return None;
+        }
+        let index = match self
+            .transfers
+            .binary_search_by_key(&transfer_id, |transfer| transfer.transfer_id)
+        {
+            Ok(index) => return (!self.transfers[index].finished).then_some(index),
+            Err(index) => index,
+        };
+        self.transfers.insert(
+            index,
+            IncomingTransfer {
+                transfer_id,
+                num_chunks,
+                chunks: BTreeMap::new(),
+                received_prefix: 0,
+                max_chunk: self.settings.receive_window,
+                received_bytes: 0,
+                finished: false,
+            },
+        );
+        while self
+            .transfers
+            .iter()
+            .filter(|transfer| !transfer.finished)
+            .count()
+            > self.settings.max_concurrent_transfers.max(1)
+        {
+            debug!(transfer_id = ?self.transfers[0].transfer_id, "dropping transfer abandoned by the sender");
+            self.finish(0);
+        }
+        self.transfers
+            .binary_search_by_key(&transfer_id, |transfer| transfer.transfer_id)
+            .ok()
+            .filter(|index| !self.transfers[*index].finished)
+    }
+
+    /// Grant more chunks to the sender, once the chunks received in order moved forward by half a window
+    fn update_window(&mut self, index: usize) {
+        let transfer = &mut self.transfers[index];
+        let receive_window = self.settings.receive_window;
+        let max_chunk = transfer
+            .received_prefix
+            .saturating_add(receive_window)
+            .min(transfer.num_chunks);
+        if max_chunk
+            >= transfer
+                .max_chunk
+                .saturating_add((receive_window / 2).max(1))
+            || (max_chunk == transfer.num_chunks && max_chunk > transfer.max_chunk)
+        {
+            transfer.max_chunk = max_chunk;
+            self.controls.push((
+                transfer.transfer_id,
+                BulkControl::WindowUpdate { max_chunk },
+            ));
+        }
+    }
+}
+
+impl ChannelReceive for BulkReceiver {
+    fn update(&mut self, _: &TimeManager, _: &TickManager) {}
+
+    fn buffer_recv(&mut self, message: MessageContainer) -> anyhow::Result<()> {
+        let MessageContainer::Single(data) = message else {
+            return Err(anyhow!("bulk channels only receive chunks"));
+        };
+        let tick = data.tick;
+        let (header, chunk) =
+            ChunkHeader::read(&data.bytes).ok_or_else(|| anyhow!("invalid chunk header"))?;
+        let Some(index) = self.get_or_insert(header.transfer_id, header.num_chunks) else {
+            trace!(transfer_id = ?header.transfer_id, "ignoring chunk of a transfer that is finished");
+            return Ok(());
+        };
+        // every chunk contains at least one byte
+        if header.num_chunks as usize > self.settings.max_transfer_size {
+            debug!(transfer_id = ?header.transfer_id, "cancelling transfer bigger than the maximum transfer size");
+            self.cancel(index);
+            return Ok(());
+        }
+        let transfer = &mut self.transfers[index];
+        if header.num_chunks != transfer.num_chunks {
+            return Err(anyhow!("the number of chunks of the transfer changed"));
+        }
+        // do not buffer more chunks than what we granted to the sender
+        if header.chunk_index >= transfer.max_chunk {
+            trace!(?header, "ignoring chunk outside of the receive window");
+            return Ok(());
+        }
+        if transfer.chunks.contains_key(&header.chunk_index) {
+            return Ok(());
+        }
+        transfer.received_bytes += chunk.len();
+        if transfer.received_bytes > self.settings.max_transfer_size {
+            debug!(transfer_id = ?header.transfer_id, "cancelling transfer bigger than the maximum transfer size");
+            self.cancel(index);
+            return Ok(());
+        }
+        transfer.chunks.insert(header.chunk_index, chunk);
+        while transfer.chunks.contains_key(&transfer.received_prefix) {
+            transfer.received_prefix += 1;
+        }
+
+        if transfer.received_prefix == transfer.num_chunks {
+            let transfer_id = transfer.transfer_id;
+            trace!(?transfer_id, "received all the chunks of the transfer");
+            let payload = transfer.payload();
+            self.finish(index);
+            let mut data = SingleData::new(Some(transfer_id), payload, 1.0);
+            // the transfer is read with the tick of its last chunk
+            data.tick = tick;
+            self.recv_message_buffer.push_back(data);
+            return Ok(());
+        }
+        self.update_window(index);
+        Ok(())
+    }
+
+    fn read_message(&mut self) -> Option<SingleData> {
+        self.recv_message_buffer.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(transfer_id: u16, chunk_index: u32, num_chunks: u32) -> MessageContainer {
+        let bytes = ChunkHeader {
+            transfer_id: MessageId(transfer_id),
+            chunk_index,
+            num_chunks,
+        }
+        .write(&[chunk_index as u8; 2]);
+        SingleData::new(None, bytes, 1.0).into()
+    }
+
+    #[test]
+    fn test_bulk_receiver() -> anyhow::Result<()> {
+        let settings = BulkSettings::default().with_receive_window(4);
+        let mut receiver = BulkReceiver::new(settings);
+
+        receiver.buffer_recv(chunk(0, 1, 10))?;
+        receiver.buffer_recv(chunk(0, 0, 10))?;
+        assert!(receiver.read_message().is_none());
+        // the receiver grants more chunks once it received half a window in order
+        assert_eq!(
+            receiver.take_controls(),
+            vec![(MessageId(0), BulkControl::WindowUpdate { max_chunk: 6 })]
+        );
+        // chunks outside of the window are ignored
+        receiver.buffer_recv(chunk(0, 7, 10))?;
+        for chunk_index in 2..6 {
+            receiver.buffer_recv(chunk(0, chunk_index, 10))?;
+        }
+        assert_eq!(
+            receiver.take_controls(),
+            vec![
+                (MessageId(0), BulkControl::WindowUpdate { max_chunk: 8 }),
+                (MessageId(0), BulkControl::WindowUpdate { max_chunk: 10 })
+            ]
+        );
+        for chunk_index in 6..10 {
+            assert!(receiver.read_message().is_none());
+            receiver.buffer_recv(chunk(0, chunk_index, 10))?;
+        }
+        let message = receiver.read_message().unwrap();
+        assert_eq!(message.id, Some(MessageId(0)));
+        let expected: Vec<u8> = (0..10).flat_map(|i| [i, i]).collect();
+        assert_eq!(message.bytes.as_ref(), expected.as_slice());
+
+        // duplicate chunks of a completed transfer are ignored
+        receiver.buffer_recv(chunk(0, 3, 10))?;
+        assert!(receiver.read_message().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_receiver_cancel() -> anyhow::Result<()> {
+        let mut receiver = BulkReceiver::new(BulkSettings::default());
+        receiver.buffer_recv(chunk(0, 0, 2))?;
+        assert_eq!(receiver.cancel_current(), Some(MessageId(0)));
+        assert_eq!(
+            receiver.take_controls(),
+            vec![(MessageId(0), BulkControl::CancelReceive)]
+        );
+        // the chunks of the cancelled transfer are ignored
+        receiver.buffer_recv(chunk(0, 1, 2))?;
+        assert!(receiver.read_message().is_none());
+
+        // the sender cancels the next transfer
+        receiver.buffer_recv(chunk(1, 0, 2))?;
+        receiver.receive_control(MessageId(1), BulkControl::CancelSend);
+        assert_eq!(receiver.take_cancelled_transfers(), vec![MessageId(1)]);
+        assert_eq!(receiver.cancel_current(), None);
+
+        // a transfer that is started drops the transfer that the sender abandoned
+        receiver.buffer_recv(chunk(2, 0, 2))?;
+        receiver.buffer_recv(chunk(3, 0, 1))?;
+        let message = receiver.read_message().unwrap();
+        assert_eq!(message.id, Some(MessageId(3)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_receiver_max_transfer_size() -> anyhow::Result<()> {
+        let settings = BulkSettings::default().with_max_transfer_size(5);
+        let mut receiver = BulkReceiver::new(settings);
+
+        // the transfer announces more chunks than the maximum size
+        receiver.buffer_recv(chunk(0, 0, 6))?;
+        assert_eq!(
+            receiver.take_controls(),
+            vec![(MessageId(0), BulkControl::CancelReceive)]
+        );
+
+        // the chunks received exceed the maximum size
+        receiver.buffer_recv(chunk(1, 0, 4))?;
+        receiver.buffer_recv(chunk(1, 1, 4))?;
+        assert!(receiver.take_controls().is_empty());
+        receiver.buffer_recv(chunk(1, 2, 4))?;
+        assert_eq!(
+            receiver.take_controls(),
+            vec![(MessageId(1), BulkControl::CancelReceive)]
+        );
+        assert!(receiver.transfers.is_empty());
+        receiver.buffer_recv(chunk(1, 3, 4))?;
+        assert!(receiver.read_message().is_none());
+
+        // smaller transfers are still received
+        receiver.buffer_recv(chunk(2, 0, 2))?;
+        receiver.buffer_recv(chunk(2, 1, 2))?;
+        assert_eq!(receiver.read_message().unwrap().id, Some(MessageId(2)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_receiver_max_concurrent_transfers() -> anyhow::Result<()> {
+        let settings = BulkSettings::default().with_max_concurrent_transfers(2);
+        let mut receiver = BulkReceiver::new(settings);
+
+        receiver.buffer_recv(chunk(0, 0, 2))?;
+        receiver.buffer_recv(chunk(1, 0, 2))?;
+        // the transfer 1 completes while the transfer 0 is still being received
+        receiver.buffer_recv(chunk(1, 1, 2))?;
+        assert_eq!(receiver.read_message().unwrap().id, Some(MessageId(1)));
+        assert_eq!(receiver.next_transfer_id, MessageId(0));
+
+        // starting a third transfer drops the oldest one
+        receiver.buffer_recv(chunk(2, 0, 2))?;
+        receiver.buffer_recv(chunk(3, 0, 2))?;
+        assert_eq!(receiver.transfers.len(), 2);
+        assert_eq!(receiver.next_transfer_id, MessageId(2));
+        receiver.buffer_recv(chunk(0, 1, 2))?;
+        assert!(receiver.read_message().is_none());
+
+        receiver.buffer_recv(chunk(2, 1, 2))?;
+        receiver.buffer_recv(chunk(3, 1, 2))?;
+        assert_eq!(receiver.read_message().unwrap().id, Some(MessageId(2)));
+        assert_eq!(receiver.read_message().unwrap().id, Some(MessageId(3)));
+        assert!(receiver.transfers.is_empty());
+        Ok(())
+    }
+}
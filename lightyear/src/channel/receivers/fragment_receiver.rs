@@ -1,25 +1,36 @@
 use std::collections::HashMap;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bytes::Bytes;
 use tracing::trace;
 
+use crate::channel::error::ChannelError;
 use crate::packet::message::{FragmentData, MessageId, SingleData};
-use crate::packet::packet::FRAGMENT_SIZE;
 use crate::shared::time_manager::WrappedTime;
 
 /// `FragmentReceiver` is used to reconstruct fragmented messages
 pub struct FragmentReceiver {
     fragment_messages: HashMap<MessageId, FragmentConstructor>,
+    /// Maximum size of a reassembled message
+    max_message_size: Option<usize>,
+    /// Maximum number of messages that can be reassembled at the same time
+    max_reassemblies: Option<usize>,
 }
 
 impl FragmentReceiver {
     pub fn new() -> Self {
         Self {
             fragment_messages: HashMap::new(),
+            max_message_size: None,
+            max_reassemblies: None,
         }
     }
 
+    pub fn set_limits(&mut self, max_message_size: Option<usize>, max_reassemblies: Option<usize>) {
+        self.max_message_size = max_message_size;
+        self.max_reassemblies = max_reassemblies;
+    }
+
     /// Discard all messages for which the latest fragment was received before the cleanup time
     /// (i.e. we probably lost some fragments and we will never complete the message)
     ///
@@ -32,11 +43,42 @@ impl FragmentReceiver {
         })
     }
 
+    /// Returns a [`ChannelError`] if the fragment does not respect the limits of the channel. In that case
+    /// the fragment is dropped (as well as the other fragments of the message that were already received,
+    /// if the message is too large).
     pub fn receive_fragment(
         &mut self,
         fragment: FragmentData,
         current_time: Option<WrappedTime>,
     ) -> Result<Option<SingleData>> {
+        if let Some(max_reassemblies) = self.max_reassemblies {
+            if !self.fragment_messages.contains_key(&fragment.message_id)
+                && self.fragment_messages.len() >= max_reassemblies
+            {
+                return Err(ChannelError::TooManyReassemblies { max_reassemblies }.into());
+            }
+        }
+        if let Some(max_size) = self.max_message_size {
+            // all the fragments except the last one have the same size, so we can estimate the size of
+            // the message before receiving all the fragments
+            let is_last_fragment =
+                fragment.fragment_id as usize + 1 == fragment.num_fragments as usize;
+            let estimated_size = if is_last_fragment {
+                0
+            } else {
+                (fragment.num_fragments as usize).saturating_sub(1) * fragment.bytes.len()
+            };
+            let received_size = self
+                .fragment_messages
+                .get(&fragment.message_id)
+                .map_or(0, |c| c.num_received_bytes)
+                + fragment.bytes.len();
+            let size = estimated_size.max(received_size);
+            if size > max_size {
+                self.fragment_messages.remove(&fragment.message_id);
+                return Err(ChannelError::FragmentedMessageTooLarge { size, max_size }.into());
+            }
+        }
         let fragment_message = self
             .fragment_messages
             .entry(fragment.message_id)
@@ -45,7 +87,7 @@ impl FragmentReceiver {
         // completed the fragmented message!
         if let Some(payload) = fragment_message.receive_fragment(
             fragment.fragment_id as usize,
-            fragment.bytes.as_ref(),
+            fragment.bytes,
             current_time,
         )? {
             self.fragment_messages.remove(&fragment.message_id);
@@ -66,9 +108,11 @@ impl FragmentReceiver {
 pub struct FragmentConstructor {
     num_fragments: usize,
     num_received_fragments: usize,
-    received: Vec<bool>,
-    // bytes: Bytes,
-    bytes: Vec<u8>,
+    num_received_bytes: usize,
+    /// The bytes of each fragment that was received.
+    /// The fragment size depends on the MTU of the sender, so we only know the size of the message
+    /// once all the fragments are received
+    fragments: Vec<Option<Bytes>>,
 
     last_received: Option<WrappedTime>,
 }
@@ -78,8 +122,8 @@ impl FragmentConstructor {
         Self {
             num_fragments,
             num_received_fragments: 0,
-            received: vec![false; num_fragments],
-            bytes: vec![0; num_fragments * FRAGMENT_SIZE],
+            num_received_bytes: 0,
+            fragments: vec![None; num_fragments],
             last_received: None,
         }
     }
@@ -87,31 +131,28 @@ impl FragmentConstructor {
     pub fn receive_fragment(
         &mut self,
         fragment_index: usize,
-        bytes: &[u8],
+        bytes: Bytes,
         received_time: Option<WrappedTime>,
     ) -> Result<Option<Bytes>> {
         self.last_received = received_time;
 
-        let is_last_fragment = fragment_index == self.num_fragments - 1;
-        // TODO: check sizes?
-
-        if !self.received[fragment_index] {
-            self.received[fragment_index] = true;
+        let fragment = self
+            .fragments
+            .get_mut(fragment_index)
+            .context("fragment index is bigger than the number of fragments")?;
+        if fragment.is_none() {
+            self.num_received_bytes += bytes.len();
+            *fragment = Some(bytes);
             self.num_received_fragments += 1;
-
-            if is_last_fragment {
-                let len = (self.num_fragments - 1) * FRAGMENT_SIZE + bytes.len();
-                self.bytes.resize(len, 0);
-            }
-
-            let start = fragment_index * FRAGMENT_SIZE;
-            let end = start + bytes.len();
-            self.bytes[start..end].copy_from_slice(bytes);
         }
 
         if self.num_received_fragments == self.num_fragments {
             trace!("Received all fragments!");
-            let payload = std::mem::take(&mut self.bytes);
+            let len = self.fragments.iter().flatten().map(Bytes::len).sum();
+            let mut payload = Vec::with_capacity(len);
+            for fragment in self.fragments.drain(..).flatten() {
+                payload.extend_from_slice(fragment.as_ref());
+            }
             return Ok(Some(payload.into()));
         }
 
@@ -122,6 +163,7 @@ impl FragmentConstructor {
 #[cfg(test)]
 mod tests {
     use crate::channel::senders::fragment_sender::FragmentSender;
+    use crate::packet::packet::FRAGMENT_SIZE;
 
     use super::*;
 
@@ -145,4 +187,69 @@ mod tests {
         );
         Ok(())
     }
+
+    /// The sender can use a different fragment size than ours, if it discovered a different MTU
+    #[test]
+    fn test_receiver_different_fragment_size() -> Result<()> {
+        let mut receiver = FragmentReceiver::new();
+        let fragment_size = 3 * FRAGMENT_SIZE;
+        let message_bytes = Bytes::from(vec![1u8; fragment_size + 10]);
+        let mut sender = FragmentSender::new();
+        sender.fragment_size = fragment_size;
+        let fragments = sender.build_fragments(MessageId(0), None, message_bytes.clone(), 0.0);
+        assert_eq!(fragments.len(), 2);
+
+        // receive the fragments out of order
+        assert_eq!(receiver.receive_fragment(fragments[1].clone(), None)?, None);
+        let data = receiver
+            .receive_fragment(fragments[0].clone(), None)?
+            .unwrap();
+        assert_eq!(data.bytes, message_bytes);
+        Ok(())
+    }
+
+    #[test]
+    fn test_receiver_limits() -> Result<()> {
+        let mut receiver = FragmentReceiver::new();
+        receiver.set_limits(Some(FRAGMENT_SIZE + 100), Some(1));
+        let sender = FragmentSender::new();
+
+        // we can tell that the message is too big from the first fragment
+        let large_message = Bytes::from(vec![1u8; FRAGMENT_SIZE * 2 + 10]);
+        let fragments = sender.build_fragments(MessageId(0), None, large_message, 0.0);
+        let error = receiver
+            .receive_fragment(fragments[0].clone(), None)
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ChannelError>(),
+            Some(&ChannelError::FragmentedMessageTooLarge {
+                size: FRAGMENT_SIZE * 2,
+                max_size: FRAGMENT_SIZE + 100,
+            })
+        );
+        assert!(receiver.fragment_messages.is_empty());
+
+        // only one message can be reassembled at a time
+        let message = Bytes::from(vec![1u8; FRAGMENT_SIZE + 10]);
+        let fragments = sender.build_fragments(MessageId(1), None, message.clone(), 0.0);
+        let other_fragments = sender.build_fragments(MessageId(2), None, message.clone(), 0.0);
+        assert_eq!(receiver.receive_fragment(fragments[0].clone(), None)?, None);
+        let error = receiver
+            .receive_fragment(other_fragments[0].clone(), None)
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ChannelError>(),
+            Some(&ChannelError::TooManyReassemblies {
+                max_reassemblies: 1
+            })
+        );
+        assert_eq!(
+            receiver
+                .receive_fragment(fragments[1].clone(), None)?
+                .unwrap()
+                .bytes,
+            message
+        );
+        Ok(())
+    }
 }
@@ -4,6 +4,9 @@ use crate::shared::tick_manager::TickManager;
 use crate::shared::time_manager::TimeManager;
 use enum_dispatch::enum_dispatch;
 
+/// Receive the transfers of a Bulk channel
+pub(crate) mod bulk;
+
 /// Utilities to receive a Message from multiple fragment packets
 pub(crate) mod fragment_receiver;
 
@@ -35,6 +38,15 @@ pub trait ChannelReceive {
 
     /// Reads a message from the internal buffer to get its content
     fn read_message(&mut self) -> Option<SingleData>;
+
+    /// Limit the size of the fragmented messages, and the number of fragmented messages that
+    /// can be reassembled at the same time
+    fn set_fragment_limits(
+        &mut self,
+        _max_message_size: Option<usize>,
+        _max_reassemblies: Option<usize>,
+    ) {
+    }
 }
 
 /// This enum contains the various types of receivers available
@@ -46,4 +58,5 @@ pub enum ChannelReceiver {
     SequencedReliable(sequenced_reliable::SequencedReliableReceiver),
     UnorderedReliable(unordered_reliable::UnorderedReliableReceiver),
     TickUnreliable(tick_unreliable::TickUnreliableReceiver),
+    Bulk(bulk::BulkReceiver),
 }
@@ -1,12 +1,15 @@
 use std::collections::{btree_map, BTreeMap};
 
 use anyhow::anyhow;
+use bevy::utils::Duration;
+use tracing::trace;
 
 use crate::channel::receivers::fragment_receiver::FragmentReceiver;
 use crate::channel::receivers::ChannelReceive;
 use crate::packet::message::{MessageContainer, MessageId, SingleData};
 pub use crate::shared::tick_manager::TickManager;
 pub use crate::shared::time_manager::TimeManager;
+use crate::shared::time_manager::WrappedTime;
 
 /// Ordered Reliable receiver: make sure that all messages are received,
 /// and return them in order
@@ -18,6 +21,12 @@ pub struct OrderedReliableReceiver {
     /// Buffer of the messages that we received, but haven't processed yet
     recv_message_buffer: BTreeMap<MessageId, SingleData>,
     fragment_receiver: FragmentReceiver,
+    /// If set, the sender drops the messages that were not acked after this duration, so we stop
+    /// waiting for a missing message after this duration
+    time_to_live: Option<Duration>,
+    /// Time at which we started waiting for the pending message, while later messages were already received
+    waiting_since: Option<WrappedTime>,
+    current_time: WrappedTime,
 }
 
 impl OrderedReliableReceiver {
@@ -26,12 +35,53 @@ impl OrderedReliableReceiver {
             pending_recv_message_id: MessageId(0),
             recv_message_buffer: BTreeMap::new(),
             fragment_receiver: FragmentReceiver::new(),
+            time_to_live: None,
+            waiting_since: None,
+            current_time: WrappedTime::default(),
+        }
+    }
+
+    /// Skip the missing messages if we have been waiting for them for longer than `time_to_live`
+    /// (the sender has stopped trying to send them)
+    pub(crate) fn with_time_to_live(mut self, time_to_live: Duration) -> Self {
+        self.time_to_live = Some(time_to_live);
+        self
+    }
+
+    /// If the pending message is missing but later messages were received, skip to the next received
+    /// message once the pending message has expired on the sender side
+    fn skip_expired_messages(&mut self) {
+        let Some(time_to_live) = self.time_to_live else {
+            return;
+        };
+        let Some(next_message_id) = self.recv_message_buffer.keys().next().copied() else {
+            self.waiting_since = None;
+            return;
+        };
+        if next_message_id == self.pending_recv_message_id {
+            self.waiting_since = None;
+            return;
+        }
+        // the later message was buffered by the sender after the missing messages, so all the missing messages
+        // are expired once we have waited for `time_to_live` since we started waiting for them
+        let waiting_since = *self.waiting_since.get_or_insert(self.current_time);
+        if self.current_time - waiting_since > chrono::Duration::from_std(time_to_live).unwrap() {
+            trace!(
+                from = ?self.pending_recv_message_id,
+                to = ?next_message_id,
+                "skipping expired messages"
+            );
+            self.pending_recv_message_id = next_message_id;
+            self.waiting_since = None;
         }
     }
 }
 
 impl ChannelReceive for OrderedReliableReceiver {
-    fn update(&mut self, _: &TimeManager, _: &TickManager) {}
+    fn update(&mut self, time_manager: &TimeManager, _: &TickManager) {
+        self.current_time = time_manager.current_time();
+        self.skip_expired_messages();
+    }
 
     /// Queues a received message in an internal buffer
     fn buffer_recv(&mut self, message: MessageContainer) -> anyhow::Result<()> {
@@ -75,17 +125,29 @@ impl ChannelReceive for OrderedReliableReceiver {
         // if we have finally received the message we are waiting for, return it and
         // wait for the next one
         self.pending_recv_message_id += 1;
+        self.waiting_since = None;
         Some(message)
     }
+
+    fn set_fragment_limits(
+        &mut self,
+        max_message_size: Option<usize>,
+        max_reassemblies: Option<usize>,
+    ) {
+        self.fragment_receiver
+            .set_limits(max_message_size, max_reassemblies);
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use bevy::utils::Duration;
     use bytes::Bytes;
 
     use crate::channel::receivers::ordered_reliable::OrderedReliableReceiver;
     use crate::channel::receivers::ChannelReceive;
     use crate::packet::message::{MessageId, SingleData};
+    use crate::shared::time_manager::WrappedTime;
 
     #[test]
     fn test_ordered_reliable_receiver_internals() -> anyhow::Result<()> {
@@ -121,4 +183,34 @@ mod tests {
         assert_eq!(receiver.read_message(), Some(single2.clone()));
         Ok(())
     }
+
+    #[test]
+    fn test_ordered_reliable_receiver_skip_expired_messages() -> anyhow::Result<()> {
+        let mut receiver =
+            OrderedReliableReceiver::new().with_time_to_live(Duration::from_millis(500));
+        let mut single = SingleData::new(None, Bytes::from("hello"), 1.0);
+
+        // message 0 is missing
+        single.id = Some(MessageId(1));
+        receiver.buffer_recv(single.clone().into())?;
+        receiver.current_time = WrappedTime::new(1000);
+        receiver.skip_expired_messages();
+        assert_eq!(receiver.read_message(), None);
+
+        // we keep waiting for message 0 until its time-to-live expires
+        receiver.current_time = WrappedTime::new(1400);
+        receiver.skip_expired_messages();
+        assert_eq!(receiver.read_message(), None);
+
+        receiver.current_time = WrappedTime::new(1600);
+        receiver.skip_expired_messages();
+        assert_eq!(receiver.read_message(), Some(single.clone()));
+        assert_eq!(receiver.pending_recv_message_id, MessageId(2));
+
+        // message 0 arrives late: it is ignored
+        single.id = Some(MessageId(0));
+        receiver.buffer_recv(single.clone().into())?;
+        assert_eq!(receiver.read_message(), None);
+        Ok(())
+    }
 }
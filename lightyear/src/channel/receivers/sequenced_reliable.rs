@@ -75,6 +75,15 @@ impl ChannelReceive for SequencedReliableReceiver {
             }
         }
     }
+
+    fn set_fragment_limits(
+        &mut self,
+        max_message_size: Option<usize>,
+        max_reassemblies: Option<usize>,
+    ) {
+        self.fragment_receiver
+            .set_limits(max_message_size, max_reassemblies);
+    }
 }
 
 #[cfg(test)]
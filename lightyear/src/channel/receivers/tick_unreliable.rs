@@ -83,6 +83,15 @@ impl ChannelReceive for TickUnreliableReceiver {
             .map(|(_, data)| data)
         // TODO: naia does a more optimized version by return a Vec<Message> instead of Option<Message>
     }
+
+    fn set_fragment_limits(
+        &mut self,
+        max_message_size: Option<usize>,
+        max_reassemblies: Option<usize>,
+    ) {
+        self.fragment_receiver
+            .set_limits(max_message_size, max_reassemblies);
+    }
 }
 
 #[cfg(test)]
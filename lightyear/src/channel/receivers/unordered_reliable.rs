@@ -1,12 +1,14 @@
 use std::collections::{btree_map, BTreeMap, HashSet};
 
 use anyhow::anyhow;
+use bevy::utils::Duration;
+use tracing::trace;
 
 use crate::channel::receivers::fragment_receiver::FragmentReceiver;
 use crate::channel::receivers::ChannelReceive;
 use crate::packet::message::{MessageContainer, MessageId, SingleData};
 use crate::shared::tick_manager::TickManager;
-use crate::shared::time_manager::TimeManager;
+use crate::shared::time_manager::{TimeManager, WrappedTime};
 
 /// Unordered Reliable receiver: make sure that all messages are received,
 /// and return them in any order
@@ -21,6 +23,12 @@ pub struct UnorderedReliableReceiver {
     fragment_receiver: FragmentReceiver,
     /// Keep tracking of the message ids we have received, so we can update the oldest_pending_message_id
     received_message_ids: HashSet<MessageId>,
+    /// If set, the sender drops the messages that were not acked after this duration, so we stop
+    /// waiting for a missing message after this duration
+    time_to_live: Option<Duration>,
+    /// Time at which we started waiting for the pending message, while later messages were already received
+    waiting_since: Option<WrappedTime>,
+    current_time: WrappedTime,
 }
 
 impl UnorderedReliableReceiver {
@@ -30,12 +38,66 @@ impl UnorderedReliableReceiver {
             recv_message_buffer: BTreeMap::new(),
             fragment_receiver: FragmentReceiver::new(),
             received_message_ids: HashSet::new(),
+            time_to_live: None,
+            waiting_since: None,
+            current_time: WrappedTime::default(),
+        }
+    }
+
+    /// Stop waiting for the missing messages if we have been waiting for them for longer than `time_to_live`
+    /// (the sender has stopped trying to send them)
+    pub(crate) fn with_time_to_live(mut self, time_to_live: Duration) -> Self {
+        self.time_to_live = Some(time_to_live);
+        self
+    }
+
+    /// Update the pending message id (skip through all message ids we have already received out of order)
+    fn advance_pending_message_id(&mut self) {
+        while self
+            .received_message_ids
+            .contains(&self.pending_recv_message_id)
+        {
+            self.received_message_ids
+                .remove(&self.pending_recv_message_id);
+            self.pending_recv_message_id += 1;
+        }
+    }
+
+    /// If the pending message is missing but later messages were received, stop waiting for it
+    /// once it has expired on the sender side
+    fn skip_expired_messages(&mut self) {
+        let Some(time_to_live) = self.time_to_live else {
+            return;
+        };
+        let Some(next_message_id) = self.received_message_ids.iter().min().copied() else {
+            self.waiting_since = None;
+            return;
+        };
+        if next_message_id == self.pending_recv_message_id {
+            self.waiting_since = None;
+            return;
+        }
+        // the later message was buffered by the sender after the missing messages, so all the missing messages
+        // are expired once we have waited for `time_to_live` since we started waiting for them
+        let waiting_since = *self.waiting_since.get_or_insert(self.current_time);
+        if self.current_time - waiting_since > chrono::Duration::from_std(time_to_live).unwrap() {
+            trace!(
+                from = ?self.pending_recv_message_id,
+                to = ?next_message_id,
+                "skipping expired messages"
+            );
+            self.pending_recv_message_id = next_message_id;
+            self.waiting_since = None;
+            self.advance_pending_message_id();
         }
     }
 }
 
 impl ChannelReceive for UnorderedReliableReceiver {
-    fn update(&mut self, _: &TimeManager, _: &TickManager) {}
+    fn update(&mut self, time_manager: &TimeManager, _: &TickManager) {
+        self.current_time = time_manager.current_time();
+        self.skip_expired_messages();
+    }
 
     /// Queues a received message in an internal buffer
     fn buffer_recv(&mut self, message: MessageContainer) -> anyhow::Result<()> {
@@ -85,20 +147,21 @@ impl ChannelReceive for UnorderedReliableReceiver {
 
         // this was the message we were waiting for (as a reliable receiver)
         if self.pending_recv_message_id == message_id {
-            // update the pending message id (skip through all message ids we have already received out of order)
-            while self
-                .received_message_ids
-                .contains(&self.pending_recv_message_id)
-            {
-                self.received_message_ids
-                    .remove(&self.pending_recv_message_id);
-                self.pending_recv_message_id += 1;
-            }
+            self.advance_pending_message_id();
         }
 
         // receive oldest message in the buffer
         Some(message)
     }
+
+    fn set_fragment_limits(
+        &mut self,
+        max_message_size: Option<usize>,
+        max_reassemblies: Option<usize>,
+    ) {
+        self.fragment_receiver
+            .set_limits(max_message_size, max_reassemblies);
+    }
 }
 
 #[cfg(test)]
@@ -145,4 +208,26 @@ mod tests {
         assert_eq!(receiver.pending_recv_message_id, MessageId(2));
         Ok(())
     }
+
+    #[test]
+    fn test_unordered_reliable_receiver_skip_expired_messages() -> anyhow::Result<()> {
+        let mut receiver =
+            UnorderedReliableReceiver::new().with_time_to_live(Duration::from_millis(500));
+        let mut single = SingleData::new(None, Bytes::from("hello"), 1.0);
+
+        // message 0 is missing, message 1 is received right away
+        single.id = Some(MessageId(1));
+        receiver.buffer_recv(single.clone().into())?;
+        assert_eq!(receiver.read_message(), Some(single.clone()));
+        receiver.current_time = WrappedTime::new(1000);
+        receiver.skip_expired_messages();
+        assert_eq!(receiver.pending_recv_message_id, MessageId(0));
+
+        // after the time-to-live, we stop waiting for message 0
+        receiver.current_time = WrappedTime::new(1600);
+        receiver.skip_expired_messages();
+        assert_eq!(receiver.pending_recv_message_id, MessageId(2));
+        assert!(receiver.received_message_ids.is_empty());
+        Ok(())
+    }
 }
@@ -50,6 +50,15 @@ impl ChannelReceive for UnorderedUnreliableReceiver {
     fn read_message(&mut self) -> Option<SingleData> {
         self.recv_message_buffer.pop_front()
     }
+
+    fn set_fragment_limits(
+        &mut self,
+        max_message_size: Option<usize>,
+        max_reassemblies: Option<usize>,
+    ) {
+        self.fragment_receiver
+            .set_limits(max_message_size, max_reassemblies);
+    }
 }
 
 #[cfg(test)]
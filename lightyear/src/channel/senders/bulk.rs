@@ -0,0 +1,461 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use bevy::utils::Duration;
+use bytes::Bytes;
+use crossbeam_channel::{Receiver, Sender};
+use tracing::{debug, trace};
+
+use crate::channel::builder::BulkSettings;
+use crate::channel::bulk::{BulkControl, ChunkHeader, CHUNK_HEADER_BYTES};
+use crate::channel::senders::ChannelSend;
+use crate::packet::message::{FragmentData, MessageAck, MessageId, SingleData};
+use crate::packet::packet::FRAGMENT_SIZE;
+use crate::shared::ping::manager::PingManager;
+use crate::shared::tick_manager::TickManager;
+use crate::shared::time_manager::{TimeManager, WrappedTime};
+
+/// A chunk that was sent, but not acked yet
+struct InFlightChunk {
+    message_id: MessageId,
+    last_sent: WrappedTime,
+}
+
+/// A transfer that is being sent, or waiting for the previous transfers to complete
+struct OutgoingTransfer {
+    transfer_id: MessageId,
+    payload: Bytes,
+    /// Number of bytes of data in each chunk (excluding the chunk header)
+    chunk_size: usize,
+    num_chunks: u32,
+    acked_chunks: Vec<bool>,
+    num_acked_chunks: u32,
+    acked_bytes: usize,
+    /// Index of the next chunk that was never sent
+    next_chunk: u32,
+    /// The chunks with an index lower than this limit can be sent (granted by the receiver)
+    max_chunk: u32,
+    in_flight: BTreeMap<u32, InFlightChunk>,
+    /// Index of the chunk sent with each message id
+    chunk_message_ids: HashMap<MessageId, u32>,
+    base_priority: f32,
+    accumulated_priority: f32,
+}
+
+impl OutgoingTransfer {
+    fn new(
+        transfer_id: MessageId,
+        payload: Bytes,
+        chunk_size: usize,
+        receive_window: u32,
+        priority: f32,
+    ) -> Self {
+        // an empty payload is still sent as one empty chunk
+        let num_chunks = ((payload.len() + chunk_size - 1) / chunk_size).max(1) as u32;
+        Self {
+            transfer_id,
+            payload,
+            chunk_size,
+            num_chunks,
+            acked_chunks: vec![false; num_chunks as usize],
+            num_acked_chunks: 0,
+            acked_bytes: 0,
+            next_chunk: 0,
+            max_chunk: receive_window,
+            in_flight: BTreeMap::new(),
+            chunk_message_ids: HashMap::new(),
+            base_priority: priority,
+            accumulated_priority: 0.0,
+        }
+    }
+
+    /// Data of the chunk, excluding the chunk header
+    fn chunk_data(&self, chunk_index: u32) -> Bytes {
+        let start = chunk_index as usize * self.chunk_size;
+        let end = (start + self.chunk_size).min(self.payload.len());
+        self.payload.slice(start..end)
+    }
+
+    /// Chunk to send, including the chunk header
+    fn chunk(&self, chunk_index: u32) -> Bytes {
+        ChunkHeader {
+            transfer_id: self.transfer_id,
+            chunk_index,
+            num_chunks: self.num_chunks,
+        }
+        .write(&self.chunk_data(chunk_index))
+    }
+}
+
+/// Sender of a [`Bulk`](crate::channel::builder::ChannelMode::Bulk) channel: sends the transfers one after
+/// the other, with a limited number of chunks in flight
+pub struct BulkSender {
+    settings: BulkSettings,
+    /// Transfers that were not fully acked yet. Only the first one is being sent
+    transfers: VecDeque<OutgoingTransfer>,
+    next_transfer_id: MessageId,
+    /// Message id to use for the next chunk that is sent for the first time
+    next_chunk_message_id: MessageId,
+    /// Number of bytes of data in each chunk of the new transfers (excluding the chunk header)
+    chunk_size: usize,
+    /// list of chunks that we want to fit into packets and send
+    single_messages_to_send: VecDeque<SingleData>,
+    /// List of senders that want to be notified when a transfer is fully acked
+    ack_senders: Vec<Sender<MessageId>>,
+    /// Control messages to send to the receiver
+    controls: Vec<(MessageId, BulkControl)>,
+    /// Transfers that were cancelled by the receiver
+    cancelled_by_receiver: Vec<MessageId>,
+    /// (transfer id, acked bytes, total bytes) of the transfers that progressed
+    progress: Vec<(MessageId, usize, usize)>,
+    /// Number of chunks resent since the last call to `take_num_resends`
+    num_resends: u32,
+    /// Factor applied to the resend delay, set by the congestion control
+    resend_backoff: f32,
+    /// False if the transport delivers the chunks reliably, so they never need to be resent
+    resends_enabled: bool,
+    current_rtt: Duration,
+    current_time: WrappedTime,
+}
+
+impl BulkSender {
+    pub fn new(settings: BulkSettings) -> Self {
+        Self {
+            settings,
+            transfers: VecDeque::new(),
+            next_transfer_id: MessageId(0),
+            next_chunk_message_id: MessageId(0),
+            chunk_size: FRAGMENT_SIZE - CHUNK_HEADER_BYTES,
+            single_messages_to_send: VecDeque::new(),
+            ack_senders: Vec::new(),
+            controls: Vec::new(),
+            cancelled_by_receiver: Vec::new(),
+            progress: Vec::new(),
+            num_resends: 0,
+            resend_backoff: 1.0,
+            resends_enabled: true,
+            current_rtt: Duration::default(),
+            current_time: WrappedTime::default(),
+        }
+    }
+
+    /// Stop sending a transfer.
+    ///
+    /// Returns false if the transfer was already fully acked (or cancelled)
+    pub(crate) fn cancel(&mut self, transfer_id: MessageId) -> bool {
+        let Some(transfer) = self.remove_transfer(transfer_id) else {
+            return false;
+        };
+        // the receiver only knows about the transfers for which we sent chunks
+        if transfer.next_chunk > 0 {
+            self.controls.push((transfer_id, BulkControl::CancelSend));
+        }
+        true
+    }
+
+    /// Handle a control message sent by the receiver
+    pub(crate) fn receive_control(&mut self, transfer_id: MessageId, control: BulkControl) {
+        match control {
+            BulkControl::WindowUpdate { max_chunk } => {
+                if let Some(transfer) = self
+                    .transfers
+                    .iter_mut()
+                    .find(|transfer| transfer.transfer_id == transfer_id)
+                {
+                    transfer.max_chunk = transfer.max_chunk.max(max_chunk);
+                }
+            }
+            BulkControl::CancelReceive => {
+                if self.remove_transfer(transfer_id).is_some() {
+                    debug!(?transfer_id, "transfer cancelled by the receiver");
+                    self.cancelled_by_receiver.push(transfer_id);
+                }
+            }
+            BulkControl::CancelSend => {
+                trace!(?transfer_id, "ignoring control message sent by a sender");
+            }
+        }
+    }
+
+    /// Control messages to send to the receiver
+    pub(crate) fn take_controls(&mut self) -> Vec<(MessageId, BulkControl)> {
+        std::mem::take(&mut self.controls)
+    }
+
+    /// Transfers that were cancelled by the receiver since the last call
+    pub(crate) fn take_cancelled_transfers(&mut self) -> Vec<MessageId> {
+        std::mem::take(&mut self.cancelled_by_receiver)
+    }
+
+    /// (transfer id, acked bytes, total bytes) of the transfers that progressed since the last call
+    pub(crate) fn take_progress(&mut self) -> Vec<(MessageId, usize, usize)> {
+        std::mem::take(&mut self.progress)
+    }
+
+    fn remove_transfer(&mut self, transfer_id: MessageId) -> Option<OutgoingTransfer> {
+        let index = self
+            .transfers
+            .iter()
+            .position(|transfer| transfer.transfer_id == transfer_id)?;
+        self.transfers.remove(index)
+    }
+
+    /// Notify the subscribers that a transfer was fully acked
+    fn notify_ack_subscribers(&self, transfer_id: MessageId) {
+        for sender in &self.ack_senders {
+            // the subscriber might have been dropped, in which case nobody is waiting for the ack
+            let _ = sender.send(transfer_id);
+        }
+    }
+}
+
+impl ChannelSend for BulkSender {
+    fn update(&mut self, time_manager: &TimeManager, ping_manager: &PingManager, _: &TickManager) {
+        self.current_time = time_manager.current_time();
+        self.current_rtt = ping_manager.rtt();
+    }
+
+    /// Add a new transfer to the queue of transfers to send.
+    ///
+    /// Returns the transfer id
+    fn buffer_send(&mut self, message: Bytes, priority: f32) -> Option<MessageId> {
+        let transfer_id = self.next_transfer_id;
+        self.transfers.push_back(OutgoingTransfer::new(
+            transfer_id,
+            message,
+            self.chunk_size,
+            self.settings.receive_window,
+            priority,
+        ));
+        self.next_transfer_id += 1;
+        Some(transfer_id)
+    }
+
+    fn send_packet(&mut self) -> (VecDeque<SingleData>, VecDeque<FragmentData>) {
+        (
+            std::mem::take(&mut self.single_messages_to_send),
+            VecDeque::new(),
+        )
+    }
+
+    /// Collect the chunks of the current transfer that need to be resent, and the new chunks
+    /// that fit in the send window and in the window granted by the receiver
+    fn collect_messages_to_send(&mut self) {
+        let Some(transfer) = self.transfers.front_mut() else {
+            return;
+        };
+        transfer.accumulated_priority += transfer.base_priority;
+        let priority = transfer.accumulated_priority;
+        let resend_delay = chrono::Duration::from_std(
+            self.settings
+                .reliable
+                .resend_delay(self.current_rtt)
+                .mul_f32(self.resend_backoff),
+        )
+        .unwrap();
+
+        // resend the chunks that were not acked in time
+        let mut resent_chunks = vec![];
+        for (chunk_index, chunk) in transfer.in_flight.iter_mut() {
+            if self.resends_enabled && self.current_time - chunk.last_sent > resend_delay {
+                chunk.last_sent = self.current_time;
+                resent_chunks.push((*chunk_index, chunk.message_id));
+            }
+        }
+        self.num_resends += resent_chunks.len() as u32;
+        for (chunk_index, message_id) in resent_chunks {
+            self.single_messages_to_send.push_back(SingleData::new(
+                Some(message_id),
+                transfer.chunk(chunk_index),
+                priority,
+            ));
+        }
+
+        // send new chunks
+        let max_chunk = transfer.max_chunk.min(transfer.num_chunks);
+        while (transfer.in_flight.len() as u32) < self.settings.send_window
+            && transfer.next_chunk < max_chunk
+        {
+            let chunk_index = transfer.next_chunk;
+            let message_id = self.next_chunk_message_id;
+            self.next_chunk_message_id += 1;
+            transfer.next_chunk += 1;
+            transfer.in_flight.insert(
+                chunk_index,
+                InFlightChunk {
+                    message_id,
+                    last_sent: self.current_time,
+                },
+            );
+            transfer.chunk_message_ids.insert(message_id, chunk_index);
+            self.single_messages_to_send.push_back(SingleData::new(
+                Some(message_id),
+                transfer.chunk(chunk_index),
+                priority,
+            ));
+        }
+    }
+
+    fn notify_message_delivered(&mut self, message_ack: &MessageAck) {
+        let Some(transfer) = self.transfers.front_mut() else {
+            return;
+        };
+        let Some(chunk_index) = transfer.chunk_message_ids.remove(&message_ack.message_id) else {
+            return;
+        };
+        transfer.in_flight.remove(&chunk_index);
+        if transfer.acked_chunks[chunk_index as usize] {
+            return;
+        }
+        transfer.acked_chunks[chunk_index as usize] = true;
+        transfer.num_acked_chunks += 1;
+        transfer.acked_bytes += transfer.chunk_data(chunk_index).len();
+
+        // only keep the latest progress of each transfer
+        let progress = (
+            transfer.transfer_id,
+            transfer.acked_bytes,
+            transfer.payload.len(),
+        );
+        match self.progress.last_mut() {
+            Some(last) if last.0 == transfer.transfer_id => *last = progress,
+            _ => self.progress.push(progress),
+        }
+
+        if transfer.num_acked_chunks == transfer.num_chunks {
+            let transfer_id = transfer.transfer_id;
+            trace!(?transfer_id, "transfer complete");
+            self.transfers.pop_front();
+            self.notify_ack_subscribers(transfer_id);
+        }
+    }
+
+    fn has_messages_to_send(&self) -> bool {
+        !self.single_messages_to_send.is_empty()
+    }
+
+    fn subscribe_acks(&mut self) -> Receiver<MessageId> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.ack_senders.push(sender);
+        receiver
+    }
+
+    /// The new size is only used for the transfers that are buffered afterwards
+    fn set_fragment_size(&mut self, fragment_size: usize) {
+        self.chunk_size = fragment_size - CHUNK_HEADER_BYTES;
+    }
+
+    fn take_num_resends(&mut self) -> u32 {
+        std::mem::take(&mut self.num_resends)
+    }
+
+    fn set_resend_backoff(&mut self, backoff: f32) {
+        self.resend_backoff = backoff;
+    }
+
+    fn disable_resends(&mut self) {
+        self.resends_enabled = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channel::bulk::ChunkHeader;
+
+    use super::*;
+
+    fn sent_chunks(sender: &mut BulkSender) -> Vec<(MessageId, ChunkHeader)> {
+        sender.collect_messages_to_send();
+        sender
+            .send_packet()
+            .0
+            .into_iter()
+            .map(|data| (data.id.unwrap(), ChunkHeader::read(&data.bytes).unwrap().0))
+            .collect()
+    }
+
+    fn ack(sender: &mut BulkSender, message_id: MessageId) {
+        sender.notify_message_delivered(&MessageAck {
+            message_id,
+            fragment_id: None,
+        });
+    }
+
+    #[test]
+    fn test_bulk_sender_windows() {
+        let settings = BulkSettings::default()
+            .with_send_window(2)
+            .with_receive_window(3);
+        let mut sender = BulkSender::new(settings);
+        sender.chunk_size = 10;
+        let acks = sender.subscribe_acks();
+        let transfer_id = sender.buffer_send(Bytes::from(vec![1; 45]), 1.0).unwrap();
+
+        // only 2 chunks in flight
+        let chunks = sent_chunks(&mut sender);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].1.num_chunks, 5);
+        assert!(sent_chunks(&mut sender).is_empty());
+
+        // a chunk is acked: the next chunk can be sent
+        ack(&mut sender, chunks[0].0);
+        assert_eq!(sender.take_progress(), vec![(transfer_id, 10, 45)]);
+        let chunks = sent_chunks(&mut sender);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].1.chunk_index, 2);
+
+        // the receiver only granted 3 chunks
+        ack(&mut sender, MessageId(1));
+        ack(&mut sender, MessageId(2));
+        assert!(sent_chunks(&mut sender).is_empty());
+        sender.receive_control(transfer_id, BulkControl::WindowUpdate { max_chunk: 5 });
+        let chunks = sent_chunks(&mut sender);
+        assert_eq!(chunks.len(), 2);
+        ack(&mut sender, chunks[0].0);
+        ack(&mut sender, chunks[1].0);
+        assert_eq!(sender.take_progress(), vec![(transfer_id, 45, 45)]);
+        assert_eq!(acks.try_recv(), Ok(transfer_id));
+        assert!(sender.transfers.is_empty());
+    }
+
+    #[test]
+    fn test_bulk_sender_resend() {
+        let mut sender = BulkSender::new(BulkSettings::default());
+        sender.chunk_size = 10;
+        sender.buffer_send(Bytes::from(vec![1; 15]), 1.0);
+        let chunks = sent_chunks(&mut sender);
+        assert_eq!(chunks.len(), 2);
+        ack(&mut sender, chunks[0].0);
+
+        // the chunk that was not acked is sent again with the same message id
+        sender.current_time += Duration::from_millis(200);
+        assert_eq!(sent_chunks(&mut sender), vec![chunks[1]]);
+        assert_eq!(sender.take_num_resends(), 1);
+    }
+
+    #[test]
+    fn test_bulk_sender_cancel() {
+        let mut sender = BulkSender::new(BulkSettings::default());
+        sender.chunk_size = 10;
+        let first = sender.buffer_send(Bytes::from(vec![1; 15]), 1.0).unwrap();
+        let second = sender.buffer_send(Bytes::from(vec![2; 15]), 1.0).unwrap();
+        let third = sender.buffer_send(Bytes::from(vec![3; 15]), 1.0).unwrap();
+        sent_chunks(&mut sender);
+
+        // the receiver is notified if the transfer was started
+        assert!(sender.cancel(first));
+        assert_eq!(
+            sender.take_controls(),
+            vec![(first, BulkControl::CancelSend)]
+        );
+        assert!(sender.cancel(third));
+        assert!(sender.take_controls().is_empty());
+        assert!(!sender.cancel(third));
+
+        // the receiver cancels the transfer
+        let chunks = sent_chunks(&mut sender);
+        assert_eq!(chunks[0].1.transfer_id, second);
+        sender.receive_control(second, BulkControl::CancelReceive);
+        assert_eq!(sender.take_cancelled_transfers(), vec![second]);
+        assert!(sent_chunks(&mut sender).is_empty());
+    }
+}
@@ -7,6 +7,7 @@ use crossbeam_channel::Receiver;
 use enum_dispatch::enum_dispatch;
 use std::collections::VecDeque;
 
+pub(crate) mod bulk;
 pub(crate) mod fragment_ack_receiver;
 pub(crate) mod fragment_sender;
 pub(crate) mod reliable;
@@ -53,6 +54,28 @@ pub trait ChannelSend {
 
     /// Create a new receiver that will receive a message id when a sent message is acked
     fn subscribe_acks(&mut self) -> Receiver<MessageId>;
+
+    /// Set the maximum size of a fragment, when the path MTU of the connection changes.
+    ///
+    /// Messages that were already fragmented keep their fragment size.
+    fn set_fragment_size(&mut self, fragment_size: usize);
+
+    /// Number of messages (or fragments) that were sent again since the last call,
+    /// because they were not acked in time
+    fn take_num_resends(&mut self) -> u32 {
+        0
+    }
+
+    /// Multiply the delay before resending a message that was not acked, for example to resend less
+    /// often while the link is congested
+    fn set_resend_backoff(&mut self, _backoff: f32) {}
+
+    /// Never resend the messages that were not acked, because the transport already delivers them reliably
+    fn disable_resends(&mut self) {}
+
+    /// Limit the number of fragmented messages that are being sent at the same time, so that the receiver
+    /// never has to drop the fragments of a reliable message because it reassembles too many messages
+    fn set_max_reassemblies(&mut self, _max_reassemblies: Option<usize>) {}
 }
 
 /// Enum dispatch lets us derive ChannelSend on each enum variant
@@ -63,4 +86,5 @@ pub enum ChannelSender {
     SequencedUnreliable(sequenced_unreliable::SequencedUnreliableSender),
     Reliable(reliable::ReliableSender),
     TickUnreliable(tick_unreliable::TickUnreliableSender),
+    Bulk(bulk::BulkSender),
 }
@@ -3,7 +3,7 @@ use std::collections::VecDeque;
 use std::collections::{BTreeMap, HashSet};
 
 use bytes::Bytes;
-use crossbeam_channel::Receiver;
+use crossbeam_channel::{Receiver, Sender};
 use tracing::{info, trace};
 
 use crate::channel::builder::ReliableSettings;
@@ -35,12 +35,16 @@ pub struct UnackedMessageWithPriority {
     pub unacked_message: UnackedMessage,
     pub base_priority: f32,
     pub accumulated_priority: f32,
+    /// Time at which the message was buffered (used to drop messages after their time-to-live)
+    pub buffered_at: WrappedTime,
 }
 
 /// A sender that makes sure to resend messages until it receives an ack
 pub struct ReliableSender {
     /// Settings for reliability
     reliable_settings: ReliableSettings,
+    /// If set, messages that are not acked after this duration are dropped instead of being resent
+    time_to_live: Option<Duration>,
     // TODO: maybe optimize by using a RingBuffer
     /// Ordered map of the messages that haven't been acked yet
     unacked_messages: BTreeMap<MessageId, UnackedMessageWithPriority>,
@@ -58,7 +62,18 @@ pub struct ReliableSender {
 
     /// Used to split a message into fragments if the message is too big
     fragment_sender: FragmentSender,
-
+    /// List of senders that want to be notified when a message is acked
+    ack_senders: Vec<Sender<MessageId>>,
+    /// Number of messages (or fragments) resent since the last call to `take_num_resends`
+    num_resends: u32,
+
+    /// Factor applied to the resend delay, set by the congestion control
+    resend_backoff: f32,
+    /// False if the transport delivers the messages reliably, so they never need to be resent
+    resends_enabled: bool,
+    /// Maximum number of fragmented messages that are being sent at the same time
+    /// (the receiver cannot reassemble more, and the fragments that it drops are still acked)
+    max_reassemblies: Option<usize>,
     current_rtt: Duration,
     current_time: WrappedTime,
 }
@@ -67,16 +82,52 @@ impl ReliableSender {
     pub fn new(reliable_settings: ReliableSettings) -> Self {
         Self {
             reliable_settings,
+            time_to_live: None,
             unacked_messages: Default::default(),
             next_send_message_id: MessageId(0),
             single_messages_to_send: Default::default(),
             fragmented_messages_to_send: Default::default(),
             message_ids_to_send: Default::default(),
             fragment_sender: FragmentSender::new(),
+            ack_senders: Vec::new(),
+            num_resends: 0,
+            resend_backoff: 1.0,
+            resends_enabled: true,
+            max_reassemblies: None,
             current_rtt: Duration::default(),
             current_time: WrappedTime::default(),
         }
     }
+
+    /// Stop resending the messages that were not acked after `time_to_live`
+    pub(crate) fn with_time_to_live(mut self, time_to_live: Duration) -> Self {
+        self.time_to_live = Some(time_to_live);
+        self
+    }
+
+    /// Drop the messages whose time-to-live has expired
+    fn drop_expired_messages(&mut self) {
+        let Some(time_to_live) = self.time_to_live else {
+            return;
+        };
+        let time_to_live = chrono::Duration::from_std(time_to_live).unwrap();
+        let current_time = self.current_time;
+        self.unacked_messages.retain(|message_id, message| {
+            let expired = current_time - message.buffered_at > time_to_live;
+            if expired {
+                trace!(?message_id, "dropping message whose time-to-live expired");
+            }
+            !expired
+        });
+    }
+
+    /// Notify the subscribers that a message was fully acked
+    fn notify_ack_subscribers(&self, message_id: MessageId) {
+        for sender in &self.ack_senders {
+            // the subscriber might have been dropped, in which case nobody is waiting for the ack
+            let _ = sender.send(message_id);
+        }
+    }
 }
 
 // Stragegy:
@@ -124,6 +175,7 @@ impl ChannelSend for ReliableSender {
             // store with 0.0 accumulated priority because priority gets accumulated when we collect the messages
             // for sending (even the first time the message is sent)
             accumulated_priority: 0.0,
+            buffered_at: self.current_time,
         };
         self.unacked_messages
             .insert(message_id, unacked_message_with_priority);
@@ -162,19 +214,37 @@ impl ChannelSend for ReliableSender {
     /// Either because they have never been sent, or because they need to be resent
     /// Needs to be called before [`ReliableSender::send_packet`]
     fn collect_messages_to_send(&mut self) {
+        self.drop_expired_messages();
         // resend delay is based on the rtt
-        let resend_delay =
-            chrono::Duration::from_std(self.reliable_settings.resend_delay(self.current_rtt))
-                .unwrap();
+        let resend_delay = chrono::Duration::from_std(
+            self.reliable_settings
+                .resend_delay(self.current_rtt)
+                .mul_f32(self.resend_backoff),
+        )
+        .unwrap();
         let should_send = |last_sent: &Option<WrappedTime>| -> bool {
             match last_sent {
                 // send it the message has never been sent
                 None => true,
                 // or if we sent it a while back but didn't get an ack
-                Some(last_sent) => self.current_time - *last_sent > resend_delay,
+                Some(last_sent) => {
+                    self.resends_enabled && self.current_time - *last_sent > resend_delay
+                }
             }
         };
 
+        // fragmented messages for which some fragments were already sent, but not all of them were acked
+        let mut num_fragmented_in_flight = self
+            .unacked_messages
+            .values()
+            .filter(|message| match &message.unacked_message {
+                UnackedMessage::Fragmented(fragment_acks) => {
+                    fragment_acks.iter().any(|f| f.last_sent.is_some())
+                }
+                _ => false,
+            })
+            .count();
+
         // Iterate through all unacked messages, oldest message ids first
         for (message_id, unacked_message_with_priority) in self.unacked_messages.iter_mut() {
             // accumulate the priority for all messages (including the ones that were just added, since we set the accumulated priority to 0.0)
@@ -199,6 +269,9 @@ impl ChannelSend for ReliableSender {
                             fragment_id: None,
                         };
                         if !self.message_ids_to_send.contains(&message_info) {
+                            if last_sent.is_some() {
+                                self.num_resends += 1;
+                            }
                             let message = SingleData::new(
                                 Some(*message_id),
                                 bytes.clone(),
@@ -211,6 +284,16 @@ impl ChannelSend for ReliableSender {
                     }
                 }
                 UnackedMessage::Fragmented(fragment_acks) => {
+                    // wait until the receiver completes some of the fragmented messages before sending a new one
+                    if fragment_acks.iter().all(|f| f.last_sent.is_none()) {
+                        if self
+                            .max_reassemblies
+                            .is_some_and(|max| num_fragmented_in_flight >= max)
+                        {
+                            continue;
+                        }
+                        num_fragmented_in_flight += 1;
+                    }
                     // only send the fragments that haven't been acked and should be resent
                     fragment_acks
                         .iter_mut()
@@ -222,6 +305,9 @@ impl ChannelSend for ReliableSender {
                                 fragment_id: Some(f.data.fragment_id),
                             };
                             if !self.message_ids_to_send.contains(&message_info) {
+                                if f.last_sent.is_some() {
+                                    self.num_resends += 1;
+                                }
                                 let message = f.data.clone();
                                 self.fragmented_messages_to_send.push_back(message);
                                 self.message_ids_to_send.insert(message_info);
@@ -243,6 +329,7 @@ impl ChannelSend for ReliableSender {
                         )
                     }
                     self.unacked_messages.remove(&message_ack.message_id);
+                    self.notify_ack_subscribers(message_ack.message_id);
                 }
                 UnackedMessage::Fragmented(fragment_acks) => {
                     let Some(fragment_id) = message_ack.fragment_id else {
@@ -254,6 +341,7 @@ impl ChannelSend for ReliableSender {
                         // all fragments were acked
                         if fragment_acks.iter().all(|f| f.acked) {
                             self.unacked_messages.remove(&message_ack.message_id);
+                            self.notify_ack_subscribers(message_ack.message_id);
                         }
                     }
                 }
@@ -265,8 +353,32 @@ impl ChannelSend for ReliableSender {
         !self.single_messages_to_send.is_empty() || !self.fragmented_messages_to_send.is_empty()
     }
 
+    fn set_fragment_size(&mut self, fragment_size: usize) {
+        self.fragment_sender.fragment_size = fragment_size;
+    }
+
+    fn take_num_resends(&mut self) -> u32 {
+        std::mem::take(&mut self.num_resends)
+    }
+
+    fn set_resend_backoff(&mut self, backoff: f32) {
+        self.resend_backoff = backoff;
+    }
+
+    fn disable_resends(&mut self) {
+        self.resends_enabled = false;
+    }
+
+    fn set_max_reassemblies(&mut self, max_reassemblies: Option<usize>) {
+        self.max_reassemblies = max_reassemblies;
+    }
+
+    /// Create a new receiver that will receive a message id when a message is acked
+    /// (i.e. when all its fragments were acked, for fragmented messages)
     fn subscribe_acks(&mut self) -> Receiver<MessageId> {
-        todo!()
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.ack_senders.push(sender);
+        receiver
     }
 }
 
@@ -281,6 +393,28 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_reliable_sender_resend_backoff() {
+        let mut sender = ReliableSender::new(ReliableSettings {
+            rtt_resend_factor: 1.5,
+            rtt_resend_min_delay: Duration::default(),
+        });
+        sender.current_rtt = Duration::from_millis(100);
+        sender.current_time = WrappedTime::new(0);
+        sender.buffer_send(Bytes::from("hello"), 1.0);
+        sender.collect_messages_to_send();
+        sender.send_packet();
+
+        // the link is congested: the message is resent after 4 times the usual delay
+        sender.set_resend_backoff(4.0);
+        sender.current_time += Duration::from_millis(200);
+        sender.collect_messages_to_send();
+        assert_eq!(sender.take_num_resends(), 0);
+        sender.current_time += Duration::from_millis(500);
+        sender.collect_messages_to_send();
+        assert_eq!(sender.take_num_resends(), 1);
+    }
+
     #[test]
     fn test_reliable_sender_internals() {
         let mut sender = ReliableSender::new(ReliableSettings {
@@ -325,4 +459,64 @@ mod tests {
         // this time there are no new messages to send
         assert_eq!(sender.single_messages_to_send.len(), 1);
     }
+
+    #[test]
+    fn test_reliable_sender_subscribe_acks() {
+        let mut sender = ReliableSender::new(ReliableSettings::default());
+        sender.set_fragment_size(10);
+        let receiver = sender.subscribe_acks();
+
+        // single message
+        let message_id = sender.buffer_send(Bytes::from("hello"), 1.0).unwrap();
+        sender.notify_message_delivered(&MessageAck {
+            message_id,
+            fragment_id: None,
+        });
+        assert_eq!(receiver.try_recv().unwrap(), message_id);
+
+        // a duplicate ack does not notify again
+        sender.notify_message_delivered(&MessageAck {
+            message_id,
+            fragment_id: None,
+        });
+        assert!(receiver.try_recv().is_err());
+
+        // fragmented message: only notified when all the fragments are acked
+        let message_id = sender.buffer_send(Bytes::from(vec![0; 15]), 1.0).unwrap();
+        sender.notify_message_delivered(&MessageAck {
+            message_id,
+            fragment_id: Some(0),
+        });
+        assert!(receiver.try_recv().is_err());
+        sender.notify_message_delivered(&MessageAck {
+            message_id,
+            fragment_id: Some(1),
+        });
+        assert_eq!(receiver.try_recv().unwrap(), message_id);
+    }
+
+    #[test]
+    fn test_reliable_sender_time_to_live() {
+        let mut sender = ReliableSender::new(ReliableSettings::default())
+            .with_time_to_live(Duration::from_millis(500));
+        sender.current_rtt = Duration::from_millis(100);
+        sender.current_time = WrappedTime::new(0);
+
+        sender.buffer_send(Bytes::from("hello"), 1.0);
+        sender.collect_messages_to_send();
+        assert_eq!(sender.single_messages_to_send.len(), 1);
+        sender.send_packet();
+
+        // the message is not acked: it is resent until its time-to-live expires
+        sender.current_time += Duration::from_millis(400);
+        sender.collect_messages_to_send();
+        assert_eq!(sender.single_messages_to_send.len(), 1);
+        sender.send_packet();
+
+        // after the time-to-live, the message is dropped
+        sender.current_time += Duration::from_millis(200);
+        sender.collect_messages_to_send();
+        assert!(sender.single_messages_to_send.is_empty());
+        assert!(sender.unacked_messages.is_empty());
+    }
 }
@@ -79,6 +79,10 @@ impl ChannelSend for SequencedUnreliableSender {
         !self.single_messages_to_send.is_empty() || !self.fragmented_messages_to_send.is_empty()
     }
 
+    fn set_fragment_size(&mut self, fragment_size: usize) {
+        self.fragment_sender.fragment_size = fragment_size;
+    }
+
     fn subscribe_acks(&mut self) -> Receiver<MessageId> {
         unreachable!()
     }
@@ -119,6 +119,10 @@ impl ChannelSend for UnorderedUnreliableWithAcksSender {
         !self.single_messages_to_send.is_empty() || !self.fragmented_messages_to_send.is_empty()
     }
 
+    fn set_fragment_size(&mut self, fragment_size: usize) {
+        self.fragment_sender.fragment_size = fragment_size;
+    }
+
     /// Create a new receiver that will receive a message id when a message is acked
     fn subscribe_acks(&mut self) -> Receiver<MessageId> {
         let (sender, receiver) = crossbeam_channel::unbounded();
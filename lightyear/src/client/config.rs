@@ -11,6 +11,8 @@ use crate::client::prediction::plugin::PredictionConfig;
 use crate::client::replication::ReplicationConfig;
 use crate::client::sync::SyncConfig;
 use crate::connection::client::NetConfig;
+use crate::packet::congestion::CongestionConfig;
+use crate::packet::mtu::MtuConfig;
 use crate::shared::config::{Mode, SharedConfig};
 use crate::shared::ping::manager::PingConfig;
 
@@ -23,6 +25,9 @@ pub struct NetcodeConfig {
     /// A negative value means no timeout.
     /// This is used for Authentication::Manual tokens
     pub client_timeout_secs: i32,
+    /// Fingerprint of the [`Protocol`](crate::protocol::Protocol), sent in the connection request.
+    /// It is set by the [`ClientPlugin`](crate::client::plugin::ClientPlugin).
+    pub(crate) protocol_fingerprint: u64,
 }
 
 impl Default for NetcodeConfig {
@@ -31,6 +36,7 @@ impl Default for NetcodeConfig {
             num_disconnect_packets: 10,
             keepalive_packet_send_rate: 1.0 / 10.0,
             client_timeout_secs: 3,
+            protocol_fingerprint: 0,
         }
     }
 }
@@ -40,6 +46,7 @@ impl NetcodeConfig {
         crate::connection::netcode::ClientConfig::default()
             .num_disconnect_packets(self.num_disconnect_packets)
             .packet_send_rate(self.keepalive_packet_send_rate)
+            .protocol_fingerprint(self.protocol_fingerprint)
     }
 }
 
@@ -51,6 +58,12 @@ pub struct PacketConfig {
     pub send_bandwidth_cap: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub bandwidth_cap_enabled: bool,
+    /// Discovery of the largest packet size that can be sent on the connection
+    pub mtu: MtuConfig,
+    /// Adjusts the bandwidth budget to the state of the network.
+    ///
+    /// If enabled, it replaces the static bandwidth cap
+    pub congestion: CongestionConfig,
 }
 
 impl Default for PacketConfig {
@@ -59,6 +72,8 @@ impl Default for PacketConfig {
             // 56 KB/s bandwidth cap
             send_bandwidth_cap: Quota::per_second(nonzero!(56000u32)),
             bandwidth_cap_enabled: false,
+            mtu: MtuConfig::default(),
+            congestion: CongestionConfig::default(),
         }
     }
 }
@@ -79,6 +94,16 @@ impl PacketConfig {
         self.bandwidth_cap_enabled = true;
         self
     }
+
+    pub fn with_mtu_config(mut self, mtu: MtuConfig) -> Self {
+        self.mtu = mtu;
+        self
+    }
+
+    pub fn with_congestion_config(mut self, congestion: CongestionConfig) -> Self {
+        self.congestion = congestion;
+        self
+    }
 }
 
 /// The configuration object that lets you create a `ClientPlugin` with the desired settings.
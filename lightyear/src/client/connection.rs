@@ -1,23 +1,33 @@
 //! Specify how a Client sends/receives messages with a Server
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bevy::ecs::component::Tick as BevyTick;
-use bevy::ecs::entity::{EntityHashMap, MapEntities};
+use bevy::ecs::entity::{EntityHashMap, EntityHashSet, MapEntities};
 use bevy::prelude::{Entity, Local, Resource, World};
 use bevy::reflect::Reflect;
 use bevy::utils::Duration;
 use serde::Serialize;
 use tracing::{debug, info, trace, trace_span, warn};
 
-use crate::_reexport::{ClientMarker, EntityUpdatesChannel, PingChannel, ReplicationSend};
+use crate::_reexport::{
+    AuthorityChannel, BulkControlChannel, ClientMarker, EntityUpdatesChannel, PingChannel,
+    ReplicationSend, ShouldBeInterpolated,
+};
+use crate::channel::bulk::TransferProgress;
 use crate::channel::senders::ChannelSend;
+use crate::client::components::Confirmed;
 use crate::client::config::PacketConfig;
 use crate::client::message::ClientMessage;
 use crate::client::sync::SyncConfig;
+use crate::connection::client::DisconnectReason;
 use crate::inputs::native::input_buffer::InputBuffer;
+use crate::packet::channel_stats::{ChannelStats, NetworkStats};
+use crate::packet::message::MessageHandle;
 use crate::packet::message_manager::MessageManager;
 use crate::packet::packet::Packet;
 use crate::packet::packet_manager::Payload;
-use crate::prelude::{Channel, ChannelKind, ClientId, Message, NetworkTarget};
+use crate::prelude::{
+    Channel, ChannelKind, ClientId, Message, NetworkTarget, ReplicationGroup, ShouldBePredicted,
+};
 use crate::protocol::channel::ChannelRegistry;
 use crate::protocol::Protocol;
 use crate::serialize::reader::ReadBuffer;
@@ -25,7 +35,11 @@ use crate::server::message::ServerMessage;
 use crate::shared::events::connection::ConnectionEvents;
 use crate::shared::ping::manager::{PingConfig, PingManager};
 use crate::shared::ping::message::SyncMessage;
-use crate::shared::replication::components::{Replicate, ReplicationGroupId};
+use crate::shared::replication::authority::{AuthorityChange, AuthorityMessage, HasAuthority};
+use crate::shared::replication::components::{
+    Replicate, ReplicateRemovePolicy, ReplicationGroupId,
+};
+use crate::shared::replication::entity_map::ToRemoteMapper;
 use crate::shared::replication::receive::ReplicationReceiver;
 use crate::shared::replication::send::ReplicationSender;
 use crate::shared::replication::ReplicationMessage;
@@ -61,13 +75,22 @@ pub struct ConnectionManager<P: Protocol> {
     pub(crate) message_manager: MessageManager,
     pub(crate) replication_sender: ReplicationSender<P>,
     pub(crate) replication_receiver: ReplicationReceiver<P>,
+    /// Entities that the server gave us the authority over. They are replicated to the server with the
+    /// server's entity ids.
+    pub(crate) authority: EntityHashSet,
+    /// Authority messages received from the server that could not be applied yet
+    pub(crate) pending_authority: Vec<AuthorityMessage>,
     pub(crate) events: ConnectionEvents<P>,
+    /// Handle to return for the next message sent by the user
+    next_message_handle: MessageHandle,
 
     pub(crate) ping_manager: PingManager,
     pub(crate) sync_manager: SyncManager,
     // TODO: maybe don't do any replication until connection is synced?
     // track if we are connected or not
     pub(crate) is_connected: bool,
+    /// Reason of the last disconnection, used to emit a single event when a connection attempt fails
+    pub(crate) disconnect_reason: Option<DisconnectReason>,
 }
 
 impl<P: Protocol> ConnectionManager<P> {
@@ -77,9 +100,14 @@ impl<P: Protocol> ConnectionManager<P> {
         sync_config: SyncConfig,
         ping_config: PingConfig,
         input_delay_ticks: u16,
+        reliable_transport: bool,
     ) -> Self {
         // create the message manager and the channels
-        let mut message_manager = MessageManager::new(channel_registry, packet_config.into());
+        let mut message_manager =
+            MessageManager::new(channel_registry, packet_config.clone().into())
+                .with_mtu_discovery(packet_config.mtu)
+                .with_congestion_control(packet_config.congestion)
+                .with_reliable_transport(reliable_transport);
         // get the acks-tracker for entity updates
         let update_acks_tracker = message_manager
             .channels
@@ -97,10 +125,14 @@ impl<P: Protocol> ConnectionManager<P> {
             message_manager,
             replication_sender,
             replication_receiver,
+            authority: EntityHashSet::default(),
+            pending_authority: vec![],
             ping_manager: PingManager::new(ping_config),
             sync_manager: SyncManager::new(sync_config, input_delay_ticks),
             events: ConnectionEvents::default(),
+            next_message_handle: MessageHandle::default(),
             is_connected: false,
+            disconnect_reason: None,
         }
     }
 
@@ -110,6 +142,29 @@ impl<P: Protocol> ConnectionManager<P> {
         self.sync_manager.is_synced()
     }
 
+    /// Largest packet size (in bytes) that can currently be sent to the server, as discovered by
+    /// the path MTU discovery (see [`MtuConfig`](crate::packet::mtu::MtuConfig))
+    pub fn mtu(&self) -> usize {
+        self.message_manager.mtu()
+    }
+
+    /// Estimate of the bandwidth (in bytes per second) available to send messages to the server,
+    /// if congestion control is enabled (see [`CongestionConfig`](crate::packet::congestion::CongestionConfig))
+    pub fn bandwidth(&self) -> Option<u32> {
+        self.message_manager.bandwidth()
+    }
+
+    /// Statistics (messages and bytes sent and received, resends, time-to-ack, etc.) of each channel
+    /// of the connection to the server
+    pub fn network_stats(&self) -> &NetworkStats {
+        self.message_manager.stats()
+    }
+
+    /// Statistics of the channel `C` of the connection to the server
+    pub fn channel_stats<C: Channel>(&self) -> ChannelStats {
+        self.message_manager.stats().channel(ChannelKind::of::<C>())
+    }
+
     pub(crate) fn received_new_server_tick(&self) -> bool {
         self.sync_manager.duration_since_latest_received_server_tick == Duration::default()
     }
@@ -137,7 +192,13 @@ impl<P: Protocol> ConnectionManager<P> {
     }
 
     /// Send a message to the server
-    pub fn send_message<C: Channel, M: Message>(&mut self, message: M) -> Result<()>
+    ///
+    /// Returns a [`MessageHandle`] that identifies the message in the
+    /// [`MessageDeliveredEvent`](crate::client::events::MessageDeliveredEvent) and
+    /// [`MessageLostEvent`](crate::client::events::MessageLostEvent) events.
+    /// These events are only emitted for messages sent on reliable channels or on
+    /// [`UnorderedUnreliableWithAcks`](crate::channel::builder::ChannelMode::UnorderedUnreliableWithAcks) channels.
+    pub fn send_message<C: Channel, M: Message>(&mut self, message: M) -> Result<MessageHandle>
     where
         P::Message: From<M>,
     {
@@ -148,11 +209,13 @@ impl<P: Protocol> ConnectionManager<P> {
     }
 
     /// Send a message to the server, the message should be re-broadcasted according to the `target`
+    ///
+    /// The returned [`MessageHandle`] tracks the delivery of the message to the server.
     pub fn send_message_to_target<C: Channel, M: Message>(
         &mut self,
         message: M,
         target: NetworkTarget,
-    ) -> Result<()>
+    ) -> Result<MessageHandle>
     where
         P::Message: From<M>,
     {
@@ -160,12 +223,176 @@ impl<P: Protocol> ConnectionManager<P> {
         self.buffer_message(message.into(), channel, target)
     }
 
+    /// Cancel a transfer that we are sending to the server on a [`Bulk`](crate::channel::builder::ChannelMode::Bulk)
+    /// channel. The server is notified that the transfer was cancelled.
+    ///
+    /// Returns an error if the transfer was already completed or cancelled.
+    pub fn cancel_transfer(&mut self, handle: MessageHandle) -> Result<()> {
+        self.message_manager.cancel_transfer(handle)
+    }
+
+    /// Cancel the transfer that we are currently receiving from the server on the
+    /// [`Bulk`](crate::channel::builder::ChannelMode::Bulk) channel `C`.
+    /// The server is notified that the transfer was cancelled.
+    pub fn cancel_incoming_transfer<C: Channel>(&mut self) -> Result<()> {
+        self.message_manager
+            .cancel_incoming_transfer(ChannelKind::of::<C>())
+    }
+
+    /// Ask the server for the authority over a confirmed entity that the server replicates to us.
+    ///
+    /// The server receives an [`AuthorityEvent`](crate::server::events::AuthorityEvent) and decides
+    /// whether to grant us the authority.
+    pub fn request_authority(&mut self, entity: Entity) -> Result<()> {
+        let remote_entity = *self
+            .replication_receiver
+            .remote_entity_map
+            .get_remote(entity)
+            .context("the entity was not replicated from the server")?;
+        let message = ClientMessage::<P>::Authority(AuthorityMessage::Request(remote_entity));
+        message.emit_send_logs("AuthorityChannel");
+        self.message_manager
+            .buffer_send(message, ChannelKind::of::<AuthorityChannel>())?;
+        Ok(())
+    }
+
+    /// Whether the server gave us the authority over the entity
+    pub fn has_authority(&self, entity: Entity) -> bool {
+        self.authority.contains(&entity)
+    }
+
+    /// Apply the authority changes received from the server
+    fn apply_authority_messages(&mut self, world: &mut World, tick: Tick) {
+        // the entities that were despawned by the server are not ours anymore
+        self.authority
+            .retain(|entity| world.get_entity(*entity).is_some());
+        for message in std::mem::take(&mut self.pending_authority) {
+            let Some(local_entity) = self
+                .replication_receiver
+                .remote_entity_map
+                .get_local(message.entity())
+                .copied()
+            else {
+                match message {
+                    // the server can grant us the authority over an entity whose spawn we haven't received yet
+                    AuthorityMessage::Grant { .. } => self.pending_authority.push(message),
+                    AuthorityMessage::Revoke(entity) => self
+                        .pending_authority
+                        .retain(|pending| pending.entity() != entity),
+                    AuthorityMessage::Request(_) => {}
+                }
+                continue;
+            };
+            match message {
+                AuthorityMessage::Grant { group_id, .. } => {
+                    let Some(mut entity_mut) = world.get_entity_mut(local_entity) else {
+                        continue;
+                    };
+                    // the entity is not a confirmed entity anymore, which despawns its predicted/interpolated entities
+                    entity_mut.remove::<(Confirmed, ShouldBePredicted, ShouldBeInterpolated)>();
+                    entity_mut.insert((
+                        HasAuthority,
+                        Replicate::<P> {
+                            replication_group: ReplicationGroup::new_id(group_id.0),
+                            replicate_hierarchy: false,
+                            ..Default::default()
+                        },
+                    ));
+                    self.authority.insert(local_entity);
+                    self.events
+                        .push_authority_change(local_entity, AuthorityChange::Granted);
+                }
+                AuthorityMessage::Revoke(_) => {
+                    if !self.authority.remove(&local_entity) {
+                        continue;
+                    }
+                    // the server re-sends the prediction/interpolation markers, which makes the entity
+                    // a confirmed entity again
+                    // (the server already knows that we stop replicating the entity, so we don't
+                    // apply the `ReplicateRemovePolicy` when `Replicate` is removed)
+                    self.replication_sender
+                        .replicate_component_cache
+                        .remove(&local_entity);
+                    // the entity is confirmed again even if it has no predicted/interpolated entity
+                    let confirmed_tick = self
+                        .replication_receiver
+                        .get_confirmed_tick(local_entity)
+                        .unwrap_or(tick);
+                    if let Some(mut entity_mut) = world.get_entity_mut(local_entity) {
+                        entity_mut.remove::<(HasAuthority, Replicate<P>)>();
+                        entity_mut.insert(Confirmed {
+                            predicted: None,
+                            interpolated: None,
+                            tick: confirmed_tick,
+                        });
+                    }
+                    self.events
+                        .push_authority_change(local_entity, AuthorityChange::Revoked);
+                }
+                AuthorityMessage::Request(_) => {
+                    warn!(
+                        ?message,
+                        "Received an authority message that only a client can send"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Replace the ids of the entities that we have authority over by the server's ids, since the server
+    /// applies our replication messages for these entities directly to its own entities
+    fn map_authority_entities(
+        &self,
+        message_data: &mut ReplicationMessageData<P::Components, P::ComponentKinds>,
+    ) {
+        let to_remote = |entity: &mut Entity| {
+            if !self.authority.contains(&*entity) {
+                return true;
+            }
+            match self
+                .replication_receiver
+                .remote_entity_map
+                .get_remote(*entity)
+            {
+                Some(remote_entity) => {
+                    *entity = *remote_entity;
+                    true
+                }
+                // the entity was despawned by the server
+                None => false,
+            }
+        };
+        match message_data {
+            ReplicationMessageData::Actions(m) => m.actions.retain_mut(|(entity, actions)| {
+                // the entity already exists on the server
+                if self.authority.contains(&*entity) {
+                    actions.spawn = false;
+                }
+                to_remote(entity)
+            }),
+            ReplicationMessageData::Updates(m) => {
+                m.updates.retain_mut(|(entity, _)| to_remote(entity));
+                m.deltas.retain_mut(|(entity, _)| to_remote(entity));
+            }
+        }
+    }
+
+    /// The components of the entities that we have authority over are applied by the server to its own entities,
+    /// so the entities that they reference must also be the server's entities
+    fn map_authority_component(&self, entity: Entity, component: &mut P::Components) {
+        if self.authority.contains(&entity) {
+            component.map_entities(&mut ToRemoteMapper(
+                &self.replication_receiver.remote_entity_map,
+            ));
+        }
+    }
+
     pub(crate) fn buffer_message(
         &mut self,
         message: P::Message,
         channel: ChannelKind,
         target: NetworkTarget,
-    ) -> Result<()> {
+    ) -> Result<MessageHandle> {
         // TODO: i know channel names never change so i should be able to get them as static
         // TODO: just have a channel registry enum as well?
         let channel_name = self
@@ -176,8 +403,10 @@ impl<P: Protocol> ConnectionManager<P> {
             .to_string();
         let message = ClientMessage::<P>::Message(message, target);
         message.emit_send_logs(&channel_name);
-        self.message_manager.buffer_send(message, channel)?;
-        Ok(())
+        let handle = self.next_message_handle.next();
+        self.message_manager
+            .buffer_send_tracked(message, channel, handle)?;
+        Ok(handle)
     }
 
     pub(crate) fn buffer_replication_messages(
@@ -196,7 +425,10 @@ impl<P: Protocol> ConnectionManager<P> {
         self.replication_sender
             .finalize(tick)
             .into_iter()
-            .try_for_each(|(channel, group_id, message_data, priority)| {
+            .try_for_each(|(channel, group_id, mut message_data, priority)| {
+                if !self.authority.is_empty() {
+                    self.map_authority_entities(&mut message_data);
+                }
                 let should_track_ack = matches!(message_data, ReplicationMessageData::Updates(_));
                 let channel_name = self
                     .message_manager
@@ -221,17 +453,24 @@ impl<P: Protocol> ConnectionManager<P> {
                     self.replication_sender
                         .updates_message_id_to_group_id
                         .insert(message_id, (group_id, bevy_tick));
+                    self.replication_sender
+                        .delta
+                        .track_message(group_id, message_id);
                 }
                 Ok(())
             })
     }
 
     /// Send packets that are ready to be sent
+    ///
+    /// Returns the (unreliable, reliable) payloads. If `split_by_reliability` is false, all the payloads are
+    /// returned in the first list and may contain messages from both reliable and unreliable channels.
     pub(crate) fn send_packets(
         &mut self,
         time_manager: &TimeManager,
         tick_manager: &TickManager,
-    ) -> Result<Vec<Payload>> {
+        split_by_reliability: bool,
+    ) -> Result<(Vec<Payload>, Vec<Payload>)> {
         // update the ping manager with the actual send time
         // TODO: issues here: we would like to send the ping/pong messages immediately, otherwise the recorded current time is incorrect
         //   - can give infinity priority to this channel?
@@ -263,7 +502,20 @@ impl<P: Protocol> ConnectionManager<P> {
                     Ok::<(), anyhow::Error>(())
                 })?;
         }
-        let payloads = self.message_manager.send_packets(tick_manager.tick());
+        // flow control and cancellation of the bulk transfers
+        for control in self.message_manager.take_bulk_control_messages() {
+            let message = ClientMessage::<P>::Bulk(control);
+            let channel = ChannelKind::of::<BulkControlChannel>();
+            self.message_manager.buffer_send(message, channel)?;
+        }
+        let payloads = if split_by_reliability {
+            self.message_manager
+                .send_packets_by_reliability(tick_manager.tick())
+        } else {
+            self.message_manager
+                .send_packets(tick_manager.tick())
+                .map(|payloads| (payloads, vec![]))
+        };
 
         // update the replication sender about which messages were actually sent, and accumulate priority
         self.replication_sender.recv_send_notification();
@@ -327,6 +579,16 @@ impl<P: Protocol> ConnectionManager<P> {
                                 }
                             }
                         }
+                        ServerMessage::Bulk(control) => {
+                            if let Err(e) =
+                                self.message_manager.receive_bulk_control_message(control)
+                            {
+                                warn!("could not handle bulk control message: {:?}", e);
+                            }
+                        }
+                        ServerMessage::Authority(message) => {
+                            self.pending_authority.push(message);
+                        }
                     }
                 }
             }
@@ -355,6 +617,33 @@ impl<P: Protocol> ConnectionManager<P> {
                     });
             }
         }
+        self.apply_authority_messages(world, tick_manager.tick());
+
+        // delivery status of the messages that we sent
+        for (handle, channel_kind) in self.message_manager.take_delivered_messages() {
+            self.events.push_message_delivered(handle, channel_kind);
+        }
+        for (handle, channel_kind) in self.message_manager.take_lost_messages() {
+            self.events.push_message_lost(handle, channel_kind);
+        }
+        for (handle, channel_kind, acked_bytes, total_bytes) in
+            self.message_manager.take_transfer_progress()
+        {
+            self.events.push_transfer_progress(
+                handle,
+                channel_kind,
+                TransferProgress {
+                    acked_bytes,
+                    total_bytes,
+                },
+            );
+        }
+        for (handle, channel_kind) in self.message_manager.take_cancelled_transfers() {
+            self.events.push_transfer_cancelled(handle, channel_kind);
+        }
+        for (channel_kind, error) in self.message_manager.take_rejected_messages() {
+            self.events.push_message_rejected(channel_kind, error);
+        }
 
         // TODO: do i really need this? I could just create events in this function directly?
         //  why do i need to make events a field of the connection?
@@ -459,14 +748,32 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         Ok(())
     }
 
+    fn prepare_replication_stop(
+        &mut self,
+        entity: Entity,
+        replicate: &Replicate<P>,
+        target: NetworkTarget,
+        system_current_tick: BevyTick,
+    ) -> Result<()> {
+        let policy = replicate.remove_policy;
+        if policy == ReplicateRemovePolicy::Despawn {
+            self.prepare_entity_despawn(entity, replicate, target, system_current_tick)?;
+        }
+        let group_id = replicate.replication_group.group_id(Some(entity));
+        self.replication_sender
+            .prepare_replication_stop(entity, group_id, policy);
+        Ok(())
+    }
+
     fn prepare_component_insert(
         &mut self,
         entity: Entity,
-        component: P::Components,
+        mut component: P::Components,
         replicate: &Replicate<P>,
         target: NetworkTarget,
         system_current_tick: BevyTick,
     ) -> Result<()> {
+        self.map_authority_component(entity, &mut component);
         let group_id = replicate.replication_group.group_id(Some(entity));
         let kind: P::ComponentKinds = (&component).into();
         // debug!(
@@ -509,12 +816,13 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
     fn prepare_entity_update(
         &mut self,
         entity: Entity,
-        component: P::Components,
+        mut component: P::Components,
         replicate: &Replicate<P>,
         target: NetworkTarget,
         component_change_tick: BevyTick,
         system_current_tick: BevyTick,
     ) -> Result<()> {
+        self.map_authority_component(entity, &mut component);
         let kind: P::ComponentKinds = (&component).into();
         let group_id = replicate.group_id(Some(entity));
         // TODO: should we have additional state tracking so that we know we are in the process of sending this entity to clients?
@@ -585,5 +893,8 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
                 }
             }
         }
+        // stop using old values as baselines for delta compression
+        self.replication_sender.delta.cleanup(tick);
+        self.replication_receiver.delta.cleanup(tick);
     }
 }
@@ -1,8 +1,10 @@
 use bevy::app::{App, Plugin, PostUpdate};
 use bevy::diagnostic::Diagnostics;
-use bevy::prelude::{Real, Res, ResMut, Time};
+use bevy::prelude::{Local, Real, Res, ResMut, Time};
 
+use crate::client::connection::ConnectionManager;
 use crate::connection::client::{ClientConnection, NetClient};
+use crate::packet::channel_stats::{ChannelDiagnostics, NetworkStats};
 use crate::prelude::Protocol;
 use crate::transport::io::IoDiagnosticsPlugin;
 
@@ -27,9 +29,36 @@ fn io_diagnostics_system(
         IoDiagnosticsPlugin::update_diagnostics(&mut io.stats, &time, &mut diagnostics);
     }
 }
+
+fn channel_diagnostics_system<P: Protocol>(
+    connection: Res<ConnectionManager<P>>,
+    time: Res<Time<Real>>,
+    mut previous_stats: Local<NetworkStats>,
+    mut diagnostics: Diagnostics,
+) {
+    ChannelDiagnostics::update_diagnostics(
+        "client",
+        connection.network_stats(),
+        &mut previous_stats,
+        &connection.message_manager.channel_registry,
+        &time,
+        &mut diagnostics,
+    );
+}
+
 impl<P: Protocol> Plugin for ClientDiagnosticsPlugin<P> {
     fn build(&self, app: &mut App) {
+        let channel_registry = app
+            .world
+            .resource::<ConnectionManager<P>>()
+            .message_manager
+            .channel_registry
+            .clone();
+        ChannelDiagnostics::register(app, "client", &channel_registry);
         app.add_plugins(IoDiagnosticsPlugin);
-        app.add_systems(PostUpdate, io_diagnostics_system);
+        app.add_systems(
+            PostUpdate,
+            (io_diagnostics_system, channel_diagnostics_system::<P>),
+        );
     }
 }
@@ -79,3 +79,19 @@ pub type ComponentInsertEvent<C> = crate::shared::events::components::ComponentI
 pub type ComponentRemoveEvent<C> = crate::shared::events::components::ComponentRemoveEvent<C, ()>;
 /// Bevy [`Event`] emitted on the client when a (non-replication) message is received
 pub type MessageEvent<M> = crate::shared::events::components::MessageEvent<M, ()>;
+/// Bevy [`Event`] emitted on the client when a message sent to the server was acked by the server
+pub type MessageDeliveredEvent = crate::shared::events::components::MessageDeliveredEvent<()>;
+/// Bevy [`Event`] emitted on the client when a message sent to the server was lost
+/// (on an unreliable channel, or after its time-to-live expired on a reliable channel)
+pub type MessageLostEvent = crate::shared::events::components::MessageLostEvent<()>;
+/// Bevy [`Event`] emitted on the client when more of a transfer sent to the server on a bulk channel was acked
+pub type TransferProgressEvent = crate::shared::events::components::TransferProgressEvent<()>;
+/// Bevy [`Event`] emitted on the client when the server cancelled a transfer on a bulk channel
+pub type TransferCancelledEvent = crate::shared::events::components::TransferCancelledEvent<()>;
+/// Bevy [`Event`] emitted on the client when a message from the server was dropped because it did not respect
+/// the limits of its channel
+pub type MessageRejectedEvent = crate::shared::events::components::MessageRejectedEvent<()>;
+/// Bevy [`Event`] emitted on the client when the server granted or revoked the authority over an entity
+pub type AuthorityEvent = crate::shared::events::components::AuthorityEvent<()>;
+/// Bevy [`Event`] emitted on the client when the server stopped replicating an entity
+pub type ReplicationStopEvent = crate::shared::events::components::ReplicationStopEvent<()>;
@@ -320,11 +320,9 @@ fn prepare_input_message<P: Protocol>(
             "sending input message: {:?}",
             message.end_tick
         );
-        connection
-            .send_message::<InputChannel, _>(message)
-            .unwrap_or_else(|err| {
-                error!("Error while sending input message: {:?}", err);
-            })
+        if let Err(err) = connection.send_message::<InputChannel, _>(message) {
+            error!("Error while sending input message: {:?}", err);
+        }
     }
     // NOTE: actually we keep the input values! because they might be needed when we rollback for client prediction
     // TODO: figure out when we can delete old inputs. Basically when the oldest prediction group tick has passed?
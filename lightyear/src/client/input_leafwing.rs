@@ -694,11 +694,9 @@ fn prepare_input_message<P: Protocol, A: LeafwingUserAction>(
             "sending input message: {:?}",
             message.diffs
         );
-        connection
-            .send_message::<InputChannel, InputMessage<A>>(message)
-            .unwrap_or_else(|err| {
-                error!("Error while sending input message: {:?}", err);
-            })
+        if let Err(err) = connection.send_message::<InputChannel, InputMessage<A>>(message) {
+            error!("Error while sending input message: {:?}", err);
+        }
     }
 
     // NOTE: actually we keep the input values! because they might be needed when we rollback for client prediction
@@ -949,6 +947,7 @@ mod tests {
             incoming_latency: Duration::from_millis(0),
             incoming_jitter: Duration::from_millis(0),
             incoming_loss: 0.0,
+            ..Default::default()
         };
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
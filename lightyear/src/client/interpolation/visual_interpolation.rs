@@ -220,6 +220,7 @@ mod tests {
             incoming_latency: Duration::from_millis(0),
             incoming_jitter: Duration::from_millis(0),
             incoming_loss: 0.0,
+            ..Default::default()
         };
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
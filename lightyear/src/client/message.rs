@@ -6,9 +6,11 @@ use bitcode::encoding::Fixed;
 use bitcode::{Decode, Encode};
 
 use crate::_reexport::{BitSerializable, MessageProtocol, ReadBuffer, WriteBuffer};
+use crate::channel::bulk::BulkControlMessage;
 use crate::prelude::{ChannelKind, NetworkTarget};
 use crate::protocol::Protocol;
 use crate::shared::ping::message::SyncMessage;
+use crate::shared::replication::authority::AuthorityMessage;
 use crate::shared::replication::{ReplicationMessage, ReplicationMessageData};
 
 pub(crate) struct MessageMetadata {
@@ -29,6 +31,13 @@ pub enum ClientMessage<P: Protocol> {
     // the reason why we include sync here instead of doing another MessageManager is so that
     // the sync messages can be added to packets that have other messages
     Sync(SyncMessage),
+    // flow control and cancellation of the transfers of the bulk channels
+    #[bitcode_hint(frequency = 1)]
+    Bulk(BulkControlMessage),
+    // transfer of the authority over replicated entities
+    #[bitcode_hint(frequency = 1)]
+    #[bitcode(with_serde)]
+    Authority(AuthorityMessage),
 }
 
 impl<P: Protocol> BitSerializable for ClientMessage<P> {
@@ -140,6 +149,12 @@ impl<P: Protocol> ClientMessage<P> {
                     metrics::counter!("send_pong", "channel" => channel_name).increment(1);
                 }
             },
+            ClientMessage::Bulk(message) => {
+                trace!(channel = ?channel_name, ?message, "Sending bulk control message");
+            }
+            ClientMessage::Authority(message) => {
+                trace!(channel = ?channel_name, ?message, "Sending authority message");
+            }
         }
     }
 }
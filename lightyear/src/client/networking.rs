@@ -4,20 +4,28 @@ use std::ops::DerefMut;
 use bevy::ecs::system::{RunSystemOnce, SystemChangeTick, SystemState};
 use bevy::prelude::ResMut;
 use bevy::prelude::*;
-use tracing::{error, trace};
+use tracing::{error, trace, warn};
 
 use crate::_reexport::{ClientMarker, ReplicationSend};
 use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
-use crate::client::events::{ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent};
+use crate::client::events::{
+    AuthorityEvent, ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent,
+    MessageDeliveredEvent, MessageLostEvent, MessageRejectedEvent, ReplicationStopEvent,
+    TransferCancelledEvent, TransferProgressEvent,
+};
 use crate::client::sync::SyncSet;
-use crate::connection::client::{ClientConnection, NetClient};
-use crate::prelude::{SharedConfig, TickManager, TimeManager};
+use crate::connection::client::{ClientConnection, DisconnectReason, NetClient};
+use crate::prelude::{LinkConditionerSettings, SharedConfig, TickManager, TimeManager};
 use crate::protocol::component::ComponentProtocol;
 use crate::protocol::message::MessageProtocol;
 use crate::protocol::Protocol;
 use crate::shared::config::Mode;
-use crate::shared::events::connection::{IterEntityDespawnEvent, IterEntitySpawnEvent};
+use crate::shared::events::connection::{
+    IterAuthorityEvent, IterEntityDespawnEvent, IterEntitySpawnEvent, IterMessageDeliveredEvent,
+    IterMessageLostEvent, IterMessageRejectedEvent, IterReplicationStopEvent,
+    IterTransferCancelledEvent, IterTransferProgressEvent,
+};
 use crate::shared::sets::InternalMainSet;
 use crate::shared::tick_manager::TickEvent;
 use crate::shared::time_manager::is_client_ready_to_send;
@@ -67,7 +75,12 @@ impl<P: Protocol> Plugin for ClientNetworkingPlugin<P> {
             // SYSTEMS
             .add_systems(
                 PreUpdate,
-                receive::<P>.in_set(InternalMainSet::<ClientMarker>::Receive),
+                (
+                    update_link_conditioner
+                        .run_if(resource_exists_and_changed::<LinkConditionerSettings>)
+                        .before(InternalMainSet::<ClientMarker>::Receive),
+                    receive::<P>.in_set(InternalMainSet::<ClientMarker>::Receive),
+                ),
             )
             .add_systems(
                 PostUpdate,
@@ -166,14 +179,25 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                                 tick_manager.as_ref(),
                                             );
                                         } else {
-                                            // push an event indicating that we just disconnected
-                                            if connection.is_connected {
+                                            let disconnect_reason = netcode.disconnect_reason();
+                                            // push an event indicating that we just disconnected, or that
+                                            // the connection attempt failed
+                                            if connection.is_connected
+                                                || (disconnect_reason.is_some()
+                                                    && disconnect_reason != connection.disconnect_reason)
+                                            {
                                                 let mut disconnect_event_writer =
                                                     world.get_resource_mut::<Events<DisconnectEvent>>().unwrap();
-                                                debug!("Client disconnected event");
-                                                disconnect_event_writer.send(DisconnectEvent::new(()));
+                                                if disconnect_reason == Some(DisconnectReason::ProtocolMismatch) {
+                                                    error!("The server uses a different protocol than the client");
+                                                }
+                                                debug!(reason = ?disconnect_reason, "Client disconnected event");
+                                                disconnect_event_writer.send(
+                                                    DisconnectEvent::new(()).with_reason(disconnect_reason),
+                                                );
                                                 connection.is_connected = false;
                                             }
+                                            connection.disconnect_reason = disconnect_reason;
                                         }
 
                                         // RECV PACKETS: buffer packets into message managers
@@ -217,6 +241,74 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                                         .send(EntityDespawnEvent::new(entity, ()));
                                                 }
                                             }
+                                            // MessageDelivered/MessageLost events
+                                            if events.has_message_delivered() {
+                                                let mut message_delivered_event_writer = world
+                                                    .get_resource_mut::<Events<MessageDeliveredEvent>>()
+                                                    .unwrap();
+                                                for (handle, channel, _) in events.into_iter_message_delivered() {
+                                                    message_delivered_event_writer
+                                                        .send(MessageDeliveredEvent::new(handle, channel, ()));
+                                                }
+                                            }
+                                            if events.has_message_lost() {
+                                                let mut message_lost_event_writer = world
+                                                    .get_resource_mut::<Events<MessageLostEvent>>()
+                                                    .unwrap();
+                                                for (handle, channel, _) in events.into_iter_message_lost() {
+                                                    message_lost_event_writer
+                                                        .send(MessageLostEvent::new(handle, channel, ()));
+                                                }
+                                            }
+                                            // TransferProgress/TransferCancelled events
+                                            if events.has_transfer_progress() {
+                                                let mut transfer_progress_event_writer = world
+                                                    .get_resource_mut::<Events<TransferProgressEvent>>()
+                                                    .unwrap();
+                                                for (handle, channel, progress, _) in events.into_iter_transfer_progress() {
+                                                    transfer_progress_event_writer
+                                                        .send(TransferProgressEvent::new(handle, channel, progress, ()));
+                                                }
+                                            }
+                                            if events.has_transfer_cancelled() {
+                                                let mut transfer_cancelled_event_writer = world
+                                                    .get_resource_mut::<Events<TransferCancelledEvent>>()
+                                                    .unwrap();
+                                                for (handle, channel, _) in events.into_iter_transfer_cancelled() {
+                                                    transfer_cancelled_event_writer
+                                                        .send(TransferCancelledEvent::new(handle, channel, ()));
+                                                }
+                                            }
+                                            // MessageRejected events
+                                            if events.has_message_rejected() {
+                                                let mut message_rejected_event_writer = world
+                                                    .get_resource_mut::<Events<MessageRejectedEvent>>()
+                                                    .unwrap();
+                                                for (channel, error, _) in events.into_iter_message_rejected() {
+                                                    message_rejected_event_writer
+                                                        .send(MessageRejectedEvent::new(channel, error, ()));
+                                                }
+                                            }
+                                            // Authority events
+                                            if events.has_authority() {
+                                                let mut authority_event_writer = world
+                                                    .get_resource_mut::<Events<AuthorityEvent>>()
+                                                    .unwrap();
+                                                for (entity, change, _) in events.into_iter_authority() {
+                                                    authority_event_writer
+                                                        .send(AuthorityEvent::new(entity, change, ()));
+                                                }
+                                            }
+                                            // ReplicationStop events
+                                            if events.has_replication_stop() {
+                                                let mut replication_stop_event_writer = world
+                                                    .get_resource_mut::<Events<ReplicationStopEvent>>()
+                                                    .unwrap();
+                                                for (entity, policy, _) in events.into_iter_replication_stop() {
+                                                    replication_stop_event_writer
+                                                        .send(ReplicationStopEvent::new(entity, policy, ()));
+                                                }
+                                            }
 
                                             // Update component events (updates, inserts, removes)
                                             P::Components::push_component_events(
@@ -235,6 +327,21 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
     );
 }
 
+/// Apply the [`LinkConditionerSettings`] to the client's io whenever they are modified
+///
+/// Connections that don't use an [`Io`](crate::transport::io::Io) (Steam, QUIC, local) can't be updated at runtime.
+pub(crate) fn update_link_conditioner(
+    settings: Res<LinkConditionerSettings>,
+    mut netclient: ResMut<ClientConnection>,
+) {
+    match netclient.io_mut() {
+        Some(io) => io.set_conditioner(settings.0.clone()),
+        None => warn!(
+            "the link conditioner of the client connection cannot be updated at runtime, the new settings are ignored"
+        ),
+    }
+}
+
 pub(crate) fn send<P: Protocol>(
     mut netcode: ResMut<ClientConnection>,
     system_change_tick: SystemChangeTick,
@@ -250,14 +357,29 @@ pub(crate) fn send<P: Protocol>(
             error!("Error preparing replicate send: {}", e);
         });
     // SEND_PACKETS: send buffered packets to io
-    let packet_bytes = connection
-        .send_packets(time_manager.as_ref(), tick_manager.as_ref())
+    let (packet_bytes, reliable_packet_bytes) = connection
+        .send_packets(
+            time_manager.as_ref(),
+            tick_manager.as_ref(),
+            netcode.supports_reliable_send(),
+        )
         .unwrap();
     for packet_byte in packet_bytes {
         let _ = netcode.send(packet_byte.as_slice()).map_err(|e| {
             error!("Error sending packet: {}", e);
         });
     }
+    for packet_byte in reliable_packet_bytes {
+        let _ = netcode.send_reliable(packet_byte.as_slice()).map_err(|e| {
+            error!("Error sending packet: {}", e);
+        });
+    }
+    // send the packets buffered by the transport
+    if let Some(io) = netcode.io_mut() {
+        let _ = io.flush().map_err(|e| {
+            error!("Error sending packets: {}", e);
+        });
+    }
 
     // no need to clear the connection, because we already std::mem::take it
     // client.connection.clear();
@@ -13,7 +13,7 @@ use crate::client::networking::ClientNetworkingPlugin;
 use crate::client::prediction::plugin::PredictionPlugin;
 use crate::client::replication::{ClientReplicationPlugin, ReplicationConfig};
 use crate::client::sync::SyncConfig;
-use crate::connection::client::{ClientConnection, NetConfig};
+use crate::connection::client::{ClientConnection, NetClient, NetConfig};
 use crate::protocol::component::ComponentProtocol;
 use crate::protocol::message::MessageProtocol;
 use crate::protocol::Protocol;
@@ -66,7 +66,21 @@ impl<P: Protocol> Plugin for ClientPlugin<P> {
             .register_type::<ReplicationConfig>();
 
         let config = self.config.lock().unwrap().deref_mut().take().unwrap();
-        let netclient = config.client_config.net.clone().build_client();
+        let netclient = config
+            .client_config
+            .net
+            .clone()
+            .with_protocol_fingerprint(config.protocol.fingerprint())
+            .with_mtu_discovery(config.client_config.packet.mtu.enabled);
+        // the path MTU can only be discovered if the transport does not fragment the probes
+        let mut packet_config = config.client_config.packet.clone();
+        packet_config.mtu.enabled &= netclient.supports_mtu_discovery();
+        // the packets must leave room for the header added by the transport
+        packet_config.mtu = packet_config
+            .mtu
+            .reserve_header_bytes(netclient.transport_header_bytes());
+        let netclient = netclient.build_client();
+        let reliable_transport = netclient.supports_reliable_send();
 
         // in this mode, the server acts as a client
         if config.client_config.shared.mode == Mode::HostServer {
@@ -83,10 +97,11 @@ impl<P: Protocol> Plugin for ClientPlugin<P> {
             .insert_resource(config.client_config.clone())
             .insert_resource(ConnectionManager::<P>::new(
                 config.protocol.channel_registry(),
-                config.client_config.packet,
+                packet_config,
                 config.client_config.sync,
                 config.client_config.ping,
                 config.client_config.prediction.input_delay_ticks,
+                reliable_transport,
             ))
             // PLUGINS //
             .add_plugins(ClientNetworkingPlugin::<P>::default())
@@ -9,12 +9,28 @@ use crate::client::config::NetcodeConfig;
 use crate::connection::id::ClientId;
 use crate::connection::netcode::ConnectToken;
 
+#[cfg(all(feature = "quic", not(target_family = "wasm")))]
+use crate::connection::quic::client::QuicConfig;
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 use crate::connection::steam::client::SteamConfig;
 use crate::packet::packet::Packet;
 
 use crate::prelude::{generate_key, Io, IoConfig, Key, LinkConditionerConfig};
 
+/// Reason why the client was disconnected from the server, or could not connect to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The server uses a [`Protocol`](crate::protocol::Protocol) with a different fingerprint
+    /// (the channels, messages or components are different)
+    ProtocolMismatch,
+    /// The server denied the connection request, most likely because it is full
+    ConnectionDenied,
+    /// The connect token has expired
+    TokenExpired,
+    /// The server did not respond in time
+    TimedOut,
+}
+
 // TODO: add diagnostics methods?
 pub trait NetClient: Send + Sync {
     // type Error;
@@ -28,6 +44,12 @@ pub trait NetClient: Send + Sync {
     /// Returns true if the client is connected to the server
     fn is_connected(&self) -> bool;
 
+    /// Returns the reason why the client is disconnected, if the connection was lost or
+    /// the connection attempt failed
+    fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        None
+    }
+
     /// Update the connection state + internal bookkeeping (keep-alives, etc.)
     fn try_update(&mut self, delta_ms: f64) -> Result<()>;
 
@@ -37,6 +59,18 @@ pub trait NetClient: Send + Sync {
     /// Send a packet to the server
     fn send(&mut self, buf: &[u8]) -> Result<()>;
 
+    /// Returns true if the connection has a reliable transport available (for example QUIC streams).
+    /// In that case, the packets that only contain messages from reliable channels are sent with
+    /// [`send_reliable`](NetClient::send_reliable)
+    fn supports_reliable_send(&self) -> bool {
+        false
+    }
+
+    /// Send a packet to the server using the reliable transport of the connection
+    fn send_reliable(&mut self, buf: &[u8]) -> Result<()> {
+        self.send(buf)
+    }
+
     /// Get the id of the client
     fn id(&self) -> ClientId;
 
@@ -74,6 +108,14 @@ pub enum NetConfig {
         config: SteamConfig,
         conditioner: Option<LinkConditionerConfig>,
     },
+    /// Connect to the server using QUIC directly, without netcode.
+    ///
+    /// Reliable channels are sent on QUIC streams, and unreliable channels as QUIC datagrams
+    #[cfg(all(feature = "quic", not(target_family = "wasm")))]
+    Quic {
+        #[reflect(ignore)]
+        config: QuicConfig,
+    },
     Local {
         id: u64,
     },
@@ -90,6 +132,48 @@ impl Default for NetConfig {
 }
 
 impl NetConfig {
+    /// Set the fingerprint of the protocol that is sent to the server during the handshake
+    pub(crate) fn with_protocol_fingerprint(mut self, protocol_fingerprint: u64) -> Self {
+        match &mut self {
+            NetConfig::Netcode { config, .. } => {
+                config.protocol_fingerprint = protocol_fingerprint;
+            }
+            #[cfg(all(feature = "quic", not(target_family = "wasm")))]
+            NetConfig::Quic { config } => {
+                config.protocol_fingerprint = protocol_fingerprint;
+            }
+            // the other connections do not have a handshake that can carry the fingerprint
+            #[allow(unreachable_patterns)]
+            _ => {}
+        }
+        self
+    }
+
+    /// Number of bytes that the transport adds to every packet
+    pub(crate) fn transport_header_bytes(&self) -> usize {
+        match self {
+            NetConfig::Netcode { io, .. } => io.header_bytes(),
+            _ => 0,
+        }
+    }
+
+    /// Prevent the fragmentation of the packets if the path MTU discovery is enabled.
+    /// Only the UDP transport of netcode supports it
+    pub(crate) fn with_mtu_discovery(mut self, enabled: bool) -> Self {
+        if let (true, NetConfig::Netcode { io, .. }) = (enabled, &mut self) {
+            io.enable_mtu_discovery();
+        }
+        self
+    }
+
+    /// Returns true if the path MTU of the connection can be discovered
+    pub(crate) fn supports_mtu_discovery(&self) -> bool {
+        match self {
+            NetConfig::Netcode { io, .. } => io.supports_mtu_discovery(),
+            _ => false,
+        }
+    }
+
     pub fn build_client(self) -> ClientConnection {
         match self {
             NetConfig::Netcode {
@@ -126,6 +210,13 @@ impl NetConfig {
                     client: Box::new(client),
                 }
             }
+            #[cfg(all(feature = "quic", not(target_family = "wasm")))]
+            NetConfig::Quic { config } => {
+                let client = super::quic::client::Client::new(config);
+                ClientConnection {
+                    client: Box::new(client),
+                }
+            }
             NetConfig::Local { id } => {
                 let client = super::local::client::Client::new(id);
                 ClientConnection {
@@ -149,6 +240,10 @@ impl NetClient for ClientConnection {
         self.client.is_connected()
     }
 
+    fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.client.disconnect_reason()
+    }
+
     fn try_update(&mut self, delta_ms: f64) -> Result<()> {
         self.client.try_update(delta_ms)
     }
@@ -161,6 +256,14 @@ impl NetClient for ClientConnection {
         self.client.send(buf)
     }
 
+    fn supports_reliable_send(&self) -> bool {
+        self.client.supports_reliable_send()
+    }
+
+    fn send_reliable(&mut self, buf: &[u8]) -> Result<()> {
+        self.client.send_reliable(buf)
+    }
+
     fn id(&self) -> ClientId {
         self.client.id()
     }
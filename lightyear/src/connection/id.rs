@@ -9,6 +9,8 @@ pub enum ClientId {
     Netcode(u64),
     /// The client id of a steam user
     Steam(u64),
+    /// A client id that is unique between QUIC connections
+    Quic(u64),
     /// A local client to use when running in HostServer mode
     Local(u64),
 }
@@ -20,6 +22,7 @@ impl ClientId {
         match self {
             ClientId::Netcode(x) => *x,
             ClientId::Steam(x) => *x,
+            ClientId::Quic(x) => *x,
             ClientId::Local(x) => *x,
         }
     }
@@ -7,6 +7,9 @@ pub(crate) mod server;
 
 pub mod id;
 mod local;
+#[cfg_attr(docsrs, doc(cfg(all(feature = "quic", not(target_family = "wasm")))))]
+#[cfg(all(feature = "quic", not(target_family = "wasm")))]
+pub(crate) mod quic;
 #[cfg_attr(docsrs, doc(cfg(all(feature = "steam", not(target_family = "wasm")))))]
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 pub(crate) mod steam;
@@ -6,23 +6,26 @@ use anyhow::Context;
 use bevy::prelude::Resource;
 use tracing::{debug, error, info, trace, warn};
 
-use crate::connection::client::NetClient;
+use crate::connection::client::{DisconnectReason, NetClient};
 use crate::connection::id;
 use crate::prelude::IoConfig;
 use crate::serialize::reader::ReadBuffer;
 use crate::serialize::wordbuffer::reader::{BufferPool, ReadWordBuffer};
 use crate::transport::io::Io;
+#[cfg(feature = "compression")]
+use crate::transport::middleware::compression::{CompressionConfig, Compressor, Decompressor};
 use crate::transport::{PacketReceiver, PacketSender, Transport, LOCAL_SOCKET};
 
 use super::{
     bytes::Bytes,
     error::{Error, Result},
     packet::{
-        DisconnectPacket, KeepAlivePacket, Packet, PayloadPacket, RequestPacket, ResponsePacket,
+        DeniedReason, DisconnectPacket, KeepAlivePacket, Packet, PayloadPacket, RequestPacket,
+        ResponsePacket,
     },
     replay::ReplayProtection,
     token::{ChallengeToken, ConnectToken},
-    utils, ClientId, MAX_PACKET_SIZE, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC,
+    utils, ClientId, MAX_MTU, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC,
 };
 
 type Callback<Ctx> = Box<dyn FnMut(ClientState, ClientState, &mut Ctx) + Send + Sync + 'static>;
@@ -32,6 +35,7 @@ type Callback<Ctx> = Box<dyn FnMut(ClientState, ClientState, &mut Ctx) + Send +
 /// * `num_disconnect_packets` - The number of redundant disconnect packets that will be sent to a server when the clients wants to disconnect.
 /// * `packet_send_rate` - The rate at which periodic packets will be sent to the server.
 /// * `on_state_change` - A callback that will be called when the client changes states.
+/// * `protocol_fingerprint` - A hash of the protocol used by the client, that is checked by the server during the connection handshake.
 ///
 /// # Example
 /// ```
@@ -59,6 +63,7 @@ pub struct ClientConfig<Ctx> {
     packet_send_rate: f64,
     context: Ctx,
     on_state_change: Option<Callback<Ctx>>,
+    protocol_fingerprint: u64,
 }
 
 impl Default for ClientConfig<()> {
@@ -68,6 +73,7 @@ impl Default for ClientConfig<()> {
             packet_send_rate: PACKET_SEND_RATE_SEC,
             context: (),
             on_state_change: None,
+            protocol_fingerprint: 0,
         }
     }
 }
@@ -84,6 +90,7 @@ impl<Ctx> ClientConfig<Ctx> {
             packet_send_rate: PACKET_SEND_RATE_SEC,
             context: ctx,
             on_state_change: None,
+            protocol_fingerprint: 0,
         }
     }
     /// Set the number of redundant disconnect packets that will be sent to a server when the clients wants to disconnect.
@@ -106,6 +113,13 @@ impl<Ctx> ClientConfig<Ctx> {
         self.on_state_change = Some(Box::new(cb));
         self
     }
+    /// Set the fingerprint of the protocol used by the client, which is sent in the connection request packet.
+    /// The server denies the connection if it doesn't match its own fingerprint.
+    /// The default is `0`.
+    pub fn protocol_fingerprint(mut self, protocol_fingerprint: u64) -> Self {
+        self.protocol_fingerprint = protocol_fingerprint;
+        self
+    }
 }
 
 /// The states in the client state machine.
@@ -145,6 +159,8 @@ pub enum ClientState {
     ChallengeResponseTimedOut,
     /// The server has denied the client's connection request, most likely due to the server being full.
     ConnectionDenied,
+    /// The server has denied the client's connection request because the client uses a different protocol.
+    ProtocolMismatch,
     /// The client is disconnected from the server.
     Disconnected,
     /// The client is waiting for a response from the server after sending a connection request packet.
@@ -196,6 +212,10 @@ pub struct NetcodeClient<Ctx = ()> {
     should_disconnect_state: ClientState,
     packet_queue: VecDeque<crate::packet::packet::Packet>,
     buffer_pool: BufferPool,
+    #[cfg(feature = "compression")]
+    compressor: Option<Compressor>,
+    #[cfg(feature = "compression")]
+    decompressor: Option<Decompressor>,
     cfg: ClientConfig<Ctx>,
 }
 
@@ -231,6 +251,10 @@ impl<Ctx> NetcodeClient<Ctx> {
             should_disconnect_state: ClientState::Disconnected,
             packet_queue: VecDeque::new(),
             buffer_pool: BufferPool::default(),
+            #[cfg(feature = "compression")]
+            compressor: None,
+            #[cfg(feature = "compression")]
+            decompressor: None,
             cfg,
         })
     }
@@ -327,6 +351,7 @@ impl<Ctx> NetcodeClient<Ctx> {
                 debug!("client sending connection request packet to server");
                 RequestPacket::create(
                     self.token.protocol_id,
+                    self.cfg.protocol_fingerprint,
                     self.token.expire_timestamp,
                     self.token.nonce,
                     self.token.private_data,
@@ -378,11 +403,15 @@ impl<Ctx> NetcodeClient<Ctx> {
         }
         match (packet, self.state) {
             (
-                Packet::Denied(_),
+                Packet::Denied(pkt),
                 ClientState::SendingConnectionRequest | ClientState::SendingChallengeResponse,
             ) => {
+                debug!(reason = ?pkt.reason, "client connection request was denied by the server");
                 self.should_disconnect = true;
-                self.should_disconnect_state = ClientState::ConnectionDenied;
+                self.should_disconnect_state = match pkt.reason {
+                    DeniedReason::ServerFull => ClientState::ConnectionDenied,
+                    DeniedReason::ProtocolMismatch => ClientState::ProtocolMismatch,
+                };
             }
             (Packet::Challenge(pkt), ClientState::SendingConnectionRequest) => {
                 debug!("client received connection challenge packet from server");
@@ -404,8 +433,21 @@ impl<Ctx> NetcodeClient<Ctx> {
                 // TODO: we decode the data immediately so we don't need to keep the buffer around!
                 //  we could just
                 // instead of allocating a new buffer, fetch one from the pool
+                #[cfg(feature = "compression")]
+                let buf = match self.decompressor.as_mut() {
+                    Some(decompressor) => match decompressor.decompress(pkt.buf) {
+                        Ok(buf) => buf,
+                        Err(e) => {
+                            error!("client ignored payload that could not be decompressed: {e}");
+                            return Ok(());
+                        }
+                    },
+                    None => pkt.buf,
+                };
+                #[cfg(not(feature = "compression"))]
+                let buf = pkt.buf;
                 trace!("read from netcode client pre");
-                let mut reader = self.buffer_pool.start_read(pkt.buf);
+                let mut reader = self.buffer_pool.start_read(buf);
                 let packet = crate::packet::packet::Packet::decode(&mut reader)
                     .map_err(|_| super::packet::Error::InvalidPayload)?;
                 trace!(
@@ -504,9 +546,40 @@ impl<Ctx> NetcodeClient<Ctx> {
         while let Some((buf, addr)) = io.recv().map_err(Error::from)? {
             self.recv_packet(buf, now, addr)?;
         }
+        #[cfg(feature = "compression")]
+        if let Some(decompressor) = self.decompressor.as_mut() {
+            let (compressed, uncompressed) = decompressor.take_received_bytes();
+            io.stats
+                .record_compressed_received(compressed, uncompressed);
+        }
         Ok(())
     }
 
+    /// Compress the payload of the packets sent to the server, and decompress the payload of the packets
+    /// received from it
+    #[cfg(feature = "compression")]
+    pub(crate) fn set_compression(&mut self, config: Option<&CompressionConfig>) -> Result<()> {
+        self.compressor = config.map(Compressor::new).transpose()?;
+        self.decompressor = config.map(Decompressor::new).transpose()?;
+        Ok(())
+    }
+
+    /// Record the connect token in the capture of the io, or restore it if the io plays back a capture,
+    /// so that the captured packets of the server can be decrypted again
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn capture_session(&mut self, io: &Io) -> Result<()> {
+        match io.replayed_session() {
+            Some(session) => {
+                self.token = ConnectToken::try_from_bytes(session).map_err(Error::InvalidToken)?;
+                Ok(())
+            }
+            None => {
+                let token = self.token.clone().try_into_bytes()?;
+                Ok(io.record_session(&token)?)
+            }
+        }
+    }
+
     /// Returns the netcode client id of the client once it is connected, or returns 0 if not connected.
     pub fn id(&self) -> ClientId {
         self.id
@@ -547,8 +620,12 @@ impl<Ctx> NetcodeClient<Ctx> {
     /// Returns an error if the client can't send or receive packets.
     pub fn try_update(&mut self, delta_ms: f64, io: &mut Io) -> Result<()> {
         self.time += delta_ms;
+        // send the packets that were delayed by the link conditioner
+        io.flush()?;
         self.recv_packets(io)?;
         self.send_packets(io)?;
+        // send the packets buffered by the transport
+        io.flush()?;
         self.update_state();
         Ok(())
     }
@@ -593,14 +670,22 @@ impl<Ctx> NetcodeClient<Ctx> {
 
     /// Sends a packet to the server.
     ///
-    /// The provided buffer must be smaller than [`MAX_PACKET_SIZE`].
+    /// The provided buffer must be smaller than [`MAX_MTU`](crate::packet::mtu::MAX_MTU).
     pub fn send(&mut self, buf: &[u8], io: &mut Io) -> Result<()> {
         if self.state != ClientState::Connected {
             trace!("tried to send but not connected");
             return Ok(());
         }
-        if buf.len() > MAX_PACKET_SIZE {
-            return Err(Error::SizeMismatch(MAX_PACKET_SIZE, buf.len()));
+        if buf.len() > MAX_MTU {
+            return Err(Error::SizeMismatch(MAX_MTU, buf.len()));
+        }
+        #[cfg(feature = "compression")]
+        if let Some(compressor) = self.compressor.as_mut() {
+            let mut compressed = [0u8; MAX_PKT_BUF_SIZE];
+            let len = compressor.compress(buf, &mut compressed)?;
+            self.send_packet(PayloadPacket::create(&compressed[..len]), io)?;
+            io.stats.record_compressed_sent(len, buf.len());
+            return Ok(());
         }
         self.send_packet(PayloadPacket::create(buf), io)?;
         Ok(())
@@ -657,6 +742,14 @@ impl<Ctx: Send + Sync> NetClient for Client<Ctx> {
             .context("io config is not initialized")?
             .build();
         io.connect().context("could not connect io")?;
+        #[cfg(not(target_family = "wasm"))]
+        self.client
+            .capture_session(&io)
+            .context("could not capture the session")?;
+        #[cfg(feature = "compression")]
+        self.client
+            .set_compression(io.compression())
+            .context("could not create the compressor")?;
         self.io = Some(io);
         self.client.connect();
         // TODO: have a separate explicit function to start listening on the io
@@ -669,7 +762,8 @@ impl<Ctx: Send + Sync> NetClient for Client<Ctx> {
         self.client
             .disconnect(io)
             .context("Error when disconnecting from server")?;
-        // drop the io
+        // send the buffered disconnect packets before dropping the io
+        io.flush().context("Could not flush the io")?;
         io.close().context("Could not close the io")?;
         std::mem::take(&mut self.io);
         Ok(())
@@ -679,6 +773,18 @@ impl<Ctx: Send + Sync> NetClient for Client<Ctx> {
         self.client.is_connected()
     }
 
+    fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        match self.client.state() {
+            ClientState::ProtocolMismatch => Some(DisconnectReason::ProtocolMismatch),
+            ClientState::ConnectionDenied => Some(DisconnectReason::ConnectionDenied),
+            ClientState::ConnectTokenExpired => Some(DisconnectReason::TokenExpired),
+            ClientState::ConnectionTimedOut
+            | ClientState::ConnectionRequestTimedOut
+            | ClientState::ChallengeResponseTimedOut => Some(DisconnectReason::TimedOut),
+            _ => None,
+        }
+    }
+
     fn try_update(&mut self, delta_ms: f64) -> anyhow::Result<()> {
         let io = self.io.as_mut().context("io is not initialized")?;
         self.client
@@ -131,6 +131,15 @@ pub use error::{Error, Result};
 pub use server::{Callback, ClientId, NetcodeServer, Server, ServerConfig};
 pub use token::{ConnectToken, ConnectTokenBuilder, InvalidTokenError};
 
+// used by the other connections to authenticate their clients with connect tokens
+pub(crate) use bytes::Bytes;
+pub(crate) use crypto::{chacha_decrypt, chacha_encrypt};
+pub(crate) use packet::RequestPacket;
+pub(crate) use token::ConnectTokenPrivate;
+pub(crate) use utils::now;
+
+use crate::packet::mtu::MAX_MTU;
+
 mod bytes;
 mod client;
 mod crypto;
@@ -142,7 +151,7 @@ mod token;
 mod utils;
 
 pub(crate) const MAC_BYTES: usize = 16;
-pub(crate) const MAX_PKT_BUF_SIZE: usize = 1300;
+pub(crate) const MAX_PKT_BUF_SIZE: usize = MAX_MTU + 100;
 pub(crate) const CONNECTION_TIMEOUT_SEC: i32 = 15;
 pub(crate) const PACKET_SEND_RATE_SEC: f64 = 1.0 / 10.0;
 
@@ -153,6 +162,11 @@ pub const USER_DATA_BYTES: usize = 256;
 /// The size of the connect token in bytes.
 pub const CONNECT_TOKEN_BYTES: usize = 2048;
 /// The maximum size of a packet in bytes.
+///
+/// The path MTU discovery can raise the size of the packets of a connection up to [`MAX_MTU`](crate::packet::mtu::MAX_MTU)
+/// (see [`packet::mtu`](crate::packet::mtu)).
 pub const MAX_PACKET_SIZE: usize = 1200;
 /// The version of the netcode protocol implemented by this crate.
-pub const NETCODE_VERSION: &[u8; 13] = b"NETCODE 1.02\0";
+///
+/// It differs from the standard netcode 1.02: the connection request packet also contains the fingerprint of the protocol.
+pub const NETCODE_VERSION: &[u8; 13] = b"NETCODE 1.03\0";
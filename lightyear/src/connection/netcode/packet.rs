@@ -26,7 +26,7 @@ pub enum Error {
     InvalidSequenceBytes(u8),
     #[error("packet length is less than 1")]
     TooSmall,
-    #[error("packet length is greater than 1200")]
+    #[error("packet length is greater than the maximum packet size")]
     TooLarge,
     #[error("bad packet length, expected {expected} but got {actual}")]
     LengthMismatch { expected: usize, actual: usize },
@@ -77,6 +77,8 @@ where
 pub struct RequestPacket {
     pub version_info: [u8; NETCODE_VERSION.len()],
     pub protocol_id: u64,
+    /// Hash of the protocol used by the client, checked by the server
+    pub protocol_fingerprint: u64,
     pub expire_timestamp: u64,
     pub token_nonce: XNonce,
     pub token_data: Box<[u8; ConnectTokenPrivate::SIZE]>,
@@ -85,6 +87,7 @@ pub struct RequestPacket {
 impl RequestPacket {
     pub fn create(
         protocol_id: u64,
+        protocol_fingerprint: u64,
         expire_timestamp: u64,
         token_nonce: XNonce,
         token_data: [u8; ConnectTokenPrivate::SIZE],
@@ -92,6 +95,7 @@ impl RequestPacket {
         Packet::Request(RequestPacket {
             version_info: *NETCODE_VERSION,
             protocol_id,
+            protocol_fingerprint,
             expire_timestamp,
             token_nonce,
             token_data: Box::new(token_data),
@@ -132,6 +136,7 @@ impl Bytes for RequestPacket {
     fn write_to(&self, writer: &mut impl WriteBytesExt) -> Result<(), Self::Error> {
         writer.write_all(&self.version_info)?;
         writer.write_u64::<LittleEndian>(self.protocol_id)?;
+        writer.write_u64::<LittleEndian>(self.protocol_fingerprint)?;
         writer.write_u64::<LittleEndian>(self.expire_timestamp)?;
         writer.write_all(&self.token_nonce)?;
         writer.write_all(&self.token_data[..])?;
@@ -142,6 +147,7 @@ impl Bytes for RequestPacket {
         let mut version_info = [0; NETCODE_VERSION.len()];
         reader.read_exact(&mut version_info)?;
         let protocol_id = reader.read_u64::<LittleEndian>()?;
+        let protocol_fingerprint = reader.read_u64::<LittleEndian>()?;
         let expire_timestamp = reader.read_u64::<LittleEndian>()?;
         let mut nonce = [0; size_of::<XNonce>()];
         reader.read_exact(&mut nonce)?;
@@ -151,6 +157,7 @@ impl Bytes for RequestPacket {
         Ok(Self {
             version_info,
             protocol_id,
+            protocol_fingerprint,
             expire_timestamp,
             token_nonce,
             token_data: Box::new(token_data),
@@ -158,22 +165,44 @@ impl Bytes for RequestPacket {
     }
 }
 
-pub struct DeniedPacket {}
+/// Reason why the server denied a connection request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeniedReason {
+    /// The server has reached its maximum number of clients
+    ServerFull = 0,
+    /// The client uses a protocol that has a different fingerprint than the server's protocol
+    ProtocolMismatch = 1,
+}
+
+pub struct DeniedPacket {
+    pub reason: DeniedReason,
+}
 
 impl DeniedPacket {
-    pub fn create() -> Packet<'static> {
-        Packet::Denied(DeniedPacket {})
+    pub fn create(reason: DeniedReason) -> Packet<'static> {
+        Packet::Denied(DeniedPacket { reason })
     }
 }
 
 impl Bytes for DeniedPacket {
     type Error = io::Error;
-    fn write_to(&self, _writer: &mut impl WriteBytesExt) -> Result<(), Self::Error> {
+    fn write_to(&self, writer: &mut impl WriteBytesExt) -> Result<(), Self::Error> {
+        writer.write_u8(self.reason as u8)?;
         Ok(())
     }
 
-    fn read_from(_reader: &mut impl byteorder::ReadBytesExt) -> Result<Self, io::Error> {
-        Ok(Self {})
+    fn read_from(reader: &mut impl byteorder::ReadBytesExt) -> Result<Self, io::Error> {
+        let reason = match reader.read_u8()? {
+            0 => DeniedReason::ServerFull,
+            1 => DeniedReason::ProtocolMismatch,
+            r => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid denied reason {r}"),
+                ))
+            }
+        };
+        Ok(Self { reason })
     }
 }
 
@@ -485,6 +514,23 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn request_packet_bad_version() {
+        // the request packets of the previous version do not contain the protocol fingerprint
+        let packet = RequestPacket {
+            version_info: *b"NETCODE 1.02\0",
+            protocol_id: 0x1234_5678_9abc_def0,
+            protocol_fingerprint: 0,
+            expire_timestamp: 1,
+            token_nonce: XChaCha20Poly1305::generate_nonce(&mut OsRng),
+            token_data: Box::new([0; ConnectTokenPrivate::SIZE]),
+        };
+        assert!(matches!(
+            packet.validate(0x1234_5678_9abc_def0, 0),
+            Err(Error::BadVersion)
+        ));
+    }
+
     #[test]
     fn sequence_number_bytes_required() {
         assert_eq!(sequence_len(0), 1);
@@ -535,6 +581,7 @@ mod tests {
         let packet = Packet::Request(RequestPacket {
             version_info: *NETCODE_VERSION,
             protocol_id,
+            protocol_fingerprint: 0xdead_beef,
             expire_timestamp,
             token_nonce: nonce,
             token_data: Box::new(token_data),
@@ -561,6 +608,7 @@ mod tests {
 
         assert_eq!(req_pkt.version_info, *NETCODE_VERSION);
         assert_eq!(req_pkt.protocol_id, protocol_id);
+        assert_eq!(req_pkt.protocol_fingerprint, 0xdead_beef);
         assert_eq!(req_pkt.expire_timestamp, expire_timestamp);
         assert_eq!(req_pkt.token_nonce, nonce);
 
@@ -585,7 +633,7 @@ mod tests {
         let sequence = 0u64;
         let mut replay_protection = ReplayProtection::new();
 
-        let packet = Packet::Denied(DeniedPacket {});
+        let packet = DeniedPacket::create(DeniedReason::ProtocolMismatch);
 
         let mut buf = [0u8; MAX_PKT_BUF_SIZE];
         let size = packet
@@ -602,9 +650,10 @@ mod tests {
         )
         .unwrap();
 
-        let Packet::Denied(_denied_pkt) = packet else {
+        let Packet::Denied(denied_pkt) = packet else {
             panic!("wrong packet type");
         };
+        assert_eq!(denied_pkt.reason, DeniedReason::ProtocolMismatch);
     }
 
     #[test]
@@ -4,7 +4,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, Context};
 use bevy::prelude::Resource;
-use tracing::{debug, error, trace};
+use tracing::{debug, error, info, trace};
 
 use crate::connection::id;
 use crate::connection::netcode::token::TOKEN_EXPIRE_SEC;
@@ -13,6 +13,8 @@ use crate::serialize::reader::ReadBuffer;
 use crate::serialize::wordbuffer::reader::{BufferPool, ReadWordBuffer};
 use crate::server::config::NetcodeConfig;
 use crate::transport::io::Io;
+#[cfg(feature = "compression")]
+use crate::transport::middleware::compression::{CompressionConfig, Compressor, Decompressor};
 use crate::transport::{PacketReceiver, PacketSender, Transport};
 
 use super::{
@@ -21,18 +23,22 @@ use super::{
     error::{Error, Result},
     generate_key,
     packet::{
-        ChallengePacket, DeniedPacket, DisconnectPacket, KeepAlivePacket, Packet, PayloadPacket,
-        RequestPacket, ResponsePacket,
+        ChallengePacket, DeniedPacket, DeniedReason, DisconnectPacket, KeepAlivePacket, Packet,
+        PayloadPacket, RequestPacket, ResponsePacket,
     },
     replay::ReplayProtection,
     token::{ChallengeToken, ConnectToken, ConnectTokenBuilder, ConnectTokenPrivate},
-    MAC_BYTES, MAX_PACKET_SIZE, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC,
+    MAC_BYTES, MAX_MTU, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC, PRIVATE_KEY_BYTES,
 };
 
 pub const MAX_CLIENTS: usize = 256;
 
 const CLIENT_TIMEOUT_SECS: i32 = 10;
 
+/// Size of the session recorded in a packet capture: the challenge key and the start time of the server
+#[cfg(not(target_family = "wasm"))]
+const SESSION_BYTES: usize = PRIVATE_KEY_BYTES + 8;
+
 #[derive(Clone, Copy)]
 struct TokenEntry {
     time: f64,
@@ -246,6 +252,7 @@ pub struct ServerConfig<Ctx> {
     token_expire_secs: i32,
     client_timeout_secs: i32,
     server_addr: SocketAddr,
+    protocol_fingerprint: u64,
     context: Ctx,
     on_connect: Option<Callback<Ctx>>,
     on_disconnect: Option<Callback<Ctx>>,
@@ -259,6 +266,7 @@ impl Default for ServerConfig<()> {
             token_expire_secs: TOKEN_EXPIRE_SEC,
             client_timeout_secs: CLIENT_TIMEOUT_SECS,
             server_addr: SocketAddr::from(([0, 0, 0, 0], 0)),
+            protocol_fingerprint: 0,
             context: (),
             on_connect: None,
             on_disconnect: None,
@@ -279,6 +287,7 @@ impl<Ctx> ServerConfig<Ctx> {
             token_expire_secs: TOKEN_EXPIRE_SEC,
             client_timeout_secs: CLIENT_TIMEOUT_SECS,
             server_addr: SocketAddr::from(([0, 0, 0, 0], 0)),
+            protocol_fingerprint: 0,
             context: ctx,
             on_connect: None,
             on_disconnect: None,
@@ -314,6 +323,13 @@ impl<Ctx> ServerConfig<Ctx> {
         self.server_addr = server_addr;
         self
     }
+    /// Set the fingerprint of the protocol used by the server.
+    /// Connection requests from clients with a different fingerprint are denied.
+    /// The default is `0`.
+    pub fn protocol_fingerprint(mut self, protocol_fingerprint: u64) -> Self {
+        self.protocol_fingerprint = protocol_fingerprint;
+        self
+    }
     /// Provide a callback that will be called when a client is connected to the server. <br>
     /// The callback will be called with the client index and the context that was provided (provide a `None` context if you don't need one).
     ///
@@ -379,9 +395,15 @@ pub struct NetcodeServer<Ctx = ()> {
     token_sequence: u64,
     challenge_sequence: u64,
     challenge_key: Key,
+    /// Number of seconds that the clock of a replayed server is set back by, to match the captured session
+    timestamp_offset: u64,
     protocol_id: u64,
     conn_cache: ConnectionCache,
     token_entries: TokenEntries,
+    #[cfg(feature = "compression")]
+    compressor: Option<Compressor>,
+    #[cfg(feature = "compression")]
+    decompressor: Option<Decompressor>,
     cfg: ServerConfig<Ctx>,
 }
 
@@ -398,8 +420,13 @@ impl NetcodeServer {
             token_sequence: 0,
             challenge_sequence: 0,
             challenge_key: crypto::generate_key(),
+            timestamp_offset: 0,
             conn_cache: ConnectionCache::new(0.0),
             token_entries: TokenEntries::new(),
+            #[cfg(feature = "compression")]
+            compressor: None,
+            #[cfg(feature = "compression")]
+            decompressor: None,
             cfg: ServerConfig::default(),
         };
         // info!("server started on {}", server.io.local_addr());
@@ -432,8 +459,13 @@ impl<Ctx> NetcodeServer<Ctx> {
             token_sequence: 0,
             challenge_sequence: 0,
             challenge_key: crypto::generate_key(),
+            timestamp_offset: 0,
             conn_cache: ConnectionCache::new(0.0),
             token_entries: TokenEntries::new(),
+            #[cfg(feature = "compression")]
+            compressor: None,
+            #[cfg(feature = "compression")]
+            decompressor: None,
             cfg,
         };
         // info!("server started on {}", server.addr());
@@ -492,8 +524,23 @@ impl<Ctx> NetcodeServer<Ctx> {
             Packet::Payload(packet) => {
                 self.touch_client(client_id)?;
                 if let Some(idx) = client_id {
+                    #[cfg(feature = "compression")]
+                    let buf = match self.decompressor.as_mut() {
+                        Some(decompressor) => {
+                            match decompressor.decompress(packet.buf) {
+                                Ok(buf) => buf,
+                                Err(e) => {
+                                    error!("server ignored payload that could not be decompressed: {e}");
+                                    return Ok(());
+                                }
+                            }
+                        }
+                        None => packet.buf,
+                    };
+                    #[cfg(not(feature = "compression"))]
+                    let buf = packet.buf;
                     // use a buffer from the pool to avoid re-allocating
-                    let mut reader = self.conn_cache.buffer_pool.start_read(packet.buf);
+                    let mut reader = self.conn_cache.buffer_pool.start_read(buf);
                     let packet = crate::packet::packet::Packet::decode(&mut reader)
                         .map_err(|_| super::packet::Error::InvalidPayload)?;
                     // return the buffer to the pool
@@ -627,10 +674,24 @@ impl<Ctx> NetcodeServer<Ctx> {
             debug!("server ignored connection request. connect token has already been used");
             return Ok(());
         };
+        if packet.protocol_fingerprint != self.cfg.protocol_fingerprint {
+            info!(
+                client_fingerprint = packet.protocol_fingerprint,
+                server_fingerprint = self.cfg.protocol_fingerprint,
+                "server denied connection request. the client uses a different protocol"
+            );
+            self.send_to_addr(
+                DeniedPacket::create(DeniedReason::ProtocolMismatch),
+                from_addr,
+                token.server_to_client_key,
+                sender,
+            )?;
+            return Ok(());
+        };
         if self.num_connected_clients() >= MAX_CLIENTS {
             debug!("server denied connection request. server is full");
             self.send_to_addr(
-                DeniedPacket::create(),
+                DeniedPacket::create(DeniedReason::ServerFull),
                 from_addr,
                 token.server_to_client_key,
                 sender,
@@ -687,7 +748,7 @@ impl<Ctx> NetcodeServer<Ctx> {
         if self.num_connected_clients() >= MAX_CLIENTS {
             debug!("server denied connection response. server is full");
             self.send_to_addr(
-                DeniedPacket::create(),
+                DeniedPacket::create(DeniedReason::ServerFull),
                 from_addr,
                 self.conn_cache
                     .clients
@@ -808,7 +869,10 @@ impl<Ctx> NetcodeServer<Ctx> {
         sender: &mut impl PacketSender,
         receiver: &mut impl PacketReceiver,
     ) -> Result<()> {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)?
+            .as_secs()
+            .saturating_sub(self.timestamp_offset);
         while let Some((buf, addr)) = receiver.recv().map_err(Error::from)? {
             self.recv_packet(buf, now, addr, sender)?;
         }
@@ -836,10 +900,22 @@ impl<Ctx> NetcodeServer<Ctx> {
     pub fn try_update(&mut self, delta_ms: f64, io: &mut Io) -> Result<()> {
         self.time += delta_ms;
         self.conn_cache.update(delta_ms);
-        let (sender, receiver) = io.split();
+        // send the packets that were delayed by the link conditioner
+        io.flush()?;
         self.check_for_timeouts();
-        self.recv_packets(sender, receiver)?;
+        {
+            let (mut sender, mut receiver) = io.split();
+            self.recv_packets(&mut sender, &mut receiver)?;
+        }
+        #[cfg(feature = "compression")]
+        if let Some(decompressor) = self.decompressor.as_mut() {
+            let (compressed, uncompressed) = decompressor.take_received_bytes();
+            io.stats
+                .record_compressed_received(compressed, uncompressed);
+        }
         self.send_packets(io)?;
+        // send the packets buffered by the transport
+        io.flush()?;
         Ok(())
     }
     /// Receives a packet from a client, if one is available in the queue.
@@ -876,10 +952,10 @@ impl<Ctx> NetcodeServer<Ctx> {
     }
     /// Sends a packet to a client.
     ///
-    /// The provided buffer must be smaller than [`MAX_PACKET_SIZE`].
+    /// The provided buffer must be smaller than [`MAX_MTU`](crate::packet::mtu::MAX_MTU).
     pub fn send(&mut self, buf: &[u8], client_id: ClientId, io: &mut Io) -> Result<()> {
-        if buf.len() > MAX_PACKET_SIZE {
-            return Err(Error::SizeMismatch(MAX_PACKET_SIZE, buf.len()));
+        if buf.len() > MAX_MTU {
+            return Err(Error::SizeMismatch(MAX_MTU, buf.len()));
         }
         let Some(conn) = self.conn_cache.clients.get_mut(&client_id) else {
             return Err(Error::ClientNotFound);
@@ -894,13 +970,56 @@ impl<Ctx> NetcodeServer<Ctx> {
             // send a keep-alive packet to the client to confirm the connection
             self.send_to_client(KeepAlivePacket::create(client_id), client_id, io)?;
         }
+        #[cfg(feature = "compression")]
+        if let Some(compressor) = self.compressor.as_mut() {
+            let mut compressed = [0u8; MAX_PKT_BUF_SIZE];
+            let len = compressor.compress(buf, &mut compressed)?;
+            self.send_to_client(PayloadPacket::create(&compressed[..len]), client_id, io)?;
+            io.stats.record_compressed_sent(len, buf.len());
+            return Ok(());
+        }
         let packet = PayloadPacket::create(buf);
         self.send_to_client(packet, client_id, io)
     }
 
+    /// Compress the payload of the packets sent to the clients, and decompress the payload of the packets
+    /// received from them
+    #[cfg(feature = "compression")]
+    pub(crate) fn set_compression(&mut self, config: Option<&CompressionConfig>) -> Result<()> {
+        self.compressor = config.map(Compressor::new).transpose()?;
+        self.decompressor = config.map(Decompressor::new).transpose()?;
+        Ok(())
+    }
+
+    /// Record the challenge key and the start time of the server in the capture of the io,
+    /// or restore them if the io plays back a capture, so that the captured handshakes are accepted again
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn capture_session(&mut self, io: &Io) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        match io.replayed_session() {
+            Some(session) => {
+                if session.len() != SESSION_BYTES {
+                    return Err(Error::SizeMismatch(SESSION_BYTES, session.len()));
+                }
+                let (challenge_key, start_time) = session.split_at(PRIVATE_KEY_BYTES);
+                self.challenge_key.copy_from_slice(challenge_key);
+                let start_time = u64::from_le_bytes(start_time.try_into().unwrap());
+                self.timestamp_offset = now.saturating_sub(start_time);
+                Ok(())
+            }
+            None => {
+                let mut session = [0u8; SESSION_BYTES];
+                let (challenge_key, start_time) = session.split_at_mut(PRIVATE_KEY_BYTES);
+                challenge_key.copy_from_slice(&self.challenge_key);
+                start_time.copy_from_slice(&now.to_le_bytes());
+                Ok(io.record_session(&session)?)
+            }
+        }
+    }
+
     /// Sends a packet to all connected clients.
     ///
-    /// The provided buffer must be smaller than [`MAX_PACKET_SIZE`].
+    /// The provided buffer must be smaller than [`MAX_MTU`](crate::packet::mtu::MAX_MTU).
     pub fn send_all(&mut self, buf: &[u8], io: &mut Io) -> Result<()> {
         for id in self.conn_cache.ids() {
             match self.send(buf, id, io) {
@@ -1023,6 +1142,14 @@ pub struct Server {
 impl NetServer for Server {
     fn start(&mut self) -> anyhow::Result<()> {
         self.io.connect()?;
+        #[cfg(not(target_family = "wasm"))]
+        self.server
+            .capture_session(&self.io)
+            .context("could not capture the session")?;
+        #[cfg(feature = "compression")]
+        self.server
+            .set_compression(self.io.compression())
+            .context("could not create the compressor")?;
         Ok(())
     }
 
@@ -1095,8 +1222,12 @@ impl NetServer for Server {
         self.server.cfg.context.disconnections.clone()
     }
 
-    fn io(&self) -> &Io {
-        &self.io
+    fn io(&self) -> Option<&Io> {
+        Some(&self.io)
+    }
+
+    fn io_mut(&mut self) -> Option<&mut Io> {
+        Some(&mut self.io)
     }
 }
 
@@ -1114,6 +1245,7 @@ impl Server {
             });
         cfg = cfg.keep_alive_send_rate(config.keep_alive_send_rate);
         cfg = cfg.num_disconnect_packets(config.num_disconnect_packets);
+        cfg = cfg.protocol_fingerprint(config.protocol_fingerprint);
         let server = NetcodeServer::with_config(config.protocol_id, private_key, cfg)
             .expect("Could not create server netcode");
 
@@ -0,0 +1,327 @@
+use std::collections::VecDeque;
+use std::fmt::Formatter;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Context, Result};
+use async_compat::Compat;
+use bevy::tasks::{futures_lite, IoTaskPool, Task};
+use quinn::{Connection, Endpoint, VarInt};
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::{error, info};
+
+use crate::_reexport::ReadBuffer;
+use crate::connection::client::{Authentication, DisconnectReason, NetClient};
+use crate::connection::id::ClientId;
+use crate::connection::netcode::{Bytes, RequestPacket, CONNECTION_TIMEOUT_SEC};
+use crate::packet::packet::Packet;
+use crate::prelude::Io;
+use crate::serialize::wordbuffer::reader::BufferPool;
+
+use super::{send_packet, spawn_receive_tasks, HandshakeReply};
+
+/// How the client validates the certificate presented by the server
+#[derive(Debug, Clone)]
+pub enum CertificateValidation {
+    /// Only trust the given DER-encoded certificates (for example the self-signed certificate of the server)
+    Trusted(Vec<Vec<u8>>),
+    /// Accept any certificate.
+    ///
+    /// The connection is still encrypted but the server is not authenticated, so this should only
+    /// be used during development.
+    Disabled,
+}
+
+#[derive(Clone)]
+pub struct QuicConfig {
+    pub client_addr: SocketAddr,
+    pub server_addr: SocketAddr,
+    /// Name of the server, used to validate its certificate
+    pub server_name: String,
+    /// Connect token sent to the server, which contains the id of the client.
+    /// The id must be unique among the clients connected to the server
+    pub auth: Authentication,
+    pub certificate_validation: CertificateValidation,
+    /// Fingerprint of the protocol, sent to the server during the handshake.
+    /// It is set by the [`ClientPlugin`](crate::client::plugin::ClientPlugin)
+    pub protocol_fingerprint: u64,
+}
+
+impl Default for QuicConfig {
+    fn default() -> Self {
+        Self {
+            client_addr: SocketAddr::from(([0, 0, 0, 0], 0)),
+            server_addr: SocketAddr::from(([127, 0, 0, 1], 5000)),
+            server_name: "localhost".to_string(),
+            auth: Authentication::default(),
+            certificate_validation: CertificateValidation::Trusted(vec![]),
+            protocol_fingerprint: 0,
+        }
+    }
+}
+
+impl std::fmt::Debug for QuicConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // do not print the connect token
+        f.debug_struct("QuicConfig")
+            .field("client_addr", &self.client_addr)
+            .field("server_addr", &self.server_addr)
+            .field("server_name", &self.server_name)
+            .field("certificate_validation", &self.certificate_validation)
+            .finish()
+    }
+}
+
+impl QuicConfig {
+    pub(super) fn client_config(&self) -> Result<quinn::ClientConfig> {
+        let builder = rustls::ClientConfig::builder().with_safe_defaults();
+        let crypto = match &self.certificate_validation {
+            CertificateValidation::Trusted(certificates) => {
+                let mut roots = rustls::RootCertStore::empty();
+                for certificate in certificates {
+                    roots
+                        .add(&rustls::Certificate(certificate.clone()))
+                        .context("invalid trusted certificate")?;
+                }
+                builder.with_root_certificates(roots).with_no_client_auth()
+            }
+            CertificateValidation::Disabled => builder
+                .with_custom_certificate_verifier(Arc::new(SkipServerVerification))
+                .with_no_client_auth(),
+        };
+        Ok(quinn::ClientConfig::new(Arc::new(crypto)))
+    }
+}
+
+/// Certificate verifier that accepts any server certificate
+struct SkipServerVerification;
+
+impl ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+pub struct Client {
+    config: QuicConfig,
+    endpoint: Option<Endpoint>,
+    /// Task that establishes the connection and performs the handshake
+    connecting: Option<Task<Result<Result<(Connection, u64), HandshakeReply>>>>,
+    connection: Option<Connection>,
+    /// Id of the client, that the server got from the connect token
+    client_id: u64,
+    from_server: Option<UnboundedReceiver<(Vec<u8>, ClientId)>>,
+    packet_queue: VecDeque<Packet>,
+    buffer_pool: BufferPool,
+    /// Set if the server rejected the connection
+    disconnect_reason: Option<DisconnectReason>,
+}
+
+impl Client {
+    pub fn new(config: QuicConfig) -> Self {
+        Self {
+            config,
+            endpoint: None,
+            connecting: None,
+            connection: None,
+            client_id: 0,
+            from_server: None,
+            packet_queue: VecDeque::new(),
+            buffer_pool: BufferPool::default(),
+            disconnect_reason: None,
+        }
+    }
+
+    /// Serialize the parts of the connect token that the server needs to authenticate the client
+    fn connection_request(&self) -> Result<Vec<u8>> {
+        let token = self
+            .config
+            .auth
+            .clone()
+            .get_token(CONNECTION_TIMEOUT_SEC)
+            .context("could not generate the connect token")?;
+        let request = RequestPacket {
+            version_info: token.version_info,
+            protocol_id: token.protocol_id,
+            protocol_fingerprint: self.config.protocol_fingerprint,
+            expire_timestamp: token.expire_timestamp,
+            token_nonce: token.nonce,
+            token_data: Box::new(token.private_data),
+        };
+        let mut bytes = vec![];
+        request.write_to(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Connect to the server and send the connect token.
+    ///
+    /// Returns the connection and the client id once the server accepts the connection,
+    /// or the reply of the server if it rejects the connection
+    async fn handshake(
+        endpoint: Endpoint,
+        server_addr: SocketAddr,
+        server_name: String,
+        request: Vec<u8>,
+    ) -> Result<Result<(Connection, u64), HandshakeReply>> {
+        let connection = endpoint.connect(server_addr, &server_name)?.await?;
+        let (mut send, mut recv) = connection.open_bi().await?;
+        send.write_all(&request).await?;
+        send.finish().await?;
+        let mut reply = [0; 1];
+        recv.read_exact(&mut reply).await?;
+        match HandshakeReply::from_u8(reply[0]) {
+            Some(HandshakeReply::Accepted) => {
+                let mut client_id = [0; 8];
+                recv.read_exact(&mut client_id).await?;
+                Ok(Ok((connection, u64::from_le_bytes(client_id))))
+            }
+            Some(reply) => Ok(Err(reply)),
+            None => Err(anyhow!("invalid handshake reply: {}", reply[0])),
+        }
+    }
+}
+
+impl NetClient for Client {
+    fn connect(&mut self) -> Result<()> {
+        let client_config = self.config.client_config()?;
+        let request = self.connection_request()?;
+        let client_addr = self.config.client_addr;
+        // need to run this with Compat because it requires the tokio reactor
+        let mut endpoint =
+            futures_lite::future::block_on(Compat::new(
+                async move { Endpoint::client(client_addr) },
+            ))
+            .context("could not create QUIC client endpoint")?;
+        endpoint.set_default_client_config(client_config);
+
+        info!("Connecting to QUIC server at {:?}", self.config.server_addr);
+        self.disconnect_reason = None;
+        self.connecting = Some(IoTaskPool::get().spawn(Compat::new(Self::handshake(
+            endpoint.clone(),
+            self.config.server_addr,
+            self.config.server_name.clone(),
+            request,
+        ))));
+        self.endpoint = Some(endpoint);
+        Ok(())
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        if let Some(connection) = self.connection.take() {
+            connection.close(VarInt::from_u32(0), b"client disconnected");
+        }
+        self.connecting = None;
+        self.from_server = None;
+        self.endpoint = None;
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connection
+            .as_ref()
+            .is_some_and(|connection| connection.close_reason().is_none())
+    }
+
+    fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.disconnect_reason
+    }
+
+    fn try_update(&mut self, delta_ms: f64) -> Result<()> {
+        // check if the connection has been established
+        if let Some(connecting) = self.connecting.as_mut() {
+            if let Some(result) =
+                futures_lite::future::block_on(futures_lite::future::poll_once(connecting))
+            {
+                self.connecting = None;
+                let (connection, client_id) = match result
+                    .context("could not connect to the QUIC server")?
+                {
+                    Ok(accepted) => accepted,
+                    Err(reply) => {
+                        self.disconnect_reason = Some(match reply {
+                            HandshakeReply::ProtocolMismatch => DisconnectReason::ProtocolMismatch,
+                            _ => DisconnectReason::ConnectionDenied,
+                        });
+                        return Err(anyhow!("connection rejected by the server: {:?}", reply));
+                    }
+                };
+                self.client_id = client_id;
+                info!("Connected to QUIC server at {:?}", self.config.server_addr);
+                let (sender, receiver) = mpsc::unbounded_channel();
+                spawn_receive_tasks(connection.clone(), self.id(), sender);
+                self.from_server = Some(receiver);
+                self.connection = Some(connection);
+            }
+        }
+
+        // buffer incoming packets
+        if let Some(from_server) = self.from_server.as_mut() {
+            while let Ok((data, _)) = from_server.try_recv() {
+                // get a buffer from the pool to avoid new allocations
+                let mut reader = self.buffer_pool.start_read(data.as_slice());
+                let packet = Packet::decode(&mut reader);
+                // return the buffer to the pool
+                self.buffer_pool.attach(reader);
+                match packet {
+                    Ok(packet) => self.packet_queue.push_back(packet),
+                    Err(e) => error!("could not decode packet from the server: {:?}", e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Option<Packet> {
+        self.packet_queue.pop_front()
+    }
+
+    fn send(&mut self, buf: &[u8]) -> Result<()> {
+        let connection = self
+            .connection
+            .as_ref()
+            .context("client is not connected")?;
+        send_packet(connection, buf, false)
+    }
+
+    fn supports_reliable_send(&self) -> bool {
+        true
+    }
+
+    fn send_reliable(&mut self, buf: &[u8]) -> Result<()> {
+        let connection = self
+            .connection
+            .as_ref()
+            .context("client is not connected")?;
+        send_packet(connection, buf, true)
+    }
+
+    fn id(&self) -> ClientId {
+        ClientId::Quic(self.client_id)
+    }
+
+    fn local_addr(&self) -> SocketAddr {
+        self.endpoint
+            .as_ref()
+            .and_then(|endpoint| endpoint.local_addr().ok())
+            .unwrap_or(self.config.client_addr)
+    }
+
+    fn io(&self) -> Option<&Io> {
+        None
+    }
+
+    fn io_mut(&mut self) -> Option<&mut Io> {
+        None
+    }
+}
@@ -0,0 +1,324 @@
+//! Connection that uses QUIC directly, without netcode.
+//!
+//! QUIC provides encryption (via TLS 1.3) and congestion control for the connection.
+//! Packets that contain messages from unreliable channels are sent as QUIC datagrams, and packets that
+//! only contain messages from reliable channels are sent on QUIC streams (one unidirectional stream per packet,
+//! so that a lost packet does not block the other ones).
+//!
+//! When the connection is established, the client opens a bidirectional stream to send its netcode
+//! [`ConnectToken`](crate::connection::netcode::ConnectToken) to the server, which gets the client id from the token
+//! and replies with a [`HandshakeReply`] (followed by the client id, if the connection is accepted).
+//!
+//! Since the packets of the reliable channels are delivered by QUIC, these channels never resend their messages.
+//! If a reliable packet cannot be written on its stream, the connection is closed, since the message would be lost.
+use anyhow::Result;
+use async_compat::Compat;
+use bevy::tasks::IoTaskPool;
+use quinn::{Connection, SendDatagramError, VarInt};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{debug, error, trace};
+
+use crate::connection::id::ClientId;
+use crate::packet::mtu::MAX_MTU;
+
+pub(crate) mod client;
+pub(crate) mod server;
+
+/// Error code used to close the connection when a reliable packet could not be sent
+const SEND_FAILED: VarInt = VarInt::from_u32(1);
+
+/// Reply sent by the server at the end of the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum HandshakeReply {
+    Accepted = 0,
+    /// The server already has the maximum number of clients
+    ServerFull = 1,
+    /// Another client is already connected with the same client id
+    ClientIdTaken = 2,
+    /// The connect token could not be decrypted, or it has expired
+    InvalidToken = 3,
+    /// The fingerprint of the protocol of the client is different from the server's
+    ProtocolMismatch = 4,
+}
+
+impl HandshakeReply {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Accepted),
+            1 => Some(Self::ServerFull),
+            2 => Some(Self::ClientIdTaken),
+            3 => Some(Self::InvalidToken),
+            4 => Some(Self::ProtocolMismatch),
+            _ => None,
+        }
+    }
+}
+
+/// Send a packet on the connection.
+///
+/// Unreliable packets are sent as QUIC datagrams, unless they are bigger than the maximum datagram size
+/// allowed by the current path MTU, in which case they are sent on a stream like reliable packets.
+///
+/// The stream is written asynchronously. If a reliable packet cannot be written, the connection is closed,
+/// since the reliable channels rely on QUIC to deliver their messages and never resend them.
+fn send_packet(connection: &Connection, buf: &[u8], reliable: bool) -> Result<()> {
+    if !reliable {
+        match connection.send_datagram(buf.to_vec().into()) {
+            Ok(()) => return Ok(()),
+            Err(SendDatagramError::TooLarge) => {
+                trace!(
+                    len = buf.len(),
+                    "packet is too large for a QUIC datagram, sending it on a stream"
+                );
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let connection = connection.clone();
+    let buf = buf.to_vec();
+    IoTaskPool::get()
+        .spawn(Compat::new(async move {
+            if let Err(e) = write_stream(&connection, &buf).await {
+                if reliable {
+                    error!(
+                        "could not send reliable packet on a QUIC stream, closing the connection: {:?}",
+                        e
+                    );
+                    connection.close(SEND_FAILED, b"could not send a reliable packet");
+                } else {
+                    debug!("could not send packet on a QUIC stream: {:?}", e);
+                }
+            }
+        }))
+        .detach();
+    Ok(())
+}
+
+/// Write the packet on a new unidirectional stream
+async fn write_stream(connection: &Connection, buf: &[u8]) -> Result<()> {
+    let mut stream = connection.open_uni().await?;
+    stream.write_all(buf).await?;
+    stream.finish().await?;
+    Ok(())
+}
+
+/// Spawn the tasks that read the datagrams and streams received on the connection
+/// and forward them to `sender`.
+///
+/// The tasks stop when the connection is closed.
+fn spawn_receive_tasks(
+    connection: Connection,
+    client_id: ClientId,
+    sender: UnboundedSender<(Vec<u8>, ClientId)>,
+) {
+    let datagram_connection = connection.clone();
+    let datagram_sender = sender.clone();
+    IoTaskPool::get()
+        .spawn(Compat::new(async move {
+            while let Ok(datagram) = datagram_connection.read_datagram().await {
+                if datagram_sender
+                    .send((datagram.to_vec(), client_id))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }))
+        .detach();
+    IoTaskPool::get()
+        .spawn(Compat::new(async move {
+            while let Ok(mut stream) = connection.accept_uni().await {
+                let sender = sender.clone();
+                IoTaskPool::get()
+                    .spawn(Compat::new(async move {
+                        match stream.read_to_end(MAX_MTU).await {
+                            Ok(data) => {
+                                let _ = sender.send((data, client_id));
+                            }
+                            Err(e) => debug!("could not read packet from a QUIC stream: {:?}", e),
+                        }
+                    }))
+                    .detach();
+            }
+        }))
+        .detach();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use async_compat::Compat;
+    use bevy::tasks::{futures_lite, IoTaskPool, TaskPool};
+    use bevy::utils::Duration;
+    use quinn::Endpoint;
+
+    use crate::connection::client::{Authentication, DisconnectReason, NetClient};
+    use crate::connection::id::ClientId;
+    use crate::connection::netcode::generate_key;
+    use crate::connection::server::NetServer;
+
+    use super::client::{CertificateValidation, Client, QuicConfig as ClientQuicConfig};
+    use super::server::{QuicConfig as ServerQuicConfig, Server};
+
+    fn client_config(server: &ServerQuicConfig, client_id: u64) -> ClientQuicConfig {
+        ClientQuicConfig {
+            client_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+            server_addr: server.server_addr,
+            server_name: "localhost".to_string(),
+            auth: Authentication::Manual {
+                server_addr: server.server_addr,
+                client_id,
+                private_key: server.connect_token_key,
+                protocol_id: server.protocol_id,
+            },
+            certificate_validation: CertificateValidation::Trusted(
+                server.certificate_chain.clone(),
+            ),
+            protocol_fingerprint: server.protocol_fingerprint,
+        }
+    }
+
+    /// Start a server on a free port, and return it with its config (which contains the address of the server)
+    fn start_server(
+        configure: impl FnOnce(&mut ServerQuicConfig),
+    ) -> anyhow::Result<(Server, ServerQuicConfig)> {
+        let mut config = ServerQuicConfig::self_signed(
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            vec!["localhost".to_string()],
+        )?;
+        configure(&mut config);
+        let mut server = Server::new(config.clone());
+        server.start()?;
+        config.server_addr = server.local_addr().unwrap();
+        Ok((server, config))
+    }
+
+    /// Update the server and the clients until `condition` is true
+    fn update_until(
+        server: &mut Server,
+        clients: &mut [&mut Client],
+        condition: impl Fn(&Server, &[&mut Client]) -> bool,
+    ) -> bool {
+        for _ in 0..200 {
+            let _ = server.try_update(0.0);
+            for client in clients.iter_mut() {
+                let _ = client.try_update(0.0);
+            }
+            if condition(server, clients) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn test_quic_handshake() -> anyhow::Result<()> {
+        IoTaskPool::get_or_init(TaskPool::new);
+        let (mut server, server_config) = start_server(|_| {})?;
+
+        let mut client = Client::new(client_config(&server_config, 1));
+        client.connect()?;
+        assert!(update_until(
+            &mut server,
+            &mut [&mut client],
+            |server, clients| {
+                clients[0].is_connected() && !server.connected_client_ids().is_empty()
+            }
+        ));
+        assert_eq!(server.connected_client_ids(), vec![ClientId::Quic(1)]);
+
+        // a second client with the same id is rejected
+        let mut duplicate = Client::new(client_config(&server_config, 1));
+        duplicate.connect()?;
+        update_until(&mut server, &mut [&mut duplicate], |_, _| false);
+        assert!(!duplicate.is_connected());
+        assert_eq!(server.connected_client_ids(), vec![ClientId::Quic(1)]);
+
+        // disconnecting the client removes it from the server
+        client.disconnect()?;
+        assert!(update_until(&mut server, &mut [], |server, _| {
+            server.connected_client_ids().is_empty()
+        }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_quic_invalid_token() -> anyhow::Result<()> {
+        IoTaskPool::get_or_init(TaskPool::new);
+        let (mut server, server_config) = start_server(|_| {})?;
+
+        // the token is encrypted with a key that the server doesn't know
+        let mut config = client_config(&server_config, 1);
+        config.auth = Authentication::Manual {
+            server_addr: server_config.server_addr,
+            client_id: 1,
+            private_key: generate_key(),
+            protocol_id: server_config.protocol_id,
+        };
+        let mut client = Client::new(config);
+        client.connect()?;
+        update_until(&mut server, &mut [&mut client], |_, _| false);
+        assert!(!client.is_connected());
+        assert!(server.connected_client_ids().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_quic_protocol_mismatch() -> anyhow::Result<()> {
+        IoTaskPool::get_or_init(TaskPool::new);
+        let (mut server, server_config) = start_server(|config| config.protocol_fingerprint = 1)?;
+
+        let mut config = client_config(&server_config, 1);
+        config.protocol_fingerprint = 2;
+        let mut client = Client::new(config);
+        client.connect()?;
+        assert!(update_until(
+            &mut server,
+            &mut [&mut client],
+            |_, clients| { clients[0].disconnect_reason().is_some() }
+        ));
+        assert_eq!(
+            client.disconnect_reason(),
+            Some(DisconnectReason::ProtocolMismatch)
+        );
+        assert!(!client.is_connected());
+        assert!(server.connected_client_ids().is_empty());
+
+        // a client with the same protocol can connect
+        let mut client = Client::new(client_config(&server_config, 1));
+        client.connect()?;
+        assert!(update_until(
+            &mut server,
+            &mut [&mut client],
+            |server, clients| {
+                clients[0].is_connected() && !server.connected_client_ids().is_empty()
+            }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_quic_handshake_timeout() -> anyhow::Result<()> {
+        IoTaskPool::get_or_init(TaskPool::new);
+        let (server, server_config) =
+            start_server(|config| config.handshake_timeout = Duration::from_millis(100))?;
+
+        // the client opens the QUIC connection but never sends its connect token
+        let client_config = client_config(&server_config, 1).client_config()?;
+        let closed = futures_lite::future::block_on(Compat::new(async move {
+            let mut endpoint = Endpoint::client(SocketAddr::from(([127, 0, 0, 1], 0)))?;
+            endpoint.set_default_client_config(client_config);
+            let connection = endpoint
+                .connect(server_config.server_addr, "localhost")?
+                .await?;
+            let reason = tokio::time::timeout(Duration::from_secs(2), connection.closed()).await;
+            Ok::<_, anyhow::Error>(reason.is_ok())
+        }))?;
+        assert!(closed);
+        assert!(server.connected_client_ids().is_empty());
+        Ok(())
+    }
+}
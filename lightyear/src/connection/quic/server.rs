@@ -0,0 +1,416 @@
+use std::collections::VecDeque;
+use std::fmt::Formatter;
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use async_compat::Compat;
+use bevy::tasks::{futures_lite, IoTaskPool};
+use bevy::utils::{Duration, HashMap};
+use quinn::{Connecting, Connection, Endpoint, VarInt};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tracing::{debug, error, info};
+
+use crate::_reexport::ReadBuffer;
+use crate::connection::id::ClientId;
+use crate::connection::netcode::{
+    generate_key, now, Bytes, ConnectTokenPrivate, Key, RequestPacket,
+};
+use crate::connection::server::NetServer;
+use crate::packet::packet::Packet;
+use crate::prelude::Io;
+use crate::serialize::wordbuffer::reader::BufferPool;
+
+use super::{send_packet, spawn_receive_tasks, HandshakeReply};
+
+/// Maximum size of the connection request sent by the clients during the handshake
+const MAX_REQUEST_BYTES: usize = 2048;
+
+#[derive(Clone)]
+pub struct QuicConfig {
+    pub server_addr: SocketAddr,
+    /// DER-encoded certificate chain presented to the clients
+    pub certificate_chain: Vec<Vec<u8>>,
+    /// DER-encoded (PKCS#8) private key of the certificate
+    pub private_key: Vec<u8>,
+    pub max_clients: usize,
+    /// Protocol id of the connect tokens accepted by the server
+    pub protocol_id: u64,
+    /// Key used to decrypt the connect tokens sent by the clients
+    pub connect_token_key: Key,
+    /// Clients that do not send their connect token within this duration are disconnected
+    pub handshake_timeout: Duration,
+    /// Fingerprint of the protocol. Clients with a different fingerprint are rejected.
+    /// It is set by the [`ServerPlugin`](crate::server::plugin::ServerPlugin)
+    pub protocol_fingerprint: u64,
+}
+
+impl QuicConfig {
+    /// Create a config that uses a self-signed certificate, valid for the given names.
+    ///
+    /// The clients need to trust [`certificate_chain`](QuicConfig::certificate_chain) to be able to connect.
+    /// The key used to decrypt the connect tokens is generated randomly; use [`with_key`](QuicConfig::with_key)
+    /// to share it with the backend that generates the tokens.
+    pub fn self_signed(server_addr: SocketAddr, subject_alt_names: Vec<String>) -> Result<Self> {
+        let certificate = rcgen::generate_simple_self_signed(subject_alt_names)
+            .context("could not generate self-signed certificate")?;
+        Ok(Self {
+            server_addr,
+            certificate_chain: vec![certificate.serialize_der()?],
+            private_key: certificate.serialize_private_key_der(),
+            max_clients: 16,
+            protocol_id: 0,
+            connect_token_key: generate_key(),
+            handshake_timeout: Duration::from_secs(5),
+            protocol_fingerprint: 0,
+        })
+    }
+
+    pub fn with_protocol_id(mut self, protocol_id: u64) -> Self {
+        self.protocol_id = protocol_id;
+        self
+    }
+
+    pub fn with_key(mut self, key: Key) -> Self {
+        self.connect_token_key = key;
+        self
+    }
+}
+
+impl std::fmt::Debug for QuicConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // do not print the private key
+        f.debug_struct("QuicConfig")
+            .field("server_addr", &self.server_addr)
+            .field("max_clients", &self.max_clients)
+            .field("protocol_id", &self.protocol_id)
+            .field("handshake_timeout", &self.handshake_timeout)
+            .field("protocol_fingerprint", &self.protocol_fingerprint)
+            .finish()
+    }
+}
+
+/// Connection events sent by the tasks that handle each client
+enum ConnectionEvent {
+    Connected(ClientId),
+    Disconnected(ClientId),
+}
+
+pub struct Server {
+    config: QuicConfig,
+    endpoint: Option<Endpoint>,
+    /// Connections that completed the handshake.
+    /// Shared with the tasks that accept new connections
+    connections: Arc<Mutex<HashMap<ClientId, Connection>>>,
+    events: Option<UnboundedReceiver<ConnectionEvent>>,
+    from_clients: Option<UnboundedReceiver<(Vec<u8>, ClientId)>>,
+    packet_queue: VecDeque<(Packet, ClientId)>,
+    buffer_pool: BufferPool,
+    new_connections: Vec<ClientId>,
+    new_disconnections: Vec<ClientId>,
+}
+
+impl Server {
+    pub fn new(config: QuicConfig) -> Self {
+        Self {
+            config,
+            endpoint: None,
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            events: None,
+            from_clients: None,
+            packet_queue: VecDeque::new(),
+            buffer_pool: BufferPool::default(),
+            new_connections: Vec::new(),
+            new_disconnections: Vec::new(),
+        }
+    }
+
+    /// Address that the server is listening on, once it is started
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.endpoint
+            .as_ref()
+            .and_then(|endpoint| endpoint.local_addr().ok())
+    }
+
+    fn server_config(&self) -> Result<quinn::ServerConfig> {
+        let certificate_chain = self
+            .config
+            .certificate_chain
+            .iter()
+            .map(|certificate| rustls::Certificate(certificate.clone()))
+            .collect();
+        let private_key = rustls::PrivateKey(self.config.private_key.clone());
+        quinn::ServerConfig::with_single_cert(certificate_chain, private_key)
+            .context("invalid QUIC server certificate")
+    }
+
+    /// Decrypt the connect token sent by a new client, and return the client id that it contains
+    fn authenticate(request: &[u8], config: &QuicConfig) -> Result<ClientId, HandshakeReply> {
+        let decrypt = || {
+            let mut request = RequestPacket::read_from(&mut Cursor::new(request))?;
+            request.validate(config.protocol_id, now())?;
+            request.decrypt_token_data(config.connect_token_key)?;
+            let token = ConnectTokenPrivate::read_from(&mut Cursor::new(&request.token_data[..]))?;
+            Ok::<_, anyhow::Error>((request.protocol_fingerprint, token.client_id))
+        };
+        let (protocol_fingerprint, client_id) = decrypt().map_err(|e| {
+            debug!("invalid connect token: {:?}", e);
+            HandshakeReply::InvalidToken
+        })?;
+        if protocol_fingerprint != config.protocol_fingerprint {
+            debug!(
+                client_fingerprint = protocol_fingerprint,
+                server_fingerprint = config.protocol_fingerprint,
+                "the client uses a different protocol"
+            );
+            return Err(HandshakeReply::ProtocolMismatch);
+        }
+        Ok(ClientId::Quic(client_id))
+    }
+
+    /// Perform the handshake with a new client, then wait until the connection is closed
+    async fn handle_client(
+        connecting: Connecting,
+        config: Arc<QuicConfig>,
+        connections: Arc<Mutex<HashMap<ClientId, Connection>>>,
+        events: UnboundedSender<ConnectionEvent>,
+        from_clients: UnboundedSender<(Vec<u8>, ClientId)>,
+    ) -> Result<()> {
+        // the connection is closed if the future is dropped because the client is too slow
+        let handshake = async {
+            let connection = connecting.await?;
+            let (send, mut recv) = connection.accept_bi().await?;
+            let request = recv.read_to_end(MAX_REQUEST_BYTES).await?;
+            Ok::<_, anyhow::Error>((connection, send, request))
+        };
+        let (connection, mut send, request) =
+            tokio::time::timeout(config.handshake_timeout, handshake)
+                .await
+                .context("the client did not send its connect token in time")??;
+        let client_id = Self::authenticate(&request, &config);
+
+        let reply = match &client_id {
+            Err(reply) => *reply,
+            Ok(client_id) => {
+                let mut connections = connections.lock().unwrap();
+                if connections.contains_key(client_id) {
+                    HandshakeReply::ClientIdTaken
+                } else if connections.len() >= config.max_clients {
+                    HandshakeReply::ServerFull
+                } else {
+                    connections.insert(*client_id, connection.clone());
+                    HandshakeReply::Accepted
+                }
+            }
+        };
+        let sent = async {
+            send.write_all(&[reply as u8]).await?;
+            if let (HandshakeReply::Accepted, Ok(client_id)) = (reply, &client_id) {
+                send.write_all(&client_id.to_bits().to_le_bytes()).await?;
+            }
+            send.finish().await?;
+            Ok::<(), anyhow::Error>(())
+        };
+        let sent = tokio::time::timeout(config.handshake_timeout, sent)
+            .await
+            .unwrap_or_else(|_| {
+                Err(anyhow!(
+                    "the client did not receive the handshake reply in time"
+                ))
+            });
+        let client_id = match client_id {
+            Ok(client_id) if reply == HandshakeReply::Accepted => client_id,
+            _ => {
+                info!(
+                    "Rejected connection from {:?}: {:?}",
+                    connection.remote_address(),
+                    reply
+                );
+                connection.close(VarInt::from_u32(reply as u32), b"connection rejected");
+                return Ok(());
+            }
+        };
+        if let Err(e) = sent {
+            connections.lock().unwrap().remove(&client_id);
+            return Err(e);
+        }
+
+        info!("Client with id: {:?} connected!", client_id);
+        let _ = events.send(ConnectionEvent::Connected(client_id));
+        spawn_receive_tasks(connection.clone(), client_id, from_clients);
+
+        // wait for the connection to be closed for any reason
+        let reason = connection.closed().await;
+        info!(
+            "Client with id: {:?} disconnected! Reason: {:?}",
+            client_id, reason
+        );
+        // the client might have already been removed if the server was stopped
+        if connections.lock().unwrap().remove(&client_id).is_some() {
+            let _ = events.send(ConnectionEvent::Disconnected(client_id));
+        }
+        Ok(())
+    }
+}
+
+impl NetServer for Server {
+    fn start(&mut self) -> Result<()> {
+        let server_config = self.server_config()?;
+        let server_addr = self.config.server_addr;
+        // need to run this with Compat because it requires the tokio reactor
+        let endpoint = futures_lite::future::block_on(Compat::new(async move {
+            Endpoint::server(server_config, server_addr)
+        }))
+        .context("could not create QUIC server endpoint")?;
+
+        let (events_sender, events_receiver) = mpsc::unbounded_channel();
+        let (from_clients_sender, from_clients_receiver) = mpsc::unbounded_channel();
+        self.events = Some(events_receiver);
+        self.from_clients = Some(from_clients_receiver);
+
+        let accept_endpoint = endpoint.clone();
+        let config = Arc::new(self.config.clone());
+        let connections = self.connections.clone();
+        IoTaskPool::get()
+            .spawn(Compat::new(async move {
+                // returns None once the endpoint is closed
+                while let Some(connecting) = accept_endpoint.accept().await {
+                    let config = config.clone();
+                    let connections = connections.clone();
+                    let events_sender = events_sender.clone();
+                    let from_clients_sender = from_clients_sender.clone();
+                    IoTaskPool::get()
+                        .spawn(Compat::new(async move {
+                            if let Err(e) = Self::handle_client(
+                                connecting,
+                                config,
+                                connections,
+                                events_sender,
+                                from_clients_sender,
+                            )
+                            .await
+                            {
+                                debug!("QUIC connection failed: {:?}", e);
+                            }
+                        }))
+                        .detach();
+                }
+            }))
+            .detach();
+        self.endpoint = Some(endpoint);
+        info!("QUIC server started on {:?}", server_addr);
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        if let Some(endpoint) = self.endpoint.take() {
+            endpoint.close(VarInt::from_u32(0), b"server stopped");
+        }
+        for (client_id, _) in self.connections.lock().unwrap().drain() {
+            self.new_disconnections.push(client_id);
+        }
+        self.events = None;
+        self.from_clients = None;
+        info!("QUIC server has been stopped.");
+        Ok(())
+    }
+
+    fn disconnect(&mut self, client_id: ClientId) -> Result<()> {
+        match client_id {
+            ClientId::Quic(_) => {
+                // the client is removed (and the disconnection is reported) by the task that handles the
+                // connection, once the connection is closed
+                if let Some(connection) = self.connections.lock().unwrap().get(&client_id) {
+                    connection.close(VarInt::from_u32(0), b"disconnected by the server");
+                }
+                Ok(())
+            }
+            _ => Err(anyhow!("the client id must be of type Quic")),
+        }
+    }
+
+    fn connected_client_ids(&self) -> Vec<ClientId> {
+        self.connections.lock().unwrap().keys().cloned().collect()
+    }
+
+    fn try_update(&mut self, delta_ms: f64) -> Result<()> {
+        // reset connection events
+        self.new_connections.clear();
+        self.new_disconnections.clear();
+
+        let (Some(events), Some(from_clients)) = (self.events.as_mut(), self.from_clients.as_mut())
+        else {
+            return Err(anyhow!("the QUIC server is not started"));
+        };
+
+        // buffer incoming packets
+        // (we read the packets before the connection events, so that we never return a packet from a client
+        // whose connection has not been reported yet)
+        while let Ok((data, client_id)) = from_clients.try_recv() {
+            // get a buffer from the pool to avoid new allocations
+            let mut reader = self.buffer_pool.start_read(data.as_slice());
+            let packet = Packet::decode(&mut reader);
+            // return the buffer to the pool
+            self.buffer_pool.attach(reader);
+            match packet {
+                Ok(packet) => self.packet_queue.push_back((packet, client_id)),
+                Err(e) => error!("could not decode packet from {:?}: {:?}", client_id, e),
+            }
+        }
+
+        // process connection events
+        while let Ok(event) = events.try_recv() {
+            match event {
+                ConnectionEvent::Connected(client_id) => self.new_connections.push(client_id),
+                ConnectionEvent::Disconnected(client_id) => self.new_disconnections.push(client_id),
+            }
+        }
+        // drop the packets from clients that are not connected anymore
+        let connections = self.connections.lock().unwrap();
+        self.packet_queue
+            .retain(|(_, client_id)| connections.contains_key(client_id));
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Option<(Packet, ClientId)> {
+        self.packet_queue.pop_front()
+    }
+
+    fn send(&mut self, buf: &[u8], client_id: ClientId) -> Result<()> {
+        let connections = self.connections.lock().unwrap();
+        let connection = connections
+            .get(&client_id)
+            .context("client is not connected")?;
+        send_packet(connection, buf, false)
+    }
+
+    fn supports_reliable_send(&self) -> bool {
+        true
+    }
+
+    fn send_reliable(&mut self, buf: &[u8], client_id: ClientId) -> Result<()> {
+        let connections = self.connections.lock().unwrap();
+        let connection = connections
+            .get(&client_id)
+            .context("client is not connected")?;
+        send_packet(connection, buf, true)
+    }
+
+    fn new_connections(&self) -> Vec<ClientId> {
+        self.new_connections.clone()
+    }
+
+    fn new_disconnections(&self) -> Vec<ClientId> {
+        self.new_disconnections.clone()
+    }
+
+    fn io(&self) -> Option<&Io> {
+        None
+    }
+
+    fn io_mut(&mut self) -> Option<&mut Io> {
+        None
+    }
+}
@@ -3,6 +3,8 @@ use bevy::prelude::Resource;
 use bevy::utils::HashMap;
 
 use crate::connection::id::ClientId;
+#[cfg(all(feature = "quic", not(target_family = "wasm")))]
+use crate::connection::quic::server::QuicConfig;
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 use crate::connection::steam::server::SteamConfig;
 use crate::packet::packet::Packet;
@@ -36,11 +38,27 @@ pub trait NetServer: Send + Sync {
     /// Send a packet to one of the connected clients
     fn send(&mut self, buf: &[u8], client_id: ClientId) -> Result<()>;
 
+    /// Returns true if the connections have a reliable transport available (for example QUIC streams).
+    /// In that case, the packets that only contain messages from reliable channels are sent with
+    /// [`send_reliable`](NetServer::send_reliable)
+    fn supports_reliable_send(&self) -> bool {
+        false
+    }
+
+    /// Send a packet to one of the connected clients using the reliable transport of the connection
+    fn send_reliable(&mut self, buf: &[u8], client_id: ClientId) -> Result<()> {
+        self.send(buf, client_id)
+    }
+
     fn new_connections(&self) -> Vec<ClientId>;
 
     fn new_disconnections(&self) -> Vec<ClientId>;
 
-    fn io(&self) -> &Io;
+    /// Get immutable access to the inner io
+    fn io(&self) -> Option<&Io>;
+
+    /// Get mutable access to the inner io
+    fn io_mut(&mut self) -> Option<&mut Io>;
 }
 
 /// A wrapper around a `Box<dyn NetServer>`
@@ -61,6 +79,13 @@ pub enum NetConfig {
         config: SteamConfig,
         conditioner: Option<LinkConditionerConfig>,
     },
+    /// Accept client connections using QUIC directly, without netcode.
+    ///
+    /// Reliable channels are sent on QUIC streams, and unreliable channels as QUIC datagrams
+    #[cfg(all(feature = "quic", not(target_family = "wasm")))]
+    Quic {
+        config: QuicConfig,
+    },
 }
 
 impl Default for NetConfig {
@@ -73,10 +98,54 @@ impl Default for NetConfig {
 }
 
 impl NetConfig {
+    /// Set the fingerprint of the protocol that the server expects during the handshake
+    pub(crate) fn with_protocol_fingerprint(mut self, protocol_fingerprint: u64) -> Self {
+        match &mut self {
+            NetConfig::Netcode { config, .. } => {
+                config.protocol_fingerprint = protocol_fingerprint;
+            }
+            #[cfg(all(feature = "quic", not(target_family = "wasm")))]
+            NetConfig::Quic { config } => {
+                config.protocol_fingerprint = protocol_fingerprint;
+            }
+            // steam does not have a handshake that can carry the fingerprint
+            #[allow(unreachable_patterns)]
+            _ => {}
+        }
+        self
+    }
+
+    /// Number of bytes that the transport adds to every packet
+    pub(crate) fn transport_header_bytes(&self) -> usize {
+        match self {
+            NetConfig::Netcode { io, .. } => io.header_bytes(),
+            #[allow(unreachable_patterns)]
+            _ => 0,
+        }
+    }
+
+    /// Prevent the fragmentation of the packets if the path MTU discovery is enabled.
+    /// Only the UDP transport of netcode supports it
+    pub(crate) fn with_mtu_discovery(mut self, enabled: bool) -> Self {
+        if let (true, NetConfig::Netcode { io, .. }) = (enabled, &mut self) {
+            io.enable_mtu_discovery();
+        }
+        self
+    }
+
+    /// Returns true if the path MTU of the connections can be discovered
+    pub(crate) fn supports_mtu_discovery(&self) -> bool {
+        match self {
+            NetConfig::Netcode { io, .. } => io.supports_mtu_discovery(),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
     pub fn build_server(self) -> ServerConnection {
         match self {
             NetConfig::Netcode { config, io } => {
-                let io = io.build();
+                let io = io.build_server();
                 let server = super::netcode::Server::new(config, io);
                 ServerConnection {
                     server: Box::new(server),
@@ -96,6 +165,13 @@ impl NetConfig {
                     server: Box::new(server),
                 }
             }
+            #[cfg(all(feature = "quic", not(target_family = "wasm")))]
+            NetConfig::Quic { config } => {
+                let server = super::quic::server::Server::new(config);
+                ServerConnection {
+                    server: Box::new(server),
+                }
+            }
         }
     }
 }
@@ -129,6 +205,14 @@ impl NetServer for ServerConnection {
         self.server.send(buf, client_id)
     }
 
+    fn supports_reliable_send(&self) -> bool {
+        self.server.supports_reliable_send()
+    }
+
+    fn send_reliable(&mut self, buf: &[u8], client_id: ClientId) -> Result<()> {
+        self.server.send_reliable(buf, client_id)
+    }
+
     fn new_connections(&self) -> Vec<ClientId> {
         self.server.new_connections()
     }
@@ -137,9 +221,13 @@ impl NetServer for ServerConnection {
         self.server.new_disconnections()
     }
 
-    fn io(&self) -> &Io {
+    fn io(&self) -> Option<&Io> {
         self.server.io()
     }
+
+    fn io_mut(&mut self) -> Option<&mut Io> {
+        self.server.io_mut()
+    }
 }
 
 type ServerConnectionIdx = usize;
@@ -35,6 +35,23 @@ pub(crate) fn get_networking_options(
             NetworkingConfigValue::FakePacketReorderRecv,
             100.0,
         ));
+        options.push(NetworkingConfigEntry::new_float(
+            NetworkingConfigValue::FakePacketDupRecv,
+            conditioner.incoming_duplication * 100.0,
+        ));
+        options.push(NetworkingConfigEntry::new_float(
+            NetworkingConfigValue::FakePacketLossSend,
+            conditioner.outgoing_loss * 100.0,
+        ));
+        options.push(NetworkingConfigEntry::new_int32(
+            NetworkingConfigValue::FakePacketLagSend,
+            conditioner.outgoing_latency.as_millis() as i32,
+        ));
+        options.push(NetworkingConfigEntry::new_float(
+            NetworkingConfigValue::FakePacketDupSend,
+            conditioner.outgoing_duplication * 100.0,
+        ));
+        // NOTE: the steamworks bindings don't expose the fake rate limit, so `outgoing_bandwidth` is ignored
     }
     options
 }
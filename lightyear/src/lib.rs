@@ -17,17 +17,21 @@ You can find more information in the [book](https://cbournhonesque.github.io/lig
 // re-exports (mostly used in the derive macro crate or for internal purposes)
 #[doc(hidden)]
 pub mod _reexport {
+    pub use anyhow;
     pub use enum_delegate;
     pub use enum_dispatch::enum_dispatch;
     pub use paste::paste;
+    pub use serde;
 
     pub use lightyear_macros::{
         component_protocol_internal, message_protocol_internal, ChannelInternal,
+        ReplicateFieldsInternal,
     };
 
     pub use crate::channel::builder::TickBufferChannel;
     pub use crate::channel::builder::{
-        EntityActionsChannel, EntityUpdatesChannel, InputChannel, PingChannel,
+        AuthorityChannel, BulkControlChannel, EntityActionsChannel, EntityUpdatesChannel,
+        InputChannel, PingChannel,
     };
     pub use crate::client::interpolation::{
         add_interpolation_systems, add_prepare_interpolation_systems,
@@ -39,8 +43,10 @@ pub mod _reexport {
         ComponentBehaviour, ComponentKindBehaviour, ComponentProtocol, ComponentProtocolKind,
         FromType,
     };
+    pub use crate::protocol::layout::type_layout;
     pub use crate::protocol::message::InputMessageKind;
     pub use crate::protocol::message::{MessageKind, MessageProtocol};
+    pub use crate::protocol::schema::VariantSchema;
     pub use crate::protocol::{BitSerializable, EventContext};
     pub use crate::serialize::reader::ReadBuffer;
     pub use crate::serialize::wordbuffer::reader::ReadWordBuffer;
@@ -57,6 +63,9 @@ pub mod _reexport {
         push_component_insert_events, push_component_remove_events, push_component_update_events,
     };
     pub use crate::shared::replication::components::ShouldBeInterpolated;
+    pub use crate::shared::replication::resources::{
+        ReplicatedResource, ResourceReceivePlugin, ResourceSendPlugin,
+    };
     pub use crate::shared::replication::systems::add_per_component_replication_send_systems;
     pub use crate::shared::replication::ReplicationSend;
     pub use crate::shared::sets::{ClientMarker, ServerMarker};
@@ -67,38 +76,60 @@ pub mod _reexport {
 
 /// Prelude containing commonly used types
 pub mod prelude {
-    pub use lightyear_macros::{component_protocol, message_protocol, Channel};
+    pub use lightyear_macros::{component_protocol, message_protocol, Channel, ReplicateFields};
 
     pub use crate::channel::builder::TickBufferChannel;
     pub use crate::channel::builder::{
-        Channel, ChannelBuilder, ChannelContainer, ChannelDirection, ChannelMode, ChannelSettings,
-        DefaultUnorderedUnreliableChannel, ReliableSettings,
+        BulkSettings, Channel, ChannelBuilder, ChannelContainer, ChannelDirection, ChannelMode,
+        ChannelSettings, DeadlineSettings, DefaultUnorderedUnreliableChannel, ReliableSettings,
     };
+    pub use crate::channel::bulk::TransferProgress;
+    pub use crate::channel::error::ChannelError;
     pub use crate::client::prediction::prespawn::PreSpawnedPlayerObject;
     pub use crate::connection::id::ClientId;
     pub use crate::connection::netcode::{generate_key, Key};
     #[cfg(feature = "leafwing")]
     pub use crate::inputs::leafwing::LeafwingUserAction;
     pub use crate::inputs::native::UserAction;
-    pub use crate::packet::message::Message;
+    pub use crate::packet::channel_stats::{ChannelStats, NetworkStats};
+    pub use crate::packet::congestion::CongestionConfig;
+    pub use crate::packet::message::{Message, MessageHandle};
+    pub use crate::packet::mtu::MtuConfig;
     pub use crate::protocol::channel::{ChannelKind, ChannelRegistry};
+    pub use crate::protocol::component::ReplicateFields;
+    pub use crate::protocol::schema::{ChannelSchema, InputSchema, ProtocolSchema, VariantSchema};
     pub use crate::protocol::Protocol;
     pub use crate::protocolize;
     pub use crate::shared::config::{Mode, SharedConfig};
     pub use crate::shared::ping::manager::PingConfig;
     pub use crate::shared::plugin::{NetworkIdentity, SharedPlugin};
+    pub use crate::shared::replication::authority::{AuthorityChange, HasAuthority};
     pub use crate::shared::replication::components::{
-        NetworkTarget, PrePredicted, ReplicationGroup, ReplicationMode, ShouldBePredicted,
+        NetworkTarget, PrePredicted, ReplicateRemovePolicy, ReplicationGroup, ReplicationMode,
+        ShouldBePredicted,
     };
     pub use crate::shared::replication::entity_map::{ExternalMapper, RemoteEntityMap};
     pub use crate::shared::replication::hierarchy::ParentSync;
+    pub use crate::shared::replication::resources::{
+        ReplicateResource, ResourceInsertEvent, ResourceRemoveEvent, ResourceUpdateEvent,
+    };
     pub use crate::shared::sets::{FixedUpdateSet, MainSet};
     pub use crate::shared::tick_manager::TickManager;
     pub use crate::shared::tick_manager::{Tick, TickConfig};
     pub use crate::shared::time_manager::TimeManager;
     pub use crate::transport::config::{IoConfig, TransportConfig};
     pub use crate::transport::io::Io;
-    pub use crate::transport::middleware::conditioner::LinkConditionerConfig;
+    #[cfg(not(target_family = "wasm"))]
+    pub use crate::transport::middleware::capture::{
+        CapturedPacket, PacketCapture, PacketDirection,
+    };
+    #[cfg(feature = "compression")]
+    pub use crate::transport::middleware::compression::CompressionConfig;
+    pub use crate::transport::middleware::conditioner::{
+        BurstLossConfig, LinkConditionerConfig, LinkConditionerSettings,
+    };
+    #[cfg(not(target_family = "wasm"))]
+    pub use crate::transport::udp::UdpConfig;
 
     pub mod client {
         pub use crate::client::components::{
@@ -106,8 +137,10 @@ pub mod prelude {
         };
         pub use crate::client::config::{ClientConfig, NetcodeConfig, PacketConfig};
         pub use crate::client::events::{
-            ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent, ConnectEvent,
-            DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent, MessageEvent,
+            AuthorityEvent, ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent,
+            ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent,
+            MessageDeliveredEvent, MessageEvent, MessageLostEvent, MessageRejectedEvent,
+            ReplicationStopEvent, TransferCancelledEvent, TransferProgressEvent,
         };
         pub use crate::client::input::{InputConfig, InputManager, InputSystemSet};
         #[cfg(feature = "leafwing")]
@@ -131,16 +164,22 @@ pub mod prelude {
         pub use crate::client::replication::ReplicationConfig;
         pub use crate::client::sync::SyncConfig;
         pub use crate::connection::client::{
-            Authentication, ClientConnection, NetClient, NetConfig,
+            Authentication, ClientConnection, DisconnectReason, NetClient, NetConfig,
         };
+        #[cfg(all(feature = "quic", not(target_family = "wasm")))]
+        pub use crate::connection::quic::client::{CertificateValidation, QuicConfig};
         #[cfg(all(feature = "steam", not(target_family = "wasm")))]
         pub use crate::connection::steam::client::SteamConfig;
+        #[cfg(feature = "websocket")]
+        pub use crate::transport::websocket::{WebSocketCertificateValidation, WebSocketTlsConfig};
     }
     pub mod server {
         pub use crate::server::config::{NetcodeConfig, PacketConfig, ServerConfig};
         pub use crate::server::events::{
-            ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent, ConnectEvent,
-            DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent, MessageEvent,
+            AuthorityEvent, ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent,
+            ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent,
+            MessageDeliveredEvent, MessageEvent, MessageLostEvent, MessageRejectedEvent,
+            ReplicationStopEvent, TransferCancelledEvent, TransferProgressEvent,
         };
         pub use crate::server::plugin::{PluginConfig, ServerPlugin};
         pub use crate::server::replication::{
@@ -148,6 +187,8 @@ pub mod prelude {
         };
         pub use crate::server::room::{RoomId, RoomManager, RoomMut, RoomRef};
 
+        #[cfg(all(feature = "quic", not(target_family = "wasm")))]
+        pub use crate::connection::quic::server::QuicConfig;
         pub use crate::connection::server::{
             NetConfig, NetServer, ServerConnection, ServerConnections,
         };
@@ -155,6 +196,8 @@ pub mod prelude {
         pub use crate::connection::steam::server::SteamConfig;
         #[cfg(feature = "leafwing")]
         pub use crate::server::input_leafwing::LeafwingInputPlugin;
+        #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+        pub use crate::transport::websocket::tls::WebSocketCertificate;
         #[cfg(all(feature = "webtransport", not(target_family = "wasm")))]
         pub use wtransport::tls::Certificate;
     }
@@ -0,0 +1,234 @@
+//! Network statistics of each channel of a connection
+//!
+//! The [`MessageManager`](crate::packet::message_manager::MessageManager) of each connection counts the messages
+//! that go through each channel. The counters are cumulative: they keep growing for as long as the connection is alive.
+//!
+//! The statistics can be read from the client and server `ConnectionManager`s, and are also published
+//! as Bevy [`Diagnostic`]s (see [`ChannelDiagnostics`]).
+use std::collections::HashMap;
+
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::{App, Real, Res, Time};
+use bevy::utils::Duration;
+use derive_more::{Add, AddAssign};
+
+use crate::protocol::channel::{ChannelKind, ChannelRegistry};
+use crate::transport::io::IoDiagnosticsPlugin;
+
+/// Counters of the messages sent and received on a channel
+#[derive(Default, Copy, Clone, Debug, PartialEq, Add, AddAssign)]
+pub struct ChannelStats {
+    /// Number of messages written in packets, including resends.
+    /// Each fragment of a fragmented message counts as one message.
+    pub messages_sent: u64,
+    /// Number of bytes of the messages written in packets (excluding the packet headers)
+    pub bytes_sent: u64,
+    /// Number of fragments written in packets, including resends
+    pub fragments_sent: u64,
+    /// Number of messages (or fragments) that were sent again because they were not acked in time
+    pub resends: u64,
+    /// Number of unreliable messages that were ready to be sent, but were discarded because the bandwidth budget
+    /// was used by messages with a higher priority (they are only discarded after waiting for longer than the
+    /// `max_buffered_age` of the channel). The reliable messages that could not be sent are sent again later,
+    /// so they are not counted.
+    pub messages_dropped: u64,
+    /// Number of messages received, where each fragment of a fragmented message counts as one message
+    pub messages_received: u64,
+    /// Number of bytes of the messages received
+    pub bytes_received: u64,
+    /// Number of fragments received
+    pub fragments_received: u64,
+    /// Number of messages (or fragments) acked by the remote. Only channels that watch acks are counted.
+    pub messages_acked: u64,
+    /// Sum of the durations between the moment a message was sent and the moment it was acked
+    pub total_time_to_ack: Duration,
+}
+
+impl ChannelStats {
+    /// Average duration between the moment a message was sent and the moment it was acked
+    /// (from the last time it was sent, if it was resent)
+    pub fn average_time_to_ack(&self) -> Option<Duration> {
+        if self.messages_acked == 0 {
+            return None;
+        }
+        Some(self.total_time_to_ack / self.messages_acked as u32)
+    }
+
+    /// Difference with older statistics. The statistics can go down on the server, when a client disconnects.
+    fn saturating_sub(&self, other: &Self) -> Self {
+        Self {
+            messages_sent: self.messages_sent.saturating_sub(other.messages_sent),
+            bytes_sent: self.bytes_sent.saturating_sub(other.bytes_sent),
+            fragments_sent: self.fragments_sent.saturating_sub(other.fragments_sent),
+            resends: self.resends.saturating_sub(other.resends),
+            messages_dropped: self.messages_dropped.saturating_sub(other.messages_dropped),
+            messages_received: self
+                .messages_received
+                .saturating_sub(other.messages_received),
+            bytes_received: self.bytes_received.saturating_sub(other.bytes_received),
+            fragments_received: self
+                .fragments_received
+                .saturating_sub(other.fragments_received),
+            messages_acked: self.messages_acked.saturating_sub(other.messages_acked),
+            total_time_to_ack: self
+                .total_time_to_ack
+                .saturating_sub(other.total_time_to_ack),
+        }
+    }
+}
+
+/// Statistics of all the channels of a connection
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct NetworkStats {
+    channels: HashMap<ChannelKind, ChannelStats>,
+}
+
+impl NetworkStats {
+    /// Statistics of a single channel
+    pub fn channel(&self, channel_kind: ChannelKind) -> ChannelStats {
+        self.channels
+            .get(&channel_kind)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Statistics of every channel that was used
+    pub fn channels(&self) -> impl Iterator<Item = (&ChannelKind, &ChannelStats)> {
+        self.channels.iter()
+    }
+
+    /// Statistics of the whole connection (the sum over all the channels)
+    pub fn total(&self) -> ChannelStats {
+        self.channels
+            .values()
+            .fold(ChannelStats::default(), |total, stats| total + *stats)
+    }
+
+    pub(crate) fn channel_mut(&mut self, channel_kind: ChannelKind) -> &mut ChannelStats {
+        self.channels.entry(channel_kind).or_default()
+    }
+
+    /// Add the statistics of another connection (to aggregate the statistics of all the clients on the server)
+    pub(crate) fn merge(&mut self, other: &NetworkStats) {
+        for (channel_kind, stats) in other.channels() {
+            *self.channel_mut(*channel_kind) += *stats;
+        }
+    }
+}
+
+/// Publishes the [`NetworkStats`] of each channel as Bevy [`Diagnostic`]s.
+///
+/// The paths of the diagnostics are `{side}/channel/{channel name}/{measurement}`, where side is `client` or
+/// `server` (on the server, the statistics of all the clients are added together).
+pub struct ChannelDiagnostics;
+
+impl ChannelDiagnostics {
+    /// KB of messages sent per second
+    pub const KB_SENT: &'static str = "KB sent per second";
+    /// KB of messages received per second
+    pub const KB_RECEIVED: &'static str = "KB received per second";
+    /// Messages resent per second
+    pub const RESENDS: &'static str = "resends per second";
+    /// Messages dropped by the priority filter per second
+    pub const DROPPED: &'static str = "messages dropped per second";
+    /// Average time-to-ack of the messages acked since the previous update, in milliseconds
+    pub const TIME_TO_ACK: &'static str = "time to ack (ms)";
+
+    const MEASUREMENTS: [&'static str; 5] = [
+        Self::KB_SENT,
+        Self::KB_RECEIVED,
+        Self::RESENDS,
+        Self::DROPPED,
+        Self::TIME_TO_ACK,
+    ];
+
+    /// Path of the diagnostic of a channel
+    pub fn path(side: &str, channel_name: &str, measurement: &str) -> DiagnosticPath {
+        DiagnosticPath::new(format!("{side}/channel/{channel_name}/{measurement}"))
+    }
+
+    /// Register the diagnostics of every channel of the registry
+    pub(crate) fn register(app: &mut App, side: &str, channel_registry: &ChannelRegistry) {
+        for channel_name in channel_registry.names() {
+            for measurement in Self::MEASUREMENTS {
+                app.register_diagnostic(
+                    Diagnostic::new(Self::path(side, channel_name, measurement))
+                        .with_max_history_length(IoDiagnosticsPlugin::DIAGNOSTIC_HISTORY_LEN),
+                );
+            }
+        }
+    }
+
+    /// Add a measurement for each channel, computed from the difference between the current statistics
+    /// and the statistics at the previous update
+    pub(crate) fn update_diagnostics(
+        side: &str,
+        stats: &NetworkStats,
+        previous: &mut NetworkStats,
+        channel_registry: &ChannelRegistry,
+        time: &Res<Time<Real>>,
+        diagnostics: &mut Diagnostics,
+    ) {
+        let delta_seconds = time.delta_seconds_f64();
+        if delta_seconds == 0.0 {
+            return;
+        }
+        for (channel_kind, channel_stats) in stats.channels() {
+            let Some(channel_name) = channel_registry.name(channel_kind) else {
+                continue;
+            };
+            let delta = channel_stats.saturating_sub(&previous.channel(*channel_kind));
+            diagnostics.add_measurement(&Self::path(side, channel_name, Self::KB_SENT), || {
+                (delta.bytes_sent as f64 / 1000.0) / delta_seconds
+            });
+            diagnostics.add_measurement(&Self::path(side, channel_name, Self::KB_RECEIVED), || {
+                (delta.bytes_received as f64 / 1000.0) / delta_seconds
+            });
+            diagnostics.add_measurement(&Self::path(side, channel_name, Self::RESENDS), || {
+                delta.resends as f64 / delta_seconds
+            });
+            diagnostics.add_measurement(&Self::path(side, channel_name, Self::DROPPED), || {
+                delta.messages_dropped as f64 / delta_seconds
+            });
+            if let Some(time_to_ack) = delta.average_time_to_ack() {
+                diagnostics
+                    .add_measurement(&Self::path(side, channel_name, Self::TIME_TO_ACK), || {
+                        time_to_ack.as_secs_f64() * 1000.0
+                    });
+            }
+        }
+        *previous = stats.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_stats() {
+        let channel = ChannelKind::from(std::any::TypeId::of::<u8>());
+        let other_channel = ChannelKind::from(std::any::TypeId::of::<u16>());
+        let mut stats = NetworkStats::default();
+        assert_eq!(stats.channel(channel), ChannelStats::default());
+        assert_eq!(stats.channel(channel).average_time_to_ack(), None);
+
+        let channel_stats = stats.channel_mut(channel);
+        channel_stats.bytes_sent += 100;
+        channel_stats.messages_acked += 2;
+        channel_stats.total_time_to_ack += Duration::from_millis(60);
+        stats.channel_mut(other_channel).bytes_sent += 50;
+
+        assert_eq!(
+            stats.channel(channel).average_time_to_ack(),
+            Some(Duration::from_millis(30))
+        );
+        assert_eq!(stats.total().bytes_sent, 150);
+
+        // the statistics of several connections can be added together
+        let mut aggregated = NetworkStats::default();
+        aggregated.merge(&stats);
+        aggregated.merge(&stats);
+        assert_eq!(aggregated.channel(other_channel).bytes_sent, 100);
+    }
+}
@@ -0,0 +1,375 @@
+//! Congestion control
+//!
+//! Each connection estimates the bandwidth that the path can carry, and uses it as the budget of the
+//! priority filter: messages that do not fit in the budget are not sent this time (see the bandwidth management
+//! section of the book).
+//!
+//! The estimate follows an AIMD (additive increase, multiplicative decrease) scheme:
+//! - every [`CongestionConfig::update_interval`], the connection looks for signs of congestion:
+//!   - the packet loss is above [`CongestionConfig::loss_threshold`]
+//!   - the RTT grew above the smallest RTT observed during the last [`CongestionConfig::min_rtt_window`], by more than
+//!     [`CongestionConfig::max_queuing_delay`] (or twice the jitter, if it is bigger). This means that the packets
+//!     are waiting in the queues of the routers, which happens before they start getting dropped. The smallest RTT
+//!     is only tracked over a window so that the connection adapts when the route changes to a longer one.
+//! - if the link is congested, the bandwidth is multiplied by [`CongestionConfig::decrease_factor`]
+//!   (at most once per [`CongestionConfig::decrease_cooldown`], to let the previous decrease take effect)
+//! - otherwise the bandwidth grows by [`CongestionConfig::additive_increase`] bytes per second, every second
+//!
+//! A packet is considered lost as soon as a packet sent a few packets later is acked, so the loss is detected
+//! within one RTT.
+//!
+//! Every decrease also doubles the delay before the reliable channels resend a message that was not acked
+//! (up to [`MAX_RESEND_BACKOFF`] times the delay given by their `rtt_resend_factor`), so that the resends don't make
+//! the congestion worse. The delay goes back to normal as soon as the link is not congested anymore.
+//!
+//! The bandwidth always stays between [`CongestionConfig::min_bandwidth`] and [`CongestionConfig::max_bandwidth`].
+use std::collections::VecDeque;
+
+use bevy::reflect::Reflect;
+use bevy::utils::Duration;
+use tracing::{debug, trace};
+
+use crate::_reexport::WrappedTime;
+
+/// Maximum factor applied to the resend delay of the reliable channels while the link is congested
+pub const MAX_RESEND_BACKOFF: f32 = 8.0;
+
+/// Configuration of the congestion control of each connection
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub struct CongestionConfig {
+    /// If false, the bandwidth budget is not adjusted to the state of the network
+    /// (the static bandwidth cap is used if it is enabled)
+    pub enabled: bool,
+    /// Bandwidth (in bytes per second) used when the connection starts
+    pub initial_bandwidth: u32,
+    /// The bandwidth never goes below this value (in bytes per second).
+    ///
+    /// It cannot be smaller than the largest packet size ([`MAX_MTU`](crate::packet::mtu::MAX_MTU)).
+    pub min_bandwidth: u32,
+    /// The bandwidth never goes above this value (in bytes per second)
+    pub max_bandwidth: u32,
+    /// Number of bytes per second added to the bandwidth every second, while the link is not congested
+    pub additive_increase: u32,
+    /// Factor applied to the bandwidth when the link is congested
+    pub decrease_factor: f32,
+    /// The link is considered congested if the ratio of lost packets is above this threshold
+    pub loss_threshold: f32,
+    /// The link is considered congested if the RTT is above the smallest RTT observed by more than this delay
+    /// (or by more than twice the jitter, if it is bigger)
+    pub max_queuing_delay: Duration,
+    /// The smallest RTT is the minimum of the RTTs observed during this duration
+    pub min_rtt_window: Duration,
+    /// Minimum duration between two decreases of the bandwidth
+    pub decrease_cooldown: Duration,
+    /// Duration between two updates of the bandwidth estimate
+    pub update_interval: Duration,
+}
+
+impl Default for CongestionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            // 56 KB/s, same as the default bandwidth cap
+            initial_bandwidth: 56000,
+            min_bandwidth: 10000,
+            max_bandwidth: 1_000_000,
+            additive_increase: 8000,
+            decrease_factor: 0.7,
+            loss_threshold: 0.05,
+            max_queuing_delay: Duration::from_millis(50),
+            min_rtt_window: Duration::from_secs(10),
+            decrease_cooldown: Duration::from_secs(1),
+            update_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+impl CongestionConfig {
+    pub fn enable(mut self) -> Self {
+        self.enabled = true;
+        self
+    }
+
+    pub fn with_initial_bandwidth(mut self, bytes_per_second: u32) -> Self {
+        self.initial_bandwidth = bytes_per_second;
+        self
+    }
+
+    pub fn with_min_bandwidth(mut self, bytes_per_second: u32) -> Self {
+        self.min_bandwidth = bytes_per_second;
+        self
+    }
+
+    pub fn with_max_bandwidth(mut self, bytes_per_second: u32) -> Self {
+        self.max_bandwidth = bytes_per_second;
+        self
+    }
+
+    pub fn with_additive_increase(mut self, bytes_per_second: u32) -> Self {
+        self.additive_increase = bytes_per_second;
+        self
+    }
+
+    pub fn with_decrease_factor(mut self, decrease_factor: f32) -> Self {
+        self.decrease_factor = decrease_factor;
+        self
+    }
+
+    pub fn with_loss_threshold(mut self, loss_threshold: f32) -> Self {
+        self.loss_threshold = loss_threshold;
+        self
+    }
+
+    pub fn with_max_queuing_delay(mut self, max_queuing_delay: Duration) -> Self {
+        self.max_queuing_delay = max_queuing_delay;
+        self
+    }
+
+    pub fn with_min_rtt_window(mut self, min_rtt_window: Duration) -> Self {
+        self.min_rtt_window = min_rtt_window;
+        self
+    }
+}
+
+/// Network conditions observed by the connection, used to detect congestion
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct NetworkConditions {
+    /// Ratio of the packets sent recently that were lost
+    pub(crate) packet_loss: f32,
+    pub(crate) rtt: Duration,
+    pub(crate) jitter: Duration,
+}
+
+/// Estimates the bandwidth available on a connection
+#[derive(Debug)]
+pub(crate) struct CongestionController {
+    config: CongestionConfig,
+    /// Current estimate of the bandwidth, in bytes per second
+    bandwidth: u32,
+    /// RTTs observed during the last [`CongestionConfig::min_rtt_window`], in increasing order: the first one
+    /// is the smallest RTT, i.e. the RTT when the queues of the path are empty
+    rtt_samples: VecDeque<(WrappedTime, Duration)>,
+    /// Factor applied to the resend delay of the reliable channels
+    resend_backoff: f32,
+    last_update: Option<WrappedTime>,
+    last_decrease: Option<WrappedTime>,
+}
+
+impl CongestionController {
+    pub(crate) fn new(mut config: CongestionConfig) -> Self {
+        config.min_bandwidth = config.min_bandwidth.max(crate::packet::mtu::MAX_MTU as u32);
+        config.max_bandwidth = config.max_bandwidth.max(config.min_bandwidth);
+        Self {
+            bandwidth: config
+                .initial_bandwidth
+                .clamp(config.min_bandwidth, config.max_bandwidth),
+            config,
+            rtt_samples: VecDeque::new(),
+            resend_backoff: 1.0,
+            last_update: None,
+            last_decrease: None,
+        }
+    }
+
+    /// Current estimate of the bandwidth, in bytes per second
+    pub(crate) fn bandwidth(&self) -> u32 {
+        self.bandwidth
+    }
+
+    pub(crate) fn min_bandwidth(&self) -> u32 {
+        self.config.min_bandwidth
+    }
+
+    pub(crate) fn max_bandwidth(&self) -> u32 {
+        self.config.max_bandwidth
+    }
+
+    /// Factor to apply to the resend delay of the reliable channels, to resend less often while the link is congested
+    pub(crate) fn resend_backoff(&self) -> f32 {
+        self.resend_backoff
+    }
+
+    /// Update the bandwidth estimate with the latest network conditions.
+    ///
+    /// Returns the new bandwidth if it changed.
+    pub(crate) fn update(
+        &mut self,
+        current_time: WrappedTime,
+        conditions: NetworkConditions,
+    ) -> Option<u32> {
+        let Some(last_update) = self.last_update else {
+            self.last_update = Some(current_time);
+            return None;
+        };
+        let elapsed = current_time - last_update;
+        if elapsed < chrono::Duration::from_std(self.config.update_interval).unwrap() {
+            return None;
+        }
+        self.last_update = Some(current_time);
+
+        let min_rtt = self.update_min_rtt(current_time, conditions.rtt);
+        let queuing_delay = conditions.rtt.saturating_sub(min_rtt);
+        let max_queuing_delay = self.config.max_queuing_delay.max(conditions.jitter * 2);
+        let congested = conditions.packet_loss > self.config.loss_threshold
+            || queuing_delay > max_queuing_delay;
+        trace!(
+            ?conditions,
+            ?min_rtt,
+            ?queuing_delay,
+            congested,
+            "updating congestion control"
+        );
+
+        let previous_bandwidth = self.bandwidth;
+        if congested {
+            let cooldown = chrono::Duration::from_std(self.config.decrease_cooldown).unwrap();
+            if self
+                .last_decrease
+                .is_some_and(|last_decrease| current_time - last_decrease < cooldown)
+            {
+                return None;
+            }
+            self.last_decrease = Some(current_time);
+            self.bandwidth = (self.bandwidth as f32 * self.config.decrease_factor) as u32;
+            self.resend_backoff = (self.resend_backoff * 2.0).min(MAX_RESEND_BACKOFF);
+        } else {
+            self.resend_backoff = 1.0;
+            let increase = self.config.additive_increase as f32
+                * elapsed.num_microseconds().unwrap_or(i64::MAX) as f32
+                / 1_000_000.0;
+            self.bandwidth = self.bandwidth.saturating_add(increase as u32);
+        }
+        self.bandwidth = self
+            .bandwidth
+            .clamp(self.config.min_bandwidth, self.config.max_bandwidth);
+        if self.bandwidth == previous_bandwidth {
+            return None;
+        }
+        if congested {
+            debug!(
+                bandwidth = self.bandwidth,
+                ?conditions,
+                "congestion detected, reducing the bandwidth"
+            );
+        }
+        Some(self.bandwidth)
+    }
+
+    /// Add a RTT sample, and return the smallest RTT observed during the window
+    fn update_min_rtt(&mut self, current_time: WrappedTime, rtt: Duration) -> Duration {
+        // the samples that are bigger than the new one can never be the minimum again
+        while self
+            .rtt_samples
+            .back()
+            .is_some_and(|(_, sample)| *sample >= rtt)
+        {
+            self.rtt_samples.pop_back();
+        }
+        self.rtt_samples.push_back((current_time, rtt));
+        let window = chrono::Duration::from_std(self.config.min_rtt_window).unwrap();
+        while self
+            .rtt_samples
+            .front()
+            .is_some_and(|(time, _)| current_time - *time > window)
+        {
+            self.rtt_samples.pop_front();
+        }
+        // the new sample is always in the window
+        self.rtt_samples.front().unwrap().1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conditions(packet_loss: f32, rtt_ms: u64) -> NetworkConditions {
+        NetworkConditions {
+            packet_loss,
+            rtt: Duration::from_millis(rtt_ms),
+            jitter: Duration::from_millis(5),
+        }
+    }
+
+    #[test]
+    fn test_congestion_controller() {
+        let config = CongestionConfig::default()
+            .enable()
+            .with_initial_bandwidth(20000)
+            .with_min_bandwidth(10000)
+            .with_max_bandwidth(40000)
+            .with_additive_increase(20000)
+            .with_decrease_factor(0.5);
+        let mut controller = CongestionController::new(config);
+        let mut time = WrappedTime::default();
+        let step = Duration::from_millis(100);
+        assert_eq!(controller.update(time, conditions(0.0, 50)), None);
+        assert_eq!(controller.bandwidth(), 20000);
+
+        // no congestion: the bandwidth grows linearly
+        time += step;
+        assert_eq!(controller.update(time, conditions(0.0, 50)), Some(22000));
+        // nothing happens until the next update interval
+        assert_eq!(
+            controller.update(time + Duration::from_millis(10), conditions(0.0, 50)),
+            None
+        );
+
+        // packet loss: the bandwidth is reduced
+        time += step;
+        assert_eq!(controller.update(time, conditions(0.1, 50)), Some(11000));
+        // and the reliable channels wait longer before resending
+        assert_eq!(controller.resend_backoff(), 2.0);
+        // not reduced again during the cooldown
+        time += step;
+        assert_eq!(controller.update(time, conditions(0.1, 50)), None);
+
+        // the RTT increases well above the minimum RTT: the bandwidth is reduced,
+        // but it never goes below the minimum
+        time += Duration::from_secs(1);
+        assert_eq!(controller.update(time, conditions(0.0, 150)), Some(10000));
+        time += Duration::from_secs(1);
+        assert_eq!(controller.update(time, conditions(0.0, 150)), None);
+        assert_eq!(controller.resend_backoff(), MAX_RESEND_BACKOFF);
+
+        // small variations of the RTT are not considered as congestion
+        time += Duration::from_secs(1);
+        assert_eq!(controller.update(time, conditions(0.0, 80)), Some(30000));
+        assert_eq!(controller.resend_backoff(), 1.0);
+        // and the bandwidth never goes above the maximum
+        time += Duration::from_secs(1);
+        assert_eq!(controller.update(time, conditions(0.0, 80)), Some(40000));
+        time += step;
+        assert_eq!(controller.update(time, conditions(0.0, 80)), None);
+    }
+
+    #[test]
+    fn test_min_rtt_window() {
+        let config = CongestionConfig::default()
+            .enable()
+            .with_initial_bandwidth(20000)
+            .with_min_bandwidth(10000)
+            .with_max_bandwidth(40000)
+            .with_additive_increase(20000)
+            .with_decrease_factor(0.5)
+            .with_min_rtt_window(Duration::from_secs(5));
+        let mut controller = CongestionController::new(config);
+        let mut time = WrappedTime::default();
+        assert_eq!(controller.update(time, conditions(0.0, 50)), None);
+        time += Duration::from_millis(100);
+        assert_eq!(controller.update(time, conditions(0.0, 50)), Some(22000));
+
+        // the route changes to a longer one: the RTT increase is considered as congestion at first
+        time += Duration::from_secs(1);
+        assert_eq!(controller.update(time, conditions(0.0, 150)), Some(11000));
+
+        // once the samples of the old route leave the window, the longer RTT becomes the new minimum
+        for _ in 0..4 {
+            time += Duration::from_secs(1);
+            controller.update(time, conditions(0.0, 150));
+        }
+        time += Duration::from_secs(1);
+        assert_eq!(controller.update(time, conditions(0.0, 150)), Some(30000));
+        assert_eq!(controller.resend_backoff(), 1.0);
+    }
+}
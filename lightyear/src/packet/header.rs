@@ -50,6 +50,10 @@ const ACK_BITFIELD_SIZE: u8 = 32;
 // we can only buffer up to `MAX_SEND_PACKET_QUEUE_SIZE` packets for sending
 const MAX_SEND_PACKET_QUEUE_SIZE: u8 = 255;
 const CLEAR_UNACKED_PACKETS_DELAY: chrono::Duration = chrono::Duration::milliseconds(5000);
+/// When the early loss detection is enabled, a sent packet is considered lost as soon as a packet sent this many
+/// packets later was acked, instead of waiting for [`CLEAR_UNACKED_PACKETS_DELAY`]. (the remote acks the packets
+/// as soon as it receives them, so a gap in the acks means that the packet was lost, unless it was reordered)
+const PACKET_REORDERING_THRESHOLD: i16 = 3;
 
 /// Keeps track of sent and received packets to be able to write the packet headers correctly
 /// For more information: [GafferOnGames](https://gafferongames.com/post/reliability_ordering_and_congestion_avoidance_over_udp/)
@@ -62,6 +66,15 @@ pub struct PacketHeaderManager {
     // so we can resend them when dropped
     // sent_packets_not_acked: HashSet<PacketId>,
     sent_packets_not_acked: HashMap<PacketId, WrappedTime>,
+    /// Most recent of our packets that was acked by the remote
+    last_acked_packet_id: Option<PacketId>,
+    /// Packets that were considered lost since the last call to [`take_lost_packets`](Self::take_lost_packets)
+    lost_packets: Vec<PacketId>,
+    /// Consider the packets lost as soon as more recent packets are acked (used by the congestion control)
+    early_loss_detection: bool,
+    /// Packets that were considered lost because more recent packets were acked, but whose ack can still arrive
+    /// (if they were only reordered or delayed) until [`CLEAR_UNACKED_PACKETS_DELAY`]
+    early_lost_packets: HashMap<PacketId, WrappedTime>,
     stats_manager: PacketStatsManager,
 
     // channel to notify the sender of the packet_id of the packets that were delivered
@@ -84,6 +97,10 @@ impl PacketHeaderManager {
             stats_manager: PacketStatsManager::default(),
             // sent_packets_not_acked: HashSet::with_capacity(MAX_SEND_PACKET_QUEUE_SIZE as usize),
             sent_packets_not_acked: HashMap::new(),
+            last_acked_packet_id: None,
+            lost_packets: Vec::new(),
+            early_loss_detection: false,
+            early_lost_packets: HashMap::new(),
             recv_buffer: ReceiveBuffer::new(),
             // ack_notification_sender,
             // ack_notification_receiver,
@@ -99,10 +116,45 @@ impl PacketHeaderManager {
             if self.current_time - (*time_sent) > CLEAR_UNACKED_PACKETS_DELAY {
                 trace!("sent packet got lost");
                 self.stats_manager.sent_packet_lost();
+                self.lost_packets.push(*packet_id);
                 return false;
             }
             true
         });
+        // stop waiting for the late acks of the packets that were already considered lost
+        // (they are reported again, so that we can forget about them)
+        self.early_lost_packets.retain(|packet_id, time_sent| {
+            if self.current_time - (*time_sent) > CLEAR_UNACKED_PACKETS_DELAY {
+                self.lost_packets.push(*packet_id);
+                return false;
+            }
+            true
+        });
+    }
+
+    /// Consider the packets lost as soon as more recent packets are acked, instead of waiting for a delay
+    pub(crate) fn enable_early_loss_detection(&mut self) {
+        self.early_loss_detection = true;
+    }
+
+    /// Return the packets that were considered lost (not acked after a delay, or not acked while
+    /// more recent packets were) since the last call.
+    ///
+    /// A packet that was considered lost because more recent packets were acked can still be acked
+    /// later (see [`is_waiting_for_late_ack`](Self::is_waiting_for_late_ack)): it is returned a second time
+    /// once we stop waiting for its ack.
+    pub(crate) fn take_lost_packets(&mut self) -> Vec<PacketId> {
+        std::mem::take(&mut self.lost_packets)
+    }
+
+    /// Returns true if the packet was considered lost, but its ack can still arrive
+    pub(crate) fn is_waiting_for_late_ack(&self, packet_id: &PacketId) -> bool {
+        self.early_lost_packets.contains_key(packet_id)
+    }
+
+    /// Ratio of the packets we sent recently that were lost
+    pub(crate) fn packet_loss(&self) -> f32 {
+        self.stats_manager.packet_loss()
     }
 
     // /// Get the receiver for the ack notification channel
@@ -141,6 +193,12 @@ impl PacketHeaderManager {
         if let Some(packet) = self.update_sent_packets_not_acked(&header.last_ack_packet_id) {
             self.stats_manager.sent_packet_acked();
             newly_acked_packets.push(packet);
+            if self
+                .last_acked_packet_id
+                .map_or(true, |last_acked| packet > last_acked)
+            {
+                self.last_acked_packet_id = Some(packet);
+            }
         }
         for i in 1..=ACK_BITFIELD_SIZE {
             let packet_id = PacketId(header.last_ack_packet_id.wrapping_sub(i as u16));
@@ -151,9 +209,30 @@ impl PacketHeaderManager {
                 }
             }
         }
+        if self.early_loss_detection {
+            self.detect_lost_packets();
+        }
         newly_acked_packets
     }
 
+    /// Consider that the packets that were sent well before the most recent acked packet, and that are still not
+    /// acked, are lost. This detects the loss within one RTT, instead of after a fixed timeout.
+    fn detect_lost_packets(&mut self) {
+        let Some(last_acked) = self.last_acked_packet_id else {
+            return;
+        };
+        self.sent_packets_not_acked.retain(|packet_id, time_sent| {
+            if last_acked - *packet_id >= PACKET_REORDERING_THRESHOLD {
+                trace!(?packet_id, "sent packet got lost");
+                self.stats_manager.sent_packet_lost();
+                self.lost_packets.push(*packet_id);
+                self.early_lost_packets.insert(*packet_id, *time_sent);
+                return false;
+            }
+            true
+        });
+    }
+
     /// Update the list of sent packets that have not been acked yet
     /// when we receive confirmation that packet_id was delivered
     ///
@@ -168,6 +247,11 @@ impl PacketHeaderManager {
             self.sent_packets_not_acked.remove(packet_id);
             return Some(*packet_id);
         }
+        // the packet was considered lost too early: it was only delayed
+        if self.early_lost_packets.remove(packet_id).is_some() {
+            trace!(?packet_id, "sent packet considered lost was acked");
+            return Some(*packet_id);
+        }
         None
     }
 
@@ -341,6 +425,66 @@ mod tests {
         assert_eq!(recv_buffer.get_bitfield(), 1 << (32 - 1));
     }
 
+    #[test]
+    fn test_detect_lost_packets_from_ack_gaps() {
+        let mut manager = PacketHeaderManager::new();
+        manager.enable_early_loss_detection();
+        for _ in 0..6 {
+            manager.prepare_send_packet_header(PacketType::Data);
+        }
+
+        // the remote received the packets 2, 3 and 4, but not 0 and 1
+        let header = PacketHeader {
+            packet_type: PacketType::Data,
+            packet_id: PacketId(0),
+            last_ack_packet_id: PacketId(4),
+            ack_bitfield: 0b11,
+            tick: Tick(0),
+        };
+        let mut acked = manager.process_recv_packet_header(&header);
+        acked.sort();
+        assert_eq!(acked, vec![PacketId(2), PacketId(3), PacketId(4)]);
+
+        // the packets sent well before the last acked packet are lost, without waiting for the timeout;
+        // the packet 5 was sent after the last acked packet so it could still arrive
+        let mut lost = manager.take_lost_packets();
+        lost.sort();
+        assert_eq!(lost, vec![PacketId(0), PacketId(1)]);
+        assert_eq!(manager.sent_packets_not_acked().len(), 1);
+        assert!(manager.is_waiting_for_late_ack(&PacketId(1)));
+
+        // the packet 1 was only delayed: its ack is still delivered
+        let header = PacketHeader {
+            last_ack_packet_id: PacketId(5),
+            ack_bitfield: 0b1111,
+            ..header
+        };
+        let acked = manager.process_recv_packet_header(&header);
+        assert_eq!(acked, vec![PacketId(5), PacketId(1)]);
+        assert!(!manager.is_waiting_for_late_ack(&PacketId(1)));
+        assert!(manager.take_lost_packets().is_empty());
+    }
+
+    #[test]
+    fn test_no_early_loss_detection_by_default() {
+        let mut manager = PacketHeaderManager::new();
+        for _ in 0..6 {
+            manager.prepare_send_packet_header(PacketType::Data);
+        }
+        let header = PacketHeader {
+            packet_type: PacketType::Data,
+            packet_id: PacketId(0),
+            last_ack_packet_id: PacketId(4),
+            ack_bitfield: 0b11,
+            tick: Tick(0),
+        };
+        manager.process_recv_packet_header(&header);
+
+        // the packets are only considered lost after the timeout
+        assert!(manager.take_lost_packets().is_empty());
+        assert_eq!(manager.sent_packets_not_acked().len(), 3);
+    }
+
     #[test]
     fn test_serde_header() -> anyhow::Result<()> {
         let header = PacketHeader {
@@ -4,7 +4,6 @@ use bytes::Bytes;
 
 use bitcode::encoding::{Fixed, Gamma};
 
-use crate::packet::packet::FRAGMENT_SIZE;
 use crate::protocol::{BitSerializable, EventContext};
 use crate::serialize::reader::ReadBuffer;
 use crate::serialize::writer::WriteBuffer;
@@ -21,6 +20,23 @@ use bevy::ecs::entity::MapEntities;
 // Internal id that we assign to each message sent over the network
 wrapping_id!(MessageId);
 
+/// Handle returned when a message is buffered for sending.
+///
+/// The same handle is included in the [`MessageDeliveredEvent`](crate::shared::events::components::MessageDeliveredEvent)
+/// or [`MessageLostEvent`](crate::shared::events::components::MessageLostEvent) emitted for that message,
+/// so that you can know which of your messages were received by the remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, PartialOrd, Ord)]
+pub struct MessageHandle(pub(crate) u64);
+
+impl MessageHandle {
+    /// Return the current handle, and advance to the next one
+    pub(crate) fn next(&mut self) -> Self {
+        let handle = *self;
+        self.0 += 1;
+        handle
+    }
+}
+
 // TODO: for now messages must be able to be used as events, since we output them in our message events
 /// A [`Message`] is basically any type that can be (de)serialized over the network.
 ///
@@ -184,16 +200,10 @@ impl FragmentData {
         writer.encode(&self.fragment_id, Gamma)?;
         writer.encode(&self.num_fragments, Gamma)?;
         // TODO: be able to just concat the bytes to the buffer?
-        if self.is_last_fragment() {
-            // writing the slice includes writing the length of the slice
-            writer.encode(self.bytes.as_ref(), Fixed)?;
-            // writer.serialize(&self.bytes.to_vec());
-            // writer.serialize(&self.fragment_message_bytes.as_ref());
-        } else {
-            let bytes_array: [u8; FRAGMENT_SIZE] = self.bytes.as_ref().try_into().unwrap();
-            // Serde does not handle arrays well (https://github.com/serde-rs/serde/issues/573)
-            writer.encode(&bytes_array, Fixed)?;
-        }
+        // writing the slice includes writing the length of the slice.
+        // We need it even if this is not the last fragment, because the fragment size depends on the MTU
+        // of the sender
+        writer.encode(self.bytes.as_ref(), Fixed)?;
         let num_bits_written = writer.num_bits_written() - num_bits_before;
         Ok(num_bits_written)
     }
@@ -206,24 +216,9 @@ impl FragmentData {
         let tick = reader.decode::<Option<Tick>>(Fixed)?;
         let fragment_id = reader.decode::<FragmentIndex>(Gamma)?;
         let num_fragments = reader.decode::<FragmentIndex>(Gamma)?;
-        let bytes = if fragment_id == num_fragments - 1 {
-            // let num_bytes = reader.decode::<usize>(Gamma)?;
-            // let num_bytes_non_zero = std::num::NonZeroUsize::new(num_bytes)
-            //     .ok_or_else(|| anyhow::anyhow!("num_bytes is 0"))?;
-            // let read_bytes = reader.read_bytes(num_bytes_non_zero)?;
-            // reader.
-            // let read_bytes = reader.decode::<&[u8]>()?;
-            // TODO: avoid the extra copy
-            //  - maybe have the encoding of bytes be
-            let read_bytes = reader.decode::<Vec<u8>>(Fixed)?;
-            Bytes::from(read_bytes)
-        } else {
-            // Serde does not handle arrays well (https://github.com/serde-rs/serde/issues/573)
-            let read_bytes = reader.decode::<[u8; FRAGMENT_SIZE]>(Fixed)?;
-            // TODO: avoid the extra copy
-            let bytes_vec: Vec<u8> = read_bytes.to_vec();
-            Bytes::from(bytes_vec)
-        };
+        // TODO: avoid the extra copy
+        let read_bytes = reader.decode::<Vec<u8>>(Fixed)?;
+        let bytes = Bytes::from(read_bytes);
         Ok(Self {
             message_id,
             tick,
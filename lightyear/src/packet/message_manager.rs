@@ -1,4 +1,4 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 
 use anyhow::{anyhow, Context};
 use bevy::ptr::UnsafeCellDeref;
@@ -6,13 +6,20 @@ use bevy::reflect::Reflect;
 use bitcode::buffer::BufferTrait;
 use bitcode::word_buffer::WordBuffer;
 use crossbeam_channel::Receiver;
-use tracing::{info, trace};
+use tracing::{info, trace, warn};
 
-use crate::channel::builder::ChannelContainer;
-use crate::channel::receivers::ChannelReceive;
-use crate::channel::senders::ChannelSend;
-use crate::packet::message::{FragmentData, MessageAck, MessageId, SingleData};
-use crate::packet::packet::{Packet, PacketId, MTU_PAYLOAD_BYTES};
+use crate::channel::builder::{ChannelContainer, ChannelMode};
+use crate::channel::bulk::{BulkControl, BulkControlMessage};
+use crate::channel::error::ChannelError;
+use crate::channel::receivers::{ChannelReceive, ChannelReceiver};
+use crate::channel::senders::{ChannelSend, ChannelSender};
+use crate::packet::channel_stats::NetworkStats;
+use crate::packet::congestion::{CongestionConfig, CongestionController, NetworkConditions};
+use crate::packet::message::{
+    FragmentData, MessageAck, MessageContainer, MessageHandle, MessageId, SingleData,
+};
+use crate::packet::mtu::{MtuConfig, MtuDiscovery};
+use crate::packet::packet::{fragment_size, Packet, PacketId, MTU_PAYLOAD_BYTES};
 use crate::packet::packet_manager::{PacketBuilder, Payload, PACKET_BUFFER_CAPACITY};
 use crate::packet::priority_manager::{PriorityConfig, PriorityManager};
 use crate::protocol::channel::{ChannelKind, ChannelRegistry};
@@ -25,25 +32,61 @@ use crate::serialize::writer::WriteBuffer;
 use crate::shared::ping::manager::PingManager;
 use crate::shared::tick_manager::Tick;
 use crate::shared::tick_manager::TickManager;
-use crate::shared::time_manager::TimeManager;
+use crate::shared::time_manager::{TimeManager, WrappedTime};
 
 // TODO: hard to split message manager into send/receive because the acks need both the send side and receive side
 //  maybe have a separate actor for acks?
 
 pub const DEFAULT_MESSAGE_PRIORITY: f32 = 1.0;
 
+/// Tracked messages from unreliable channels are considered lost if they were not acked after this delay
+/// (messages from reliable channels are considered lost once their time-to-live expires).
+/// It is longer than the delay after which a packet is considered lost, so that it only applies to messages
+/// that were never sent (for example because they were dropped by the bandwidth limiter)
+const TRACKED_MESSAGE_TIMEOUT: chrono::Duration = chrono::Duration::milliseconds(10000);
+
+/// A message sent by the user, for which we emit an event when it gets delivered or lost
+struct TrackedMessage {
+    handle: MessageHandle,
+    buffered_at: WrappedTime,
+}
+
 /// Wrapper to: send/receive messages via channels to a remote address
 /// By splitting the data into packets and sending them through a given transport
 pub struct MessageManager {
     /// Handles sending/receiving packets (including acks)
     packet_manager: PacketBuilder,
     priority_manager: PriorityManager,
+    /// Finds the largest packet size that can be sent on this connection
+    mtu_discovery: MtuDiscovery,
+    /// Estimates the bandwidth available on this connection (if congestion control is enabled)
+    congestion_controller: Option<CongestionController>,
     pub(crate) channels: HashMap<ChannelKind, ChannelContainer>,
     pub(crate) channel_registry: ChannelRegistry,
     // TODO: can use Vec<ChannelKind, Vec<MessageId>> to be more efficient?
     /// Map to keep track of which messages have been sent in which packets, so that
     /// reliable senders can stop trying to send a message that has already been received
     packet_to_message_ack_map: HashMap<PacketId, HashMap<ChannelKind, Vec<MessageAck>>>,
+    /// Time at which the packets in `packet_to_message_ack_map` were sent, to compute the time-to-ack
+    packet_send_times: HashMap<PacketId, WrappedTime>,
+    /// Statistics of each channel
+    stats: NetworkStats,
+    /// Messages for which we want to know if they were delivered or lost
+    tracked_messages: HashMap<(ChannelKind, MessageId), TrackedMessage>,
+    /// Receivers notified when a message of the channel is acked (only for channels with tracked messages)
+    ack_receivers: HashMap<ChannelKind, Receiver<MessageId>>,
+    /// Tracked messages that were delivered since the last call to [`take_delivered_messages`](Self::take_delivered_messages)
+    delivered_messages: Vec<(MessageHandle, ChannelKind)>,
+    /// Tracked messages that were lost since the last call to [`take_lost_messages`](Self::take_lost_messages)
+    lost_messages: Vec<(MessageHandle, ChannelKind)>,
+    /// Progress of the transfers sent on bulk channels: (handle, channel, acked bytes, total bytes)
+    transfer_progress: Vec<(MessageHandle, ChannelKind, usize, usize)>,
+    /// Transfers of bulk channels that were cancelled by the remote. The handle is only present
+    /// for the transfers that we were sending.
+    cancelled_transfers: Vec<(Option<MessageHandle>, ChannelKind)>,
+    /// Messages from the remote that were dropped because they did not respect the limits of their channel
+    rejected_messages: Vec<(ChannelKind, ChannelError)>,
+    current_time: WrappedTime,
     writer: WriteWordBuffer,
     // read_buffer: WordBuffer,
     reader_pool: BufferPool,
@@ -51,12 +94,33 @@ pub struct MessageManager {
 
 impl MessageManager {
     pub fn new(channel_registry: &ChannelRegistry, priority_config: PriorityConfig) -> Self {
+        let channels = channel_registry.channels();
+        let mut priority_manager = PriorityManager::new(priority_config);
+        for (channel_kind, channel) in channels.iter() {
+            if let ChannelMode::Bulk(settings) = &channel.setting.mode {
+                if let Some(net_id) = channel_registry.get_net_from_kind(channel_kind) {
+                    priority_manager.add_bulk_channel(*net_id, settings.bandwidth_share);
+                }
+            }
+        }
         Self {
             packet_manager: PacketBuilder::new(),
-            priority_manager: PriorityManager::new(priority_config),
-            channels: channel_registry.channels(),
+            priority_manager,
+            mtu_discovery: MtuDiscovery::new(MtuConfig::default().disable()),
+            congestion_controller: None,
+            channels,
             channel_registry: channel_registry.clone(),
             packet_to_message_ack_map: HashMap::new(),
+            packet_send_times: HashMap::new(),
+            stats: NetworkStats::default(),
+            tracked_messages: HashMap::new(),
+            ack_receivers: HashMap::new(),
+            delivered_messages: Vec::new(),
+            lost_messages: Vec::new(),
+            transfer_progress: Vec::new(),
+            cancelled_transfers: Vec::new(),
+            rejected_messages: Vec::new(),
+            current_time: WrappedTime::default(),
             writer: WriteWordBuffer::with_capacity(PACKET_BUFFER_CAPACITY),
             // TODO: it looks like we don't really need the pool this case, we can just keep re-using the same buffer
             reader_pool: BufferPool::new(1),
@@ -64,6 +128,78 @@ impl MessageManager {
         }
     }
 
+    /// Discover the path MTU of the connection, instead of always using the default packet size.
+    ///
+    /// The discovery must only be enabled if the transport drops the packets bigger than the path MTU:
+    /// otherwise the probes would be fragmented and reassembled instead of being dropped
+    pub(crate) fn with_mtu_discovery(mut self, config: MtuConfig) -> Self {
+        self.mtu_discovery = MtuDiscovery::new(config);
+        self.sync_mtu();
+        self
+    }
+
+    /// Send the packets of the reliable channels on a transport that delivers them reliably
+    /// (for example QUIC streams), in which case the reliable channels never resend their messages
+    pub(crate) fn with_reliable_transport(mut self, reliable_transport: bool) -> Self {
+        if reliable_transport {
+            for channel in self.channels.values_mut() {
+                if channel.setting.mode.is_reliable() {
+                    channel.sender.disable_resends();
+                }
+            }
+        }
+        self
+    }
+
+    /// Adjust the bandwidth budget of the connection to the state of the network, instead of using the static
+    /// bandwidth cap
+    pub(crate) fn with_congestion_control(mut self, config: CongestionConfig) -> Self {
+        if config.enabled {
+            // detect the losses within one RTT, so that the bandwidth reacts quickly to the congestion
+            self.packet_manager
+                .header_manager
+                .enable_early_loss_detection();
+            let controller = CongestionController::new(config);
+            self.priority_manager
+                .set_bandwidth_range(controller.min_bandwidth(), controller.max_bandwidth());
+            self.priority_manager
+                .update_bandwidth(controller.bandwidth());
+            self.congestion_controller = Some(controller);
+        }
+        self
+    }
+
+    /// Current estimate of the bandwidth available on this connection (in bytes per second),
+    /// if congestion control is enabled
+    pub fn bandwidth(&self) -> Option<u32> {
+        self.congestion_controller
+            .as_ref()
+            .map(|controller| controller.bandwidth())
+    }
+
+    /// Statistics of each channel of this connection
+    pub fn stats(&self) -> &NetworkStats {
+        &self.stats
+    }
+
+    /// Maximum size of the packets sent on this connection
+    pub fn mtu(&self) -> usize {
+        self.packet_manager.mtu()
+    }
+
+    /// Use the latest MTU found by the path MTU discovery to build packets and fragment messages
+    fn sync_mtu(&mut self) {
+        let mtu = self.mtu_discovery.mtu();
+        if mtu == self.packet_manager.mtu() {
+            return;
+        }
+        info!(mtu, "updating the MTU of the connection");
+        self.packet_manager.set_mtu(mtu);
+        for channel in self.channels.values_mut() {
+            channel.sender.set_fragment_size(fragment_size(mtu));
+        }
+    }
+
     pub(crate) fn get_replication_update_send_receiver(&mut self) -> Receiver<MessageId> {
         self.priority_manager
             .subscribe_replication_update_sent_messages()
@@ -76,8 +212,29 @@ impl MessageManager {
         ping_manager: &PingManager,
         tick_manager: &TickManager,
     ) {
+        self.current_time = time_manager.current_time();
         self.packet_manager.header_manager.update(time_manager);
+        self.handle_lost_packets();
+        self.mtu_discovery
+            .update(time_manager.current_time(), ping_manager.rtt());
+        self.sync_mtu();
+        if let Some(controller) = self.congestion_controller.as_mut() {
+            let conditions = NetworkConditions {
+                packet_loss: self.packet_manager.header_manager.packet_loss(),
+                rtt: ping_manager.rtt(),
+                jitter: ping_manager.jitter(),
+            };
+            if let Some(bandwidth) = controller.update(self.current_time, conditions) {
+                trace!(bandwidth, "updating the bandwidth budget");
+                self.priority_manager.update_bandwidth(bandwidth);
+            }
+        }
+        let resend_backoff = self
+            .congestion_controller
+            .as_ref()
+            .map_or(1.0, |controller| controller.resend_backoff());
         for channel in self.channels.values_mut() {
+            channel.sender.set_resend_backoff(resend_backoff);
             channel
                 .sender
                 .update(time_manager, ping_manager, tick_manager);
@@ -85,6 +242,262 @@ impl MessageManager {
         }
     }
 
+    /// Stop waiting for the acks of the packets that were lost, and mark the tracked messages
+    /// of unreliable channels that they contained as lost
+    fn handle_lost_packets(&mut self) {
+        for packet_id in self.packet_manager.header_manager.take_lost_packets() {
+            // a packet that was considered lost early can still be acked: keep the messages of the reliable channels
+            // so that they don't get sent again if the ack arrives
+            let message_map = if self
+                .packet_manager
+                .header_manager
+                .is_waiting_for_late_ack(&packet_id)
+            {
+                let Some(message_map) = self.packet_to_message_ack_map.get_mut(&packet_id) else {
+                    continue;
+                };
+                let unreliable_channels: Vec<ChannelKind> = message_map
+                    .keys()
+                    .filter(|channel_kind| {
+                        !self
+                            .channels
+                            .get(channel_kind)
+                            .is_some_and(|channel| channel.setting.mode.is_reliable())
+                    })
+                    .copied()
+                    .collect();
+                unreliable_channels
+                    .into_iter()
+                    .filter_map(|channel_kind| {
+                        message_map
+                            .remove(&channel_kind)
+                            .map(|message_acks| (channel_kind, message_acks))
+                    })
+                    .collect::<HashMap<_, _>>()
+            } else {
+                self.packet_send_times.remove(&packet_id);
+                let Some(message_map) = self.packet_to_message_ack_map.remove(&packet_id) else {
+                    continue;
+                };
+                message_map
+            };
+            for (channel_kind, message_acks) in message_map {
+                // reliable channels will send the message again
+                if self
+                    .channels
+                    .get(&channel_kind)
+                    .is_some_and(|channel| channel.setting.mode.is_reliable())
+                {
+                    continue;
+                }
+                for message_ack in message_acks {
+                    if let Some(tracked) = self
+                        .tracked_messages
+                        .remove(&(channel_kind, message_ack.message_id))
+                    {
+                        trace!(?channel_kind, handle = ?tracked.handle, "tracked message lost");
+                        self.lost_messages.push((tracked.handle, channel_kind));
+                    }
+                }
+            }
+        }
+        // unreliable messages that were never acked (for example because they were never sent),
+        // and messages from reliable channels that were dropped after their time-to-live expired
+        let current_time = self.current_time;
+        let channels = &self.channels;
+        let lost_messages = &mut self.lost_messages;
+        self.tracked_messages.retain(|(channel_kind, _), tracked| {
+            let timeout = match channels.get(channel_kind) {
+                Some(channel) if channel.setting.mode.is_reliable() => {
+                    let Some(time_to_live) = channel.setting.mode.time_to_live() else {
+                        // the message will be resent until it is acked
+                        return true;
+                    };
+                    chrono::Duration::from_std(time_to_live).unwrap()
+                }
+                _ => TRACKED_MESSAGE_TIMEOUT,
+            };
+            if current_time - tracked.buffered_at > timeout {
+                lost_messages.push((tracked.handle, *channel_kind));
+                return false;
+            }
+            true
+        });
+    }
+
+    /// Buffer a message to be sent on this connection, and keep track of whether it gets delivered or lost.
+    ///
+    /// Only messages sent on channels that watch acks (reliable channels, or
+    /// [`UnorderedUnreliableWithAcks`](crate::channel::builder::ChannelMode::UnorderedUnreliableWithAcks) channels)
+    /// can be tracked; on other channels the message is simply buffered.
+    pub(crate) fn buffer_send_tracked<M: BitSerializable>(
+        &mut self,
+        message: M,
+        channel_kind: ChannelKind,
+        handle: MessageHandle,
+    ) -> anyhow::Result<()> {
+        let message_id = self.buffer_send(message, channel_kind)?;
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .context("Channel not found")?;
+        if !channel.setting.mode.is_watching_acks() {
+            return Ok(());
+        }
+        if let Some(message_id) = message_id {
+            self.ack_receivers
+                .entry(channel_kind)
+                .or_insert_with(|| channel.sender.subscribe_acks());
+            self.tracked_messages.insert(
+                (channel_kind, message_id),
+                TrackedMessage {
+                    handle,
+                    buffered_at: self.current_time,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Take the tracked messages that were delivered since the last call
+    pub(crate) fn take_delivered_messages(&mut self) -> Vec<(MessageHandle, ChannelKind)> {
+        std::mem::take(&mut self.delivered_messages)
+    }
+
+    /// Take the tracked messages that were lost since the last call
+    pub(crate) fn take_lost_messages(&mut self) -> Vec<(MessageHandle, ChannelKind)> {
+        std::mem::take(&mut self.lost_messages)
+    }
+
+    /// Take the messages from the remote that were dropped since the last call, because they did not
+    /// respect the limits of their channel
+    pub(crate) fn take_rejected_messages(&mut self) -> Vec<(ChannelKind, ChannelError)> {
+        std::mem::take(&mut self.rejected_messages)
+    }
+
+    /// Take the progress of the transfers sent on bulk channels since the last call
+    pub(crate) fn take_transfer_progress(
+        &mut self,
+    ) -> Vec<(MessageHandle, ChannelKind, usize, usize)> {
+        std::mem::take(&mut self.transfer_progress)
+    }
+
+    /// Take the transfers of bulk channels that were cancelled by the remote since the last call
+    pub(crate) fn take_cancelled_transfers(&mut self) -> Vec<(Option<MessageHandle>, ChannelKind)> {
+        std::mem::take(&mut self.cancelled_transfers)
+    }
+
+    /// Cancel a transfer that we are sending on a bulk channel
+    pub(crate) fn cancel_transfer(&mut self, handle: MessageHandle) -> anyhow::Result<()> {
+        let (channel_kind, transfer_id) = self
+            .tracked_messages
+            .iter()
+            .find(|(_, tracked)| tracked.handle == handle)
+            .map(|(key, _)| *key)
+            .context("the transfer was already completed or cancelled")?;
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .context("Channel not found")?;
+        let ChannelSender::Bulk(sender) = &mut channel.sender else {
+            return Err(anyhow!("the message was not sent on a bulk channel"));
+        };
+        sender.cancel(transfer_id);
+        self.tracked_messages.remove(&(channel_kind, transfer_id));
+        Ok(())
+    }
+
+    /// Cancel the transfer that we are currently receiving on a bulk channel
+    pub(crate) fn cancel_incoming_transfer(
+        &mut self,
+        channel_kind: ChannelKind,
+    ) -> anyhow::Result<()> {
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .context("Channel not found")?;
+        let ChannelReceiver::Bulk(receiver) = &mut channel.receiver else {
+            return Err(anyhow!("the channel is not a bulk channel"));
+        };
+        receiver
+            .cancel_current()
+            .context("no transfer is being received on this channel")?;
+        Ok(())
+    }
+
+    /// Take the control messages that the bulk channels need to send to the remote
+    pub(crate) fn take_bulk_control_messages(&mut self) -> Vec<BulkControlMessage> {
+        let mut messages = Vec::new();
+        for (channel_kind, channel) in self.channels.iter_mut() {
+            let (ChannelSender::Bulk(sender), ChannelReceiver::Bulk(receiver)) =
+                (&mut channel.sender, &mut channel.receiver)
+            else {
+                continue;
+            };
+            let Some(net_id) = self.channel_registry.get_net_from_kind(channel_kind) else {
+                continue;
+            };
+            for (transfer_id, control) in sender
+                .take_controls()
+                .into_iter()
+                .chain(receiver.take_controls())
+            {
+                messages.push(BulkControlMessage {
+                    channel: *net_id,
+                    transfer_id,
+                    control,
+                });
+            }
+        }
+        messages
+    }
+
+    /// Handle a control message sent by the remote for one of our bulk channels
+    pub(crate) fn receive_bulk_control_message(
+        &mut self,
+        message: BulkControlMessage,
+    ) -> anyhow::Result<()> {
+        let channel_kind = *self
+            .channel_registry
+            .get_kind_from_net_id(message.channel)
+            .context(format!(
+                "Could not recognize net_id {} as a channel",
+                message.channel
+            ))?;
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .context("Channel not found")?;
+        let (ChannelSender::Bulk(sender), ChannelReceiver::Bulk(receiver)) =
+            (&mut channel.sender, &mut channel.receiver)
+        else {
+            return Err(anyhow!("the channel is not a bulk channel"));
+        };
+        match message.control {
+            // sent by the remote sender, for the transfers we receive
+            BulkControl::CancelSend => {
+                receiver.receive_control(message.transfer_id, message.control);
+                for _ in receiver.take_cancelled_transfers() {
+                    self.cancelled_transfers.push((None, channel_kind));
+                }
+            }
+            // sent by the remote receiver, for the transfers we send
+            BulkControl::WindowUpdate { .. } | BulkControl::CancelReceive => {
+                sender.receive_control(message.transfer_id, message.control);
+                for transfer_id in sender.take_cancelled_transfers() {
+                    if let Some(tracked) =
+                        self.tracked_messages.remove(&(channel_kind, transfer_id))
+                    {
+                        trace!(?channel_kind, handle = ?tracked.handle, "transfer cancelled by the receiver");
+                        self.cancelled_transfers
+                            .push((Some(tracked.handle), channel_kind));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Buffer a message to be sent on this connection
     /// Returns the message id associated with the message, if there is one
     pub fn buffer_send<M: BitSerializable>(
@@ -120,6 +533,20 @@ impl MessageManager {
         self.writer.start_write();
         message.encode(&mut self.writer)?;
         let message_bytes: Vec<u8> = self.writer.finish_write().into();
+        // the transfers of bulk channels have their own size limit
+        let max_size = match &channel.setting.mode {
+            ChannelMode::Bulk(settings) => Some(settings.max_transfer_size),
+            _ => channel.setting.max_message_size,
+        };
+        if let Some(max_size) = max_size {
+            if message_bytes.len() > max_size {
+                return Err(ChannelError::MessageTooLarge {
+                    size: message_bytes.len(),
+                    max_size,
+                }
+                .into());
+            }
+        }
         Ok(channel.sender.buffer_send(message_bytes.into(), priority))
     }
 
@@ -127,7 +554,86 @@ impl MessageManager {
     // TODO: maybe pass TickManager instead of Tick? Find a more elegant way to pass extra data that might not be used?
     //  (ticks are not purely necessary without client prediction)
     //  maybe be generic over a Context ?
+    ///
+    /// This also includes the probes of the path MTU discovery.
     pub fn send_packets(&mut self, current_tick: Tick) -> anyhow::Result<Vec<Payload>> {
+        let mut bytes = vec![];
+        if let Some((data_to_send, num_bytes_added_to_limiter)) =
+            self.collect_data_to_send(current_tick)?
+        {
+            bytes = self.build_payloads(data_to_send, current_tick)?;
+            self.adjust_limiter(&bytes, num_bytes_added_to_limiter);
+        }
+        // the probes count towards the bandwidth quota; a probe that doesn't fit is sent later
+        if let Some(size) = self
+            .mtu_discovery
+            .probe_size()
+            .filter(|size| self.priority_manager.check_bandwidth(*size as u32))
+        {
+            let (packet_id, probe) = self.packet_manager.build_mtu_probe(size, current_tick)?;
+            self.mtu_discovery.probe_sent(packet_id, size);
+            bytes.push(probe);
+        }
+        Ok(bytes)
+    }
+
+    /// Same as [`send_packets`](Self::send_packets), but messages from reliable and unreliable channels
+    /// are never written in the same packet.
+    ///
+    /// This lets connections that have a reliable transport available (for example QUIC streams)
+    /// send the reliable packets on it. Returns the (unreliable, reliable) payloads.
+    /// The reliable channels should not resend their messages, since the transport delivers the reliable packets
+    /// (see [`with_reliable_transport`](Self::with_reliable_transport)).
+    ///
+    /// No MTU probes are sent, since these connections discover the path MTU themselves.
+    pub fn send_packets_by_reliability(
+        &mut self,
+        current_tick: Tick,
+    ) -> anyhow::Result<(Vec<Payload>, Vec<Payload>)> {
+        let Some((data_to_send, num_bytes_added_to_limiter)) =
+            self.collect_data_to_send(current_tick)?
+        else {
+            return Ok((vec![], vec![]));
+        };
+        let mut reliable_data = BTreeMap::new();
+        let mut unreliable_data = BTreeMap::new();
+        for (channel_id, data) in data_to_send {
+            let channel_kind = self
+                .channel_registry
+                .get_kind_from_net_id(channel_id)
+                .context("cannot find channel kind")?;
+            let channel = self
+                .channels
+                .get(channel_kind)
+                .context("Channel not found")?;
+            if channel.setting.mode.is_reliable() {
+                reliable_data.insert(channel_id, data);
+            } else {
+                unreliable_data.insert(channel_id, data);
+            }
+        }
+        let unreliable_bytes = self.build_payloads(unreliable_data, current_tick)?;
+        let reliable_bytes = self.build_payloads(reliable_data, current_tick)?;
+        self.adjust_limiter(
+            unreliable_bytes.iter().chain(reliable_bytes.iter()),
+            num_bytes_added_to_limiter,
+        );
+        Ok((unreliable_bytes, reliable_bytes))
+    }
+
+    /// Get the data that is ready to be sent from all channels, filtered by the priority manager.
+    ///
+    /// Returns `None` if there is no data to send, otherwise returns the data along with the number of
+    /// bytes that were added to the rate limiter.
+    fn collect_data_to_send(
+        &mut self,
+        current_tick: Tick,
+    ) -> anyhow::Result<
+        Option<(
+            BTreeMap<NetId, (VecDeque<SingleData>, VecDeque<FragmentData>)>,
+            u32,
+        )>,
+    > {
         // Step 1. Get the list of packets to send from all channels
         // for each channel, prepare packets using the buffered messages that are ready to be sent
         // TODO: iterate through the channels in order of channel priority? (with accumulation)
@@ -139,6 +645,8 @@ impl MessageManager {
                 .get_net_from_kind(channel_kind)
                 .context("cannot find channel id")?;
             channel.sender.collect_messages_to_send();
+            self.stats.channel_mut(*channel_kind).resends +=
+                channel.sender.take_num_resends() as u64;
             if channel.sender.has_messages_to_send() {
                 let (single_data, fragment_data) = channel.sender.send_packet();
                 if !single_data.is_empty() || !fragment_data.is_empty() {
@@ -148,8 +656,8 @@ impl MessageManager {
             }
         }
         // return early if there are no messages to send
-        if !has_data_to_send {
-            return Ok(vec![]);
+        if !has_data_to_send && !self.priority_manager.has_buffered_messages() {
+            return Ok(None);
         }
 
         // priority manager: get the list of messages we can send according to the rate limiter
@@ -158,8 +666,43 @@ impl MessageManager {
             data_to_send,
             &self.channel_registry,
             current_tick,
+            self.current_time,
         );
 
+        // update the statistics of each channel
+        for (channel_id, num_discarded) in self.priority_manager.take_num_discarded() {
+            let channel_kind = self
+                .channel_registry
+                .get_kind_from_net_id(channel_id)
+                .context("cannot find channel kind")?;
+            self.stats.channel_mut(*channel_kind).messages_dropped += num_discarded as u64;
+        }
+        for (channel_id, (single_data, fragment_data)) in data_to_send.iter() {
+            let channel_kind = self
+                .channel_registry
+                .get_kind_from_net_id(*channel_id)
+                .context("cannot find channel kind")?;
+            let stats = self.stats.channel_mut(*channel_kind);
+            stats.messages_sent += (single_data.len() + fragment_data.len()) as u64;
+            stats.fragments_sent += fragment_data.len() as u64;
+            stats.bytes_sent += single_data
+                .iter()
+                .map(|data| data.bytes.len())
+                .chain(fragment_data.iter().map(|data| data.bytes.len()))
+                .sum::<usize>() as u64;
+        }
+        Ok(Some((data_to_send, num_bytes_added_to_limiter)))
+    }
+
+    /// Build the packets for the given data, and encode them into payloads
+    fn build_payloads(
+        &mut self,
+        data_to_send: BTreeMap<NetId, (VecDeque<SingleData>, VecDeque<FragmentData>)>,
+        current_tick: Tick,
+    ) -> anyhow::Result<Vec<Payload>> {
+        if data_to_send.is_empty() {
+            return Ok(vec![]);
+        }
         let packets = self.packet_manager.build_packets(data_to_send);
 
         let mut bytes = Vec::new();
@@ -200,22 +743,25 @@ impl MessageManager {
                     }
                     Ok::<(), anyhow::Error>(())
                 })?;
+            if self.packet_to_message_ack_map.contains_key(&packet_id) {
+                self.packet_send_times.insert(packet_id, self.current_time);
+            }
         }
+        Ok(bytes)
+    }
 
-        // adjust the real amount of bytes that we sent through the limiter (to account for the actual packet size)
+    /// Adjust the real amount of bytes that we sent through the limiter (to account for the actual packet size)
+    fn adjust_limiter<'a>(
+        &mut self,
+        payloads: impl IntoIterator<Item = &'a Payload>,
+        num_bytes_added_to_limiter: u32,
+    ) {
         if self.priority_manager.config.enabled {
-            let total_bytes_sent = bytes.iter().map(|b| b.len() as u32).sum::<u32>();
-            if let Ok(remaining_bytes_to_add) =
-                (total_bytes_sent - num_bytes_added_to_limiter).try_into()
-            {
-                let _ = self
-                    .priority_manager
-                    .limiter
-                    .check_n(remaining_bytes_to_add);
-            }
+            let total_bytes_sent = payloads.into_iter().map(|b| b.len() as u32).sum::<u32>();
+            let _ = self
+                .priority_manager
+                .check_bandwidth(total_bytes_sent.saturating_sub(num_bytes_added_to_limiter));
         }
-
-        Ok(bytes)
     }
 
     /// Process packet received over the network as raw bytes
@@ -241,8 +787,17 @@ impl MessageManager {
 
         // Step 3. Update the list of messages that have been acked
         for acked_packet in acked_packets {
+            self.mtu_discovery.packet_acked(acked_packet);
+            let time_to_ack = self
+                .packet_send_times
+                .remove(&acked_packet)
+                .and_then(|sent_at| (self.current_time - sent_at).to_std().ok())
+                .unwrap_or_default();
             if let Some(message_map) = self.packet_to_message_ack_map.remove(&acked_packet) {
                 for (channel_kind, message_acks) in message_map {
+                    let stats = self.stats.channel_mut(channel_kind);
+                    stats.messages_acked += message_acks.len() as u64;
+                    stats.total_time_to_ack += time_to_ack * message_acks.len() as u32;
                     let channel = self
                         .channels
                         .get_mut(&channel_kind)
@@ -254,6 +809,36 @@ impl MessageManager {
             }
         }
 
+        // progress of the transfers of the bulk channels (before the completed transfers stop being tracked)
+        for (channel_kind, channel) in self.channels.iter_mut() {
+            let ChannelSender::Bulk(sender) = &mut channel.sender else {
+                continue;
+            };
+            for (transfer_id, acked_bytes, total_bytes) in sender.take_progress() {
+                if let Some(tracked) = self.tracked_messages.get(&(*channel_kind, transfer_id)) {
+                    self.transfer_progress.push((
+                        tracked.handle,
+                        *channel_kind,
+                        acked_bytes,
+                        total_bytes,
+                    ));
+                }
+            }
+        }
+
+        // the channels notify us when a message is fully acked (i.e. all of its fragments)
+        for (channel_kind, receiver) in self.ack_receivers.iter() {
+            for message_id in receiver.try_iter() {
+                if let Some(tracked) = self.tracked_messages.remove(&(*channel_kind, message_id)) {
+                    trace!(?channel_kind, handle = ?tracked.handle, "tracked message delivered");
+                    self.delivered_messages
+                        .push((tracked.handle, *channel_kind));
+                }
+            }
+        }
+
+        self.sync_mtu();
+
         // Step 4. Put the messages from the packet in the internal buffers for each channel
         for (channel_net_id, messages) in packet.data.contents() {
             let channel_kind = self
@@ -272,9 +857,26 @@ impl MessageManager {
                 messages,
                 channel_kind
             );
+            let stats = self.stats.channel_mut(*channel_kind);
+            for message in messages.iter() {
+                stats.messages_received += 1;
+                stats.bytes_received += message.bytes().len() as u64;
+                if matches!(message, MessageContainer::Fragment(_)) {
+                    stats.fragments_received += 1;
+                }
+            }
             for mut message in messages {
                 message.set_tick(tick);
-                channel.receiver.buffer_recv(message)?;
+                if let Err(e) = channel.receiver.buffer_recv(message) {
+                    // the remote sent a message that exceeds the limits of the channel: drop it and report it
+                    let error = e.downcast::<ChannelError>()?;
+                    warn!(
+                        ?channel_kind,
+                        ?error,
+                        "Dropped a message received from the remote"
+                    );
+                    self.rejected_messages.push((*channel_kind, error));
+                }
             }
         }
         Ok(tick)
@@ -318,6 +920,7 @@ mod tests {
     use bevy::utils::Duration;
 
     use crate::_reexport::*;
+    use crate::channel::builder::DEFAULT_MAX_REASSEMBLIES;
     use crate::packet::message::MessageId;
     use crate::packet::packet::FRAGMENT_SIZE;
     use crate::packet::priority_manager::PriorityConfig;
@@ -526,6 +1129,79 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    /// Messages from reliable and unreliable channels should be sent in separate packets
+    fn test_message_manager_send_packets_by_reliability() -> Result<(), anyhow::Error> {
+        let protocol = protocol();
+
+        // Create message managers
+        let mut client_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+
+        // client: buffer send messages on an unreliable and a reliable channel, and then send
+        let message = MyMessageProtocol::Message1(Message1("1".to_string()));
+        let unreliable_kind = ChannelKind::of::<Channel1>();
+        let reliable_kind = ChannelKind::of::<EntityActionsChannel>();
+        client_message_manager.buffer_send(message.clone(), unreliable_kind)?;
+        client_message_manager.buffer_send(message.clone(), reliable_kind)?;
+        let (unreliable_bytes, reliable_bytes) =
+            client_message_manager.send_packets_by_reliability(Tick(0))?;
+        assert_eq!(unreliable_bytes.len(), 1);
+        assert_eq!(reliable_bytes.len(), 1);
+
+        // server: the reliable packet only contains messages from the reliable channel
+        let packet = Packet::decode(&mut ReadWordBuffer::start_read(
+            reliable_bytes[0].as_slice(),
+        ))?;
+        server_message_manager.recv_packet(packet)?;
+        let data = server_message_manager.read_messages();
+        assert_eq!(
+            data.get(&reliable_kind).unwrap(),
+            &vec![(Tick(0), message.clone())]
+        );
+        assert!(!data.contains_key(&unreliable_kind));
+
+        let packet = Packet::decode(&mut ReadWordBuffer::start_read(
+            unreliable_bytes[0].as_slice(),
+        ))?;
+        server_message_manager.recv_packet(packet)?;
+        let data = server_message_manager.read_messages();
+        assert_eq!(
+            data.get(&unreliable_kind).unwrap(),
+            &vec![(Tick(0), message.clone())]
+        );
+        assert!(!data.contains_key(&reliable_kind));
+        Ok(())
+    }
+
+    #[test]
+    /// Reliable messages that are sent on a reliable transport are never resent
+    fn test_message_manager_no_resends_on_reliable_transport() -> anyhow::Result<()> {
+        let protocol = protocol();
+        let mut client_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default())
+                .with_reliable_transport(true);
+        let mut time_manager = TimeManager::default();
+        let ping_manager = PingManager::new(PingConfig::default());
+        let tick_manager = TickManager::from_config(TickConfig::new(Duration::from_millis(10)));
+
+        let message = MyMessageProtocol::Message1(Message1("1".to_string()));
+        client_message_manager.buffer_send(message, ChannelKind::of::<EntityActionsChannel>())?;
+        let (_, reliable_bytes) = client_message_manager.send_packets_by_reliability(Tick(0))?;
+        assert_eq!(reliable_bytes.len(), 1);
+
+        // the message is not acked, but it is not sent again
+        time_manager.update(Duration::from_millis(6000));
+        client_message_manager.update(&time_manager, &ping_manager, &tick_manager);
+        let (unreliable_bytes, reliable_bytes) =
+            client_message_manager.send_packets_by_reliability(Tick(1))?;
+        assert!(unreliable_bytes.is_empty());
+        assert!(reliable_bytes.is_empty());
+        Ok(())
+    }
+
     #[test]
     fn test_notify_ack() -> anyhow::Result<()> {
         let protocol = protocol();
@@ -582,4 +1258,451 @@ mod tests {
         assert_eq!(update_acks_tracker.try_recv()?, message_id);
         Ok(())
     }
+
+    #[test]
+    fn test_message_manager_delivery_tracking() -> anyhow::Result<()> {
+        let protocol = protocol();
+        let mut client_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+
+        // only the channels that watch acks can track messages
+        let message = MyMessageProtocol::Message2(Message2(1));
+        let reliable_kind = ChannelKind::of::<EntityActionsChannel>();
+        client_message_manager.buffer_send_tracked(
+            message.clone(),
+            Channel2::kind(),
+            MessageHandle(0),
+        )?;
+        client_message_manager.buffer_send_tracked(
+            message.clone(),
+            reliable_kind,
+            MessageHandle(1),
+        )?;
+        client_message_manager.buffer_send_tracked(
+            message.clone(),
+            Channel1::kind(),
+            MessageHandle(2),
+        )?;
+        assert_eq!(client_message_manager.tracked_messages.len(), 2);
+
+        for payload in client_message_manager.send_packets(Tick(0))? {
+            let packet = Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+            server_message_manager.recv_packet(packet)?;
+        }
+        // the server sends back a message to ack the packets
+        server_message_manager.buffer_send(message.clone(), Channel1::kind())?;
+        for payload in server_message_manager.send_packets(Tick(0))? {
+            let packet = Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+            client_message_manager.recv_packet(packet)?;
+        }
+
+        let mut delivered = client_message_manager.take_delivered_messages();
+        delivered.sort_by_key(|(handle, _)| *handle);
+        assert_eq!(
+            delivered,
+            vec![
+                (MessageHandle(0), Channel2::kind()),
+                (MessageHandle(1), reliable_kind)
+            ]
+        );
+        assert!(client_message_manager.take_lost_messages().is_empty());
+        assert!(client_message_manager.tracked_messages.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_manager_loss_tracking() -> anyhow::Result<()> {
+        let protocol = protocol();
+        let mut client_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+        let mut time_manager = TimeManager::default();
+        let ping_manager = PingManager::new(PingConfig::default());
+        let tick_manager = TickManager::from_config(TickConfig::new(Duration::from_millis(10)));
+
+        let message = MyMessageProtocol::Message2(Message2(1));
+        let reliable_kind = ChannelKind::of::<EntityActionsChannel>();
+        client_message_manager.buffer_send_tracked(
+            message.clone(),
+            Channel2::kind(),
+            MessageHandle(0),
+        )?;
+        client_message_manager.buffer_send_tracked(
+            message.clone(),
+            reliable_kind,
+            MessageHandle(1),
+        )?;
+        // the packets are never received by the server
+        client_message_manager.send_packets(Tick(0))?;
+
+        // the packet is considered lost: only the unreliable message is lost,
+        // the reliable message will be sent again
+        time_manager.update(Duration::from_millis(6000));
+        client_message_manager.update(&time_manager, &ping_manager, &tick_manager);
+        assert_eq!(
+            client_message_manager.take_lost_messages(),
+            vec![(MessageHandle(0), Channel2::kind())]
+        );
+        assert!(client_message_manager.packet_to_message_ack_map.is_empty());
+        assert_eq!(client_message_manager.tracked_messages.len(), 1);
+
+        // an unreliable message that is never sent is also considered lost after a while
+        client_message_manager.buffer_send_tracked(
+            message.clone(),
+            Channel2::kind(),
+            MessageHandle(2),
+        )?;
+        time_manager.update(Duration::from_millis(11000));
+        client_message_manager.update(&time_manager, &ping_manager, &tick_manager);
+        assert_eq!(
+            client_message_manager.take_lost_messages(),
+            vec![(MessageHandle(2), Channel2::kind())]
+        );
+        assert!(client_message_manager.take_delivered_messages().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_manager_congestion_control() -> anyhow::Result<()> {
+        let protocol = protocol();
+        let config = CongestionConfig::default()
+            .enable()
+            .with_initial_bandwidth(50000)
+            .with_decrease_factor(0.5);
+        let mut client_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default())
+                .with_congestion_control(config);
+        let mut server_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+        assert_eq!(client_message_manager.bandwidth(), Some(50000));
+        assert!(client_message_manager.priority_manager.config.enabled);
+        let mut time_manager = TimeManager::default();
+        let ping_manager = PingManager::new(PingConfig::default());
+        let tick_manager = TickManager::from_config(TickConfig::new(Duration::from_millis(10)));
+
+        // the first two packets are never received by the server
+        let message = MyMessageProtocol::Message2(Message2(1));
+        for i in 0..5 {
+            client_message_manager.buffer_send(message.clone(), Channel2::kind())?;
+            for payload in client_message_manager.send_packets(Tick(0))? {
+                if i >= 2 {
+                    let packet =
+                        Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+                    server_message_manager.recv_packet(packet)?;
+                }
+            }
+        }
+        time_manager.update(Duration::from_millis(100));
+        client_message_manager.update(&time_manager, &ping_manager, &tick_manager);
+        assert_eq!(client_message_manager.bandwidth(), Some(50000));
+
+        // the packets are considered lost as soon as the acks of the next packets arrive,
+        // and the bandwidth is reduced
+        server_message_manager.buffer_send(message, Channel1::kind())?;
+        for payload in server_message_manager.send_packets(Tick(0))? {
+            let packet = Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+            client_message_manager.recv_packet(packet)?;
+        }
+        time_manager.update(Duration::from_millis(100));
+        client_message_manager.update(&time_manager, &ping_manager, &tick_manager);
+        assert_eq!(client_message_manager.bandwidth(), Some(25000));
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_message_manager_mtu_probe_bandwidth() -> anyhow::Result<()> {
+        use governor::{DefaultDirectRateLimiter, Quota};
+        use nonzero_ext::nonzero;
+
+        use crate::packet::mtu::DEFAULT_MTU;
+
+        let protocol = protocol();
+        let quota = Quota::per_second(nonzero!(2000u32));
+        let mut client_message_manager = MessageManager::new(
+            protocol.channel_registry(),
+            PriorityConfig {
+                bandwidth_quota: quota,
+                enabled: true,
+            },
+        )
+        .with_mtu_discovery(MtuConfig::default().enable());
+
+        // the bandwidth quota does not have enough capacity left for the probe
+        let _ = client_message_manager
+            .priority_manager
+            .limiter
+            .check_n(nonzero!(1500u32));
+        assert!(client_message_manager.send_packets(Tick(0))?.is_empty());
+
+        // the probe is sent once the quota is replenished, and it consumes the quota
+        client_message_manager.priority_manager.limiter = DefaultDirectRateLimiter::direct(quota);
+        let payloads = client_message_manager.send_packets(Tick(1))?;
+        assert_eq!(payloads.len(), 1);
+        assert!(payloads[0].len() > DEFAULT_MTU);
+        assert!(!client_message_manager
+            .priority_manager
+            .check_bandwidth(payloads[0].len() as u32));
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_manager_channel_stats() -> anyhow::Result<()> {
+        let protocol = protocol();
+        let mut client_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+
+        let message = MyMessageProtocol::Message2(Message2(1));
+        client_message_manager.buffer_send(message.clone(), Channel2::kind())?;
+        for payload in client_message_manager.send_packets(Tick(0))? {
+            let packet = Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+            server_message_manager.recv_packet(packet)?;
+        }
+        let client_stats = client_message_manager.stats().channel(Channel2::kind());
+        assert_eq!(client_stats.messages_sent, 1);
+        assert!(client_stats.bytes_sent > 0);
+        let server_stats = server_message_manager.stats().channel(Channel2::kind());
+        assert_eq!(server_stats.messages_received, 1);
+        assert_eq!(server_stats.bytes_received, client_stats.bytes_sent);
+
+        // the server sends back a message to ack the packet
+        server_message_manager.buffer_send(message, Channel1::kind())?;
+        for payload in server_message_manager.send_packets(Tick(0))? {
+            let packet = Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+            client_message_manager.recv_packet(packet)?;
+        }
+        let client_stats = client_message_manager.stats().channel(Channel2::kind());
+        assert_eq!(client_stats.messages_acked, 1);
+        assert!(client_stats.average_time_to_ack().is_some());
+        assert!(client_message_manager.packet_send_times.is_empty());
+        assert_eq!(client_message_manager.stats().total().messages_received, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_manager_bulk_transfer() -> anyhow::Result<()> {
+        let mut channel_registry = ChannelRegistry::new();
+        channel_registry.add::<Channel1>(ChannelSettings {
+            mode: ChannelMode::Bulk(
+                BulkSettings::default()
+                    .with_send_window(2)
+                    .with_receive_window(4),
+            ),
+            ..ChannelSettings::default()
+        });
+        channel_registry.add::<Channel2>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            ..ChannelSettings::default()
+        });
+        let mut client_message_manager =
+            MessageManager::new(&channel_registry, PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(&channel_registry, PriorityConfig::default());
+
+        // a message that needs 8 chunks
+        let message = MyMessageProtocol::Message1(Message1("a".repeat(FRAGMENT_SIZE * 7)));
+        client_message_manager.buffer_send_tracked(
+            message.clone(),
+            Channel1::kind(),
+            MessageHandle(0),
+        )?;
+
+        let mut progress = vec![];
+        let mut received = HashMap::new();
+        for _ in 0..10 {
+            // only the chunks in the send window are sent
+            let payloads = client_message_manager.send_packets(Tick(0))?;
+            for payload in payloads {
+                let packet = Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+                server_message_manager.recv_packet(packet)?;
+            }
+            for control in server_message_manager.take_bulk_control_messages() {
+                client_message_manager.receive_bulk_control_message(control)?;
+            }
+            // the server sends back a message to ack the packets
+            server_message_manager
+                .buffer_send(MyMessageProtocol::Message2(Message2(1)), Channel2::kind())?;
+            for payload in server_message_manager.send_packets(Tick(0))? {
+                let packet = Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+                client_message_manager.recv_packet(packet)?;
+            }
+            progress.extend(client_message_manager.take_transfer_progress());
+            received.extend(server_message_manager.read_messages::<MyMessageProtocol>());
+        }
+
+        assert_eq!(
+            received.get(&Channel1::kind()).unwrap(),
+            &vec![(Tick(0), message)]
+        );
+        // the progress increases until the transfer is fully acked
+        assert!(progress.len() > 1);
+        assert!(progress.windows(2).all(|w| w[0].2 < w[1].2));
+        let (handle, channel_kind, acked_bytes, total_bytes) = *progress.last().unwrap();
+        assert_eq!((handle, channel_kind), (MessageHandle(0), Channel1::kind()));
+        assert_eq!(acked_bytes, total_bytes);
+        assert_eq!(
+            client_message_manager.take_delivered_messages(),
+            vec![(MessageHandle(0), Channel1::kind())]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_manager_bulk_cancel() -> anyhow::Result<()> {
+        let mut channel_registry = ChannelRegistry::new();
+        channel_registry.add::<Channel1>(ChannelSettings {
+            mode: ChannelMode::Bulk(BulkSettings::default().with_send_window(1)),
+            ..ChannelSettings::default()
+        });
+        let mut client_message_manager =
+            MessageManager::new(&channel_registry, PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(&channel_registry, PriorityConfig::default());
+
+        let message = MyMessageProtocol::Message1(Message1("a".repeat(FRAGMENT_SIZE * 3)));
+        client_message_manager.buffer_send_tracked(message, Channel1::kind(), MessageHandle(0))?;
+        for payload in client_message_manager.send_packets(Tick(0))? {
+            let packet = Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+            server_message_manager.recv_packet(packet)?;
+        }
+
+        // the receiver cancels the transfer: the sender stops tracking it
+        server_message_manager.cancel_incoming_transfer(Channel1::kind())?;
+        assert!(server_message_manager
+            .cancel_incoming_transfer(Channel1::kind())
+            .is_err());
+        for control in server_message_manager.take_bulk_control_messages() {
+            client_message_manager.receive_bulk_control_message(control)?;
+        }
+        assert_eq!(
+            client_message_manager.take_cancelled_transfers(),
+            vec![(Some(MessageHandle(0)), Channel1::kind())]
+        );
+        assert!(client_message_manager
+            .cancel_transfer(MessageHandle(0))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_manager_message_size_limits() -> anyhow::Result<()> {
+        let mut client_channel_registry = ChannelRegistry::new();
+        client_channel_registry.add::<Channel1>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            ..ChannelSettings::default()
+        });
+        let mut server_channel_registry = ChannelRegistry::new();
+        server_channel_registry.add::<Channel1>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            max_message_size: Some(FRAGMENT_SIZE),
+            ..ChannelSettings::default()
+        });
+        let mut client_message_manager =
+            MessageManager::new(&client_channel_registry, PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(&server_channel_registry, PriorityConfig::default());
+        let message = MyMessageProtocol::Message1(Message1("a".repeat(FRAGMENT_SIZE * 2)));
+
+        // we cannot send messages that are too large
+        let error = server_message_manager
+            .buffer_send(message.clone(), Channel1::kind())
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ChannelError>(),
+            Some(ChannelError::MessageTooLarge {
+                max_size: FRAGMENT_SIZE,
+                ..
+            })
+        ));
+
+        // messages that are too large are dropped by the receiver
+        client_message_manager.buffer_send(message, Channel1::kind())?;
+        for payload in client_message_manager.send_packets(Tick(0))? {
+            let packet = Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+            server_message_manager.recv_packet(packet)?;
+        }
+        let rejected = server_message_manager.take_rejected_messages();
+        assert!(!rejected.is_empty());
+        assert!(rejected.iter().all(|(channel_kind, error)| {
+            channel_kind == &Channel1::kind()
+                && matches!(error, ChannelError::FragmentedMessageTooLarge { .. })
+        }));
+        assert!(server_message_manager
+            .read_messages::<MyMessageProtocol>()
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_manager_max_reassemblies_reliable() -> anyhow::Result<()> {
+        let mut channel_registry = ChannelRegistry::new();
+        channel_registry.add::<Channel1>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            ..ChannelSettings::default()
+        });
+        channel_registry.add::<Channel2>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            ..ChannelSettings::default()
+        });
+        let mut client_message_manager =
+            MessageManager::new(&channel_registry, PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(&channel_registry, PriorityConfig::default());
+
+        // more fragmented messages than the receiver can reassemble at the same time
+        let num_messages = DEFAULT_MAX_REASSEMBLIES + 8;
+        let messages: Vec<_> = (0..num_messages)
+            .map(|i| MyMessageProtocol::Message1(Message1(i.to_string().repeat(FRAGMENT_SIZE))))
+            .collect();
+        for message in &messages {
+            client_message_manager.buffer_send(message.clone(), Channel1::kind())?;
+        }
+
+        let mut time_manager = TimeManager::default();
+        let ping_manager = PingManager::new(PingConfig::default());
+        let tick_manager = TickManager::from_config(TickConfig::new(Duration::from_millis(10)));
+        let mut received = vec![];
+        for round in 0..4 {
+            time_manager.update(Duration::from_secs(1));
+            client_message_manager.update(&time_manager, &ping_manager, &tick_manager);
+            server_message_manager.update(&time_manager, &ping_manager, &tick_manager);
+            for payload in client_message_manager.send_packets(Tick(0))? {
+                let packet = Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+                // the first fragments are lost the first time, so the receiver starts reassembling
+                // every message that was sent
+                if round == 0
+                    && packet
+                        .message_acks()
+                        .values()
+                        .flatten()
+                        .any(|ack| ack.fragment_id == Some(0))
+                {
+                    continue;
+                }
+                server_message_manager.recv_packet(packet)?;
+            }
+            // the server sends back a message to ack the packets
+            server_message_manager
+                .buffer_send(MyMessageProtocol::Message2(Message2(1)), Channel2::kind())?;
+            for payload in server_message_manager.send_packets(Tick(0))? {
+                let packet = Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+                client_message_manager.recv_packet(packet)?;
+            }
+            if let Some(messages) = server_message_manager
+                .read_messages::<MyMessageProtocol>()
+                .remove(&Channel1::kind())
+            {
+                received.extend(messages.into_iter().map(|(_, message)| message));
+            }
+        }
+
+        // the sender waits for the first messages to be reassembled before sending the next ones,
+        // so no fragment is dropped and every message is delivered
+        assert!(server_message_manager.take_rejected_messages().is_empty());
+        assert_eq!(received, messages);
+        Ok(())
+    }
 }
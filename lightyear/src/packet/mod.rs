@@ -3,7 +3,7 @@ Module to group messages into packets
 
 # Packet
 This module defines the concept of a [`Packet`] which is a byte array that will be sent over the network.
-A [`Packet`] has a maximum size that depends on the transport (around 1400 bytes for UDP), and is
+A [`Packet`] has a maximum size that depends on the path MTU of the connection (see [`mtu`]), and is
 composed of a header and a payload.
 
 The header will compute important information such as the packet sequence number, the packet type, etc.
@@ -19,12 +19,21 @@ Packets that are over the maximum packet size will be fragmented into multiple [
 [`FragmentedPacket`]: packet::FragmentedPacket
 */
 
+/// Network statistics of each channel of a connection
+pub mod channel_stats;
+
+/// Estimates the bandwidth available on a connection
+pub mod congestion;
+
 /// Manages the [`PacketHeader`](header::PacketHeader) which includes important packet information
 pub mod header;
 
 /// Defines the [`Message`](message::Message) struct, which is a piece of serializable data
 pub mod message;
 
+/// Discovers the largest packet size that can be sent on a connection
+pub mod mtu;
+
 /// Manages sending and receiving [`Packets`](packet::Packet) over the network
 pub mod message_manager;
 
@@ -0,0 +1,375 @@
+//! Path MTU discovery
+//!
+//! The discovery is opt-in (see [`MtuConfig::enabled`]): it requires a transport that drops the packets bigger
+//! than the path MTU instead of fragmenting them, which is only the case of the UDP socket on Linux.
+//!
+//! Every connection starts by building packets of at most [`MtuConfig::min_mtu`] bytes, a size that is assumed
+//! to reach the remote on any path. It then sends padded probe packets to find the largest packet size that
+//! the path can carry, with a binary search between [`MtuConfig::min_mtu`] and [`MtuConfig::max_mtu`].
+//!
+//! A probe is confirmed when the remote acks it (like any other packet), and is considered lost if it
+//! is not acked before [`MtuConfig::probe_timeout`]. Each size is probed [`MtuConfig::max_probe_attempts`] times
+//! before being considered too big, since probes can also be lost for other reasons than their size.
+//!
+//! Once the search is complete, the connection checks every [`MtuConfig::reprobe_interval`] that packets of the
+//! discovered size still reach the remote, and then searches for a bigger size. If the path MTU shrank
+//! (for example because the route changed), the connection falls back to [`MtuConfig::min_mtu`] and searches again.
+use bevy::reflect::Reflect;
+use bevy::utils::Duration;
+use tracing::{debug, trace};
+
+use crate::_reexport::WrappedTime;
+use crate::connection::netcode::MAX_PACKET_SIZE;
+use crate::packet::packet::PacketId;
+
+/// Size (in bytes) of the packets built for a connection before its path MTU is discovered.
+///
+/// This fits in the MTU of almost every path.
+pub const DEFAULT_MTU: usize = MAX_PACKET_SIZE;
+
+/// Largest packet size (in bytes) that a connection can use.
+///
+/// This fits in an Ethernet frame (1500 bytes), or in a jumbo frame (9000 bytes) with the `jumbo_frames` feature,
+/// minus the IP, UDP and connection headers.
+#[cfg(not(feature = "jumbo_frames"))]
+pub const MAX_MTU: usize = 1440;
+#[cfg(feature = "jumbo_frames")]
+pub const MAX_MTU: usize = 8900;
+
+/// Smallest packet size (in bytes) that a connection can use.
+///
+/// Any IPv4 path can carry UDP datagrams of this size without fragmentation.
+const MIN_MTU: usize = 508;
+
+/// The search stops once the largest confirmed size and the smallest lost size are this close
+const SEARCH_PRECISION: usize = 8;
+
+/// Configuration of the path MTU discovery of each connection.
+///
+/// The sizes do not include the headers added by the connection and the transport (for example
+/// 25 bytes for netcode, plus 28 or 48 bytes for the UDP and IP headers)
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub struct MtuConfig {
+    /// If false (the default), packets are always built with [`min_mtu`](Self::min_mtu) bytes.
+    ///
+    /// Enabling the discovery sets the Don't Fragment bit on the packets of the UDP socket. It is only available
+    /// with the UDP transport on Linux, where the socket can prevent the IP layer from fragmenting the probes;
+    /// it stays disabled on other platforms and with the other transports (relay, QUIC, WebTransport, etc.),
+    /// where the probes would always reach the remote.
+    pub enabled: bool,
+    /// Size of the packets used until a bigger size is confirmed, or when probes of the current size are lost.
+    ///
+    /// It must fit in the MTU of every path that the connection can use; lower it if your clients connect through
+    /// tunnels or VPNs with a small MTU. It cannot be smaller than 508 bytes.
+    pub min_mtu: usize,
+    /// Largest packet size to probe for. It is capped at [`MAX_MTU`].
+    ///
+    /// The default fits in an Ethernet frame; raise it to use jumbo frames on a local network
+    /// (this requires the `jumbo_frames` feature).
+    pub max_mtu: usize,
+    /// A probe that is not acked after this duration (or after twice the RTT, if it is longer) is considered lost
+    pub probe_timeout: Duration,
+    /// Number of lost probes of the same size before considering that the size does not fit in the path MTU
+    pub max_probe_attempts: u8,
+    /// Duration between two checks of the discovered MTU, once the search is complete
+    pub reprobe_interval: Duration,
+}
+
+impl Default for MtuConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_mtu: DEFAULT_MTU,
+            max_mtu: 1440,
+            probe_timeout: Duration::from_secs(1),
+            max_probe_attempts: 2,
+            reprobe_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+impl MtuConfig {
+    pub fn with_max_mtu(mut self, max_mtu: usize) -> Self {
+        self.max_mtu = max_mtu;
+        self
+    }
+
+    pub fn enable(mut self) -> Self {
+        self.enabled = true;
+        self
+    }
+
+    pub fn disable(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    /// Reserve `header_bytes` in every packet for the header that the transport adds to it (for example
+    /// when the packets go through a relay), so that the packets on the wire still fit in the probed sizes
+    pub(crate) fn reserve_header_bytes(mut self, header_bytes: usize) -> Self {
+        self.min_mtu = self.min_mtu.saturating_sub(header_bytes);
+        self.max_mtu = self.max_mtu.saturating_sub(header_bytes);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SearchState {
+    /// Searching for the largest size that reaches the remote, between the current MTU and the upper bound
+    Searching,
+    /// Checking that packets of the current MTU still reach the remote
+    Validating,
+    /// The search completed at the given time
+    Complete { since: WrappedTime },
+}
+
+#[derive(Debug)]
+struct Probe {
+    packet_id: PacketId,
+    size: usize,
+    sent_time: WrappedTime,
+}
+
+/// Discovers the path MTU of a single connection, by probing it with padded packets
+#[derive(Debug)]
+pub(crate) struct MtuDiscovery {
+    config: MtuConfig,
+    /// Largest packet size that is known to reach the remote
+    mtu: usize,
+    /// Smallest packet size that is known to be lost
+    upper: usize,
+    state: SearchState,
+    /// The probe that is waiting for an ack
+    probe: Option<Probe>,
+    /// Number of consecutive lost probes of the current size
+    lost_probes: u8,
+    current_time: WrappedTime,
+}
+
+impl MtuDiscovery {
+    pub(crate) fn new(mut config: MtuConfig) -> Self {
+        config.min_mtu = config.min_mtu.clamp(MIN_MTU, MAX_MTU);
+        config.max_mtu = config.max_mtu.clamp(config.min_mtu, MAX_MTU);
+        let mut discovery = Self {
+            mtu: config.min_mtu,
+            upper: config.max_mtu + 1,
+            state: SearchState::Searching,
+            probe: None,
+            lost_probes: 0,
+            current_time: WrappedTime::default(),
+            config,
+        };
+        discovery.check_search_complete();
+        discovery
+    }
+
+    /// Largest packet size that can be used on the connection
+    pub(crate) fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    /// Update the time, and check if the probe that was sent is lost
+    pub(crate) fn update(&mut self, current_time: WrappedTime, rtt: Duration) {
+        self.current_time = current_time;
+        let timeout = self.config.probe_timeout.max(rtt * 2);
+        if self
+            .probe
+            .as_ref()
+            .is_some_and(|probe| current_time >= probe.sent_time + timeout)
+        {
+            let probe = self.probe.take().unwrap();
+            self.probe_lost(probe.size);
+        }
+    }
+
+    /// Returns the size of the next probe to send, if one should be sent now
+    pub(crate) fn probe_size(&mut self) -> Option<usize> {
+        if !self.config.enabled || self.probe.is_some() {
+            return None;
+        }
+        if let SearchState::Complete { since } = self.state {
+            if self.current_time < since + self.config.reprobe_interval {
+                return None;
+            }
+            // check that the path MTU did not shrink, then look for a bigger one
+            self.upper = self.config.max_mtu + 1;
+            self.state = if self.mtu > self.config.min_mtu {
+                SearchState::Validating
+            } else {
+                SearchState::Searching
+            };
+            self.check_search_complete();
+        }
+        match self.state {
+            SearchState::Searching => Some((self.mtu + self.upper) / 2),
+            SearchState::Validating => Some(self.mtu),
+            SearchState::Complete { .. } => None,
+        }
+    }
+
+    /// Keep track of the packet that contains the probe returned by [`probe_size`](Self::probe_size)
+    pub(crate) fn probe_sent(&mut self, packet_id: PacketId, size: usize) {
+        trace!(?packet_id, size, "sending MTU probe");
+        self.probe = Some(Probe {
+            packet_id,
+            size,
+            sent_time: self.current_time,
+        });
+    }
+
+    /// Notify that the remote acked one of our packets
+    pub(crate) fn packet_acked(&mut self, packet_id: PacketId) {
+        if !self
+            .probe
+            .as_ref()
+            .is_some_and(|probe| probe.packet_id == packet_id)
+        {
+            return;
+        }
+        let probe = self.probe.take().unwrap();
+        trace!(size = probe.size, "MTU probe acked");
+        self.lost_probes = 0;
+        match self.state {
+            SearchState::Searching => {
+                self.mtu = self.mtu.max(probe.size);
+                debug!(mtu = self.mtu, "confirmed a bigger path MTU");
+            }
+            // the path can still carry packets of the current MTU, look for a bigger one
+            SearchState::Validating => self.state = SearchState::Searching,
+            SearchState::Complete { .. } => {}
+        }
+        self.check_search_complete();
+    }
+
+    fn probe_lost(&mut self, size: usize) {
+        self.lost_probes += 1;
+        trace!(size, lost_probes = self.lost_probes, "MTU probe lost");
+        // try the same size again
+        if self.lost_probes < self.config.max_probe_attempts {
+            return;
+        }
+        self.lost_probes = 0;
+        match self.state {
+            SearchState::Searching => self.upper = size,
+            SearchState::Validating => {
+                debug!(
+                    mtu = self.mtu,
+                    fallback = self.config.min_mtu,
+                    "packets of the discovered MTU are lost, falling back to the minimum MTU"
+                );
+                self.upper = self.mtu;
+                self.mtu = self.config.min_mtu;
+                self.state = SearchState::Searching;
+            }
+            SearchState::Complete { .. } => {}
+        }
+        self.check_search_complete();
+    }
+
+    fn check_search_complete(&mut self) {
+        if self.state == SearchState::Searching && self.upper - self.mtu <= SEARCH_PRECISION {
+            debug!(mtu = self.mtu, "path MTU discovery complete");
+            self.state = SearchState::Complete {
+                since: self.current_time,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the discovery for the given duration on a path that drops the packets bigger than `path_mtu`
+    fn run(
+        discovery: &mut MtuDiscovery,
+        time: &mut WrappedTime,
+        duration: Duration,
+        path_mtu: usize,
+    ) -> usize {
+        let mut num_probes = 0;
+        let end = *time + duration;
+        let mut packet_id = PacketId(0);
+        while *time < end {
+            discovery.update(*time, Duration::default());
+            if let Some(size) = discovery.probe_size() {
+                discovery.probe_sent(packet_id, size);
+                num_probes += 1;
+                if size <= path_mtu {
+                    discovery.packet_acked(packet_id);
+                }
+                packet_id = PacketId(packet_id.wrapping_add(1));
+            }
+            *time += Duration::from_millis(100);
+        }
+        num_probes
+    }
+
+    #[test]
+    fn test_discover_path_mtu() {
+        let mut discovery = MtuDiscovery::new(MtuConfig::default().enable());
+        let mut time = WrappedTime::default();
+        assert_eq!(discovery.mtu(), DEFAULT_MTU);
+
+        run(&mut discovery, &mut time, Duration::from_secs(20), 1400);
+        assert!(discovery.mtu() <= 1400);
+        assert!(discovery.mtu() > 1400 - SEARCH_PRECISION);
+        assert!(matches!(discovery.state, SearchState::Complete { .. }));
+    }
+
+    #[test]
+    fn test_path_supports_max_mtu() {
+        let mut discovery = MtuDiscovery::new(MtuConfig::default().enable().with_max_mtu(MAX_MTU));
+        let mut time = WrappedTime::default();
+
+        run(&mut discovery, &mut time, Duration::from_secs(5), 9000);
+        assert!(discovery.mtu() > MAX_MTU - SEARCH_PRECISION);
+    }
+
+    #[test]
+    fn test_fallback_when_path_mtu_shrinks() {
+        let mut discovery = MtuDiscovery::new(MtuConfig::default().enable());
+        let mut time = WrappedTime::default();
+        run(&mut discovery, &mut time, Duration::from_secs(20), 1400);
+        assert!(discovery.mtu() > 1300);
+
+        // the route changes: the probes of the discovered MTU are lost, so we fall back to the minimum
+        // before searching again
+        discovery.update(time + Duration::from_secs(30), Duration::default());
+        let size = discovery.probe_size().unwrap();
+        assert_eq!(size, discovery.mtu());
+        time += Duration::from_secs(30);
+        for i in 0..2 {
+            discovery.update(time, Duration::default());
+            assert_eq!(discovery.probe_size(), Some(size));
+            discovery.probe_sent(PacketId(i), size);
+            time += Duration::from_secs(1);
+        }
+        discovery.update(time, Duration::default());
+        assert_eq!(discovery.mtu(), DEFAULT_MTU);
+
+        run(&mut discovery, &mut time, Duration::from_secs(20), 1250);
+        assert!(discovery.mtu() <= 1250);
+        assert!(discovery.mtu() > 1250 - SEARCH_PRECISION);
+    }
+
+    #[test]
+    fn test_reserve_header_bytes() {
+        let config = MtuConfig::default().enable().reserve_header_bytes(20);
+        let mut discovery = MtuDiscovery::new(config);
+        let mut time = WrappedTime::default();
+        assert_eq!(discovery.mtu(), DEFAULT_MTU - 20);
+
+        run(&mut discovery, &mut time, Duration::from_secs(20), 1500);
+        assert!(discovery.mtu() <= 1440 - 20);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut discovery = MtuDiscovery::new(MtuConfig::default().disable());
+        let mut time = WrappedTime::default();
+        let num_probes = run(&mut discovery, &mut time, Duration::from_secs(5), 1400);
+        assert_eq!(num_probes, 0);
+        assert_eq!(discovery.mtu(), DEFAULT_MTU);
+    }
+}
@@ -2,9 +2,9 @@ use std::collections::{BTreeMap, HashMap};
 
 use bitcode::encoding::{Fixed, Gamma};
 
-use crate::connection::netcode::MAX_PACKET_SIZE;
 use crate::packet::header::PacketHeader;
 use crate::packet::message::{FragmentData, MessageAck, MessageContainer, SingleData};
+use crate::packet::mtu::DEFAULT_MTU;
 use crate::packet::packet_type::PacketType;
 use crate::protocol::channel::ChannelId;
 use crate::protocol::registry::NetId;
@@ -20,15 +20,26 @@ wrapping_id!(PacketId);
 /// PacketType: 2 bits
 /// Rest: 10 bytes
 const HEADER_BYTES: usize = 11;
-/// The maximum of bytes that the payload of the packet can contain (excluding the header)
+
+/// The maximum of bytes that the payload of a packet of `mtu` bytes can contain (excluding the header)
 /// remove 1 byte for byte alignment at the end
-pub(crate) const MTU_PAYLOAD_BYTES: usize = MAX_PACKET_SIZE - HEADER_BYTES - 1;
+pub(crate) const fn mtu_payload_bytes(mtu: usize) -> usize {
+    mtu - HEADER_BYTES - 1
+}
 
-/// The maximum number of bytes for a message before it is fragmented
+/// The maximum number of bytes for a message before it is fragmented, for packets of `mtu` bytes
 /// The final size of the fragmented packet (channel_net_id: 2, fragment_id: 1, tick: 2, message_id: 2, num_fragments: 1, number of bytes in fragment: 4)
-/// must be lower than MTU_PAYLOAD_BYTES
+/// must be lower than the payload size
 /// (might even be 13 in some situations?)
-pub(crate) const FRAGMENT_SIZE: usize = MTU_PAYLOAD_BYTES - 12;
+pub(crate) const fn fragment_size(mtu: usize) -> usize {
+    mtu_payload_bytes(mtu) - 12
+}
+
+/// The maximum of bytes that the payload of the packet can contain, before the path MTU is discovered
+pub(crate) const MTU_PAYLOAD_BYTES: usize = mtu_payload_bytes(DEFAULT_MTU);
+
+/// The maximum number of bytes for a message before it is fragmented, before the path MTU is discovered
+pub(crate) const FRAGMENT_SIZE: usize = fragment_size(DEFAULT_MTU);
 
 // TODO: we don't need SinglePacket vs FragmentPacket; we can just re-use the same thing
 //  because MessageContainer already has the information about whether it is a fragment or not
@@ -229,6 +240,8 @@ impl BitSerializable for FragmentedPacket {
 pub(crate) enum PacketData {
     Single(SinglePacket),
     Fragmented(FragmentedPacket),
+    /// Packet padded to a given size to probe the path MTU. It does not contain any message
+    MtuProbe,
 }
 
 impl PacketData {
@@ -238,6 +251,7 @@ impl PacketData {
             PacketData::Fragmented(fragmented_packet) => {
                 1 + fragmented_packet.packet.num_messages()
             }
+            PacketData::MtuProbe => 0,
         }
     }
     pub(crate) fn contents(self) -> HashMap<NetId, Vec<MessageContainer>> {
@@ -263,6 +277,7 @@ impl PacketData {
                         .extend(message_containers);
                 }
             }
+            PacketData::MtuProbe => {}
         }
         res
     }
@@ -279,6 +294,7 @@ impl Packet {
         match &self.data {
             PacketData::Single(single_packet) => single_packet.data.is_empty(),
             PacketData::Fragmented(fragmented_packet) => fragmented_packet.packet.data.is_empty(),
+            PacketData::MtuProbe => true,
         }
     }
 
@@ -291,6 +307,8 @@ impl Packet {
         match &self.data {
             PacketData::Single(single_packet) => single_packet.encode(writer),
             PacketData::Fragmented(fragmented_packet) => fragmented_packet.encode(writer),
+            // the padding is added after the packet is encoded
+            PacketData::MtuProbe => Ok(()),
         }
     }
 
@@ -312,7 +330,13 @@ impl Packet {
                     header,
                     data: PacketData::Fragmented(fragmented_packet),
                 })
-            } // _ => Err(anyhow::anyhow!("Packet type not supported")),
+            }
+            // ignore the padding
+            PacketType::MtuProbe => Ok(Self {
+                header,
+                data: PacketData::MtuProbe,
+            }),
+            // _ => Err(anyhow::anyhow!("Packet type not supported")),
         }
     }
 
@@ -329,6 +353,7 @@ impl Packet {
             PacketData::Fragmented(fragmented_packet) => {
                 fragmented_packet.packet.add_channel(channel);
             }
+            PacketData::MtuProbe => unreachable!("MTU probes cannot contain messages"),
         }
     }
 
@@ -338,6 +363,7 @@ impl Packet {
             PacketData::Fragmented(fragmented_packet) => {
                 fragmented_packet.packet.add_message(channel, message);
             }
+            PacketData::MtuProbe => unreachable!("MTU probes cannot contain messages"),
         }
     }
 
@@ -347,6 +373,7 @@ impl Packet {
         match &self.data {
             PacketData::Single(single_packet) => single_packet.num_messages(),
             PacketData::Fragmented(fragmented_packet) => fragmented_packet.packet.num_messages(),
+            PacketData::MtuProbe => 0,
         }
     }
 
@@ -354,6 +381,7 @@ impl Packet {
         match &self.data {
             PacketData::Single(single_packet) => single_packet.message_acks(),
             PacketData::Fragmented(fragmented_packet) => fragmented_packet.message_acks(),
+            PacketData::MtuProbe => HashMap::new(),
         }
     }
 }
@@ -3,11 +3,12 @@ use std::collections::{BTreeMap, VecDeque};
 use bitcode::encoding::Gamma;
 use bitcode::word_buffer::WordBuffer;
 
-use crate::connection::netcode::MAX_PACKET_SIZE;
 use crate::packet::header::PacketHeaderManager;
 use crate::packet::message::{FragmentData, MessageContainer, SingleData};
+use crate::packet::mtu::{DEFAULT_MTU, MAX_MTU};
 use crate::packet::packet::{
-    FragmentedPacket, Packet, PacketData, SinglePacket, FRAGMENT_SIZE, MTU_PAYLOAD_BYTES,
+    fragment_size, mtu_payload_bytes, FragmentedPacket, Packet, PacketData, PacketId, SinglePacket,
+    MTU_PAYLOAD_BYTES,
 };
 use crate::packet::packet_type::PacketType;
 use crate::protocol::registry::NetId;
@@ -15,6 +16,7 @@ use crate::protocol::BitSerializable;
 use crate::serialize::reader::ReadBuffer;
 use crate::serialize::wordbuffer::writer::WriteWordBuffer;
 use crate::serialize::writer::WriteBuffer;
+use crate::shared::tick_manager::Tick;
 
 // enough to hold a biggest fragment + writing channel/message_id/etc.
 // pub(crate) const PACKET_BUFFER_CAPACITY: usize = MTU_PAYLOAD_BYTES * (u8::BITS as usize) + 50;
@@ -26,6 +28,10 @@ pub type Payload = Vec<u8>;
 /// messages into packets)
 pub(crate) struct PacketBuilder {
     pub(crate) header_manager: PacketHeaderManager,
+    /// Maximum size of the packets, given by the path MTU of the connection
+    mtu: usize,
+    /// Maximum number of bits that the payload of a packet can contain
+    payload_capacity: usize,
     // Pre-allocated buffer to encode/decode without allocation.
     // TODO: should this be associated with Packet?
     try_write_buffer: WriteWordBuffer,
@@ -36,19 +42,37 @@ impl PacketBuilder {
     pub fn new() -> Self {
         Self {
             header_manager: PacketHeaderManager::new(),
+            mtu: DEFAULT_MTU,
+            payload_capacity: PACKET_BUFFER_CAPACITY,
             // write buffer to encode packets bit by bit
             try_write_buffer: WriteBuffer::with_capacity(2 * PACKET_BUFFER_CAPACITY),
             write_buffer: WriteBuffer::with_capacity(PACKET_BUFFER_CAPACITY),
         }
     }
 
+    /// Maximum size of the packets that are built
+    pub(crate) fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    /// Update the maximum size of the packets, when the path MTU of the connection changes
+    pub(crate) fn set_mtu(&mut self, mtu: usize) {
+        let payload_capacity = mtu_payload_bytes(mtu) * (u8::BITS as usize);
+        if payload_capacity > self.payload_capacity {
+            self.try_write_buffer = WriteBuffer::with_capacity(2 * payload_capacity);
+            self.write_buffer = WriteBuffer::with_capacity(payload_capacity);
+        }
+        self.mtu = mtu;
+        self.payload_capacity = payload_capacity;
+    }
+
     /// Reset the buffers used to encode packets
     pub fn clear_try_write_buffer(&mut self) {
         self.try_write_buffer.start_write();
         debug_assert_eq!(self.try_write_buffer.num_bits_written(), 0);
         // self.try_write_buffer = WriteBuffer::with_capacity(2 * PACKET_BUFFER_CAPACITY);
         self.try_write_buffer
-            .set_reserved_bits(PACKET_BUFFER_CAPACITY);
+            .set_reserved_bits(self.payload_capacity);
     }
 
     //
@@ -56,7 +80,7 @@ impl PacketBuilder {
     pub fn clear_write_buffer(&mut self) {
         self.write_buffer.start_write();
         // self.write_buffer = WriteBuffer::with_capacity(2 * PACKET_BUFFER_CAPACITY);
-        self.write_buffer.set_reserved_bits(PACKET_BUFFER_CAPACITY);
+        self.write_buffer.set_reserved_bits(self.payload_capacity);
     }
 
     /// Encode a packet into raw bytes
@@ -64,13 +88,13 @@ impl PacketBuilder {
         // TODO: check that we haven't allocated!
         // self.clear_write_buffer();
 
-        let mut write_buffer = WriteWordBuffer::with_capacity(PACKET_BUFFER_CAPACITY);
-        write_buffer.set_reserved_bits(PACKET_BUFFER_CAPACITY);
+        let mut write_buffer = WriteWordBuffer::with_capacity(self.payload_capacity);
+        write_buffer.set_reserved_bits(self.payload_capacity);
         packet.encode(&mut write_buffer)?;
         // TODO: we should actually call finish write to byte align!
         // TODO: CAREFUL, THIS COULD ALLOCATE A BIT MORE TO BYTE ALIGN?
         let payload = Payload::from(write_buffer.finish_write());
-        assert!(payload.len() <= MAX_PACKET_SIZE, "packet = {:?}", packet);
+        assert!(payload.len() <= MAX_MTU, "packet = {:?}", packet);
         Ok(payload)
 
         // packet.encode(&mut self.write_buffer)?;
@@ -78,6 +102,30 @@ impl PacketBuilder {
         // Ok(bytes)
     }
 
+    /// Build a packet padded to `size` bytes, to check if the path MTU of the connection can carry it.
+    ///
+    /// Returns the id of the packet, so that we know that the probe was received when the packet is acked.
+    pub(crate) fn build_mtu_probe(
+        &mut self,
+        size: usize,
+        current_tick: Tick,
+    ) -> anyhow::Result<(PacketId, Payload)> {
+        let mut header = self
+            .header_manager
+            .prepare_send_packet_header(PacketType::MtuProbe);
+        header.tick = current_tick;
+        let packet_id = header.packet_id;
+        let packet = Packet {
+            header,
+            data: PacketData::MtuProbe,
+        };
+        let mut write_buffer = WriteWordBuffer::with_capacity(size);
+        packet.encode(&mut write_buffer)?;
+        let mut payload = Payload::from(write_buffer.finish_write());
+        payload.resize(size, 0);
+        Ok((packet_id, payload))
+    }
+
     /// Start building new packet, we start with an empty packet
     /// that can write to a given channel
     pub(crate) fn build_new_single_packet(&mut self) -> Packet {
@@ -120,7 +168,8 @@ impl PacketBuilder {
         //  - could try to compute it manually, but the length of Bytes is encoded with Gamma
         //  - could serialize the packet somewhere, and check the number of bits written
 
-        debug_assert!(packet.fragment.bytes.len() <= FRAGMENT_SIZE);
+        // fragments built before the MTU of the connection decreased are still sent with their original size
+        debug_assert!(packet.fragment.bytes.len() <= fragment_size(MAX_MTU));
         if is_last_fragment {
             packet.encode(&mut self.try_write_buffer).unwrap();
             // reserve one extra bit for the continuation bit between fragment/single packet data
@@ -338,7 +387,7 @@ impl PacketBuilder {
             // Start by writing all fragmented packets
             for fragment_data in fragment_messages.into_iter() {
                 let is_last_fragment = fragment_data.is_last_fragment();
-                debug_assert!(fragment_data.bytes.len() <= FRAGMENT_SIZE);
+                debug_assert!(fragment_data.bytes.len() <= fragment_size(MAX_MTU));
                 let mut packet = self.build_new_fragment_packet(channel_id, fragment_data);
                 if is_last_fragment {
                     loop {
@@ -401,9 +450,12 @@ impl PacketBuilder {
 
                     // TODO: bin packing, add the biggest message that could fit
                     //  use a free list of Option<SingleData> to keep track of which messages have been added?
+                    // A message that does not fit in an empty packet was buffered before the MTU of the connection
+                    // decreased: send it in its own packet.
                     if self
                         .can_add_message(single_messages.front().unwrap())
                         .unwrap()
+                        || packet.data.num_messages() == 0
                     {
                         let message = single_messages.pop_front().unwrap();
                         // add message to packet
@@ -686,6 +738,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_write_message_after_mtu_increase() -> anyhow::Result<()> {
+        let channel_registry = get_channel_registry();
+        let mut manager = PacketBuilder::new();
+        let channel_kind = ChannelKind::of::<Channel1>();
+        let channel_id = channel_registry.get_net_from_kind(&channel_kind).unwrap();
+
+        // the message does not fit in a packet of the default MTU, but fits after the path MTU is discovered
+        let message = Bytes::from(vec![1u8; MTU_PAYLOAD_BYTES + 100]);
+        manager.set_mtu(DEFAULT_MTU + 200);
+        let mut packet = manager.build_new_single_packet();
+        assert!(manager.can_add_channel_to_packet(channel_id, &mut packet)?,);
+        assert!(manager.can_add_bits(message.len() * (u8::BITS as usize)),);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_mtu_probe() -> anyhow::Result<()> {
+        let mut manager = PacketBuilder::new();
+        let (packet_id, payload) = manager.build_mtu_probe(1400, Tick(3))?;
+        assert_eq!(payload.len(), 1400);
+
+        let packet = Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()))?;
+        assert_eq!(packet.header().packet_id, packet_id);
+        assert_eq!(packet.header().get_packet_type(), PacketType::MtuProbe);
+        assert!(packet.is_empty());
+        Ok(())
+    }
+
     #[test]
     fn test_pack_big_message() {
         let channel_registry = get_channel_registry();
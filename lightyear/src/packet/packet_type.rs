@@ -21,4 +21,7 @@ pub enum PacketType {
     // A packet containing actual data, but which is fragmented into multiple parts
     #[bitcode_hint(frequency = 5)]
     DataFragment,
+    // A packet padded to a given size, used to discover the path MTU
+    #[bitcode_hint(frequency = 1)]
+    MtuProbe,
 }
@@ -1,21 +1,28 @@
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::num::NonZeroU32;
 
+use bevy::utils::Duration;
 use crossbeam_channel::{Receiver, Sender};
 use governor::{DefaultDirectRateLimiter, Quota};
 use nonzero_ext::*;
 use tracing::{debug, error, trace};
 
-use crate::_reexport::EntityUpdatesChannel;
+use crate::_reexport::{EntityUpdatesChannel, WrappedTime};
 use crate::packet::message::{FragmentData, MessageContainer, MessageId, SingleData};
-use crate::prelude::{ChannelKind, ChannelRegistry, Tick};
+use crate::packet::mtu::MAX_MTU;
+use crate::prelude::{ChannelKind, ChannelMode, ChannelRegistry, ChannelSettings, Tick};
 use crate::protocol::registry::NetId;
 
 #[derive(Debug)]
 pub struct BufferedMessage {
+    /// Priority of the message, that accumulates while the message is waiting to be sent
     priority: f32,
+    /// Priority that is added to the accumulated priority every time the message could not be sent
+    base_priority: f32,
     channel_net_id: NetId,
     message_container: MessageContainer,
+    /// Time at which the message was first considered by the priority filter
+    buffered_at: WrappedTime,
 }
 
 #[derive(Debug, Clone)]
@@ -59,8 +66,20 @@ pub(crate) struct PriorityManager {
     pub(crate) config: PriorityConfig,
     // TODO: can I do without this limiter?
     pub(crate) limiter: DefaultDirectRateLimiter,
-    // Messages that could not be sent because of the bandwidth quota
-    // buffered_data: Vec<BufferedMessage>,
+    /// Limiters of the bulk channels, that can only use a share of the bandwidth budget
+    bulk_limiters: HashMap<NetId, BulkLimiter>,
+    /// Number of limiter cells that each byte costs.
+    ///
+    /// The congestion control adjusts the bandwidth budget by changing this cost, instead of rebuilding
+    /// the limiters (which would lose their state)
+    byte_cost: f32,
+    /// Largest value that `byte_cost` can take, i.e. the quota of the limiters divided by the minimum bandwidth
+    max_byte_cost: f32,
+    /// Unreliable messages that could not be sent because of the bandwidth quota, and that will be
+    /// considered again (with a higher priority) the next time we send packets
+    buffered_data: Vec<BufferedMessage>,
+    /// Number of messages of each channel that were not sent and were discarded
+    num_discarded: HashMap<NetId, u32>,
     /// List of senders to notify when a replication update message is actually sent (included in packet)
     replication_update_senders: Vec<Sender<MessageId>>,
 }
@@ -70,11 +89,69 @@ impl PriorityManager {
         Self {
             config: config.clone(),
             limiter: DefaultDirectRateLimiter::direct(config.bandwidth_quota),
-            // buffered_data: Vec::new(),
+            bulk_limiters: HashMap::new(),
+            byte_cost: 1.0,
+            max_byte_cost: 1.0,
+            buffered_data: Vec::new(),
+            num_discarded: HashMap::new(),
             replication_update_senders: Vec::new(),
         }
     }
 
+    /// Let the bandwidth budget vary between `min_bandwidth` and `max_bandwidth` (in bytes per second),
+    /// for example with the estimate of the congestion control.
+    ///
+    /// The limiters are built once with the maximum bandwidth; [`update_bandwidth`](Self::update_bandwidth)
+    /// then only changes the cost of each byte.
+    pub(crate) fn set_bandwidth_range(&mut self, min_bandwidth: u32, max_bandwidth: u32) {
+        let Some(max) = NonZeroU32::new(max_bandwidth) else {
+            return;
+        };
+        self.config.bandwidth_quota = Quota::per_second(max).allow_burst(max);
+        self.config.enabled = true;
+        self.byte_cost = 1.0;
+        self.max_byte_cost = max_bandwidth as f32 / min_bandwidth.clamp(1, max_bandwidth) as f32;
+        self.limiter = DefaultDirectRateLimiter::direct(self.config.bandwidth_quota);
+        for limiter in self.bulk_limiters.values_mut() {
+            limiter.set_quota(&self.config.bandwidth_quota, self.max_byte_cost);
+        }
+    }
+
+    /// Cap the bandwidth used by a bulk channel to a share of the bandwidth budget
+    pub(crate) fn add_bulk_channel(&mut self, net_id: NetId, bandwidth_share: f32) {
+        let mut limiter = BulkLimiter {
+            share: bandwidth_share.clamp(0.0, 1.0),
+            rate: 0.0,
+            burst: 0.0,
+            available: 0.0,
+            last_update: None,
+        };
+        limiter.set_quota(&self.config.bandwidth_quota, self.max_byte_cost);
+        limiter.available = limiter.burst;
+        self.bulk_limiters.insert(net_id, limiter);
+    }
+
+    /// Update the bandwidth budget (in bytes per second), within the range given to
+    /// [`set_bandwidth_range`](Self::set_bandwidth_range).
+    ///
+    /// The limiters keep the capacity they accumulated, so updating the budget often is cheap and does not
+    /// let us send more than the budget.
+    pub(crate) fn update_bandwidth(&mut self, bytes_per_second: u32) {
+        let max_bandwidth = 1.0
+            / self
+                .config
+                .bandwidth_quota
+                .replenish_interval()
+                .as_secs_f32();
+        self.byte_cost =
+            (max_bandwidth / bytes_per_second.max(1) as f32).clamp(1.0, self.max_byte_cost);
+    }
+
+    /// Number of limiter cells that `bytes` cost with the current bandwidth budget
+    fn cost(&self, bytes: NonZeroU32) -> NonZeroU32 {
+        NonZeroU32::new((bytes.get() as f32 * self.byte_cost).ceil() as u32).unwrap_or(bytes)
+    }
+
     /// Create a channel to notify when a replication update message is actually sent (included in packet)
     /// (as opposed to dropped because of the bandwidth quota)
     pub(crate) fn subscribe_replication_update_sent_messages(&mut self) -> Receiver<MessageId> {
@@ -83,15 +160,42 @@ impl PriorityManager {
         receiver
     }
 
+    /// Consume `bytes` from the bandwidth quota, for data that is not a message (for example an MTU probe).
+    ///
+    /// Returns false if the quota does not have enough capacity, in which case nothing is consumed.
+    pub(crate) fn check_bandwidth(&self, bytes: u32) -> bool {
+        if !self.config.enabled {
+            return true;
+        }
+        NonZeroU32::new(bytes).map_or(true, |bytes| {
+            matches!(self.limiter.check_n(self.cost(bytes)), Ok(Ok(())))
+        })
+    }
+
+    /// Whether some messages that could not be sent previously are waiting to be sent
+    pub(crate) fn has_buffered_messages(&self) -> bool {
+        !self.buffered_data.is_empty()
+    }
+
+    /// Number of messages of each channel that were discarded (not sent and not kept to be sent later)
+    /// since the last call
+    pub(crate) fn take_num_discarded(&mut self) -> HashMap<NetId, u32> {
+        std::mem::take(&mut self.num_discarded)
+    }
+
     // TODO: maybe accumulat ethe used_bytes in the priority_manager instead of returning here?
     /// Filter the messages by priority and bandwidth quota
     /// Returns the list of messages that we can send, along with the amount of bytes we used
     /// in the rate limiter.
+    ///
+    /// The unreliable messages that could not be sent are kept (up to the `max_buffered_age` of their channel)
+    /// and are considered again on the next call, with an accumulated priority.
     pub(crate) fn priority_filter(
         &mut self,
         data: Vec<(NetId, (VecDeque<SingleData>, VecDeque<FragmentData>))>,
         channel_registry: &ChannelRegistry,
         tick: Tick,
+        current_time: WrappedTime,
     ) -> (
         BTreeMap<NetId, (VecDeque<SingleData>, VecDeque<FragmentData>)>,
         u32,
@@ -131,8 +235,10 @@ impl PriorityManager {
                         }
                         BufferedMessage {
                             priority: single.priority * channel_priority,
+                            base_priority: single.priority * channel_priority,
                             channel_net_id: net_id,
                             message_container: MessageContainer::Single(single),
+                            buffered_at: current_time,
                         }
                     })
                     .chain(fragment.into_iter().map(move |mut fragment| {
@@ -141,19 +247,22 @@ impl PriorityManager {
                         }
                         BufferedMessage {
                             priority: fragment.priority * channel_priority,
+                            base_priority: fragment.priority * channel_priority,
                             channel_net_id: net_id,
                             message_container: MessageContainer::Fragment(fragment),
+                            buffered_at: current_time,
                         }
                     }))
             })
             .collect::<Vec<_>>();
-        // // NOTE: maybe we cannot do this, because for some channels we need to know
-        // //  if the message was actually sent or not? (reliable channels)
-        // // add all new messages to the list of messages that could not be sent
-        // self.buffered_data.extend(all_messages);
+        // the messages that could not be sent previously accumulate priority, so that messages
+        // from low priority channels are not starved
+        all_messages.extend(self.buffered_data.drain(..).map(|mut buffered_message| {
+            buffered_message.priority += buffered_message.base_priority;
+            buffered_message
+        }));
 
         // sort from highest priority to lower
-        // self.buffered_data
         all_messages.sort_by(|a, b| a.priority.partial_cmp(&b.priority).unwrap());
         trace!(
             "all messages to send, sorted by priority: {:?}",
@@ -164,23 +273,49 @@ impl PriorityManager {
         let mut data_to_send: BTreeMap<NetId, (VecDeque<SingleData>, VecDeque<FragmentData>)> =
             BTreeMap::new();
         let mut bytes_used = 0;
+        // messages that were not sent, but that could still fit in the bandwidth budget
+        let mut not_sent = vec![];
         while let Some(buffered_message) = all_messages.pop() {
             trace!(channel=?buffered_message.channel_net_id, "Sending message with priority {:?}", buffered_message.priority);
             // we don't use the exact size of the message, but the size of the bytes
             // we will adjust for this later
             let message_bytes = buffered_message.message_container.bytes().len() as u32;
-            let nonzero_message_bytes = NonZeroU32::try_from(message_bytes).unwrap();
-            let Ok(result) = self.limiter.check_n(nonzero_message_bytes) else {
+            let message_cost = self.cost(NonZeroU32::try_from(message_bytes).unwrap());
+            // bulk channels cannot use more than their share of the bandwidth, so that they don't starve
+            // the other channels. The chunks that are not sent will be sent again later.
+            // The share is only consumed once the main limiter accepts the message.
+            if let Some(bulk_limiter) = self.bulk_limiters.get_mut(&buffered_message.channel_net_id)
+            {
+                bulk_limiter.update(current_time);
+                if !bulk_limiter.has_capacity(message_cost) {
+                    trace!(channel=?buffered_message.channel_net_id, "Bandwidth share of the bulk channel reached");
+                    not_sent.push(buffered_message);
+                    continue;
+                }
+            }
+            let Ok(result) = self.limiter.check_n(message_cost) else {
                 error!("the bandwidth does not have enough capacity for a message of this size!");
+                // reliable messages will be sent again by their channel
+                if !is_reliable(channel_registry, buffered_message.channel_net_id) {
+                    *self
+                        .num_discarded
+                        .entry(buffered_message.channel_net_id)
+                        .or_default() += 1;
+                }
                 break;
             };
             let Ok(()) = result else {
                 debug!("Bandwidth quota reached, no more messages can be sent this tick");
+                not_sent.push(buffered_message);
                 break;
             };
 
             // keep track of the bytes we added to the rate limiter
             bytes_used += message_bytes;
+            if let Some(bulk_limiter) = self.bulk_limiters.get_mut(&buffered_message.channel_net_id)
+            {
+                bulk_limiter.consume(message_cost);
+            }
 
             // the message is allowed, add it to the list of messages to send
             let channel_data = data_to_send
@@ -216,13 +351,33 @@ impl PriorityManager {
             }
         }
 
-        // all the other messages that don't make the cut, we just drop
-        // - unreliable messages: they are unreliable so it's ok
-        // - reliable messages: they will be retried later, maybe with higher priority?
+        // all the other messages that don't make the cut:
+        // - unreliable messages: we keep them to try again later, unless they are older than the `max_buffered_age` of their channel
+        // - reliable messages: they will be retried later by the channel sender, with an accumulated priority
         // - unreliable entity updates: the replication sender keeps track for each entity of when we were able to send an update
         //   - PROBLEM: we could have the entity action not get sent (bandwidth), and then the priority still drops because the entity update
         //     was sent right after...
         // - reliable entity actions:
+        for buffered_message in not_sent.into_iter().chain(all_messages) {
+            let settings = &channel_registry
+                .get_builder_from_net_id(buffered_message.channel_net_id)
+                .unwrap()
+                .settings;
+            let keep = max_buffered_age(settings).is_some_and(|max_age| {
+                (current_time - buffered_message.buffered_at)
+                    .to_std()
+                    .unwrap_or_default()
+                    < max_age
+            });
+            if keep {
+                self.buffered_data.push(buffered_message);
+            } else if !settings.mode.is_reliable() {
+                *self
+                    .num_discarded
+                    .entry(buffered_message.channel_net_id)
+                    .or_default() += 1;
+            }
+        }
         let num_messages_sent = data_to_send
             .values()
             .map(|(single, fragment)| single.len() + fragment.len())
@@ -230,9 +385,267 @@ impl PriorityManager {
         debug!(
             bytes_sent = ?bytes_used,
             ?num_messages_sent,
-            num_messages_discarded = ?all_messages.len(),
+            num_messages_buffered = ?self.buffered_data.len(),
             "priority filter done.");
 
         (data_to_send, bytes_used)
     }
 }
+
+fn is_reliable(channel_registry: &ChannelRegistry, net_id: NetId) -> bool {
+    channel_registry
+        .get_builder_from_net_id(net_id)
+        .is_some_and(|builder| builder.settings.mode.is_reliable())
+}
+
+/// How long a message of this channel that could not be sent can be kept to be sent later.
+///
+/// Only unreliable messages without acks are kept: the reliable channels resend their messages themselves, and
+/// the replication updates are recomputed by the replication sender.
+fn max_buffered_age(settings: &ChannelSettings) -> Option<Duration> {
+    match settings.mode {
+        ChannelMode::UnorderedUnreliable | ChannelMode::SequencedUnreliable => {
+            settings.max_buffered_age
+        }
+        _ => None,
+    }
+}
+
+/// Limits a bulk channel to a share of the bandwidth budget.
+///
+/// Unlike the governor limiters, its capacity can be checked without being consumed, so that a chunk that is
+/// then rejected by the main limiter does not use the share of the channel.
+#[derive(Debug)]
+struct BulkLimiter {
+    /// Fraction of the bandwidth budget that the channel can use
+    share: f32,
+    /// Number of limiter cells replenished per second
+    rate: f32,
+    /// Maximum number of cells that can be accumulated.
+    ///
+    /// It is big enough to send a full packet (even when each byte costs `max_byte_cost` cells),
+    /// otherwise the channel could never send anything.
+    burst: f32,
+    /// Number of cells available
+    available: f32,
+    last_update: Option<WrappedTime>,
+}
+
+impl BulkLimiter {
+    fn set_quota(&mut self, bandwidth_quota: &Quota, max_byte_cost: f32) {
+        let cells_per_second = 1.0 / bandwidth_quota.replenish_interval().as_secs_f32();
+        self.rate = (cells_per_second * self.share).max(1.0);
+        self.burst = self.rate.max((MAX_MTU as f32 * max_byte_cost).ceil());
+        self.available = self.available.min(self.burst);
+    }
+
+    /// Replenish the cells for the time elapsed since the last update
+    fn update(&mut self, current_time: WrappedTime) {
+        if let Some(last_update) = self.last_update {
+            let elapsed = (current_time - last_update)
+                .to_std()
+                .unwrap_or_default()
+                .as_secs_f32();
+            self.available = (self.available + elapsed * self.rate).min(self.burst);
+        }
+        self.last_update = Some(current_time);
+    }
+
+    fn has_capacity(&self, cost: NonZeroU32) -> bool {
+        self.available >= cost.get() as f32
+    }
+
+    fn consume(&mut self, cost: NonZeroU32) {
+        self.available -= cost.get() as f32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::prelude::*;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    fn message(
+        channel: NetId,
+        priority: f32,
+    ) -> (NetId, (VecDeque<SingleData>, VecDeque<FragmentData>)) {
+        let single = SingleData::new(None, Bytes::from(vec![0; 60]), priority);
+        (channel, (VecDeque::from(vec![single]), VecDeque::new()))
+    }
+
+    #[test]
+    fn test_priority_accumulation() {
+        let mut channel_registry = ChannelRegistry::new();
+        channel_registry.add::<Channel1>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            priority: 3.0,
+            max_buffered_age: Some(Duration::from_millis(100)),
+            ..ChannelSettings::default()
+        });
+        channel_registry.add::<Channel2>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            priority: 2.0,
+            max_buffered_age: Some(Duration::from_millis(100)),
+            ..ChannelSettings::default()
+        });
+        let channel_1 = *channel_registry
+            .get_net_from_kind(&Channel1::kind())
+            .unwrap();
+        let channel_2 = *channel_registry
+            .get_net_from_kind(&Channel2::kind())
+            .unwrap();
+        // the budget only allows to send one message of 60 bytes
+        let quota = Quota::per_hour(nonzero!(100u32));
+        let mut manager = PriorityManager::new(PriorityConfig {
+            bandwidth_quota: quota,
+            enabled: true,
+        });
+
+        // the message of the channel with the lowest priority is kept
+        let (data, _) = manager.priority_filter(
+            vec![message(channel_1, 1.0), message(channel_2, 1.0)],
+            &channel_registry,
+            Tick(0),
+            WrappedTime::new(0),
+        );
+        assert!(data.contains_key(&channel_1));
+        assert!(!data.contains_key(&channel_2));
+        assert!(manager.has_buffered_messages());
+        assert!(manager.take_num_discarded().is_empty());
+
+        // its priority accumulated, so it is sent before the new message of the other channel
+        manager.limiter = DefaultDirectRateLimiter::direct(quota);
+        let (data, _) = manager.priority_filter(
+            vec![message(channel_1, 1.0)],
+            &channel_registry,
+            Tick(1),
+            WrappedTime::new(50),
+        );
+        assert!(!data.contains_key(&channel_1));
+        let (single, _) = data.get(&channel_2).unwrap();
+        // the message keeps the tick at which it was supposed to be sent
+        assert_eq!(single[0].tick, Some(Tick(0)));
+
+        // the message of channel 2 is discarded once it is older than the max age
+        manager.limiter = DefaultDirectRateLimiter::direct(quota);
+        let (data, _) = manager.priority_filter(
+            vec![message(channel_2, 1.0)],
+            &channel_registry,
+            Tick(2),
+            WrappedTime::new(60),
+        );
+        assert!(data.contains_key(&channel_1));
+        manager.limiter = DefaultDirectRateLimiter::direct(quota);
+        let (data, _) = manager.priority_filter(
+            vec![message(channel_1, 2.0)],
+            &channel_registry,
+            Tick(3),
+            WrappedTime::new(200),
+        );
+        assert!(data.contains_key(&channel_1));
+        assert!(!manager.has_buffered_messages());
+        assert_eq!(manager.take_num_discarded().get(&channel_2), Some(&1));
+    }
+
+    #[test]
+    fn test_messages_dropped() {
+        let mut channel_registry = ChannelRegistry::new();
+        channel_registry.add::<Channel1>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            ..ChannelSettings::default()
+        });
+        channel_registry.add::<Channel2>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            ..ChannelSettings::default()
+        });
+        let channel_1 = *channel_registry
+            .get_net_from_kind(&Channel1::kind())
+            .unwrap();
+        let channel_2 = *channel_registry
+            .get_net_from_kind(&Channel2::kind())
+            .unwrap();
+        // the budget does not allow to send any message
+        let quota = Quota::per_hour(nonzero!(100u32));
+        let mut manager = PriorityManager::new(PriorityConfig {
+            bandwidth_quota: quota,
+            enabled: true,
+        });
+        manager.priority_filter(
+            vec![message(channel_1, 1.0)],
+            &channel_registry,
+            Tick(0),
+            WrappedTime::new(0),
+        );
+
+        // by default the unreliable messages are not kept, and the reliable messages are sent
+        // again later by their channel, so they are not counted as dropped
+        let (data, _) = manager.priority_filter(
+            vec![message(channel_1, 1.0), message(channel_2, 2.0)],
+            &channel_registry,
+            Tick(1),
+            WrappedTime::new(0),
+        );
+        assert!(data.is_empty());
+        assert!(!manager.has_buffered_messages());
+        let num_discarded = manager.take_num_discarded();
+        assert_eq!(num_discarded.get(&channel_1), Some(&1));
+        assert_eq!(num_discarded.get(&channel_2), None);
+    }
+
+    #[test]
+    fn test_bulk_share_not_consumed_when_main_limiter_rejects() {
+        let mut channel_registry = ChannelRegistry::new();
+        channel_registry.add::<Channel1>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            priority: 10.0,
+            ..ChannelSettings::default()
+        });
+        channel_registry.add::<Channel2>(ChannelSettings {
+            mode: ChannelMode::Bulk(BulkSettings::default()),
+            ..ChannelSettings::default()
+        });
+        let channel_1 = *channel_registry
+            .get_net_from_kind(&Channel1::kind())
+            .unwrap();
+        let channel_2 = *channel_registry
+            .get_net_from_kind(&Channel2::kind())
+            .unwrap();
+        // the budget only allows to send one message of 60 bytes
+        let quota = Quota::per_hour(nonzero!(100u32));
+        let mut manager = PriorityManager::new(PriorityConfig {
+            bandwidth_quota: quota,
+            enabled: true,
+        });
+        manager.add_bulk_channel(channel_2, 0.5);
+        let available = manager.bulk_limiters[&channel_2].available;
+
+        // the main limiter rejects the chunk of the bulk channel, which keeps its share
+        let (data, _) = manager.priority_filter(
+            vec![message(channel_1, 1.0), message(channel_2, 1.0)],
+            &channel_registry,
+            Tick(0),
+            WrappedTime::new(0),
+        );
+        assert!(data.contains_key(&channel_1));
+        assert!(!data.contains_key(&channel_2));
+        assert_eq!(manager.bulk_limiters[&channel_2].available, available);
+
+        // once the main limiter accepts it, the chunk consumes the share
+        manager.limiter = DefaultDirectRateLimiter::direct(quota);
+        let (data, _) = manager.priority_filter(
+            vec![message(channel_2, 1.0)],
+            &channel_registry,
+            Tick(1),
+            WrappedTime::new(0),
+        );
+        assert!(data.contains_key(&channel_2));
+        assert_eq!(
+            manager.bulk_limiters[&channel_2].available,
+            available - 60.0
+        );
+    }
+}
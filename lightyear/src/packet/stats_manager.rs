@@ -79,6 +79,11 @@ impl PacketStatsManager {
         }
     }
 
+    /// Ratio of the packets sent during the stats buffer duration that were lost
+    pub(crate) fn packet_loss(&self) -> f32 {
+        self.final_stats.packet_loss
+    }
+
     // TODO: we could just emit raw stats, and then compute packet loss over an interval using prometheus/grafana
     /// Notify that a packet was sent
     pub(crate) fn sent_packet(&mut self) {
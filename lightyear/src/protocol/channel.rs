@@ -86,6 +86,11 @@ impl ChannelRegistry {
         self.name_map.get(kind).map(|s| s.as_str())
     }
 
+    /// Names of all the channels in the registry
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.name_map.values().map(|s| s.as_str())
+    }
+
     pub fn get_builder_from_net_id(&self, channel_id: ChannelId) -> Option<&ChannelBuilder> {
         let channel_kind = self.get_kind_from_net_id(channel_id)?;
         self.get_builder_from_kind(channel_kind)
@@ -14,7 +14,10 @@ use serde::{Deserialize, Serialize};
 
 use crate::client::components::{ComponentSyncMode, LerpFn, SyncMetadata};
 use crate::prelude::{Message, PreSpawnedPlayerObject};
+use crate::protocol::schema::VariantSchema;
 use crate::protocol::{BitSerializable, EventContext, Protocol};
+use crate::serialize::reader::ReadBuffer;
+use crate::serialize::writer::WriteBuffer;
 use crate::shared::events::connection::{
     IterComponentInsertEvent, IterComponentRemoveEvent, IterComponentUpdateEvent,
 };
@@ -31,6 +34,7 @@ pub trait ComponentProtocol:
     BitSerializable
     + Serialize
     + DeserializeOwned
+    + Clone
     + MapEntities
     + ComponentBehaviour
     + Debug
@@ -47,6 +51,32 @@ pub trait ComponentProtocol:
     /// Map from the type-id to the component kind for each component in the protocol
     fn type_ids() -> HashMap<TypeId, <Self::Protocol as Protocol>::ComponentKinds>;
 
+    /// Name, type, net id and encoding of each variant of the protocol, in declaration order
+    fn variants() -> Vec<VariantSchema>;
+
+    /// Returns true if the updates of this component are delta-compressed (`#[protocol(delta)]` or `#[protocol(fields)]`),
+    /// i.e. sent as a diff against the last value acked by the remote
+    fn delta_compression(&self) -> bool;
+
+    /// Returns true if the updates of this component only contain the fields that changed since the last value
+    /// acked by the remote (`#[protocol(fields)]`)
+    fn field_replication(&self) -> bool;
+
+    /// Number of fields of a component that uses field-level replication (see [`ReplicateFields`]), 0 otherwise
+    fn num_fields(&self) -> usize;
+
+    /// Returns true if the field at `index` is different from the same field of `previous`,
+    /// or if `previous` is a different component
+    fn field_changed(&self, previous: &Self, index: usize) -> bool;
+
+    /// Write the fields whose index is true in `fields` (see [`ReplicateFields`]).
+    ///
+    /// Returns an error if the component doesn't use field-level replication.
+    fn write_fields(&self, fields: &[bool], writer: &mut impl WriteBuffer) -> anyhow::Result<()>;
+
+    /// Overwrite the fields that were written by [`write_fields`](Self::write_fields)
+    fn read_fields(&mut self, reader: &mut impl ReadBuffer) -> anyhow::Result<()>;
+
     /// Apply a ComponentInsert to an entity
     fn insert(self, entity: &mut EntityWorldMut);
 
@@ -58,6 +88,12 @@ pub trait ComponentProtocol:
         app: &mut App,
     );
 
+    /// Add the systems that copy the resources of the protocol (`#[protocol(resource)]`) to the replicated entities
+    fn add_resource_send_systems<R: ReplicationSend<Self::Protocol>>(app: &mut App);
+
+    /// Add the systems that copy the replicated entities to the resources of the protocol (`#[protocol(resource)]`)
+    fn add_resource_receive_systems<R: ReplicationSend<Self::Protocol>>(app: &mut App);
+
     /// Adds Component-related events to the app
     fn add_events<Ctx: EventContext>(app: &mut App);
 
@@ -123,6 +159,29 @@ pub trait ComponentProtocol:
     }
 }
 
+/// Component whose updates can contain only the fields that changed, instead of the whole component.
+///
+/// It is used for the variants of the `ComponentProtocol` that are marked with `#[protocol(fields)]`,
+/// and can be derived with `#[derive(ReplicateFields)]`.
+/// The sender compares each field with the previous value that it sent to know when the field last changed,
+/// so that an update only contains the fields that changed since the value acked by the remote.
+/// Each field is serialized separately, with its own type's serde implementation: the field-level serde
+/// attributes and the `#[protocol(quantize)]`/`#[protocol(encoding)]` encodings of the variant are not applied
+/// to the partial updates. The derive skips the fields marked with `#[serde(skip)]`.
+pub trait ReplicateFields {
+    /// Number of fields of the component
+    fn num_fields(&self) -> usize;
+
+    /// Returns true if the field at `index` is different from the same field of `previous`
+    fn field_changed(&self, previous: &Self, index: usize) -> bool;
+
+    /// Write, for each field, whether its index is true in `fields`, followed by the field if it is
+    fn write_fields(&self, fields: &[bool], writer: &mut impl WriteBuffer) -> anyhow::Result<()>;
+
+    /// Overwrite the fields that were written by [`write_fields`](Self::write_fields)
+    fn read_fields(&mut self, reader: &mut impl ReadBuffer) -> anyhow::Result<()>;
+}
+
 // TODO: enum_delegate doesn't work with generics + cannot be used multiple times since it derives a bunch of Into/From traits
 /// Trait to delegate a method from the ComponentProtocol enum to the inner Component type
 ///  We use it mainly for the IntoKind, From implementations
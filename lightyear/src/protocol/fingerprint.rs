@@ -0,0 +1,118 @@
+//! Stable hash of a [`Protocol`], used to check that the client and the server use the same protocol
+//!
+//! The fingerprint is sent by the client in the connection request; the server rejects the connection
+//! if it doesn't match its own fingerprint.
+use std::hash::Hasher;
+
+use crate::protocol::component::ComponentProtocol;
+use crate::protocol::layout::type_layout;
+use crate::protocol::message::MessageProtocol;
+use crate::protocol::schema::{mode_name, VariantSchema};
+use crate::protocol::Protocol;
+
+/// FNV-1a hasher.
+///
+/// We don't use the std `DefaultHasher` because its algorithm is not guaranteed to be the same
+/// between different releases of Rust, and the client and server could be compiled with different versions.
+struct FnvHasher(u64);
+
+impl FnvHasher {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+
+    /// Hash a string, followed by a separator so that ("ab", "c") and ("a", "bc") give different hashes
+    fn write_str(&mut self, s: &str) {
+        self.write(s.as_bytes());
+        self.write_u8(0xff);
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+}
+
+/// Compute the fingerprint of a protocol from:
+/// - the name, mode and direction of each channel, in the order in which they were added
+/// - the name, type and encoding of each variant of the [`MessageProtocol`]
+/// - the name, type and encoding of each variant of the [`ComponentProtocol`]
+/// - the [layout](crate::protocol::layout) of the messages, components and inputs (the names and types of their fields)
+///
+/// Only the kind of each channel mode is included: the settings of the channels (like the priority or
+/// the resend delays) only affect the local side of the connection.
+pub(crate) fn protocol_fingerprint<P: Protocol>(protocol: &P) -> u64 {
+    let mut hasher = FnvHasher::new();
+
+    hasher.write_str("channels");
+    let channel_registry = protocol.channel_registry();
+    for net_id in 0..channel_registry.kind_map.next_net_id {
+        let Some(kind) = channel_registry.get_kind_from_net_id(net_id) else {
+            continue;
+        };
+        hasher.write_str(channel_registry.name(kind).unwrap_or_default());
+        if let Some(builder) = channel_registry.get_builder_from_kind(kind) {
+            hasher.write_str(mode_name(&builder.settings.mode));
+            hasher.write_str(&format!("{:?}", builder.settings.direction));
+        }
+    }
+
+    hasher.write_str("messages");
+    for variant in P::Message::variants() {
+        hash_variant(&mut hasher, &variant);
+    }
+
+    hasher.write_str("components");
+    for variant in P::Components::variants() {
+        hash_variant(&mut hasher, &variant);
+    }
+
+    hasher.write_str("layouts");
+    hasher.write_str(&type_layout::<P::Message>());
+    hasher.write_str(&type_layout::<P::Components>());
+    hasher.write_str(&type_layout::<P::Input>());
+    hasher.finish()
+}
+
+fn hash_variant(hasher: &mut FnvHasher, variant: &VariantSchema) {
+    hasher.write_str(variant.name);
+    hasher.write_str(variant.type_name);
+    hasher.write_str(variant.encoding.unwrap_or_default());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[test]
+    fn test_fnv_hasher() {
+        // reference value of the FNV-1a 64-bit hash
+        let mut hasher = FnvHasher::new();
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_protocol_fingerprint() {
+        assert_eq!(protocol().fingerprint(), protocol().fingerprint());
+
+        // the default protocol doesn't have the channels that were added
+        assert_ne!(
+            protocol().fingerprint(),
+            MyProtocol::default().fingerprint()
+        );
+    }
+}
@@ -0,0 +1,527 @@
+//! Description of the layout of a type, derived from its [`Deserialize`](serde::Deserialize) implementation
+//!
+//! The description lists the structs and enums contained in the type, with the names and the types of their fields.
+//! It is included in the [fingerprint](super::fingerprint) of the protocol, so that a client and a server whose
+//! messages or components have different fields cannot connect, even if the types have the same names.
+//!
+//! The type is traced by deserializing it from a [`Tracer`], which records every call that the
+//! `Deserialize` implementation makes instead of reading actual data.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Display;
+
+use bevy::prelude::Entity;
+use serde::de::value::StrDeserializer;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Visitor};
+
+/// Sequences, maps and options that are nested deeper than this are traced as empty, so that the
+/// trace of recursive types terminates
+const MAX_DEPTH: usize = 16;
+
+#[derive(Default)]
+struct State {
+    /// Shape of each struct and enum variant that was traced, by name
+    containers: BTreeMap<String, String>,
+    /// Indices of the variants of each enum that were already traced
+    traced_variants: HashMap<&'static str, BTreeSet<usize>>,
+    /// Variants that contain an enum with variants that were not traced yet
+    routes: BTreeSet<(&'static str, usize)>,
+    /// Variants that are being traced
+    stack: Vec<(&'static str, usize)>,
+    /// Number of variants traced so far
+    num_traced: usize,
+    /// True if the state changed during the current pass
+    changed: bool,
+}
+
+/// Returns a description of the layout of `T`.
+///
+/// Only one variant of each enum can be traced at a time, so the type is traced again until all the variants
+/// that can be reached were traced. Once all the variants of an enum were traced, the next passes go through
+/// the variants that lead to enums that were not fully traced.
+pub fn type_layout<T: DeserializeOwned>() -> String {
+    let mut state = State::default();
+    let mut root = String::new();
+    loop {
+        state.changed = false;
+        state.stack.clear();
+        root.clear();
+        // the trace stops early if the `Deserialize` implementation rejects the values given by the tracer.
+        // In that case we keep the layout that was recorded so far, which is the same on both sides
+        let _ = T::deserialize(Tracer {
+            out: &mut root,
+            state: &mut state,
+            depth: 0,
+        });
+        if !state.changed {
+            break;
+        }
+    }
+    let mut layout = root;
+    for (name, shape) in &state.containers {
+        layout.push_str(&format!(";{name}={shape}"));
+    }
+    layout
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// Deserializer that writes the description of the value that is deserialized in `out`
+struct Tracer<'a> {
+    out: &'a mut String,
+    state: &'a mut State,
+    depth: usize,
+}
+
+impl<'a> Tracer<'a> {
+    fn elements<'b>(
+        &'b mut self,
+        out: &'b mut String,
+        names: &'static [&'static str],
+        len: usize,
+    ) -> Elements<'b> {
+        Elements {
+            out,
+            state: self.state,
+            depth: self.depth + 1,
+            names,
+            len,
+            index: 0,
+        }
+    }
+
+    /// Number of elements given to the sequences and maps
+    fn num_elements(&self) -> usize {
+        usize::from(self.depth < MAX_DEPTH)
+    }
+}
+
+macro_rules! trace_primitive {
+    ($($method:ident => $name:literal, $visit:ident($($value:expr)?);)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.out.push_str($name);
+                visitor.$visit($($value)?)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Tracer<'a> {
+    type Error = Error;
+
+    trace_primitive! {
+        deserialize_bool => "bool", visit_bool(false);
+        deserialize_i8 => "i8", visit_i8(0);
+        deserialize_i16 => "i16", visit_i16(0);
+        deserialize_i32 => "i32", visit_i32(0);
+        deserialize_i64 => "i64", visit_i64(0);
+        deserialize_i128 => "i128", visit_i128(0);
+        deserialize_u8 => "u8", visit_u8(0);
+        deserialize_u16 => "u16", visit_u16(0);
+        deserialize_u32 => "u32", visit_u32(0);
+        // the bits of a valid entity, which is the most common type serialized as a u64
+        deserialize_u64 => "u64", visit_u64(Entity::PLACEHOLDER.to_bits());
+        deserialize_u128 => "u128", visit_u128(0);
+        deserialize_f32 => "f32", visit_f32(0.0);
+        deserialize_f64 => "f64", visit_f64(0.0);
+        deserialize_char => "char", visit_char('a');
+        deserialize_str => "str", visit_str("");
+        deserialize_string => "str", visit_string(String::new());
+        deserialize_bytes => "bytes", visit_bytes(&[]);
+        deserialize_byte_buf => "bytes", visit_byte_buf(Vec::new());
+        deserialize_unit => "()", visit_unit();
+        deserialize_any => "any", visit_unit();
+        deserialize_identifier => "identifier", visit_str("");
+        deserialize_ignored_any => "ignored", visit_unit();
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.out.push_str("Option<");
+        let result = if self.depth < MAX_DEPTH {
+            visitor.visit_some(Tracer {
+                out: &mut *self.out,
+                state: &mut *self.state,
+                depth: self.depth + 1,
+            })
+        } else {
+            visitor.visit_none()
+        };
+        self.out.push('>');
+        result
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.state
+            .containers
+            .insert(name.to_string(), "()".to_string());
+        self.out.push_str(name);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut shape = String::new();
+        let result = visitor.visit_newtype_struct(Tracer {
+            out: &mut shape,
+            state: &mut *self.state,
+            depth: self.depth + 1,
+        });
+        self.state
+            .containers
+            .insert(name.to_string(), format!("({shape})"));
+        self.out.push_str(name);
+        result
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        let mut shape = String::new();
+        let len = self.num_elements();
+        let result = visitor.visit_seq(self.elements(&mut shape, &[], len));
+        self.out.push_str(&format!("Seq<{shape}>"));
+        result
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        mut self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut shape = String::new();
+        let result = visitor.visit_seq(self.elements(&mut shape, &[], len));
+        self.out.push_str(&format!("({shape})"));
+        result
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut shape = String::new();
+        let result = visitor.visit_seq(self.elements(&mut shape, &[], len));
+        self.state
+            .containers
+            .insert(name.to_string(), format!("({shape})"));
+        self.out.push_str(name);
+        result
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut shape = String::new();
+        let remaining = self.depth < MAX_DEPTH;
+        let result = visitor.visit_map(Entries {
+            out: &mut shape,
+            state: &mut *self.state,
+            depth: self.depth + 1,
+            remaining,
+        });
+        self.out.push_str(&format!("Map<{shape}>"));
+        result
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut shape = String::new();
+        let result = visitor.visit_seq(self.elements(&mut shape, fields, fields.len()));
+        self.state
+            .containers
+            .insert(name.to_string(), format!("{{{shape}}}"));
+        self.out.push_str(name);
+        result
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.state
+            .containers
+            .insert(name.to_string(), format!("enum[{}]", variants.join(",")));
+        self.out.push_str(name);
+        if variants.is_empty() {
+            return Err(de::Error::custom("enum without variants"));
+        }
+        let traced = self.state.traced_variants.entry(name).or_default();
+        let (index, is_route) = match (0..variants.len()).find(|index| !traced.contains(index)) {
+            // trace a variant that was not traced yet
+            Some(index) => {
+                traced.insert(index);
+                if traced.len() < variants.len() {
+                    let stack = self.state.stack.iter().copied();
+                    self.state.routes.extend(stack);
+                }
+                self.state.num_traced += 1;
+                self.state.changed = true;
+                (index, false)
+            }
+            // go through a variant that leads to an enum that was not fully traced
+            None => (0..variants.len())
+                .find(|index| self.state.routes.contains(&(name, *index)))
+                .map_or((0, false), |index| (index, true)),
+        };
+        let num_traced = self.state.num_traced;
+        self.state.stack.push((name, index));
+        let variant = variants[index];
+        let result = visitor.visit_enum(Variant {
+            name: format!("{name}::{variant}"),
+            variant,
+            state: &mut *self.state,
+            depth: self.depth + 1,
+        });
+        self.state.stack.pop();
+        // the enums contained in the variant were fully traced
+        if is_route && self.state.num_traced == num_traced {
+            self.state.routes.remove(&(name, index));
+            self.state.changed = true;
+        }
+        result
+    }
+}
+
+/// Elements of a sequence, a tuple or a struct
+struct Elements<'a> {
+    out: &'a mut String,
+    state: &'a mut State,
+    depth: usize,
+    /// Names of the fields, for structs
+    names: &'static [&'static str],
+    len: usize,
+    index: usize,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for Elements<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.index == self.len {
+            return Ok(None);
+        }
+        if self.index > 0 {
+            self.out.push(',');
+        }
+        if let Some(name) = self.names.get(self.index) {
+            self.out.push_str(name);
+            self.out.push(':');
+        }
+        self.index += 1;
+        seed.deserialize(Tracer {
+            out: &mut *self.out,
+            state: &mut *self.state,
+            depth: self.depth,
+        })
+        .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.index)
+    }
+}
+
+/// Entries of a map
+struct Entries<'a> {
+    out: &'a mut String,
+    state: &'a mut State,
+    depth: usize,
+    remaining: bool,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for Entries<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if !std::mem::take(&mut self.remaining) {
+            return Ok(None);
+        }
+        seed.deserialize(Tracer {
+            out: &mut *self.out,
+            state: &mut *self.state,
+            depth: self.depth,
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        self.out.push(',');
+        seed.deserialize(Tracer {
+            out: &mut *self.out,
+            state: &mut *self.state,
+            depth: self.depth,
+        })
+    }
+}
+
+/// Variant of an enum that is being traced
+struct Variant<'a> {
+    /// Name of the enum and of the variant
+    name: String,
+    variant: &'static str,
+    state: &'a mut State,
+    depth: usize,
+}
+
+impl<'a> Variant<'a> {
+    fn record(self, shape: String) {
+        self.state.containers.insert(self.name, shape);
+    }
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for Variant<'a> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let variant = seed.deserialize(StrDeserializer::<Error>::new(self.variant))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for Variant<'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        self.record("()".to_string());
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        let mut shape = String::new();
+        let result = seed.deserialize(Tracer {
+            out: &mut shape,
+            state: &mut *self.state,
+            depth: self.depth,
+        });
+        self.record(format!("({shape})"));
+        result
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        let mut shape = String::new();
+        let result = visitor.visit_seq(Elements {
+            out: &mut shape,
+            state: &mut *self.state,
+            depth: self.depth,
+            names: &[],
+            len,
+            index: 0,
+        });
+        self.record(format!("({shape})"));
+        result
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut shape = String::new();
+        let result = visitor.visit_seq(Elements {
+            out: &mut shape,
+            state: &mut *self.state,
+            depth: self.depth,
+            names: fields,
+            len: fields.len(),
+            index: 0,
+        });
+        self.record(format!("{{{shape}}}"));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    enum Shape {
+        Point,
+        Circle(Position, f32),
+        Polygon { points: Vec<Position> },
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    enum Message {
+        Empty,
+        Shapes(Vec<Shape>),
+        Nested(Option<Box<Message>>),
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Tree {
+        children: Vec<Tree>,
+        parent: Option<Entity>,
+    }
+
+    #[test]
+    fn test_type_layout() {
+        assert_eq!(
+            type_layout::<Shape>(),
+            "Shape;Position={x:f32,y:f32};Shape=enum[Point,Circle,Polygon];Shape::Circle=(Position,f32);\
+            Shape::Point=();Shape::Polygon={points:Seq<Position>}"
+        );
+        // the variants of nested enums are traced too
+        let layout = type_layout::<Message>();
+        assert!(layout.contains(";Shape::Circle=(Position,f32);"));
+        assert!(layout.contains(";Shape::Polygon={points:Seq<Position>}"));
+        // recursive types are traced up to a maximum depth
+        assert!(type_layout::<Tree>().ends_with(";Tree={children:Seq<Tree>,parent:Option<u64>}"));
+    }
+
+    #[test]
+    fn test_type_layout_fields() {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Position {
+            x: f32,
+            z: f32,
+        }
+        // the layout changes if a field is renamed, even if the types have the same name
+        assert_ne!(type_layout::<Position>(), type_layout::<self::Position>());
+    }
+}
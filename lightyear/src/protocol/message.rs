@@ -9,6 +9,7 @@ use serde::Serialize;
 use crate::inputs::native::input_buffer::InputMessage;
 use crate::packet::message::Message;
 use crate::protocol::registry::TypeKind;
+use crate::protocol::schema::VariantSchema;
 use crate::protocol::{BitSerializable, EventContext, Protocol};
 #[cfg(feature = "leafwing")]
 use crate::shared::events::components::InputMessageEvent;
@@ -46,6 +47,9 @@ pub trait MessageProtocol:
     /// Get the name of the Message
     fn name(&self) -> &'static str;
 
+    /// Name, type, net id and encoding of each variant of the protocol, in declaration order
+    fn variants() -> Vec<VariantSchema>;
+
     /// Returns the MessageKind of the Message
     fn kind(&self) -> MessageKind;
 
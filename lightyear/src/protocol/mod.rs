@@ -19,6 +19,7 @@ use crate::channel::builder::{Channel, ChannelSettings};
 use crate::protocol::channel::ChannelRegistry;
 use crate::protocol::component::{ComponentProtocol, ComponentProtocolKind};
 use crate::protocol::message::MessageProtocol;
+use crate::protocol::schema::ProtocolSchema;
 use crate::serialize::reader::ReadBuffer;
 use crate::serialize::writer::WriteBuffer;
 use crate::shared::replication::ReplicationSend;
@@ -29,12 +30,19 @@ pub(crate) mod channel;
 /// Defines the various components that can be sent over the network
 pub(crate) mod component;
 
+/// Stable hash of the protocol, checked during the connection handshake
+pub(crate) mod fingerprint;
+pub(crate) mod layout;
+
 /// Defines the various messages that can be sent over the network
 pub(crate) mod message;
 
 /// Provides a mapping from a type to a unique identifier that can be serialized
 pub(crate) mod registry;
 
+/// Machine-readable description of the protocol
+pub(crate) mod schema;
+
 // TODO: how to make components or messages or inputs optional? Just by having an implementation for () ?
 /// The [`Protocol`] trait defines the various channels, inputs, messages and components that will be used to transmit information between
 /// the client and server.
@@ -99,6 +107,27 @@ pub trait Protocol: Send + Sync + Clone + Debug + TypePath + 'static {
     fn add_channel<C: Channel>(&mut self, settings: ChannelSettings) -> &mut Self;
     fn channel_registry(&self) -> &ChannelRegistry;
     fn add_per_component_replication_send_systems<R: ReplicationSend<Self>>(app: &mut App);
+
+    /// Stable hash of the channels, messages and components of the protocol.
+    ///
+    /// It is checked by the server when a client connects, so that a client using a different
+    /// protocol is rejected with [`DisconnectReason::ProtocolMismatch`](crate::connection::client::DisconnectReason::ProtocolMismatch).
+    fn fingerprint(&self) -> u64 {
+        fingerprint::protocol_fingerprint(self)
+    }
+
+    /// Description of the channels, messages, components and inputs of the protocol,
+    /// with the net ids that identify them on the wire
+    fn schema(&self) -> ProtocolSchema {
+        ProtocolSchema::new(self)
+    }
+
+    /// [`schema`](Protocol::schema) of the protocol, serialized as JSON
+    #[cfg(feature = "schema_json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "schema_json")))]
+    fn schema_json(&self) -> String {
+        self.schema().to_json()
+    }
 }
 
 // TODO: give an option to change names of types
@@ -161,17 +190,22 @@ macro_rules! protocolize {
                         direction: ChannelDirection::Bidirectional,
                         // we want to send the entity actions as soon as possible
                         priority: 10.0,
+                        ..ChannelSettings::default()
                     });
                     protocol.add_channel::<EntityUpdatesChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliableWithAcks,
                         direction: ChannelDirection::Bidirectional,
                         priority: 1.0,
+                        ..ChannelSettings::default()
                     });
                     protocol.add_channel::<PingChannel>(ChannelSettings {
                         mode: ChannelMode::SequencedUnreliable,
                         direction: ChannelDirection::Bidirectional,
                         // we always want to include the ping in the packet
                         priority: 1000.0,
+                        // a ping that is sent late would give a wrong estimate of the RTT
+                        max_buffered_age: None,
+                        ..ChannelSettings::default()
                     });
                     protocol.add_channel::<InputChannel>(ChannelSettings {
                         // we want to use unordered unreliable because the server has a buffer to re-order the inputs anyway
@@ -181,16 +215,33 @@ macro_rules! protocolize {
                         mode: ChannelMode::UnorderedUnreliable,
                         direction: ChannelDirection::ClientToServer,
                         priority: 3.0,
+                        ..ChannelSettings::default()
                     });
                     protocol.add_channel::<DefaultUnorderedUnreliableChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliable,
                         direction: ChannelDirection::Bidirectional,
                         priority: 1.0,
+                        ..ChannelSettings::default()
                     });
                     protocol.add_channel::<TickBufferChannel>(ChannelSettings {
                         mode: ChannelMode::TickBuffered,
                         direction: ChannelDirection::ClientToServer,
                         priority: 1.0,
+                        ..ChannelSettings::default()
+                    });
+                    protocol.add_channel::<BulkControlChannel>(ChannelSettings {
+                        mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+                        direction: ChannelDirection::Bidirectional,
+                        // the window updates should be sent quickly so that the bulk transfers don't stall
+                        priority: 100.0,
+                        ..ChannelSettings::default()
+                    });
+                    protocol.add_channel::<AuthorityChannel>(ChannelSettings {
+                        // the authority changes of an entity must be applied in the order they were made
+                        mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+                        direction: ChannelDirection::Bidirectional,
+                        priority: 10.0,
+                        ..ChannelSettings::default()
                     });
                     protocol
                 }
@@ -258,32 +309,54 @@ macro_rules! protocolize {
                         direction: ChannelDirection::Bidirectional,
                         // we want to send the entity actions as soon as possible
                         priority: 10.0,
+                        ..ChannelSettings::default()
                     });
                     protocol.add_channel::<EntityUpdatesChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliableWithAcks,
                         direction: ChannelDirection::Bidirectional,
                         priority: 1.0,
+                        ..ChannelSettings::default()
                     });
                     protocol.add_channel::<PingChannel>(ChannelSettings {
                         mode: ChannelMode::SequencedUnreliable,
                         direction: ChannelDirection::Bidirectional,
                         // we always want to include the ping in the packet
                         priority: 1000.0,
+                        // a ping that is sent late would give a wrong estimate of the RTT
+                        max_buffered_age: None,
+                        ..ChannelSettings::default()
                     });
                     protocol.add_channel::<InputChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliable,
                         direction: ChannelDirection::ClientToServer,
                         priority: 3.0,
+                        ..ChannelSettings::default()
                     });
                     protocol.add_channel::<DefaultUnorderedUnreliableChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliable,
                         direction: ChannelDirection::Bidirectional,
                         priority: 1.0,
+                        ..ChannelSettings::default()
                     });
                     protocol.add_channel::<TickBufferChannel>(ChannelSettings {
                         mode: ChannelMode::TickBuffered,
                         direction: ChannelDirection::ClientToServer,
                         priority: 1.0,
+                        ..ChannelSettings::default()
+                    });
+                    protocol.add_channel::<BulkControlChannel>(ChannelSettings {
+                        mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+                        direction: ChannelDirection::Bidirectional,
+                        // the window updates should be sent quickly so that the bulk transfers don't stall
+                        priority: 100.0,
+                        ..ChannelSettings::default()
+                    });
+                    protocol.add_channel::<AuthorityChannel>(ChannelSettings {
+                        // the authority changes of an entity must be applied in the order they were made
+                        mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+                        direction: ChannelDirection::Bidirectional,
+                        priority: 10.0,
+                        ..ChannelSettings::default()
                     });
                     protocol
                 }
@@ -0,0 +1,189 @@
+//! Machine-readable description of a [`Protocol`]
+//!
+//! The schema lists the channels, the variants of the message and component protocols and the input types,
+//! with the net ids that are used to identify them on the wire, and the serde layout of their types
+//! (the fields of the structs and the variants of the enums that they contain).
+//! With the `schema_json` feature, it can be exported as JSON (see `Protocol::schema_json`) for tools that
+//! are not written in Rust, or to check for breaking changes between two releases of a game.
+use serde::Serialize;
+
+use crate::channel::builder::ChannelMode;
+use crate::protocol::channel::ChannelId;
+use crate::protocol::component::ComponentProtocol;
+use crate::protocol::layout::type_layout;
+use crate::protocol::message::MessageProtocol;
+use crate::protocol::Protocol;
+
+/// Description of a [`Protocol`]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProtocolSchema {
+    /// Fingerprint of the protocol (see [`Protocol::fingerprint`])
+    pub fingerprint: u64,
+    /// Channels of the protocol, sorted by net id
+    pub channels: Vec<ChannelSchema>,
+    /// Variants of the [`MessageProtocol`], sorted by net id
+    pub messages: Vec<VariantSchema>,
+    /// Variants of the [`ComponentProtocol`], sorted by net id
+    pub components: Vec<VariantSchema>,
+    /// Input types of the protocol
+    pub inputs: Vec<InputSchema>,
+}
+
+/// Description of a channel
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChannelSchema {
+    pub net_id: ChannelId,
+    pub name: String,
+    /// Name of the [`ChannelMode`], without its settings
+    pub mode: &'static str,
+    /// Name of the [`ChannelDirection`](crate::channel::builder::ChannelDirection)
+    pub direction: String,
+    pub priority: f32,
+}
+
+/// Description of a variant of the [`MessageProtocol`] or of the [`ComponentProtocol`].
+///
+/// This is generated by the `message_protocol` and `component_protocol` macros.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct VariantSchema {
+    /// Index of the variant in the enum, which is used to identify the variant on the wire
+    pub net_id: u16,
+    /// Name of the variant
+    pub name: &'static str,
+    /// Type contained in the variant, as written in the enum
+    #[serde(rename = "type")]
+    pub type_name: &'static str,
+    /// Layout of the type, derived from its `Deserialize` implementation: the names and types of the fields
+    /// of the structs and the variants of the enums that it contains
+    pub layout: String,
+    /// Custom encoding of the variant (`gamma`, `fixed` or `quantized:<min>:<max>:<bits>`),
+    /// see [`encoding`](crate::serialize::encoding)
+    pub encoding: Option<&'static str>,
+}
+
+/// Description of an input type
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct InputSchema {
+    /// Name of the associated type of the [`Protocol`] (`Input`, `LeafwingInput1` or `LeafwingInput2`)
+    pub name: &'static str,
+    /// Full path of the input type, as returned by [`std::any::type_name`].
+    /// It is only informative: the exact name can change between compiler versions
+    #[serde(rename = "type")]
+    pub type_name: &'static str,
+    /// Layout of the input type, derived from its `Deserialize` implementation
+    pub layout: String,
+}
+
+impl ProtocolSchema {
+    pub(crate) fn new<P: Protocol>(protocol: &P) -> Self {
+        let channel_registry = protocol.channel_registry();
+        let channels = (0..channel_registry.kind_map.next_net_id)
+            .filter_map(|net_id| {
+                let kind = channel_registry.get_kind_from_net_id(net_id)?;
+                let settings = &channel_registry.get_builder_from_kind(kind)?.settings;
+                Some(ChannelSchema {
+                    net_id,
+                    name: channel_registry.name(kind).unwrap_or_default().to_string(),
+                    mode: mode_name(&settings.mode),
+                    direction: format!("{:?}", settings.direction),
+                    priority: settings.priority,
+                })
+            })
+            .collect();
+
+        #[allow(unused_mut)]
+        let mut inputs = vec![InputSchema {
+            name: "Input",
+            type_name: std::any::type_name::<P::Input>(),
+            layout: type_layout::<P::Input>(),
+        }];
+        #[cfg(feature = "leafwing")]
+        {
+            inputs.push(InputSchema {
+                name: "LeafwingInput1",
+                type_name: std::any::type_name::<P::LeafwingInput1>(),
+                layout: type_layout::<P::LeafwingInput1>(),
+            });
+            inputs.push(InputSchema {
+                name: "LeafwingInput2",
+                type_name: std::any::type_name::<P::LeafwingInput2>(),
+                layout: type_layout::<P::LeafwingInput2>(),
+            });
+        }
+
+        Self {
+            fingerprint: protocol.fingerprint(),
+            channels,
+            messages: P::Message::variants(),
+            components: P::Components::variants(),
+            inputs,
+        }
+    }
+
+    /// Serialize the schema to pretty-printed JSON
+    #[cfg(feature = "schema_json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "schema_json")))]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("the protocol schema can always be serialized")
+    }
+}
+
+pub(crate) fn mode_name(mode: &ChannelMode) -> &'static str {
+    match mode {
+        ChannelMode::UnorderedUnreliableWithAcks => "UnorderedUnreliableWithAcks",
+        ChannelMode::UnorderedUnreliable => "UnorderedUnreliable",
+        ChannelMode::SequencedUnreliable => "SequencedUnreliable",
+        ChannelMode::UnorderedReliable(_) => "UnorderedReliable",
+        ChannelMode::SequencedReliable(_) => "SequencedReliable",
+        ChannelMode::OrderedReliable(_) => "OrderedReliable",
+        ChannelMode::UnorderedReliableWithDeadline(_) => "UnorderedReliableWithDeadline",
+        ChannelMode::OrderedReliableWithDeadline(_) => "OrderedReliableWithDeadline",
+        ChannelMode::Bulk(_) => "Bulk",
+        ChannelMode::TickBuffered => "TickBuffered",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[test]
+    fn test_protocol_schema() {
+        let protocol = protocol();
+        let schema = protocol.schema();
+        assert_eq!(schema.fingerprint, protocol.fingerprint());
+
+        // the channels added by the user come after the internal channels
+        let channel = schema.channels.last().unwrap();
+        assert_eq!(channel.net_id as usize, schema.channels.len() - 1);
+        assert_eq!(channel.name, "Channel2");
+        assert_eq!(channel.mode, "UnorderedUnreliableWithAcks");
+
+        assert_eq!(
+            schema.messages[1],
+            VariantSchema {
+                net_id: 1,
+                name: "Message2",
+                type_name: "Message2",
+                layout: type_layout::<Message2>(),
+                encoding: None,
+            }
+        );
+        assert!(schema.messages[1].layout.contains("u32"));
+        assert_eq!(schema.components[3].name, "Component4");
+        assert!(schema.components[5].layout.contains("health"));
+        assert_eq!(schema.inputs[0].type_name, std::any::type_name::<MyInput>());
+        assert_eq!(schema.inputs[0].layout, type_layout::<MyInput>());
+    }
+
+    #[cfg(feature = "schema_json")]
+    #[test]
+    fn test_protocol_schema_json() {
+        let json = protocol().schema().to_json();
+        assert!(json.contains("\"name\": \"Message1\""));
+        assert!(json.contains("\"type\": \"Component1\""));
+        assert!(json.contains("\"layout\": "));
+    }
+}
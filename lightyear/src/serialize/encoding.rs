@@ -0,0 +1,278 @@
+//! Compact bit-level encodings for the variants of the message and component protocols, or for their fields
+//!
+//! The `#[protocol(quantize(min = -100.0, max = 100.0, bits = 16))]` and `#[protocol(encoding = "gamma")]`
+//! attributes of the `message_protocol` and `component_protocol` macros serialize the variant with these functions.
+//! Fields can be encoded separately with `#[serde(with = "...")]`, using the [`gamma`] module or a module
+//! generated by [`quantize_field!`](crate::quantize_field).
+//!
+//! The value is wrapped in a serde newtype struct named [`ENCODING_NEWTYPE_NAME`], and the [`Encoding`] is passed
+//! to bitcode along with it (see [`with_encoding`](bitcode::serde::with_encoding)):
+//! - `Gamma`: integers are encoded with Elias gamma coding, which is smaller for small values
+//! - `Quantized`: every float of the value is mapped to an integer of `bits` bits in the range `[min, max]`
+//!
+//! Other serde formats ignore the wrapper.
+use std::fmt::Formatter;
+use std::marker::PhantomData;
+
+pub use bitcode::serde::{NewtypeEncoding as Encoding, ENCODING_NEWTYPE_NAME};
+use serde::de::{Deserialize, Deserializer, Visitor};
+use serde::{Serialize, Serializer};
+
+/// Serialize a value with the bitcode `encoding`
+pub fn serialize_with_encoding<S: Serializer, T: Serialize + ?Sized>(
+    encoding: Encoding,
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    bitcode::serde::with_encoding(encoding, || {
+        serializer.serialize_newtype_struct(ENCODING_NEWTYPE_NAME, value)
+    })
+}
+
+/// Deserialize a value that was serialized with [`serialize_with_encoding`]
+pub fn deserialize_with_encoding<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    encoding: Encoding,
+    deserializer: D,
+) -> Result<T, D::Error> {
+    bitcode::serde::with_encoding(encoding, || {
+        deserializer.deserialize_newtype_struct(ENCODING_NEWTYPE_NAME, EncodedVisitor(PhantomData))
+    })
+}
+
+/// Serde helpers to encode a field with Elias gamma coding: `#[serde(with = "lightyear::serialize::encoding::gamma")]`
+pub mod gamma {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Encoding;
+
+    pub fn serialize<S: Serializer, T: Serialize + ?Sized>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::serialize_with_encoding(Encoding::Gamma, value, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        super::deserialize_with_encoding(Encoding::Gamma, deserializer)
+    }
+}
+
+/// Generates a module of serde helpers that quantize the floats of a field, to be used with
+/// `#[serde(with = "module")]`.
+///
+/// Each field can use its own range and precision:
+/// ```rust,ignore
+/// lightyear::quantize_field!(position_encoding, min = -1000.0, max = 1000.0, bits = 20);
+/// lightyear::quantize_field!(rotation_encoding, min = -1.0, max = 1.0, bits = 12);
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Transform {
+///     #[serde(with = "position_encoding")]
+///     position: Vec3,
+///     #[serde(with = "rotation_encoding")]
+///     rotation: Quat,
+/// }
+/// ```
+#[macro_export]
+macro_rules! quantize_field {
+    ($vis:vis $module:ident, min = $min:expr, max = $max:expr, bits = $bits:expr) => {
+        $vis mod $module {
+            use $crate::serialize::encoding::Encoding;
+
+            const ENCODING: Encoding = Encoding::Quantized {
+                min: $min,
+                max: $max,
+                bits: $bits,
+            };
+            const _: () = assert!(
+                $min < $max && $bits >= 1 && $bits <= 32,
+                "the range must not be empty, and `bits` must be between 1 and 32"
+            );
+
+            pub fn serialize<S, T>(value: &T, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+            where
+                S: $crate::_reexport::serde::Serializer,
+                T: $crate::_reexport::serde::Serialize + ?Sized,
+            {
+                $crate::serialize::encoding::serialize_with_encoding(ENCODING, value, serializer)
+            }
+
+            pub fn deserialize<'de, D, T>(deserializer: D) -> ::std::result::Result<T, D::Error>
+            where
+                D: $crate::_reexport::serde::Deserializer<'de>,
+                T: $crate::_reexport::serde::Deserialize<'de>,
+            {
+                $crate::serialize::encoding::deserialize_with_encoding(ENCODING, deserializer)
+            }
+        }
+    };
+}
+
+struct EncodedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for EncodedVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a value with a custom encoding")
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        T::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use crate::serialize::reader::ReadBuffer;
+    use crate::serialize::wordbuffer::reader::ReadWordBuffer;
+    use crate::serialize::wordbuffer::writer::WriteWordBuffer;
+    use crate::serialize::writer::WriteBuffer;
+
+    use super::*;
+
+    const QUANTIZED: Encoding = Encoding::Quantized {
+        min: -100.0,
+        max: 100.0,
+        bits: 16,
+    };
+    const GAMMA: Encoding = Encoding::Gamma;
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    enum Protocol {
+        Position(Position),
+        #[serde(
+            serialize_with = "serialize_quantized",
+            deserialize_with = "deserialize_quantized"
+        )]
+        QuantizedPosition(Position),
+        #[serde(
+            serialize_with = "serialize_gamma",
+            deserialize_with = "deserialize_gamma"
+        )]
+        Health(u32),
+        Transform(Transform),
+    }
+
+    crate::quantize_field!(position_encoding, min = -100.0, max = 100.0, bits = 16);
+    crate::quantize_field!(rotation_encoding, min = -1.0, max = 1.0, bits = 8);
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Transform {
+        #[serde(with = "position_encoding")]
+        position: Position,
+        #[serde(with = "rotation_encoding")]
+        rotation: f32,
+        scale: f32,
+    }
+
+    fn serialize_quantized<S: Serializer>(
+        value: &Position,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serialize_with_encoding(QUANTIZED, value, serializer)
+    }
+
+    fn deserialize_quantized<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Position, D::Error> {
+        deserialize_with_encoding(QUANTIZED, deserializer)
+    }
+
+    fn serialize_gamma<S: Serializer>(value: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with_encoding(GAMMA, value, serializer)
+    }
+
+    fn deserialize_gamma<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        deserialize_with_encoding(GAMMA, deserializer)
+    }
+
+    fn num_bits(value: &Protocol) -> usize {
+        let mut writer = WriteWordBuffer::with_capacity(100);
+        writer.serialize(value).unwrap();
+        writer.num_bits_written()
+    }
+
+    fn roundtrip(value: &Protocol) -> Protocol {
+        let mut writer = WriteWordBuffer::with_capacity(100);
+        writer.serialize(value).unwrap();
+        let bytes = writer.finish_write().to_vec();
+        let mut reader = ReadWordBuffer::start_read(&bytes);
+        let decoded = reader.deserialize::<Protocol>().unwrap();
+        reader.finish_read().unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_quantized_encoding() {
+        let position = Position {
+            x: 100.0,
+            y: -100.0,
+        };
+        // 1 bit for the variant index and 32 bits for each float
+        assert_eq!(num_bits(&Protocol::Position(position.clone())), 1 + 2 * 32);
+        // 3 bits for the variant index and 16 bits for each float
+        assert_eq!(
+            num_bits(&Protocol::QuantizedPosition(position.clone())),
+            3 + 2 * 16
+        );
+        assert_eq!(
+            roundtrip(&Protocol::QuantizedPosition(position.clone())),
+            Protocol::QuantizedPosition(position)
+        );
+
+        // values are rounded to the nearest step
+        let Protocol::QuantizedPosition(decoded) =
+            roundtrip(&Protocol::QuantizedPosition(Position { x: 12.345, y: 0.0 }))
+        else {
+            panic!("wrong variant");
+        };
+        assert!((decoded.x - 12.345).abs() < 0.01);
+        assert!(decoded.y.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_quantized_fields() {
+        let transform = Transform {
+            position: Position {
+                x: 100.0,
+                y: -100.0,
+            },
+            rotation: 1.0,
+            scale: 2.0,
+        };
+        // 5 bits for the variant index, then each field uses its own precision
+        assert_eq!(
+            num_bits(&Protocol::Transform(transform.clone())),
+            5 + 2 * 16 + 8 + 32
+        );
+        assert_eq!(
+            roundtrip(&Protocol::Transform(transform.clone())),
+            Protocol::Transform(transform)
+        );
+    }
+
+    #[test]
+    fn test_gamma_encoding() {
+        // a small integer only takes a few bits with gamma encoding
+        assert!(num_bits(&Protocol::Health(3)) < 16);
+        assert_eq!(roundtrip(&Protocol::Health(3)), Protocol::Health(3));
+        assert_eq!(
+            roundtrip(&Protocol::Health(u32::MAX)),
+            Protocol::Health(u32::MAX)
+        );
+    }
+}
@@ -5,6 +5,8 @@ use nonzero_ext::nonzero;
 
 use crate::connection::netcode::Key;
 use crate::connection::server::NetConfig;
+use crate::packet::congestion::CongestionConfig;
+use crate::packet::mtu::MtuConfig;
 use crate::server::replication::ReplicationConfig;
 use crate::shared::config::SharedConfig;
 use crate::shared::ping::manager::PingConfig;
@@ -18,6 +20,9 @@ pub struct NetcodeConfig {
     pub client_timeout_secs: i32,
     pub protocol_id: u64,
     pub private_key: Option<Key>,
+    /// Fingerprint of the [`Protocol`](crate::protocol::Protocol); the connection requests of clients
+    /// with a different fingerprint are denied. It is set by the [`ServerPlugin`](crate::server::plugin::ServerPlugin).
+    pub(crate) protocol_fingerprint: u64,
 }
 
 impl Default for NetcodeConfig {
@@ -28,6 +33,7 @@ impl Default for NetcodeConfig {
             client_timeout_secs: 10,
             protocol_id: 0,
             private_key: None,
+            protocol_fingerprint: 0,
         }
     }
 }
@@ -55,6 +61,12 @@ pub struct PacketConfig {
     pub per_client_send_bandwidth_cap: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub bandwidth_cap_enabled: bool,
+    /// Discovery of the largest packet size that can be sent on the connection
+    pub mtu: MtuConfig,
+    /// Adjusts the bandwidth budget to the state of the network.
+    ///
+    /// If enabled, it replaces the static bandwidth cap
+    pub congestion: CongestionConfig,
 }
 
 impl Default for PacketConfig {
@@ -63,6 +75,8 @@ impl Default for PacketConfig {
             // 56 KB/s bandwidth cap
             per_client_send_bandwidth_cap: Quota::per_second(nonzero!(56000u32)),
             bandwidth_cap_enabled: false,
+            mtu: MtuConfig::default(),
+            congestion: CongestionConfig::default(),
         }
     }
 }
@@ -83,6 +97,16 @@ impl PacketConfig {
         self.bandwidth_cap_enabled = true;
         self
     }
+
+    pub fn with_mtu_config(mut self, mtu: MtuConfig) -> Self {
+        self.mtu = mtu;
+        self
+    }
+
+    pub fn with_congestion_config(mut self, congestion: CongestionConfig) -> Self {
+        self.congestion = congestion;
+        self
+    }
 }
 
 /// Configuration for the server plugin
@@ -9,13 +9,16 @@ use serde::Serialize;
 use tracing::{debug, info, trace, trace_span, warn};
 
 use crate::_reexport::{
-    EntityUpdatesChannel, FromType, InputMessageKind, MessageProtocol, PingChannel,
-    ReplicationSend, ServerMarker, ShouldBeInterpolated,
+    AuthorityChannel, BulkControlChannel, EntityUpdatesChannel, FromType, InputMessageKind,
+    MessageProtocol, PingChannel, ReplicationSend, ServerMarker, ShouldBeInterpolated,
 };
+use crate::channel::bulk::TransferProgress;
 use crate::channel::senders::ChannelSend;
 use crate::client::message::ClientMessage;
 use crate::connection::id::ClientId;
 use crate::inputs::native::input_buffer::InputBuffer;
+use crate::packet::channel_stats::{ChannelStats, NetworkStats};
+use crate::packet::message::MessageHandle;
 use crate::packet::message_manager::MessageManager;
 use crate::packet::packet::Packet;
 use crate::packet::packet_manager::Payload;
@@ -31,7 +34,10 @@ use crate::server::message::ServerMessage;
 use crate::shared::events::connection::ConnectionEvents;
 use crate::shared::ping::manager::{PingConfig, PingManager};
 use crate::shared::ping::message::SyncMessage;
-use crate::shared::replication::components::{NetworkTarget, Replicate, ReplicationGroupId};
+use crate::shared::replication::authority::{AuthorityChange, AuthorityMessage};
+use crate::shared::replication::components::{
+    NetworkTarget, Replicate, ReplicateRemovePolicy, ReplicationGroupId,
+};
 use crate::shared::replication::receive::ReplicationReceiver;
 use crate::shared::replication::send::ReplicationSender;
 use crate::shared::replication::ReplicationMessage;
@@ -45,17 +51,21 @@ type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
 #[derive(Resource)]
 pub struct ConnectionManager<P: Protocol> {
     pub(crate) connections: HashMap<ClientId, Connection<P>>,
-    channel_registry: ChannelRegistry,
+    pub(crate) channel_registry: ChannelRegistry,
     pub(crate) events: ServerEvents<P>,
 
     // NOTE: we put this here because we only need one per world, not one per connection
     /// Stores the last `Replicate` component for each replicated entity owned by the current world (the world that sends replication updates)
     /// Needed to know the value of the Replicate component after the entity gets despawned, to know how we replicate the EntityDespawn
     pub replicate_component_cache: EntityHashMap<Entity, Replicate<P>>,
+    /// Client that has authority over each entity, for the entities whose authority was granted to a client
+    pub(crate) authority: EntityHashMap<Entity, ClientId>,
 
     // list of clients that connected since the last time we sent replication messages
     // (we want to keep track of them because we need to replicate the entire world state to them)
     pub(crate) new_clients: Vec<ClientId>,
+    /// Handle to return for the next message sent by the user
+    next_message_handle: MessageHandle,
 
     packet_config: PacketConfig,
     ping_config: PingConfig,
@@ -72,7 +82,9 @@ impl<P: Protocol> ConnectionManager<P> {
             channel_registry,
             events: ServerEvents::new(),
             replicate_component_cache: EntityHashMap::default(),
+            authority: EntityHashMap::default(),
             new_clients: vec![],
+            next_message_handle: MessageHandle::default(),
             packet_config,
             ping_config,
         }
@@ -130,6 +142,175 @@ impl<P: Protocol> ConnectionManager<P> {
             .context("client id not found")
     }
 
+    /// Largest packet size (in bytes) that can currently be sent to the client, as discovered by
+    /// the path MTU discovery (see [`MtuConfig`](crate::packet::mtu::MtuConfig))
+    pub fn mtu(&self, client_id: ClientId) -> Result<usize> {
+        Ok(self.connection(client_id)?.message_manager.mtu())
+    }
+
+    /// Estimate of the bandwidth (in bytes per second) available to send messages to the client,
+    /// if congestion control is enabled (see [`CongestionConfig`](crate::packet::congestion::CongestionConfig))
+    pub fn bandwidth(&self, client_id: ClientId) -> Result<Option<u32>> {
+        Ok(self.connection(client_id)?.message_manager.bandwidth())
+    }
+
+    /// Statistics (messages and bytes sent and received, resends, time-to-ack, etc.) of each channel
+    /// of the connection to a client
+    pub fn network_stats(&self, client_id: ClientId) -> Result<&NetworkStats> {
+        Ok(self.connection(client_id)?.message_manager.stats())
+    }
+
+    /// Statistics of the channel `C` of the connection to a client
+    pub fn channel_stats<C: Channel>(&self, client_id: ClientId) -> Result<ChannelStats> {
+        Ok(self
+            .network_stats(client_id)?
+            .channel(ChannelKind::of::<C>()))
+    }
+
+    /// Cancel a transfer that we are sending on a [`Bulk`](crate::channel::builder::ChannelMode::Bulk) channel,
+    /// for all the clients that it is still being sent to. The clients are notified that the transfer was cancelled.
+    ///
+    /// Returns an error if the transfer was already completed or cancelled for every client.
+    pub fn cancel_transfer(&mut self, handle: MessageHandle) -> Result<()> {
+        let mut cancelled = false;
+        for connection in self.connections.values_mut() {
+            cancelled |= connection.message_manager.cancel_transfer(handle).is_ok();
+        }
+        if !cancelled {
+            return Err(anyhow::anyhow!(
+                "the transfer was already completed or cancelled"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Cancel the transfer that we are currently receiving from the client on the
+    /// [`Bulk`](crate::channel::builder::ChannelMode::Bulk) channel `C`.
+    /// The client is notified that the transfer was cancelled.
+    pub fn cancel_incoming_transfer<C: Channel>(&mut self, client_id: ClientId) -> Result<()> {
+        self.connection_mut(client_id)?
+            .message_manager
+            .cancel_incoming_transfer(ChannelKind::of::<C>())
+    }
+
+    /// The replication group of a replicated entity, and the replicated entities of that group
+    fn group_entities(&self, entity: Entity) -> Result<(ReplicationGroupId, Vec<Entity>)> {
+        let group_id = self
+            .replicate_component_cache
+            .get(&entity)
+            .context("the entity is not replicated")?
+            .group_id(Some(entity));
+        let entities = self
+            .replicate_component_cache
+            .iter()
+            .filter(|(e, replicate)| replicate.group_id(Some(**e)) == group_id)
+            .map(|(e, _)| *e)
+            .collect();
+        Ok((group_id, entities))
+    }
+
+    /// Give the authority over a replicated entity to a client.
+    ///
+    /// The authority is given over all the entities of the entity's replication group, since they are replicated together.
+    /// The client will replicate the entities to the server (which applies the changes to its entities, and replicates them
+    /// to the other clients) instead of receiving their updates.
+    /// If another client had authority over the entities, its authority is revoked first.
+    pub fn grant_authority(&mut self, entity: Entity, client_id: ClientId) -> Result<()> {
+        let (group_id, entities) = self.group_entities(entity)?;
+        if entities
+            .iter()
+            .all(|entity| self.authority(*entity) == Some(client_id))
+        {
+            return Ok(());
+        }
+        let connection = self.connection(client_id)?;
+        for entity in &entities {
+            // the client must have received the entity to take it over
+            if !self.replicate_component_cache[entity]
+                .replication_target
+                .should_send_to(&client_id)
+            {
+                return Err(anyhow::anyhow!(
+                    "the entity {:?} is not replicated to the client",
+                    entity
+                ));
+            }
+            // the client sends the replication messages for the entity with our entity id, so we can apply them directly
+            // to our entity. This is only possible if the client is not already replicating an entity with the same id.
+            if connection
+                .replication_receiver
+                .remote_entity_map
+                .get_local(*entity)
+                .is_some_and(|local| local != entity)
+            {
+                return Err(anyhow::anyhow!(
+                    "the client already replicates an entity with the id {:?}",
+                    entity
+                ));
+            }
+        }
+        if entities
+            .iter()
+            .any(|entity| matches!(self.authority(*entity), Some(c) if c != client_id))
+        {
+            self.revoke_authority(entity)?;
+        }
+        for entity in entities {
+            if self.authority.insert(entity, client_id) == Some(client_id) {
+                continue;
+            }
+            let connection = self.connection_mut(client_id)?;
+            connection
+                .replication_receiver
+                .remote_entity_map
+                .insert(entity, entity);
+            connection.buffer_authority_message(AuthorityMessage::Grant { entity, group_id })?;
+        }
+        Ok(())
+    }
+
+    /// Take back the authority over an entity (and the other entities of its replication group)
+    /// from the client that has it.
+    ///
+    /// The client will receive the updates of the entities from the server again.
+    pub fn revoke_authority(&mut self, entity: Entity) -> Result<()> {
+        let (_, entities) = self.group_entities(entity)?;
+        for entity in entities {
+            let Some(client_id) = self.authority.remove(&entity) else {
+                continue;
+            };
+            let replicate = self.replicate_component_cache[&entity].clone();
+            let connection = self.connection_mut(client_id)?;
+            connection
+                .replication_receiver
+                .remote_entity_map
+                .remove_by_remote(entity);
+            connection.buffer_authority_message(AuthorityMessage::Revoke(entity))?;
+            // the client's entity becomes a confirmed entity again: spawn its predicted/interpolated entities
+            let group_id = replicate.group_id(Some(entity));
+            if replicate.prediction_target.should_send_to(&client_id) {
+                connection.replication_sender.prepare_component_insert(
+                    entity,
+                    group_id,
+                    P::Components::from(ShouldBePredicted),
+                );
+            }
+            if replicate.interpolation_target.should_send_to(&client_id) {
+                connection.replication_sender.prepare_component_insert(
+                    entity,
+                    group_id,
+                    P::Components::from(ShouldBeInterpolated),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The client that has authority over the entity, or None if the server has authority over it
+    pub fn authority(&self, entity: Entity) -> Option<ClientId> {
+        self.authority.get(&entity).copied()
+    }
+
     pub(crate) fn update(&mut self, time_manager: &TimeManager, tick_manager: &TickManager) {
         self.connections.values_mut().for_each(|connection| {
             connection.update(time_manager, tick_manager);
@@ -137,7 +318,10 @@ impl<P: Protocol> ConnectionManager<P> {
     }
 
     /// Add a new [`Connection`] to the list of connections with the given [`ClientId`]
-    pub(crate) fn add(&mut self, client_id: ClientId) {
+    /// Add a connection for a new client.
+    ///
+    /// `reliable_transport` is true if the reliable packets are sent on a reliable transport (for example QUIC streams)
+    pub(crate) fn add(&mut self, client_id: ClientId, reliable_transport: bool) {
         if let Entry::Vacant(e) = self.connections.entry(client_id) {
             #[cfg(feature = "metrics")]
             metrics::gauge!("connected_clients").increment(1.0);
@@ -147,6 +331,7 @@ impl<P: Protocol> ConnectionManager<P> {
                 &self.channel_registry,
                 self.packet_config.clone(),
                 self.ping_config.clone(),
+                reliable_transport,
             );
             self.events.push_connection(client_id);
             self.new_clients.push(client_id);
@@ -163,6 +348,8 @@ impl<P: Protocol> ConnectionManager<P> {
         info!("Client {} disconnected", client_id);
         self.events.push_disconnection(client_id);
         self.connections.remove(&client_id);
+        // the server takes back the authority over the entities of the client
+        self.authority.retain(|_, c| *c != client_id);
     }
 
     /// Get the inputs for all clients for the given tick
@@ -207,6 +394,7 @@ impl<P: Protocol> ConnectionManager<P> {
         message: P::Message,
         channel: ChannelKind,
         target: NetworkTarget,
+        handle: Option<MessageHandle>,
     ) -> Result<()> {
         // Rc is fine because the copies are all created on the same thread
         // let message = Rc::new(message);
@@ -216,28 +404,40 @@ impl<P: Protocol> ConnectionManager<P> {
             // TODO: here we should avoid the clone, it's the same message.. just use Rc?
             //  need to update the ServerMessage enum to use Rc<P::Message>!
             //  or serialize first, so we can use Bytes? where would the buffer be?
-            .try_for_each(|(_, c)| c.buffer_message(message.clone(), channel))
+            .try_for_each(|(_, c)| c.buffer_message(message.clone(), channel, handle))
     }
 
     /// Queues up a message to be sent to all clients
+    ///
+    /// Returns a [`MessageHandle`] that identifies the message in the
+    /// [`MessageDeliveredEvent`](crate::server::events::MessageDeliveredEvent) and
+    /// [`MessageLostEvent`](crate::server::events::MessageLostEvent) events (one event per client
+    /// that the message was sent to).
+    /// These events are only emitted for messages sent on reliable channels or on
+    /// [`UnorderedUnreliableWithAcks`](crate::channel::builder::ChannelMode::UnorderedUnreliableWithAcks) channels.
     pub fn send_message_to_target<C: Channel, M: Message>(
         &mut self,
         message: M,
         target: NetworkTarget,
-    ) -> Result<()>
+    ) -> Result<MessageHandle>
     where
         M: Clone,
         P::Message: From<M>,
     {
-        self.buffer_message(message.into(), ChannelKind::of::<C>(), target)
+        // the same handle is used for all the clients that the message is sent to
+        let handle = self.next_message_handle.next();
+        self.buffer_message(message.into(), ChannelKind::of::<C>(), target, Some(handle))?;
+        Ok(handle)
     }
 
     /// Queues up a message to be sent to a client
+    ///
+    /// The returned [`MessageHandle`] tracks the delivery of the message to the client.
     pub fn send_message<C: Channel, M: Message>(
         &mut self,
         client_id: ClientId,
         message: M,
-    ) -> Result<()>
+    ) -> Result<MessageHandle>
     where
         M: Clone,
         P::Message: From<M>,
@@ -281,7 +481,8 @@ impl<P: Protocol> ConnectionManager<P> {
                     .extend(std::mem::take(&mut connection.messages_to_rebroadcast));
             });
         for (message, target, channel_kind) in messages_to_rebroadcast {
-            self.buffer_message(message, channel_kind, target)?;
+            // the delivery of rebroadcasted messages is not tracked
+            self.buffer_message(message, channel_kind, target, None)?;
         }
         Ok(())
     }
@@ -311,9 +512,14 @@ impl<P: Protocol> Connection<P> {
         channel_registry: &ChannelRegistry,
         packet_config: PacketConfig,
         ping_config: PingConfig,
+        reliable_transport: bool,
     ) -> Self {
         // create the message manager and the channels
-        let mut message_manager = MessageManager::new(channel_registry, packet_config.into());
+        let mut message_manager =
+            MessageManager::new(channel_registry, packet_config.clone().into())
+                .with_mtu_discovery(packet_config.mtu)
+                .with_congestion_control(packet_config.congestion)
+                .with_reliable_transport(reliable_transport);
         // get the acks-tracker for entity updates
         let update_acks_tracker = message_manager
             .channels
@@ -349,6 +555,7 @@ impl<P: Protocol> Connection<P> {
         &mut self,
         message: P::Message,
         channel: ChannelKind,
+        handle: Option<MessageHandle>,
     ) -> Result<()> {
         // TODO: i know channel names never change so i should be able to get them as static
         // TODO: just have a channel registry enum as well?
@@ -360,7 +567,22 @@ impl<P: Protocol> Connection<P> {
             .to_string();
         let message = ServerMessage::<P>::Message(message);
         message.emit_send_logs(&channel_name);
-        self.message_manager.buffer_send(message, channel)?;
+        match handle {
+            Some(handle) => self
+                .message_manager
+                .buffer_send_tracked(message, channel, handle)?,
+            None => {
+                self.message_manager.buffer_send(message, channel)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn buffer_authority_message(&mut self, message: AuthorityMessage) -> Result<()> {
+        let message = ServerMessage::<P>::Authority(message);
+        message.emit_send_logs("AuthorityChannel");
+        self.message_manager
+            .buffer_send(message, ChannelKind::of::<AuthorityChannel>())?;
         Ok(())
     }
 
@@ -395,17 +617,24 @@ impl<P: Protocol> Connection<P> {
                     self.replication_sender
                         .updates_message_id_to_group_id
                         .insert(message_id, (group_id, bevy_tick));
+                    self.replication_sender
+                        .delta
+                        .track_message(group_id, message_id);
                 }
                 Ok(())
             })
     }
 
     /// Send packets that are ready to be sent
+    ///
+    /// Returns the (unreliable, reliable) payloads. If `split_by_reliability` is false, all the payloads are
+    /// returned in the first list and may contain messages from both reliable and unreliable channels.
     pub fn send_packets(
         &mut self,
         time_manager: &TimeManager,
         tick_manager: &TickManager,
-    ) -> Result<Vec<Payload>> {
+        split_by_reliability: bool,
+    ) -> Result<(Vec<Payload>, Vec<Payload>)> {
         // update the ping manager with the actual send time
         // TODO: issues here: we would like to send the ping/pong messages immediately, otherwise the recorded current time is incorrect
         //   - can give infinity priority to this channel?
@@ -437,7 +666,20 @@ impl<P: Protocol> Connection<P> {
                     Ok::<(), anyhow::Error>(())
                 })?;
         }
-        let payloads = self.message_manager.send_packets(tick_manager.tick());
+        // flow control and cancellation of the bulk transfers
+        for control in self.message_manager.take_bulk_control_messages() {
+            let message = ServerMessage::<P>::Bulk(control);
+            let channel = ChannelKind::of::<BulkControlChannel>();
+            self.message_manager.buffer_send(message, channel)?;
+        }
+        let payloads = if split_by_reliability {
+            self.message_manager
+                .send_packets_by_reliability(tick_manager.tick())
+        } else {
+            self.message_manager
+                .send_packets(tick_manager.tick())
+                .map(|payloads| (payloads, vec![]))
+        };
 
         // update the replication sender about which messages were actually sent, and accumulate priority
         self.replication_sender.recv_send_notification();
@@ -513,6 +755,23 @@ impl<P: Protocol> Connection<P> {
                                 }
                             }
                         }
+                        ClientMessage::Bulk(control) => {
+                            if let Err(e) =
+                                self.message_manager.receive_bulk_control_message(control)
+                            {
+                                warn!("could not handle bulk control message: {:?}", e);
+                            }
+                        }
+                        ClientMessage::Authority(AuthorityMessage::Request(entity)) => {
+                            self.events
+                                .push_authority_change(entity, AuthorityChange::Requested);
+                        }
+                        ClientMessage::Authority(message) => {
+                            warn!(
+                                ?message,
+                                "Received an authority message that only the server can send"
+                            );
+                        }
                     }
                 }
             }
@@ -538,6 +797,32 @@ impl<P: Protocol> Connection<P> {
                 });
         }
 
+        // delivery status of the messages that we sent
+        for (handle, channel_kind) in self.message_manager.take_delivered_messages() {
+            self.events.push_message_delivered(handle, channel_kind);
+        }
+        for (handle, channel_kind) in self.message_manager.take_lost_messages() {
+            self.events.push_message_lost(handle, channel_kind);
+        }
+        for (handle, channel_kind, acked_bytes, total_bytes) in
+            self.message_manager.take_transfer_progress()
+        {
+            self.events.push_transfer_progress(
+                handle,
+                channel_kind,
+                TransferProgress {
+                    acked_bytes,
+                    total_bytes,
+                },
+            );
+        }
+        for (handle, channel_kind) in self.message_manager.take_cancelled_transfers() {
+            self.events.push_transfer_cancelled(handle, channel_kind);
+        }
+        for (channel_kind, error) in self.message_manager.take_rejected_messages() {
+            self.events.push_message_rejected(channel_kind, error);
+        }
+
         // TODO: do i really need this? I could just create events in this function directly?
         //  why do i need to make events a field of the connection?
         //  is it because of push_connection?
@@ -640,6 +925,17 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
             //     "Send entity despawn for tick {:?}",
             //     self.tick_manager.tick()
             // );
+            // the client loses its authority over the entity when the entity is despawned for that client
+            // (the client might not have received the entity yet, so it must be told to drop the grant)
+            if self.authority.get(&entity) == Some(&client_id) {
+                self.authority.remove(&entity);
+                let connection = self.connection_mut(client_id)?;
+                connection
+                    .replication_receiver
+                    .remote_entity_map
+                    .remove_by_remote(entity);
+                connection.buffer_authority_message(AuthorityMessage::Revoke(entity))?;
+            }
             let replication_sender = &mut self.connection_mut(client_id)?.replication_sender;
             // update the collect changes tick
             // replication_sender
@@ -652,6 +948,30 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         })
     }
 
+    fn prepare_replication_stop(
+        &mut self,
+        entity: Entity,
+        replicate: &Replicate<P>,
+        target: NetworkTarget,
+        system_current_tick: BevyTick,
+    ) -> Result<()> {
+        let policy = replicate.remove_policy;
+        if policy == ReplicateRemovePolicy::Despawn {
+            self.prepare_entity_despawn(entity, replicate, target.clone(), system_current_tick)?;
+        }
+        let group_id = replicate.replication_group.group_id(Some(entity));
+        // the client that has authority over the entity keeps replicating it to us
+        let authority = self.authority(entity);
+        self.apply_replication(target)
+            .filter(|client_id| Some(*client_id) != authority)
+            .try_for_each(|client_id| {
+                self.connection_mut(client_id)?
+                    .replication_sender
+                    .prepare_replication_stop(entity, group_id, policy);
+                Ok(())
+            })
+    }
+
     // TODO: perf gain if we batch this? (send vec of components) (same for update/removes)
     fn prepare_component_insert(
         &mut self,
@@ -686,7 +1006,10 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
             actual_target = replicate.prediction_target.clone();
         }
 
+        // the client that has authority over the entity is the one that sends us the changes
+        let authority = self.authority(entity);
         self.apply_replication(actual_target)
+            .filter(|client_id| Some(*client_id) != authority)
             .try_for_each(|client_id| {
                 // trace!(
                 //     ?entity,
@@ -716,23 +1039,26 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
     ) -> Result<()> {
         let group_id = replicate.replication_group.group_id(Some(entity));
         debug!(?entity, ?component_kind, "Sending RemoveComponent");
-        self.apply_replication(target).try_for_each(|client_id| {
-            let replication_sender = &mut self.connection_mut(client_id)?.replication_sender;
-            // TODO: I don't think it's actually correct to only correct the changes since that action.
-            // what if we do:
-            // - Frame 1: update is ACKED
-            // - Frame 2: update
-            // - Frame 3: action
-            // - Frame 4: send
-            // then we won't send the frame-2 update because we only collect changes since frame 3
-            // replication_sender
-            //     .group_channels
-            //     .entry(group)
-            //     .or_default()
-            //     .update_collect_changes_since_this_tick(system_current_tick);
-            replication_sender.prepare_component_remove(entity, group_id, component_kind);
-            Ok(())
-        })
+        let authority = self.authority(entity);
+        self.apply_replication(target)
+            .filter(|client_id| Some(*client_id) != authority)
+            .try_for_each(|client_id| {
+                let replication_sender = &mut self.connection_mut(client_id)?.replication_sender;
+                // TODO: I don't think it's actually correct to only correct the changes since that action.
+                // what if we do:
+                // - Frame 1: update is ACKED
+                // - Frame 2: update
+                // - Frame 3: action
+                // - Frame 4: send
+                // then we won't send the frame-2 update because we only collect changes since frame 3
+                // replication_sender
+                //     .group_channels
+                //     .entry(group)
+                //     .or_default()
+                //     .update_collect_changes_since_this_tick(system_current_tick);
+                replication_sender.prepare_component_remove(entity, group_id, component_kind);
+                Ok(())
+            })
     }
 
     fn prepare_entity_update(
@@ -754,41 +1080,44 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         );
 
         let group_id = replicate.group_id(Some(entity));
-        self.apply_replication(target).try_for_each(|client_id| {
-            // TODO: should we have additional state tracking so that we know we are in the process of sending this entity to clients?
-            let replication_sender = &mut self.connection_mut(client_id)?.replication_sender;
-            let collect_changes_since_this_tick = replication_sender
-                .group_channels
-                .entry(group_id)
-                .or_default()
-                .collect_changes_since_this_tick;
-            // send the update for all changes newer than the last ack bevy tick for the group
-            debug!(
-                ?kind,
-                change_tick = ?component_change_tick,
-                ?collect_changes_since_this_tick,
-                "prepare entity update changed check (we want the component-change-tick to be higher than collect-changes-since-this-tick)"
-            );
-
-            if collect_changes_since_this_tick.map_or(true, |tick| {
-                component_change_tick.is_newer_than(tick, system_current_tick)
-            }) {
-                trace!(
+        let authority = self.authority(entity);
+        self.apply_replication(target)
+            .filter(|client_id| Some(*client_id) != authority)
+            .try_for_each(|client_id| {
+                // TODO: should we have additional state tracking so that we know we are in the process of sending this entity to clients?
+                let replication_sender = &mut self.connection_mut(client_id)?.replication_sender;
+                let collect_changes_since_this_tick = replication_sender
+                    .group_channels
+                    .entry(group_id)
+                    .or_default()
+                    .collect_changes_since_this_tick;
+                // send the update for all changes newer than the last ack bevy tick for the group
+                debug!(
+                    ?kind,
                     change_tick = ?component_change_tick,
                     ?collect_changes_since_this_tick,
-                    current_tick = ?system_current_tick,
-                    "prepare entity update changed check"
+                    "prepare entity update changed check (we want the component-change-tick to be higher than collect-changes-since-this-tick)"
                 );
-                // trace!(
-                //     ?entity,
-                //     component = ?kind,
-                //     tick = ?self.tick_manager.tick(),
-                //     "Updating single component"
-                // );
-                replication_sender.prepare_entity_update(entity, group_id, component.clone());
-            }
-            Ok(())
-        })
+
+                if collect_changes_since_this_tick.map_or(true, |tick| {
+                    component_change_tick.is_newer_than(tick, system_current_tick)
+                }) {
+                    trace!(
+                        change_tick = ?component_change_tick,
+                        ?collect_changes_since_this_tick,
+                        current_tick = ?system_current_tick,
+                        "prepare entity update changed check"
+                    );
+                    // trace!(
+                    //     ?entity,
+                    //     component = ?kind,
+                    //     tick = ?self.tick_manager.tick(),
+                    //     "Updating single component"
+                    // );
+                    replication_sender.prepare_entity_update(entity, group_id, component.clone());
+                }
+                Ok(())
+            })
     }
 
     /// Buffer the replication messages
@@ -834,6 +1163,9 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
                     }
                 }
             }
+            // stop using old values as baselines for delta compression
+            connection.replication_sender.delta.cleanup(tick);
+            connection.replication_receiver.delta.cleanup(tick);
         }
     }
 }
@@ -0,0 +1,53 @@
+use bevy::app::{App, Plugin, PostUpdate};
+use bevy::diagnostic::Diagnostics;
+use bevy::prelude::{Local, Real, Res, Time};
+
+use crate::packet::channel_stats::{ChannelDiagnostics, NetworkStats};
+use crate::prelude::Protocol;
+use crate::server::connection::ConnectionManager;
+
+/// Publishes the statistics of each channel, added together over all the connected clients,
+/// as Bevy [`Diagnostic`](bevy::diagnostic::Diagnostic)s
+pub struct ServerDiagnosticsPlugin<P> {
+    _marker: std::marker::PhantomData<P>,
+}
+
+impl<P> Default for ServerDiagnosticsPlugin<P> {
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+fn channel_diagnostics_system<P: Protocol>(
+    connection_manager: Res<ConnectionManager<P>>,
+    time: Res<Time<Real>>,
+    mut previous_stats: Local<NetworkStats>,
+    mut diagnostics: Diagnostics,
+) {
+    let mut stats = NetworkStats::default();
+    for connection in connection_manager.connections.values() {
+        stats.merge(connection.message_manager.stats());
+    }
+    ChannelDiagnostics::update_diagnostics(
+        "server",
+        &stats,
+        &mut previous_stats,
+        &connection_manager.channel_registry,
+        &time,
+        &mut diagnostics,
+    );
+}
+
+impl<P: Protocol> Plugin for ServerDiagnosticsPlugin<P> {
+    fn build(&self, app: &mut App) {
+        let channel_registry = app
+            .world
+            .resource::<ConnectionManager<P>>()
+            .channel_registry
+            .clone();
+        ChannelDiagnostics::register(app, "server", &channel_registry);
+        app.add_systems(PostUpdate, channel_diagnostics_system::<P>);
+    }
+}
@@ -8,19 +8,26 @@ use crate::_reexport::{
     FromType, IterComponentInsertEvent, IterComponentRemoveEvent, IterComponentUpdateEvent,
     ServerMarker,
 };
+use crate::channel::bulk::TransferProgress;
+use crate::channel::error::ChannelError;
 use crate::connection::id::ClientId;
 #[cfg(feature = "leafwing")]
 use crate::inputs::leafwing::{InputMessage, LeafwingUserAction};
-use crate::packet::message::Message;
+use crate::packet::message::{Message, MessageHandle};
+use crate::protocol::channel::ChannelKind;
 use crate::protocol::Protocol;
 use crate::server::connection::ConnectionManager;
 use crate::server::networking::clear_events;
 #[cfg(feature = "leafwing")]
 use crate::shared::events::connection::IterInputMessageEvent;
 use crate::shared::events::connection::{
-    ConnectionEvents, IterEntityDespawnEvent, IterEntitySpawnEvent, IterMessageEvent,
+    ConnectionEvents, IterAuthorityEvent, IterEntityDespawnEvent, IterEntitySpawnEvent,
+    IterMessageDeliveredEvent, IterMessageEvent, IterMessageLostEvent, IterMessageRejectedEvent,
+    IterReplicationStopEvent, IterTransferCancelledEvent, IterTransferProgressEvent,
 };
 use crate::shared::events::plugin::EventsPlugin;
+use crate::shared::replication::authority::AuthorityChange;
+use crate::shared::replication::components::ReplicateRemovePolicy;
 use crate::shared::sets::InternalMainSet;
 
 type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
@@ -181,6 +188,142 @@ impl<P: Protocol> IterMessageEvent<P, ClientId> for ServerEvents<P> {
     }
 }
 
+impl<P: Protocol> IterMessageDeliveredEvent<ClientId> for ServerEvents<P> {
+    fn into_iter_message_delivered(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (MessageHandle, ChannelKind, ClientId)> + '_> {
+        Box::new(self.events.iter_mut().flat_map(|(client_id, events)| {
+            let client_id = *client_id;
+            events
+                .into_iter_message_delivered()
+                .map(move |(handle, channel_kind, _)| (handle, channel_kind, client_id))
+        }))
+    }
+
+    fn has_message_delivered(&self) -> bool {
+        self.events
+            .iter()
+            .any(|(_, connection_events)| connection_events.has_message_delivered())
+    }
+}
+
+impl<P: Protocol> IterMessageLostEvent<ClientId> for ServerEvents<P> {
+    fn into_iter_message_lost(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (MessageHandle, ChannelKind, ClientId)> + '_> {
+        Box::new(self.events.iter_mut().flat_map(|(client_id, events)| {
+            let client_id = *client_id;
+            events
+                .into_iter_message_lost()
+                .map(move |(handle, channel_kind, _)| (handle, channel_kind, client_id))
+        }))
+    }
+
+    fn has_message_lost(&self) -> bool {
+        self.events
+            .iter()
+            .any(|(_, connection_events)| connection_events.has_message_lost())
+    }
+}
+
+impl<P: Protocol> IterTransferProgressEvent<ClientId> for ServerEvents<P> {
+    fn into_iter_transfer_progress(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (MessageHandle, ChannelKind, TransferProgress, ClientId)> + '_>
+    {
+        Box::new(self.events.iter_mut().flat_map(|(client_id, events)| {
+            let client_id = *client_id;
+            events
+                .into_iter_transfer_progress()
+                .map(move |(handle, channel_kind, progress, _)| {
+                    (handle, channel_kind, progress, client_id)
+                })
+        }))
+    }
+
+    fn has_transfer_progress(&self) -> bool {
+        self.events
+            .iter()
+            .any(|(_, connection_events)| connection_events.has_transfer_progress())
+    }
+}
+
+impl<P: Protocol> IterTransferCancelledEvent<ClientId> for ServerEvents<P> {
+    fn into_iter_transfer_cancelled(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (Option<MessageHandle>, ChannelKind, ClientId)> + '_> {
+        Box::new(self.events.iter_mut().flat_map(|(client_id, events)| {
+            let client_id = *client_id;
+            events
+                .into_iter_transfer_cancelled()
+                .map(move |(handle, channel_kind, _)| (handle, channel_kind, client_id))
+        }))
+    }
+
+    fn has_transfer_cancelled(&self) -> bool {
+        self.events
+            .iter()
+            .any(|(_, connection_events)| connection_events.has_transfer_cancelled())
+    }
+}
+
+impl<P: Protocol> IterMessageRejectedEvent<ClientId> for ServerEvents<P> {
+    fn into_iter_message_rejected(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (ChannelKind, ChannelError, ClientId)> + '_> {
+        Box::new(self.events.iter_mut().flat_map(|(client_id, events)| {
+            let client_id = *client_id;
+            events
+                .into_iter_message_rejected()
+                .map(move |(channel_kind, error, _)| (channel_kind, error, client_id))
+        }))
+    }
+
+    fn has_message_rejected(&self) -> bool {
+        self.events
+            .iter()
+            .any(|(_, connection_events)| connection_events.has_message_rejected())
+    }
+}
+
+impl<P: Protocol> IterAuthorityEvent<ClientId> for ServerEvents<P> {
+    fn into_iter_authority(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (Entity, AuthorityChange, ClientId)> + '_> {
+        Box::new(self.events.iter_mut().flat_map(|(client_id, events)| {
+            let client_id = *client_id;
+            events
+                .into_iter_authority()
+                .map(move |(entity, change, _)| (entity, change, client_id))
+        }))
+    }
+
+    fn has_authority(&self) -> bool {
+        self.events
+            .iter()
+            .any(|(_, connection_events)| connection_events.has_authority())
+    }
+}
+
+impl<P: Protocol> IterReplicationStopEvent<ClientId> for ServerEvents<P> {
+    fn into_iter_replication_stop(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (Entity, ReplicateRemovePolicy, ClientId)> + '_> {
+        Box::new(self.events.iter_mut().flat_map(|(client_id, events)| {
+            let client_id = *client_id;
+            events
+                .into_iter_replication_stop()
+                .map(move |(entity, policy, _)| (entity, policy, client_id))
+        }))
+    }
+
+    fn has_replication_stop(&self) -> bool {
+        self.events
+            .iter()
+            .any(|(_, connection_events)| connection_events.has_replication_stop())
+    }
+}
+
 impl<P: Protocol> IterEntitySpawnEvent<ClientId> for ServerEvents<P> {
     fn into_iter_entity_spawn(&mut self) -> Box<dyn Iterator<Item = (Entity, ClientId)> + '_> {
         Box::new(self.events.iter_mut().flat_map(|(client_id, events)| {
@@ -315,6 +458,23 @@ pub type ComponentRemoveEvent<C> =
 /// Bevy [`Event`] emitted on the server on the frame where an input message from a client is received
 pub(crate) type InputMessageEvent<A> =
     crate::shared::events::components::InputMessageEvent<A, ClientId>;
+/// Bevy [`Event`] emitted on the server when a message sent to a client was acked by that client
+pub type MessageDeliveredEvent = crate::shared::events::components::MessageDeliveredEvent<ClientId>;
+/// Bevy [`Event`] emitted on the server when a message sent to a client was lost
+/// (on an unreliable channel, or after its time-to-live expired on a reliable channel)
+pub type MessageLostEvent = crate::shared::events::components::MessageLostEvent<ClientId>;
+/// Bevy [`Event`] emitted on the server when more of a transfer sent to a client on a bulk channel was acked
+pub type TransferProgressEvent = crate::shared::events::components::TransferProgressEvent<ClientId>;
+/// Bevy [`Event`] emitted on the server when a client cancelled a transfer on a bulk channel
+pub type TransferCancelledEvent =
+    crate::shared::events::components::TransferCancelledEvent<ClientId>;
+/// Bevy [`Event`] emitted on the server when a message from a client was dropped because it did not respect
+/// the limits of its channel
+pub type MessageRejectedEvent = crate::shared::events::components::MessageRejectedEvent<ClientId>;
+/// Bevy [`Event`] emitted on the server when a client requested the authority over an entity
+pub type AuthorityEvent = crate::shared::events::components::AuthorityEvent<ClientId>;
+/// Bevy [`Event`] emitted on the server when a client stopped replicating an entity
+pub type ReplicationStopEvent = crate::shared::events::components::ReplicationStopEvent<ClientId>;
 /// Bevy [`Event`] emitted on the server on the frame where a (non-replication) message is received
 pub type MessageEvent<M> = crate::shared::events::components::MessageEvent<M, ClientId>;
 
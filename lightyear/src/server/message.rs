@@ -5,8 +5,10 @@ use bitcode::__private::Fixed;
 use bitcode::{Decode, Encode};
 
 use crate::_reexport::{BitSerializable, MessageProtocol, ReadBuffer, WriteBuffer};
+use crate::channel::bulk::BulkControlMessage;
 use crate::prelude::Protocol;
 use crate::shared::ping::message::SyncMessage;
+use crate::shared::replication::authority::AuthorityMessage;
 use crate::shared::replication::{ReplicationMessage, ReplicationMessageData};
 
 #[derive(Encode, Decode, Clone, Debug)]
@@ -21,6 +23,13 @@ pub enum ServerMessage<P: Protocol> {
     // the sync messages can be added to packets that have other messages
     #[bitcode_hint(frequency = 1)]
     Sync(SyncMessage),
+    // flow control and cancellation of the transfers of the bulk channels
+    #[bitcode_hint(frequency = 1)]
+    Bulk(BulkControlMessage),
+    // transfer of the authority over replicated entities
+    #[bitcode_hint(frequency = 1)]
+    #[bitcode(with_serde)]
+    Authority(AuthorityMessage),
 }
 
 impl<P: Protocol> BitSerializable for ServerMessage<P> {
@@ -134,6 +143,12 @@ impl<P: Protocol> ServerMessage<P> {
                     metrics::counter!("send_pong", "channel" => channel_name).increment(1);
                 }
             },
+            ServerMessage::Bulk(message) => {
+                trace!(channel = ?channel_name, ?message, "Sending bulk control message");
+            }
+            ServerMessage::Authority(message) => {
+                trace!(channel = ?channel_name, ?message, "Sending authority message");
+            }
         }
     }
 }
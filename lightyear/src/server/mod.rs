@@ -7,6 +7,8 @@ pub mod config;
 
 pub mod connection;
 
+mod diagnostics;
+
 pub mod events;
 
 mod input;
@@ -2,17 +2,25 @@
 use anyhow::Context;
 use bevy::ecs::system::SystemChangeTick;
 use bevy::prelude::*;
-use tracing::{debug, error, trace, trace_span};
+use tracing::{debug, error, trace, trace_span, warn};
 
 use crate::_reexport::{ComponentProtocol, ServerMarker};
 use crate::connection::server::{NetConfig, NetServer, ServerConnection, ServerConnections};
-use crate::prelude::{TickManager, TimeManager};
+use crate::prelude::{LinkConditionerSettings, TickManager, TimeManager};
 use crate::protocol::message::MessageProtocol;
 use crate::protocol::Protocol;
 use crate::server::connection::ConnectionManager;
-use crate::server::events::{ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent};
+use crate::server::events::{
+    AuthorityEvent, ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent,
+    MessageDeliveredEvent, MessageLostEvent, MessageRejectedEvent, ReplicationStopEvent,
+    TransferCancelledEvent, TransferProgressEvent,
+};
 use crate::server::room::RoomManager;
-use crate::shared::events::connection::{IterEntityDespawnEvent, IterEntitySpawnEvent};
+use crate::shared::events::connection::{
+    IterAuthorityEvent, IterEntityDespawnEvent, IterEntitySpawnEvent, IterMessageDeliveredEvent,
+    IterMessageLostEvent, IterMessageRejectedEvent, IterReplicationStopEvent,
+    IterTransferCancelledEvent, IterTransferProgressEvent,
+};
 use crate::shared::replication::ReplicationSend;
 use crate::shared::sets::InternalMainSet;
 use crate::shared::time_manager::is_server_ready_to_send;
@@ -61,7 +69,12 @@ impl<P: Protocol> Plugin for ServerNetworkingPlugin<P> {
             // SYSTEMS //
             .add_systems(
                 PreUpdate,
-                (receive::<P>.in_set(InternalMainSet::<ServerMarker>::Receive),),
+                (
+                    update_link_conditioner
+                        .run_if(resource_exists_and_changed::<LinkConditionerSettings>)
+                        .before(InternalMainSet::<ServerMarker>::Receive),
+                    receive::<P>.in_set(InternalMainSet::<ServerMarker>::Receive),
+                ),
             )
             .add_systems(
                 PostUpdate,
@@ -96,7 +109,7 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                                     .map_err(|e| error!("Error updating netcode server: {:?}", e));
                                                 for client_id in netserver.new_connections().iter().copied() {
                                                     netservers.client_server_map.insert(client_id, server_idx);
-                                                    connection_manager.add(client_id);
+                                                    connection_manager.add(client_id, netserver.supports_reliable_send());
                                                 }
                                                 // handle disconnections
                                                 for client_id in netserver.new_disconnections().iter().copied() {
@@ -181,6 +194,67 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                                         entity_despawn_event_writer.send(EntityDespawnEvent::new(entity, client_id));
                                                     }
                                                 }
+                                                // MessageDelivered/MessageLost Events
+                                                if connection_manager.events.has_message_delivered() {
+                                                    let mut message_delivered_event_writer = world
+                                                        .get_resource_mut::<Events<MessageDeliveredEvent>>()
+                                                        .unwrap();
+                                                    for (handle, channel, client_id) in connection_manager.events.into_iter_message_delivered() {
+                                                        message_delivered_event_writer.send(MessageDeliveredEvent::new(handle, channel, client_id));
+                                                    }
+                                                }
+                                                if connection_manager.events.has_message_lost() {
+                                                    let mut message_lost_event_writer = world
+                                                        .get_resource_mut::<Events<MessageLostEvent>>()
+                                                        .unwrap();
+                                                    for (handle, channel, client_id) in connection_manager.events.into_iter_message_lost() {
+                                                        message_lost_event_writer.send(MessageLostEvent::new(handle, channel, client_id));
+                                                    }
+                                                }
+                                                // TransferProgress/TransferCancelled Events
+                                                if connection_manager.events.has_transfer_progress() {
+                                                    let mut transfer_progress_event_writer = world
+                                                        .get_resource_mut::<Events<TransferProgressEvent>>()
+                                                        .unwrap();
+                                                    for (handle, channel, progress, client_id) in connection_manager.events.into_iter_transfer_progress() {
+                                                        transfer_progress_event_writer.send(TransferProgressEvent::new(handle, channel, progress, client_id));
+                                                    }
+                                                }
+                                                if connection_manager.events.has_transfer_cancelled() {
+                                                    let mut transfer_cancelled_event_writer = world
+                                                        .get_resource_mut::<Events<TransferCancelledEvent>>()
+                                                        .unwrap();
+                                                    for (handle, channel, client_id) in connection_manager.events.into_iter_transfer_cancelled() {
+                                                        transfer_cancelled_event_writer.send(TransferCancelledEvent::new(handle, channel, client_id));
+                                                    }
+                                                }
+                                                // MessageRejected Events
+                                                if connection_manager.events.has_message_rejected() {
+                                                    let mut message_rejected_event_writer = world
+                                                        .get_resource_mut::<Events<MessageRejectedEvent>>()
+                                                        .unwrap();
+                                                    for (channel, error, client_id) in connection_manager.events.into_iter_message_rejected() {
+                                                        message_rejected_event_writer.send(MessageRejectedEvent::new(channel, error, client_id));
+                                                    }
+                                                }
+                                                // Authority Events
+                                                if connection_manager.events.has_authority() {
+                                                    let mut authority_event_writer = world
+                                                        .get_resource_mut::<Events<AuthorityEvent>>()
+                                                        .unwrap();
+                                                    for (entity, change, client_id) in connection_manager.events.into_iter_authority() {
+                                                        authority_event_writer.send(AuthorityEvent::new(entity, change, client_id));
+                                                    }
+                                                }
+                                                // ReplicationStop Events
+                                                if connection_manager.events.has_replication_stop() {
+                                                    let mut replication_stop_event_writer = world
+                                                        .get_resource_mut::<Events<ReplicationStopEvent>>()
+                                                        .unwrap();
+                                                    for (entity, policy, client_id) in connection_manager.events.into_iter_replication_stop() {
+                                                        replication_stop_event_writer.send(ReplicationStopEvent::new(entity, policy, client_id));
+                                                    }
+                                                }
 
                                                 // Update component events (updates, inserts, removes)
                                                 P::Components::push_component_events(world, &mut connection_manager.events);
@@ -192,6 +266,23 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
     });
 }
 
+/// Apply the [`LinkConditionerSettings`] to the io of every server connection whenever they are modified
+///
+/// Connections that don't use an [`Io`](crate::transport::io::Io) (Steam, QUIC) can't be updated at runtime.
+pub(crate) fn update_link_conditioner(
+    settings: Res<LinkConditionerSettings>,
+    mut netservers: ResMut<ServerConnections>,
+) {
+    for netserver in netservers.servers.iter_mut() {
+        match netserver.io_mut() {
+            Some(io) => io.set_conditioner(settings.0.clone()),
+            None => warn!(
+                "the link conditioner of this server connection cannot be updated at runtime, the new settings are ignored"
+            ),
+        }
+    }
+}
+
 // or do additional send stuff here
 pub(crate) fn send<P: Protocol>(
     change_tick: SystemChangeTick,
@@ -224,14 +315,30 @@ pub(crate) fn send<P: Protocol>(
                 .servers
                 .get_mut(netserver_idx)
                 .context("could not find server with the provided netserver idx")?;
-            for packet_byte in connection.send_packets(&time_manager, &tick_manager)? {
+            let (packet_bytes, reliable_packet_bytes) = connection.send_packets(
+                &time_manager,
+                &tick_manager,
+                netserver.supports_reliable_send(),
+            )?;
+            for packet_byte in packet_bytes {
                 netserver.send(packet_byte.as_slice(), *client_id)?;
             }
+            for packet_byte in reliable_packet_bytes {
+                netserver.send_reliable(packet_byte.as_slice(), *client_id)?;
+            }
             Ok(())
         })
         .unwrap_or_else(|e: anyhow::Error| {
             error!("Error sending packets: {}", e);
         });
+    // send the packets buffered by the transport
+    for netserver in netservers.servers.iter_mut() {
+        if let Some(io) = netserver.io_mut() {
+            io.flush().unwrap_or_else(|e| {
+                error!("Error sending packets: {}", e);
+            });
+        }
+    }
 
     // clear the list of newly connected clients
     // (cannot just use the ConnectionEvent because it is cleared after each frame)
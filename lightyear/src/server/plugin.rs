@@ -8,6 +8,7 @@ use crate::protocol::component::ComponentProtocol;
 use crate::protocol::message::MessageProtocol;
 use crate::protocol::Protocol;
 use crate::server::connection::ConnectionManager;
+use crate::server::diagnostics::ServerDiagnosticsPlugin;
 use crate::server::events::ServerEventsPlugin;
 use crate::server::input::InputPlugin;
 use crate::server::networking::ServerNetworkingPlugin;
@@ -49,18 +50,42 @@ impl<P: Protocol> ServerPlugin<P> {
 impl<P: Protocol> Plugin for ServerPlugin<P> {
     fn build(&self, app: &mut App) {
         let config = self.config.lock().unwrap().deref_mut().take().unwrap();
+        let protocol_fingerprint = config.protocol.fingerprint();
+        let net_configs = config
+            .server_config
+            .net
+            .iter()
+            .cloned()
+            .map(|net| {
+                net.with_protocol_fingerprint(protocol_fingerprint)
+                    .with_mtu_discovery(config.server_config.packet.mtu.enabled)
+            })
+            .collect::<Vec<_>>();
+        // the packets must leave room for the largest header added by the transports
+        let transport_header_bytes = net_configs
+            .iter()
+            .map(|net| net.transport_header_bytes())
+            .max()
+            .unwrap_or_default();
+        let mut packet_config = config.server_config.packet.clone();
+        // the path MTU can only be discovered if none of the transports fragment the probes
+        packet_config.mtu.enabled &= net_configs.iter().all(|net| net.supports_mtu_discovery());
+        packet_config.mtu = packet_config
+            .mtu
+            .reserve_header_bytes(transport_header_bytes);
 
         app
             // RESOURCES //
             .insert_resource(config.server_config.clone())
             .insert_resource(ConnectionManager::<P>::new(
                 config.protocol.channel_registry().clone(),
-                config.server_config.packet,
+                packet_config,
                 config.server_config.ping,
             ))
             // PLUGINS
+            .add_plugins(ServerDiagnosticsPlugin::<P>::default())
             .add_plugins(ServerEventsPlugin::<P>::default())
-            .add_plugins(ServerNetworkingPlugin::<P>::new(config.server_config.net))
+            .add_plugins(ServerNetworkingPlugin::<P>::new(net_configs))
             .add_plugins(InputPlugin::<P>::default())
             .add_plugins(RoomPlugin::<P>::default())
             .add_plugins(ServerReplicationPlugin::<P>::default())
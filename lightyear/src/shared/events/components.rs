@@ -4,9 +4,15 @@ use std::marker::PhantomData;
 
 use bevy::prelude::{Component, Entity, Event};
 
+use crate::channel::bulk::TransferProgress;
+use crate::channel::error::ChannelError;
+use crate::connection::client::DisconnectReason;
 #[cfg(feature = "leafwing")]
 use crate::inputs::leafwing::InputMessage;
-use crate::packet::message::Message;
+use crate::packet::message::{Message, MessageHandle};
+use crate::protocol::channel::ChannelKind;
+use crate::shared::replication::authority::AuthorityChange;
+use crate::shared::replication::components::ReplicateRemovePolicy;
 
 /// This event is emitted whenever a client connects to the server
 #[derive(Event)]
@@ -21,16 +27,33 @@ impl<Ctx> ConnectEvent<Ctx> {
     }
 }
 
-/// This event is emitted whenever a client disconnects from the server
+/// This event is emitted whenever a client disconnects from the server.
+///
+/// On the client, it is also emitted when a connection attempt fails for a known reason
+/// (for example if the server uses a different protocol).
 #[derive(Event)]
-pub struct DisconnectEvent<Ctx = ()>(Ctx);
+pub struct DisconnectEvent<Ctx = ()> {
+    context: Ctx,
+    reason: Option<DisconnectReason>,
+}
 
 impl<Ctx> DisconnectEvent<Ctx> {
     pub fn new(context: Ctx) -> Self {
-        Self(context)
+        Self {
+            context,
+            reason: None,
+        }
+    }
+    pub(crate) fn with_reason(mut self, reason: Option<DisconnectReason>) -> Self {
+        self.reason = reason;
+        self
     }
     pub fn context(&self) -> &Ctx {
-        &self.0
+        &self.context
+    }
+    /// Reason of the disconnection, if it is known
+    pub fn reason(&self) -> Option<DisconnectReason> {
+        self.reason
     }
 }
 
@@ -76,6 +99,260 @@ impl<M: Message, Ctx> MessageEvent<M, Ctx> {
     }
 }
 
+/// This event is emitted when a message that we sent was acked by the remote
+#[derive(Event, Debug)]
+pub struct MessageDeliveredEvent<Ctx = ()> {
+    handle: MessageHandle,
+    channel: ChannelKind,
+    context: Ctx,
+}
+
+impl<Ctx> MessageDeliveredEvent<Ctx> {
+    pub fn new(handle: MessageHandle, channel: ChannelKind, context: Ctx) -> Self {
+        Self {
+            handle,
+            channel,
+            context,
+        }
+    }
+
+    /// The handle that was returned when the message was sent
+    pub fn handle(&self) -> MessageHandle {
+        self.handle
+    }
+
+    /// The channel that the message was sent on
+    pub fn channel(&self) -> ChannelKind {
+        self.channel
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
+/// This event is emitted when a message that we sent was lost.
+///
+/// This happens for messages sent on an
+/// [`UnorderedUnreliableWithAcks`](crate::channel::builder::ChannelMode::UnorderedUnreliableWithAcks) channel
+/// that were not acked, and for messages sent on a reliable channel with a time-to-live that expired before
+/// they were acked. Messages sent on the other reliable channels are resent until they are delivered.
+#[derive(Event, Debug)]
+pub struct MessageLostEvent<Ctx = ()> {
+    handle: MessageHandle,
+    channel: ChannelKind,
+    context: Ctx,
+}
+
+impl<Ctx> MessageLostEvent<Ctx> {
+    pub fn new(handle: MessageHandle, channel: ChannelKind, context: Ctx) -> Self {
+        Self {
+            handle,
+            channel,
+            context,
+        }
+    }
+
+    /// The handle that was returned when the message was sent
+    pub fn handle(&self) -> MessageHandle {
+        self.handle
+    }
+
+    /// The channel that the message was sent on
+    pub fn channel(&self) -> ChannelKind {
+        self.channel
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
+/// This event is emitted when more of a transfer that we are sending on a
+/// [`Bulk`](crate::channel::builder::ChannelMode::Bulk) channel was acked by the remote
+#[derive(Event, Debug)]
+pub struct TransferProgressEvent<Ctx = ()> {
+    handle: MessageHandle,
+    channel: ChannelKind,
+    progress: TransferProgress,
+    context: Ctx,
+}
+
+impl<Ctx> TransferProgressEvent<Ctx> {
+    pub fn new(
+        handle: MessageHandle,
+        channel: ChannelKind,
+        progress: TransferProgress,
+        context: Ctx,
+    ) -> Self {
+        Self {
+            handle,
+            channel,
+            progress,
+            context,
+        }
+    }
+
+    /// The handle that was returned when the message was sent
+    pub fn handle(&self) -> MessageHandle {
+        self.handle
+    }
+
+    /// The channel that the message was sent on
+    pub fn channel(&self) -> ChannelKind {
+        self.channel
+    }
+
+    /// Number of bytes acked by the remote, out of the total size of the transfer
+    pub fn progress(&self) -> TransferProgress {
+        self.progress
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
+/// This event is emitted when the remote cancelled a transfer on a
+/// [`Bulk`](crate::channel::builder::ChannelMode::Bulk) channel
+#[derive(Event, Debug)]
+pub struct TransferCancelledEvent<Ctx = ()> {
+    handle: Option<MessageHandle>,
+    channel: ChannelKind,
+    context: Ctx,
+}
+
+impl<Ctx> TransferCancelledEvent<Ctx> {
+    pub fn new(handle: Option<MessageHandle>, channel: ChannelKind, context: Ctx) -> Self {
+        Self {
+            handle,
+            channel,
+            context,
+        }
+    }
+
+    /// The handle that was returned when the message was sent, if we were sending the transfer.
+    ///
+    /// None if we were receiving the transfer and the sender cancelled it.
+    pub fn handle(&self) -> Option<MessageHandle> {
+        self.handle
+    }
+
+    /// The channel of the transfer
+    pub fn channel(&self) -> ChannelKind {
+        self.channel
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
+/// This event is emitted when a message received from the remote was dropped, because it did not respect
+/// the limits of its channel (see [`ChannelSettings`](crate::channel::builder::ChannelSettings)).
+///
+/// A well-behaved remote never sends such messages: on the server, you might want to disconnect the client.
+#[derive(Event, Debug)]
+pub struct MessageRejectedEvent<Ctx = ()> {
+    channel: ChannelKind,
+    error: ChannelError,
+    context: Ctx,
+}
+
+impl<Ctx> MessageRejectedEvent<Ctx> {
+    pub fn new(channel: ChannelKind, error: ChannelError, context: Ctx) -> Self {
+        Self {
+            channel,
+            error,
+            context,
+        }
+    }
+
+    /// The channel that the message was received on
+    pub fn channel(&self) -> ChannelKind {
+        self.channel
+    }
+
+    /// The limit of the channel that the message did not respect
+    pub fn error(&self) -> &ChannelError {
+        &self.error
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
+/// This event is emitted when the authority over a replicated entity changes.
+///
+/// On the server, it is emitted when a client requests the authority over an entity; the server can then call
+/// [`grant_authority`](crate::server::connection::ConnectionManager::grant_authority).
+/// On the client, it is emitted when the server grants or revokes the authority over an entity.
+#[derive(Event, Debug)]
+pub struct AuthorityEvent<Ctx = ()> {
+    entity: Entity,
+    change: AuthorityChange,
+    context: Ctx,
+}
+
+impl<Ctx> AuthorityEvent<Ctx> {
+    pub fn new(entity: Entity, change: AuthorityChange, context: Ctx) -> Self {
+        Self {
+            entity,
+            change,
+            context,
+        }
+    }
+
+    /// The local entity whose authority changed
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn change(&self) -> AuthorityChange {
+        self.change
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
+/// This event is emitted when the remote stopped replicating an entity, because the `Replicate` component
+/// was removed from it (without despawning it).
+///
+/// The [`ReplicateRemovePolicy`] tells what happened to the local entity: it can already be despawned (`Despawn`),
+/// be a regular entity that is no longer replicated (`Detach`) or keep its last replicated values (`Freeze`).
+#[derive(Event, Debug)]
+pub struct ReplicationStopEvent<Ctx = ()> {
+    entity: Entity,
+    policy: ReplicateRemovePolicy,
+    context: Ctx,
+}
+
+impl<Ctx> ReplicationStopEvent<Ctx> {
+    pub fn new(entity: Entity, policy: ReplicateRemovePolicy, context: Ctx) -> Self {
+        Self {
+            entity,
+            policy,
+            context,
+        }
+    }
+
+    /// The local entity that is no longer replicated
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn policy(&self) -> ReplicateRemovePolicy {
+        self.policy
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
 #[derive(Event)]
 /// Event emitted on server every time we receive an event
 pub struct InputEvent<I: crate::inputs::native::UserAction, Ctx = ()> {
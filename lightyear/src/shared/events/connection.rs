@@ -7,13 +7,17 @@ use bevy::utils::HashMap;
 use tracing::trace;
 
 use crate::_reexport::{FromType, MessageProtocol};
+use crate::channel::bulk::TransferProgress;
+use crate::channel::error::ChannelError;
 #[cfg(feature = "leafwing")]
 use crate::inputs::leafwing::{InputMessage, LeafwingUserAction};
-use crate::packet::message::Message;
+use crate::packet::message::{Message, MessageHandle};
 use crate::prelude::Tick;
 use crate::protocol::channel::ChannelKind;
 use crate::protocol::message::MessageKind;
 use crate::protocol::{EventContext, Protocol};
+use crate::shared::replication::authority::AuthorityChange;
+use crate::shared::replication::components::ReplicateRemovePolicy;
 
 // TODO: don't make fields pub but instead make accessors
 #[derive(Debug, Resource)]
@@ -24,9 +28,21 @@ pub struct ConnectionEvents<P: Protocol> {
 
     // messages
     pub messages: HashMap<MessageKind, HashMap<ChannelKind, Vec<P::Message>>>,
+    // delivery tracking of the messages we sent
+    pub delivered_messages: Vec<(MessageHandle, ChannelKind)>,
+    pub lost_messages: Vec<(MessageHandle, ChannelKind)>,
+    // transfers of the bulk channels
+    pub transfer_progress: Vec<(MessageHandle, ChannelKind, TransferProgress)>,
+    pub cancelled_transfers: Vec<(Option<MessageHandle>, ChannelKind)>,
+    // messages dropped because they did not respect the limits of their channel
+    pub rejected_messages: Vec<(ChannelKind, ChannelError)>,
+    // changes of the authority over replicated entities
+    pub authority_changes: Vec<(Entity, AuthorityChange)>,
     // replication
     pub spawns: Vec<Entity>,
     pub despawns: Vec<Entity>,
+    // entities that the remote stopped replicating
+    pub replication_stops: Vec<(Entity, ReplicateRemovePolicy)>,
 
     // TODO: [IMPORTANT]: add ticks as well?
     // - should we just return the latest update for a given component/entity, or all of them?
@@ -65,9 +81,16 @@ impl<P: Protocol> ConnectionEvents<P> {
             input_messages: HashMap::new(),
             // messages
             messages: HashMap::new(),
+            delivered_messages: Vec::new(),
+            lost_messages: Vec::new(),
+            transfer_progress: Vec::new(),
+            cancelled_transfers: Vec::new(),
+            rejected_messages: Vec::new(),
+            authority_changes: Vec::new(),
             // replication
             spawns: Vec::new(),
             despawns: Vec::new(),
+            replication_stops: Vec::new(),
             component_inserts: Default::default(),
             component_removes: Default::default(),
             component_updates: Default::default(),
@@ -81,8 +104,15 @@ impl<P: Protocol> ConnectionEvents<P> {
         #[cfg(feature = "leafwing")]
         self.input_messages.clear();
         self.messages.clear();
+        self.delivered_messages.clear();
+        self.lost_messages.clear();
+        self.transfer_progress.clear();
+        self.cancelled_transfers.clear();
+        self.rejected_messages.clear();
+        self.authority_changes.clear();
         self.spawns.clear();
         self.despawns.clear();
+        self.replication_stops.clear();
         self.component_inserts.clear();
         self.component_removes.clear();
         self.component_updates.clear();
@@ -129,6 +159,56 @@ impl<P: Protocol> ConnectionEvents<P> {
         self.empty = false;
     }
 
+    pub(crate) fn push_message_delivered(
+        &mut self,
+        handle: MessageHandle,
+        channel_kind: ChannelKind,
+    ) {
+        trace!(?handle, ?channel_kind, "Message delivered");
+        self.delivered_messages.push((handle, channel_kind));
+        self.empty = false;
+    }
+
+    pub(crate) fn push_message_lost(&mut self, handle: MessageHandle, channel_kind: ChannelKind) {
+        trace!(?handle, ?channel_kind, "Message lost");
+        self.lost_messages.push((handle, channel_kind));
+        self.empty = false;
+    }
+
+    pub(crate) fn push_transfer_progress(
+        &mut self,
+        handle: MessageHandle,
+        channel_kind: ChannelKind,
+        progress: TransferProgress,
+    ) {
+        trace!(?handle, ?channel_kind, ?progress, "Transfer progress");
+        self.transfer_progress
+            .push((handle, channel_kind, progress));
+        self.empty = false;
+    }
+
+    pub(crate) fn push_transfer_cancelled(
+        &mut self,
+        handle: Option<MessageHandle>,
+        channel_kind: ChannelKind,
+    ) {
+        trace!(?handle, ?channel_kind, "Transfer cancelled by the remote");
+        self.cancelled_transfers.push((handle, channel_kind));
+        self.empty = false;
+    }
+
+    pub(crate) fn push_message_rejected(&mut self, channel_kind: ChannelKind, error: ChannelError) {
+        trace!(?channel_kind, ?error, "Message rejected");
+        self.rejected_messages.push((channel_kind, error));
+        self.empty = false;
+    }
+
+    pub(crate) fn push_authority_change(&mut self, entity: Entity, change: AuthorityChange) {
+        trace!(?entity, ?change, "Authority change");
+        self.authority_changes.push((entity, change));
+        self.empty = false;
+    }
+
     pub(crate) fn push_spawn(&mut self, entity: Entity) {
         trace!(?entity, "Received entity spawn");
         #[cfg(feature = "metrics")]
@@ -149,6 +229,12 @@ impl<P: Protocol> ConnectionEvents<P> {
         self.empty = false;
     }
 
+    pub(crate) fn push_replication_stop(&mut self, entity: Entity, policy: ReplicateRemovePolicy) {
+        trace!(?entity, ?policy, "Received replication stop");
+        self.replication_stops.push((entity, policy));
+        self.empty = false;
+    }
+
     pub(crate) fn push_insert_component(
         &mut self,
         entity: Entity,
@@ -293,6 +379,149 @@ impl<P: Protocol> IterMessageEvent<P> for ConnectionEvents<P> {
     }
 }
 
+pub trait IterMessageDeliveredEvent<Ctx: EventContext = ()> {
+    fn into_iter_message_delivered(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (MessageHandle, ChannelKind, Ctx)> + '_>;
+    fn has_message_delivered(&self) -> bool;
+}
+
+impl<P: Protocol> IterMessageDeliveredEvent for ConnectionEvents<P> {
+    fn into_iter_message_delivered(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (MessageHandle, ChannelKind, ())> + '_> {
+        let delivered = std::mem::take(&mut self.delivered_messages);
+        Box::new(
+            delivered
+                .into_iter()
+                .map(|(handle, channel_kind)| (handle, channel_kind, ())),
+        )
+    }
+
+    fn has_message_delivered(&self) -> bool {
+        !self.delivered_messages.is_empty()
+    }
+}
+
+pub trait IterMessageLostEvent<Ctx: EventContext = ()> {
+    fn into_iter_message_lost(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (MessageHandle, ChannelKind, Ctx)> + '_>;
+    fn has_message_lost(&self) -> bool;
+}
+
+impl<P: Protocol> IterMessageLostEvent for ConnectionEvents<P> {
+    fn into_iter_message_lost(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (MessageHandle, ChannelKind, ())> + '_> {
+        let lost = std::mem::take(&mut self.lost_messages);
+        Box::new(
+            lost.into_iter()
+                .map(|(handle, channel_kind)| (handle, channel_kind, ())),
+        )
+    }
+
+    fn has_message_lost(&self) -> bool {
+        !self.lost_messages.is_empty()
+    }
+}
+
+pub trait IterTransferProgressEvent<Ctx: EventContext = ()> {
+    fn into_iter_transfer_progress(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (MessageHandle, ChannelKind, TransferProgress, Ctx)> + '_>;
+    fn has_transfer_progress(&self) -> bool;
+}
+
+impl<P: Protocol> IterTransferProgressEvent for ConnectionEvents<P> {
+    fn into_iter_transfer_progress(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (MessageHandle, ChannelKind, TransferProgress, ())> + '_> {
+        let progress = std::mem::take(&mut self.transfer_progress);
+        Box::new(
+            progress
+                .into_iter()
+                .map(|(handle, channel_kind, progress)| (handle, channel_kind, progress, ())),
+        )
+    }
+
+    fn has_transfer_progress(&self) -> bool {
+        !self.transfer_progress.is_empty()
+    }
+}
+
+pub trait IterTransferCancelledEvent<Ctx: EventContext = ()> {
+    fn into_iter_transfer_cancelled(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (Option<MessageHandle>, ChannelKind, Ctx)> + '_>;
+    fn has_transfer_cancelled(&self) -> bool;
+}
+
+impl<P: Protocol> IterTransferCancelledEvent for ConnectionEvents<P> {
+    fn into_iter_transfer_cancelled(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (Option<MessageHandle>, ChannelKind, ())> + '_> {
+        let cancelled = std::mem::take(&mut self.cancelled_transfers);
+        Box::new(
+            cancelled
+                .into_iter()
+                .map(|(handle, channel_kind)| (handle, channel_kind, ())),
+        )
+    }
+
+    fn has_transfer_cancelled(&self) -> bool {
+        !self.cancelled_transfers.is_empty()
+    }
+}
+
+pub trait IterMessageRejectedEvent<Ctx: EventContext = ()> {
+    fn into_iter_message_rejected(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (ChannelKind, ChannelError, Ctx)> + '_>;
+    fn has_message_rejected(&self) -> bool;
+}
+
+impl<P: Protocol> IterMessageRejectedEvent for ConnectionEvents<P> {
+    fn into_iter_message_rejected(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (ChannelKind, ChannelError, ())> + '_> {
+        let rejected = std::mem::take(&mut self.rejected_messages);
+        Box::new(
+            rejected
+                .into_iter()
+                .map(|(channel_kind, error)| (channel_kind, error, ())),
+        )
+    }
+
+    fn has_message_rejected(&self) -> bool {
+        !self.rejected_messages.is_empty()
+    }
+}
+
+pub trait IterAuthorityEvent<Ctx: EventContext = ()> {
+    fn into_iter_authority(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (Entity, AuthorityChange, Ctx)> + '_>;
+    fn has_authority(&self) -> bool;
+}
+
+impl<P: Protocol> IterAuthorityEvent for ConnectionEvents<P> {
+    fn into_iter_authority(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (Entity, AuthorityChange, ())> + '_> {
+        let changes = std::mem::take(&mut self.authority_changes);
+        Box::new(
+            changes
+                .into_iter()
+                .map(|(entity, change)| (entity, change, ())),
+        )
+    }
+
+    fn has_authority(&self) -> bool {
+        !self.authority_changes.is_empty()
+    }
+}
+
 pub trait IterEntitySpawnEvent<Ctx: EventContext = ()> {
     fn into_iter_entity_spawn(&mut self) -> Box<dyn Iterator<Item = (Entity, Ctx)> + '_>;
     fn has_entity_spawn(&self) -> bool;
@@ -309,6 +538,30 @@ impl<P: Protocol> IterEntitySpawnEvent for ConnectionEvents<P> {
     }
 }
 
+pub trait IterReplicationStopEvent<Ctx: EventContext = ()> {
+    fn into_iter_replication_stop(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (Entity, ReplicateRemovePolicy, Ctx)> + '_>;
+    fn has_replication_stop(&self) -> bool;
+}
+
+impl<P: Protocol> IterReplicationStopEvent for ConnectionEvents<P> {
+    fn into_iter_replication_stop(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (Entity, ReplicateRemovePolicy, ())> + '_> {
+        let stops = std::mem::take(&mut self.replication_stops);
+        Box::new(
+            stops
+                .into_iter()
+                .map(|(entity, policy)| (entity, policy, ())),
+        )
+    }
+
+    fn has_replication_stop(&self) -> bool {
+        !self.replication_stops.is_empty()
+    }
+}
+
 pub trait IterEntityDespawnEvent<Ctx: EventContext = ()> {
     fn into_iter_entity_despawn(&mut self) -> Box<dyn Iterator<Item = (Entity, Ctx)> + '_>;
     fn has_entity_despawn(&self) -> bool;
@@ -6,7 +6,9 @@ use bevy::prelude::Plugin;
 use crate::_reexport::{ComponentProtocol, EventContext, MessageProtocol};
 use crate::prelude::Protocol;
 use crate::shared::events::components::{
-    ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent,
+    AuthorityEvent, ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent,
+    MessageDeliveredEvent, MessageLostEvent, MessageRejectedEvent, ReplicationStopEvent,
+    TransferCancelledEvent, TransferProgressEvent,
 };
 
 pub struct EventsPlugin<P, Ctx> {
@@ -31,6 +33,13 @@ impl<P: Protocol, Ctx: EventContext> Plugin for EventsPlugin<P, Ctx> {
         app.add_event::<ConnectEvent<Ctx>>()
             .add_event::<DisconnectEvent<Ctx>>()
             .add_event::<EntitySpawnEvent<Ctx>>()
-            .add_event::<EntityDespawnEvent<Ctx>>();
+            .add_event::<EntityDespawnEvent<Ctx>>()
+            .add_event::<MessageDeliveredEvent<Ctx>>()
+            .add_event::<MessageLostEvent<Ctx>>()
+            .add_event::<TransferProgressEvent<Ctx>>()
+            .add_event::<TransferCancelledEvent<Ctx>>()
+            .add_event::<MessageRejectedEvent<Ctx>>()
+            .add_event::<AuthorityEvent<Ctx>>()
+            .add_event::<ReplicationStopEvent<Ctx>>();
     }
 }
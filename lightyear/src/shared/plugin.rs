@@ -3,7 +3,8 @@ use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 
 use crate::prelude::{
-    IoConfig, LinkConditionerConfig, Mode, PingConfig, Protocol, TickConfig, TransportConfig,
+    BurstLossConfig, IoConfig, LinkConditionerConfig, LinkConditionerSettings, Mode, PingConfig,
+    Protocol, TickConfig, TransportConfig,
 };
 use crate::server::config::ServerConfig;
 use crate::shared::config::SharedConfig;
@@ -48,6 +49,8 @@ impl<P: Protocol> Plugin for SharedPlugin<P> {
             .register_type::<TickConfig>()
             .register_type::<PingConfig>()
             .register_type::<LinkConditionerConfig>()
+            .register_type::<BurstLossConfig>()
+            .register_type::<LinkConditionerSettings>()
             .register_type::<IoConfig>();
 
         // RESOURCES
@@ -0,0 +1,416 @@
+//! This module is responsible for transferring the authority over replicated entities between the server and the clients.
+//!
+//! By default the server has authority over the entities that it replicates: it simulates them and sends their
+//! updates to the clients. The server can grant the authority over an entity to a client (for example when a player
+//! picks up a physics object, or enters a vehicle) with
+//! [`grant_authority`](crate::server::connection::ConnectionManager::grant_authority):
+//! - the server stops sending the inserts/updates/removals of the entity to that client, and applies the replication
+//!   messages that the client sends for the entity directly to its own entity (which are then replicated to the other clients)
+//! - the client despawns the `Predicted`/`Interpolated` copies of the entity, and starts replicating the `Confirmed`
+//!   entity (which becomes a normal entity marked with [`HasAuthority`]) to the server
+//!
+//! [`revoke_authority`](crate::server::connection::ConnectionManager::revoke_authority) gives the authority back to the
+//! server, and the client's entity becomes a `Confirmed` entity again.
+//!
+//! The authority messages are sent on the [`AuthorityChannel`](crate::channel::builder::AuthorityChannel), so that they
+//! are applied in order.
+use bevy::prelude::{Component, Entity, Reflect};
+use serde::{Deserialize, Serialize};
+
+use crate::shared::replication::components::ReplicationGroupId;
+
+/// Marker component added on the client to the entities that the client has authority over.
+///
+/// The client replicates these entities to the server instead of receiving their updates.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct HasAuthority;
+
+/// How the authority over an entity changed, see [`AuthorityEvent`](crate::shared::events::components::AuthorityEvent)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthorityChange {
+    /// A client requested the authority over the entity (only emitted on the server)
+    Requested,
+    /// The server granted us the authority over the entity (only emitted on the client)
+    Granted,
+    /// The server took back the authority over the entity (only emitted on the client)
+    Revoked,
+}
+
+/// Message used to transfer the authority over an entity.
+///
+/// The entities are always the server's entities.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AuthorityMessage {
+    /// Sent by a client to ask the server for the authority over an entity
+    Request(Entity),
+    /// Sent by the server to give the authority over an entity to a client.
+    /// The client keeps replicating the entity in the same replication group as the server did.
+    Grant {
+        entity: Entity,
+        group_id: ReplicationGroupId,
+    },
+    /// Sent by the server to take back the authority over an entity
+    Revoke(Entity),
+}
+
+impl AuthorityMessage {
+    pub(crate) fn entity(&self) -> Entity {
+        match self {
+            AuthorityMessage::Request(entity) => *entity,
+            AuthorityMessage::Grant { entity, .. } => *entity,
+            AuthorityMessage::Revoke(entity) => *entity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{default, Event, Events};
+
+    use crate::client::components::Confirmed;
+    use crate::client::events::AuthorityEvent as ClientAuthorityEvent;
+    use crate::prelude::client::ReplicationConfig;
+    use crate::prelude::{ClientId, NetworkTarget, ReplicationGroup};
+    use crate::server::events::AuthorityEvent as ServerAuthorityEvent;
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    use super::*;
+
+    fn read_events<E: Event, T>(events: &Events<E>, f: impl Fn(&E) -> T) -> Vec<T> {
+        events.get_reader().read(events).map(f).collect()
+    }
+
+    fn stepper_with_client_replication() -> BevyStepper {
+        BevyStepper::default_with_client_replication(ReplicationConfig {
+            enable_send: true,
+            enable_receive: true,
+        })
+    }
+
+    fn client_local_entity(stepper: &BevyStepper, server_entity: Entity) -> Entity {
+        *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap()
+    }
+
+    fn server_authority(stepper: &BevyStepper, server_entity: Entity) -> Option<ClientId> {
+        stepper
+            .server_app
+            .world
+            .resource::<ServerConnectionManager>()
+            .authority(server_entity)
+    }
+
+    #[test]
+    fn test_authority_transfer() {
+        let mut stepper = BevyStepper::default();
+        let client_id = ClientId::Netcode(111);
+
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((
+                Component1(1.0),
+                Replicate {
+                    prediction_target: NetworkTarget::All,
+                    ..default()
+                },
+            ))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+        let predicted_entity = stepper
+            .client_app
+            .world
+            .get::<Confirmed>(client_entity)
+            .unwrap()
+            .predicted
+            .unwrap();
+
+        // the client requests the authority over the entity
+        stepper
+            .client_app
+            .world
+            .resource_mut::<ClientConnectionManager>()
+            .request_authority(client_entity)
+            .unwrap();
+        stepper.frame_step();
+        let events = read_events(
+            stepper
+                .server_app
+                .world
+                .resource::<Events<ServerAuthorityEvent>>(),
+            |event| (event.entity(), event.change(), *event.context()),
+        );
+        assert_eq!(
+            events,
+            vec![(server_entity, AuthorityChange::Requested, client_id)]
+        );
+
+        // the server grants it: the confirmed entity becomes a replicated entity, and the predicted entity is despawned
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>()
+            .grant_authority(server_entity, client_id)
+            .unwrap();
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(
+            stepper
+                .server_app
+                .world
+                .resource::<ServerConnectionManager>()
+                .authority(server_entity),
+            Some(client_id)
+        );
+        assert!(stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .has_authority(client_entity));
+        let entity_ref = stepper.client_app.world.entity(client_entity);
+        assert!(entity_ref.contains::<HasAuthority>());
+        assert!(entity_ref.contains::<Replicate>());
+        assert!(!entity_ref.contains::<Confirmed>());
+        assert!(stepper
+            .client_app
+            .world
+            .get_entity(predicted_entity)
+            .is_none());
+        let events = read_events(
+            stepper
+                .client_app
+                .world
+                .resource::<Events<ClientAuthorityEvent>>(),
+            |event| (event.entity(), event.change()),
+        );
+        assert_eq!(events, vec![(client_entity, AuthorityChange::Granted)]);
+
+        // the server revokes it: the entity is predicted again
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>()
+            .revoke_authority(server_entity)
+            .unwrap();
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(
+            stepper
+                .server_app
+                .world
+                .resource::<ServerConnectionManager>()
+                .authority(server_entity),
+            None
+        );
+        let entity_ref = stepper.client_app.world.entity(client_entity);
+        assert!(!entity_ref.contains::<HasAuthority>());
+        assert!(!entity_ref.contains::<Replicate>());
+        assert!(entity_ref.get::<Confirmed>().unwrap().predicted.is_some());
+        let events = read_events(
+            stepper
+                .client_app
+                .world
+                .resource::<Events<ClientAuthorityEvent>>(),
+            |event| event.change(),
+        );
+        assert_eq!(events.last(), Some(&AuthorityChange::Revoked));
+    }
+
+    /// The updates of the client are applied to the server's entity, including the delta-compressed components
+    /// and the entities referenced by the components
+    #[test]
+    fn test_authority_client_updates() {
+        let mut stepper = stepper_with_client_replication();
+        let client_id = ClientId::Netcode(111);
+        // make sure that the client's entities don't have the same ids as the server's entities
+        for _ in 0..5 {
+            stepper.client_app.world.spawn_empty();
+        }
+
+        let other_server_entity = stepper
+            .server_app
+            .world
+            .spawn((Component1(0.0), Replicate::default()))
+            .id();
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((
+                Component1(1.0),
+                Component5(vec![1, 2, 3]),
+                Replicate::default(),
+            ))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity = client_local_entity(&stepper, server_entity);
+        let other_client_entity = client_local_entity(&stepper, other_server_entity);
+
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>()
+            .grant_authority(server_entity, client_id)
+            .unwrap();
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(stepper
+            .client_app
+            .world
+            .entity(client_entity)
+            .contains::<HasAuthority>());
+
+        // the client modifies the entity
+        let mut entity_mut = stepper.client_app.world.entity_mut(client_entity);
+        entity_mut.get_mut::<Component1>().unwrap().0 = 2.0;
+        entity_mut.get_mut::<Component5>().unwrap().0.push(4);
+        entity_mut.insert(Component4(other_client_entity));
+        // the server only applies the messages once it reaches the tick of the client, which is ahead of it
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        let entity_ref = stepper.server_app.world.entity(server_entity);
+        assert_eq!(entity_ref.get::<Component1>(), Some(&Component1(2.0)));
+        assert_eq!(
+            entity_ref.get::<Component5>(),
+            Some(&Component5(vec![1, 2, 3, 4]))
+        );
+        assert_eq!(
+            entity_ref.get::<Component4>(),
+            Some(&Component4(other_server_entity))
+        );
+    }
+
+    /// The authority is transferred for the whole replication group, and the client's entities become
+    /// confirmed entities again when it is revoked even if they are not predicted or interpolated
+    #[test]
+    fn test_authority_replication_group() {
+        let mut stepper = BevyStepper::default();
+        let client_id = ClientId::Netcode(111);
+
+        let replicate = Replicate {
+            replication_group: ReplicationGroup::new_id(1),
+            ..default()
+        };
+        let server_entity_1 = stepper
+            .server_app
+            .world
+            .spawn((Component1(1.0), replicate.clone()))
+            .id();
+        let server_entity_2 = stepper
+            .server_app
+            .world
+            .spawn((Component2(1.0), replicate))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity_1 = client_local_entity(&stepper, server_entity_1);
+        let client_entity_2 = client_local_entity(&stepper, server_entity_2);
+
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>()
+            .grant_authority(server_entity_1, client_id)
+            .unwrap();
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(server_authority(&stepper, server_entity_1), Some(client_id));
+        assert_eq!(server_authority(&stepper, server_entity_2), Some(client_id));
+        for client_entity in [client_entity_1, client_entity_2] {
+            assert!(stepper
+                .client_app
+                .world
+                .entity(client_entity)
+                .contains::<HasAuthority>());
+        }
+
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>()
+            .revoke_authority(server_entity_2)
+            .unwrap();
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(server_authority(&stepper, server_entity_1), None);
+        assert_eq!(server_authority(&stepper, server_entity_2), None);
+        for client_entity in [client_entity_1, client_entity_2] {
+            let entity_ref = stepper.client_app.world.entity(client_entity);
+            assert!(!entity_ref.contains::<HasAuthority>());
+            assert!(!entity_ref.contains::<Replicate>());
+            let confirmed = entity_ref.get::<Confirmed>().unwrap();
+            assert_eq!(confirmed.predicted, None);
+            assert_eq!(confirmed.interpolated, None);
+        }
+    }
+
+    /// A grant for an entity that the client doesn't receive is rejected, and a grant for an entity that is
+    /// despawned before the client could apply it is dropped
+    #[test]
+    fn test_authority_grant_unreceived_entity() {
+        let mut stepper = BevyStepper::default();
+        let client_id = ClientId::Netcode(111);
+
+        // the entity is not replicated to the client
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((
+                Component1(1.0),
+                Replicate {
+                    replication_target: NetworkTarget::None,
+                    ..default()
+                },
+            ))
+            .id();
+        stepper.frame_step();
+        assert!(stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>()
+            .grant_authority(server_entity, client_id)
+            .is_err());
+        assert_eq!(server_authority(&stepper, server_entity), None);
+
+        // the entity is despawned right after the grant
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((Component1(1.0), Replicate::default()))
+            .id();
+        stepper.frame_step();
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>()
+            .grant_authority(server_entity, client_id)
+            .unwrap();
+        stepper.server_app.world.despawn(server_entity);
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(server_authority(&stepper, server_entity), None);
+        let manager = stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>();
+        assert!(manager.pending_authority.is_empty());
+        assert!(manager.authority.is_empty());
+    }
+}
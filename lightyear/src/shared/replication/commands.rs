@@ -21,6 +21,8 @@ fn remove_replicate<P: Protocol, R: ReplicationSend<P>>(entity: Entity, world: &
 pub trait RemoveReplicateCommandsExt<P: Protocol, R: ReplicationSend<P>> {
     /// Remove the replicate component from the entity.
     /// This also makes sure that if you despawn the entity right after, the despawn won't be replicated.
+    /// The remote is not notified either: the entity's [`ReplicateRemovePolicy`](crate::prelude::ReplicateRemovePolicy)
+    /// is not applied, and the remote entity is kept as is.
     ///
     /// This can be useful when you want to despawn an entity on the server, but you don't want the despawn to be replicated
     /// immediately to clients (for example because clients are playing a despawn animation)/
@@ -58,6 +60,7 @@ mod tests {
             incoming_latency: Duration::from_millis(0),
             incoming_jitter: Duration::from_millis(0),
             incoming_loss: 0.0,
+            ..Default::default()
         };
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
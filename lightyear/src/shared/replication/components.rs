@@ -37,8 +37,9 @@ pub struct Replicate<P: Protocol> {
     #[doc(hidden)]
     pub replication_clients_cache: HashMap<ClientId, ClientVisibility>,
     pub replication_mode: ReplicationMode,
-    // TODO: currently, if the host removes Replicate, then the entity is not removed in the remote
-    //  it just keeps living but doesn't receive any updates. Should we make this configurable?
+    /// What happens to the remote entity when the `Replicate` component is removed from this entity
+    /// (while the entity itself is not despawned)
+    pub remove_policy: ReplicateRemovePolicy,
     pub replication_group: ReplicationGroup,
     /// If true, recursively add `Replicate` and `ParentSync` components to all children to make sure they are replicated
     /// If false, you can still replicate hierarchies, but in a more fine-grained manner. You will have to add the `Replicate`
@@ -272,6 +273,24 @@ pub enum ReplicationMode {
     NetworkTarget,
 }
 
+/// What happens to the remote entity when the host removes the [`Replicate`] component from an entity
+/// that is not despawned.
+///
+/// In every case the remote receives a `ReplicationStopEvent`.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq, Reflect)]
+pub enum ReplicateRemovePolicy {
+    /// The remote entity is despawned, as if the entity had been despawned on the host
+    Despawn,
+    /// The remote entity keeps living, but is not linked to the host entity anymore: the `Confirmed` component
+    /// is removed (so the `Predicted`/`Interpolated` copies are despawned), and the entity is removed from
+    /// the entity mapping. Replicating the entity again will spawn a new remote entity
+    Detach,
+    /// The remote entity keeps living with its last received values, but doesn't receive any updates.
+    /// Replicating the entity again will resume the updates on the same remote entity
+    #[default]
+    Freeze,
+}
+
 impl<P: Protocol> Default for Replicate<P> {
     fn default() -> Self {
         #[allow(unused_mut)]
@@ -281,6 +300,7 @@ impl<P: Protocol> Default for Replicate<P> {
             interpolation_target: NetworkTarget::None,
             replication_clients_cache: HashMap::new(),
             replication_mode: ReplicationMode::default(),
+            remove_policy: ReplicateRemovePolicy::default(),
             replication_group: Default::default(),
             replicate_hierarchy: true,
             per_component_metadata: HashMap::default(),
@@ -0,0 +1,1178 @@
+//! Delta compression of component updates
+//!
+//! The components marked with `#[protocol(delta)]` in the `ComponentProtocol` are sent in the
+//! [`EntityUpdatesMessage`](super::EntityUpdatesMessage)s as serialized bytes. If the remote has acked a previous
+//! value of the component, the sender only sends the bytes that changed since that value (the baseline).
+//!
+//! The values are identified by the tick at which the update message that contained them was created:
+//! - the sender keeps, for each remote, the last value of each component that was acked, and the values that are in flight
+//! - the receiver keeps the values that it received, and rebuilds the component from the value that the diff refers to
+//!
+//! Once the receiver gets a diff against a baseline, it discards the older values (the sender only uses more recent
+//! baselines from then on). A diff that was reordered behind a diff against a more recent baseline can therefore not
+//! be rebuilt, so the sender only adopts an acked value as the new baseline if the receiver was guaranteed to rebuild it:
+//! if it was sent in full, or as a diff against the most recent baseline used so far.
+//!
+//! The components marked with `#[protocol(fields)]` work the same way, but instead of a diff of the bytes, the
+//! update only contains the fields that changed since the baseline (see [`ReplicateFields`](crate::prelude::ReplicateFields)).
+//! The sender tracks the tick at which each field last changed, by comparing each value with the previous one,
+//! so it only needs the tick of the baseline. The receiver keeps the values themselves as baselines, and patches
+//! a copy of the baseline, so the component is updated as a whole on the receiver
+//! (and a `ComponentUpdateEvent` is emitted, and the prediction/interpolation histories are updated) like for any other update.
+//!
+//! Only the updates sent on the [`EntityUpdatesChannel`](crate::prelude::EntityUpdatesChannel) are delta-compressed;
+//! inserts and the updates that are sent with the entity actions always contain the full component.
+use std::collections::BTreeMap;
+
+use bevy::ecs::entity::EntityHash;
+use bevy::prelude::Entity;
+use bevy::utils::{hashbrown, HashMap};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use crate::packet::message::MessageId;
+use crate::prelude::Tick;
+use crate::protocol::component::ComponentProtocol;
+use crate::protocol::Protocol;
+use crate::serialize::reader::ReadBuffer;
+use crate::serialize::wordbuffer::reader::ReadWordBuffer;
+use crate::serialize::wordbuffer::writer::WriteWordBuffer;
+use crate::serialize::writer::WriteBuffer;
+use crate::shared::replication::components::ReplicationGroupId;
+
+use super::EntityUpdatesMessage;
+
+type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
+
+/// Maximum number of unchanged bytes inside a run of changed bytes.
+/// Starting a new run costs about 2 bytes, so it's cheaper to include a few unchanged bytes in the current run.
+const MAX_GAP: usize = 2;
+
+/// The sender stops using values older than this as baselines, because the tick comparisons wrap around.
+/// The receiver keeps the values for twice as long, to account for the offset between the client and server ticks.
+const MAX_BASELINE_AGE: i16 = i16::MAX / 4;
+
+/// Difference between the serialized value of a component and a baseline value
+///
+/// The bytes after the end of the baseline are always included in the runs, so that rebuilding the value never
+/// allocates more than the baseline and the diff.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ByteDiff {
+    /// Number of bytes of the new value
+    #[serde(with = "crate::serialize::encoding::gamma")]
+    len: u32,
+    /// Number of unchanged bytes before each run
+    #[serde(with = "crate::serialize::encoding::gamma")]
+    skips: Vec<u32>,
+    /// Runs of changed bytes, XORed with the baseline
+    runs: Vec<Bytes>,
+}
+
+impl ByteDiff {
+    pub(crate) fn new(baseline: &[u8], value: &[u8]) -> Self {
+        let xor: Vec<u8> = value
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ baseline.get(i).copied().unwrap_or_default())
+            .collect();
+        let changed = |i: usize| i >= baseline.len() || xor[i] != 0;
+        let mut skips = Vec::new();
+        let mut runs = Vec::new();
+        let mut last_end = 0;
+        let mut i = 0;
+        while i < xor.len() {
+            if !changed(i) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            let mut end = i + 1;
+            while end < xor.len() {
+                if changed(end) {
+                    end += 1;
+                    continue;
+                }
+                // extend the run over a short gap of unchanged bytes
+                let gap = (end..xor.len())
+                    .take(MAX_GAP + 1)
+                    .take_while(|i| !changed(*i))
+                    .count();
+                if gap <= MAX_GAP && end + gap < xor.len() {
+                    end += gap;
+                } else {
+                    break;
+                }
+            }
+            skips.push((start - last_end) as u32);
+            runs.push(Bytes::copy_from_slice(&xor[start..end]));
+            last_end = end;
+            i = end;
+        }
+        Self {
+            len: value.len() as u32,
+            skips,
+            runs,
+        }
+    }
+
+    /// Rebuild the new value from the baseline. Returns None if the diff is invalid.
+    pub(crate) fn apply(&self, baseline: &[u8]) -> Option<Bytes> {
+        let len = self.len as usize;
+        // the bytes after the end of the baseline must all be in the runs
+        let runs_end = self
+            .skips
+            .iter()
+            .zip(self.runs.iter())
+            .map(|(skip, run)| *skip as usize + run.len())
+            .sum::<usize>();
+        if len > baseline.len() && len > runs_end {
+            return None;
+        }
+        let mut value = baseline.to_vec();
+        value.resize(len, 0);
+        let mut position = 0;
+        for (skip, run) in self.skips.iter().zip(self.runs.iter()) {
+            position += *skip as usize;
+            let end = position + run.len();
+            if end > len {
+                return None;
+            }
+            value[position..end]
+                .iter_mut()
+                .zip(run.iter())
+                .for_each(|(byte, diff)| *byte ^= diff);
+            position = end;
+        }
+        Some(Bytes::from(value))
+    }
+
+    /// Approximate number of bytes of the serialized diff
+    fn encoded_len(&self) -> usize {
+        self.runs.iter().map(|run| run.len() + 2).sum::<usize>() + 2
+    }
+}
+
+/// Value of a delta-compressed component in an update message
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum DeltaValue {
+    /// The serialized component
+    Full(Bytes),
+    /// Difference between the serialized component and the value that was sent at `baseline_tick`
+    Diff { baseline_tick: Tick, diff: ByteDiff },
+    /// Fields of the component that are different from the value that was sent at `baseline_tick`
+    Fields { baseline_tick: Tick, fields: Bytes },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ComponentDelta<K> {
+    pub(crate) kind: K,
+    pub(crate) value: DeltaValue,
+}
+
+/// Value of a delta-compressed component that was sent in an update message,
+/// with the tick of the baseline it was encoded against (if any).
+///
+/// The serialized value is only kept for the byte diffs: the components with field-level replication
+/// only need the tick of their baseline.
+type SentValue<K> = (Entity, K, Option<Bytes>, Option<Tick>);
+
+/// Values of delta-compressed components that were sent in an update message
+type SentValues<K> = (Tick, Vec<SentValue<K>>);
+
+/// Baseline of a delta-compressed component for a remote
+struct Baseline {
+    /// Tick that identifies the last value that was acked by the remote
+    tick: Tick,
+    /// Serialized value, for the components that are sent as byte diffs
+    bytes: Option<Bytes>,
+    /// Most recent baseline tick that was used to encode a value: the receiver may have discarded the older values
+    last_used: Option<Tick>,
+}
+
+/// Changes of the fields of a component with field-level replication
+struct FieldChanges<C> {
+    /// Last value that was sent
+    value: C,
+    /// Tick at which the last value was sent
+    tick: Tick,
+    /// Tick of the first value where each field was different from the previous value,
+    /// or None if the field didn't change since the oldest baseline that can be used
+    changed: Vec<Option<Tick>>,
+}
+
+/// Sender side of the delta compression, for a single remote
+pub(crate) struct DeltaSender<P: Protocol> {
+    /// Baseline of each component: the last value that was acked by the remote
+    acked: EntityHashMap<Entity, HashMap<P::ComponentKinds, Baseline>>,
+    /// Values of the update messages that were created but whose message id is not known yet
+    unsent: EntityHashMap<ReplicationGroupId, SentValues<P::ComponentKinds>>,
+    /// Values of the update messages that were sent but not acked yet
+    sent: HashMap<MessageId, SentValues<P::ComponentKinds>>,
+    /// Tick of the last update message that contained delta-compressed values, for each group
+    last_message_tick: EntityHashMap<ReplicationGroupId, Tick>,
+    /// Changes of the fields of the components with field-level replication
+    field_changes: EntityHashMap<Entity, HashMap<P::ComponentKinds, FieldChanges<P::Components>>>,
+    writer: WriteWordBuffer,
+}
+
+impl<P: Protocol> Default for DeltaSender<P> {
+    fn default() -> Self {
+        Self {
+            field_changes: Default::default(),
+            acked: Default::default(),
+            unsent: Default::default(),
+            sent: Default::default(),
+            last_message_tick: Default::default(),
+            writer: WriteWordBuffer::with_capacity(0),
+        }
+    }
+}
+
+impl<P: Protocol> DeltaSender<P> {
+    /// Split the updates of a group between the components that are sent in full, and the delta-compressed components.
+    ///
+    /// Also returns the tick that identifies the delta-compressed values, if the remote can use them as baselines.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn encode_updates(
+        &mut self,
+        group_id: ReplicationGroupId,
+        tick: Tick,
+        updates: EntityHashMap<Entity, Vec<P::Components>>,
+    ) -> (
+        Vec<(Entity, Vec<P::Components>)>,
+        Vec<(Entity, Vec<ComponentDelta<P::ComponentKinds>>)>,
+        Option<Tick>,
+    ) {
+        let mut full_updates = Vec::with_capacity(updates.len());
+        let mut deltas = Vec::new();
+        let mut sent_values = Vec::new();
+        for (entity, components) in updates {
+            let (delta_components, components): (Vec<_>, Vec<_>) = components
+                .into_iter()
+                .partition(|component| component.delta_compression());
+            if !components.is_empty() {
+                full_updates.push((entity, components));
+            }
+            let mut entity_deltas = Vec::with_capacity(delta_components.len());
+            for component in delta_components {
+                let kind: P::ComponentKinds = (&component).into();
+                let encoded = if component.field_replication() {
+                    self.encode_fields(entity, tick, &component)
+                        .map(|value| (value, None))
+                } else {
+                    self.encode_bytes(entity, &component)
+                        .map(|(value, bytes)| (value, Some(bytes)))
+                };
+                let (value, bytes) = match encoded {
+                    Ok(encoded) => encoded,
+                    Err(e) => {
+                        error!(?entity, ?kind, ?e, "could not serialize component");
+                        continue;
+                    }
+                };
+                let baseline_tick = match value {
+                    DeltaValue::Full(_) => None,
+                    DeltaValue::Diff { baseline_tick, .. }
+                    | DeltaValue::Fields { baseline_tick, .. } => Some(baseline_tick),
+                };
+                entity_deltas.push(ComponentDelta { kind, value });
+                sent_values.push((entity, kind, bytes, baseline_tick));
+            }
+            if !entity_deltas.is_empty() {
+                deltas.push((entity, entity_deltas));
+            }
+        }
+        if sent_values.is_empty() {
+            return (full_updates, deltas, None);
+        }
+        // the values are identified by the tick of the message, so they can only be used as baselines
+        // if no other update message was created for the group at the same tick
+        if self.last_message_tick.insert(group_id, tick) == Some(tick) {
+            return (full_updates, deltas, None);
+        }
+        self.unsent.insert(group_id, (tick, sent_values));
+        (full_updates, deltas, Some(tick))
+    }
+
+    fn serialize(writer: &mut WriteWordBuffer, component: &P::Components) -> anyhow::Result<Bytes> {
+        writer.start_write();
+        writer.serialize(component)?;
+        Ok(Bytes::copy_from_slice(writer.finish_write()))
+    }
+
+    /// Baseline of the component, and mark it as used to encode a value
+    fn use_baseline(&mut self, entity: Entity, kind: P::ComponentKinds) -> Option<&Baseline> {
+        let baseline = self.acked.get_mut(&entity)?.get_mut(&kind)?;
+        baseline.last_used = Some(baseline.tick);
+        Some(baseline)
+    }
+
+    /// Encode the value as a diff of the bytes against the last acked value, if that is smaller than the full value
+    fn encode_bytes(
+        &mut self,
+        entity: Entity,
+        component: &P::Components,
+    ) -> anyhow::Result<(DeltaValue, Bytes)> {
+        let kind: P::ComponentKinds = component.into();
+        let bytes = Self::serialize(&mut self.writer, component)?;
+        let diff = self
+            .acked
+            .get(&entity)
+            .and_then(|values| values.get(&kind))
+            .and_then(|baseline| Some((baseline.tick, baseline.bytes.as_ref()?)))
+            .map(|(baseline_tick, baseline)| (baseline_tick, ByteDiff::new(baseline, &bytes)))
+            .filter(|(_, diff)| diff.encoded_len() < bytes.len());
+        let Some((baseline_tick, diff)) = diff else {
+            return Ok((DeltaValue::Full(bytes.clone()), bytes));
+        };
+        self.use_baseline(entity, kind);
+        Ok((
+            DeltaValue::Diff {
+                baseline_tick,
+                diff,
+            },
+            bytes,
+        ))
+    }
+
+    /// Track the fields of the value that changed, and encode the fields that changed since the last acked value
+    fn encode_fields(
+        &mut self,
+        entity: Entity,
+        tick: Tick,
+        component: &P::Components,
+    ) -> anyhow::Result<DeltaValue> {
+        let kind: P::ComponentKinds = component.into();
+        let changed = self.track_field_changes(entity, kind, tick, component);
+        let Some(baseline_tick) = self
+            .use_baseline(entity, kind)
+            .map(|baseline| baseline.tick)
+        else {
+            return Ok(DeltaValue::Full(Self::serialize(
+                &mut self.writer,
+                component,
+            )?));
+        };
+        let fields: Vec<bool> = changed
+            .iter()
+            .map(|changed| matches!(changed, Some(changed) if *changed > baseline_tick))
+            .collect();
+        self.writer.start_write();
+        component.write_fields(&fields, &mut self.writer)?;
+        Ok(DeltaValue::Fields {
+            baseline_tick,
+            fields: Bytes::copy_from_slice(self.writer.finish_write()),
+        })
+    }
+
+    /// Compare the fields of the value with the previous value that was sent, and return the tick at which each
+    /// field last changed
+    fn track_field_changes(
+        &mut self,
+        entity: Entity,
+        kind: P::ComponentKinds,
+        tick: Tick,
+        component: &P::Components,
+    ) -> Vec<Option<Tick>> {
+        let values = self.field_changes.entry(entity).or_default();
+        let Some(changes) = values.get_mut(&kind) else {
+            // no value was sent before: the remote cannot have a baseline yet
+            let changed = vec![Some(tick); component.num_fields()];
+            values.insert(
+                kind,
+                FieldChanges {
+                    value: component.clone(),
+                    tick,
+                    changed: changed.clone(),
+                },
+            );
+            return changed;
+        };
+        // a value that was sent earlier at the same tick can become a baseline with that tick:
+        // the changes since that value must be sent in the updates that use it
+        let change_tick = if changes.tick == tick { tick + 1 } else { tick };
+        changes
+            .changed
+            .resize(component.num_fields(), Some(change_tick));
+        for (index, changed) in changes.changed.iter_mut().enumerate() {
+            if component.field_changed(&changes.value, index) {
+                *changed = Some(change_tick);
+            }
+        }
+        changes.value = component.clone();
+        changes.tick = tick;
+        changes.changed.clone()
+    }
+
+    /// The update message of the group was buffered with the given message id
+    pub(crate) fn track_message(&mut self, group_id: ReplicationGroupId, message_id: MessageId) {
+        if let Some(values) = self.unsent.remove(&group_id) {
+            self.sent.insert(message_id, values);
+        }
+    }
+
+    /// The remote received the update message: its values can be used as baselines
+    pub(crate) fn ack(&mut self, message_id: MessageId) {
+        let Some((tick, values)) = self.sent.remove(&message_id) else {
+            return;
+        };
+        for (entity, kind, bytes, baseline_tick) in values {
+            let acked = self.acked.entry(entity).or_default();
+            let Some(baseline) = acked.get_mut(&kind) else {
+                // without a baseline, we don't know which values the receiver discarded:
+                // only a value that was sent in full can be used
+                if baseline_tick.is_some() {
+                    continue;
+                }
+                acked.insert(
+                    kind,
+                    Baseline {
+                        tick,
+                        bytes,
+                        last_used: None,
+                    },
+                );
+                continue;
+            };
+            // messages can be acked out of order
+            if tick <= baseline.tick {
+                continue;
+            }
+            // the receiver could not rebuild the value if it received a diff against a more recent baseline first
+            if baseline_tick.is_some() && baseline_tick < baseline.last_used {
+                continue;
+            }
+            baseline.tick = tick;
+            baseline.bytes = bytes;
+        }
+    }
+
+    /// Forget all the values of an entity that is despawned on the remote
+    pub(crate) fn remove_entity(&mut self, entity: Entity) {
+        self.acked.remove(&entity);
+        self.field_changes.remove(&entity);
+        self.unsent
+            .values_mut()
+            .chain(self.sent.values_mut())
+            .for_each(|(_, values)| values.retain(|(e, _, _, _)| *e != entity));
+    }
+
+    /// Stop using values that are too old as baselines
+    pub(crate) fn cleanup(&mut self, tick: Tick) {
+        self.acked.retain(|_, values| {
+            values.retain(|_, baseline| tick - baseline.tick <= MAX_BASELINE_AGE);
+            !values.is_empty()
+        });
+        // messages that were lost are never acked
+        self.sent
+            .retain(|_, (sent_tick, _)| tick - *sent_tick <= MAX_BASELINE_AGE);
+        self.last_message_tick
+            .retain(|_, message_tick| tick - *message_tick <= MAX_BASELINE_AGE);
+        // the baselines are more recent than the old changes, and the tick comparisons would wrap around
+        self.field_changes
+            .values_mut()
+            .flat_map(|values| values.values_mut())
+            .flat_map(|changes| changes.changed.iter_mut())
+            .for_each(|changed| {
+                if changed.is_some_and(|changed| tick - changed > MAX_BASELINE_AGE) {
+                    *changed = None;
+                }
+            });
+    }
+}
+
+/// Value of a delta-compressed component that was received, kept as a baseline
+enum ReceivedValue<C> {
+    /// Serialized value of a component that is sent as byte diffs
+    Bytes(Bytes),
+    /// Value of a component with field-level replication
+    Component(C),
+}
+
+/// Receiver side of the delta compression, for a single remote
+pub(crate) struct DeltaReceiver<P: Protocol> {
+    /// Values of the delta-compressed components that were received, identified by the tick of their message
+    history: EntityHashMap<
+        Entity,
+        HashMap<P::ComponentKinds, BTreeMap<Tick, ReceivedValue<P::Components>>>,
+    >,
+}
+
+impl<P: Protocol> Default for DeltaReceiver<P> {
+    fn default() -> Self {
+        Self {
+            history: Default::default(),
+        }
+    }
+}
+
+impl<P: Protocol> DeltaReceiver<P> {
+    /// Rebuild the delta-compressed components of an update message, and add them to the other updates of the message.
+    ///
+    /// This must be called for every update message that is received, even if it is too old to be applied,
+    /// because the sender can use its values as baselines.
+    pub(crate) fn decode_updates(
+        &mut self,
+        message: &mut EntityUpdatesMessage<P::Components, P::ComponentKinds>,
+    ) {
+        for (entity, deltas) in std::mem::take(&mut message.deltas) {
+            let mut components = Vec::with_capacity(deltas.len());
+            for delta in deltas {
+                let kind = delta.kind;
+                match self.decode(entity, message.delta_tick, delta) {
+                    Ok(Some(component)) => components.push(component),
+                    // the baseline was already discarded, which only happens if a more recent update was received
+                    Ok(None) => debug!(
+                        ?entity,
+                        ?kind,
+                        "could not find the baseline of a delta-compressed update"
+                    ),
+                    Err(e) => error!(?entity, ?kind, ?e, "could not decode component"),
+                }
+            }
+            if components.is_empty() {
+                continue;
+            }
+            match message.updates.iter_mut().find(|(e, _)| *e == entity) {
+                Some((_, updates)) => updates.extend(components),
+                None => message.updates.push((entity, components)),
+            }
+        }
+    }
+
+    /// Rebuild the component, and store it if the message can be used as a baseline.
+    ///
+    /// Returns None if the baseline is not available anymore.
+    fn decode(
+        &mut self,
+        entity: Entity,
+        delta_tick: Option<Tick>,
+        delta: ComponentDelta<P::ComponentKinds>,
+    ) -> anyhow::Result<Option<P::Components>> {
+        let history = self
+            .history
+            .entry(entity)
+            .or_default()
+            .entry(delta.kind)
+            .or_default();
+        let baseline_tick = match &delta.value {
+            DeltaValue::Full(_) => None,
+            DeltaValue::Diff { baseline_tick, .. } | DeltaValue::Fields { baseline_tick, .. } => {
+                Some(*baseline_tick)
+            }
+        };
+        // the serialized value is only kept as a baseline for the byte diffs
+        let (component, bytes) = match delta.value {
+            DeltaValue::Full(bytes) => {
+                let component = Self::deserialize(&bytes)?;
+                let bytes = (!component.field_replication()).then_some(bytes);
+                (component, bytes)
+            }
+            DeltaValue::Diff {
+                baseline_tick,
+                diff,
+            } => {
+                let Some(ReceivedValue::Bytes(baseline)) = history.get(&baseline_tick) else {
+                    return Ok(None);
+                };
+                let bytes = diff
+                    .apply(baseline)
+                    .ok_or_else(|| anyhow::anyhow!("invalid diff"))?;
+                (Self::deserialize(&bytes)?, Some(bytes))
+            }
+            DeltaValue::Fields {
+                baseline_tick,
+                fields,
+            } => {
+                let Some(ReceivedValue::Component(baseline)) = history.get(&baseline_tick) else {
+                    return Ok(None);
+                };
+                let mut component = baseline.clone();
+                component.read_fields(&mut ReadWordBuffer::start_read(&fields))?;
+                (component, None)
+            }
+        };
+        if let Some(baseline_tick) = baseline_tick {
+            // the sender only uses more recent baselines from now on
+            *history = history.split_off(&baseline_tick);
+        }
+        if let Some(tick) = delta_tick {
+            let value = match bytes {
+                Some(bytes) => ReceivedValue::Bytes(bytes),
+                None => ReceivedValue::Component(component.clone()),
+            };
+            history.insert(tick, value);
+        }
+        Ok(Some(component))
+    }
+
+    fn deserialize(bytes: &[u8]) -> anyhow::Result<P::Components> {
+        let mut reader = ReadWordBuffer::start_read(bytes);
+        reader.deserialize::<P::Components>()
+    }
+
+    /// Forget the values received up to the tick at which the entity was despawned
+    pub(crate) fn remove_entity(&mut self, entity: Entity, tick: Tick) {
+        if let Some(values) = self.history.get_mut(&entity) {
+            values.retain(|_, history| {
+                *history = history.split_off(&(tick + 1));
+                !history.is_empty()
+            });
+            if values.is_empty() {
+                self.history.remove(&entity);
+            }
+        }
+    }
+
+    /// Forget the values that are too old to be used as baselines by the sender
+    pub(crate) fn cleanup(&mut self, tick: Tick) {
+        self.history.retain(|_, values| {
+            values.retain(|_, history| {
+                history.retain(|value_tick, _| tick - *value_tick <= 2 * MAX_BASELINE_AGE);
+                !history.is_empty()
+            });
+            !values.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_reexport::ReplicateFieldsInternal;
+    use crate::protocol::component::ReplicateFields;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[test]
+    fn test_byte_diff() {
+        let baseline = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+
+        // same length: only the changed bytes are included, short gaps are merged into the run
+        let value = [1, 2, 0, 4, 0, 6, 7, 8, 9, 11];
+        let diff = ByteDiff::new(&baseline, &value);
+        assert_eq!(diff.skips, vec![2, 4]);
+        assert_eq!(diff.runs.len(), 2);
+        assert_eq!(diff.runs[0].len(), 3);
+        assert_eq!(diff.apply(&baseline).unwrap().as_ref(), &value);
+
+        // identical values
+        let diff = ByteDiff::new(&baseline, &baseline);
+        assert!(diff.runs.is_empty());
+        assert_eq!(diff.apply(&baseline).unwrap().as_ref(), &baseline);
+
+        // the value can be longer or shorter than the baseline
+        let value = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+        let diff = ByteDiff::new(&baseline, &value);
+        assert_eq!(diff.apply(&baseline).unwrap().as_ref(), &value);
+        let value = [1, 2, 3];
+        let diff = ByteDiff::new(&baseline, &value);
+        assert_eq!(diff.apply(&baseline).unwrap().as_ref(), &value);
+        // the bytes after the end of the baseline are sent even if they are zero
+        let value = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 0, 0, 0];
+        let diff = ByteDiff::new(&baseline, &value);
+        assert_eq!(diff.runs.last().unwrap().len(), 3);
+        assert_eq!(diff.apply(&baseline).unwrap().as_ref(), &value);
+
+        // invalid diffs are rejected
+        let diff = ByteDiff {
+            len: 2,
+            skips: vec![1],
+            runs: vec![Bytes::from_static(&[1, 1])],
+        };
+        assert!(diff.apply(&baseline).is_none());
+        // a diff cannot make the value grow more than the bytes it contains
+        let diff = ByteDiff {
+            len: u32::MAX,
+            skips: vec![10],
+            runs: vec![Bytes::from_static(&[1, 1])],
+        };
+        assert!(diff.apply(&baseline).is_none());
+    }
+
+    #[test]
+    fn test_delta_compression() {
+        let mut sender = DeltaSender::<MyProtocol>::default();
+        let mut receiver = DeltaReceiver::<MyProtocol>::default();
+        let entity = Entity::from_raw(0);
+        let group_id = ReplicationGroupId(0);
+        let mut inventory = Component5((0..100).collect());
+
+        let send =
+            |sender: &mut DeltaSender<MyProtocol>, tick: Tick, component: MyComponentsProtocol| {
+                let (updates, deltas, delta_tick) = sender.encode_updates(
+                    group_id,
+                    tick,
+                    EntityHashMap::from_iter([(entity, vec![component])]),
+                );
+                assert!(updates.is_empty());
+                EntityUpdatesMessage {
+                    last_action_tick: None,
+                    delta_tick,
+                    updates,
+                    deltas,
+                }
+            };
+
+        // nothing was acked: the full value is sent
+        let mut message = send(
+            &mut sender,
+            Tick(1),
+            MyComponentsProtocol::Component5(inventory.clone()),
+        );
+        assert_eq!(message.delta_tick, Some(Tick(1)));
+        assert!(matches!(message.deltas[0].1[0].value, DeltaValue::Full(_)));
+        sender.track_message(group_id, MessageId(0));
+        receiver.decode_updates(&mut message);
+        assert_eq!(
+            message.updates,
+            vec![(
+                entity,
+                vec![MyComponentsProtocol::Component5(inventory.clone())]
+            )]
+        );
+
+        // the first message is acked: the next update is a diff against it
+        sender.ack(MessageId(0));
+        inventory.0[50] = 1000;
+        let mut message = send(
+            &mut sender,
+            Tick(2),
+            MyComponentsProtocol::Component5(inventory.clone()),
+        );
+        let DeltaValue::Diff {
+            baseline_tick,
+            ref diff,
+        } = message.deltas[0].1[0].value
+        else {
+            panic!("expected a diff");
+        };
+        assert_eq!(baseline_tick, Tick(1));
+        assert!(diff.encoded_len() < 16);
+        sender.track_message(group_id, MessageId(1));
+        receiver.decode_updates(&mut message);
+        assert_eq!(
+            message.updates,
+            vec![(
+                entity,
+                vec![MyComponentsProtocol::Component5(inventory.clone())]
+            )]
+        );
+
+        // the second message is not acked yet: the diff is still computed against the first one
+        inventory.0[10] = 1000;
+        let mut message = send(
+            &mut sender,
+            Tick(3),
+            MyComponentsProtocol::Component5(inventory.clone()),
+        );
+        assert!(matches!(
+            message.deltas[0].1[0].value,
+            DeltaValue::Diff {
+                baseline_tick: Tick(1),
+                ..
+            }
+        ));
+        receiver.decode_updates(&mut message);
+        assert_eq!(
+            message.updates,
+            vec![(
+                entity,
+                vec![MyComponentsProtocol::Component5(inventory.clone())]
+            )]
+        );
+
+        // a second message for the group at the same tick cannot be used as a baseline
+        let message = send(
+            &mut sender,
+            Tick(3),
+            MyComponentsProtocol::Component5(inventory.clone()),
+        );
+        assert_eq!(message.delta_tick, None);
+
+        // after a despawn, the full value is sent again
+        sender.remove_entity(entity);
+        let message = send(
+            &mut sender,
+            Tick(4),
+            MyComponentsProtocol::Component5(inventory.clone()),
+        );
+        assert!(matches!(message.deltas[0].1[0].value, DeltaValue::Full(_)));
+    }
+
+    /// A diff that is received after a diff against a more recent baseline cannot be rebuilt:
+    /// the sender must not use its value as a baseline
+    #[test]
+    fn test_delta_compression_reordered_messages() {
+        let mut sender = DeltaSender::<MyProtocol>::default();
+        let mut receiver = DeltaReceiver::<MyProtocol>::default();
+        let entity = Entity::from_raw(0);
+        let group_id = ReplicationGroupId(0);
+        let mut inventory = Component5((0..100).collect());
+
+        let send = |sender: &mut DeltaSender<MyProtocol>, tick: u16, inventory: &Component5| {
+            let (updates, deltas, delta_tick) = sender.encode_updates(
+                group_id,
+                Tick(tick),
+                EntityHashMap::from_iter([(
+                    entity,
+                    vec![MyComponentsProtocol::Component5(inventory.clone())],
+                )]),
+            );
+            sender.track_message(group_id, MessageId(tick));
+            EntityUpdatesMessage {
+                last_action_tick: None,
+                delta_tick,
+                updates,
+                deltas,
+            }
+        };
+        let mut receive = |message: &mut EntityUpdatesMessage<_, _>| {
+            receiver.decode_updates(message);
+            message.updates.clone()
+        };
+
+        let mut message = send(&mut sender, 1, &inventory);
+        receive(&mut message);
+        sender.ack(MessageId(1));
+
+        // two diffs against the value of tick 1
+        inventory.0[1] = 1000;
+        let mut message_2 = send(&mut sender, 2, &inventory);
+        inventory.0[2] = 1000;
+        let mut message_3 = send(&mut sender, 3, &inventory);
+
+        // the message 2 is received and acked: the next diff uses it as baseline
+        receive(&mut message_2);
+        sender.ack(MessageId(2));
+        inventory.0[4] = 1000;
+        let mut message_4 = send(&mut sender, 4, &inventory);
+        assert!(matches!(
+            message_4.deltas[0].1[0].value,
+            DeltaValue::Diff {
+                baseline_tick: Tick(2),
+                ..
+            }
+        ));
+        assert_eq!(
+            receive(&mut message_4),
+            vec![(
+                entity,
+                vec![MyComponentsProtocol::Component5(inventory.clone())]
+            )]
+        );
+
+        // the message 3 arrives late: its baseline was discarded, so it cannot be rebuilt
+        assert!(receive(&mut message_3).is_empty());
+        sender.ack(MessageId(3));
+
+        // the sender does not use the value of the message 3 as baseline, so the receiver can still rebuild the updates
+        inventory.0[5] = 1000;
+        let mut message_5 = send(&mut sender, 5, &inventory);
+        assert!(matches!(
+            message_5.deltas[0].1[0].value,
+            DeltaValue::Diff {
+                baseline_tick: Tick(2),
+                ..
+            }
+        ));
+        assert_eq!(
+            receive(&mut message_5),
+            vec![(
+                entity,
+                vec![MyComponentsProtocol::Component5(inventory.clone())]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_field_replication() {
+        let mut sender = DeltaSender::<MyProtocol>::default();
+        let mut receiver = DeltaReceiver::<MyProtocol>::default();
+        let entity = Entity::from_raw(0);
+        let group_id = ReplicationGroupId(0);
+        let mut player = Component6 {
+            name: "player".to_string(),
+            values: (0..20).collect(),
+            health: 100,
+        };
+
+        let send = |sender: &mut DeltaSender<MyProtocol>, tick: u16, component: &Component6| {
+            let (updates, deltas, delta_tick) = sender.encode_updates(
+                group_id,
+                Tick(tick),
+                EntityHashMap::from_iter([(
+                    entity,
+                    vec![MyComponentsProtocol::Component6(component.clone())],
+                )]),
+            );
+            sender.track_message(group_id, MessageId(tick));
+            EntityUpdatesMessage {
+                last_action_tick: None,
+                delta_tick,
+                updates,
+                deltas,
+            }
+        };
+        let sent_fields = |message: &EntityUpdatesMessage<_, _>| {
+            let DeltaValue::Fields {
+                baseline_tick,
+                ref fields,
+            } = message.deltas[0].1[0].value
+            else {
+                panic!("expected the changed fields");
+            };
+            let mut component = Component6 {
+                name: String::new(),
+                values: vec![],
+                health: 0,
+            };
+            component
+                .read_fields(&mut ReadWordBuffer::start_read(fields))
+                .unwrap();
+            (baseline_tick, component)
+        };
+
+        // nothing was acked: the full value is sent
+        let mut message = send(&mut sender, 1, &player);
+        assert!(matches!(message.deltas[0].1[0].value, DeltaValue::Full(_)));
+        receiver.decode_updates(&mut message);
+        sender.ack(MessageId(1));
+
+        // only the field that changed is sent
+        player.health = 90;
+        let mut message = send(&mut sender, 2, &player);
+        let (baseline_tick, fields) = sent_fields(&message);
+        assert_eq!(baseline_tick, Tick(1));
+        assert_eq!(
+            fields,
+            Component6 {
+                name: String::new(),
+                values: vec![],
+                health: 90,
+            }
+        );
+        receiver.decode_updates(&mut message);
+        assert_eq!(
+            message.updates,
+            vec![(
+                entity,
+                vec![MyComponentsProtocol::Component6(player.clone())]
+            )]
+        );
+
+        // the message 2 is not acked yet: the update contains all the fields that changed since the message 1
+        player.name = "renamed".to_string();
+        let mut message = send(&mut sender, 3, &player);
+        let (baseline_tick, fields) = sent_fields(&message);
+        assert_eq!(baseline_tick, Tick(1));
+        assert_eq!(
+            fields,
+            Component6 {
+                name: "renamed".to_string(),
+                values: vec![],
+                health: 90,
+            }
+        );
+        receiver.decode_updates(&mut message);
+        assert_eq!(
+            message.updates,
+            vec![(
+                entity,
+                vec![MyComponentsProtocol::Component6(player.clone())]
+            )]
+        );
+
+        // the message 3 is acked: a field that changes back to the value it had before is still sent
+        sender.ack(MessageId(3));
+        player.health = 100;
+        let mut message = send(&mut sender, 4, &player);
+        let (baseline_tick, fields) = sent_fields(&message);
+        assert_eq!(baseline_tick, Tick(3));
+        assert_eq!(fields.health, 100);
+        assert!(fields.name.is_empty());
+        receiver.decode_updates(&mut message);
+        assert_eq!(
+            message.updates,
+            vec![(
+                entity,
+                vec![MyComponentsProtocol::Component6(player.clone())]
+            )]
+        );
+    }
+
+    /// A field that changes in a second message for the group at the same tick is still sent
+    /// in the updates that use the first message as baseline
+    #[test]
+    fn test_field_replication_same_tick() {
+        let mut sender = DeltaSender::<MyProtocol>::default();
+        let mut receiver = DeltaReceiver::<MyProtocol>::default();
+        let entity = Entity::from_raw(0);
+        let group_id = ReplicationGroupId(0);
+        let mut player = Component6 {
+            name: "player".to_string(),
+            values: (0..20).collect(),
+            health: 100,
+        };
+
+        let send =
+            |sender: &mut DeltaSender<MyProtocol>, tick: u16, id: u16, player: &Component6| {
+                let (updates, deltas, delta_tick) = sender.encode_updates(
+                    group_id,
+                    Tick(tick),
+                    EntityHashMap::from_iter([(
+                        entity,
+                        vec![MyComponentsProtocol::Component6(player.clone())],
+                    )]),
+                );
+                sender.track_message(group_id, MessageId(id));
+                EntityUpdatesMessage {
+                    last_action_tick: None,
+                    delta_tick,
+                    updates,
+                    deltas,
+                }
+            };
+
+        let mut message = send(&mut sender, 1, 1, &player);
+        receiver.decode_updates(&mut message);
+        player.health = 90;
+        let mut message = send(&mut sender, 1, 2, &player);
+        assert_eq!(message.delta_tick, None);
+        receiver.decode_updates(&mut message);
+        sender.ack(MessageId(1));
+        sender.ack(MessageId(2));
+
+        let mut message = send(&mut sender, 2, 3, &player);
+        assert!(matches!(
+            message.deltas[0].1[0].value,
+            DeltaValue::Fields {
+                baseline_tick: Tick(1),
+                ..
+            }
+        ));
+        receiver.decode_updates(&mut message);
+        assert_eq!(
+            message.updates,
+            vec![(entity, vec![MyComponentsProtocol::Component6(player)])]
+        );
+    }
+
+    /// Same as [`test_delta_compression_reordered_messages`], with field-level replication
+    #[test]
+    fn test_field_replication_reordered_messages() {
+        let mut sender = DeltaSender::<MyProtocol>::default();
+        let mut receiver = DeltaReceiver::<MyProtocol>::default();
+        let entity = Entity::from_raw(0);
+        let group_id = ReplicationGroupId(0);
+        let mut player = Component6 {
+            name: "player".to_string(),
+            values: (0..20).collect(),
+            health: 100,
+        };
+
+        let send = |sender: &mut DeltaSender<MyProtocol>, tick: u16, player: &Component6| {
+            let (updates, deltas, delta_tick) = sender.encode_updates(
+                group_id,
+                Tick(tick),
+                EntityHashMap::from_iter([(
+                    entity,
+                    vec![MyComponentsProtocol::Component6(player.clone())],
+                )]),
+            );
+            sender.track_message(group_id, MessageId(tick));
+            EntityUpdatesMessage {
+                last_action_tick: None,
+                delta_tick,
+                updates,
+                deltas,
+            }
+        };
+        let mut receive = |message: &mut EntityUpdatesMessage<_, _>| {
+            receiver.decode_updates(message);
+            message.updates.clone()
+        };
+
+        let mut message = send(&mut sender, 1, &player);
+        receive(&mut message);
+        sender.ack(MessageId(1));
+
+        // two updates against the value of tick 1
+        player.health = 90;
+        let mut message_2 = send(&mut sender, 2, &player);
+        player.name = "renamed".to_string();
+        let mut message_3 = send(&mut sender, 3, &player);
+
+        // the message 2 is received and acked: the next update uses it as baseline
+        receive(&mut message_2);
+        sender.ack(MessageId(2));
+        player.health = 80;
+        let mut message_4 = send(&mut sender, 4, &player);
+        assert!(matches!(
+            message_4.deltas[0].1[0].value,
+            DeltaValue::Fields {
+                baseline_tick: Tick(2),
+                ..
+            }
+        ));
+        assert_eq!(
+            receive(&mut message_4),
+            vec![(
+                entity,
+                vec![MyComponentsProtocol::Component6(player.clone())]
+            )]
+        );
+
+        // the message 3 arrives late and cannot be rebuilt, so the sender does not use it as baseline
+        assert!(receive(&mut message_3).is_empty());
+        sender.ack(MessageId(3));
+        player.values.push(20);
+        let mut message_5 = send(&mut sender, 5, &player);
+        assert!(matches!(
+            message_5.deltas[0].1[0].value,
+            DeltaValue::Fields {
+                baseline_tick: Tick(2),
+                ..
+            }
+        ));
+        assert_eq!(
+            receive(&mut message_5),
+            vec![(entity, vec![MyComponentsProtocol::Component6(player)])]
+        );
+    }
+
+    /// The fields marked with `#[serde(skip)]` are not replicated, and keep their local value
+    #[test]
+    fn test_field_replication_serde_skip() {
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ReplicateFieldsInternal)]
+        struct Player {
+            health: u32,
+            #[serde(skip)]
+            local: u32,
+        }
+
+        let sent = Player {
+            health: 50,
+            local: 1,
+        };
+        let mut received = Player {
+            health: 100,
+            local: 2,
+        };
+        assert_eq!(sent.num_fields(), 1);
+        let mut writer = WriteWordBuffer::with_capacity(100);
+        sent.write_fields(&[true], &mut writer).unwrap();
+        let bytes = writer.finish_write().to_vec();
+        received
+            .read_fields(&mut ReadWordBuffer::start_read(&bytes))
+            .unwrap();
+        assert_eq!(
+            received,
+            Player {
+                health: 50,
+                local: 2,
+            }
+        );
+    }
+}
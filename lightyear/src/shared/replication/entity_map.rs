@@ -113,7 +113,7 @@ impl RemoteEntityMap {
         }
     }
 
-    pub(super) fn remove_by_remote(&mut self, remote_entity: Entity) -> Option<Entity> {
+    pub(crate) fn remove_by_remote(&mut self, remote_entity: Entity) -> Option<Entity> {
         let local_entity = self.remote_to_local.remove(&remote_entity);
         if let Some(local_entity) = local_entity {
             self.local_to_remote.remove(&local_entity);
@@ -147,6 +147,15 @@ impl EntityMapper for RemoteEntityMap {
     }
 }
 
+/// Maps the local entities that were replicated from the remote to the remote entities
+pub(crate) struct ToRemoteMapper<'a>(pub(crate) &'a RemoteEntityMap);
+
+impl EntityMapper for ToRemoteMapper<'_> {
+    fn map_entity(&mut self, entity: Entity) -> Entity {
+        self.0.get_remote(entity).copied().unwrap_or(entity)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use bevy::utils::Duration;
@@ -171,6 +180,7 @@ mod tests {
             incoming_latency: Duration::from_millis(0),
             incoming_jitter: Duration::from_millis(0),
             incoming_loss: 0.0,
+            ..Default::default()
         };
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
@@ -16,15 +16,21 @@ use crate::connection::id::ClientId;
 use crate::packet::message::MessageId;
 use crate::prelude::{NetworkTarget, Tick};
 use crate::protocol::{EventContext, Protocol};
-use crate::shared::replication::components::{Replicate, ReplicationGroupId};
+use crate::shared::replication::components::{
+    Replicate, ReplicateRemovePolicy, ReplicationGroupId,
+};
+use crate::shared::replication::delta::ComponentDelta;
 
+pub(crate) mod authority;
 pub mod components;
 
 mod commands;
+pub(crate) mod delta;
 pub mod entity_map;
 pub(crate) mod hierarchy;
 pub(crate) mod plugin;
 pub(crate) mod receive;
+pub(crate) mod resources;
 pub(crate) mod send;
 pub mod systems;
 
@@ -55,6 +61,8 @@ pub struct EntityActions<C, K: Hash + Eq> {
     pub(crate) remove: HashSet<K>,
     // We also include the updates for the current tick in the actions, if there are any
     pub(crate) updates: Vec<C>,
+    // The sender stopped replicating the entity (the `Replicate` component was removed)
+    pub(crate) stop: Option<ReplicateRemovePolicy>,
 }
 
 impl<C, K: Hash + Eq> Default for EntityActions<C, K> {
@@ -65,6 +73,7 @@ impl<C, K: Hash + Eq> Default for EntityActions<C, K> {
             insert: Vec::new(),
             remove: HashSet::new(),
             updates: Vec::new(),
+            stop: None,
         }
     }
 }
@@ -79,12 +88,17 @@ pub struct EntityActionMessage<C, K: Hash + Eq> {
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
-pub struct EntityUpdatesMessage<C> {
+pub struct EntityUpdatesMessage<C, K> {
     /// The last tick for which we sent an EntityActionsMessage for this group
     /// We set this to None after a certain amount of time without any new Actions, to signify on the receiver side
     /// that there is no ordering constraint with respect to Actions for this group (i.e. the Update can be applied immediately)
     last_action_tick: Option<Tick>,
+    /// Tick that identifies the values of the delta-compressed components of this message, so that they
+    /// can be used as baselines for later updates. None if the values cannot be used as baselines.
+    pub(crate) delta_tick: Option<Tick>,
     pub(crate) updates: Vec<(Entity, Vec<C>)>,
+    /// Updates of the delta-compressed components
+    pub(crate) deltas: Vec<(Entity, Vec<ComponentDelta<K>>)>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
@@ -92,7 +106,7 @@ pub enum ReplicationMessageData<C, K: Hash + Eq> {
     /// All the entity actions (Spawn/despawn/inserts/removals) for a given group
     Actions(EntityActionMessage<C, K>),
     /// All the entity updates for a given group
-    Updates(EntityUpdatesMessage<C>),
+    Updates(EntityUpdatesMessage<C, K>),
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
@@ -145,6 +159,16 @@ pub trait ReplicationSend<P: Protocol>: Resource {
         system_current_tick: BevyTick,
     ) -> Result<()>;
 
+    /// The `Replicate` component was removed from an entity that is not despawned: notify the remote
+    /// according to the entity's [`ReplicateRemovePolicy`]
+    fn prepare_replication_stop(
+        &mut self,
+        entity: Entity,
+        replicate: &Replicate<P>,
+        target: NetworkTarget,
+        system_current_tick: BevyTick,
+    ) -> Result<()>;
+
     fn prepare_component_insert(
         &mut self,
         entity: Entity,
@@ -200,6 +224,8 @@ mod tests {
 
     use crate::prelude::client::*;
     use crate::prelude::*;
+    use crate::serialize::wordbuffer::writer::WriteWordBuffer;
+    use crate::serialize::writer::WriteBuffer;
     use crate::tests::protocol::*;
     use crate::tests::stepper::{BevyStepper, Step};
 
@@ -218,6 +244,7 @@ mod tests {
             incoming_latency: Duration::from_millis(0),
             incoming_jitter: Duration::from_millis(0),
             incoming_loss: 0.0,
+            ..Default::default()
         };
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
@@ -279,4 +306,177 @@ mod tests {
             .is_none());
         Ok(())
     }
+
+    // Components with a custom encoding are serialized with fewer bits, and are still replicated
+    #[test]
+    fn test_replication_with_encoding() {
+        let num_bits = |component: MyComponentsProtocol| {
+            let mut writer = WriteWordBuffer::with_capacity(100);
+            writer.serialize(&component).unwrap();
+            writer.num_bits_written()
+        };
+        // 7 bits for the variant index, then 16 bits for each quantized float
+        assert_eq!(
+            num_bits(MyComponentsProtocol::Component7(Component7 {
+                x: -50.0,
+                y: 100.0
+            })),
+            7 + 2 * 16
+        );
+        // 7 bits for the variant index, then 19 bits for the gamma-encoded value
+        assert_eq!(
+            num_bits(MyComponentsProtocol::Component8(Component8(1000))),
+            7 + 19
+        );
+
+        let mut stepper = BevyStepper::default();
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((
+                Component7 { x: -12.345, y: 0.0 },
+                Component8(1000),
+                Replicate::default(),
+            ))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+        let component7 = stepper
+            .client_app
+            .world
+            .get::<Component7>(client_entity)
+            .unwrap();
+        // quantized values are rounded to the nearest step
+        assert!((component7.x + 12.345).abs() < 0.01);
+        assert!(component7.y.abs() < 0.01);
+        assert_eq!(
+            stepper.client_app.world.get::<Component8>(client_entity),
+            Some(&Component8(1000))
+        );
+    }
+
+    // The Replicate component gets removed on the server (without despawning the entity),
+    // the client entity is handled according to the ReplicateRemovePolicy
+    #[test]
+    fn test_replicate_remove_policy() {
+        let mut stepper = BevyStepper::default();
+
+        let spawn = |stepper: &mut BevyStepper, remove_policy: ReplicateRemovePolicy| {
+            stepper
+                .server_app
+                .world
+                .spawn((
+                    Component1(0.0),
+                    Replicate {
+                        prediction_target: NetworkTarget::All,
+                        remove_policy,
+                        ..Default::default()
+                    },
+                ))
+                .id()
+        };
+        let server_despawn = spawn(&mut stepper, ReplicateRemovePolicy::Despawn);
+        let server_detach = spawn(&mut stepper, ReplicateRemovePolicy::Detach);
+        let server_freeze = spawn(&mut stepper, ReplicateRemovePolicy::Freeze);
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity = |stepper: &BevyStepper, server_entity| {
+            stepper
+                .client_app
+                .world
+                .resource::<ClientConnectionManager>()
+                .replication_receiver
+                .remote_entity_map
+                .get_local(server_entity)
+                .copied()
+        };
+        let client_despawn = client_entity(&stepper, server_despawn).unwrap();
+        let client_detach = client_entity(&stepper, server_detach).unwrap();
+        let client_freeze = client_entity(&stepper, server_freeze).unwrap();
+        let predicted_detach = stepper
+            .client_app
+            .world
+            .get::<Confirmed>(client_detach)
+            .unwrap()
+            .predicted
+            .unwrap();
+
+        // stop replicating the entities, and update the frozen entity
+        for entity in [server_despawn, server_detach, server_freeze] {
+            stepper
+                .server_app
+                .world
+                .entity_mut(entity)
+                .remove::<Replicate>();
+        }
+        stepper.frame_step();
+        stepper
+            .server_app
+            .world
+            .entity_mut(server_freeze)
+            .insert(Component1(1.0));
+        stepper.frame_step();
+        stepper.frame_step();
+
+        // despawn: the client entity is despawned
+        assert!(stepper
+            .client_app
+            .world
+            .get_entity(client_despawn)
+            .is_none());
+        // detach: the client entity is not a confirmed entity anymore
+        assert!(client_entity(&stepper, server_detach).is_none());
+        assert!(stepper
+            .client_app
+            .world
+            .get::<Confirmed>(client_detach)
+            .is_none());
+        assert!(stepper
+            .client_app
+            .world
+            .get_entity(predicted_detach)
+            .is_none());
+        assert_eq!(
+            stepper.client_app.world.get::<Component1>(client_detach),
+            Some(&Component1(0.0))
+        );
+        // freeze: the client entity is still confirmed, but doesn't receive updates
+        assert_eq!(client_entity(&stepper, server_freeze), Some(client_freeze));
+        assert!(stepper
+            .client_app
+            .world
+            .get::<Confirmed>(client_freeze)
+            .is_some());
+        assert_eq!(
+            stepper.client_app.world.get::<Component1>(client_freeze),
+            Some(&Component1(0.0))
+        );
+
+        // an event is emitted in every case
+        let events = stepper
+            .client_app
+            .world
+            .resource::<bevy::prelude::Events<ReplicationStopEvent>>();
+        let mut stops = events
+            .get_reader()
+            .read(events)
+            .map(|event| (event.entity(), event.policy()))
+            .collect::<Vec<_>>();
+        stops.sort_by_key(|(entity, _)| *entity);
+        let mut expected = vec![
+            (client_despawn, ReplicateRemovePolicy::Despawn),
+            (client_detach, ReplicateRemovePolicy::Detach),
+            (client_freeze, ReplicateRemovePolicy::Freeze),
+        ];
+        expected.sort_by_key(|(entity, _)| *entity);
+        assert_eq!(stops, expected);
+    }
 }
@@ -4,8 +4,8 @@ use bevy::utils::Duration;
 
 use crate::_reexport::{ComponentProtocol, ReplicationSend, ShouldBeInterpolated};
 use crate::prelude::{
-    NetworkTarget, PrePredicted, Protocol, RemoteEntityMap, ReplicationGroup, ReplicationMode,
-    ShouldBePredicted,
+    HasAuthority, NetworkTarget, PrePredicted, Protocol, RemoteEntityMap, ReplicationGroup,
+    ReplicationMode, ShouldBePredicted,
 };
 use crate::shared::replication::components::{
     PerComponentReplicationMetadata, Replicate, ReplicationGroupId, ReplicationGroupIdBuilder,
@@ -51,6 +51,7 @@ impl<P: Protocol, R: ReplicationSend<P>> Plugin for ReplicationPlugin<P, R> {
             .register_type::<ShouldBeInterpolated>()
             .register_type::<PrePredicted>()
             .register_type::<ShouldBePredicted>()
+            .register_type::<HasAuthority>()
             .register_type::<RemoteEntityMap>()
             .register_type::<PredictedEntityMap>()
             .register_type::<InterpolatedEntityMap>();
@@ -63,6 +64,7 @@ impl<P: Protocol, R: ReplicationSend<P>> Plugin for ReplicationPlugin<P, R> {
             );
             // PLUGINS
             app.add_plugins(HierarchyReceivePlugin::<P, R>::default());
+            P::Components::add_resource_receive_systems::<R>(app);
         }
         if self.enable_send {
             app.configure_sets(
@@ -103,6 +105,7 @@ impl<P: Protocol, R: ReplicationSend<P>> Plugin for ReplicationPlugin<P, R> {
             app.add_systems(Last, cleanup::<P, R>.run_if(on_timer(clean_interval)));
             // PLUGINS
             app.add_plugins(HierarchySendPlugin::<P, R>::default());
+            P::Components::add_resource_send_systems::<R>(app);
         }
     }
 }
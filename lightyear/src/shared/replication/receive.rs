@@ -16,7 +16,10 @@ use crate::protocol::component::ComponentProtocol;
 use crate::protocol::component::{ComponentBehaviour, ComponentKindBehaviour};
 use crate::protocol::Protocol;
 use crate::shared::events::connection::ConnectionEvents;
-use crate::shared::replication::components::ReplicationGroupId;
+use crate::shared::replication::components::{
+    ReplicateRemovePolicy, ReplicationGroupId, ShouldBeInterpolated, ShouldBePredicted,
+};
+use crate::shared::replication::delta::DeltaReceiver;
 
 use super::entity_map::RemoteEntityMap;
 use super::{
@@ -37,6 +40,10 @@ pub(crate) struct ReplicationReceiver<P: Protocol> {
     // BOTH
     /// Buffer to so that we have an ordered receiver per group
     pub group_channels: EntityHashMap<ReplicationGroupId, GroupChannel<P>>,
+
+    // DELTA COMPRESSION
+    /// Values of the delta-compressed components that were received, used to rebuild the next updates
+    pub delta: DeltaReceiver<P>,
 }
 
 impl<P: Protocol> ReplicationReceiver<P> {
@@ -47,6 +54,8 @@ impl<P: Protocol> ReplicationReceiver<P> {
             remote_entity_to_group: Default::default(),
             // BOTH
             group_channels: Default::default(),
+            // DELTA COMPRESSION
+            delta: DeltaReceiver::default(),
         }
     }
 
@@ -79,7 +88,10 @@ impl<P: Protocol> ReplicationReceiver<P> {
                     .actions_recv_message_buffer
                     .insert(m.sequence_id, (remote_tick, m));
             }
-            ReplicationMessageData::Updates(m) => {
+            ReplicationMessageData::Updates(mut m) => {
+                // rebuild the delta-compressed components first: even if the message is too old to be applied,
+                // the sender might use its values as baselines
+                self.delta.decode_updates(&mut m);
                 // NOTE: this is valid instead after tick wrapping because we keep clamping the latest_tick values
                 //  for each channel
                 // if we have already applied a more recent update for this group, no need to keep this one
@@ -245,7 +257,11 @@ impl<P: Protocol> ReplicationReceiver<P> {
                                 entity_mut.despawn_recursive();
                             }
                             events.push_despawn(local_entity);
+                            if let Some(policy) = actions.stop {
+                                events.push_replication_stop(local_entity, policy);
+                            }
                             self.remote_entity_to_group.remove(&entity);
+                            self.delta.remove_entity(entity, tick);
                         } else {
                             error!("Received despawn for an entity that does not exist")
                         }
@@ -314,6 +330,23 @@ impl<P: Protocol> ReplicationReceiver<P> {
                         );
                         component.update(&mut local_entity_mut);
                     }
+
+                    // the sender removed the `Replicate` component of the entity
+                    if let Some(policy) = actions.stop {
+                        debug!(remote_entity = ?entity, ?policy, "Received replication stop");
+                        if policy == ReplicateRemovePolicy::Detach {
+                            // the entity is not a confirmed entity anymore, which despawns its predicted/interpolated entities
+                            local_entity_mut
+                                .remove::<(Confirmed, ShouldBePredicted, ShouldBeInterpolated)>();
+                            self.remote_entity_map.remove_by_remote(entity);
+                            if let Some(group) = self.group_channels.get_mut(&group_id) {
+                                group.remote_entities.remove(&entity);
+                            }
+                            self.remote_entity_to_group.remove(&entity);
+                            self.delta.remove_entity(entity, tick);
+                        }
+                        events.push_replication_stop(local_entity_mut.id(), policy);
+                    }
                 }
             }
             ReplicationMessageData::Updates(m) => {
@@ -380,10 +413,10 @@ pub struct GroupChannel<P: Protocol> {
     // the first tick is the last_action_tick (we can only apply the update if the last action tick has been reached)
     // the second tick is the update's server tick when it was sent
     pub buffered_updates_with_last_action_tick:
-        BTreeMap<Tick, BTreeMap<Tick, EntityUpdatesMessage<P::Components>>>,
+        BTreeMap<Tick, BTreeMap<Tick, EntityUpdatesMessage<P::Components, P::ComponentKinds>>>,
     // updates for which there is no condition on the last_action_tick: we can apply them immediately
     pub buffered_updates_without_last_action_tick:
-        BTreeMap<Tick, EntityUpdatesMessage<P::Components>>,
+        BTreeMap<Tick, EntityUpdatesMessage<P::Components, P::ComponentKinds>>,
     /// remote tick of the latest update/action that we applied to the local group
     pub latest_tick: Option<Tick>,
 }
@@ -434,7 +467,9 @@ impl<P: Protocol> GroupChannel<P> {
         Some(message)
     }
 
-    fn read_buffered_updates(&mut self) -> Vec<(Tick, EntityUpdatesMessage<P::Components>)> {
+    fn read_buffered_updates(
+        &mut self,
+    ) -> Vec<(Tick, EntityUpdatesMessage<P::Components, P::ComponentKinds>)> {
         // if we haven't applied any actions (latest_tick is None) we cannot apply any updates
         let Some(latest_tick) = self.latest_tick else {
             return vec![];
@@ -562,7 +597,9 @@ mod tests {
                 group_id: ReplicationGroupId(0),
                 data: ReplicationMessageData::Updates(EntityUpdatesMessage {
                     last_action_tick: Some(Tick(0)),
+                    delta_tick: None,
                     updates: Default::default(),
+                    deltas: Default::default(),
                 }),
             },
             Tick(1),
@@ -583,7 +620,9 @@ mod tests {
                 group_id: ReplicationGroupId(0),
                 data: ReplicationMessageData::Updates(EntityUpdatesMessage {
                     last_action_tick: Some(Tick(2)),
+                    delta_tick: None,
                     updates: Default::default(),
+                    deltas: Default::default(),
                 }),
             },
             Tick(4),
@@ -0,0 +1,426 @@
+//! This module is responsible for replicating bevy [`Resource`]s.
+//!
+//! A resource is replicated by copying it to a component on a dedicated entity, which is then replicated like any
+//! other entity (through the `EntityActionsChannel` and the `EntityUpdatesChannel`).
+//! The entity is tagged with the [`ReplicatedResource`] component, and on the receiving side the component of the
+//! tagged entity is copied back to the resource.
+//!
+//! The resource type must also be a [`Component`], and its variant in the `ComponentProtocol` must be marked with
+//! `#[protocol(resource)]`. The `sync` attribute of the variant is used for the entity like for any other component,
+//! so the resource can be predicted or interpolated on the client.
+use std::marker::PhantomData;
+
+use bevy::ecs::component::Tick;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::client::components::Confirmed;
+use crate::prelude::NetworkTarget;
+use crate::protocol::Protocol;
+use crate::shared::replication::components::Replicate;
+use crate::shared::replication::ReplicationSend;
+use crate::shared::sets::{InternalMainSet, InternalReplicationSet};
+
+/// Insert this resource to replicate the resource `C` to the remote world.
+///
+/// Removing this resource despawns the resource on the remote.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct ReplicateResource<C> {
+    /// Which clients should receive the resource
+    pub replication_target: NetworkTarget,
+    /// Which clients should use the predicted value of the resource
+    pub prediction_target: NetworkTarget,
+    /// Which clients should use the interpolated value of the resource
+    pub interpolation_target: NetworkTarget,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for ReplicateResource<C> {
+    fn default() -> Self {
+        Self::new(NetworkTarget::All)
+    }
+}
+
+impl<C> ReplicateResource<C> {
+    pub fn new(replication_target: NetworkTarget) -> Self {
+        Self {
+            replication_target,
+            prediction_target: NetworkTarget::None,
+            interpolation_target: NetworkTarget::None,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn with_prediction_target(mut self, prediction_target: NetworkTarget) -> Self {
+        self.prediction_target = prediction_target;
+        self
+    }
+
+    pub fn with_interpolation_target(mut self, interpolation_target: NetworkTarget) -> Self {
+        self.interpolation_target = interpolation_target;
+        self
+    }
+}
+
+/// Marker component for the entities that carry a replicated resource
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Reflect)]
+pub struct ReplicatedResource;
+
+/// Event emitted on the receiving side when a replicated resource is inserted
+#[derive(Event, Debug)]
+pub struct ResourceInsertEvent<C> {
+    value: C,
+}
+
+impl<C> ResourceInsertEvent<C> {
+    pub fn new(value: C) -> Self {
+        Self { value }
+    }
+    /// Value of the resource that was inserted
+    pub fn value(&self) -> &C {
+        &self.value
+    }
+}
+
+/// Event emitted on the receiving side when a replicated resource is updated
+#[derive(Event, Debug)]
+pub struct ResourceUpdateEvent<C> {
+    value: C,
+}
+
+impl<C> ResourceUpdateEvent<C> {
+    pub fn new(value: C) -> Self {
+        Self { value }
+    }
+    /// New value of the resource
+    pub fn value(&self) -> &C {
+        &self.value
+    }
+}
+
+/// Event emitted on the receiving side when a replicated resource is removed
+#[derive(Event, Debug)]
+pub struct ResourceRemoveEvent<C> {
+    value: C,
+}
+
+impl<C> ResourceRemoveEvent<C> {
+    pub fn new(value: C) -> Self {
+        Self { value }
+    }
+    /// Last value of the resource before it was removed
+    pub fn value(&self) -> &C {
+        &self.value
+    }
+}
+
+pub struct ResourceSendPlugin<C, P, R> {
+    _marker: PhantomData<(C, P, R)>,
+}
+
+impl<C, P, R> Default for ResourceSendPlugin<C, P, R> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// The entity that carries the resource on the sending side
+#[derive(Default)]
+struct ResourceEntity {
+    entity: Option<Entity>,
+    has_resource: bool,
+}
+
+impl<C: Resource + Component + Clone, P: Protocol, R: ReplicationSend<P>>
+    ResourceSendPlugin<C, P, R>
+{
+    /// Copy the resource to the component of the replicated entity
+    ///
+    /// This only runs on the sending side
+    fn update_resource_entity(
+        mut commands: Commands,
+        mut state: Local<ResourceEntity>,
+        replicate: Option<Res<ReplicateResource<C>>>,
+        resource: Option<Res<C>>,
+    ) {
+        let Some(replicate) = replicate else {
+            // the resource is not replicated anymore: the despawn will remove it on the remote
+            if let Some(entity) = state.entity.take() {
+                trace!("Despawning the entity of a resource that is not replicated anymore");
+                commands.entity(entity).despawn();
+            }
+            state.has_resource = false;
+            return;
+        };
+        let entity = match state.entity {
+            Some(entity) => entity,
+            None => *state.entity.insert(commands.spawn_empty().id()),
+        };
+        if replicate.is_changed() {
+            commands.entity(entity).insert((
+                ReplicatedResource,
+                Replicate::<P> {
+                    replication_target: replicate.replication_target.clone(),
+                    prediction_target: replicate.prediction_target.clone(),
+                    interpolation_target: replicate.interpolation_target.clone(),
+                    replicate_hierarchy: false,
+                    ..default()
+                },
+            ));
+        }
+        match resource {
+            Some(resource) => {
+                if resource.is_changed() || !state.has_resource {
+                    commands.entity(entity).insert(C::clone(&resource));
+                    state.has_resource = true;
+                }
+            }
+            None => {
+                if state.has_resource {
+                    commands.entity(entity).remove::<C>();
+                    state.has_resource = false;
+                }
+            }
+        }
+    }
+}
+
+impl<C: Resource + Component + Clone, P: Protocol, R: ReplicationSend<P>> Plugin
+    for ResourceSendPlugin<C, P, R>
+{
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            Self::update_resource_entity
+                // we don't need to run these every frame, only every send_interval
+                .in_set(InternalMainSet::<R::SetMarker>::Send)
+                .before(InternalReplicationSet::<R::SetMarker>::All),
+        );
+    }
+}
+
+pub struct ResourceReceivePlugin<C, P, R> {
+    _marker: PhantomData<(C, P, R)>,
+}
+
+impl<C, P, R> Default for ResourceReceivePlugin<C, P, R> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Whether the resource was inserted by replication, so that we only remove it if it was
+#[derive(Resource)]
+struct ReceivedResource<C> {
+    received: bool,
+    /// Change tick of the value that was last copied to the resource, so that a change is only applied once
+    /// even though the resource is updated twice per frame
+    last_changed: Tick,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for ReceivedResource<C> {
+    fn default() -> Self {
+        Self {
+            received: false,
+            last_changed: Tick::new(0),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Writers of the events emitted when the replicated resource is inserted, updated or removed
+#[derive(SystemParam)]
+struct ResourceEventWriters<'w, C: Send + Sync + 'static> {
+    insert: EventWriter<'w, ResourceInsertEvent<C>>,
+    update: EventWriter<'w, ResourceUpdateEvent<C>>,
+    remove: EventWriter<'w, ResourceRemoveEvent<C>>,
+}
+
+impl<C: Resource + Component + Clone, P: Protocol, R: ReplicationSend<P>>
+    ResourceReceivePlugin<C, P, R>
+{
+    /// Copy the component of the entity that carries the resource to the resource.
+    ///
+    /// If the entity is predicted or interpolated, we use the value of the predicted or interpolated entity.
+    ///
+    /// This only runs on the receiving side
+    fn update_resource(
+        mut commands: Commands,
+        mut received: ResMut<ReceivedResource<C>>,
+        resource: Option<ResMut<C>>,
+        carriers: Query<
+            (Entity, Option<&Confirmed>),
+            (With<ReplicatedResource>, With<C>, Without<Replicate<P>>),
+        >,
+        values: Query<Ref<C>>,
+        mut events: ResourceEventWriters<C>,
+    ) {
+        let value = carriers.iter().next().and_then(|(entity, confirmed)| {
+            let synced = confirmed
+                .and_then(|confirmed| confirmed.predicted.or(confirmed.interpolated))
+                .and_then(|entity| values.get(entity).ok());
+            synced.or_else(|| values.get(entity).ok())
+        });
+        match (value, resource) {
+            (Some(value), None) => {
+                trace!("Inserting replicated resource");
+                commands.insert_resource(C::clone(&value));
+                received.received = true;
+                received.last_changed = value.last_changed();
+                events
+                    .insert
+                    .send(ResourceInsertEvent::new(C::clone(&value)));
+            }
+            (Some(value), Some(mut resource)) => {
+                if value.last_changed() != received.last_changed || !received.received {
+                    *resource = C::clone(&value);
+                    received.received = true;
+                    received.last_changed = value.last_changed();
+                    events
+                        .update
+                        .send(ResourceUpdateEvent::new(C::clone(&value)));
+                }
+            }
+            (None, Some(resource)) => {
+                if received.received {
+                    trace!("Removing replicated resource");
+                    commands.remove_resource::<C>();
+                    received.received = false;
+                    events
+                        .remove
+                        .send(ResourceRemoveEvent::new(C::clone(&resource)));
+                }
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+impl<C: Resource + Component + Clone, P: Protocol, R: ReplicationSend<P>> Plugin
+    for ResourceReceivePlugin<C, P, R>
+{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReceivedResource<C>>()
+            .add_event::<ResourceInsertEvent<C>>()
+            .add_event::<ResourceUpdateEvent<C>>()
+            .add_event::<ResourceRemoveEvent<C>>();
+        // update the resource right after receiving the replication messages, and again at the end of the frame
+        // to account for the predicted and interpolated values that are computed during the frame
+        app.add_systems(
+            PreUpdate,
+            Self::update_resource.after(InternalMainSet::<R::SetMarker>::Receive),
+        );
+        app.add_systems(
+            PostUpdate,
+            Self::update_resource.before(InternalMainSet::<R::SetMarker>::Send),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Events;
+
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    use super::*;
+
+    fn drain_events<E: Event>(stepper: &mut BevyStepper) -> Vec<E> {
+        stepper
+            .client_app
+            .world
+            .resource_mut::<Events<E>>()
+            .drain()
+            .collect()
+    }
+
+    #[test]
+    fn test_resource_replication() {
+        let mut stepper = BevyStepper::default();
+
+        // an entity of the client that has the same component is not used as the resource
+        stepper.client_app.world.spawn(Resource1(10.0));
+
+        // insert the resource on the server, and replicate it
+        stepper.server_app.world.insert_resource(Resource1(1.0));
+        stepper
+            .server_app
+            .world
+            .insert_resource(ReplicateResource::<Resource1>::default());
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(
+            stepper.client_app.world.get_resource::<Resource1>(),
+            Some(&Resource1(1.0))
+        );
+        let events = drain_events::<ResourceInsertEvent<Resource1>>(&mut stepper);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].value(), &Resource1(1.0));
+        assert!(drain_events::<ResourceUpdateEvent<Resource1>>(&mut stepper).is_empty());
+
+        // the server sends the value in the updates of the entity until one of them is acked
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        drain_events::<ResourceUpdateEvent<Resource1>>(&mut stepper);
+
+        // update the resource
+        stepper.server_app.world.resource_mut::<Resource1>().0 = 2.0;
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(
+            stepper.client_app.world.get_resource::<Resource1>(),
+            Some(&Resource1(2.0))
+        );
+        let events = drain_events::<ResourceUpdateEvent<Resource1>>(&mut stepper);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].value(), &Resource1(2.0));
+
+        // remove the resource
+        stepper.server_app.world.remove_resource::<Resource1>();
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(stepper
+            .client_app
+            .world
+            .get_resource::<Resource1>()
+            .is_none());
+        let events = drain_events::<ResourceRemoveEvent<Resource1>>(&mut stepper);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].value(), &Resource1(2.0));
+
+        // insert it again, then stop replicating it
+        stepper.server_app.world.insert_resource(Resource1(3.0));
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(
+            stepper.client_app.world.get_resource::<Resource1>(),
+            Some(&Resource1(3.0))
+        );
+        assert_eq!(
+            drain_events::<ResourceInsertEvent<Resource1>>(&mut stepper).len(),
+            1
+        );
+        stepper
+            .server_app
+            .world
+            .remove_resource::<ReplicateResource<Resource1>>();
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(stepper
+            .client_app
+            .world
+            .get_resource::<Resource1>()
+            .is_none());
+        let events = drain_events::<ResourceRemoveEvent<Resource1>>(&mut stepper);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].value(), &Resource1(3.0));
+    }
+}
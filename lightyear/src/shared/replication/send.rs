@@ -17,7 +17,10 @@ use crate::protocol::channel::ChannelKind;
 use crate::protocol::component::ComponentProtocol;
 use crate::protocol::component::{ComponentBehaviour, ComponentKindBehaviour};
 use crate::protocol::Protocol;
-use crate::shared::replication::components::{Replicate, ReplicationGroupId};
+use crate::shared::replication::components::{
+    Replicate, ReplicateRemovePolicy, ReplicationGroupId,
+};
+use crate::shared::replication::delta::DeltaSender;
 
 use super::{EntityActionMessage, EntityActions, EntityUpdatesMessage, ReplicationMessageData};
 
@@ -57,6 +60,10 @@ pub(crate) struct ReplicationSender<P: Protocol> {
     /// Get notified whenever a message for a given ReplicationGroup was actually sent
     /// (sometimes they might not be sent because of bandwidth constraints
     pub message_send_receiver: Receiver<MessageId>,
+
+    // DELTA COMPRESSION
+    /// Values of the delta-compressed components that were sent/acked, used as baselines for the next updates
+    pub delta: DeltaSender<P>,
 }
 
 impl<P: Protocol> ReplicationSender<P> {
@@ -75,6 +82,8 @@ impl<P: Protocol> ReplicationSender<P> {
             group_channels: Default::default(),
             // PRIORITY
             message_send_receiver,
+            // DELTA COMPRESSION
+            delta: DeltaSender::default(),
         }
     }
 
@@ -124,6 +133,7 @@ impl<P: Protocol> ReplicationSender<P> {
             if let Some((group_id, bevy_tick)) =
                 self.updates_message_id_to_group_id.remove(&message_id)
             {
+                self.delta.ack(message_id);
                 if let Some(channel) = self.group_channels.get_mut(&group_id) {
                     channel.update_collect_changes_since_this_tick(bevy_tick)
                 } else {
@@ -175,6 +185,26 @@ impl<P: Protocol> ReplicationSender<P> {
             .entry(entity)
             .or_default()
             .despawn = true;
+        // the remote won't have the entity anymore, so its values can't be used as baselines
+        self.delta.remove_entity(entity);
+    }
+
+    /// Host has removed the `Replicate` component from an entity, and we want to notify the remote
+    /// (a `Despawn` policy also needs the entity despawn to be prepared)
+    pub(crate) fn prepare_replication_stop(
+        &mut self,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+        policy: ReplicateRemovePolicy,
+    ) {
+        self.pending_actions
+            .entry(group_id)
+            .or_default()
+            .entry(entity)
+            .or_default()
+            .stop = Some(policy);
+        // we won't send updates for this entity anymore
+        self.delta.remove_entity(entity);
     }
 
     // we want to send all component inserts that happen together for the same entity in a single message
@@ -339,14 +369,16 @@ impl<P: Protocol> ReplicationSender<P> {
             let priority = channel
                 .accumulated_priority
                 .unwrap_or(channel.base_priority);
+            let (updates, deltas, delta_tick) = self.delta.encode_updates(group_id, tick, updates);
             messages.push((
                 ChannelKind::of::<EntityUpdatesChannel>(),
                 group_id,
                 ReplicationMessageData::Updates(EntityUpdatesMessage {
                     // SAFETY: the last action tick is always set because we send Actions before Updates
                     last_action_tick: channel.last_action_tick,
-                    // TODO: maybe we can just send the HashMap directly?
-                    updates: Vec::from_iter(updates.into_iter()),
+                    delta_tick,
+                    updates,
+                    deltas,
                 }),
                 priority,
             ));
@@ -490,6 +522,7 @@ mod tests {
                         insert: vec![MyComponentsProtocol::Component1(Component1(1.0))],
                         remove: HashSet::from_iter(vec![MyComponentsProtocolKind::Component2]),
                         updates: vec![MyComponentsProtocol::Component3(Component3(3.0))],
+                        stop: None,
                     }
                 ),
                 (
@@ -500,6 +533,7 @@ mod tests {
                         insert: vec![],
                         remove: HashSet::default(),
                         updates: vec![MyComponentsProtocol::Component2(Component2(4.0))],
+                        stop: None,
                     }
                 )
             ])
@@ -513,10 +547,12 @@ mod tests {
                 group_2,
                 ReplicationMessageData::Updates(EntityUpdatesMessage {
                     last_action_tick: Some(Tick(3)),
+                    delta_tick: None,
                     updates: vec![(
                         entity_3,
                         vec![MyComponentsProtocol::Component3(Component3(5.0))]
                     )],
+                    deltas: vec![],
                 }),
                 1.0
             )
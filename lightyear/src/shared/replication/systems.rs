@@ -22,8 +22,10 @@ use crate::shared::sets::{InternalMainSet, InternalReplicationSet};
 // TODO: run these systems only if there is at least 1 remote connected!!! (so we don't burn CPU when there are no connections)
 
 /// For every entity that removes their Replicate component but are not despawned, remove the component
-/// from our replicate cache (so that the entity's despawns are no longer replicated)
+/// from our replicate cache (so that the entity's despawns are no longer replicated), and notify the remote
+/// according to the entity's [`ReplicateRemovePolicy`](crate::prelude::ReplicateRemovePolicy)
 fn handle_replicate_remove<P: Protocol, R: ReplicationSend<P>>(
+    system_bevy_ticks: SystemChangeTick,
     mut sender: ResMut<R>,
     mut query: RemovedComponents<Replicate<P>>,
     entity_check: &Entities,
@@ -31,7 +33,18 @@ fn handle_replicate_remove<P: Protocol, R: ReplicationSend<P>>(
     for entity in query.read() {
         if entity_check.contains(entity) {
             debug!("handling replicate component remove (delete from cache)");
-            sender.get_mut_replicate_component_cache().remove(&entity);
+            if let Some(replicate) = sender.get_mut_replicate_component_cache().remove(&entity) {
+                let _ = sender
+                    .prepare_replication_stop(
+                        entity,
+                        &replicate,
+                        replicate.replication_target.clone(),
+                        system_bevy_ticks.this_run(),
+                    )
+                    .map_err(|e| {
+                        error!("error sending replication stop: {:?}", e);
+                    });
+            }
         }
     }
 }
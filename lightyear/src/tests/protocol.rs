@@ -1,5 +1,5 @@
 use bevy::ecs::entity::MapEntities;
-use bevy::prelude::{default, Component, Entity, EntityMapper, Reflect};
+use bevy::prelude::{default, Component, Entity, EntityMapper, Reflect, Resource};
 use cfg_if::cfg_if;
 use derive_more::{Add, Mul};
 use std::ops::Mul;
@@ -48,6 +48,30 @@ impl MapEntities for Component4 {
     }
 }
 
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Reflect)]
+pub struct Component5(pub Vec<u32>);
+
+#[derive(
+    Component, Serialize, Deserialize, Clone, Debug, PartialEq, Reflect, ReplicateFieldsInternal,
+)]
+pub struct Component6 {
+    pub name: String,
+    pub values: Vec<u32>,
+    pub health: u32,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Reflect)]
+pub struct Component7 {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Reflect)]
+pub struct Component8(pub u32);
+
+#[derive(Component, Resource, Serialize, Deserialize, Clone, Debug, PartialEq, Reflect)]
+pub struct Resource1(pub f32);
+
 #[component_protocol_internal(protocol = "MyProtocol")]
 pub enum MyComponentsProtocol {
     #[protocol(sync(mode = "full"))]
@@ -58,6 +82,16 @@ pub enum MyComponentsProtocol {
     Component3(Component3),
     #[protocol(sync(mode = "simple"), map_entities)]
     Component4(Component4),
+    #[protocol(delta)]
+    Component5(Component5),
+    #[protocol(fields)]
+    Component6(Component6),
+    #[protocol(resource)]
+    Resource1(Resource1),
+    #[protocol(quantize(min = -100.0, max = 100.0, bits = 16))]
+    Component7(Component7),
+    #[protocol(encoding = "gamma")]
+    Component8(Component8),
 }
 
 // Inputs
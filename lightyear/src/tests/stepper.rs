@@ -12,7 +12,8 @@ use tracing_subscriber::fmt::format::FmtSpan;
 use crate::connection::netcode::generate_key;
 use crate::connection::server::{NetServer, ServerConnection, ServerConnections};
 use crate::prelude::client::{
-    Authentication, ClientConfig, InputConfig, InterpolationConfig, PredictionConfig, SyncConfig,
+    Authentication, ClientConfig, InputConfig, InterpolationConfig, PredictionConfig,
+    ReplicationConfig, SyncConfig,
 };
 use crate::prelude::server::{NetcodeConfig, ServerConfig};
 use crate::prelude::*;
@@ -38,6 +39,13 @@ pub struct BevyStepper {
 
 impl Default for BevyStepper {
     fn default() -> Self {
+        Self::default_with_client_replication(ReplicationConfig::default())
+    }
+}
+
+impl BevyStepper {
+    /// Same as the default stepper, with the given replication config for the client
+    pub(crate) fn default_with_client_replication(replication: ReplicationConfig) -> Self {
         let frame_duration = Duration::from_millis(10);
         let tick_duration = Duration::from_millis(10);
         let shared_config = SharedConfig {
@@ -48,17 +56,19 @@ impl Default for BevyStepper {
             incoming_latency: Duration::from_millis(0),
             incoming_jitter: Duration::from_millis(0),
             incoming_loss: 0.0,
+            ..Default::default()
         };
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
         let interpolation_config = InterpolationConfig::default();
-        let mut stepper = Self::new(
+        let mut stepper = Self::new_with_client_replication(
             shared_config,
             sync_config,
             prediction_config,
             interpolation_config,
             link_conditioner,
             frame_duration,
+            replication,
         );
         stepper.init();
         stepper
@@ -74,6 +84,26 @@ impl BevyStepper {
         interpolation_config: InterpolationConfig,
         conditioner: LinkConditionerConfig,
         frame_duration: Duration,
+    ) -> Self {
+        Self::new_with_client_replication(
+            shared_config,
+            sync_config,
+            prediction_config,
+            interpolation_config,
+            conditioner,
+            frame_duration,
+            ReplicationConfig::default(),
+        )
+    }
+
+    pub fn new_with_client_replication(
+        shared_config: SharedConfig,
+        sync_config: SyncConfig,
+        prediction_config: PredictionConfig,
+        interpolation_config: InterpolationConfig,
+        conditioner: LinkConditionerConfig,
+        frame_duration: Duration,
+        replication: ReplicationConfig,
     ) -> Self {
         // tracing_subscriber::FmtSubscriber::builder()
         //     // .with_span_events(FmtSpan::ENTER)
@@ -139,6 +169,7 @@ impl BevyStepper {
             sync: sync_config,
             prediction: prediction_config,
             interpolation: interpolation_config,
+            replication,
             ..default()
         };
         let plugin_config = client::PluginConfig::new(config, protocol());
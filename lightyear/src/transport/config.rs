@@ -1,6 +1,7 @@
 use bevy::prelude::Reflect;
 use std::fmt::{Debug, Formatter};
 use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 
 use crossbeam_channel::{Receiver, Sender};
 
@@ -14,13 +15,31 @@ use crate::prelude::Io;
 use crate::transport::channels::Channels;
 use crate::transport::dummy::DummyIo;
 use crate::transport::local::LocalChannelBuilder;
-use crate::transport::middleware::conditioner::{LinkConditioner, LinkConditionerConfig};
+#[cfg(feature = "compression")]
+use crate::transport::middleware::compression::{CompressionConfig, COMPRESSION_HEADER_BYTES};
+use crate::transport::middleware::conditioner::LinkConditionerConfig;
 #[cfg(not(target_family = "wasm"))]
-use crate::transport::udp::UdpSocketBuilder;
+use crate::transport::relay::transport::{RelayRole, RelaySocketBuilder};
+#[cfg(not(target_family = "wasm"))]
+use crate::transport::relay::{SessionSecret, MAX_HEADER_BYTES as RELAY_HEADER_BYTES};
+#[cfg(not(target_family = "wasm"))]
+use crate::transport::replay::ReplayBuilder;
+#[cfg(not(target_family = "wasm"))]
+use crate::transport::tcp::{TcpClientSocketBuilder, TcpServerSocketBuilder};
+#[cfg(not(target_family = "wasm"))]
+use crate::transport::udp::{UdpConfig, UdpSocketBuilder};
+#[cfg(target_os = "linux")]
+use crate::transport::unix::seqpacket::{UnixSeqpacketClientBuilder, UnixSeqpacketServerBuilder};
+#[cfg(unix)]
+use crate::transport::unix::{UnixSocketClientBuilder, UnixSocketServerBuilder};
 #[cfg(feature = "websocket")]
 use crate::transport::websocket::client::WebSocketClientSocketBuilder;
 #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
 use crate::transport::websocket::server::WebSocketServerSocketBuilder;
+#[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+use crate::transport::websocket::tls::WebSocketCertificate;
+#[cfg(feature = "websocket")]
+use crate::transport::websocket::WebSocketTlsConfig;
 #[cfg(feature = "webtransport")]
 use crate::transport::webtransport::client::WebTransportClientSocketBuilder;
 use crate::transport::{Transport, TransportBuilderEnum};
@@ -32,6 +51,55 @@ pub enum TransportConfig {
     /// Use a [`UdpSocket`](std::net::UdpSocket)
     #[cfg(not(target_family = "wasm"))]
     UdpSocket(SocketAddr),
+    /// Use a [`UdpSocket`](std::net::UdpSocket) with custom settings, for example to change
+    /// the number of datagrams sent or received per syscall
+    #[cfg(not(target_family = "wasm"))]
+    UdpSocketWithConfig {
+        local_addr: SocketAddr,
+        config: UdpConfig,
+    },
+    /// Connect to a server with a TCP stream.
+    /// Useful as a fallback on networks that block UDP traffic.
+    /// Connecting fails if the connection is not established within 5 seconds
+    #[cfg(not(target_family = "wasm"))]
+    TcpClient { server_addr: SocketAddr },
+    /// Accept connections from clients using [`TransportConfig::TcpClient`]
+    #[cfg(not(target_family = "wasm"))]
+    TcpServer { server_addr: SocketAddr },
+    /// Use a Unix domain datagram socket bound to `path`, that sends packets to the server socket at `server_path`.
+    /// Useful to connect processes running on the same host.
+    ///
+    /// The socket file is removed when the transport is closed.
+    #[cfg(unix)]
+    UnixSocketClient { path: PathBuf, server_path: PathBuf },
+    /// Use a Unix domain datagram socket bound to `path`, that accepts packets from
+    /// clients using [`TransportConfig::UnixSocketClient`]
+    #[cfg(unix)]
+    UnixSocketServer { path: PathBuf },
+    /// Connect to the server socket at `server_path` with a Unix domain `SOCK_SEQPACKET` socket.
+    ///
+    /// Unlike [`TransportConfig::UnixSocketClient`], the client does not need its own socket file,
+    /// and each side is notified when the other one closes the connection. The packets are delivered reliably and in order.
+    #[cfg(target_os = "linux")]
+    UnixSeqpacketClient { server_path: PathBuf },
+    /// Use a Unix domain `SOCK_SEQPACKET` socket bound to `path`, that accepts connections from
+    /// clients using [`TransportConfig::UnixSeqpacketClient`]
+    #[cfg(target_os = "linux")]
+    UnixSeqpacketServer { path: PathBuf },
+    /// Send and receive packets through a [`RelayServer`](crate::transport::relay::server::RelayServer),
+    /// for hosts and clients that cannot reach each other directly.
+    ///
+    /// The server registers the session `session_id` on the relay, and the clients connect to the server
+    /// that hosts this session. The clients can use any server address in their connect token.
+    ///
+    /// The server needs the `session_secret` given by [`session_secret`](crate::transport::relay::session_secret)
+    /// to register the session; the clients can leave it to `None`.
+    #[cfg(not(target_family = "wasm"))]
+    Relay {
+        relay_addr: SocketAddr,
+        session_id: u64,
+        session_secret: Option<SessionSecret>,
+    },
     /// Use [`WebTransport`](https://wicg.github.io/web-transport/) as a transport layer
     #[cfg(feature = "webtransport")]
     WebTransportClient {
@@ -54,6 +122,19 @@ pub enum TransportConfig {
     /// Use [`WebSocket`](https://developer.mozilla.org/en-US/docs/Web/API/WebSocket) as a transport
     #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
     WebSocketServer { server_addr: SocketAddr },
+    /// Use [`WebSocket`](https://developer.mozilla.org/en-US/docs/Web/API/WebSocket) over TLS (`wss://`) as a transport
+    #[cfg(feature = "websocket")]
+    SecureWebSocketClient {
+        server_addr: SocketAddr,
+        tls: WebSocketTlsConfig,
+    },
+    /// Use [`WebSocket`](https://developer.mozilla.org/en-US/docs/Web/API/WebSocket) over TLS (`wss://`) as a transport
+    #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+    SecureWebSocketServer {
+        server_addr: SocketAddr,
+        /// Certificate presented to the clients
+        certificate: WebSocketCertificate,
+    },
     /// Use a crossbeam_channel as a transport. This is useful for testing.
     /// This is server-only: each tuple corresponds to a different client.
     Channels {
@@ -65,17 +146,88 @@ pub enum TransportConfig {
         recv: Receiver<Vec<u8>>,
         send: Sender<Vec<u8>>,
     },
+    /// Play back a capture file recorded with [`IoConfig::with_capture`].
+    /// The packets received during the captured session are received again; sent packets are discarded.
+    ///
+    /// The client or server must use the same configuration (protocol id, private key, etc.) as the captured session.
+    /// The secrets of the netcode session (connect token, challenge key) are restored from the capture.
+    #[cfg(not(target_family = "wasm"))]
+    Replay { path: PathBuf },
     /// Dummy transport if the connection handles its own io (for example steam sockets)
     Dummy,
 }
 
 impl TransportConfig {
+    /// Number of bytes that the transport adds to every packet, which must be reserved in the MTU of the connection
+    pub(crate) fn header_bytes(&self) -> usize {
+        match self {
+            #[cfg(not(target_family = "wasm"))]
+            TransportConfig::Relay { .. } => RELAY_HEADER_BYTES,
+            _ => 0,
+        }
+    }
+
+    /// Returns true if the transport drops the packets bigger than the path MTU instead of fragmenting them,
+    /// which is required by the path MTU discovery
+    fn supports_mtu_discovery(&self) -> bool {
+        match self {
+            #[cfg(target_os = "linux")]
+            TransportConfig::UdpSocketWithConfig { config, .. } => config.dont_fragment,
+            _ => false,
+        }
+    }
+
     fn build(self) -> TransportBuilderEnum {
         match self {
             #[cfg(not(target_family = "wasm"))]
-            TransportConfig::UdpSocket(addr) => {
-                TransportBuilderEnum::UdpSocket(UdpSocketBuilder { local_addr: addr })
+            TransportConfig::UdpSocket(addr) => TransportBuilderEnum::UdpSocket(UdpSocketBuilder {
+                local_addr: addr,
+                config: UdpConfig::default(),
+            }),
+            #[cfg(not(target_family = "wasm"))]
+            TransportConfig::UdpSocketWithConfig { local_addr, config } => {
+                TransportBuilderEnum::UdpSocket(UdpSocketBuilder { local_addr, config })
+            }
+            #[cfg(not(target_family = "wasm"))]
+            TransportConfig::TcpClient { server_addr } => {
+                TransportBuilderEnum::TcpClient(TcpClientSocketBuilder { server_addr })
+            }
+            #[cfg(not(target_family = "wasm"))]
+            TransportConfig::TcpServer { server_addr } => {
+                TransportBuilderEnum::TcpServer(TcpServerSocketBuilder { server_addr })
+            }
+            #[cfg(unix)]
+            TransportConfig::UnixSocketClient { path, server_path } => {
+                TransportBuilderEnum::UnixSocketClient(UnixSocketClientBuilder {
+                    path,
+                    server_path,
+                })
+            }
+            #[cfg(unix)]
+            TransportConfig::UnixSocketServer { path } => {
+                TransportBuilderEnum::UnixSocketServer(UnixSocketServerBuilder { path })
+            }
+            #[cfg(target_os = "linux")]
+            TransportConfig::UnixSeqpacketClient { server_path } => {
+                TransportBuilderEnum::UnixSeqpacketClient(UnixSeqpacketClientBuilder {
+                    server_path,
+                })
+            }
+            #[cfg(target_os = "linux")]
+            TransportConfig::UnixSeqpacketServer { path } => {
+                TransportBuilderEnum::UnixSeqpacketServer(UnixSeqpacketServerBuilder { path })
             }
+            #[cfg(not(target_family = "wasm"))]
+            TransportConfig::Relay {
+                relay_addr,
+                session_id,
+                session_secret,
+            } => TransportBuilderEnum::Relay(RelaySocketBuilder {
+                relay_addr,
+                session_id,
+                session_secret,
+                role: RelayRole::Client,
+            }),
             #[cfg(all(feature = "webtransport", not(target_family = "wasm")))]
             TransportConfig::WebTransportClient {
                 client_addr,
@@ -104,18 +256,43 @@ impl TransportConfig {
             }),
             #[cfg(feature = "websocket")]
             TransportConfig::WebSocketClient { server_addr } => {
-                TransportBuilderEnum::WebSocketClient(WebSocketClientSocketBuilder { server_addr })
+                TransportBuilderEnum::WebSocketClient(WebSocketClientSocketBuilder {
+                    server_addr,
+                    tls: None,
+                })
             }
             #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
             TransportConfig::WebSocketServer { server_addr } => {
-                TransportBuilderEnum::WebSocketServer(WebSocketServerSocketBuilder { server_addr })
+                TransportBuilderEnum::WebSocketServer(WebSocketServerSocketBuilder {
+                    server_addr,
+                    certificate: None,
+                })
             }
+            #[cfg(feature = "websocket")]
+            TransportConfig::SecureWebSocketClient { server_addr, tls } => {
+                TransportBuilderEnum::WebSocketClient(WebSocketClientSocketBuilder {
+                    server_addr,
+                    tls: Some(tls),
+                })
+            }
+            #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+            TransportConfig::SecureWebSocketServer {
+                server_addr,
+                certificate,
+            } => TransportBuilderEnum::WebSocketServer(WebSocketServerSocketBuilder {
+                server_addr,
+                certificate: Some(certificate),
+            }),
             TransportConfig::Channels { channels } => {
                 TransportBuilderEnum::Channels(Channels::new(channels))
             }
             TransportConfig::LocalChannel { recv, send } => {
                 TransportBuilderEnum::LocalChannel(LocalChannelBuilder { recv, send })
             }
+            #[cfg(not(target_family = "wasm"))]
+            TransportConfig::Replay { path } => {
+                TransportBuilderEnum::Replay(ReplayBuilder { path })
+            }
             TransportConfig::Dummy => TransportBuilderEnum::Dummy(DummyIo),
         }
     }
@@ -134,6 +311,14 @@ pub struct IoConfig {
     #[reflect(ignore)]
     pub transport: TransportConfig,
     pub conditioner: Option<LinkConditionerConfig>,
+    /// Compression applied to the payload of the packets sent and received through the transport,
+    /// before they are encrypted. The client and the server must use the same compression.
+    #[cfg(feature = "compression")]
+    pub compression: Option<CompressionConfig>,
+    /// If set, every packet sent and received on the wire is recorded to a capture file at this path
+    #[cfg(not(target_family = "wasm"))]
+    #[reflect(ignore)]
+    pub capture: Option<PathBuf>,
 }
 
 impl Default for IoConfig {
@@ -142,6 +327,9 @@ impl Default for IoConfig {
         Self {
             transport: TransportConfig::UdpSocket(SocketAddr::new(IpAddr::from([127, 0, 0, 1]), 0)),
             conditioner: None,
+            #[cfg(feature = "compression")]
+            compression: None,
+            capture: None,
         }
     }
 
@@ -151,6 +339,8 @@ impl Default for IoConfig {
         Self {
             transport: TransportConfig::LocalChannel { recv, send },
             conditioner: None,
+            #[cfg(feature = "compression")]
+            compression: None,
         }
     }
 }
@@ -160,6 +350,10 @@ impl IoConfig {
         Self {
             transport,
             conditioner: None,
+            #[cfg(feature = "compression")]
+            compression: None,
+            #[cfg(not(target_family = "wasm"))]
+            capture: None,
         }
     }
     pub fn with_conditioner(mut self, conditioner_config: LinkConditionerConfig) -> Self {
@@ -167,9 +361,92 @@ impl IoConfig {
         self
     }
 
-    pub fn build(self) -> Io {
-        let conditioner = self.conditioner.map(LinkConditioner::new);
-        let transport_builder = self.transport.build();
-        Io::new(transport_builder, conditioner)
+    #[cfg(feature = "compression")]
+    pub fn with_compression(mut self, compression_config: CompressionConfig) -> Self {
+        self.compression = Some(compression_config);
+        self
+    }
+
+    /// Record every packet sent and received on the wire to a capture file,
+    /// which can be played back with [`TransportConfig::Replay`]
+    #[cfg(not(target_family = "wasm"))]
+    pub fn with_capture(mut self, path: impl Into<PathBuf>) -> Self {
+        self.capture = Some(path.into());
+        self
+    }
+
+    /// Number of bytes that the transport and the compression add to every packet,
+    /// which must be reserved in the MTU of the connection
+    pub(crate) fn header_bytes(&self) -> usize {
+        let header_bytes = self.transport.header_bytes();
+        #[cfg(feature = "compression")]
+        let header_bytes = header_bytes
+            + self
+                .compression
+                .as_ref()
+                .map_or(0, |_| COMPRESSION_HEADER_BYTES);
+        header_bytes
+    }
+
+    /// Set the Don't Fragment bit on the packets sent by the UDP transports, which is required by
+    /// the path MTU discovery. The other transports cannot prevent the fragmentation and don't support the discovery.
+    pub(crate) fn enable_mtu_discovery(&mut self) {
+        match &mut self.transport {
+            #[cfg(not(target_family = "wasm"))]
+            TransportConfig::UdpSocket(local_addr) => {
+                self.transport = TransportConfig::UdpSocketWithConfig {
+                    local_addr: *local_addr,
+                    config: UdpConfig {
+                        dont_fragment: true,
+                        ..UdpConfig::default()
+                    },
+                };
+            }
+            #[cfg(not(target_family = "wasm"))]
+            TransportConfig::UdpSocketWithConfig { config, .. } => {
+                config.dont_fragment = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns true if the path MTU of the connection can be discovered with this transport
+    pub(crate) fn supports_mtu_discovery(&self) -> bool {
+        self.transport.supports_mtu_discovery()
+    }
+
+    pub fn build(mut self) -> Io {
+        let transport_builder =
+            std::mem::replace(&mut self.transport, TransportConfig::Dummy).build();
+        self.build_with_transport(transport_builder)
+    }
+
+    /// Build the [`Io`] of a server: a [`TransportConfig::Relay`] hosts the session instead of connecting to it
+    pub(crate) fn build_server(mut self) -> Io {
+        let transport_builder = match std::mem::replace(&mut self.transport, TransportConfig::Dummy)
+        {
+            #[cfg(not(target_family = "wasm"))]
+            TransportConfig::Relay {
+                relay_addr,
+                session_id,
+                session_secret,
+            } => TransportBuilderEnum::Relay(RelaySocketBuilder {
+                relay_addr,
+                session_id,
+                session_secret,
+                role: RelayRole::Host,
+            }),
+            transport => transport.build(),
+        };
+        self.build_with_transport(transport_builder)
+    }
+
+    fn build_with_transport(self, transport_builder: TransportBuilderEnum) -> Io {
+        let io = Io::new(transport_builder, self.conditioner);
+        #[cfg(feature = "compression")]
+        let io = io.with_compression(self.compression);
+        #[cfg(not(target_family = "wasm"))]
+        let io = io.with_capture(self.capture);
+        io
     }
 }
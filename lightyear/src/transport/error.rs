@@ -8,7 +8,16 @@ pub enum Error {
     #[cfg(all(feature = "webtransport", not(target_family = "wasm")))]
     #[error(transparent)]
     WebTransport(#[from] wtransport::error::ConnectingError),
+    #[cfg(feature = "compression")]
+    #[error(transparent)]
+    Lz4Compress(#[from] lz4_flex::block::CompressError),
+    #[cfg(feature = "compression")]
+    #[error(transparent)]
+    Lz4Decompress(#[from] lz4_flex::block::DecompressError),
     #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
     #[error(transparent)]
     WebSocket(#[from] tokio_tungstenite::tungstenite::error::Error),
+    #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+    #[error(transparent)]
+    Tls(#[from] rustls::Error),
 }
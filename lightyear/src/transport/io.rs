@@ -2,6 +2,7 @@
 //! bandwidth monitoring or compression
 use std::fmt::{Debug, Formatter};
 use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 
 use bevy::app::{App, Plugin};
 use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
@@ -9,13 +10,17 @@ use bevy::prelude::{Real, Res, Resource, Time};
 use crossbeam_channel::{Receiver, Sender};
 #[cfg(feature = "metrics")]
 use metrics;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::transport::local::{LocalChannel, LocalChannelBuilder};
+#[cfg(not(target_family = "wasm"))]
+use crate::transport::middleware::capture::CaptureWriter;
+#[cfg(feature = "compression")]
+use crate::transport::middleware::compression::CompressionConfig;
 use crate::transport::middleware::conditioner::{
-    ConditionedPacketReceiver, LinkConditioner, LinkConditionerConfig, PacketLinkConditioner,
+    ConditionerDirection, LinkConditioner, LinkConditionerConfig, PacketLinkConditioner,
 };
-use crate::transport::middleware::PacketReceiverWrapper;
+use crate::transport::middleware::{PacketReceiverWrapper, PacketSenderWrapper};
 use crate::transport::{PacketReceiver, PacketSender, Transport};
 
 use super::error::Result;
@@ -32,7 +37,21 @@ pub struct Io {
     sender: Option<BoxedSender>,
     receiver: Option<BoxedReceiver>,
     close_fn: Option<BoxedCloseFn>,
-    conditioner: Option<PacketLinkConditioner>,
+    /// Simulates network conditions on the packets we receive
+    incoming_conditioner: Option<PacketLinkConditioner>,
+    /// Simulates network conditions on the packets we send
+    outgoing_conditioner: Option<PacketLinkConditioner>,
+    /// Compression applied by the connection to the payload of the packets
+    #[cfg(feature = "compression")]
+    compression: Option<CompressionConfig>,
+    /// Path of the file where the packets sent and received on the wire are recorded
+    #[cfg(not(target_family = "wasm"))]
+    capture: Option<PathBuf>,
+    #[cfg(not(target_family = "wasm"))]
+    capture_writer: Option<CaptureWriter>,
+    /// Secrets of the captured session, if the io plays back a capture
+    #[cfg(not(target_family = "wasm"))]
+    replayed_session: Option<Vec<u8>>,
     pub(crate) stats: IoStats,
 }
 
@@ -42,19 +61,41 @@ impl Default for Io {
     }
 }
 
-// TODO: add stats/compression to middleware
+// TODO: add stats to middleware
 #[derive(Default, Debug)]
 pub struct IoStats {
+    /// Number of bytes sent on the wire (after compression)
     pub bytes_sent: usize,
+    /// Number of bytes received on the wire (before decompression)
     pub bytes_received: usize,
+    /// Number of bytes sent, counting the compressed payloads with their size before compression
+    pub uncompressed_bytes_sent: usize,
+    /// Number of bytes received, counting the compressed payloads with their size after decompression
+    pub uncompressed_bytes_received: usize,
     pub packets_sent: usize,
     pub packets_received: usize,
 }
 
+impl IoStats {
+    /// Record that a payload of `uncompressed` bytes was sent as `compressed` bytes
+    #[cfg(feature = "compression")]
+    pub(crate) fn record_compressed_sent(&mut self, compressed: usize, uncompressed: usize) {
+        self.uncompressed_bytes_sent =
+            (self.uncompressed_bytes_sent + uncompressed).saturating_sub(compressed);
+    }
+
+    /// Record that `compressed` received bytes were decompressed to `uncompressed` bytes
+    #[cfg(feature = "compression")]
+    pub(crate) fn record_compressed_received(&mut self, compressed: usize, uncompressed: usize) {
+        self.uncompressed_bytes_received =
+            (self.uncompressed_bytes_received + uncompressed).saturating_sub(compressed);
+    }
+}
+
 impl Io {
     pub(crate) fn new(
         transport_builder: TransportBuilderEnum,
-        conditioner: Option<PacketLinkConditioner>,
+        conditioner: Option<LinkConditionerConfig>,
     ) -> Self {
         Self {
             transport_builder: Some(transport_builder),
@@ -62,10 +103,112 @@ impl Io {
             sender: None,
             receiver: None,
             close_fn: None,
-            conditioner,
+            incoming_conditioner: Self::conditioner(
+                conditioner.as_ref(),
+                ConditionerDirection::Incoming,
+            ),
+            outgoing_conditioner: Self::conditioner(
+                conditioner.as_ref(),
+                ConditionerDirection::Outgoing,
+            ),
+            #[cfg(feature = "compression")]
+            compression: None,
+            #[cfg(not(target_family = "wasm"))]
+            capture: None,
+            #[cfg(not(target_family = "wasm"))]
+            capture_writer: None,
+            #[cfg(not(target_family = "wasm"))]
+            replayed_session: None,
             stats: IoStats::default(),
         }
     }
+
+    /// Compress the payload of the packets before sending them, and decompress it on reception.
+    ///
+    /// The compression is applied by the connection, since the payload must be compressed before it is encrypted.
+    #[cfg(feature = "compression")]
+    pub(crate) fn with_compression(mut self, compression: Option<CompressionConfig>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    #[cfg(feature = "compression")]
+    pub(crate) fn compression(&self) -> Option<&CompressionConfig> {
+        self.compression.as_ref()
+    }
+
+    /// Record the packets sent and received on the wire to a capture file
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn with_capture(mut self, capture: Option<PathBuf>) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    /// Record the secrets of the session in the capture file, if the packets are being captured
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn record_session(&self, data: &[u8]) -> Result<()> {
+        match &self.capture_writer {
+            Some(capture) => capture.record_session(data),
+            None => Ok(()),
+        }
+    }
+
+    /// Secrets of the captured session, if the io plays back a capture
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn replayed_session(&self) -> Option<&[u8]> {
+        self.replayed_session.as_deref()
+    }
+
+    /// Create a link conditioner for the packets going in the given direction,
+    /// only if the config has an effect on them
+    fn conditioner(
+        config: Option<&LinkConditionerConfig>,
+        direction: ConditionerDirection,
+    ) -> Option<PacketLinkConditioner> {
+        config
+            .filter(|config| config.has_conditions(direction))
+            .map(|config| LinkConditioner::with_direction(config.clone(), direction))
+    }
+
+    /// Change the simulated network conditions at runtime.
+    ///
+    /// Packets that are already delayed by the previous conditions keep their delivery time.
+    /// If `None` (or if the new conditions do not affect a direction), the link conditioner of that direction
+    /// is removed: the outgoing packets that it was still delaying are sent right away, and the incoming packets
+    /// that it was still delaying are dropped.
+    pub fn set_conditioner(&mut self, config: Option<LinkConditionerConfig>) {
+        for direction in [
+            ConditionerDirection::Incoming,
+            ConditionerDirection::Outgoing,
+        ] {
+            let conditioner = match direction {
+                ConditionerDirection::Incoming => &mut self.incoming_conditioner,
+                ConditionerDirection::Outgoing => &mut self.outgoing_conditioner,
+            };
+            match config
+                .as_ref()
+                .filter(|config| config.has_conditions(direction))
+            {
+                Some(config) => conditioner
+                    .get_or_insert_with(|| {
+                        LinkConditioner::with_direction(config.clone(), direction)
+                    })
+                    .set_config(config.clone()),
+                None => {
+                    if let (Some(mut conditioner), Some(sender)) =
+                        (conditioner.take(), self.sender.as_mut())
+                    {
+                        if direction == ConditionerDirection::Outgoing {
+                            if let Err(e) = conditioner.flush_all(sender) {
+                                warn!("could not send the packets delayed by the link conditioner: {e}");
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     pub fn local_addr(&self) -> SocketAddr {
         self.local_addr.expect("The transport is not connected yet")
     }
@@ -78,28 +221,93 @@ impl Io {
         // TODO: allow for connection retries
         let transport_builder = std::mem::take(&mut self.transport_builder)
             .expect("The transport has already been connected");
-        let transport = transport_builder.connect()?;
+        #[allow(unused_mut)]
+        let mut transport = transport_builder.connect()?;
+        #[cfg(not(target_family = "wasm"))]
+        if let super::TransportEnum::Replay(replay) = &mut transport {
+            self.replayed_session = replay.take_session();
+        }
         self.local_addr = Some(transport.local_addr());
         let (sender, receiver, close_fn) = transport.split();
+        #[cfg(not(target_family = "wasm"))]
+        let (sender, receiver) = match &self.capture {
+            Some(path) => {
+                let capture = CaptureWriter::create(path, self.local_addr())?;
+                self.capture_writer = Some(capture.clone());
+                let sender: BoxedSender =
+                    Box::new(PacketSenderWrapper::wrap(capture.clone(), sender));
+                let receiver: BoxedReceiver =
+                    Box::new(PacketReceiverWrapper::wrap(capture, receiver));
+                (sender, receiver)
+            }
+            None => (sender, receiver),
+        };
         self.close_fn = close_fn;
         self.sender = Some(sender);
-        if let Some(conditioner) = std::mem::take(&mut self.conditioner) {
-            self.receiver = Some(Box::new(conditioner.wrap(receiver)));
-        } else {
-            self.receiver = Some(receiver);
-        }
+        self.receiver = Some(receiver);
         Ok(())
     }
 
-    // TODO: no stats are being computed here!
-    pub fn split(&mut self) -> (&mut impl PacketSender, &mut impl PacketReceiver) {
-        (
-            self.sender
+    /// Send the outgoing packets that were delayed by the link conditioner and are now ready,
+    /// as well as the packets buffered by the transport
+    pub fn flush(&mut self) -> Result<()> {
+        if self.sender.is_none() {
+            return Ok(());
+        }
+        self.io_sender().flush()
+    }
+
+    fn io_sender(&mut self) -> IoSender<'_> {
+        IoSender {
+            sender: self
+                .sender
                 .as_mut()
                 .expect("The transport has not been connected"),
-            self.receiver
+            conditioner: self.outgoing_conditioner.as_mut(),
+            bytes_sent: &mut self.stats.bytes_sent,
+            uncompressed_bytes_sent: &mut self.stats.uncompressed_bytes_sent,
+            packets_sent: &mut self.stats.packets_sent,
+        }
+    }
+
+    fn io_receiver(&mut self) -> IoReceiver<'_> {
+        IoReceiver {
+            receiver: self
+                .receiver
                 .as_mut()
                 .expect("The transport has not been connected"),
+            conditioner: self.incoming_conditioner.as_mut(),
+            bytes_received: &mut self.stats.bytes_received,
+            uncompressed_bytes_received: &mut self.stats.uncompressed_bytes_received,
+            packets_received: &mut self.stats.packets_received,
+        }
+    }
+
+    /// Split the io into a sender and a receiver, which can be used at the same time.
+    ///
+    /// Both halves still apply the link conditioner, and compute stats.
+    pub fn split(&mut self) -> (impl PacketSender + '_, impl PacketReceiver + '_) {
+        (
+            IoSender {
+                sender: self
+                    .sender
+                    .as_mut()
+                    .expect("The transport has not been connected"),
+                conditioner: self.outgoing_conditioner.as_mut(),
+                bytes_sent: &mut self.stats.bytes_sent,
+                uncompressed_bytes_sent: &mut self.stats.uncompressed_bytes_sent,
+                packets_sent: &mut self.stats.packets_sent,
+            },
+            IoReceiver {
+                receiver: self
+                    .receiver
+                    .as_mut()
+                    .expect("The transport has not been connected"),
+                conditioner: self.incoming_conditioner.as_mut(),
+                bytes_received: &mut self.stats.bytes_received,
+                uncompressed_bytes_received: &mut self.stats.uncompressed_bytes_received,
+                packets_received: &mut self.stats.packets_received,
+            },
         )
     }
 
@@ -123,33 +331,96 @@ impl Debug for Io {
 
 impl PacketReceiver for Io {
     fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
-        // todo: compression + bandwidth monitoring
-        self.receiver.as_mut().unwrap().recv().map(|x| {
-            if let Some((ref buffer, _)) = x {
-                #[cfg(feature = "metrics")]
-                {
-                    metrics::counter!("transport.packets_received").increment(1);
-                    metrics::gauge!("transport.bytes_received").increment(buffer.len() as f64);
-                }
-                self.stats.bytes_received += buffer.len();
-                self.stats.packets_received += 1;
-            }
-            x
-        })
+        self.io_receiver().into_recv()
     }
 }
 
 impl PacketSender for Io {
     fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
-        // todo: compression + bandwidth monitoring
+        self.io_sender().send(payload, address)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Io::flush(self)
+    }
+}
+
+/// Sending half of a connected [`Io`]
+struct IoSender<'a> {
+    sender: &'a mut BoxedSender,
+    conditioner: Option<&'a mut PacketLinkConditioner>,
+    bytes_sent: &'a mut usize,
+    uncompressed_bytes_sent: &'a mut usize,
+    packets_sent: &'a mut usize,
+}
+
+impl PacketSender for IoSender<'_> {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        // todo: bandwidth monitoring
+        *self.uncompressed_bytes_sent += payload.len();
         #[cfg(feature = "metrics")]
         {
             metrics::counter!("transport.packets_sent").increment(1);
             metrics::gauge!("transport.bytes_sent").increment(payload.len() as f64);
         }
-        self.stats.bytes_sent += payload.len();
-        self.stats.packets_sent += 1;
-        self.sender.as_mut().unwrap().send(payload, address)
+        *self.bytes_sent += payload.len();
+        *self.packets_sent += 1;
+        match self.conditioner.as_mut() {
+            Some(conditioner) => conditioner.send_to(payload, address, &mut *self.sender),
+            None => self.sender.send(payload, address),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(conditioner) = self.conditioner.as_mut() {
+            conditioner.flush(&mut *self.sender)?;
+        }
+        self.sender.flush()
+    }
+}
+
+/// Receiving half of a connected [`Io`]
+struct IoReceiver<'a> {
+    receiver: &'a mut BoxedReceiver,
+    conditioner: Option<&'a mut PacketLinkConditioner>,
+    bytes_received: &'a mut usize,
+    uncompressed_bytes_received: &'a mut usize,
+    packets_received: &'a mut usize,
+}
+
+impl<'a> IoReceiver<'a> {
+    /// Receive a packet; the returned data borrows from the underlying [`Io`]
+    fn into_recv(self) -> Result<Option<(&'a mut [u8], SocketAddr)>> {
+        // todo: bandwidth monitoring
+        let packet = match self.conditioner {
+            Some(conditioner) => conditioner.recv_from(self.receiver)?,
+            None => self.receiver.recv()?,
+        };
+        let Some((buffer, address)) = packet else {
+            return Ok(None);
+        };
+        #[cfg(feature = "metrics")]
+        {
+            metrics::counter!("transport.packets_received").increment(1);
+            metrics::gauge!("transport.bytes_received").increment(buffer.len() as f64);
+        }
+        *self.bytes_received += buffer.len();
+        *self.uncompressed_bytes_received += buffer.len();
+        *self.packets_received += 1;
+        Ok(Some((buffer, address)))
+    }
+}
+
+impl PacketReceiver for IoReceiver<'_> {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        IoReceiver {
+            receiver: &mut *self.receiver,
+            conditioner: self.conditioner.as_deref_mut(),
+            bytes_received: &mut *self.bytes_received,
+            uncompressed_bytes_received: &mut *self.uncompressed_bytes_received,
+            packets_received: &mut *self.packets_received,
+        }
+        .into_recv()
     }
 }
 
@@ -160,6 +431,12 @@ impl IoDiagnosticsPlugin {
     pub const BYTES_IN: DiagnosticPath = DiagnosticPath::const_new("KB received per second");
     /// How many bytes do we send per second
     pub const BYTES_OUT: DiagnosticPath = DiagnosticPath::const_new("KB sent per second");
+    /// How many bytes do we receive per second, after decompression
+    pub const UNCOMPRESSED_BYTES_IN: DiagnosticPath =
+        DiagnosticPath::const_new("uncompressed KB received per second");
+    /// How many bytes do we send per second, before compression
+    pub const UNCOMPRESSED_BYTES_OUT: DiagnosticPath =
+        DiagnosticPath::const_new("uncompressed KB sent per second");
 
     /// How many bytes do we receive per second
     pub const PACKETS_IN: DiagnosticPath = DiagnosticPath::const_new("packets received per second");
@@ -184,6 +461,12 @@ impl IoDiagnosticsPlugin {
         diagnostics.add_measurement(&Self::BYTES_OUT, || {
             (stats.bytes_sent as f64 / 1000.0) / delta_seconds
         });
+        diagnostics.add_measurement(&Self::UNCOMPRESSED_BYTES_IN, || {
+            (stats.uncompressed_bytes_received as f64 / 1000.0) / delta_seconds
+        });
+        diagnostics.add_measurement(&Self::UNCOMPRESSED_BYTES_OUT, || {
+            (stats.uncompressed_bytes_sent as f64 / 1000.0) / delta_seconds
+        });
         diagnostics.add_measurement(&Self::PACKETS_IN, || {
             stats.packets_received as f64 / delta_seconds
         });
@@ -204,6 +487,14 @@ impl Plugin for IoDiagnosticsPlugin {
             Diagnostic::new(IoDiagnosticsPlugin::BYTES_OUT)
                 .with_max_history_length(IoDiagnosticsPlugin::DIAGNOSTIC_HISTORY_LEN),
         );
+        app.register_diagnostic(
+            Diagnostic::new(IoDiagnosticsPlugin::UNCOMPRESSED_BYTES_IN)
+                .with_max_history_length(IoDiagnosticsPlugin::DIAGNOSTIC_HISTORY_LEN),
+        );
+        app.register_diagnostic(
+            Diagnostic::new(IoDiagnosticsPlugin::UNCOMPRESSED_BYTES_OUT)
+                .with_max_history_length(IoDiagnosticsPlugin::DIAGNOSTIC_HISTORY_LEN),
+        );
         app.register_diagnostic(
             Diagnostic::new(IoDiagnosticsPlugin::PACKETS_IN)
                 .with_max_history_length(IoDiagnosticsPlugin::DIAGNOSTIC_HISTORY_LEN),
@@ -214,3 +505,40 @@ impl Plugin for IoDiagnosticsPlugin {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::Duration;
+
+    use crate::transport::local::LocalChannelBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_remove_conditioner() {
+        let (send, recv) = crossbeam_channel::unbounded();
+        let config = LinkConditionerConfig::default().with_outgoing(
+            Duration::from_secs(10),
+            Duration::default(),
+            0.0,
+        );
+        let mut io = Io::new(
+            TransportBuilderEnum::LocalChannel(LocalChannelBuilder { recv, send }),
+            Some(config),
+        );
+        // only the directions affected by the config are conditioned
+        assert!(io.incoming_conditioner.is_none());
+        assert!(io.outgoing_conditioner.is_some());
+
+        io.connect().unwrap();
+        io.send(&[1, 2, 3], &LOCAL_SOCKET).unwrap();
+        // the packet is delayed by the conditioner
+        assert!(io.recv().unwrap().is_none());
+
+        // removing the conditioner sends the delayed packets right away
+        io.set_conditioner(None);
+        assert!(io.outgoing_conditioner.is_none());
+        let (payload, _) = io.recv().unwrap().unwrap();
+        assert_eq!(payload, &[1, 2, 3]);
+    }
+}
@@ -0,0 +1,362 @@
+//! Contains the packet capture middleware, which records every datagram sent and received through
+//! the transport to a capture file.
+//!
+//! A capture can be played back with [`TransportConfig::Replay`](crate::transport::config::TransportConfig::Replay)
+//! to deterministically re-run a session through the packet, channel and replication layers.
+//!
+//! The capture file starts with a header (magic bytes, version and local address of the transport),
+//! followed by one record per datagram.
+//!
+//! The connection also records the secrets of the session (for example the netcode connect token, or the key
+//! used by the server to encrypt the challenge tokens), so that the handshake can be replayed.
+//! A capture file must therefore be kept private.
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use bevy::utils::Duration;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use cfg_if::cfg_if;
+
+use crate::transport::error::Result;
+use crate::transport::middleware::{PacketReceiverWrapper, PacketSenderWrapper};
+use crate::transport::{PacketReceiver, PacketSender};
+
+cfg_if! {
+    if #[cfg(any(test))] {
+        use mock_instant::Instant;
+    } else {
+        use bevy::utils::Instant;
+    }
+}
+
+const CAPTURE_MAGIC: &[u8; 4] = b"LYCP";
+const CAPTURE_VERSION: u8 = 2;
+
+const SENT_RECORD: u8 = 0;
+const RECEIVED_RECORD: u8 = 1;
+const SESSION_RECORD: u8 = 2;
+
+/// Direction of a captured datagram
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PacketDirection {
+    /// The datagram was sent to the remote
+    Sent,
+    /// The datagram was received from the remote
+    Received,
+}
+
+/// A datagram recorded in a capture file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedPacket {
+    /// Time elapsed between the start of the capture and the moment the datagram was sent or received
+    pub timestamp: Duration,
+    pub direction: PacketDirection,
+    /// Destination of a sent datagram, or origin of a received datagram
+    pub address: SocketAddr,
+    pub payload: Vec<u8>,
+}
+
+impl CapturedPacket {
+    fn write_to(&self, writer: &mut impl WriteBytesExt) -> std::io::Result<()> {
+        writer.write_u64::<LittleEndian>(self.timestamp.as_micros() as u64)?;
+        writer.write_u8(match self.direction {
+            PacketDirection::Sent => SENT_RECORD,
+            PacketDirection::Received => RECEIVED_RECORD,
+        })?;
+        write_addr(writer, &self.address)?;
+        write_bytes(writer, &self.payload)
+    }
+}
+
+/// A record of the capture file
+enum CaptureRecord {
+    Packet(CapturedPacket),
+    /// Secrets of the session recorded by the connection
+    Session(Vec<u8>),
+}
+
+impl CaptureRecord {
+    /// Read the next record, or return `None` if the end of the capture has been reached
+    fn read_from(reader: &mut impl ReadBytesExt) -> std::io::Result<Option<Self>> {
+        let timestamp = match reader.read_u64::<LittleEndian>() {
+            Ok(micros) => Duration::from_micros(micros),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let direction = match reader.read_u8()? {
+            SENT_RECORD => PacketDirection::Sent,
+            RECEIVED_RECORD => PacketDirection::Received,
+            SESSION_RECORD => return Ok(Some(Self::Session(read_bytes(reader)?))),
+            d => return Err(invalid_data(format!("invalid record kind: {}", d))),
+        };
+        let address = read_addr(reader)?;
+        let payload = read_bytes(reader)?;
+        Ok(Some(Self::Packet(CapturedPacket {
+            timestamp,
+            direction,
+            address,
+            payload,
+        })))
+    }
+}
+
+fn write_bytes(writer: &mut impl WriteBytesExt, bytes: &[u8]) -> std::io::Result<()> {
+    writer.write_u32::<LittleEndian>(bytes.len() as u32)?;
+    writer.write_all(bytes)
+}
+
+fn read_bytes(reader: &mut impl ReadBytesExt) -> std::io::Result<Vec<u8>> {
+    let len = reader.read_u32::<LittleEndian>()? as usize;
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid_data(msg: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+fn write_addr(writer: &mut impl WriteBytesExt, addr: &SocketAddr) -> std::io::Result<()> {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            writer.write_u8(4)?;
+            writer.write_all(&ip.octets())?;
+        }
+        IpAddr::V6(ip) => {
+            writer.write_u8(6)?;
+            writer.write_all(&ip.octets())?;
+        }
+    }
+    writer.write_u16::<LittleEndian>(addr.port())
+}
+
+fn read_addr(reader: &mut impl ReadBytesExt) -> std::io::Result<SocketAddr> {
+    let ip = match reader.read_u8()? {
+        4 => {
+            let mut octets = [0; 4];
+            reader.read_exact(&mut octets)?;
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        6 => {
+            let mut octets = [0; 16];
+            reader.read_exact(&mut octets)?;
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        v => return Err(invalid_data(format!("invalid ip version: {}", v))),
+    };
+    let port = reader.read_u16::<LittleEndian>()?;
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// The content of a capture file
+#[derive(Clone, Debug, PartialEq)]
+pub struct PacketCapture {
+    /// Local address of the transport that was captured
+    pub local_addr: SocketAddr,
+    /// Captured datagrams, ordered by timestamp
+    pub packets: Vec<CapturedPacket>,
+    /// Secrets of the session recorded by the connection, which are needed to replay the handshake
+    pub session: Option<Vec<u8>>,
+}
+
+impl PacketCapture {
+    /// Read a capture file
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != CAPTURE_MAGIC {
+            return Err(invalid_data("not a packet capture file".to_string()).into());
+        }
+        let version = reader.read_u8()?;
+        if version != CAPTURE_VERSION {
+            return Err(
+                invalid_data(format!("unsupported packet capture version: {}", version)).into(),
+            );
+        }
+        let local_addr = read_addr(&mut reader)?;
+        let mut packets = vec![];
+        let mut session = None;
+        while let Some(record) = CaptureRecord::read_from(&mut reader)? {
+            match record {
+                CaptureRecord::Packet(packet) => packets.push(packet),
+                CaptureRecord::Session(data) => session = Some(data),
+            }
+        }
+        Ok(Self {
+            local_addr,
+            packets,
+            session,
+        })
+    }
+}
+
+/// Writes the datagrams that go through the transport to a capture file.
+///
+/// The sending and receiving halves of the transport share the same writer.
+/// The file is buffered, and flushed when the [`Io`](crate::transport::io::Io) is dropped.
+#[derive(Clone)]
+pub(crate) struct CaptureWriter {
+    start: Instant,
+    writer: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl CaptureWriter {
+    /// Create a new capture file (truncating any existing file at `path`)
+    pub(crate) fn create(path: impl AsRef<Path>, local_addr: SocketAddr) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(CAPTURE_MAGIC)?;
+        writer.write_u8(CAPTURE_VERSION)?;
+        write_addr(&mut writer, &local_addr)?;
+        Ok(Self {
+            start: Instant::now(),
+            writer: Arc::new(Mutex::new(writer)),
+        })
+    }
+
+    pub(crate) fn record(
+        &self,
+        direction: PacketDirection,
+        address: SocketAddr,
+        payload: &[u8],
+    ) -> Result<()> {
+        let packet = CapturedPacket {
+            timestamp: Instant::now() - self.start,
+            direction,
+            address,
+            payload: payload.to_vec(),
+        };
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| std::io::Error::other("packet capture writer is poisoned"))?;
+        packet.write_to(&mut *writer)?;
+        Ok(())
+    }
+
+    /// Record the secrets of the session, which are needed to replay the handshake of the connection
+    pub(crate) fn record_session(&self, data: &[u8]) -> Result<()> {
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| std::io::Error::other("packet capture writer is poisoned"))?;
+        writer.write_u64::<LittleEndian>((Instant::now() - self.start).as_micros() as u64)?;
+        writer.write_u8(SESSION_RECORD)?;
+        write_bytes(&mut *writer, data)?;
+        Ok(())
+    }
+
+    /// Write the buffered records to the file
+    pub(crate) fn flush(&self) -> Result<()> {
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| std::io::Error::other("packet capture writer is poisoned"))?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<T: PacketSender> PacketSenderWrapper<T> for CaptureWriter {
+    fn wrap(self, sender: T) -> impl PacketSender {
+        CapturedPacketSender {
+            packet_sender: sender,
+            capture: self,
+        }
+    }
+}
+
+/// A wrapper around a packet sender that records the outgoing packets
+pub struct CapturedPacketSender<T: PacketSender> {
+    packet_sender: T,
+    capture: CaptureWriter,
+}
+
+impl<T: PacketSender> PacketSender for CapturedPacketSender<T> {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        self.capture
+            .record(PacketDirection::Sent, *address, payload)?;
+        self.packet_sender.send(payload, address)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.packet_sender.flush()
+    }
+}
+
+impl<T: PacketReceiver> PacketReceiverWrapper<T> for CaptureWriter {
+    fn wrap(self, receiver: T) -> impl PacketReceiver {
+        CapturedPacketReceiver {
+            packet_receiver: receiver,
+            capture: self,
+        }
+    }
+}
+
+/// A wrapper around a packet receiver that records the incoming packets
+pub struct CapturedPacketReceiver<T: PacketReceiver> {
+    packet_receiver: T,
+    capture: CaptureWriter,
+}
+
+impl<T: PacketReceiver> PacketReceiver for CapturedPacketReceiver<T> {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        match self.packet_receiver.recv()? {
+            None => Ok(None),
+            Some((data, addr)) => {
+                self.capture.record(PacketDirection::Received, addr, data)?;
+                Ok(Some((data, addr)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mock_instant::MockClock;
+
+    use super::*;
+
+    #[test]
+    fn test_capture_roundtrip() {
+        let path = std::env::temp_dir().join("lightyear_test_capture_roundtrip.cap");
+        let local_addr = SocketAddr::from(([127, 0, 0, 1], 5000));
+        let remote_addr = SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], 6000));
+
+        let capture = CaptureWriter::create(&path, local_addr).unwrap();
+        capture.record_session(&[9, 9]).unwrap();
+        capture
+            .record(PacketDirection::Sent, remote_addr, &[1, 2, 3])
+            .unwrap();
+        MockClock::advance(Duration::from_millis(20));
+        capture
+            .record(PacketDirection::Received, remote_addr, &[4, 5])
+            .unwrap();
+        capture.flush().unwrap();
+
+        let read = PacketCapture::read(&path).unwrap();
+        assert_eq!(read.local_addr, local_addr);
+        assert_eq!(read.session, Some(vec![9, 9]));
+        assert_eq!(
+            read.packets,
+            vec![
+                CapturedPacket {
+                    timestamp: Duration::default(),
+                    direction: PacketDirection::Sent,
+                    address: remote_addr,
+                    payload: vec![1, 2, 3],
+                },
+                CapturedPacket {
+                    timestamp: Duration::from_millis(20),
+                    direction: PacketDirection::Received,
+                    address: remote_addr,
+                    payload: vec![4, 5],
+                },
+            ]
+        );
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,325 @@
+//! Contains the compression of the packets: netcode compresses the payload of a packet right before
+//! encrypting it, and decompresses it right after decrypting it (encrypted bytes look random, so they
+//! can't be compressed).
+//!
+//! Every payload starts with a 1-byte header that tells if the rest of the payload is compressed.
+//! Payloads that don't shrink when compressed (for example very small packets) are sent as-is after the header,
+//! so compression makes a packet at most [`COMPRESSION_HEADER_BYTES`] bigger.
+use bevy::reflect::Reflect;
+
+use crate::transport::error::Result;
+use crate::transport::MTU;
+
+/// Number of bytes that the compression adds in front of every payload
+pub(crate) const COMPRESSION_HEADER_BYTES: usize = 1;
+
+/// Header byte for a payload that is sent as-is
+const UNCOMPRESSED: u8 = 0;
+/// Header byte for a payload that has been compressed
+const COMPRESSED: u8 = 1;
+
+/// Default compression level used by zstd
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// Algorithm used to compress the payload of the packets sent through the transport.
+///
+/// The client and the server must use the same [`CompressionConfig`].
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub enum CompressionConfig {
+    /// Compress packets using [LZ4](https://github.com/lz4/lz4), which is very fast but has a lower compression ratio
+    Lz4,
+    /// Compress packets using [zstd](https://github.com/facebook/zstd)
+    Zstd {
+        /// The compression level (between 1 and 22)
+        level: i32,
+    },
+    /// Compress packets using zstd with a pre-trained dictionary.
+    ///
+    /// Dictionaries greatly improve the compression ratio of small payloads that share a lot of structure,
+    /// such as replication updates. A dictionary can be trained from captured packets with `zstd --train`.
+    ZstdDictionary {
+        /// The compression level (between 1 and 22)
+        level: i32,
+        /// The pre-trained dictionary. It must be identical on the client and the server.
+        dictionary: Vec<u8>,
+    },
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig::Zstd {
+            level: DEFAULT_ZSTD_LEVEL,
+        }
+    }
+}
+
+enum CompressorKind {
+    Lz4,
+    Zstd(zstd::bulk::Compressor<'static>),
+}
+
+/// Compresses payloads according to a [`CompressionConfig`]
+pub(crate) struct Compressor {
+    kind: CompressorKind,
+    buffer: Vec<u8>,
+}
+
+impl Compressor {
+    pub(crate) fn new(config: &CompressionConfig) -> Result<Self> {
+        let kind = match config {
+            CompressionConfig::Lz4 => CompressorKind::Lz4,
+            CompressionConfig::Zstd { level } => {
+                CompressorKind::Zstd(zstd::bulk::Compressor::new(*level)?)
+            }
+            CompressionConfig::ZstdDictionary { level, dictionary } => {
+                CompressorKind::Zstd(zstd::bulk::Compressor::with_dictionary(*level, dictionary)?)
+            }
+        };
+        Ok(Self {
+            kind,
+            buffer: Vec::with_capacity(MTU),
+        })
+    }
+
+    /// Compress the payload into `output` (header included), and return the number of bytes written.
+    ///
+    /// `output` must be at least [`COMPRESSION_HEADER_BYTES`] bigger than the payload.
+    pub(crate) fn compress(&mut self, payload: &[u8], output: &mut [u8]) -> Result<usize> {
+        let max_output_size = match self.kind {
+            CompressorKind::Lz4 => lz4_flex::block::get_maximum_output_size(payload.len()),
+            CompressorKind::Zstd(_) => zstd::zstd_safe::compress_bound(payload.len()),
+        };
+        self.buffer.clear();
+        self.buffer.resize(max_output_size, 0);
+        let compressed_len = match &mut self.kind {
+            CompressorKind::Lz4 => lz4_flex::block::compress_into(payload, &mut self.buffer)?,
+            CompressorKind::Zstd(compressor) => {
+                compressor.compress_to_buffer(payload, &mut self.buffer)?
+            }
+        };
+        // compression is not worth it, send the payload as is
+        let (header, body) = if compressed_len < payload.len() {
+            (COMPRESSED, &self.buffer[..compressed_len])
+        } else {
+            (UNCOMPRESSED, payload)
+        };
+        let len = COMPRESSION_HEADER_BYTES + body.len();
+        let Some(output) = output.get_mut(..len) else {
+            return Err(std::io::Error::other("the compression output buffer is too small").into());
+        };
+        output[0] = header;
+        output[COMPRESSION_HEADER_BYTES..].copy_from_slice(body);
+        Ok(len)
+    }
+}
+
+enum DecompressorKind {
+    Lz4,
+    Zstd(zstd::bulk::Decompressor<'static>),
+}
+
+/// Decompresses payloads that were compressed by a [`Compressor`]
+pub(crate) struct Decompressor {
+    kind: DecompressorKind,
+    buffer: Vec<u8>,
+    /// Number of bytes received since the last call to [`take_received_bytes`](Self::take_received_bytes)
+    received_bytes: usize,
+    /// Number of bytes decompressed since the last call to [`take_received_bytes`](Self::take_received_bytes)
+    decompressed_bytes: usize,
+}
+
+impl Decompressor {
+    pub(crate) fn new(config: &CompressionConfig) -> Result<Self> {
+        let kind = match config {
+            CompressionConfig::Lz4 => DecompressorKind::Lz4,
+            CompressionConfig::Zstd { .. } => {
+                DecompressorKind::Zstd(zstd::bulk::Decompressor::new()?)
+            }
+            CompressionConfig::ZstdDictionary { dictionary, .. } => {
+                DecompressorKind::Zstd(zstd::bulk::Decompressor::with_dictionary(dictionary)?)
+            }
+        };
+        Ok(Self {
+            kind,
+            buffer: vec![0; MTU],
+            received_bytes: 0,
+            decompressed_bytes: 0,
+        })
+    }
+
+    /// Decompress a payload written by [`Compressor::compress`], and return the original payload
+    pub(crate) fn decompress<'a>(&'a mut self, data: &'a [u8]) -> Result<&'a [u8]> {
+        let payload = match data.split_first() {
+            Some((&UNCOMPRESSED, payload)) => payload,
+            Some((&COMPRESSED, payload)) => {
+                let len = match &mut self.kind {
+                    DecompressorKind::Lz4 => {
+                        lz4_flex::block::decompress_into(payload, &mut self.buffer[..])?
+                    }
+                    DecompressorKind::Zstd(decompressor) => {
+                        decompressor.decompress_to_buffer(payload, &mut self.buffer[..])?
+                    }
+                };
+                &self.buffer[..len]
+            }
+            _ => {
+                return Err(std::io::Error::other("invalid compression header").into());
+            }
+        };
+        self.received_bytes += data.len();
+        self.decompressed_bytes += payload.len();
+        Ok(payload)
+    }
+
+    /// Return the number of bytes given to [`decompress`](Self::decompress) and the number of bytes it returned
+    /// since the last call
+    pub(crate) fn take_received_bytes(&mut self) -> (usize, usize) {
+        (
+            std::mem::take(&mut self.received_bytes),
+            std::mem::take(&mut self.decompressed_bytes),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::connection::netcode::{generate_key, NetcodeClient, NetcodeServer};
+    use crate::packet::message::SingleData;
+    use crate::packet::packet_manager::PacketBuilder;
+    use crate::prelude::{IoConfig, TransportConfig};
+    use crate::transport::io::Io;
+    use crate::transport::LOCAL_SOCKET;
+
+    use super::*;
+
+    fn repetitive_payload() -> Vec<u8> {
+        (0..1000).map(|i| (i % 7) as u8).collect()
+    }
+
+    fn roundtrip(config: CompressionConfig) {
+        let mut compressor = Compressor::new(&config).unwrap();
+        let mut decompressor = Decompressor::new(&config).unwrap();
+
+        let mut output = [0; 2000];
+
+        // repetitive payloads get compressed
+        let payload = repetitive_payload();
+        let compressed_len = compressor.compress(&payload, &mut output).unwrap();
+        assert_eq!(output[0], COMPRESSED);
+        assert!(compressed_len < payload.len());
+        assert_eq!(
+            decompressor.decompress(&output[..compressed_len]).unwrap(),
+            payload.as_slice()
+        );
+
+        // payloads that don't shrink are sent as-is
+        let payload = [1, 2, 3];
+        let len = compressor.compress(&payload, &mut output).unwrap();
+        assert_eq!(&output[..len], &[UNCOMPRESSED, 1, 2, 3]);
+        assert_eq!(decompressor.decompress(&output[..len]).unwrap(), &payload);
+        assert_eq!(
+            decompressor.take_received_bytes(),
+            (compressed_len + len, 1003)
+        );
+
+        // invalid packets return an error
+        assert!(decompressor.decompress(&[COMPRESSED, 1, 2, 3]).is_err());
+        assert!(decompressor.decompress(&[2, 1, 2, 3]).is_err());
+        assert!(decompressor.decompress(&[]).is_err());
+    }
+
+    #[test]
+    fn test_lz4_roundtrip() {
+        roundtrip(CompressionConfig::Lz4);
+    }
+
+    #[test]
+    fn test_zstd_roundtrip() {
+        roundtrip(CompressionConfig::Zstd {
+            level: DEFAULT_ZSTD_LEVEL,
+        });
+    }
+
+    #[test]
+    fn test_zstd_dictionary_roundtrip() {
+        // a raw-content dictionary: any bytes can be used as a dictionary
+        roundtrip(CompressionConfig::ZstdDictionary {
+            level: DEFAULT_ZSTD_LEVEL,
+            dictionary: repetitive_payload(),
+        });
+    }
+
+    /// Connect a netcode client and server that compress their payloads
+    fn netcode_pair(config: CompressionConfig) -> (NetcodeClient, Io, NetcodeServer, Io) {
+        let (to_server_send, to_server_recv) = crossbeam_channel::unbounded();
+        let (from_server_send, from_server_recv) = crossbeam_channel::unbounded();
+        let mut client_io = IoConfig::from_transport(TransportConfig::LocalChannel {
+            recv: from_server_recv,
+            send: to_server_send,
+        })
+        .with_compression(config.clone())
+        .build();
+        let mut server_io = IoConfig::from_transport(TransportConfig::Channels {
+            channels: vec![(LOCAL_SOCKET, to_server_recv, from_server_send)],
+        })
+        .with_compression(config)
+        .build();
+        client_io.connect().unwrap();
+        server_io.connect().unwrap();
+
+        let mut server = NetcodeServer::new(0, generate_key()).unwrap();
+        server.set_compression(server_io.compression()).unwrap();
+        let token = server
+            .token(1, LOCAL_SOCKET)
+            .generate()
+            .unwrap()
+            .try_into_bytes()
+            .unwrap();
+        let mut client = NetcodeClient::new(&token).unwrap();
+        client.set_compression(client_io.compression()).unwrap();
+        client.connect();
+        for _ in 0..10 {
+            client.update(1.0, &mut client_io);
+            server.update(1.0, &mut server_io);
+        }
+        assert!(client.is_connected());
+        (client, client_io, server, server_io)
+    }
+
+    #[test]
+    fn test_compression_through_netcode() {
+        let (mut client, mut client_io, mut server, mut server_io) =
+            netcode_pair(CompressionConfig::default());
+
+        let mut builder = PacketBuilder::new();
+        let mut packet = builder.build_new_single_packet();
+        packet.add_message(0, SingleData::new(None, Bytes::from(vec![7u8; 1000]), 1.0));
+        let payload = builder.encode_packet(&packet).unwrap();
+
+        let bytes_sent = client_io.stats().bytes_sent;
+        let uncompressed_bytes_sent = client_io.stats().uncompressed_bytes_sent;
+        client.send(&payload, &mut client_io).unwrap();
+        // the plaintext payload was compressed before being encrypted
+        let wire_bytes = client_io.stats().bytes_sent - bytes_sent;
+        assert!(wire_bytes < payload.len() / 2);
+        assert!(
+            client_io.stats().uncompressed_bytes_sent - uncompressed_bytes_sent > payload.len()
+        );
+
+        let bytes_received = server_io.stats().bytes_received;
+        let uncompressed_bytes_received = server_io.stats().uncompressed_bytes_received;
+        server.update(1.0, &mut server_io);
+        assert_eq!(
+            server_io.stats().bytes_received - bytes_received,
+            wire_bytes
+        );
+        assert_eq!(
+            server_io.stats().uncompressed_bytes_received - uncompressed_bytes_received,
+            client_io.stats().uncompressed_bytes_sent - uncompressed_bytes_sent
+        );
+        let (received, _) = server.recv().unwrap();
+        assert_eq!(received.num_messages(), 1);
+    }
+}
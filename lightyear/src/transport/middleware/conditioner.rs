@@ -1,15 +1,17 @@
 //! Contains the `LinkConditioner` struct which can be used to simulate network conditions
+use bevy::prelude::Resource;
 use bevy::reflect::Reflect;
 use std::net::SocketAddr;
 
 use bevy::utils::Duration;
 use cfg_if::cfg_if;
 use rand;
+use rand::rngs::ThreadRng;
 use rand::{thread_rng, Rng};
 
 use crate::transport::error::Result;
 use crate::transport::middleware::PacketReceiverWrapper;
-use crate::transport::PacketReceiver;
+use crate::transport::{PacketReceiver, PacketSender};
 use crate::utils::ready_buffer::ReadyBuffer;
 
 cfg_if! {
@@ -20,8 +22,13 @@ cfg_if! {
     }
 }
 
+/// Packets that would have to wait longer than this in the bandwidth-limited queue are dropped
+const MAX_BANDWIDTH_QUEUE_DELAY: Duration = Duration::from_secs(1);
+/// Minimum additional delay applied to a packet that is being reordered
+const MIN_REORDER_DELAY: Duration = Duration::from_millis(10);
+
 /// Contains configuration required to initialize a LinkConditioner
-#[derive(Clone, Debug, Reflect)]
+#[derive(Clone, Debug, Default, PartialEq, Reflect)]
 pub struct LinkConditionerConfig {
     /// Delay to receive incoming messages in milliseconds (half the RTT)
     pub incoming_latency: Duration,
@@ -32,41 +39,243 @@ pub struct LinkConditionerConfig {
     /// The % chance that an incoming packet will be dropped.
     /// Represented as a value between 0 and 1
     pub incoming_loss: f32,
+    /// The % chance that an incoming packet will be received twice.
+    /// Represented as a value between 0 and 1
+    pub incoming_duplication: f32,
+    /// The % chance that an incoming packet will be held back for an additional random delay,
+    /// so that it arrives after packets that were sent later.
+    /// Represented as a value between 0 and 1
+    pub incoming_reorder: f32,
+    /// Bursty packet loss applied to incoming packets, in addition to `incoming_loss`
+    pub incoming_burst_loss: Option<BurstLossConfig>,
+    /// Delay to send outgoing messages in milliseconds (half the RTT)
+    pub outgoing_latency: Duration,
+    /// The maximum additional random latency to delay outgoing messages.
+    /// This may be added OR subtracted from `outgoing_latency`
+    pub outgoing_jitter: Duration,
+    /// The % chance that an outgoing packet will be dropped.
+    /// Represented as a value between 0 and 1
+    pub outgoing_loss: f32,
+    /// The % chance that an outgoing packet will be sent twice.
+    /// Represented as a value between 0 and 1
+    pub outgoing_duplication: f32,
+    /// The % chance that an outgoing packet will be held back for an additional random delay,
+    /// so that it is sent after packets that were sent later.
+    /// Represented as a value between 0 and 1
+    pub outgoing_reorder: f32,
+    /// Bursty packet loss applied to outgoing packets, in addition to `outgoing_loss`
+    pub outgoing_burst_loss: Option<BurstLossConfig>,
+    /// Maximum upload bandwidth in bytes per second.
+    ///
+    /// Outgoing packets are queued until the link has enough capacity to send them;
+    /// packets that would have to wait for more than 1 second are dropped.
+    pub outgoing_bandwidth: Option<u32>,
+}
+
+/// Parameters of the [Gilbert-Elliott](https://en.wikipedia.org/wiki/Burst_error) model,
+/// used to simulate bursts of packet loss.
+///
+/// The link alternates between a 'good' state and a 'bad' state. A state transition can happen
+/// for every packet, and each state has its own probability of dropping packets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct BurstLossConfig {
+    /// The % chance to switch from the good state to the bad state, for every packet
+    pub good_to_bad: f32,
+    /// The % chance to switch from the bad state to the good state, for every packet
+    pub bad_to_good: f32,
+    /// The % chance that a packet is dropped while in the good state
+    pub good_loss: f32,
+    /// The % chance that a packet is dropped while in the bad state
+    pub bad_loss: f32,
+}
+
+impl BurstLossConfig {
+    /// Creates a new BurstLossConfig where every packet is dropped while in the bad state
+    pub fn new(good_to_bad: f32, bad_to_good: f32) -> Self {
+        Self {
+            good_to_bad,
+            bad_to_good,
+            good_loss: 0.0,
+            bad_loss: 1.0,
+        }
+    }
+}
+
+/// Resource that can be used to change the network conditions at runtime.
+///
+/// Whenever this resource is inserted or modified, the new [`LinkConditionerConfig`] is applied to
+/// the [`Io`](crate::transport::io::Io) of the client, or of every server connection.
+/// Setting it to `None` removes the link conditioners.
+///
+/// The Steam and QUIC connections don't have an [`Io`](crate::transport::io::Io): their network conditions can only be set
+/// when the connection is created, and a warning is logged when this resource changes.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Reflect)]
+pub struct LinkConditionerSettings(pub Option<LinkConditionerConfig>);
+
+/// Direction of the packets that a [`LinkConditioner`] is applied to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ConditionerDirection {
+    Incoming,
+    Outgoing,
+}
+
+/// The network conditions that apply to packets going in a given direction
+#[derive(Clone, Copy, Debug)]
+struct DirectionConditions {
+    latency: Duration,
+    jitter: Duration,
+    loss: f32,
+    duplication: f32,
+    reorder: f32,
+    burst_loss: Option<BurstLossConfig>,
+    bandwidth: Option<u32>,
+}
+
+impl DirectionConditions {
+    fn new(config: &LinkConditionerConfig, direction: ConditionerDirection) -> Self {
+        match direction {
+            ConditionerDirection::Incoming => DirectionConditions {
+                latency: config.incoming_latency,
+                jitter: config.incoming_jitter,
+                loss: config.incoming_loss,
+                duplication: config.incoming_duplication,
+                reorder: config.incoming_reorder,
+                burst_loss: config.incoming_burst_loss,
+                bandwidth: None,
+            },
+            ConditionerDirection::Outgoing => DirectionConditions {
+                latency: config.outgoing_latency,
+                jitter: config.outgoing_jitter,
+                loss: config.outgoing_loss,
+                duplication: config.outgoing_duplication,
+                reorder: config.outgoing_reorder,
+                burst_loss: config.outgoing_burst_loss,
+                bandwidth: config.outgoing_bandwidth,
+            },
+        }
+    }
+
+    /// Returns true if these conditions change the packets in any way
+    fn is_active(&self) -> bool {
+        self.latency > Duration::ZERO
+            || self.jitter > Duration::ZERO
+            || self.loss > 0.0
+            || self.duplication > 0.0
+            || self.reorder > 0.0
+            || self.burst_loss.is_some()
+            || self.bandwidth.is_some_and(|b| b > 0)
+    }
 }
 
 pub(crate) type PacketLinkConditioner = LinkConditioner<(SocketAddr, Box<[u8]>)>;
 
 pub(crate) struct LinkConditioner<P: Eq> {
     config: LinkConditionerConfig,
+    direction: ConditionerDirection,
     pub time_queue: ReadyBuffer<Instant, P>,
     last_packet: Option<P>,
+    /// Whether the Gilbert-Elliott model is currently in the bad state
+    in_bad_state: bool,
+    /// Instant at which the bandwidth-limited link will be done sending the queued packets
+    link_busy_until: Option<Instant>,
 }
 
-impl<P: Eq> LinkConditioner<P> {
+impl<P: Eq + Clone> LinkConditioner<P> {
+    /// Create a conditioner for incoming packets
     pub fn new(config: LinkConditionerConfig) -> Self {
+        Self::with_direction(config, ConditionerDirection::Incoming)
+    }
+
+    pub fn with_direction(config: LinkConditionerConfig, direction: ConditionerDirection) -> Self {
         LinkConditioner {
             config,
+            direction,
             time_queue: ReadyBuffer::new(),
             last_packet: None,
+            in_bad_state: false,
+            link_busy_until: None,
         }
     }
 
-    /// Add latency/jitter/loss to a packet
-    fn condition_packet(&mut self, packet: P) {
+    /// Update the network conditions. Packets that are already queued are not affected.
+    pub fn set_config(&mut self, config: LinkConditionerConfig) {
+        self.config = config;
+    }
+
+    fn conditions(&self) -> DirectionConditions {
+        DirectionConditions::new(&self.config, self.direction)
+    }
+
+    /// Run the Gilbert-Elliott model for one packet, and return true if the packet should be dropped
+    fn burst_loss(&mut self, config: &BurstLossConfig, rng: &mut ThreadRng) -> bool {
+        let transition = if self.in_bad_state {
+            config.bad_to_good
+        } else {
+            config.good_to_bad
+        };
+        if rng.gen_range(0.0..1.0) < transition {
+            self.in_bad_state = !self.in_bad_state;
+        }
+        let loss = if self.in_bad_state {
+            config.bad_loss
+        } else {
+            config.good_loss
+        };
+        rng.gen_range(0.0..1.0) < loss
+    }
+
+    /// Compute the instant at which a packet should be delivered, including latency, jitter and reordering
+    fn delivery_time(
+        conditions: &DirectionConditions,
+        mut timestamp: Instant,
+        rng: &mut ThreadRng,
+    ) -> Instant {
+        let mut latency: i32 = conditions.latency.as_millis() as i32;
+        if conditions.jitter > Duration::default() {
+            let jitter: i32 = conditions.jitter.as_millis() as i32;
+            latency += rng.gen_range(-jitter..jitter);
+        }
+        if rng.gen_range(0.0..1.0) < conditions.reorder {
+            let max_delay = conditions.latency.max(MIN_REORDER_DELAY).as_millis() as i32;
+            latency += rng.gen_range(1..=max_delay);
+        }
+        if latency > 0 {
+            timestamp += Duration::from_millis(latency as u64);
+        }
+        timestamp
+    }
+
+    /// Add latency/jitter/loss/duplication/reordering to a packet of `size` bytes
+    fn condition_packet(&mut self, packet: P, size: usize) {
         let mut rng = thread_rng();
-        if rng.gen_range(0.0..1.0) <= self.config.incoming_loss {
+        let conditions = self.conditions();
+        if rng.gen_range(0.0..1.0) <= conditions.loss {
             return;
         }
-        let mut latency: i32 = self.config.incoming_latency.as_millis() as i32;
+        if let Some(burst_loss) = conditions.burst_loss {
+            if self.burst_loss(&burst_loss, &mut rng) {
+                return;
+            }
+        }
         // TODO: how can i use the virtual time here?
         let mut packet_timestamp = Instant::now();
-        if self.config.incoming_jitter > Duration::default() {
-            let jitter: i32 = self.config.incoming_jitter.as_millis() as i32;
-            latency += rng.gen_range(-jitter..jitter);
+        if let Some(bandwidth) = conditions.bandwidth.filter(|b| *b > 0) {
+            // the packet can only start being sent once the previous packets have been sent
+            let start = self
+                .link_busy_until
+                .map_or(packet_timestamp, |t| t.max(packet_timestamp));
+            if start > packet_timestamp + MAX_BANDWIDTH_QUEUE_DELAY {
+                return;
+            }
+            packet_timestamp = start + Duration::from_secs_f64(size as f64 / bandwidth as f64);
+            self.link_busy_until = Some(packet_timestamp);
         }
-        if latency > 0 {
-            packet_timestamp += Duration::from_millis(latency as u64);
+        if rng.gen_range(0.0..1.0) < conditions.duplication {
+            let duplicate_timestamp = Self::delivery_time(&conditions, packet_timestamp, &mut rng);
+            self.time_queue
+                .add_item(duplicate_timestamp, packet.clone());
         }
+        let packet_timestamp = Self::delivery_time(&conditions, packet_timestamp, &mut rng);
         self.time_queue.add_item(packet_timestamp, packet);
     }
 
@@ -78,6 +287,68 @@ impl<P: Eq> LinkConditioner<P> {
     }
 }
 
+impl PacketLinkConditioner {
+    /// Receive all available packets from the `receiver` and add conditioning to them,
+    /// then return the next packet that is ready to be delivered
+    pub(crate) fn recv_from(
+        &mut self,
+        receiver: &mut impl PacketReceiver,
+    ) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        loop {
+            // keep trying to receive packets from the inner packet receiver
+            let option = receiver.recv()?;
+            match option {
+                None => break,
+                // add conditioning (put the packets in the time queue)
+                Some((data, addr)) => {
+                    let size = data.len();
+                    self.condition_packet((addr, data.to_vec().into_boxed_slice()), size)
+                }
+            }
+        }
+        // only return a packet if it is ready to be returned
+        match self.pop_packet() {
+            Some((addr, data)) => {
+                // we use `last_packet` to get ownership of the data
+                self.last_packet = Some((addr, data));
+                Ok(Some((self.last_packet.as_mut().unwrap().1.as_mut(), addr)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Add conditioning to an outgoing packet, then send all the packets that are ready through the `sender`
+    pub(crate) fn send_to(
+        &mut self,
+        payload: &[u8],
+        address: &SocketAddr,
+        sender: &mut impl PacketSender,
+    ) -> Result<()> {
+        self.condition_packet(
+            (*address, payload.to_vec().into_boxed_slice()),
+            payload.len(),
+        );
+        self.flush(sender)
+    }
+
+    /// Send all the outgoing packets that are ready through the `sender`
+    pub(crate) fn flush(&mut self, sender: &mut impl PacketSender) -> Result<()> {
+        while let Some((addr, data)) = self.pop_packet() {
+            sender.send(&data, &addr)?;
+        }
+        Ok(())
+    }
+
+    /// Send all the queued outgoing packets through the `sender`, without waiting for their delivery time
+    pub(crate) fn flush_all(&mut self, sender: &mut impl PacketSender) -> Result<()> {
+        while let Some(item) = self.time_queue.heap.pop() {
+            let (addr, data) = item.item;
+            sender.send(&data, &addr)?;
+        }
+        Ok(())
+    }
+}
+
 impl<T: PacketReceiver> PacketReceiverWrapper<T> for LinkConditioner<(SocketAddr, Box<[u8]>)> {
     fn wrap(self, receiver: T) -> impl PacketReceiver {
         ConditionedPacketReceiver {
@@ -96,42 +367,66 @@ pub struct ConditionedPacketReceiver<T: PacketReceiver, P: Eq> {
 
 impl<T: PacketReceiver> PacketReceiver for ConditionedPacketReceiver<T, (SocketAddr, Box<[u8]>)> {
     fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
-        loop {
-            // keep trying to receive packets from the inner packet receiver
-            let option = self.packet_receiver.recv()?;
-            match option {
-                None => break,
-                // add conditioning (put the packets in the time queue)
-                Some((data, addr)) => self
-                    .conditioner
-                    .condition_packet((addr, data.to_vec().into_boxed_slice())),
-            }
-        }
-        // only return a packet if it is ready to be returned
-        match self.conditioner.pop_packet() {
-            Some((addr, data)) => {
-                // we use `last_packet` to get ownership of the data
-                self.conditioner.last_packet = Some((addr, data));
-                Ok(Some((
-                    self.conditioner.last_packet.as_mut().unwrap().1.as_mut(),
-                    addr,
-                )))
-            }
-            None => Ok(None),
-        }
+        self.conditioner.recv_from(&mut self.packet_receiver)
     }
 }
 
 impl LinkConditionerConfig {
+    /// Returns true if the config changes the packets going in the given direction
+    pub(crate) fn has_conditions(&self, direction: ConditionerDirection) -> bool {
+        DirectionConditions::new(self, direction).is_active()
+    }
+
     /// Creates a new LinkConditionerConfig
     pub fn new(incoming_latency: Duration, incoming_jitter: Duration, incoming_loss: f32) -> Self {
         LinkConditionerConfig {
             incoming_latency,
             incoming_jitter,
             incoming_loss,
+            ..Default::default()
         }
     }
 
+    /// Also add latency, jitter and packet loss to outgoing packets
+    pub fn with_outgoing(
+        mut self,
+        outgoing_latency: Duration,
+        outgoing_jitter: Duration,
+        outgoing_loss: f32,
+    ) -> Self {
+        self.outgoing_latency = outgoing_latency;
+        self.outgoing_jitter = outgoing_jitter;
+        self.outgoing_loss = outgoing_loss;
+        self
+    }
+
+    /// Cap the upload bandwidth, in bytes per second
+    pub fn with_outgoing_bandwidth(mut self, bytes_per_second: u32) -> Self {
+        self.outgoing_bandwidth = Some(bytes_per_second);
+        self
+    }
+
+    /// Duplicate packets in both directions
+    pub fn with_duplication(mut self, incoming: f32, outgoing: f32) -> Self {
+        self.incoming_duplication = incoming;
+        self.outgoing_duplication = outgoing;
+        self
+    }
+
+    /// Reorder packets in both directions
+    pub fn with_reorder(mut self, incoming: f32, outgoing: f32) -> Self {
+        self.incoming_reorder = incoming;
+        self.outgoing_reorder = outgoing;
+        self
+    }
+
+    /// Add bursty packet loss in both directions
+    pub fn with_burst_loss(mut self, burst_loss: BurstLossConfig) -> Self {
+        self.incoming_burst_loss = Some(burst_loss);
+        self.outgoing_burst_loss = Some(burst_loss);
+        self
+    }
+
     /// Creates a new LinkConditioner that simulates a connection which is in a
     /// good condition
     pub fn good_condition() -> Self {
@@ -139,6 +434,7 @@ impl LinkConditionerConfig {
             incoming_latency: Duration::from_millis(40),
             incoming_jitter: Duration::from_millis(6),
             incoming_loss: 0.002,
+            ..Default::default()
         }
     }
 
@@ -149,6 +445,7 @@ impl LinkConditionerConfig {
             incoming_latency: Duration::from_millis(170),
             incoming_jitter: Duration::from_millis(45),
             incoming_loss: 0.02,
+            ..Default::default()
         }
     }
 
@@ -159,6 +456,108 @@ impl LinkConditionerConfig {
             incoming_latency: Duration::from_millis(300),
             incoming_jitter: Duration::from_millis(84),
             incoming_loss: 0.04,
+            ..Default::default()
         }
     }
+
+    /// Creates a new `LinkConditioner` that simulates a congested Wi-Fi or mobile connection,
+    /// with bursts of packet loss, reordering and a limited upload bandwidth
+    pub fn bursty_wireless_condition() -> Self {
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(60),
+            incoming_jitter: Duration::from_millis(30),
+            incoming_loss: 0.005,
+            incoming_reorder: 0.01,
+            incoming_burst_loss: Some(BurstLossConfig::new(0.01, 0.3)),
+            outgoing_latency: Duration::from_millis(60),
+            outgoing_jitter: Duration::from_millis(30),
+            outgoing_loss: 0.005,
+            outgoing_reorder: 0.01,
+            outgoing_burst_loss: Some(BurstLossConfig::new(0.01, 0.3)),
+            outgoing_bandwidth: Some(128_000),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mock_instant::MockClock;
+
+    use super::*;
+
+    type TestConditioner = LinkConditioner<(SocketAddr, Box<[u8]>)>;
+
+    fn packet(i: u8) -> (SocketAddr, Box<[u8]>) {
+        (
+            SocketAddr::from(([127, 0, 0, 1], 1000)),
+            vec![i].into_boxed_slice(),
+        )
+    }
+
+    #[test]
+    fn test_duplication() {
+        let mut conditioner = TestConditioner::new(LinkConditionerConfig {
+            incoming_duplication: 1.0,
+            ..Default::default()
+        });
+        conditioner.condition_packet(packet(0), 1);
+        assert_eq!(conditioner.pop_packet(), Some(packet(0)));
+        assert_eq!(conditioner.pop_packet(), Some(packet(0)));
+        assert_eq!(conditioner.pop_packet(), None);
+    }
+
+    #[test]
+    fn test_burst_loss() {
+        // we always switch to the bad state, and drop every packet in the bad state
+        let mut conditioner = TestConditioner::new(LinkConditionerConfig {
+            incoming_burst_loss: Some(BurstLossConfig::new(1.0, 0.0)),
+            ..Default::default()
+        });
+        for i in 0..10 {
+            conditioner.condition_packet(packet(i), 1);
+        }
+        assert!(conditioner.in_bad_state);
+        assert_eq!(conditioner.pop_packet(), None);
+    }
+
+    #[test]
+    fn test_outgoing_bandwidth() {
+        // 1 byte per millisecond
+        let mut conditioner = TestConditioner::with_direction(
+            LinkConditionerConfig::default().with_outgoing_bandwidth(1000),
+            ConditionerDirection::Outgoing,
+        );
+        conditioner.condition_packet(packet(0), 10);
+        conditioner.condition_packet(packet(1), 10);
+        assert_eq!(conditioner.pop_packet(), None);
+
+        MockClock::advance(Duration::from_millis(10));
+        assert_eq!(conditioner.pop_packet(), Some(packet(0)));
+        assert_eq!(conditioner.pop_packet(), None);
+
+        MockClock::advance(Duration::from_millis(10));
+        assert_eq!(conditioner.pop_packet(), Some(packet(1)));
+
+        // the bandwidth cap only applies to outgoing packets
+        let mut conditioner =
+            TestConditioner::new(LinkConditionerConfig::default().with_outgoing_bandwidth(1000));
+        conditioner.condition_packet(packet(0), 10);
+        assert_eq!(conditioner.pop_packet(), Some(packet(0)));
+    }
+
+    #[test]
+    fn test_has_conditions() {
+        let config = LinkConditionerConfig::default();
+        assert!(!config.has_conditions(ConditionerDirection::Incoming));
+        assert!(!config.has_conditions(ConditionerDirection::Outgoing));
+
+        let config = LinkConditionerConfig::good_condition();
+        assert!(config.has_conditions(ConditionerDirection::Incoming));
+        assert!(!config.has_conditions(ConditionerDirection::Outgoing));
+
+        let config = LinkConditionerConfig::default().with_outgoing_bandwidth(1000);
+        assert!(!config.has_conditions(ConditionerDirection::Incoming));
+        assert!(config.has_conditions(ConditionerDirection::Outgoing));
+    }
 }
@@ -3,6 +3,14 @@
 //! Wrappers are used to add additional functionality to an existing transport, such as encryption, compression, metrics, etc.
 use crate::transport::{PacketReceiver, PacketSender};
 
+/// Capture is used to record the packets sent and received, so that they can be replayed later.
+#[cfg_attr(docsrs, doc(cfg(not(target_family = "wasm"))))]
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod capture;
+/// Compression is used to reduce the size of the packets sent on the wire.
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+#[cfg(feature = "compression")]
+pub(crate) mod compression;
 /// A conditioner is used to simulate network conditions such as latency, jitter and packet loss.
 pub(crate) mod conditioner;
 
@@ -10,7 +10,24 @@ use crate::transport::channels::Channels;
 use crate::transport::dummy::DummyIo;
 use crate::transport::local::{LocalChannel, LocalChannelBuilder};
 #[cfg(not(target_family = "wasm"))]
+use crate::transport::relay::transport::{RelaySocket, RelaySocketBuilder};
+#[cfg(not(target_family = "wasm"))]
+use crate::transport::replay::{Replay, ReplayBuilder};
+#[cfg(not(target_family = "wasm"))]
+use crate::transport::tcp::{
+    TcpClientSocket, TcpClientSocketBuilder, TcpServerSocket, TcpServerSocketBuilder,
+};
+#[cfg(not(target_family = "wasm"))]
 use crate::transport::udp::{UdpSocket, UdpSocketBuilder};
+#[cfg(target_os = "linux")]
+use crate::transport::unix::seqpacket::{
+    UnixSeqpacketClient, UnixSeqpacketClientBuilder, UnixSeqpacketServer,
+    UnixSeqpacketServerBuilder,
+};
+#[cfg(unix)]
+use crate::transport::unix::{
+    UnixSocketClient, UnixSocketClientBuilder, UnixSocketServer, UnixSocketServerBuilder,
+};
 #[cfg(feature = "websocket")]
 use crate::transport::websocket::client::{WebSocketClientSocket, WebSocketClientSocketBuilder};
 #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
@@ -35,9 +52,29 @@ pub(crate) mod local;
 #[cfg(not(target_family = "wasm"))]
 pub(crate) mod udp;
 
+/// The transport is a TCP stream
+#[cfg_attr(docsrs, doc(cfg(not(target_family = "wasm"))))]
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod tcp;
+
+/// The transport is a Unix domain datagram (or seqpacket) socket
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+#[cfg(unix)]
+pub(crate) mod unix;
+
+/// The transport goes through a relay server
+#[cfg_attr(docsrs, doc(cfg(not(target_family = "wasm"))))]
+#[cfg(not(target_family = "wasm"))]
+pub mod relay;
+
 /// The transport is a map of channels (used for server, during testing)
 pub(crate) mod channels;
 
+/// The transport plays back a packet capture
+#[cfg_attr(docsrs, doc(cfg(not(target_family = "wasm"))))]
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod replay;
+
 /// The transport is using WebTransport
 #[cfg_attr(docsrs, doc(cfg(feature = "webtransport")))]
 #[cfg(feature = "webtransport")]
@@ -57,8 +94,14 @@ pub const LOCAL_SOCKET: SocketAddr = SocketAddr::new(
     0,
 );
 /// Maximum transmission units; maximum size in bytes of a UDP packet
+///
+/// With the `jumbo_frames` feature, this is the payload of a jumbo frame (9000 bytes minus the IP and UDP headers),
+/// so that the receive buffers can hold any packet allowed by the path MTU discovery.
 /// See: <https://gafferongames.com/post/packet_fragmentation_and_reassembly/>
+#[cfg(not(feature = "jumbo_frames"))]
 pub(crate) const MTU: usize = 1472;
+#[cfg(feature = "jumbo_frames")]
+pub(crate) const MTU: usize = 8972;
 
 pub(crate) type BoxedSender = Box<dyn PacketSender + Send + Sync>;
 pub(crate) type BoxedReceiver = Box<dyn PacketReceiver + Send + Sync>;
@@ -96,6 +139,20 @@ pub(crate) trait Transport {
 pub(crate) enum TransportBuilderEnum {
     #[cfg(not(target_family = "wasm"))]
     UdpSocket(UdpSocketBuilder),
+    #[cfg(not(target_family = "wasm"))]
+    TcpClient(TcpClientSocketBuilder),
+    #[cfg(not(target_family = "wasm"))]
+    TcpServer(TcpServerSocketBuilder),
+    #[cfg(unix)]
+    UnixSocketClient(UnixSocketClientBuilder),
+    #[cfg(unix)]
+    UnixSocketServer(UnixSocketServerBuilder),
+    #[cfg(target_os = "linux")]
+    UnixSeqpacketClient(UnixSeqpacketClientBuilder),
+    #[cfg(target_os = "linux")]
+    UnixSeqpacketServer(UnixSeqpacketServerBuilder),
+    #[cfg(not(target_family = "wasm"))]
+    Relay(RelaySocketBuilder),
     #[cfg(feature = "webtransport")]
     WebTransportClient(WebTransportClientSocketBuilder),
     #[cfg(all(feature = "webtransport", not(target_family = "wasm")))]
@@ -106,6 +163,8 @@ pub(crate) enum TransportBuilderEnum {
     WebSocketServer(WebSocketServerSocketBuilder),
     Channels(Channels),
     LocalChannel(LocalChannelBuilder),
+    #[cfg(not(target_family = "wasm"))]
+    Replay(ReplayBuilder),
     Dummy(DummyIo),
 }
 
@@ -121,6 +180,20 @@ pub(crate) enum TransportBuilderEnum {
 pub(crate) enum TransportEnum {
     #[cfg(not(target_family = "wasm"))]
     UdpSocket(UdpSocket),
+    #[cfg(not(target_family = "wasm"))]
+    TcpClient(TcpClientSocket),
+    #[cfg(not(target_family = "wasm"))]
+    TcpServer(TcpServerSocket),
+    #[cfg(unix)]
+    UnixSocketClient(UnixSocketClient),
+    #[cfg(unix)]
+    UnixSocketServer(UnixSocketServer),
+    #[cfg(target_os = "linux")]
+    UnixSeqpacketClient(UnixSeqpacketClient),
+    #[cfg(target_os = "linux")]
+    UnixSeqpacketServer(UnixSeqpacketServer),
+    #[cfg(not(target_family = "wasm"))]
+    Relay(RelaySocket),
     #[cfg(feature = "webtransport")]
     WebTransportClient(WebTransportClientSocket),
     #[cfg(all(feature = "webtransport", not(target_family = "wasm")))]
@@ -131,6 +204,8 @@ pub(crate) enum TransportEnum {
     WebSocketServer(WebSocketServerSocket),
     Channels(Channels),
     LocalChannel(LocalChannel),
+    #[cfg(not(target_family = "wasm"))]
+    Replay(Replay),
     Dummy(DummyIo),
 }
 
@@ -138,12 +213,24 @@ pub(crate) enum TransportEnum {
 pub trait PacketSender: Send + Sync {
     /// Send data on the socket to the remote address
     fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()>;
+
+    /// Send the packets that are buffered by the sender, if any.
+    ///
+    /// Senders that write to the network in batches only send the buffered packets
+    /// when the batch is full or when this is called.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
 impl PacketSender for BoxedSender {
     fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
         (**self).send(payload, address)
     }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
 }
 
 /// Receive data from a remote address
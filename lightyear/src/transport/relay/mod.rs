@@ -0,0 +1,246 @@
+//! The transport goes through a relay server, for hosts and clients that cannot reach each other directly
+//! (for example a player hosting a game behind a NAT).
+//!
+//! The host registers a session on the relay with a session id, and the clients send their packets to the relay
+//! with the same session id. The relay forwards the packets between the host and the clients of the session,
+//! without being able to read them (netcode packets are encrypted).
+//!
+//! Only the holder of the secret of a session (see [`session_secret`]) can host it, so that nobody can take over
+//! a session by registering its id first. The secrets are derived from the key of the relay, which is only known
+//! by the relay and by the backend that creates the sessions.
+//!
+//! Each packet exchanged with the relay starts with a small header:
+//! - `Register`: sent regularly by the host with the secret of the session, to create the session and keep it alive
+//! - `ToHost`: sent by a client, forwarded to the host of the session as `FromClient`
+//! - `ToClient`: sent by the host, forwarded to one of the clients of the session as `FromHost`
+//!
+//! The host sees the clients with their real address (as seen by the relay), while the clients see the host with
+//! the server address they send packets to.
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::connection::netcode::{chacha_decrypt, chacha_encrypt, Key, MAC_BYTES};
+
+/// Standalone relay server that forwards the packets between the host and the clients of each session
+pub mod server;
+
+/// Transport used by the host and the clients to communicate through the relay
+pub(crate) mod transport;
+
+/// Maximum size of the header added to the packets that carry a payload.
+///
+/// The packets built by the connection are smaller by this amount when going through a relay, so that
+/// the packets sent on the wire still fit in the MTU.
+pub(crate) const MAX_HEADER_BYTES: usize = 1 + 1 + 16 + 2;
+
+/// Size of the header of a `Register` packet, which does not carry a payload
+const REGISTER_BYTES: usize = 1 + 8 + MAC_BYTES;
+
+/// Secret that the host of a session presents to the relay, see [`session_secret`]
+pub type SessionSecret = [u8; MAC_BYTES];
+
+/// Returns the secret of the session `session_id` on the relay that uses `key`.
+///
+/// The secret should only be given to the host of the session (the clients do not need it).
+pub fn session_secret(key: &Key, session_id: u64) -> SessionSecret {
+    let mut secret = [0; MAC_BYTES];
+    // the secret is the authentication tag of an empty message, with the session id as associated data
+    chacha_encrypt(
+        &mut secret,
+        Some(&session_id.to_be_bytes()),
+        session_id,
+        key,
+    )
+    .expect("the buffer has room for the tag");
+    secret
+}
+
+/// Returns true if `secret` is the secret of the session `session_id` on the relay that uses `key`
+pub(crate) fn verify_session_secret(key: &Key, session_id: u64, secret: &SessionSecret) -> bool {
+    let mut secret = *secret;
+    chacha_decrypt(
+        &mut secret,
+        Some(&session_id.to_be_bytes()),
+        session_id,
+        key,
+    )
+    .is_ok()
+}
+
+const REGISTER: u8 = 0;
+const TO_HOST: u8 = 1;
+const TO_CLIENT: u8 = 2;
+const FROM_HOST: u8 = 3;
+const FROM_CLIENT: u8 = 4;
+
+/// Header of a packet exchanged with the relay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RelayHeader {
+    /// Host -> relay: create the session, or keep it alive
+    Register {
+        session_id: u64,
+        secret: SessionSecret,
+    },
+    /// Client -> relay: the payload is for the host of the session
+    ToHost { session_id: u64 },
+    /// Host -> relay: the payload is for the given client
+    ToClient { client_addr: SocketAddr },
+    /// Relay -> client: the payload comes from the host of the session
+    FromHost,
+    /// Relay -> host: the payload comes from the given client
+    FromClient { client_addr: SocketAddr },
+}
+
+impl RelayHeader {
+    /// Write the header at the end of the buffer
+    pub(crate) fn write(&self, buffer: &mut Vec<u8>) {
+        match self {
+            RelayHeader::Register { session_id, secret } => {
+                buffer.push(REGISTER);
+                buffer.extend_from_slice(&session_id.to_be_bytes());
+                buffer.extend_from_slice(secret);
+            }
+            RelayHeader::ToHost { session_id } => {
+                buffer.push(TO_HOST);
+                buffer.extend_from_slice(&session_id.to_be_bytes());
+            }
+            RelayHeader::ToClient { client_addr } => {
+                buffer.push(TO_CLIENT);
+                write_addr(client_addr, buffer);
+            }
+            RelayHeader::FromHost => buffer.push(FROM_HOST),
+            RelayHeader::FromClient { client_addr } => {
+                buffer.push(FROM_CLIENT);
+                write_addr(client_addr, buffer);
+            }
+        }
+    }
+
+    /// Read the header at the start of the packet.
+    ///
+    /// Returns the header and its length (the payload starts after it), or None if the header is invalid
+    pub(crate) fn read(packet: &[u8]) -> Option<(Self, usize)> {
+        let (&kind, rest) = packet.split_first()?;
+        let (header, len) = match kind {
+            REGISTER => (
+                RelayHeader::Register {
+                    session_id: read_session_id(rest)?,
+                    secret: rest.get(8..REGISTER_BYTES - 1)?.try_into().ok()?,
+                },
+                REGISTER_BYTES - 1,
+            ),
+            TO_HOST => (
+                RelayHeader::ToHost {
+                    session_id: read_session_id(rest)?,
+                },
+                8,
+            ),
+            TO_CLIENT => {
+                let (client_addr, len) = read_addr(rest)?;
+                (RelayHeader::ToClient { client_addr }, len)
+            }
+            FROM_HOST => (RelayHeader::FromHost, 0),
+            FROM_CLIENT => {
+                let (client_addr, len) = read_addr(rest)?;
+                (RelayHeader::FromClient { client_addr }, len)
+            }
+            _ => return None,
+        };
+        Some((header, 1 + len))
+    }
+}
+
+fn read_session_id(bytes: &[u8]) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.get(..8)?.try_into().ok()?))
+}
+
+fn write_addr(addr: &SocketAddr, buffer: &mut Vec<u8>) {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            buffer.push(4);
+            buffer.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            buffer.push(6);
+            buffer.extend_from_slice(&ip.octets());
+        }
+    }
+    buffer.extend_from_slice(&addr.port().to_be_bytes());
+}
+
+/// Read an address, and return it with the number of bytes read
+fn read_addr(bytes: &[u8]) -> Option<(SocketAddr, usize)> {
+    let (&family, rest) = bytes.split_first()?;
+    let (ip, ip_len) = match family {
+        4 => {
+            let octets: [u8; 4] = rest.get(..4)?.try_into().ok()?;
+            (IpAddr::V4(Ipv4Addr::from(octets)), 4)
+        }
+        6 => {
+            let octets: [u8; 16] = rest.get(..16)?.try_into().ok()?;
+            (IpAddr::V6(Ipv6Addr::from(octets)), 16)
+        }
+        _ => return None,
+    };
+    let port = u16::from_be_bytes(rest.get(ip_len..ip_len + 2)?.try_into().ok()?);
+    Some((SocketAddr::new(ip, port), 1 + ip_len + 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_serde() {
+        let headers = [
+            RelayHeader::ToHost {
+                session_id: u64::MAX,
+            },
+            RelayHeader::ToClient {
+                client_addr: "127.0.0.1:5000".parse().unwrap(),
+            },
+            RelayHeader::FromHost,
+            RelayHeader::FromClient {
+                client_addr: "[2001:db8::1]:6000".parse().unwrap(),
+            },
+        ];
+        for header in headers {
+            let mut buffer = vec![];
+            header.write(&mut buffer);
+            assert!(buffer.len() <= MAX_HEADER_BYTES);
+            buffer.extend_from_slice(b"payload");
+            let (read, len) = RelayHeader::read(&buffer).unwrap();
+            assert_eq!(read, header);
+            assert_eq!(&buffer[len..], b"payload");
+        }
+
+        let register = RelayHeader::Register {
+            session_id: 7,
+            secret: [3; MAC_BYTES],
+        };
+        let mut buffer = vec![];
+        register.write(&mut buffer);
+        assert_eq!(buffer.len(), REGISTER_BYTES);
+        assert_eq!(RelayHeader::read(&buffer), Some((register, REGISTER_BYTES)));
+
+        // truncated or unknown headers are rejected
+        assert_eq!(RelayHeader::read(&[]), None);
+        assert_eq!(RelayHeader::read(&[TO_HOST, 0, 0]), None);
+        assert_eq!(
+            RelayHeader::read(&[REGISTER, 0, 0, 0, 0, 0, 0, 0, 7, 1]),
+            None
+        );
+        assert_eq!(RelayHeader::read(&[TO_CLIENT, 4, 127, 0]), None);
+        assert_eq!(RelayHeader::read(&[42]), None);
+    }
+
+    #[test]
+    fn test_session_secret() {
+        let key = [1; 32];
+        let secret = session_secret(&key, 7);
+        assert!(verify_session_secret(&key, 7, &secret));
+        // the secret is only valid for its session, and for the key of the relay
+        assert!(!verify_session_secret(&key, 8, &secret));
+        assert!(!verify_session_secret(&[2; 32], 7, &secret));
+        assert_ne!(session_secret(&key, 8), secret);
+    }
+}
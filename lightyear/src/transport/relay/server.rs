@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info, trace};
+
+use crate::connection::netcode::{generate_key, Key};
+use crate::transport::MTU;
+
+use super::{verify_session_secret, RelayHeader, SessionSecret, MAX_HEADER_BYTES};
+
+/// Duration between two checks for expired sessions and clients
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Settings of the [`RelayServer`]
+#[derive(Clone, Debug)]
+pub struct RelayServerConfig {
+    /// Key used to check the secrets of the sessions (see [`session_secret`](super::session_secret)).
+    ///
+    /// The default key is random, so the secrets can only be generated by the process that runs the relay.
+    pub key: Key,
+    /// A session is closed if its host did not register it again during this duration
+    pub session_timeout: Duration,
+    /// A client is removed from its session if it did not send any packet during this duration
+    pub client_timeout: Duration,
+}
+
+impl Default for RelayServerConfig {
+    fn default() -> Self {
+        Self {
+            key: generate_key(),
+            session_timeout: Duration::from_secs(10),
+            client_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+struct Session {
+    host_addr: SocketAddr,
+    last_seen: Instant,
+}
+
+struct RelayedClient {
+    last_seen: Instant,
+}
+
+/// Relay server that forwards the packets between the host and the clients of each session.
+///
+/// The packets are forwarded as they are: the relay cannot decrypt them.
+/// ```rust,no_run
+/// use lightyear::transport::relay::server::{RelayServer, RelayServerConfig};
+///
+/// let relay = RelayServer::bind("0.0.0.0:5100".parse().unwrap(), RelayServerConfig::default()).unwrap();
+/// relay.run().unwrap();
+/// ```
+pub struct RelayServer {
+    socket: UdpSocket,
+    config: RelayServerConfig,
+    sessions: HashMap<u64, Session>,
+    /// Session hosted by each host address
+    hosts: HashMap<SocketAddr, u64>,
+    /// Clients of each session. The same address can be a client of several sessions
+    clients: HashMap<(u64, SocketAddr), RelayedClient>,
+    recv_buffer: Vec<u8>,
+    send_buffer: Vec<u8>,
+    last_cleanup: Instant,
+}
+
+impl RelayServer {
+    /// Bind the relay to the given address
+    pub fn bind(addr: SocketAddr, config: RelayServerConfig) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        // wake up regularly to close the expired sessions
+        socket.set_read_timeout(Some(CLEANUP_INTERVAL))?;
+        info!(addr = ?socket.local_addr()?, "relay server started");
+        Ok(Self {
+            socket,
+            config,
+            sessions: HashMap::default(),
+            hosts: HashMap::default(),
+            clients: HashMap::default(),
+            recv_buffer: vec![0; MTU + MAX_HEADER_BYTES],
+            send_buffer: Vec::with_capacity(MTU + MAX_HEADER_BYTES),
+            last_cleanup: Instant::now(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Number of sessions that are currently open
+    pub fn num_sessions(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Forward packets until an io error happens
+    pub fn run(mut self) -> io::Result<()> {
+        loop {
+            self.poll()?;
+        }
+    }
+
+    /// Wait for the next packet (or until the read timeout) and forward it
+    pub fn poll(&mut self) -> io::Result<()> {
+        match self.socket.recv_from(&mut self.recv_buffer) {
+            Ok((len, from)) => self.handle_packet(len, from)?,
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+            }
+            Err(e) => return Err(e),
+        }
+        let now = Instant::now();
+        if now.duration_since(self.last_cleanup) >= CLEANUP_INTERVAL {
+            self.last_cleanup = now;
+            self.remove_expired(now);
+        }
+        Ok(())
+    }
+
+    fn handle_packet(&mut self, len: usize, from: SocketAddr) -> io::Result<()> {
+        let Some((header, header_len)) = RelayHeader::read(&self.recv_buffer[..len]) else {
+            trace!(?from, "dropping packet with an invalid relay header");
+            return Ok(());
+        };
+        let now = Instant::now();
+        let (header, to) = match header {
+            RelayHeader::Register { session_id, secret } => {
+                self.register(session_id, &secret, from, now);
+                return Ok(());
+            }
+            RelayHeader::ToHost { session_id } => {
+                let Some(session) = self.sessions.get(&session_id) else {
+                    trace!(?from, session_id, "dropping packet for an unknown session");
+                    return Ok(());
+                };
+                if session.host_addr == from {
+                    trace!(?from, "dropping packet sent by the host to itself");
+                    return Ok(());
+                }
+                self.clients
+                    .insert((session_id, from), RelayedClient { last_seen: now });
+                (
+                    RelayHeader::FromClient { client_addr: from },
+                    session.host_addr,
+                )
+            }
+            RelayHeader::ToClient { client_addr } => {
+                // the host can only send packets to the clients of its own session
+                let is_host_of_client = self.hosts.get(&from).is_some_and(|session_id| {
+                    self.clients.contains_key(&(*session_id, client_addr))
+                });
+                if !is_host_of_client {
+                    trace!(?from, ?client_addr, "dropping packet for an unknown client");
+                    return Ok(());
+                }
+                (RelayHeader::FromHost, client_addr)
+            }
+            RelayHeader::FromHost | RelayHeader::FromClient { .. } => {
+                trace!(
+                    ?from,
+                    "dropping packet that should only be sent by the relay"
+                );
+                return Ok(());
+            }
+        };
+        self.send_buffer.clear();
+        header.write(&mut self.send_buffer);
+        self.send_buffer
+            .extend_from_slice(&self.recv_buffer[header_len..len]);
+        match self.socket.send_to(&self.send_buffer, to) {
+            Ok(_) => Ok(()),
+            // the relay serves many sessions: one unreachable peer should not stop it
+            Err(e) => {
+                debug!(?to, ?e, "could not forward packet");
+                Ok(())
+            }
+        }
+    }
+
+    fn register(
+        &mut self,
+        session_id: u64,
+        secret: &SessionSecret,
+        host_addr: SocketAddr,
+        now: Instant,
+    ) {
+        if !verify_session_secret(&self.config.key, session_id, secret) {
+            debug!(
+                session_id,
+                ?host_addr,
+                "rejecting registration with an invalid session secret"
+            );
+            return;
+        }
+        if let Some(previous_session_id) = self.hosts.get(&host_addr) {
+            if *previous_session_id != session_id {
+                debug!(
+                    session_id,
+                    ?host_addr,
+                    "address is already hosting another session"
+                );
+                return;
+            }
+        }
+        match self.sessions.get_mut(&session_id) {
+            Some(session) => {
+                session.last_seen = now;
+                if session.host_addr != host_addr {
+                    // the host knows the secret, so it can move to another address (for example after a NAT rebinding)
+                    info!(
+                        session_id,
+                        ?host_addr,
+                        "session moved to a new host address"
+                    );
+                    self.hosts.remove(&session.host_addr);
+                    self.hosts.insert(host_addr, session_id);
+                    session.host_addr = host_addr;
+                }
+            }
+            None => {
+                info!(session_id, ?host_addr, "session registered");
+                self.sessions.insert(
+                    session_id,
+                    Session {
+                        host_addr,
+                        last_seen: now,
+                    },
+                );
+                self.hosts.insert(host_addr, session_id);
+            }
+        }
+    }
+
+    fn remove_expired(&mut self, now: Instant) {
+        let session_timeout = self.config.session_timeout;
+        let hosts = &mut self.hosts;
+        self.sessions.retain(|session_id, session| {
+            let alive = now.duration_since(session.last_seen) < session_timeout;
+            if !alive {
+                info!(session_id, "session expired");
+                hosts.remove(&session.host_addr);
+            }
+            alive
+        });
+        let client_timeout = self.config.client_timeout;
+        let sessions = &self.sessions;
+        self.clients.retain(|(session_id, _), client| {
+            sessions.contains_key(session_id)
+                && now.duration_since(client.last_seen) < client_timeout
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+
+    use crate::transport::relay::session_secret;
+
+    use super::*;
+
+    const KEY: Key = [7; 32];
+
+    fn register(session_id: u64) -> RelayHeader {
+        RelayHeader::Register {
+            session_id,
+            secret: session_secret(&KEY, session_id),
+        }
+    }
+
+    fn relay() -> RelayServer {
+        let relay = RelayServer::bind(
+            "127.0.0.1:0".parse().unwrap(),
+            RelayServerConfig {
+                key: KEY,
+                ..RelayServerConfig::default()
+            },
+        )
+        .unwrap();
+        relay
+            .socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        relay
+    }
+
+    fn socket() -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        socket
+    }
+
+    fn send(socket: &UdpSocket, header: RelayHeader, payload: &[u8], relay_addr: SocketAddr) {
+        let mut buffer = vec![];
+        header.write(&mut buffer);
+        buffer.extend_from_slice(payload);
+        socket.send_to(&buffer, relay_addr).unwrap();
+    }
+
+    fn recv(socket: &UdpSocket) -> Option<(RelayHeader, Vec<u8>)> {
+        let mut buffer = [0; 100];
+        let (len, _) = socket.recv_from(&mut buffer).ok()?;
+        let (header, header_len) = RelayHeader::read(&buffer[..len]).unwrap();
+        Some((header, buffer[header_len..len].to_vec()))
+    }
+
+    #[test]
+    fn test_relay_server() {
+        let mut relay = relay();
+        let relay_addr = relay.local_addr().unwrap();
+        let host = socket();
+        let client = socket();
+        let intruder = socket();
+
+        send(&host, register(1), &[], relay_addr);
+        relay.poll().unwrap();
+        assert_eq!(relay.num_sessions(), 1);
+
+        // another host cannot take over the session without its secret
+        send(
+            &intruder,
+            RelayHeader::Register {
+                session_id: 1,
+                secret: session_secret(&KEY, 2),
+            },
+            &[],
+            relay_addr,
+        );
+        relay.poll().unwrap();
+        assert_eq!(
+            relay.sessions.get(&1).unwrap().host_addr,
+            host.local_addr().unwrap()
+        );
+
+        // client to host
+        send(
+            &client,
+            RelayHeader::ToHost { session_id: 1 },
+            b"hello",
+            relay_addr,
+        );
+        relay.poll().unwrap();
+        let client_addr = client.local_addr().unwrap();
+        assert_eq!(
+            recv(&host),
+            Some((RelayHeader::FromClient { client_addr }, b"hello".to_vec()))
+        );
+
+        // host to client
+        send(
+            &host,
+            RelayHeader::ToClient { client_addr },
+            b"world",
+            relay_addr,
+        );
+        relay.poll().unwrap();
+        assert_eq!(
+            recv(&client),
+            Some((RelayHeader::FromHost, b"world".to_vec()))
+        );
+
+        // only the host of the session can send packets to its clients
+        send(
+            &intruder,
+            RelayHeader::ToClient { client_addr },
+            b"spoofed",
+            relay_addr,
+        );
+        relay.poll().unwrap();
+        assert_eq!(recv(&client), None);
+
+        // the session and its clients expire if the host stops registering it
+        relay.remove_expired(Instant::now() + Duration::from_secs(11));
+        assert_eq!(relay.num_sessions(), 0);
+        assert!(relay.hosts.is_empty());
+        assert!(relay.clients.is_empty());
+    }
+
+    #[test]
+    fn test_relay_session_secret() {
+        let mut relay = relay();
+        let relay_addr = relay.local_addr().unwrap();
+        let host = socket();
+        let new_host = socket();
+
+        // nobody can create a session without its secret
+        send(
+            &host,
+            RelayHeader::Register {
+                session_id: 1,
+                secret: [0; 16],
+            },
+            &[],
+            relay_addr,
+        );
+        relay.poll().unwrap();
+        assert_eq!(relay.num_sessions(), 0);
+
+        // the host can move to another address with the secret of the session
+        send(&host, register(1), &[], relay_addr);
+        relay.poll().unwrap();
+        send(&new_host, register(1), &[], relay_addr);
+        relay.poll().unwrap();
+        let new_host_addr = new_host.local_addr().unwrap();
+        assert_eq!(relay.sessions.get(&1).unwrap().host_addr, new_host_addr);
+        assert_eq!(relay.hosts.len(), 1);
+        assert_eq!(relay.hosts.get(&new_host_addr), Some(&1));
+    }
+
+    #[test]
+    fn test_relay_client_in_two_sessions() {
+        let mut relay = relay();
+        let relay_addr = relay.local_addr().unwrap();
+        let host_1 = socket();
+        let host_2 = socket();
+        let client = socket();
+        let client_addr = client.local_addr().unwrap();
+
+        send(&host_1, register(1), &[], relay_addr);
+        relay.poll().unwrap();
+        send(&host_2, register(2), &[], relay_addr);
+        relay.poll().unwrap();
+
+        // the client joins both sessions from the same address
+        for session_id in [1, 2] {
+            send(
+                &client,
+                RelayHeader::ToHost { session_id },
+                b"hello",
+                relay_addr,
+            );
+            relay.poll().unwrap();
+        }
+        assert!(recv(&host_1).is_some());
+        assert!(recv(&host_2).is_some());
+
+        // both hosts can still reach the client
+        for host in [&host_1, &host_2] {
+            send(
+                host,
+                RelayHeader::ToClient { client_addr },
+                b"world",
+                relay_addr,
+            );
+            relay.poll().unwrap();
+            assert_eq!(
+                recv(&client),
+                Some((RelayHeader::FromHost, b"world".to_vec()))
+            );
+        }
+    }
+}
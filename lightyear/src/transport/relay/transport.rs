@@ -0,0 +1,269 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, trace};
+
+use crate::transport::error::Result;
+use crate::transport::{
+    BoxedCloseFn, BoxedReceiver, BoxedSender, PacketReceiver, PacketSender, Transport,
+    TransportBuilder, TransportEnum, LOCAL_SOCKET, MTU,
+};
+
+use super::{RelayHeader, SessionSecret, MAX_HEADER_BYTES};
+
+/// Duration between two registrations of the session by the host.
+/// It must be shorter than the session timeout of the relay
+const REGISTER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Whether the transport hosts the session, or connects to the host of the session
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RelayRole {
+    Host,
+    Client,
+}
+
+pub(crate) struct RelaySocketBuilder {
+    pub(crate) relay_addr: SocketAddr,
+    pub(crate) session_id: u64,
+    /// Secret presented by the host to register the session (unused by the clients)
+    pub(crate) session_secret: Option<SessionSecret>,
+    pub(crate) role: RelayRole,
+}
+
+impl TransportBuilder for RelaySocketBuilder {
+    fn connect(self) -> Result<TransportEnum> {
+        let session_secret = match (self.role, self.session_secret) {
+            (RelayRole::Host, None) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "the host of a relay session needs the secret of the session",
+                )
+                .into())
+            }
+            (_, secret) => secret.unwrap_or_default(),
+        };
+        let bind_addr = match self.relay_addr.ip() {
+            IpAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            IpAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+        };
+        let socket = std::net::UdpSocket::bind(bind_addr)?;
+        socket.set_nonblocking(true)?;
+        let local_addr = socket.local_addr()?;
+        let socket = Arc::new(socket);
+        let server_addr = Arc::new(Mutex::new(LOCAL_SOCKET));
+        debug!(relay_addr = ?self.relay_addr, session_id = self.session_id, role = ?self.role, "connecting through relay");
+        Ok(TransportEnum::Relay(RelaySocket {
+            local_addr,
+            sender: RelaySender {
+                socket: socket.clone(),
+                relay_addr: self.relay_addr,
+                session_id: self.session_id,
+                role: self.role,
+                server_addr: server_addr.clone(),
+                buffer: Vec::with_capacity(MTU + MAX_HEADER_BYTES),
+            },
+            receiver: RelayReceiver {
+                socket,
+                relay_addr: self.relay_addr,
+                session_id: self.session_id,
+                role: self.role,
+                session_secret,
+                server_addr,
+                last_register: None,
+                buffer: [0; MTU + MAX_HEADER_BYTES],
+            },
+        }))
+    }
+}
+
+/// UDP socket that sends and receives packets through a relay server
+pub struct RelaySocket {
+    local_addr: SocketAddr,
+    sender: RelaySender,
+    receiver: RelayReceiver,
+}
+
+impl Transport for RelaySocket {
+    fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn split(self) -> (BoxedSender, BoxedReceiver, Option<BoxedCloseFn>) {
+        (Box::new(self.sender), Box::new(self.receiver), None)
+    }
+}
+
+struct RelaySender {
+    socket: Arc<std::net::UdpSocket>,
+    relay_addr: SocketAddr,
+    session_id: u64,
+    role: RelayRole,
+    /// Address that the connection layer uses for the host (only used by clients)
+    server_addr: Arc<Mutex<SocketAddr>>,
+    buffer: Vec<u8>,
+}
+
+impl PacketSender for RelaySender {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        let header = match self.role {
+            RelayRole::Host => RelayHeader::ToClient {
+                client_addr: *address,
+            },
+            RelayRole::Client => {
+                // the relay knows the host of the session, so the address is only recorded to report
+                // the packets received from the host with the same address
+                *self.server_addr.lock().unwrap() = *address;
+                RelayHeader::ToHost {
+                    session_id: self.session_id,
+                }
+            }
+        };
+        self.buffer.clear();
+        header.write(&mut self.buffer);
+        self.buffer.extend_from_slice(payload);
+        self.socket.send_to(&self.buffer, self.relay_addr)?;
+        Ok(())
+    }
+}
+
+struct RelayReceiver {
+    socket: Arc<std::net::UdpSocket>,
+    relay_addr: SocketAddr,
+    session_id: u64,
+    role: RelayRole,
+    session_secret: SessionSecret,
+    server_addr: Arc<Mutex<SocketAddr>>,
+    /// Last time the host registered the session on the relay
+    last_register: Option<Instant>,
+    buffer: [u8; MTU + MAX_HEADER_BYTES],
+}
+
+impl RelayReceiver {
+    /// Register the session on the relay (or keep it alive) if we are the host
+    fn register(&mut self) -> Result<()> {
+        if self.role != RelayRole::Host
+            || self
+                .last_register
+                .is_some_and(|last| last.elapsed() < REGISTER_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.last_register = Some(Instant::now());
+        let mut buffer = vec![];
+        RelayHeader::Register {
+            session_id: self.session_id,
+            secret: self.session_secret,
+        }
+        .write(&mut buffer);
+        self.socket.send_to(&buffer, self.relay_addr)?;
+        Ok(())
+    }
+}
+
+impl PacketReceiver for RelayReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        self.register()?;
+        loop {
+            let (recv_len, address) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            if address != self.relay_addr {
+                trace!(
+                    ?address,
+                    "dropping packet that does not come from the relay"
+                );
+                continue;
+            }
+            let from = match (RelayHeader::read(&self.buffer[..recv_len]), self.role) {
+                (Some((RelayHeader::FromClient { client_addr }, header_len)), RelayRole::Host) => {
+                    Some((client_addr, header_len))
+                }
+                (Some((RelayHeader::FromHost, header_len)), RelayRole::Client) => {
+                    Some((*self.server_addr.lock().unwrap(), header_len))
+                }
+                _ => None,
+            };
+            let Some((from, header_len)) = from else {
+                trace!("dropping packet with an unexpected relay header");
+                continue;
+            };
+            return Ok(Some((&mut self.buffer[header_len..recv_len], from)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::relay::server::{RelayServer, RelayServerConfig};
+    use crate::transport::relay::session_secret;
+    use crate::transport::{PacketReceiver, PacketSender, Transport, TransportBuilder};
+
+    use super::*;
+
+    /// Poll the receiver until a packet is received
+    fn recv(receiver: &mut BoxedReceiver) -> Option<(Vec<u8>, SocketAddr)> {
+        for _ in 0..100 {
+            if let Some((payload, address)) = receiver.recv().unwrap() {
+                return Some((payload.to_vec(), address));
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        None
+    }
+
+    #[test]
+    fn test_relay_transport() -> anyhow::Result<()> {
+        let config = RelayServerConfig::default();
+        let secret = session_secret(&config.key, 1);
+        let relay = RelayServer::bind("127.0.0.1:0".parse()?, config)?;
+        let relay_addr = relay.local_addr()?;
+        std::thread::spawn(move || relay.run());
+
+        // the host cannot register the session without its secret
+        assert!(RelaySocketBuilder {
+            relay_addr,
+            session_id: 1,
+            session_secret: None,
+            role: RelayRole::Host,
+        }
+        .connect()
+        .is_err());
+
+        let (mut host_sender, mut host_receiver, _) = RelaySocketBuilder {
+            relay_addr,
+            session_id: 1,
+            session_secret: Some(secret),
+            role: RelayRole::Host,
+        }
+        .connect()?
+        .split();
+        let (mut client_sender, mut client_receiver, _) = RelaySocketBuilder {
+            relay_addr,
+            session_id: 1,
+            session_secret: None,
+            role: RelayRole::Client,
+        }
+        .connect()?
+        .split();
+
+        // the host registers the session when it first polls for packets
+        assert!(host_receiver.recv()?.is_none());
+        std::thread::sleep(Duration::from_millis(50));
+
+        // client to host: the client sends to the server address of its connect token
+        let server_addr = "127.0.0.1:5000".parse()?;
+        client_sender.send(b"hello", &server_addr)?;
+        let (payload, client_addr) = recv(&mut host_receiver).expect("host should receive");
+        assert_eq!(payload, b"hello");
+
+        // host to client: the packet is reported as coming from the server address
+        host_sender.send(b"world", &client_addr)?;
+        let (payload, address) = recv(&mut client_receiver).expect("client should receive");
+        assert_eq!(payload, b"world");
+        assert_eq!(address, server_addr);
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+//! Transport that plays back a capture recorded with [`IoConfig::with_capture`](crate::transport::config::IoConfig::with_capture)
+//!
+//! The datagrams that were received during the captured session are delivered again, with the same timing
+//! relative to the moment the transport is connected. Outgoing datagrams are discarded.
+//!
+//! The secrets of the captured session are handed back to the connection, which restores them
+//! so that the packets of the captured handshake are accepted again.
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use cfg_if::cfg_if;
+use tracing::trace;
+
+use crate::transport::middleware::capture::{CapturedPacket, PacketCapture, PacketDirection};
+use crate::transport::{
+    BoxedCloseFn, BoxedReceiver, BoxedSender, PacketReceiver, PacketSender, Transport,
+    TransportBuilder, TransportEnum,
+};
+
+use super::error::Result;
+
+cfg_if! {
+    if #[cfg(any(test))] {
+        use mock_instant::Instant;
+    } else {
+        use bevy::utils::Instant;
+    }
+}
+
+pub(crate) struct ReplayBuilder {
+    pub(crate) path: PathBuf,
+}
+
+impl TransportBuilder for ReplayBuilder {
+    fn connect(self) -> Result<TransportEnum> {
+        let capture = PacketCapture::read(&self.path)?;
+        let packets = capture
+            .packets
+            .into_iter()
+            .filter(|packet| packet.direction == PacketDirection::Received)
+            .collect();
+        Ok(TransportEnum::Replay(Replay {
+            local_addr: capture.local_addr,
+            session: capture.session,
+            sender: ReplaySender,
+            receiver: ReplayReceiver {
+                start: Instant::now(),
+                packets,
+                buffer: vec![],
+            },
+        }))
+    }
+}
+
+pub struct Replay {
+    local_addr: SocketAddr,
+    session: Option<Vec<u8>>,
+    sender: ReplaySender,
+    receiver: ReplayReceiver,
+}
+
+impl Replay {
+    /// Take the secrets of the captured session
+    pub(crate) fn take_session(&mut self) -> Option<Vec<u8>> {
+        self.session.take()
+    }
+}
+
+impl Transport for Replay {
+    fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn split(self) -> (BoxedSender, BoxedReceiver, Option<BoxedCloseFn>) {
+        (Box::new(self.sender), Box::new(self.receiver), None)
+    }
+}
+
+struct ReplayReceiver {
+    start: Instant,
+    packets: VecDeque<CapturedPacket>,
+    buffer: Vec<u8>,
+}
+
+impl PacketReceiver for ReplayReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        let elapsed = Instant::now() - self.start;
+        if self
+            .packets
+            .front()
+            .map_or(true, |packet| packet.timestamp > elapsed)
+        {
+            return Ok(None);
+        }
+        let packet = self.packets.pop_front().unwrap();
+        self.buffer = packet.payload;
+        Ok(Some((self.buffer.as_mut_slice(), packet.address)))
+    }
+}
+
+struct ReplaySender;
+
+impl PacketSender for ReplaySender {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        trace!(
+            ?address,
+            len = payload.len(),
+            "discarding packet sent during replay"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::Duration;
+    use bytes::Bytes;
+    use mock_instant::MockClock;
+
+    use crate::connection::netcode::{generate_key, NetcodeClient, NetcodeServer};
+    use crate::packet::message::SingleData;
+    use crate::packet::packet_manager::PacketBuilder;
+    use crate::prelude::{IoConfig, TransportConfig};
+    use crate::transport::io::Io;
+    use crate::transport::middleware::capture::CaptureWriter;
+    use crate::transport::LOCAL_SOCKET;
+
+    use super::*;
+
+    const PROTOCOL_ID: u64 = 0;
+
+    #[test]
+    fn test_replay() -> Result<()> {
+        let path = std::env::temp_dir().join("lightyear_test_replay.cap");
+        let local_addr = SocketAddr::from(([127, 0, 0, 1], 5000));
+        let remote_addr = SocketAddr::from(([127, 0, 0, 1], 6000));
+
+        let capture = CaptureWriter::create(&path, local_addr)?;
+        capture.record(PacketDirection::Sent, remote_addr, &[0])?;
+        MockClock::advance(Duration::from_millis(10));
+        capture.record(PacketDirection::Received, remote_addr, &[1])?;
+        MockClock::advance(Duration::from_millis(10));
+        capture.record(PacketDirection::Received, remote_addr, &[2])?;
+        capture.flush()?;
+        drop(capture);
+
+        let replay = ReplayBuilder { path: path.clone() }.connect()?;
+        assert_eq!(replay.local_addr(), local_addr);
+        let (_, mut receiver, _) = replay.split();
+
+        // the packets are replayed with the same timing as the captured session
+        assert!(receiver.recv()?.is_none());
+        MockClock::advance(Duration::from_millis(10));
+        assert_eq!(receiver.recv()?, Some((&mut [1u8][..], remote_addr)));
+        assert!(receiver.recv()?.is_none());
+        MockClock::advance(Duration::from_millis(10));
+        assert_eq!(receiver.recv()?, Some((&mut [2u8][..], remote_addr)));
+        assert!(receiver.recv()?.is_none());
+
+        let _ = std::fs::remove_file(path);
+        Ok(())
+    }
+
+    fn replay_io(path: &PathBuf) -> Io {
+        let mut io =
+            IoConfig::from_transport(TransportConfig::Replay { path: path.clone() }).build();
+        io.connect().unwrap();
+        io
+    }
+
+    fn payload() -> Vec<u8> {
+        let mut builder = PacketBuilder::new();
+        let mut packet = builder.build_new_single_packet();
+        packet.add_message(0, SingleData::new(None, Bytes::from(vec![7u8; 10]), 1.0));
+        builder.encode_packet(&packet).unwrap()
+    }
+
+    /// Capture a session between a netcode client and server, then replay each side of it:
+    /// the handshake must succeed again and the payloads must be received again
+    #[test]
+    fn test_replay_netcode() {
+        let dir = tempfile::tempdir().unwrap();
+        let client_path = dir.path().join("client.cap");
+        let server_path = dir.path().join("server.cap");
+        let private_key = generate_key();
+
+        let (to_server_send, to_server_recv) = crossbeam_channel::unbounded();
+        let (from_server_send, from_server_recv) = crossbeam_channel::unbounded();
+        let mut client_io = IoConfig::from_transport(TransportConfig::LocalChannel {
+            recv: from_server_recv,
+            send: to_server_send,
+        })
+        .with_capture(&client_path)
+        .build();
+        let mut server_io = IoConfig::from_transport(TransportConfig::Channels {
+            channels: vec![(LOCAL_SOCKET, to_server_recv, from_server_send)],
+        })
+        .with_capture(&server_path)
+        .build();
+        client_io.connect().unwrap();
+        server_io.connect().unwrap();
+
+        let mut server = NetcodeServer::new(PROTOCOL_ID, private_key).unwrap();
+        server.capture_session(&server_io).unwrap();
+        let token = server
+            .token(1, LOCAL_SOCKET)
+            .generate()
+            .unwrap()
+            .try_into_bytes()
+            .unwrap();
+        let mut client = NetcodeClient::new(&token).unwrap();
+        client.capture_session(&client_io).unwrap();
+        client.connect();
+        for _ in 0..10 {
+            MockClock::advance(Duration::from_millis(100));
+            client.update(0.1, &mut client_io);
+            server.update(0.1, &mut server_io);
+        }
+        assert!(client.is_connected());
+        client.send(&payload(), &mut client_io).unwrap();
+        server.send(&payload(), 1, &mut server_io).unwrap();
+        MockClock::advance(Duration::from_millis(100));
+        client.update(0.1, &mut client_io);
+        server.update(0.1, &mut server_io);
+        assert!(client.recv().is_some());
+        assert!(server.recv().is_some());
+        drop(client_io);
+        drop(server_io);
+
+        // the server is replayed with a new challenge key
+        let mut server_io = replay_io(&server_path);
+        let mut server = NetcodeServer::new(PROTOCOL_ID, private_key).unwrap();
+        server.capture_session(&server_io).unwrap();
+        for _ in 0..11 {
+            MockClock::advance(Duration::from_millis(100));
+            server.update(0.1, &mut server_io);
+        }
+        assert_eq!(server.connected_client_ids().collect::<Vec<_>>(), vec![1]);
+        let (packet, client_id) = server.recv().unwrap();
+        assert_eq!((packet.num_messages(), client_id), (1, 1));
+
+        // the client is replayed with a token from another server
+        let other_token = NetcodeServer::new(PROTOCOL_ID, generate_key())
+            .unwrap()
+            .token(2, LOCAL_SOCKET)
+            .generate()
+            .unwrap()
+            .try_into_bytes()
+            .unwrap();
+        let mut client_io = replay_io(&client_path);
+        let mut client = NetcodeClient::new(&other_token).unwrap();
+        client.capture_session(&client_io).unwrap();
+        client.connect();
+        for _ in 0..11 {
+            MockClock::advance(Duration::from_millis(100));
+            client.update(0.1, &mut client_io);
+        }
+        assert!(client.is_connected());
+        assert_eq!(client.id(), 1);
+        assert_eq!(client.recv().unwrap().num_messages(), 1);
+    }
+}
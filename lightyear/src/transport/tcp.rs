@@ -0,0 +1,490 @@
+//! The transport is a TCP stream, for networks where UDP is blocked.
+//!
+//! Each packet is framed with a 2-byte big-endian length prefix.
+//! The sockets are non-blocking: packets that cannot be written immediately are buffered
+//! and written on the next calls to `send`.
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+use bevy::utils::Duration;
+
+use crossbeam_channel::{Receiver, Sender};
+use tracing::{debug, info, warn};
+
+use crate::transport::{
+    BoxedCloseFn, BoxedReceiver, BoxedSender, PacketReceiver, PacketSender, Transport,
+    TransportBuilder, TransportEnum, MTU,
+};
+
+use super::error::Result;
+
+/// Maximum time that the client waits for the TCP connection to the server to be established
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Size of the length prefix written before each packet
+const LENGTH_PREFIX_BYTES: usize = 2;
+/// Maximum number of bytes buffered by a reader: one packet of the maximum size, with its length prefix
+const MAX_READ_BUFFER_BYTES: usize = LENGTH_PREFIX_BYTES + MTU;
+/// Maximum number of bytes that can be waiting to be written on a stream.
+/// Packets sent while the buffer is full are dropped, like they would be on a congested UDP socket.
+const MAX_PENDING_BYTES: usize = 256 * 1024;
+
+/// Writes length-prefixed packets on a non-blocking TCP stream
+struct FramedWriter {
+    stream: TcpStream,
+    /// Bytes that could not be written on the stream yet
+    pending: Vec<u8>,
+}
+
+impl FramedWriter {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            pending: Vec::new(),
+        }
+    }
+
+    fn send(&mut self, payload: &[u8]) -> std::io::Result<()> {
+        if payload.len() > u16::MAX as usize {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "packet is too large to be sent over TCP",
+            ));
+        }
+        if self.pending.len() > MAX_PENDING_BYTES {
+            warn!("TCP send buffer is full, dropping packet");
+        } else {
+            self.pending
+                .extend_from_slice(&(payload.len() as u16).to_be_bytes());
+            self.pending.extend_from_slice(payload);
+        }
+        self.flush()
+    }
+
+    /// Write as many pending bytes as possible without blocking
+    fn flush(&mut self) -> std::io::Result<()> {
+        let mut written = 0;
+        let result = loop {
+            if written == self.pending.len() {
+                break Ok(());
+            }
+            match self.stream.write(&self.pending[written..]) {
+                Ok(0) => break Err(ErrorKind::WriteZero.into()),
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        self.pending.drain(..written);
+        result
+    }
+}
+
+/// Reads length-prefixed packets from a non-blocking TCP stream
+struct FramedReader {
+    stream: TcpStream,
+    /// Bytes received on the stream that have not been returned as packets yet
+    buffer: Vec<u8>,
+}
+
+impl FramedReader {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Returns the length of the next packet if it has been fully received
+    fn complete_packet_len(&self) -> std::io::Result<Option<usize>> {
+        if self.buffer.len() < LENGTH_PREFIX_BYTES {
+            return Ok(None);
+        }
+        let len = u16::from_be_bytes([self.buffer[0], self.buffer[1]]) as usize;
+        if len > MTU {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("received a TCP packet bigger than the MTU: {}", len),
+            ));
+        }
+        Ok((self.buffer.len() >= LENGTH_PREFIX_BYTES + len).then_some(len))
+    }
+
+    /// Read the next packet from the stream into `packet`.
+    ///
+    /// Returns the length of the packet, or `None` if no complete packet is available.
+    /// Returns an error if the stream was closed by the remote.
+    fn read_packet(&mut self, packet: &mut [u8; MTU]) -> std::io::Result<Option<usize>> {
+        if self.complete_packet_len()?.is_none() {
+            self.fill()?;
+        }
+        let Some(len) = self.complete_packet_len()? else {
+            return Ok(None);
+        };
+        packet[..len].copy_from_slice(&self.buffer[LENGTH_PREFIX_BYTES..LENGTH_PREFIX_BYTES + len]);
+        self.buffer.drain(..LENGTH_PREFIX_BYTES + len);
+        Ok(Some(len))
+    }
+
+    /// Read the bytes available on the stream without blocking, until the buffer is full
+    fn fill(&mut self) -> std::io::Result<()> {
+        let mut chunk = [0; MTU];
+        while self.buffer.len() < MAX_READ_BUFFER_BYTES {
+            let max_len = (MAX_READ_BUFFER_BYTES - self.buffer.len()).min(MTU);
+            match self.stream.read(&mut chunk[..max_len]) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Configure a newly connected stream, and split it into a writer and a reader
+fn split_stream(stream: TcpStream) -> std::io::Result<(FramedWriter, FramedReader)> {
+    stream.set_nonblocking(true)?;
+    // packets should be sent immediately
+    stream.set_nodelay(true)?;
+    let reader = FramedReader::new(stream.try_clone()?);
+    Ok((FramedWriter::new(stream), reader))
+}
+
+pub(crate) struct TcpClientSocketBuilder {
+    pub(crate) server_addr: SocketAddr,
+}
+
+impl TransportBuilder for TcpClientSocketBuilder {
+    fn connect(self) -> Result<TransportEnum> {
+        let stream = TcpStream::connect_timeout(&self.server_addr, CONNECT_TIMEOUT)?;
+        let local_addr = stream.local_addr()?;
+        info!("Connected to TCP server at {:?}", self.server_addr);
+        let (writer, reader) = split_stream(stream)?;
+        Ok(TransportEnum::TcpClient(TcpClientSocket {
+            local_addr,
+            sender: TcpClientSocketSender {
+                server_addr: self.server_addr,
+                writer,
+            },
+            receiver: TcpClientSocketReceiver {
+                server_addr: self.server_addr,
+                reader,
+                buffer: [0; MTU],
+                closed: false,
+            },
+        }))
+    }
+}
+
+/// TCP client socket
+pub struct TcpClientSocket {
+    local_addr: SocketAddr,
+    sender: TcpClientSocketSender,
+    receiver: TcpClientSocketReceiver,
+}
+
+impl Transport for TcpClientSocket {
+    fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn split(self) -> (BoxedSender, BoxedReceiver, Option<BoxedCloseFn>) {
+        (Box::new(self.sender), Box::new(self.receiver), None)
+    }
+}
+
+struct TcpClientSocketSender {
+    server_addr: SocketAddr,
+    writer: FramedWriter,
+}
+
+impl PacketSender for TcpClientSocketSender {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        // there is only one stream, to the server
+        if *address != self.server_addr {
+            debug!(?address, "no TCP stream for this address, dropping packet");
+            return Ok(());
+        }
+        self.writer.send(payload)?;
+        Ok(())
+    }
+}
+
+struct TcpClientSocketReceiver {
+    server_addr: SocketAddr,
+    reader: FramedReader,
+    buffer: [u8; MTU],
+    /// Set when the stream has been closed, so that the error is only returned once
+    closed: bool,
+}
+
+impl PacketReceiver for TcpClientSocketReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        if self.closed {
+            return Ok(None);
+        }
+        match self.reader.read_packet(&mut self.buffer) {
+            Ok(Some(len)) => Ok(Some((&mut self.buffer[..len], self.server_addr))),
+            Ok(None) => Ok(None),
+            Err(e) => {
+                self.closed = true;
+                Err(e.into())
+            }
+        }
+    }
+}
+
+pub(crate) struct TcpServerSocketBuilder {
+    pub(crate) server_addr: SocketAddr,
+}
+
+impl TransportBuilder for TcpServerSocketBuilder {
+    fn connect(self) -> Result<TransportEnum> {
+        let listener = TcpListener::bind(self.server_addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        info!("TCP server listening on {:?}", local_addr);
+        let (stream_events_sender, stream_events_receiver) = crossbeam_channel::unbounded();
+        Ok(TransportEnum::TcpServer(TcpServerSocket {
+            local_addr,
+            sender: TcpServerSocketSender {
+                writers: HashMap::new(),
+                stream_events: stream_events_receiver,
+            },
+            receiver: TcpServerSocketReceiver {
+                listener,
+                readers: HashMap::new(),
+                stream_events: stream_events_sender,
+                buffer: [0; MTU],
+            },
+        }))
+    }
+}
+
+/// TCP server socket, that accepts connections from multiple clients
+pub struct TcpServerSocket {
+    local_addr: SocketAddr,
+    sender: TcpServerSocketSender,
+    receiver: TcpServerSocketReceiver,
+}
+
+impl Transport for TcpServerSocket {
+    fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn split(self) -> (BoxedSender, BoxedReceiver, Option<BoxedCloseFn>) {
+        (Box::new(self.sender), Box::new(self.receiver), None)
+    }
+}
+
+/// Sent by the server receiver to the server sender when a client stream is accepted or closed
+enum StreamEvent {
+    Accepted(SocketAddr, FramedWriter),
+    Closed(SocketAddr),
+}
+
+struct TcpServerSocketSender {
+    writers: HashMap<SocketAddr, FramedWriter>,
+    /// Streams of the clients accepted or closed by the receiver
+    stream_events: Receiver<StreamEvent>,
+}
+
+impl PacketSender for TcpServerSocketSender {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        for event in self.stream_events.try_iter() {
+            match event {
+                StreamEvent::Accepted(address, writer) => {
+                    self.writers.insert(address, writer);
+                }
+                StreamEvent::Closed(address) => {
+                    self.writers.remove(&address);
+                }
+            }
+        }
+        let Some(writer) = self.writers.get_mut(address) else {
+            debug!(?address, "no TCP stream for this address, dropping packet");
+            return Ok(());
+        };
+        if let Err(e) = writer.send(payload) {
+            // the stream is broken, the client will have to reconnect
+            self.writers.remove(address);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+struct TcpServerSocketReceiver {
+    listener: TcpListener,
+    readers: HashMap<SocketAddr, FramedReader>,
+    stream_events: Sender<StreamEvent>,
+    buffer: [u8; MTU],
+}
+
+impl TcpServerSocketReceiver {
+    /// Accept all the pending client connections
+    fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    debug!(?address, "accepted TCP connection");
+                    match split_stream(stream) {
+                        Ok((writer, reader)) => {
+                            self.readers.insert(address, reader);
+                            let _ = self
+                                .stream_events
+                                .send(StreamEvent::Accepted(address, writer));
+                        }
+                        Err(e) => warn!(?address, "could not set up TCP connection: {:?}", e),
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                // the error only concerns the connection that was being accepted (or the listener is
+                // temporarily out of resources): keep receiving packets from the connected clients
+                Err(e) => {
+                    warn!("could not accept TCP connection: {:?}", e);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl PacketReceiver for TcpServerSocketReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        self.accept();
+        let mut received = None;
+        let mut closed = vec![];
+        for (address, reader) in self.readers.iter_mut() {
+            match reader.read_packet(&mut self.buffer) {
+                Ok(Some(len)) => {
+                    received = Some((len, *address));
+                    break;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    debug!(?address, "TCP connection closed: {:?}", e);
+                    closed.push(*address);
+                }
+            }
+        }
+        for address in closed {
+            self.readers.remove(&address);
+            let _ = self.stream_events.send(StreamEvent::Closed(address));
+        }
+        Ok(received.map(|(len, address)| (&mut self.buffer[..len], address)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::{SocketAddr, TcpStream};
+    use std::str::FromStr;
+
+    use bevy::utils::Duration;
+
+    use crate::transport::{
+        PacketReceiver, PacketSender, Transport, TransportBuilder, TransportEnum,
+    };
+
+    use super::{TcpClientSocketBuilder, TcpServerSocket, TcpServerSocketBuilder};
+
+    fn server_socket() -> Result<TcpServerSocket, anyhow::Error> {
+        let TransportEnum::TcpServer(socket) = (TcpServerSocketBuilder {
+            server_addr: SocketAddr::from_str("127.0.0.1:0")?,
+        })
+        .connect()?
+        else {
+            unreachable!()
+        };
+        Ok(socket)
+    }
+
+    #[test]
+    fn test_tcp_socket() -> Result<(), anyhow::Error> {
+        // let the OS assign a port
+        let server_socket = TcpServerSocketBuilder {
+            server_addr: SocketAddr::from_str("127.0.0.1:0")?,
+        }
+        .connect()?;
+        let server_addr = server_socket.local_addr();
+        let (mut server_sender, mut server_receiver, _) = server_socket.split();
+
+        let client_socket = TcpClientSocketBuilder { server_addr }.connect()?;
+        let client_addr = client_socket.local_addr();
+        let (mut client_sender, mut client_receiver, _) = client_socket.split();
+
+        // the packets are framed, so they are received separately
+        client_sender.send(b"hello", &server_addr)?;
+        client_sender.send(b"world", &server_addr)?;
+
+        // sleep a little to give time to the message to arrive in the socket
+        std::thread::sleep(Duration::from_millis(20));
+
+        let Some((recv_msg, address)) = server_receiver.recv()? else {
+            panic!("server expected to receive a packet from client");
+        };
+        assert_eq!(address, client_addr);
+        assert_eq!(recv_msg, b"hello");
+        let Some((recv_msg, _)) = server_receiver.recv()? else {
+            panic!("server expected to receive a packet from client");
+        };
+        assert_eq!(recv_msg, b"world");
+        assert!(server_receiver.recv()?.is_none());
+
+        // server to client
+        server_sender.send(b"hello client", &client_addr)?;
+        std::thread::sleep(Duration::from_millis(20));
+
+        let Some((recv_msg, address)) = client_receiver.recv()? else {
+            panic!("client expected to receive a packet from server");
+        };
+        assert_eq!(address, server_addr);
+        assert_eq!(recv_msg, b"hello client");
+        Ok(())
+    }
+
+    #[test]
+    fn test_tcp_client_disconnect() -> Result<(), anyhow::Error> {
+        let mut server_socket = server_socket()?;
+        let client = TcpStream::connect(server_socket.local_addr())?;
+        let client_addr = client.local_addr()?;
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert!(server_socket.receiver.recv()?.is_none());
+        server_socket.sender.send(b"hello", &client_addr)?;
+        assert!(server_socket.sender.writers.contains_key(&client_addr));
+
+        // the stream of the client is removed once the client disconnects
+        drop(client);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(server_socket.receiver.recv()?.is_none());
+        assert!(server_socket.receiver.readers.is_empty());
+        server_socket.sender.send(b"hello", &client_addr)?;
+        assert!(server_socket.sender.writers.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tcp_packet_too_large() -> Result<(), anyhow::Error> {
+        let mut server_socket = server_socket()?;
+        let mut client = TcpStream::connect(server_socket.local_addr())?;
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(server_socket.receiver.recv()?.is_none());
+        assert_eq!(server_socket.receiver.readers.len(), 1);
+
+        // the length prefix is bigger than the MTU: the connection is closed
+        client.write_all(&u16::MAX.to_be_bytes())?;
+        client.write_all(&[0; 1000])?;
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(server_socket.receiver.recv()?.is_none());
+        assert!(server_socket.receiver.readers.is_empty());
+        Ok(())
+    }
+}
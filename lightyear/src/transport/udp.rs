@@ -11,16 +11,66 @@ use crate::transport::{
 
 use super::error::Result;
 
+/// Batched syscalls and segmentation offload
+#[cfg(target_os = "linux")]
+mod linux;
+
+/// Settings of the [`UdpSocket`](std::net::UdpSocket) transport
+#[derive(Clone, Debug)]
+pub struct UdpConfig {
+    /// Maximum number of datagrams that are sent or received with a single syscall
+    /// (with `sendmmsg`/`recvmmsg`). The outgoing packets are buffered until the batch is full
+    /// or until the io is flushed.
+    ///
+    /// Batching is only available on Linux: other platforms, or a batch size of 1 (the default),
+    /// use one syscall per datagram and send each packet immediately.
+    pub batch_size: usize,
+    /// Use UDP generic segmentation offload (GSO) and generic receive offload (GRO) when the kernel supports them.
+    ///
+    /// Only used on Linux, when the batch size is greater than 1.
+    pub segmentation_offload: bool,
+    /// Set the Don't Fragment bit on the outgoing datagrams, so that the datagrams bigger than the path MTU
+    /// are dropped instead of being fragmented.
+    ///
+    /// This is required by the path MTU discovery, and is set automatically when the discovery is enabled
+    /// (see [`MtuConfig`](crate::packet::mtu::MtuConfig)). Only available on Linux.
+    pub dont_fragment: bool,
+}
+
+impl Default for UdpConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 1,
+            segmentation_offload: true,
+            dont_fragment: false,
+        }
+    }
+}
+
 pub struct UdpSocketBuilder {
     pub(crate) local_addr: SocketAddr,
+    pub(crate) config: UdpConfig,
 }
 
 impl TransportBuilder for UdpSocketBuilder {
     fn connect(self) -> Result<TransportEnum> {
         let udp_socket = std::net::UdpSocket::bind(self.local_addr)?;
         let local_addr = udp_socket.local_addr()?;
+        udp_socket.set_nonblocking(true)?;
+        #[cfg(target_os = "linux")]
+        if self.config.dont_fragment {
+            linux::set_dont_fragment(&udp_socket, local_addr.is_ipv6())?;
+        }
+        #[cfg(target_os = "linux")]
+        if self.config.batch_size > 1 {
+            let socket = Arc::new(udp_socket);
+            return Ok(TransportEnum::UdpSocket(UdpSocket {
+                local_addr,
+                sender: Box::new(linux::BatchSender::new(socket.clone(), &self.config)),
+                receiver: Box::new(linux::BatchReceiver::new(socket, &self.config)),
+            }));
+        }
         let socket = Arc::new(Mutex::new(udp_socket));
-        socket.as_ref().lock().unwrap().set_nonblocking(true)?;
         let sender = UdpSocketBuffer {
             socket: socket.clone(),
             buffer: [0; MTU],
@@ -28,8 +78,8 @@ impl TransportBuilder for UdpSocketBuilder {
         let receiver = sender.clone();
         Ok(TransportEnum::UdpSocket(UdpSocket {
             local_addr,
-            sender,
-            receiver,
+            sender: Box::new(sender),
+            receiver: Box::new(receiver),
         }))
     }
 }
@@ -37,8 +87,8 @@ impl TransportBuilder for UdpSocketBuilder {
 /// UDP Socket
 pub struct UdpSocket {
     local_addr: SocketAddr,
-    sender: UdpSocketBuffer,
-    receiver: UdpSocketBuffer,
+    sender: BoxedSender,
+    receiver: BoxedReceiver,
 }
 
 impl Transport for UdpSocket {
@@ -47,7 +97,7 @@ impl Transport for UdpSocket {
     }
 
     fn split(self) -> (BoxedSender, BoxedReceiver, Option<BoxedCloseFn>) {
-        (Box::new(self.sender), Box::new(self.receiver), None)
+        (self.sender, self.receiver, None)
     }
 }
 
@@ -61,12 +111,20 @@ pub struct UdpSocketBuffer {
 
 impl PacketSender for UdpSocketBuffer {
     fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
-        self.socket
+        match self
+            .socket
             .as_ref()
             .lock()
             .unwrap()
-            .send_to(payload, address)?;
-        Ok(())
+            .send_to(payload, address)
+        {
+            Ok(_) => Ok(()),
+            // the packet is bigger than the MTU of the network interface (for example an MTU probe):
+            // drop it like any packet bigger than the path MTU
+            #[cfg(target_os = "linux")]
+            Err(e) if e.raw_os_error() == Some(libc::EMSGSIZE) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
     }
 }
 
@@ -101,22 +159,28 @@ mod tests {
 
     use crate::transport::middleware::conditioner::{LinkConditioner, LinkConditionerConfig};
     use crate::transport::middleware::PacketReceiverWrapper;
-    use crate::transport::udp::UdpSocketBuilder;
+    use crate::transport::udp::{UdpConfig, UdpSocketBuilder};
     use crate::transport::{PacketReceiver, PacketSender, Transport, TransportBuilder};
 
     #[test]
     fn test_udp_socket() -> Result<(), anyhow::Error> {
         // let the OS assign a port
         let local_addr = SocketAddr::from_str("127.0.0.1:0")?;
-        let client_socket = UdpSocketBuilder { local_addr }
-            .connect()
-            .context("could not connect to socket")?;
+        let client_socket = UdpSocketBuilder {
+            local_addr,
+            config: UdpConfig::default(),
+        }
+        .connect()
+        .context("could not connect to socket")?;
         let client_addr = client_socket.local_addr();
         let (mut client_sender, _, _) = client_socket.split();
 
-        let server_socket = UdpSocketBuilder { local_addr }
-            .connect()
-            .context("could not connect to socket")?;
+        let server_socket = UdpSocketBuilder {
+            local_addr,
+            config: UdpConfig::default(),
+        }
+        .connect()
+        .context("could not connect to socket")?;
         let server_addr = server_socket.local_addr();
         let (_, mut server_receiver, _) = server_socket.split();
 
@@ -134,6 +198,50 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_udp_socket_batched() -> Result<(), anyhow::Error> {
+        // let the OS assign a port
+        let local_addr = SocketAddr::from_str("127.0.0.1:0")?;
+        let config = UdpConfig {
+            batch_size: 8,
+            ..UdpConfig::default()
+        };
+        let client_socket = UdpSocketBuilder {
+            local_addr,
+            config: config.clone(),
+        }
+        .connect()
+        .context("could not connect to socket")?;
+        let client_addr = client_socket.local_addr();
+        let (mut client_sender, _, _) = client_socket.split();
+
+        let server_socket = UdpSocketBuilder { local_addr, config }
+            .connect()
+            .context("could not connect to socket")?;
+        let server_addr = server_socket.local_addr();
+        let (_, mut server_receiver, _) = server_socket.split();
+
+        // consecutive packets of the same size can be sent as a single message with GSO
+        let packets: Vec<Vec<u8>> = (0..20u8)
+            .map(|i| vec![i; if i < 12 { 1000 } else { 10 + i as usize }])
+            .collect();
+        for packet in &packets {
+            client_sender.send(packet, &server_addr)?;
+        }
+        client_sender.flush()?;
+
+        // sleep a little to give time to the messages to arrive in the socket
+        std::thread::sleep(Duration::from_millis(10));
+
+        let mut received = vec![];
+        while let Some((recv_msg, address)) = server_receiver.recv()? {
+            assert_eq!(address, client_addr);
+            received.push(recv_msg.to_vec());
+        }
+        assert_eq!(received, packets);
+        Ok(())
+    }
+
     #[test]
     fn test_udp_socket_with_conditioner() -> Result<(), anyhow::Error> {
         use mock_instant::MockClock;
@@ -141,15 +249,21 @@ mod tests {
         // let the OS assign a port
         let local_addr = SocketAddr::from_str("127.0.0.1:0")?;
 
-        let client_socket = UdpSocketBuilder { local_addr }
-            .connect()
-            .context("could not connect to socket")?;
+        let client_socket = UdpSocketBuilder {
+            local_addr,
+            config: UdpConfig::default(),
+        }
+        .connect()
+        .context("could not connect to socket")?;
         let client_addr = client_socket.local_addr();
         let (mut client_sender, _, _) = client_socket.split();
 
-        let server_socket = UdpSocketBuilder { local_addr }
-            .connect()
-            .context("could not connect to socket")?;
+        let server_socket = UdpSocketBuilder {
+            local_addr,
+            config: UdpConfig::default(),
+        }
+        .connect()
+        .context("could not connect to socket")?;
         let server_addr = server_socket.local_addr();
         let (_, server_receiver, _) = server_socket.split();
 
@@ -157,6 +271,7 @@ mod tests {
             incoming_latency: Duration::from_millis(100),
             incoming_jitter: Duration::from_millis(0),
             incoming_loss: 0.0,
+            ..Default::default()
         })
         .wrap(server_receiver);
 
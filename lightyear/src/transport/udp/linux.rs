@@ -0,0 +1,489 @@
+//! Batched send and receive on Linux, with `sendmmsg`/`recvmmsg`.
+//!
+//! If the kernel supports it, consecutive packets of the same size sent to the same address are also
+//! merged into a single message with UDP generic segmentation offload (GSO), and datagrams received from the same
+//! address can be coalesced by the kernel with generic receive offload (GRO).
+use std::collections::VecDeque;
+use std::io;
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::fd::AsRawFd;
+use std::sync::Arc;
+
+use tracing::{debug, trace};
+
+use crate::transport::error::Result;
+use crate::transport::{PacketReceiver, PacketSender, MTU};
+
+use super::UdpConfig;
+
+/// Maximum number of segments in a GSO message (`UDP_MAX_SEGMENTS` in the kernel)
+const MAX_GSO_SEGMENTS: usize = 64;
+/// Maximum payload size of a GSO message, or of a datagram coalesced with GRO
+const MAX_SEGMENTED_SIZE: usize = u16::MAX as usize;
+/// Size of the buffer for the ancillary data of a message. It only ever contains one `UDP_SEGMENT` or `UDP_GRO` header
+const CMSG_BUFFER_SIZE: usize = 64;
+
+/// Buffer for the ancillary data of a message, aligned like `cmsghdr`
+#[derive(Clone, Copy)]
+#[repr(C, align(8))]
+struct CmsgBuffer([u8; CMSG_BUFFER_SIZE]);
+
+/// Returns true if the kernel supports UDP GSO on the socket
+fn supports_gso(socket: &std::net::UdpSocket) -> bool {
+    let mut value: libc::c_int = 0;
+    let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: value and len are valid for writes and len is the size of value
+    let ret = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_UDP,
+            libc::UDP_SEGMENT,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    ret == 0
+}
+
+/// Enable UDP GRO on the socket. Returns false if the kernel does not support it
+fn enable_gro(socket: &std::net::UdpSocket) -> bool {
+    let value: libc::c_int = 1;
+    // SAFETY: value is valid for reads of the given size
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_UDP,
+            libc::UDP_GRO,
+            &value as *const libc::c_int as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    ret == 0
+}
+
+/// Set the Don't Fragment bit on the outgoing datagrams, so that packets bigger than the path MTU are dropped
+/// instead of being fragmented by the IP layer. This lets the connection discover the path MTU with probe packets.
+///
+/// The kernel's own path MTU estimate is ignored: datagrams bigger than the MTU of the local interface fail with
+/// `EMSGSIZE`, and bigger than the path MTU are dropped along the way.
+pub(super) fn set_dont_fragment(socket: &std::net::UdpSocket, ipv6: bool) -> io::Result<()> {
+    let (level, name, value) = if ipv6 {
+        (
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MTU_DISCOVER,
+            libc::IPV6_PMTUDISC_PROBE,
+        )
+    } else {
+        (
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            libc::IP_PMTUDISC_PROBE,
+        )
+    };
+    // SAFETY: value is valid for reads of the given size
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn to_sockaddr(address: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    // SAFETY: sockaddr_storage is valid when zeroed, and is large enough and suitably aligned to hold
+    // a sockaddr_in or a sockaddr_in6
+    unsafe {
+        let mut storage: libc::sockaddr_storage = mem::zeroed();
+        let len = match address {
+            SocketAddr::V4(address) => {
+                let sin =
+                    &mut *(&mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in);
+                sin.sin_family = libc::AF_INET as libc::sa_family_t;
+                sin.sin_port = address.port().to_be();
+                sin.sin_addr = libc::in_addr {
+                    s_addr: u32::from_ne_bytes(address.ip().octets()),
+                };
+                mem::size_of::<libc::sockaddr_in>()
+            }
+            SocketAddr::V6(address) => {
+                let sin6 =
+                    &mut *(&mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in6);
+                sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sin6.sin6_port = address.port().to_be();
+                sin6.sin6_flowinfo = address.flowinfo();
+                sin6.sin6_addr = libc::in6_addr {
+                    s6_addr: address.ip().octets(),
+                };
+                sin6.sin6_scope_id = address.scope_id();
+                mem::size_of::<libc::sockaddr_in6>()
+            }
+        };
+        (storage, len as libc::socklen_t)
+    }
+}
+
+fn from_sockaddr(storage: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    // SAFETY: the family of the address tells us which type is stored in the sockaddr_storage
+    unsafe {
+        match storage.ss_family as libc::c_int {
+            libc::AF_INET => {
+                let sin = &*(storage as *const libc::sockaddr_storage as *const libc::sockaddr_in);
+                Some(SocketAddr::V4(SocketAddrV4::new(
+                    Ipv4Addr::from(sin.sin_addr.s_addr.to_ne_bytes()),
+                    u16::from_be(sin.sin_port),
+                )))
+            }
+            libc::AF_INET6 => {
+                let sin6 =
+                    &*(storage as *const libc::sockaddr_storage as *const libc::sockaddr_in6);
+                Some(SocketAddr::V6(SocketAddrV6::new(
+                    Ipv6Addr::from(sin6.sin6_addr.s6_addr),
+                    u16::from_be(sin6.sin6_port),
+                    sin6.sin6_flowinfo,
+                    sin6.sin6_scope_id,
+                )))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A packet waiting to be sent
+struct QueuedPacket {
+    address: SocketAddr,
+    /// Position of the payload in the data buffer of the sender
+    start: usize,
+    len: usize,
+}
+
+/// Buffers the outgoing packets and sends them in batches with `sendmmsg`
+pub(super) struct BatchSender {
+    socket: Arc<std::net::UdpSocket>,
+    batch_size: usize,
+    /// True if consecutive packets to the same address can be sent as a single GSO message
+    gso: bool,
+    /// Payloads of the queued packets, stored contiguously so that they can be sent with GSO
+    data: Vec<u8>,
+    packets: Vec<QueuedPacket>,
+}
+
+impl BatchSender {
+    pub(super) fn new(socket: Arc<std::net::UdpSocket>, config: &UdpConfig) -> Self {
+        let gso = config.segmentation_offload && supports_gso(&socket);
+        debug!(
+            gso,
+            batch_size = config.batch_size,
+            "created batched UDP sender"
+        );
+        Self {
+            socket,
+            batch_size: config.batch_size,
+            gso,
+            data: Vec::with_capacity(config.batch_size * MTU),
+            packets: Vec::with_capacity(config.batch_size),
+        }
+    }
+
+    /// Number of queued packets, starting at `first`, that can be sent as a single GSO message.
+    ///
+    /// All the segments of a message must have the same size, except the last one which can be smaller.
+    fn segment_count(&self, first: usize) -> usize {
+        let segment = &self.packets[first];
+        let mut count = 1;
+        let mut total_len = segment.len;
+        while first + count < self.packets.len() && count < MAX_GSO_SEGMENTS {
+            let packet = &self.packets[first + count];
+            if packet.address != segment.address
+                || packet.len > segment.len
+                || total_len + packet.len > MAX_SEGMENTED_SIZE
+            {
+                break;
+            }
+            count += 1;
+            total_len += packet.len;
+            if packet.len < segment.len {
+                break;
+            }
+        }
+        count
+    }
+
+    /// Send one message per group of packets with a single `sendmmsg` call.
+    ///
+    /// Each group is given as (index of the first packet, number of packets).
+    /// Returns the number of messages that were sent.
+    fn send_messages(&self, groups: &[(usize, usize)]) -> io::Result<usize> {
+        let mut addresses = Vec::with_capacity(groups.len());
+        let mut iovecs = Vec::with_capacity(groups.len());
+        let mut cmsgs = vec![CmsgBuffer([0; CMSG_BUFFER_SIZE]); groups.len()];
+        for &(first, count) in groups {
+            let segment = &self.packets[first];
+            let last = &self.packets[first + count - 1];
+            addresses.push(to_sockaddr(&segment.address));
+            iovecs.push(libc::iovec {
+                iov_base: self.data[segment.start..].as_ptr() as *mut libc::c_void,
+                iov_len: last.start + last.len - segment.start,
+            });
+        }
+        // SAFETY: mmsghdr is valid when zeroed
+        let mut messages: Vec<libc::mmsghdr> = (0..groups.len())
+            .map(|_| unsafe { mem::zeroed() })
+            .collect();
+        for (i, &(first, count)) in groups.iter().enumerate() {
+            let header = &mut messages[i].msg_hdr;
+            header.msg_name =
+                &mut addresses[i].0 as *mut libc::sockaddr_storage as *mut libc::c_void;
+            header.msg_namelen = addresses[i].1;
+            header.msg_iov = &mut iovecs[i];
+            header.msg_iovlen = 1;
+            if count > 1 {
+                let segment_size = self.packets[first].len as u16;
+                header.msg_control = cmsgs[i].0.as_mut_ptr() as *mut libc::c_void;
+                // SAFETY: the control buffer is large enough and aligned for one cmsghdr containing a u16
+                unsafe {
+                    header.msg_controllen =
+                        libc::CMSG_SPACE(mem::size_of::<u16>() as libc::c_uint) as _;
+                    let cmsg = libc::CMSG_FIRSTHDR(header);
+                    (*cmsg).cmsg_level = libc::SOL_UDP;
+                    (*cmsg).cmsg_type = libc::UDP_SEGMENT;
+                    (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<u16>() as libc::c_uint) as _;
+                    std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut u16, segment_size);
+                }
+            }
+        }
+        // SAFETY: all the pointers in the messages point to buffers that outlive the call
+        let ret = unsafe {
+            libc::sendmmsg(
+                self.socket.as_raw_fd(),
+                messages.as_mut_ptr(),
+                messages.len() as libc::c_uint,
+                0,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ret as usize)
+    }
+}
+
+impl PacketSender for BatchSender {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        self.packets.push(QueuedPacket {
+            address: *address,
+            start: self.data.len(),
+            len: payload.len(),
+        });
+        self.data.extend_from_slice(payload);
+        if self.packets.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        // index of the next packet to send
+        let mut next = 0;
+        let result = loop {
+            if next >= self.packets.len() {
+                break Ok(());
+            }
+            let mut groups = Vec::with_capacity(self.batch_size);
+            let mut packet = next;
+            while packet < self.packets.len() && groups.len() < self.batch_size {
+                let count = if self.gso {
+                    self.segment_count(packet)
+                } else {
+                    1
+                };
+                groups.push((packet, count));
+                packet += count;
+            }
+            match self.send_messages(&groups) {
+                Ok(0) => break Ok(()),
+                Ok(sent) => {
+                    next = groups[..sent]
+                        .last()
+                        .map_or(next, |(first, count)| first + count);
+                }
+                // the network device cannot perform the segmentation: send the packets individually
+                Err(e) if self.gso && e.raw_os_error() == Some(libc::EIO) => {
+                    debug!("UDP GSO is not supported by the network device, disabling it");
+                    self.gso = false;
+                }
+                // the first message is bigger than the MTU of the network interface (for example an MTU probe):
+                // drop it and send the rest
+                Err(e) if e.raw_os_error() == Some(libc::EMSGSIZE) => {
+                    trace!("dropping datagram bigger than the MTU of the network interface");
+                    next = groups[0].0 + groups[0].1;
+                }
+                Err(e) => break Err(e.into()),
+            }
+        };
+        // packets that could not be sent are dropped, like any UDP packet
+        self.packets.clear();
+        self.data.clear();
+        result
+    }
+}
+
+impl Drop for BatchSender {
+    fn drop(&mut self) {
+        // send the packets that are still buffered (for example the disconnect packets)
+        if let Err(e) = self.flush() {
+            debug!("could not flush the batched UDP sender on drop: {e:?}");
+        }
+    }
+}
+
+/// Receives datagrams in batches with `recvmmsg`
+pub(super) struct BatchReceiver {
+    socket: Arc<std::net::UdpSocket>,
+    batch_size: usize,
+    /// True if the kernel can coalesce datagrams with GRO
+    gro: bool,
+    /// Size of the buffer of each message
+    buffer_size: usize,
+    buffers: Vec<u8>,
+    addresses: Vec<libc::sockaddr_storage>,
+    cmsgs: Vec<CmsgBuffer>,
+    /// Datagrams received by the last `recvmmsg` call that have not been returned yet:
+    /// (position in the buffers, length, origin)
+    received: VecDeque<(usize, usize, SocketAddr)>,
+}
+
+impl BatchReceiver {
+    pub(super) fn new(socket: Arc<std::net::UdpSocket>, config: &UdpConfig) -> Self {
+        let gro = config.segmentation_offload && enable_gro(&socket);
+        // coalesced datagrams can be much bigger than the MTU
+        let buffer_size = if gro { MAX_SEGMENTED_SIZE } else { MTU };
+        debug!(
+            gro,
+            batch_size = config.batch_size,
+            "created batched UDP receiver"
+        );
+        Self {
+            socket,
+            batch_size: config.batch_size,
+            gro,
+            buffer_size,
+            buffers: vec![0; config.batch_size * buffer_size],
+            // SAFETY: sockaddr_storage is valid when zeroed
+            addresses: vec![unsafe { mem::zeroed() }; config.batch_size],
+            cmsgs: vec![CmsgBuffer([0; CMSG_BUFFER_SIZE]); config.batch_size],
+            received: VecDeque::with_capacity(config.batch_size),
+        }
+    }
+
+    /// Read the available datagrams with a single `recvmmsg` call
+    fn recv_batch(&mut self) -> io::Result<()> {
+        let mut iovecs: Vec<libc::iovec> = self
+            .buffers
+            .chunks_exact_mut(self.buffer_size)
+            .map(|buffer| libc::iovec {
+                iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+                iov_len: buffer.len(),
+            })
+            .collect();
+        // SAFETY: mmsghdr is valid when zeroed
+        let mut messages: Vec<libc::mmsghdr> = (0..self.batch_size)
+            .map(|_| unsafe { mem::zeroed() })
+            .collect();
+        for (i, message) in messages.iter_mut().enumerate() {
+            let header = &mut message.msg_hdr;
+            header.msg_name =
+                &mut self.addresses[i] as *mut libc::sockaddr_storage as *mut libc::c_void;
+            header.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            header.msg_iov = &mut iovecs[i];
+            header.msg_iovlen = 1;
+            header.msg_control = self.cmsgs[i].0.as_mut_ptr() as *mut libc::c_void;
+            header.msg_controllen = CMSG_BUFFER_SIZE as _;
+        }
+        // SAFETY: all the pointers in the messages point to buffers that outlive the call
+        let ret = unsafe {
+            libc::recvmmsg(
+                self.socket.as_raw_fd(),
+                messages.as_mut_ptr(),
+                messages.len() as libc::c_uint,
+                0,
+                std::ptr::null_mut(),
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        for (i, message) in messages.iter().take(ret as usize).enumerate() {
+            let len = message.msg_len as usize;
+            if message.msg_hdr.msg_flags & libc::MSG_TRUNC != 0 {
+                trace!("dropping truncated datagram");
+                continue;
+            }
+            let Some(address) = from_sockaddr(&self.addresses[i]) else {
+                trace!("dropping datagram with an unknown address family");
+                continue;
+            };
+            let start = i * self.buffer_size;
+            // datagrams coalesced with GRO are split back into segments of the given size
+            let segment_size = if self.gro {
+                gro_segment_size(&message.msg_hdr).unwrap_or(len)
+            } else {
+                len
+            };
+            let mut offset = 0;
+            while offset < len {
+                let segment_len = segment_size.min(len - offset);
+                self.received
+                    .push_back((start + offset, segment_len, address));
+                offset += segment_len;
+            }
+            if len == 0 {
+                self.received.push_back((start, 0, address));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the size of the segments of a datagram coalesced with GRO
+fn gro_segment_size(header: &libc::msghdr) -> Option<usize> {
+    // SAFETY: the control buffer was filled by the kernel, and the CMSG macros stay within msg_controllen
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(header);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_UDP && (*cmsg).cmsg_type == libc::UDP_GRO {
+                let segment_size =
+                    std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int);
+                return (segment_size > 0).then_some(segment_size as usize);
+            }
+            cmsg = libc::CMSG_NXTHDR(header, cmsg);
+        }
+    }
+    None
+}
+
+impl PacketReceiver for BatchReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        if self.received.is_empty() {
+            match self.recv_batch() {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    // Nothing to receive on the socket
+                    return Ok(None);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(self
+            .received
+            .pop_front()
+            .map(|(start, len, address)| (&mut self.buffers[start..start + len], address)))
+    }
+}
@@ -0,0 +1,393 @@
+//! The transport is a Unix domain datagram socket, for processes running on the same host.
+//!
+//! Unix sockets are addressed by a path on the filesystem, but the rest of lightyear identifies remotes with a
+//! [`SocketAddr`]. The server therefore assigns a unique synthetic address to every client socket path,
+//! and the client reports the packets it receives as coming from the address that it sends packets to.
+//! The address of a client is released once its socket is closed.
+//!
+//! On Linux, `SOCK_SEQPACKET` sockets are also available (see [`seqpacket`]).
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tracing::{debug, trace};
+
+use crate::transport::{
+    BoxedCloseFn, BoxedReceiver, BoxedSender, PacketReceiver, PacketSender, Transport,
+    TransportBuilder, TransportEnum, LOCAL_SOCKET, MTU,
+};
+
+use super::error::Result;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod seqpacket;
+
+/// Returns the synthetic address of the `id`-th client of a server socket.
+///
+/// The addresses are in the IPv6 unique local range (fd00::/8), so that they are never routable
+fn client_address(id: u128) -> SocketAddr {
+    SocketAddr::new(IpAddr::V6(Ipv6Addr::from((0xfd_u128 << 120) | id)), 0)
+}
+
+/// Remove the socket file left at `path` by a previous run
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    if std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Bind a datagram socket to `path`, removing any stale socket file left by a previous run
+fn bind(path: &Path) -> Result<UnixDatagram> {
+    remove_stale_socket(path)?;
+    let socket = UnixDatagram::bind(path)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+/// Returns a function that removes the socket file when the transport is closed
+fn remove_socket_file(path: PathBuf) -> BoxedCloseFn {
+    Box::new(move || {
+        std::fs::remove_file(&path)?;
+        Ok(())
+    })
+}
+
+pub(crate) struct UnixSocketClientBuilder {
+    pub(crate) path: PathBuf,
+    pub(crate) server_path: PathBuf,
+}
+
+impl TransportBuilder for UnixSocketClientBuilder {
+    fn connect(self) -> Result<TransportEnum> {
+        let socket = bind(&self.path)?;
+        socket.connect(&self.server_path)?;
+        let socket = Arc::new(socket);
+        let server_addr = Arc::new(Mutex::new(LOCAL_SOCKET));
+        Ok(TransportEnum::UnixSocketClient(UnixSocketClient {
+            path: self.path,
+            sender: UnixSocketClientSender {
+                socket: socket.clone(),
+                server_addr: server_addr.clone(),
+            },
+            receiver: UnixSocketClientReceiver {
+                socket,
+                server_addr,
+                buffer: [0; MTU],
+            },
+        }))
+    }
+}
+
+/// Unix datagram socket connected to a server socket
+pub struct UnixSocketClient {
+    path: PathBuf,
+    sender: UnixSocketClientSender,
+    receiver: UnixSocketClientReceiver,
+}
+
+impl Transport for UnixSocketClient {
+    fn local_addr(&self) -> SocketAddr {
+        LOCAL_SOCKET
+    }
+
+    fn split(self) -> (BoxedSender, BoxedReceiver, Option<BoxedCloseFn>) {
+        (
+            Box::new(self.sender),
+            Box::new(self.receiver),
+            Some(remove_socket_file(self.path)),
+        )
+    }
+}
+
+struct UnixSocketClientSender {
+    socket: Arc<UnixDatagram>,
+    /// Address that the connection layer uses for the server
+    server_addr: Arc<Mutex<SocketAddr>>,
+}
+
+impl PacketSender for UnixSocketClientSender {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        // the socket is connected to the server, so the address is only recorded to report
+        // the packets received from the server with the same address
+        *self.server_addr.lock().unwrap() = *address;
+        match self.socket.send(payload) {
+            Ok(_) => Ok(()),
+            // the receive buffer of the server is full: drop the packet like an unreliable transport would
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                trace!("unix socket of the server is full, dropping packet");
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+struct UnixSocketClientReceiver {
+    socket: Arc<UnixDatagram>,
+    server_addr: Arc<Mutex<SocketAddr>>,
+    buffer: [u8; MTU],
+}
+
+impl PacketReceiver for UnixSocketClientReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        match self.socket.recv(&mut self.buffer) {
+            Ok(recv_len) => {
+                let server_addr = *self.server_addr.lock().unwrap();
+                Ok(Some((&mut self.buffer[..recv_len], server_addr)))
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Maps the socket paths of the clients to the synthetic addresses used by the connection layer
+#[derive(Default)]
+struct ClientAddresses {
+    addrs: HashMap<PathBuf, SocketAddr>,
+    paths: HashMap<SocketAddr, PathBuf>,
+    /// Number of addresses assigned so far, so that an address is never reused
+    num_assigned: u128,
+}
+
+impl ClientAddresses {
+    /// Get the address of the client bound to `path`, or assign a new one
+    fn get_or_insert(&mut self, path: &Path) -> SocketAddr {
+        if let Some(addr) = self.addrs.get(path) {
+            return *addr;
+        }
+        self.num_assigned += 1;
+        let addr = client_address(self.num_assigned);
+        self.addrs.insert(path.to_path_buf(), addr);
+        self.paths.insert(addr, path.to_path_buf());
+        addr
+    }
+
+    /// Forget the client at `addr`, once its socket is closed
+    fn remove(&mut self, addr: &SocketAddr) {
+        if let Some(path) = self.paths.remove(addr) {
+            self.addrs.remove(&path);
+        }
+    }
+}
+
+pub(crate) struct UnixSocketServerBuilder {
+    pub(crate) path: PathBuf,
+}
+
+impl TransportBuilder for UnixSocketServerBuilder {
+    fn connect(self) -> Result<TransportEnum> {
+        let socket = Arc::new(bind(&self.path)?);
+        let clients = Arc::new(Mutex::new(ClientAddresses::default()));
+        Ok(TransportEnum::UnixSocketServer(UnixSocketServer {
+            path: self.path,
+            sender: UnixSocketServerSender {
+                socket: socket.clone(),
+                clients: clients.clone(),
+            },
+            receiver: UnixSocketServerReceiver {
+                socket,
+                clients,
+                buffer: [0; MTU],
+            },
+        }))
+    }
+}
+
+/// Unix datagram socket that receives packets from multiple client sockets
+pub struct UnixSocketServer {
+    path: PathBuf,
+    sender: UnixSocketServerSender,
+    receiver: UnixSocketServerReceiver,
+}
+
+impl Transport for UnixSocketServer {
+    fn local_addr(&self) -> SocketAddr {
+        LOCAL_SOCKET
+    }
+
+    fn split(self) -> (BoxedSender, BoxedReceiver, Option<BoxedCloseFn>) {
+        (
+            Box::new(self.sender),
+            Box::new(self.receiver),
+            Some(remove_socket_file(self.path)),
+        )
+    }
+}
+
+struct UnixSocketServerSender {
+    socket: Arc<UnixDatagram>,
+    clients: Arc<Mutex<ClientAddresses>>,
+}
+
+impl PacketSender for UnixSocketServerSender {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        let mut clients = self.clients.lock().unwrap();
+        let Some(path) = clients.paths.get(address) else {
+            debug!(?address, "no unix socket for this address, dropping packet");
+            return Ok(());
+        };
+        match self.socket.send_to(payload, path) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                trace!(
+                    ?address,
+                    "unix socket of the client is full, dropping packet"
+                );
+                Ok(())
+            }
+            // the client closed its socket (and removed the socket file)
+            Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+                debug!(?address, "unix socket of the client is closed: {:?}", e);
+                clients.remove(address);
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+struct UnixSocketServerReceiver {
+    socket: Arc<UnixDatagram>,
+    clients: Arc<Mutex<ClientAddresses>>,
+    buffer: [u8; MTU],
+}
+
+impl PacketReceiver for UnixSocketServerReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((recv_len, address)) => {
+                    let Some(path) = address.as_pathname() else {
+                        // we cannot reply to a socket that is not bound to a path
+                        trace!("dropping packet from an unnamed unix socket");
+                        continue;
+                    };
+                    let addr = self.clients.lock().unwrap().get_or_insert(path);
+                    return Ok(Some((&mut self.buffer[..recv_len], addr)));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::{
+        PacketReceiver, PacketSender, Transport, TransportBuilder, TransportEnum,
+    };
+
+    use super::{UnixSocketClientBuilder, UnixSocketServerBuilder};
+
+    #[test]
+    fn test_unix_socket() -> Result<(), anyhow::Error> {
+        let dir = tempfile::tempdir()?;
+        let server_path = dir.path().join("server.sock");
+        let client_path = dir.path().join("client.sock");
+        let server_addr = "127.0.0.1:5000".parse()?;
+
+        let (mut server_sender, mut server_receiver, server_close) = UnixSocketServerBuilder {
+            path: server_path.clone(),
+        }
+        .connect()?
+        .split();
+        let (mut client_sender, mut client_receiver, client_close) = UnixSocketClientBuilder {
+            path: client_path.clone(),
+            server_path,
+        }
+        .connect()?
+        .split();
+
+        // client to server
+        client_sender.send(b"hello", &server_addr)?;
+        let Some((recv_msg, client_addr)) = server_receiver.recv()? else {
+            panic!("server expected to receive a packet from client");
+        };
+        assert_eq!(recv_msg, b"hello");
+
+        // server to client: the packet is reported as coming from the address the client sends to
+        server_sender.send(b"world", &client_addr)?;
+        let Some((recv_msg, address)) = client_receiver.recv()? else {
+            panic!("client expected to receive a packet from server");
+        };
+        assert_eq!(recv_msg, b"world");
+        assert_eq!(address, server_addr);
+
+        // closing the transports removes the socket files
+        server_close.unwrap()()?;
+        client_close.unwrap()()?;
+        assert!(!client_path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unix_socket_client_closed() -> Result<(), anyhow::Error> {
+        let dir = tempfile::tempdir()?;
+        let server_path = dir.path().join("server.sock");
+        let client_path = dir.path().join("client.sock");
+        let server_addr = "127.0.0.1:5000".parse()?;
+
+        let TransportEnum::UnixSocketServer(server) = (UnixSocketServerBuilder {
+            path: server_path.clone(),
+        })
+        .connect()?
+        else {
+            unreachable!()
+        };
+        let clients = server.sender.clients.clone();
+        let (mut server_sender, mut server_receiver, server_close) = server.split();
+        let (mut client_sender, _, client_close) = UnixSocketClientBuilder {
+            path: client_path,
+            server_path,
+        }
+        .connect()?
+        .split();
+
+        client_sender.send(b"hello", &server_addr)?;
+        let Some((_, client_addr)) = server_receiver.recv()? else {
+            panic!("server expected to receive a packet from client");
+        };
+        assert_eq!(clients.lock().unwrap().paths.len(), 1);
+
+        // the address of the client is released once its socket is closed
+        client_close.unwrap()()?;
+        server_sender.send(b"world", &client_addr)?;
+        assert!(clients.lock().unwrap().paths.is_empty());
+        assert!(clients.lock().unwrap().addrs.is_empty());
+        server_close.unwrap()()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_unix_socket_full() -> Result<(), anyhow::Error> {
+        let dir = tempfile::tempdir()?;
+        let server_path = dir.path().join("server.sock");
+        let server_addr = "127.0.0.1:5000".parse()?;
+
+        let (_, mut server_receiver, _) = UnixSocketServerBuilder {
+            path: server_path.clone(),
+        }
+        .connect()?
+        .split();
+        let (mut client_sender, _, _) = UnixSocketClientBuilder {
+            path: dir.path().join("client.sock"),
+            server_path,
+        }
+        .connect()?
+        .split();
+
+        // the server doesn't read its socket: once its buffer is full, the packets are dropped
+        for _ in 0..10000 {
+            client_sender.send(&[0; 1000], &server_addr)?;
+        }
+        assert!(server_receiver.recv()?.is_some());
+        Ok(())
+    }
+}
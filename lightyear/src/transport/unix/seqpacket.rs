@@ -0,0 +1,418 @@
+//! Unix domain `SOCK_SEQPACKET` sockets, on Linux.
+//!
+//! Sequenced-packet sockets are connection-oriented like streams (the server accepts a connection per client,
+//! and both sides know when the other one closes it), but they preserve the boundaries of the packets,
+//! and are reliable and ordered.
+//!
+//! The standard library does not create them, so the sockets are created with `libc` and then
+//! wrapped in a [`UnixListener`] or a [`UnixStream`], which only read and write the file descriptor.
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::mem;
+use std::net::SocketAddr;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tracing::{debug, trace};
+
+use crate::transport::error::Result;
+use crate::transport::{
+    BoxedCloseFn, BoxedReceiver, BoxedSender, PacketReceiver, PacketSender, Transport,
+    TransportBuilder, TransportEnum, LOCAL_SOCKET, MTU,
+};
+
+use super::{client_address, remove_socket_file, remove_stale_socket};
+
+/// Create a `SOCK_SEQPACKET` socket
+fn socket() -> io::Result<OwnedFd> {
+    // SAFETY: socket has no memory safety requirements
+    let fd = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: fd is a newly created file descriptor that nothing else owns
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Returns the address of the socket at `path`, and its length
+fn socket_address(path: &Path) -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
+    // SAFETY: sockaddr_un is valid when zeroed
+    let mut address: libc::sockaddr_un = unsafe { mem::zeroed() };
+    address.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    let bytes = path.as_os_str().as_bytes();
+    // keep room for the nul terminator
+    if bytes.len() >= address.sun_path.len() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "unix socket path is too long",
+        ));
+    }
+    for (dst, src) in address.sun_path.iter_mut().zip(bytes) {
+        *dst = *src as libc::c_char;
+    }
+    let len = mem::size_of::<libc::sa_family_t>() + bytes.len() + 1;
+    Ok((address, len as libc::socklen_t))
+}
+
+/// Create a socket listening on `path`
+fn listen(path: &Path) -> io::Result<UnixListener> {
+    let fd = socket()?;
+    let (address, len) = socket_address(path)?;
+    // SAFETY: address is a valid sockaddr_un of length len
+    let ret = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            &address as *const libc::sockaddr_un as *const libc::sockaddr,
+            len,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: listen has no memory safety requirements
+    if unsafe { libc::listen(fd.as_raw_fd(), libc::SOMAXCONN) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let listener = UnixListener::from(fd);
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+/// Create a socket connected to the server socket at `path`
+fn connect(path: &Path) -> io::Result<UnixStream> {
+    let fd = socket()?;
+    let (address, len) = socket_address(path)?;
+    // SAFETY: address is a valid sockaddr_un of length len
+    let ret = unsafe {
+        libc::connect(
+            fd.as_raw_fd(),
+            &address as *const libc::sockaddr_un as *const libc::sockaddr,
+            len,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stream = UnixStream::from(fd);
+    stream.set_nonblocking(true)?;
+    Ok(stream)
+}
+
+pub(crate) struct UnixSeqpacketClientBuilder {
+    pub(crate) server_path: PathBuf,
+}
+
+impl TransportBuilder for UnixSeqpacketClientBuilder {
+    fn connect(self) -> Result<TransportEnum> {
+        let stream = Arc::new(connect(&self.server_path)?);
+        let server_addr = Arc::new(Mutex::new(LOCAL_SOCKET));
+        Ok(TransportEnum::UnixSeqpacketClient(UnixSeqpacketClient {
+            sender: UnixSeqpacketClientSender {
+                stream: stream.clone(),
+                server_addr: server_addr.clone(),
+            },
+            receiver: UnixSeqpacketClientReceiver {
+                stream,
+                server_addr,
+                buffer: [0; MTU],
+                closed: false,
+            },
+        }))
+    }
+}
+
+/// Unix seqpacket socket connected to a server socket
+pub struct UnixSeqpacketClient {
+    sender: UnixSeqpacketClientSender,
+    receiver: UnixSeqpacketClientReceiver,
+}
+
+impl Transport for UnixSeqpacketClient {
+    fn local_addr(&self) -> SocketAddr {
+        LOCAL_SOCKET
+    }
+
+    fn split(self) -> (BoxedSender, BoxedReceiver, Option<BoxedCloseFn>) {
+        (Box::new(self.sender), Box::new(self.receiver), None)
+    }
+}
+
+struct UnixSeqpacketClientSender {
+    stream: Arc<UnixStream>,
+    /// Address that the connection layer uses for the server
+    server_addr: Arc<Mutex<SocketAddr>>,
+}
+
+impl PacketSender for UnixSeqpacketClientSender {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        // the socket is connected to the server, so the address is only recorded to report
+        // the packets received from the server with the same address
+        *self.server_addr.lock().unwrap() = *address;
+        match self.stream.as_ref().write(payload) {
+            Ok(_) => Ok(()),
+            // the send buffer is full: drop the packet like an unreliable transport would
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                trace!("unix seqpacket socket is full, dropping packet");
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+struct UnixSeqpacketClientReceiver {
+    stream: Arc<UnixStream>,
+    server_addr: Arc<Mutex<SocketAddr>>,
+    buffer: [u8; MTU],
+    /// Set when the server closed the connection, so that the error is only returned once
+    closed: bool,
+}
+
+impl PacketReceiver for UnixSeqpacketClientReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        if self.closed {
+            return Ok(None);
+        }
+        match self.stream.as_ref().read(&mut self.buffer) {
+            Ok(0) => {
+                self.closed = true;
+                Err(io::Error::from(ErrorKind::UnexpectedEof).into())
+            }
+            Ok(recv_len) => {
+                let server_addr = *self.server_addr.lock().unwrap();
+                Ok(Some((&mut self.buffer[..recv_len], server_addr)))
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => {
+                self.closed = true;
+                Err(e.into())
+            }
+        }
+    }
+}
+
+/// Connections of the clients, indexed by the synthetic addresses used by the connection layer
+#[derive(Default)]
+struct ClientStreams {
+    streams: HashMap<SocketAddr, UnixStream>,
+    /// Number of addresses assigned so far, so that an address is never reused
+    num_assigned: u128,
+}
+
+impl ClientStreams {
+    fn insert(&mut self, stream: UnixStream) -> SocketAddr {
+        self.num_assigned += 1;
+        let addr = client_address(self.num_assigned);
+        self.streams.insert(addr, stream);
+        addr
+    }
+}
+
+pub(crate) struct UnixSeqpacketServerBuilder {
+    pub(crate) path: PathBuf,
+}
+
+impl TransportBuilder for UnixSeqpacketServerBuilder {
+    fn connect(self) -> Result<TransportEnum> {
+        remove_stale_socket(&self.path)?;
+        let listener = listen(&self.path)?;
+        let clients = Arc::new(Mutex::new(ClientStreams::default()));
+        Ok(TransportEnum::UnixSeqpacketServer(UnixSeqpacketServer {
+            path: self.path,
+            sender: UnixSeqpacketServerSender {
+                clients: clients.clone(),
+            },
+            receiver: UnixSeqpacketServerReceiver {
+                listener,
+                clients,
+                buffer: [0; MTU],
+            },
+        }))
+    }
+}
+
+/// Unix seqpacket socket that accepts connections from multiple clients
+pub struct UnixSeqpacketServer {
+    path: PathBuf,
+    sender: UnixSeqpacketServerSender,
+    receiver: UnixSeqpacketServerReceiver,
+}
+
+impl Transport for UnixSeqpacketServer {
+    fn local_addr(&self) -> SocketAddr {
+        LOCAL_SOCKET
+    }
+
+    fn split(self) -> (BoxedSender, BoxedReceiver, Option<BoxedCloseFn>) {
+        (
+            Box::new(self.sender),
+            Box::new(self.receiver),
+            Some(remove_socket_file(self.path)),
+        )
+    }
+}
+
+struct UnixSeqpacketServerSender {
+    clients: Arc<Mutex<ClientStreams>>,
+}
+
+impl PacketSender for UnixSeqpacketServerSender {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        let mut clients = self.clients.lock().unwrap();
+        let Some(stream) = clients.streams.get(address) else {
+            debug!(?address, "no unix socket for this address, dropping packet");
+            return Ok(());
+        };
+        match (&*stream).write(payload) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                trace!(?address, "unix seqpacket socket is full, dropping packet");
+                Ok(())
+            }
+            Err(e) => {
+                debug!(?address, "unix seqpacket connection closed: {:?}", e);
+                clients.streams.remove(address);
+                Ok(())
+            }
+        }
+    }
+}
+
+struct UnixSeqpacketServerReceiver {
+    listener: UnixListener,
+    clients: Arc<Mutex<ClientStreams>>,
+    buffer: [u8; MTU],
+}
+
+impl PacketReceiver for UnixSeqpacketServerReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        let mut clients = self.clients.lock().unwrap();
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    let addr = clients.insert(stream);
+                    debug!(?addr, "accepted unix seqpacket connection");
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    debug!("error accepting unix seqpacket connection: {:?}", e);
+                    break;
+                }
+            }
+        }
+        let mut closed = vec![];
+        let mut received = None;
+        for (address, stream) in clients.streams.iter() {
+            match (&*stream).read(&mut self.buffer) {
+                Ok(0) => closed.push(*address),
+                Ok(recv_len) => {
+                    received = Some((recv_len, *address));
+                    break;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => {
+                    debug!(?address, "unix seqpacket connection closed: {:?}", e);
+                    closed.push(*address);
+                }
+            }
+        }
+        for address in closed {
+            debug!(?address, "unix seqpacket connection closed");
+            clients.streams.remove(&address);
+        }
+        Ok(received.map(|(recv_len, address)| (&mut self.buffer[..recv_len], address)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::{
+        PacketReceiver, PacketSender, Transport, TransportBuilder, TransportEnum,
+    };
+
+    use super::{UnixSeqpacketClientBuilder, UnixSeqpacketServerBuilder};
+
+    #[test]
+    fn test_unix_seqpacket_socket() -> Result<(), anyhow::Error> {
+        let dir = tempfile::tempdir()?;
+        let server_path = dir.path().join("server.sock");
+        let server_addr = "127.0.0.1:5000".parse()?;
+
+        let TransportEnum::UnixSeqpacketServer(server) = (UnixSeqpacketServerBuilder {
+            path: server_path.clone(),
+        })
+        .connect()?
+        else {
+            unreachable!()
+        };
+        let clients = server.sender.clients.clone();
+        let (mut server_sender, mut server_receiver, server_close) = server.split();
+        let (mut client_sender, mut client_receiver, _) = UnixSeqpacketClientBuilder {
+            server_path: server_path.clone(),
+        }
+        .connect()?
+        .split();
+
+        // client to server: the packet boundaries are preserved
+        client_sender.send(b"hello", &server_addr)?;
+        client_sender.send(b"world!", &server_addr)?;
+        let Some((recv_msg, client_addr)) = server_receiver.recv()? else {
+            panic!("server expected to receive a packet from client");
+        };
+        assert_eq!(recv_msg, b"hello");
+        let Some((recv_msg, address)) = server_receiver.recv()? else {
+            panic!("server expected to receive a packet from client");
+        };
+        assert_eq!(recv_msg, b"world!");
+        assert_eq!(address, client_addr);
+
+        // server to client: the packet is reported as coming from the address the client sends to
+        server_sender.send(b"pong", &client_addr)?;
+        let Some((recv_msg, address)) = client_receiver.recv()? else {
+            panic!("client expected to receive a packet from server");
+        };
+        assert_eq!(recv_msg, b"pong");
+        assert_eq!(address, server_addr);
+
+        // the connection is removed once the client closes it
+        drop(client_sender);
+        drop(client_receiver);
+        assert!(server_receiver.recv()?.is_none());
+        assert!(clients.lock().unwrap().streams.is_empty());
+
+        server_close.unwrap()()?;
+        assert!(!server_path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unix_seqpacket_server_closed() -> Result<(), anyhow::Error> {
+        let dir = tempfile::tempdir()?;
+        let server_path = dir.path().join("server.sock");
+        let server_addr = "127.0.0.1:5000".parse()?;
+
+        let (server_sender, mut server_receiver, server_close) = UnixSeqpacketServerBuilder {
+            path: server_path.clone(),
+        }
+        .connect()?
+        .split();
+        let (mut client_sender, mut client_receiver, _) =
+            UnixSeqpacketClientBuilder { server_path }
+                .connect()?
+                .split();
+        client_sender.send(b"hello", &server_addr)?;
+        assert!(server_receiver.recv()?.is_some());
+
+        // the client returns an error once when the server closes the connection
+        drop(server_sender);
+        drop(server_receiver);
+        server_close.unwrap()()?;
+        assert!(client_receiver.recv().is_err());
+        assert!(client_receiver.recv()?.is_none());
+        Ok(())
+    }
+}
@@ -7,11 +7,12 @@ use std::{
 };
 
 use async_compat::Compat;
-use bevy::tasks::{futures_lite, IoTaskPool};
+use bevy::tasks::{futures_lite, IoTaskPool, Task};
 use bevy::utils::hashbrown::HashMap;
 use futures_util::stream::FusedStream;
 use futures_util::{future, pin_mut, stream::TryStreamExt, SinkExt, StreamExt, TryFutureExt};
 use tokio::{
+    io::{AsyncRead, AsyncWrite},
     net::{TcpListener, TcpStream},
     sync::{
         mpsc::{
@@ -21,13 +22,16 @@ use tokio::{
         Mutex,
     },
 };
+use tokio_rustls::{client::TlsStream, TlsConnector};
 use tokio_tungstenite::{
-    connect_async, connect_async_with_config, tungstenite::Message, MaybeTlsStream,
+    client_async_with_config, connect_async, connect_async_with_config, tungstenite::Message,
+    MaybeTlsStream, WebSocketStream,
 };
 use tracing::{debug, info, trace};
 use tracing_log::log::error;
 
 use crate::transport::error::{Error, Result};
+use crate::transport::websocket::WebSocketTlsConfig;
 use crate::transport::{
     BoxedCloseFn, BoxedReceiver, BoxedSender, PacketReceiver, PacketSender, Transport,
     TransportBuilder, TransportEnum, LOCAL_SOCKET, MTU,
@@ -35,11 +39,13 @@ use crate::transport::{
 
 pub(crate) struct WebSocketClientSocketBuilder {
     pub(crate) server_addr: SocketAddr,
+    /// If set, the connection is encrypted with TLS (`wss://`)
+    pub(crate) tls: Option<WebSocketTlsConfig>,
 }
 
 impl TransportBuilder for WebSocketClientSocketBuilder {
     fn connect(self) -> Result<TransportEnum> {
-        let (serverbound_tx, mut serverbound_rx) = unbounded_channel::<Message>();
+        let (serverbound_tx, serverbound_rx) = unbounded_channel::<Message>();
         let (clientbound_tx, clientbound_rx) = unbounded_channel::<Message>();
         let (close_tx, mut close_rx) = mpsc::channel(1);
 
@@ -52,43 +58,33 @@ impl TransportBuilder for WebSocketClientSocketBuilder {
 
         // TODO: make connect async?
         // connect to the server
-        let (ws_stream, _) = IoTaskPool::get()
+        let server_addr = self.server_addr;
+        let tls = self.tls;
+        let (send_handle, recv_handle) = IoTaskPool::get()
             .scope(|scope| {
                 scope.spawn(Compat::new(async move {
-                    connect_async_with_config(format!("ws://{}/", self.server_addr), None, true)
-                        .await
+                    let handles = match tls {
+                        None => {
+                            let (ws_stream, _) = connect_async_with_config(
+                                format!("ws://{}/", server_addr),
+                                None,
+                                true,
+                            )
+                            .await?;
+                            spawn_tasks(ws_stream, serverbound_rx, clientbound_tx)
+                        }
+                        Some(tls) => {
+                            let ws_stream = connect_tls(server_addr, tls).await?;
+                            spawn_tasks(ws_stream, serverbound_rx, clientbound_tx)
+                        }
+                    };
+                    Ok::<_, Error>(handles)
                 }))
             })
             .pop()
             .unwrap()?;
         info!("WebSocket handshake has been successfully completed");
-        let (mut write, mut read) = ws_stream.split();
-
-        let send_handle = IoTaskPool::get().spawn(Compat::new(async move {
-            while let Some(msg) = read.next().await {
-                let msg = msg
-                    .map_err(|e| {
-                        error!("Error while receiving websocket msg: {}", e);
-                    })
-                    .unwrap();
-
-                clientbound_tx
-                    .send(msg)
-                    .expect("Unable to propagate the read websocket message to the receiver");
-            }
-            // when we reach this point, the stream is closed
-        }));
-        let recv_handle = IoTaskPool::get().spawn(Compat::new(async move {
-            while let Some(msg) = serverbound_rx.recv().await {
-                write
-                    .send(msg)
-                    .await
-                    .map_err(|e| {
-                        error!("Encountered error while sending websocket msg: {}", e);
-                    })
-                    .unwrap();
-            }
-        }));
+
         // wait for a signal that the io should be closed
         IoTaskPool::get()
             .spawn(async move {
@@ -107,6 +103,65 @@ impl TransportBuilder for WebSocketClientSocketBuilder {
     }
 }
 
+/// Open a TCP connection to the server, then perform the TLS and WebSocket handshakes
+async fn connect_tls(
+    server_addr: SocketAddr,
+    tls: WebSocketTlsConfig,
+) -> Result<WebSocketStream<TlsStream<TcpStream>>> {
+    let connector = TlsConnector::from(Arc::new(tls.certificate_validation.client_config()?));
+    let server_name = rustls::ServerName::try_from(tls.server_name.as_str())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let stream = TcpStream::connect(server_addr).await?;
+    stream.set_nodelay(true)?;
+    let stream = connector.connect(server_name, stream).await?;
+    let (ws_stream, _) = client_async_with_config(
+        format!("wss://{}:{}/", tls.server_name, server_addr.port()),
+        stream,
+        None,
+    )
+    .await?;
+    Ok(ws_stream)
+}
+
+/// Spawn the tasks that forward the messages between the websocket and the channels
+fn spawn_tasks<S>(
+    ws_stream: WebSocketStream<S>,
+    mut serverbound_rx: UnboundedReceiver<Message>,
+    clientbound_tx: UnboundedSender<Message>,
+) -> (Task<()>, Task<()>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut write, mut read) = ws_stream.split();
+
+    let send_handle = IoTaskPool::get().spawn(Compat::new(async move {
+        while let Some(msg) = read.next().await {
+            let msg = msg
+                .map_err(|e| {
+                    error!("Error while receiving websocket msg: {}", e);
+                })
+                .unwrap();
+
+            clientbound_tx
+                .send(msg)
+                .expect("Unable to propagate the read websocket message to the receiver");
+        }
+        // when we reach this point, the stream is closed
+    }));
+    let recv_handle = IoTaskPool::get().spawn(Compat::new(async move {
+        while let Some(msg) = serverbound_rx.recv().await {
+            write
+                .send(msg)
+                .await
+                .map_err(|e| {
+                    error!("Encountered error while sending websocket msg: {}", e);
+                })
+                .unwrap();
+        }
+    }));
+    (send_handle, recv_handle)
+}
+
 pub struct WebSocketClientSocket {
     local_addr: SocketAddr,
     sender: WebSocketClientSocketSender,
@@ -18,6 +18,7 @@ use web_sys::{
 };
 
 use crate::transport::error::{Error, Result};
+use crate::transport::websocket::WebSocketTlsConfig;
 use crate::transport::{
     BoxedCloseFn, BoxedReceiver, BoxedSender, PacketReceiver, PacketSender, Transport,
     TransportBuilder, TransportEnum, LOCAL_SOCKET, MTU,
@@ -25,6 +26,9 @@ use crate::transport::{
 
 pub(crate) struct WebSocketClientSocketBuilder {
     pub(crate) server_addr: SocketAddr,
+    /// If set, the connection is encrypted with TLS (`wss://`).
+    /// The certificate of the server is validated by the browser
+    pub(crate) tls: Option<WebSocketTlsConfig>,
 }
 
 impl TransportBuilder for WebSocketClientSocketBuilder {
@@ -43,7 +47,11 @@ impl TransportBuilder for WebSocketClientSocketBuilder {
 
         info!("Starting client websocket task");
 
-        let ws = WebSocket::new(&format!("ws://{}/", self.server_addr)).unwrap();
+        let url = match &self.tls {
+            None => format!("ws://{}/", self.server_addr),
+            Some(tls) => format!("wss://{}:{}/", tls.server_name, self.server_addr.port()),
+        };
+        let ws = WebSocket::new(&url).unwrap();
 
         ws.set_binary_type(BinaryType::Arraybuffer);
 
@@ -1,4 +1,6 @@
 //! Transport using the WebSocket protocol (based on TCP, HTTP)
+//!
+//! The connection can be encrypted with TLS (`wss://`), which is required by browsers on pages served over HTTPS.
 cfg_if::cfg_if! {
     if #[cfg(all(feature = "websocket", target_family = "wasm"))] {
             pub mod client_wasm;
@@ -7,28 +9,64 @@ cfg_if::cfg_if! {
             pub mod server;
             pub mod client_native;
             pub use client_native as client;
+            pub(crate) mod tls;
     }
 }
 
+/// TLS settings used by the client to connect to the server with `wss://`
+#[derive(Debug, Clone)]
+pub struct WebSocketTlsConfig {
+    /// Domain name of the server. It is used in the url of the server and must match the server's certificate
+    pub server_name: String,
+    /// How the native client validates the certificate presented by the server.
+    ///
+    /// This is ignored on wasm, where the browser always validates the certificate.
+    pub certificate_validation: WebSocketCertificateValidation,
+}
+
+/// How the client validates the certificate presented by the server
+#[derive(Debug, Clone, Default)]
+pub enum WebSocketCertificateValidation {
+    /// Trust the certificates signed by the Mozilla root certificate authorities
+    #[default]
+    WebPkiRoots,
+    /// Only trust the given DER-encoded certificates (for example the self-signed certificate of a local server)
+    Trusted(Vec<Vec<u8>>),
+    /// Accept any certificate.
+    ///
+    /// The connection is still encrypted but the server is not authenticated, so this should only
+    /// be used during development.
+    Disabled,
+}
+
 #[cfg(test)]
 mod tests {
+    use bevy::tasks::{IoTaskPool, TaskPool};
     use bevy::utils::Duration;
 
-    use crate::transport::{PacketReceiver, PacketSender};
+    use crate::transport::{PacketReceiver, PacketSender, Transport, TransportBuilder};
 
     use super::client::*;
     use super::server::*;
 
     #[cfg(not(target_family = "wasm"))]
-    #[tokio::test]
-    async fn test_websocket_native() -> anyhow::Result<()> {
+    #[test]
+    fn test_websocket_native() -> anyhow::Result<()> {
+        IoTaskPool::get_or_init(TaskPool::new);
         let server_addr = "127.0.0.1:7000".parse().unwrap();
 
-        let client_socket = WebSocketClientSocket::new(server_addr);
-        let server_socket = WebSocketServerSocket::new(server_addr);
+        let client_socket = WebSocketClientSocketBuilder {
+            server_addr,
+            tls: None,
+        };
+        let server_socket = WebSocketServerSocketBuilder {
+            server_addr,
+            certificate: None,
+        };
 
-        let (mut server_send, mut server_recv) = server_socket.listen();
-        let (mut client_send, mut client_recv) = client_socket.listen();
+        // the connections are closed when the close functions are dropped
+        let (mut server_send, mut server_recv, _server_close) = server_socket.connect()?.split();
+        let (mut client_send, mut client_recv, _client_close) = client_socket.connect()?.split();
 
         let msg = b"hello world";
 
@@ -36,7 +74,7 @@ mod tests {
         client_send.send(msg, &server_addr)?;
 
         // sleep a little to give time to the message to arrive in the socket
-        tokio::time::sleep(Duration::from_millis(20)).await;
+        std::thread::sleep(Duration::from_millis(20));
 
         if let Some((recv_msg, address)) = server_recv.recv()? {
             assert_eq!(recv_msg, msg);
@@ -48,7 +86,7 @@ mod tests {
         };
 
         // sleep a little to give time to the message to arrive in the socket
-        tokio::time::sleep(Duration::from_millis(20)).await;
+        std::thread::sleep(Duration::from_millis(20));
 
         let Some((recv_msg, address)) = client_recv.recv()? else {
             panic!("client expected to receive a packet from server");
@@ -58,4 +96,58 @@ mod tests {
         dbg!(recv_msg);
         Ok(())
     }
+
+    #[cfg(not(target_family = "wasm"))]
+    #[test]
+    fn test_secure_websocket_native() -> anyhow::Result<()> {
+        use super::tls::WebSocketCertificate;
+        use super::{WebSocketCertificateValidation, WebSocketTlsConfig};
+
+        IoTaskPool::get_or_init(TaskPool::new);
+        let server_addr = "127.0.0.1:7001".parse().unwrap();
+        let certificate = WebSocketCertificate::self_signed(vec!["localhost".to_string()])?;
+        let WebSocketCertificate::InMemory {
+            certificate_chain, ..
+        } = &certificate
+        else {
+            unreachable!()
+        };
+
+        let client_socket = WebSocketClientSocketBuilder {
+            server_addr,
+            tls: Some(WebSocketTlsConfig {
+                server_name: "localhost".to_string(),
+                certificate_validation: WebSocketCertificateValidation::Trusted(
+                    certificate_chain.clone(),
+                ),
+            }),
+        };
+        let server_socket = WebSocketServerSocketBuilder {
+            server_addr,
+            certificate: Some(certificate.clone()),
+        };
+
+        // the connections are closed when the close functions are dropped
+        let (mut server_send, mut server_recv, _server_close) = server_socket.connect()?.split();
+        let (mut client_send, mut client_recv, _client_close) = client_socket.connect()?.split();
+
+        let msg = b"hello world";
+
+        // the packets go through the TLS handshake before reaching the socket
+        client_send.send(msg, &server_addr)?;
+        std::thread::sleep(Duration::from_millis(50));
+        let Some((recv_msg, address)) = server_recv.recv()? else {
+            panic!("server expected to receive a packet from client");
+        };
+        assert_eq!(recv_msg, msg);
+
+        server_send.send(msg, &address)?;
+        std::thread::sleep(Duration::from_millis(50));
+        let Some((recv_msg, address)) = client_recv.recv()? else {
+            panic!("client expected to receive a packet from server");
+        };
+        assert_eq!(address, server_addr);
+        assert_eq!(recv_msg, msg);
+        Ok(())
+    }
 }
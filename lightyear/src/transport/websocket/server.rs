@@ -12,14 +12,17 @@ use futures_util::{
     SinkExt, StreamExt, TryFutureExt,
 };
 use tokio::{
+    io::{AsyncRead, AsyncWrite},
     net::{TcpListener, TcpStream},
     sync::mpsc::{error::TryRecvError, unbounded_channel, UnboundedReceiver, UnboundedSender},
 };
+use tokio_rustls::TlsAcceptor;
 use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
 use tracing::{info, trace};
 use tracing_log::log::error;
 
 use crate::transport::error::{Error, Result};
+use crate::transport::websocket::tls::WebSocketCertificate;
 use crate::transport::{
     BoxedCloseFn, BoxedReceiver, BoxedSender, PacketReceiver, PacketSender, Transport,
     TransportBuilder, TransportEnum, MTU,
@@ -27,6 +30,8 @@ use crate::transport::{
 
 pub(crate) struct WebSocketServerSocketBuilder {
     pub(crate) server_addr: SocketAddr,
+    /// If set, the server only accepts connections encrypted with TLS (`wss://`)
+    pub(crate) certificate: Option<WebSocketCertificate>,
 }
 
 impl TransportBuilder for WebSocketServerSocketBuilder {
@@ -44,6 +49,14 @@ impl TransportBuilder for WebSocketServerSocketBuilder {
             serverbound_rx,
         };
 
+        // load the certificate before listening, so that an invalid certificate is reported immediately
+        let tls_acceptor = self
+            .certificate
+            .map(|certificate| {
+                Ok::<_, Error>(TlsAcceptor::from(Arc::new(certificate.server_config()?)))
+            })
+            .transpose()?;
+
         let listener = futures_lite::future::block_on(Compat::new(async move {
             TcpListener::bind(self.server_addr).await
         }))?;
@@ -54,57 +67,39 @@ impl TransportBuilder for WebSocketServerSocketBuilder {
                 while let Ok((stream, addr)) = listener.accept().await {
                     let clientbound_tx_map = clientbound_tx_map.clone();
                     let serverbound_tx = serverbound_tx.clone();
-
-                    let ws_stream = tokio_tungstenite::accept_async(stream)
-                        .await
-                        .expect("Error during the websocket handshake occurred");
-                    info!("New WebSocket connection: {}", addr);
-
-                    let (clientbound_tx, mut clientbound_rx) = unbounded_channel::<Message>();
-                    let (mut write, mut read) = ws_stream.split();
-
-                    clientbound_tx_map
-                        .lock()
-                        .unwrap()
-                        .insert(addr, clientbound_tx);
-
-                    let serverbound_tx = serverbound_tx.clone();
-
-                    let clientbound_handle = IoTaskPool::get().spawn(async move {
-                        while let Some(msg) = clientbound_rx.recv().await {
-                            write
-                                .send(msg)
-                                .await
-                                .map_err(|e| {
-                                    error!("Encountered error while sending websocket msg: {}", e);
-                                })
-                                .unwrap();
-                        }
-                        write.close().await.unwrap_or_else(|e| {
-                            error!("Error closing websocket: {:?}", e);
-                        });
-                    });
-                    let serverbound_handle = IoTaskPool::get().spawn(async move {
-                        while let Some(msg) = read.next().await {
-                            match msg {
-                                Ok(msg) => {
-                                    serverbound_tx.send((addr, msg)).unwrap_or_else(|e| {
-                                        error!("receive websocket error: {:?}", e)
-                                    });
-                                }
-                                Err(e) => {
-                                    error!("receive websocket error: {:?}", e);
+                    let tls_acceptor = tls_acceptor.clone();
+                    // handle each connection in its own task, so that a slow handshake does not
+                    // prevent other clients from connecting
+                    IoTaskPool::get()
+                        .spawn(Compat::new(async move {
+                            let result = match tls_acceptor {
+                                None => {
+                                    handle_connection(
+                                        stream,
+                                        addr,
+                                        clientbound_tx_map,
+                                        serverbound_tx,
+                                    )
+                                    .await
                                 }
+                                Some(tls_acceptor) => match tls_acceptor.accept(stream).await {
+                                    Ok(stream) => {
+                                        handle_connection(
+                                            stream,
+                                            addr,
+                                            clientbound_tx_map,
+                                            serverbound_tx,
+                                        )
+                                        .await
+                                    }
+                                    Err(e) => Err(e.into()),
+                                },
+                            };
+                            if let Err(e) = result {
+                                error!("WebSocket connection with {} failed: {:?}", addr, e);
                             }
-                        }
-                    });
-
-                    let _closed =
-                        futures_lite::future::race(clientbound_handle, serverbound_handle).await;
-
-                    info!("Connection with {} closed", addr);
-                    clientbound_tx_map.lock().unwrap().remove(&addr);
-                    // dropping the task handles cancels them
+                        }))
+                        .detach();
                 }
             }))
             .detach();
@@ -116,34 +111,71 @@ impl TransportBuilder for WebSocketServerSocketBuilder {
     }
 }
 
+/// Perform the WebSocket handshake with a client, then forward the messages between the websocket
+/// and the channels until the connection is closed
+async fn handle_connection<S>(
+    stream: S,
+    addr: SocketAddr,
+    clientbound_tx_map: ClientBoundTxMap,
+    serverbound_tx: UnboundedSender<(SocketAddr, Message)>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let ws_stream = tokio_tungstenite::accept_async(stream).await?;
+    info!("New WebSocket connection: {}", addr);
+
+    let (clientbound_tx, mut clientbound_rx) = unbounded_channel::<Message>();
+    let (mut write, mut read) = ws_stream.split();
+
+    clientbound_tx_map
+        .lock()
+        .unwrap()
+        .insert(addr, clientbound_tx);
+
+    let clientbound_handle = IoTaskPool::get().spawn(async move {
+        while let Some(msg) = clientbound_rx.recv().await {
+            write
+                .send(msg)
+                .await
+                .map_err(|e| {
+                    error!("Encountered error while sending websocket msg: {}", e);
+                })
+                .unwrap();
+        }
+        write.close().await.unwrap_or_else(|e| {
+            error!("Error closing websocket: {:?}", e);
+        });
+    });
+    let serverbound_handle = IoTaskPool::get().spawn(async move {
+        while let Some(msg) = read.next().await {
+            match msg {
+                Ok(msg) => {
+                    serverbound_tx
+                        .send((addr, msg))
+                        .unwrap_or_else(|e| error!("receive websocket error: {:?}", e));
+                }
+                Err(e) => {
+                    error!("receive websocket error: {:?}", e);
+                }
+            }
+        }
+    });
+
+    let _closed = futures_lite::future::race(clientbound_handle, serverbound_handle).await;
+
+    info!("Connection with {} closed", addr);
+    clientbound_tx_map.lock().unwrap().remove(&addr);
+    // dropping the task handles cancels them
+    Ok(())
+}
+
 pub struct WebSocketServerSocket {
     local_addr: SocketAddr,
     sender: WebSocketServerSocketSender,
     receiver: WebSocketServerSocketReceiver,
 }
 
-impl WebSocketServerSocket {
-    /*fn get_tls_acceptor(&self) -> Option<TlsAcceptor> {
-        if let Some(config) = &self.tls_config {
-            let server_config = ServerConfig::builder()
-                .with_no_client_auth()
-                .with_single_cert(
-                    certs(&mut BufReader::new(&*config.certs))
-                        .map(|e| e.unwrap())
-                        .collect(),
-                    rsa_private_keys(&mut BufReader::new(&*config.keys))
-                        .map(|e| e.unwrap().into())
-                        .next()
-                        .unwrap(),
-                )
-                .unwrap();
-            Some(TlsAcceptor::from(Arc::new(server_config)))
-        } else {
-            None
-        }
-    }*/
-}
-
 type ClientBoundTxMap = Arc<Mutex<HashMap<SocketAddr, UnboundedSender<Message>>>>;
 
 impl Transport for WebSocketServerSocket {
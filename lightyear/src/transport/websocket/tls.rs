@@ -0,0 +1,155 @@
+//! TLS configuration for the native WebSocket client and server
+use std::fmt::Formatter;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{
+    Certificate, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerConfig,
+};
+
+use crate::transport::error::Result;
+
+use super::WebSocketCertificateValidation;
+
+/// Certificate used by the WebSocket server to accept `wss://` connections
+#[derive(Clone)]
+pub enum WebSocketCertificate {
+    /// Load the certificate chain and the private key from PEM files
+    Files {
+        certificate_path: PathBuf,
+        private_key_path: PathBuf,
+    },
+    /// Use a DER-encoded certificate chain and private key (PKCS#8, PKCS#1 or SEC1)
+    InMemory {
+        certificate_chain: Vec<Vec<u8>>,
+        private_key: Vec<u8>,
+    },
+}
+
+impl WebSocketCertificate {
+    /// Generate a self-signed certificate, valid for the given names.
+    ///
+    /// The clients need to trust the certificate (with [`WebSocketCertificateValidation::Trusted`]) to be able to connect.
+    pub fn self_signed(subject_alt_names: Vec<String>) -> Result<Self> {
+        let certificate =
+            rcgen::generate_simple_self_signed(subject_alt_names).map_err(std::io::Error::other)?;
+        Ok(Self::InMemory {
+            certificate_chain: vec![certificate.serialize_der().map_err(std::io::Error::other)?],
+            private_key: certificate.serialize_private_key_der(),
+        })
+    }
+
+    /// DER-encoded certificate chain and private key
+    fn load(&self) -> Result<(Vec<Certificate>, PrivateKey)> {
+        match self {
+            WebSocketCertificate::Files {
+                certificate_path,
+                private_key_path,
+            } => {
+                let mut reader = BufReader::new(std::fs::File::open(certificate_path)?);
+                let certificate_chain = rustls_pemfile::certs(&mut reader)?
+                    .into_iter()
+                    .map(Certificate)
+                    .collect();
+                let mut reader = BufReader::new(std::fs::File::open(private_key_path)?);
+                let private_key = rustls_pemfile::read_all(&mut reader)?
+                    .into_iter()
+                    .find_map(|item| match item {
+                        rustls_pemfile::Item::PKCS8Key(key)
+                        | rustls_pemfile::Item::RSAKey(key)
+                        | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+                        _ => None,
+                    })
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("no private key found in {:?}", private_key_path),
+                        )
+                    })?;
+                Ok((certificate_chain, private_key))
+            }
+            WebSocketCertificate::InMemory {
+                certificate_chain,
+                private_key,
+            } => Ok((
+                certificate_chain.iter().cloned().map(Certificate).collect(),
+                PrivateKey(private_key.clone()),
+            )),
+        }
+    }
+
+    pub(crate) fn server_config(&self) -> Result<ServerConfig> {
+        let (certificate_chain, private_key) = self.load()?;
+        Ok(ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certificate_chain, private_key)?)
+    }
+}
+
+impl std::fmt::Debug for WebSocketCertificate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // do not print the private key
+        match self {
+            WebSocketCertificate::Files {
+                certificate_path,
+                private_key_path,
+            } => f
+                .debug_struct("Files")
+                .field("certificate_path", certificate_path)
+                .field("private_key_path", private_key_path)
+                .finish(),
+            WebSocketCertificate::InMemory { .. } => f.debug_struct("InMemory").finish(),
+        }
+    }
+}
+
+impl WebSocketCertificateValidation {
+    pub(crate) fn client_config(&self) -> Result<ClientConfig> {
+        let builder = ClientConfig::builder().with_safe_defaults();
+        let config = match self {
+            WebSocketCertificateValidation::WebPkiRoots => {
+                let mut roots = RootCertStore::empty();
+                roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+                    OwnedTrustAnchor::from_subject_spki_name_constraints(
+                        anchor.subject,
+                        anchor.spki,
+                        anchor.name_constraints,
+                    )
+                }));
+                builder.with_root_certificates(roots).with_no_client_auth()
+            }
+            WebSocketCertificateValidation::Trusted(certificates) => {
+                let mut roots = RootCertStore::empty();
+                for certificate in certificates {
+                    roots.add(&Certificate(certificate.clone()))?;
+                }
+                builder.with_root_certificates(roots).with_no_client_auth()
+            }
+            WebSocketCertificateValidation::Disabled => builder
+                .with_custom_certificate_verifier(Arc::new(SkipServerVerification))
+                .with_no_client_auth(),
+        };
+        Ok(config)
+    }
+}
+
+/// Certificate verifier that accepts any server certificate
+struct SkipServerVerification;
+
+impl ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
@@ -1,4 +1,7 @@
-use crate::shared::{get_fields, strip_attributes};
+use crate::shared::{
+    encoding_impl, get_fields, strip_attributes, variants_method, EncodingField, QuantizeField,
+    VariantEncoding,
+};
 use darling::ast::NestedMeta;
 use darling::util::{Flag, PathList};
 use darling::{Error, FromField, FromMeta, FromVariant};
@@ -31,6 +34,16 @@ struct AttrField {
     sync: Option<SyncField>,
     #[darling(default)]
     map_entities: MapField,
+    #[darling(default)]
+    quantize: Option<QuantizeField>,
+    #[darling(default)]
+    encoding: Option<EncodingField>,
+    #[darling(default)]
+    delta: Flag,
+    #[darling(default)]
+    fields: Flag,
+    #[darling(default)]
+    resource: Flag,
 }
 
 #[derive(Debug, FromMeta, PartialEq, Eq)]
@@ -112,6 +125,9 @@ pub fn component_protocol_impl(
         #[protocol(map_entities)]
         ParentSync(ParentSync)
     });
+    input.variants.push(parse_quote! {
+        ReplicatedResource(ReplicatedResource)
+    });
     #[cfg(feature = "leafwing")]
     for i in 1..3 {
         let variant = Ident::new(&format!("ActionState{}", i), Span::call_site());
@@ -124,11 +140,34 @@ pub fn component_protocol_impl(
 
     // Helper Properties
     let fields = get_fields(&input);
-    let input_without_attributes = strip_attributes(&input, ATTRIBUTES);
+    let mut input_without_attributes = strip_attributes(&input, ATTRIBUTES);
     let attr_fields: Vec<AttrField> = fields
         .iter()
         .map(|field| FromField::from_field(field).unwrap())
         .collect();
+    let variant_encodings = match attr_fields
+        .iter()
+        .filter_map(|field| {
+            VariantEncoding::new(
+                field.ident.as_ref().unwrap(),
+                &field.ty,
+                &field.quantize,
+                &field.encoding,
+            )
+            .transpose()
+        })
+        .collect::<darling::Result<Vec<_>>>()
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return e.write_errors().into();
+        }
+    };
+    let encoding_impl = encoding_impl(
+        &mut input_without_attributes,
+        &variant_encodings,
+        &shared_crate_name,
+    );
 
     // Names
     let enum_name = &input.ident;
@@ -144,6 +183,7 @@ pub fn component_protocol_impl(
 
     // Methods
     let add_systems_method = add_per_component_replication_send_systems_method(&fields, protocol);
+    let add_resource_systems_methods = add_resource_systems_methods(&attr_fields, protocol);
     let add_events_method = add_events_method(&fields);
     let push_component_events_method = push_component_events_method(&fields, protocol);
     let add_sync_systems_method = add_sync_systems_method(&attr_fields, protocol);
@@ -154,6 +194,8 @@ pub fn component_protocol_impl(
     let insert_method = insert_method(&input, &fields);
     let update_method = update_method(&input, &fields);
     let type_ids_method = type_ids_method(&fields, &enum_kind_name);
+    let variants_method = variants_method(&input, &variant_encodings);
+    let delta_compression_methods = delta_compression_methods(&attr_fields, &shared_crate_name);
 
     // EnumKind methods
     let enum_kind = get_enum_kind(&input, &enum_kind_name);
@@ -185,9 +227,12 @@ pub fn component_protocol_impl(
                 type Protocol = #protocol;
 
                 #type_ids_method
+                #variants_method
+                #delta_compression_methods
                 #insert_method
                 #update_method
                 #add_systems_method
+                #add_resource_systems_methods
                 #add_events_method
                 #push_component_events_method
                 #add_sync_systems_method
@@ -217,6 +262,7 @@ pub fn component_protocol_impl(
 
             #sync_component_impl
             #map_entities_method
+            #encoding_impl
 
             #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Reflect)]
             #[repr(C)]
@@ -270,6 +316,28 @@ fn add_per_component_replication_send_systems_method(
     }
 }
 
+fn add_resource_systems_methods(fields: &Vec<AttrField>, protocol_name: &Ident) -> TokenStream {
+    let resource_types: Vec<&Type> = fields
+        .iter()
+        .filter(|field| field.resource.is_present())
+        .map(|field| &field.ty)
+        .collect();
+    quote! {
+        #[allow(unused_variables)]
+        fn add_resource_send_systems<R: ReplicationSend<#protocol_name>>(app: &mut App) {
+            #(
+                app.add_plugins(ResourceSendPlugin::<#resource_types, #protocol_name, R>::default());
+            )*
+        }
+        #[allow(unused_variables)]
+        fn add_resource_receive_systems<R: ReplicationSend<#protocol_name>>(app: &mut App) {
+            #(
+                app.add_plugins(ResourceReceivePlugin::<#resource_types, #protocol_name, R>::default());
+            )*
+        }
+    }
+}
+
 fn push_component_events_method(fields: &Vec<Field>, protocol_name: &Ident) -> TokenStream {
     let mut body = quote! {};
     for field in fields {
@@ -680,3 +748,83 @@ fn type_ids_method(fields: &Vec<Field>, enum_kind_name: &Ident) -> TokenStream {
         }
     }
 }
+
+fn delta_compression_methods(
+    fields: &Vec<AttrField>,
+    shared_crate_name: &TokenStream,
+) -> TokenStream {
+    let matches_variants = |variants: Vec<&Ident>| {
+        if variants.is_empty() {
+            quote! { false }
+        } else {
+            quote! { matches!(self, #(Self::#variants(_))|*) }
+        }
+    };
+    let delta_variants: Vec<&Ident> = fields
+        .iter()
+        .filter(|field| field.delta.is_present() || field.fields.is_present())
+        .map(|field| field.ident.as_ref().unwrap())
+        .collect();
+    let field_variants: Vec<&Ident> = fields
+        .iter()
+        .filter(|field| field.fields.is_present())
+        .map(|field| field.ident.as_ref().unwrap())
+        .collect();
+    let delta_compression_body = matches_variants(delta_variants);
+    let field_replication_body = matches_variants(field_variants.clone());
+    quote! {
+        fn delta_compression(&self) -> bool {
+            #delta_compression_body
+        }
+        fn field_replication(&self) -> bool {
+            #field_replication_body
+        }
+        #[allow(unused_variables)]
+        fn num_fields(&self) -> usize {
+            match self {
+                #(Self::#field_variants(value) => ReplicateFields::num_fields(value),)*
+                _ => 0,
+            }
+        }
+        #[allow(unused_variables)]
+        fn field_changed(&self, previous: &Self, index: usize) -> bool {
+            match (self, previous) {
+                #(
+                    (Self::#field_variants(value), Self::#field_variants(previous)) => {
+                        ReplicateFields::field_changed(value, previous, index)
+                    }
+                )*
+                _ => true,
+            }
+        }
+        #[allow(unused_variables)]
+        fn write_fields(
+            &self,
+            fields: &[bool],
+            writer: &mut impl WriteBuffer,
+        ) -> #shared_crate_name::_reexport::anyhow::Result<()> {
+            match self {
+                #(
+                    Self::#field_variants(value) => ReplicateFields::write_fields(value, fields, writer),
+                )*
+                _ => Err(#shared_crate_name::_reexport::anyhow::anyhow!(
+                    "cannot write the fields of {:?}", self
+                )),
+            }
+        }
+        #[allow(unused_variables)]
+        fn read_fields(
+            &mut self,
+            reader: &mut impl ReadBuffer,
+        ) -> #shared_crate_name::_reexport::anyhow::Result<()> {
+            match self {
+                #(
+                    Self::#field_variants(value) => ReplicateFields::read_fields(value, reader),
+                )*
+                _ => Err(#shared_crate_name::_reexport::anyhow::anyhow!(
+                    "cannot read the fields of {:?}", self
+                )),
+            }
+        }
+    }
+}
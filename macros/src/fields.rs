@@ -0,0 +1,115 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Data, DeriveInput, Field, Index, Member, Meta, Token};
+
+pub fn replicate_fields_impl(
+    input: proc_macro::TokenStream,
+    shared_crate_name: TokenStream,
+) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let Data::Struct(data_struct) = &input.data else {
+        return syn::Error::new(
+            Span::call_site(),
+            "Can only derive ReplicateFields on a struct",
+        )
+        .to_compile_error()
+        .into();
+    };
+    let mut members: Vec<Member> = vec![];
+    for (i, field) in data_struct.fields.iter().enumerate() {
+        match is_skipped(field) {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(e) => return e.to_compile_error().into(),
+        }
+        members.push(match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        });
+    }
+
+    let indices: Vec<usize> = (0..members.len()).collect();
+    let num_fields = members.len();
+
+    // Names
+    let struct_name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = &input.generics.split_for_impl();
+
+    let gen = quote! {
+        impl #impl_generics #shared_crate_name::prelude::ReplicateFields for #struct_name #type_generics #where_clause {
+            fn num_fields(&self) -> usize {
+                #num_fields
+            }
+
+            fn field_changed(&self, previous: &Self, index: usize) -> bool {
+                match index {
+                    #(#indices => self.#members != previous.#members,)*
+                    _ => false,
+                }
+            }
+
+            fn write_fields(
+                &self,
+                fields: &[bool],
+                writer: &mut impl #shared_crate_name::_reexport::WriteBuffer,
+            ) -> #shared_crate_name::_reexport::anyhow::Result<()> {
+                #(
+                    let send = fields.get(#indices).copied().unwrap_or_default();
+                    #shared_crate_name::_reexport::WriteBuffer::serialize(writer, &send)?;
+                    if send {
+                        #shared_crate_name::_reexport::WriteBuffer::serialize(writer, &self.#members)?;
+                    }
+                )*
+                Ok(())
+            }
+
+            fn read_fields(
+                &mut self,
+                reader: &mut impl #shared_crate_name::_reexport::ReadBuffer,
+            ) -> #shared_crate_name::_reexport::anyhow::Result<()> {
+                #(
+                    if #shared_crate_name::_reexport::ReadBuffer::deserialize::<bool>(reader)? {
+                        self.#members = #shared_crate_name::_reexport::ReadBuffer::deserialize(reader)?;
+                    }
+                )*
+                Ok(())
+            }
+        }
+    };
+
+    proc_macro::TokenStream::from(gen)
+}
+
+/// Returns true if the field is marked with `#[serde(skip)]`: it is then not replicated, and keeps its
+/// local value when an update is received (the same way serde leaves it to its default value).
+///
+/// The fields are serialized directly, so their other serde attributes (`with`, `serialize_with`, ...)
+/// are not applied. `skip_serializing` and `skip_deserializing` are rejected, since a field could then be
+/// written without being read.
+fn is_skipped(field: &Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("serde"))
+    {
+        let metas = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+        for meta in metas {
+            let path = meta.path();
+            if path.is_ident("skip") {
+                skip = true;
+            } else if path.is_ident("skip_serializing")
+                || path.is_ident("skip_serializing_if")
+                || path.is_ident("skip_deserializing")
+            {
+                return Err(syn::Error::new_spanned(
+                    path,
+                    "ReplicateFields only supports `#[serde(skip)]` to skip a field",
+                ));
+            }
+        }
+    }
+    Ok(skip)
+}
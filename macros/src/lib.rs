@@ -10,10 +10,12 @@ use syn::{parse_macro_input, ItemEnum};
 
 use channel::channel_impl;
 use component::component_protocol_impl;
+use fields::replicate_fields_impl;
 use message::message_protocol_impl;
 
 mod channel;
 mod component;
+mod fields;
 mod message;
 mod shared;
 
@@ -74,3 +76,19 @@ pub fn component_protocol(
     let shared_crate_name = quote! { lightyear };
     component_protocol_impl(args, input, shared_crate_name)
 }
+
+#[doc(hidden)]
+#[proc_macro_derive(ReplicateFieldsInternal)]
+pub fn replicate_fields_derive_internal(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { crate };
+    replicate_fields_impl(input, shared_crate_name)
+}
+
+/// Derives the ReplicateFields trait for a given struct, to replicate only the fields that changed
+///
+/// The fields marked with `#[serde(skip)]` are not replicated.
+#[proc_macro_derive(ReplicateFields)]
+pub fn replicate_fields_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { lightyear };
+    replicate_fields_impl(input, shared_crate_name)
+}
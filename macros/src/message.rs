@@ -1,4 +1,7 @@
-use crate::shared::{generate_unique_ident, get_fields, strip_attributes};
+use crate::shared::{
+    encoding_impl, generate_unique_ident, get_fields, strip_attributes, variants_method,
+    EncodingField, QuantizeField, VariantEncoding,
+};
 use darling::ast::NestedMeta;
 use darling::util::PathList;
 use darling::{Error, FromDeriveInput, FromField, FromMeta};
@@ -26,6 +29,10 @@ struct AttrField {
     ty: Type,
     #[darling(default)]
     map_entities: MapField,
+    #[darling(default)]
+    quantize: Option<QuantizeField>,
+    #[darling(default)]
+    encoding: Option<EncodingField>,
 }
 
 #[derive(Debug, Default, FromMeta, PartialEq, Eq)]
@@ -80,11 +87,34 @@ pub fn message_protocol_impl(
 
     // Helper Properties
     let fields = get_fields(&input);
-    let input_without_attributes = strip_attributes(&input, ATTRIBUTES);
+    let mut input_without_attributes = strip_attributes(&input, ATTRIBUTES);
     let fields: Vec<AttrField> = fields
         .iter()
         .map(|field| FromField::from_field(field).unwrap())
         .collect();
+    let variant_encodings = match fields
+        .iter()
+        .filter_map(|field| {
+            VariantEncoding::new(
+                field.ident.as_ref().unwrap(),
+                &field.ty,
+                &field.quantize,
+                &field.encoding,
+            )
+            .transpose()
+        })
+        .collect::<darling::Result<Vec<_>>>()
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return e.write_errors().into();
+        }
+    };
+    let encoding_impl = encoding_impl(
+        &mut input_without_attributes,
+        &variant_encodings,
+        &shared_crate_name,
+    );
 
     // Names
     let enum_name = &input.ident;
@@ -101,6 +131,7 @@ pub fn message_protocol_impl(
     let add_events_method = add_events_method(&fields);
     let push_message_events_method = push_message_events_method(&fields, protocol);
     let name_method = name_method(&input, &fields);
+    let variants_method = variants_method(&input, &variant_encodings);
     let map_entities_impl = map_entities_impl(&input, &fields);
     let encode_method = encode_method();
     let decode_method = decode_method();
@@ -124,6 +155,7 @@ pub fn message_protocol_impl(
                 type Protocol = #protocol;
 
                 #name_method
+                #variants_method
                 #message_kind_method
                 #input_message_kind_method
                 #add_events_method
@@ -140,6 +172,7 @@ pub fn message_protocol_impl(
 
             // #from_into_methods
             #map_entities_impl
+            #encoding_impl
             // impl BitSerializable for #enum_name {
             //     #encode_method
             //     #decode_method
@@ -1,5 +1,10 @@
-use proc_macro2::{Ident, Span};
-use syn::{Data, DeriveInput, Field, Fields, ItemEnum};
+use darling::FromMeta;
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse_quote, Data, DeriveInput, Expr, ExprLit, ExprUnary, Field, Fields, ItemEnum, Lit, LitStr,
+    Type, UnOp,
+};
 
 pub enum StructType {
     Struct,
@@ -58,3 +63,200 @@ pub(crate) fn strip_attributes(input: &ItemEnum, attributes_to_remove: &[&str])
     }
     input
 }
+
+/// Values of the `#[protocol(quantize(min = -100.0, max = 100.0, bits = 16))]` attribute
+#[derive(Debug, FromMeta)]
+pub(crate) struct QuantizeField {
+    min: Expr,
+    max: Expr,
+    bits: u8,
+}
+
+/// Values of the `#[protocol(encoding = "gamma")]` attribute
+#[derive(Debug, FromMeta)]
+pub(crate) enum EncodingField {
+    Fixed,
+    Gamma,
+}
+
+/// A variant of the protocol that is serialized with a custom bitcode encoding
+pub(crate) struct VariantEncoding {
+    variant: Ident,
+    ty: Type,
+    /// Name of the encoding, for example `gamma` or `quantized:-100:100:16`
+    encoding: String,
+    kind: EncodingKind,
+}
+
+/// Encoding of a variant, resolved when the macro is expanded
+enum EncodingKind {
+    Fixed,
+    Gamma,
+    Quantized { min: f64, max: f64, bits: u8 },
+}
+
+impl VariantEncoding {
+    pub(crate) fn new(
+        variant: &Ident,
+        ty: &Type,
+        quantize: &Option<QuantizeField>,
+        encoding: &Option<EncodingField>,
+    ) -> darling::Result<Option<Self>> {
+        let kind = match (quantize, encoding) {
+            (None, None) => return Ok(None),
+            (Some(_), Some(_)) => {
+                return Err(darling::Error::custom(
+                    "`quantize` and `encoding` cannot be used on the same variant",
+                )
+                .with_span(variant))
+            }
+            (Some(quantize), None) => {
+                let min = expr_to_f64(&quantize.min)?;
+                let max = expr_to_f64(&quantize.max)?;
+                if min >= max {
+                    return Err(darling::Error::custom("`min` must be smaller than `max`")
+                        .with_span(variant));
+                }
+                if !(1..=32).contains(&quantize.bits) {
+                    return Err(darling::Error::custom("`bits` must be between 1 and 32")
+                        .with_span(variant));
+                }
+                EncodingKind::Quantized {
+                    min,
+                    max,
+                    bits: quantize.bits,
+                }
+            }
+            (None, Some(EncodingField::Fixed)) => EncodingKind::Fixed,
+            (None, Some(EncodingField::Gamma)) => EncodingKind::Gamma,
+        };
+        let encoding = match kind {
+            EncodingKind::Fixed => "fixed".to_string(),
+            EncodingKind::Gamma => "gamma".to_string(),
+            EncodingKind::Quantized { min, max, bits } => {
+                format!("quantized:{}:{}:{}", min, max, bits)
+            }
+        };
+        Ok(Some(Self {
+            variant: variant.clone(),
+            ty: ty.clone(),
+            encoding,
+            kind,
+        }))
+    }
+}
+
+fn expr_to_f64(expr: &Expr) -> darling::Result<f64> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Float(lit),
+            ..
+        }) => Ok(lit.base10_parse::<f64>()?),
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(lit), ..
+        }) => Ok(lit.base10_parse::<f64>()?),
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => expr_to_f64(expr).map(|v| -v),
+        Expr::Group(group) => expr_to_f64(&group.expr),
+        Expr::Paren(paren) => expr_to_f64(&paren.expr),
+        _ => Err(darling::Error::custom("expected a number").with_span(expr)),
+    }
+}
+
+/// Negative literals do not round-trip through a `TokenStream`, so the sign is emitted separately
+fn f64_tokens(value: f64) -> TokenStream {
+    let literal = Literal::f64_suffixed(value.abs());
+    if value.is_sign_negative() {
+        quote! { -#literal }
+    } else {
+        quote! { #literal }
+    }
+}
+
+/// Add `#[serde(serialize_with, deserialize_with)]` to the variants that have a custom encoding,
+/// and generate the functions that they use
+pub(crate) fn encoding_impl(
+    input: &mut ItemEnum,
+    encodings: &[VariantEncoding],
+    shared_crate_name: &TokenStream,
+) -> TokenStream {
+    let mut body = quote! {};
+    for encoding in encodings {
+        let variant_name = encoding.variant.to_string().to_lowercase();
+        let serialize_fn = format_ident!("serialize_{}_with_encoding", variant_name);
+        let deserialize_fn = format_ident!("deserialize_{}_with_encoding", variant_name);
+        let serialize_fn_name = LitStr::new(&serialize_fn.to_string(), Span::call_site());
+        let deserialize_fn_name = LitStr::new(&deserialize_fn.to_string(), Span::call_site());
+        // the encoding that bitcode uses for the variant (see `lightyear::serialize::encoding`)
+        let encoding_type = quote! { #shared_crate_name::serialize::encoding::Encoding };
+        let encoding_value = match encoding.kind {
+            EncodingKind::Fixed => quote! { #encoding_type::Fixed },
+            EncodingKind::Gamma => quote! { #encoding_type::Gamma },
+            EncodingKind::Quantized { min, max, bits } => {
+                let (min, max) = (f64_tokens(min), f64_tokens(max));
+                let bits = Literal::u8_suffixed(bits);
+                quote! { #encoding_type::Quantized { min: #min, max: #max, bits: #bits } }
+            }
+        };
+        let ty = &encoding.ty;
+
+        let variant = input
+            .variants
+            .iter_mut()
+            .find(|v| v.ident == encoding.variant)
+            .unwrap();
+        variant.attrs.push(parse_quote! {
+            #[serde(serialize_with = #serialize_fn_name, deserialize_with = #deserialize_fn_name)]
+        });
+
+        body = quote! {
+            #body
+            fn #serialize_fn<S: serde::Serializer>(value: &#ty, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                #shared_crate_name::serialize::encoding::serialize_with_encoding(#encoding_value, value, serializer)
+            }
+            fn #deserialize_fn<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<#ty, D::Error> {
+                #shared_crate_name::serialize::encoding::deserialize_with_encoding(#encoding_value, deserializer)
+            }
+        };
+    }
+    body
+}
+
+/// Generate the `variants` method, which returns the name, type, serde layout, net id and encoding of each variant.
+///
+/// The net id is the index of the variant in the enum, which is what bitcode writes on the wire.
+pub(crate) fn variants_method(input: &ItemEnum, encodings: &[VariantEncoding]) -> TokenStream {
+    let mut body = quote! {};
+    for (net_id, field) in get_fields(input).iter().enumerate() {
+        let ident = field.ident.as_ref().unwrap();
+        let name = LitStr::new(&ident.to_string(), Span::call_site());
+        let ty = &field.ty;
+        let type_name = LitStr::new(&quote!(#ty).to_string(), Span::call_site());
+        let net_id = net_id as u16;
+        let encoding = match encodings.iter().find(|e| &e.variant == ident) {
+            Some(e) => {
+                let encoding = LitStr::new(&e.encoding, Span::call_site());
+                quote! { Some(#encoding) }
+            }
+            None => quote! { None },
+        };
+        body = quote! {
+            #body
+            VariantSchema {
+                net_id: #net_id,
+                name: #name,
+                type_name: #type_name,
+                layout: type_layout::<#ty>(),
+                encoding: #encoding,
+            },
+        };
+    }
+    quote! {
+        fn variants() -> Vec<VariantSchema> {
+            vec![#body]
+        }
+    }
+}